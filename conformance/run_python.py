@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""Run the conformance scenarios against the Python binding.
+
+Requires the `rune_python` extension on the path (see rune-python/README.md)
+and PyYAML. Exits non-zero if any scenario gets a different answer; the
+checks mirror `Scenario::check` in rune-core/src/conformance.rs.
+"""
+
+import pathlib
+import sys
+import tempfile
+
+import yaml
+from rune_python import RUNE
+
+SCENARIOS = pathlib.Path(__file__).parent / "scenarios"
+
+
+def run(scenario):
+    with tempfile.NamedTemporaryFile("w", suffix=".rune") as config:
+        config.write(scenario.get("config", ""))
+        config.flush()
+        engine = RUNE(config.name)
+
+    for fact in scenario.get("facts", []):
+        engine.add_fact(fact["predicate"], fact.get("args", []))
+
+    request = scenario["request"]
+    session = scenario.get("session", [])
+    if session:
+        engine.open_session(
+            request["principal"],
+            [(a["predicate"], a.get("args", [])) for a in session],
+        )
+
+    return engine.decide(request["action"], request["principal"], request["resource"])
+
+
+def check(scenario, outcome):
+    expect = scenario["expect"]
+    if outcome["decision"].lower() != expect["decision"].lower():
+        return "expected decision {}, got {}".format(expect["decision"], outcome["decision"])
+    for reason in expect.get("reasons", []):
+        if reason not in outcome["reasons"]:
+            return "expected reason {!r} among {!r}".format(reason, outcome["reasons"])
+    return None
+
+
+def main():
+    paths = sorted(SCENARIOS.glob("*.yaml"))
+    if not paths:
+        print("No conformance scenarios found", file=sys.stderr)
+        return 1
+
+    failures = 0
+    for path in paths:
+        scenario = yaml.safe_load(path.read_text())
+        try:
+            error = check(scenario, run(scenario))
+        except ValueError as e:
+            error = str(e)
+        if error:
+            failures += 1
+            print("FAIL {}: {}: {}".format(path.name, scenario["name"], error))
+        else:
+            print("ok   {}".format(path.name))
+
+    print("{} scenarios, {} failed".format(len(paths), failures))
+    return 1 if failures else 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
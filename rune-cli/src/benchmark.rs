@@ -0,0 +1,381 @@
+//! `rune benchmark`: throughput and latency of a fixed workload
+//!
+//! Requests cycle through ten users, two actions and a hundred documents,
+//! named like `rune gen` names its users and documents, and are decided on
+//! `--threads` threads after a short warmup.
+//!
+//! `--output` saves the results as JSON, and `--baseline` compares a run
+//! with saved results: throughput and mean, p50 and p99 latency. With
+//! `--fail-on-regression 10%` the run fails if any of them is more than 10%
+//! worse than the baseline, so CI can gate changes on a representative
+//! workload. Runs only compare on the same machine and workload; a baseline
+//! with other settings is flagged.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use rune_core::{Action, Principal, RUNEEngine, Request, RequestBuilder, Resource};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::Instant;
+
+use crate::stress::{Histogram, LatencySummary};
+
+/// Settings for a benchmark run
+pub struct BenchmarkOptions {
+    /// RUNE file to evaluate against
+    pub config: Option<String>,
+    /// Number of requests to decide
+    pub requests: usize,
+    /// Threads deciding them; 0 means one per CPU
+    pub threads: usize,
+    /// File to save the results to
+    pub output: Option<String>,
+    /// Results of an earlier run to compare with
+    pub baseline: Option<String>,
+    /// Worst tolerated regression of any measurement, in percent
+    pub fail_on_regression: Option<f64>,
+}
+
+/// Results of a run, as saved by `--output`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    config: Option<String>,
+    requests: usize,
+    threads: usize,
+    successful: usize,
+    failed: usize,
+    duration_secs: f64,
+    throughput_per_sec: f64,
+    latency: LatencySummary,
+    cache_size: usize,
+    cache_hit_rate: f64,
+}
+
+/// One measurement compared with the baseline
+#[derive(Debug, Clone, PartialEq)]
+struct Comparison {
+    metric: &'static str,
+    unit: &'static str,
+    baseline: f64,
+    current: f64,
+    /// How much worse the current run is, in percent; negative is better
+    regression_pct: f64,
+}
+
+/// Parse a percentage such as `10%` or `2.5`
+pub fn parse_percent(input: &str) -> std::result::Result<f64, String> {
+    let number = input.trim();
+    let number = number.strip_suffix('%').unwrap_or(number).trim_end();
+    match number.parse::<f64>() {
+        Ok(percent) if percent.is_finite() && percent >= 0.0 => Ok(percent),
+        Ok(_) => Err(format!("percentage must not be negative: {}", input)),
+        Err(_) => Err(format!("invalid percentage: {}", input)),
+    }
+}
+
+/// Run the benchmark, save and compare its results
+pub fn benchmark_command(options: BenchmarkOptions) -> Result<()> {
+    // Read the baseline first, so a bad path does not cost a whole run
+    let baseline = match &options.baseline {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read baseline: {}", path))?;
+            let report: BenchmarkReport = serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid baseline: {}", path))?;
+            Some((path, report))
+        }
+        None => None,
+    };
+
+    println!("{} Running benchmark...", "→".blue());
+    println!("  Requests: {}", options.requests);
+    println!("  Threads: {}", options.threads);
+
+    let engine = RUNEEngine::new();
+    if let Some(path) = &options.config {
+        println!("{} Loading configuration from {}...", "→".blue(), path);
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", path))?;
+        engine
+            .apply_config(rune_core::parse_rune_file(&contents)?.with_path(path))
+            .with_context(|| format!("Failed to load configuration: {}", path))?;
+    }
+
+    let report = run(&engine, &options)?;
+    print_report(&report);
+
+    if let Some(path) = &options.output {
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write results: {}", path))?;
+        println!("\n{} Results saved to {}", "✓".green(), path);
+    }
+
+    let Some((path, baseline)) = baseline else {
+        return Ok(());
+    };
+    let comparisons = compare(&baseline, &report);
+    print_comparison(
+        path,
+        &baseline,
+        &report,
+        &comparisons,
+        options.fail_on_regression,
+    );
+
+    if let Some(limit) = options.fail_on_regression {
+        let regressions: Vec<String> = comparisons
+            .iter()
+            .filter(|c| c.regression_pct > limit)
+            .map(|c| format!("{} {:.1}% worse", c.metric, c.regression_pct))
+            .collect();
+        if !regressions.is_empty() {
+            bail!(
+                "Performance regressed by more than {}%: {}",
+                limit,
+                regressions.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Decide the workload and measure it
+fn run(engine: &RUNEEngine, options: &BenchmarkOptions) -> Result<BenchmarkReport> {
+    use rayon::prelude::*;
+
+    let requests: Vec<Request> = (0..options.requests)
+        .map(|i| {
+            RequestBuilder::new()
+                .principal(Principal::user(format!("user-{}", i % 10)))
+                .action(Action::new(if i % 2 == 0 { "read" } else { "write" }))
+                .resource(Resource::file(format!("/data/doc-{}", i % 100)))
+                .build()
+        })
+        .collect::<rune_core::Result<_>>()?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .build()
+        .context("Failed to start benchmark threads")?;
+
+    println!("{} Warming up cache...", "→".blue());
+    for request in requests.iter().take(100) {
+        let _ = engine.authorize(request);
+    }
+
+    println!("{} Running benchmark...", "→".blue());
+    let start = Instant::now();
+    let (latency, successful) = pool.install(|| {
+        requests
+            .par_iter()
+            .fold(
+                || (Histogram::default(), 0),
+                |(mut latency, successful), request| {
+                    let started = Instant::now();
+                    let ok = engine.authorize(request).is_ok();
+                    latency.record(started.elapsed());
+                    (latency, successful + usize::from(ok))
+                },
+            )
+            .reduce(
+                || (Histogram::default(), 0),
+                |(mut latency, successful), (other, more)| {
+                    latency.merge(&other);
+                    (latency, successful + more)
+                },
+            )
+    });
+    let duration = start.elapsed();
+    let cache = engine.cache_stats();
+
+    Ok(BenchmarkReport {
+        config: options.config.clone(),
+        requests: options.requests,
+        threads: pool.current_num_threads(),
+        successful,
+        failed: options.requests - successful,
+        duration_secs: duration.as_secs_f64(),
+        throughput_per_sec: options.requests as f64 / duration.as_secs_f64(),
+        latency: latency.summary(),
+        cache_size: cache.size,
+        cache_hit_rate: cache.hit_rate,
+    })
+}
+
+/// Compare the measurements of `current` with those of `baseline`
+fn compare(baseline: &BenchmarkReport, current: &BenchmarkReport) -> Vec<Comparison> {
+    // Throughput regresses as it falls, latency as it rises
+    let measurements = [
+        (
+            "throughput",
+            "req/sec",
+            baseline.throughput_per_sec,
+            current.throughput_per_sec,
+            true,
+        ),
+        (
+            "mean latency",
+            "µs",
+            baseline.latency.mean_us,
+            current.latency.mean_us,
+            false,
+        ),
+        (
+            "p50 latency",
+            "µs",
+            baseline.latency.p50_us,
+            current.latency.p50_us,
+            false,
+        ),
+        (
+            "p99 latency",
+            "µs",
+            baseline.latency.p99_us,
+            current.latency.p99_us,
+            false,
+        ),
+    ];
+    measurements
+        .into_iter()
+        .map(|(metric, unit, before, after, higher_is_better)| {
+            let change = if before > 0.0 {
+                (after - before) / before * 100.0
+            } else {
+                0.0
+            };
+            Comparison {
+                metric,
+                unit,
+                baseline: before,
+                current: after,
+                regression_pct: if higher_is_better { -change } else { change },
+            }
+        })
+        .collect()
+}
+
+fn print_report(report: &BenchmarkReport) {
+    println!("\n{} Benchmark Results", "═".blue().bold());
+    println!("{} Total requests: {}", "▸".blue(), report.requests);
+    println!("{} Successful: {}", "▸".blue(), report.successful);
+    println!("{} Failed: {}", "▸".blue(), report.failed);
+    println!("{} Duration: {:.3}s", "▸".blue(), report.duration_secs);
+    println!(
+        "{} Throughput: {:.0} req/sec",
+        "▸".blue(),
+        report.throughput_per_sec
+    );
+    println!(
+        "{} Avg latency: {:.3}ms",
+        "▸".blue(),
+        report.duration_secs * 1000.0 / report.requests as f64
+    );
+    println!(
+        "{} Latency per request: mean {:.1}µs, p50 {:.1}µs, p99 {:.1}µs, max {:.1}µs",
+        "▸".blue(),
+        report.latency.mean_us,
+        report.latency.p50_us,
+        report.latency.p99_us,
+        report.latency.max_us
+    );
+
+    println!("\n{} Cache Statistics", "═".blue().bold());
+    println!("{} Cache size: {}", "▸".blue(), report.cache_size);
+    println!(
+        "{} Hit rate: {:.1}%",
+        "▸".blue(),
+        report.cache_hit_rate * 100.0
+    );
+}
+
+fn print_comparison(
+    path: &str,
+    baseline: &BenchmarkReport,
+    current: &BenchmarkReport,
+    comparisons: &[Comparison],
+    limit: Option<f64>,
+) {
+    println!("\n{} Compared with {}", "═".blue().bold(), path);
+    if (baseline.requests, baseline.threads, &baseline.config)
+        != (current.requests, current.threads, &current.config)
+    {
+        println!(
+            "{} The baseline ran a different workload ({} requests on {} threads, config {})",
+            "⚠".yellow(),
+            baseline.requests,
+            baseline.threads,
+            baseline.config.as_deref().unwrap_or("none")
+        );
+    }
+    for c in comparisons {
+        let change = if c.regression_pct > 0.0 {
+            format!("{:.1}% worse", c.regression_pct)
+        } else {
+            format!("{:.1}% better", -c.regression_pct)
+        };
+        let change = match limit {
+            Some(limit) if c.regression_pct > limit => change.red().bold(),
+            _ => change.normal(),
+        };
+        println!(
+            "{} {}: {:.1} → {:.1} {} ({})",
+            "▸".blue(),
+            c.metric,
+            c.baseline,
+            c.current,
+            c.unit,
+            change
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(throughput: f64, p99_us: f64) -> BenchmarkReport {
+        BenchmarkReport {
+            config: None,
+            requests: 1000,
+            threads: 4,
+            successful: 1000,
+            failed: 0,
+            duration_secs: 1000.0 / throughput,
+            throughput_per_sec: throughput,
+            latency: LatencySummary {
+                mean_us: 10.0,
+                p50_us: 8.0,
+                p90_us: 20.0,
+                p99_us,
+                p999_us: 100.0,
+                max_us: 200.0,
+            },
+            cache_size: 100,
+            cache_hit_rate: 0.9,
+        }
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("10%"), Ok(10.0));
+        assert_eq!(parse_percent("2.5"), Ok(2.5));
+        assert_eq!(parse_percent(" 0 % "), Ok(0.0));
+        assert!(parse_percent("-5%").is_err());
+        assert!(parse_percent("ten").is_err());
+    }
+
+    #[test]
+    fn test_compare_regressions() {
+        let comparisons = compare(&report(1000.0, 50.0), &report(800.0, 40.0));
+        let regression = |metric: &str| {
+            comparisons
+                .iter()
+                .find(|c| c.metric == metric)
+                .unwrap()
+                .regression_pct
+        };
+        // Lower throughput is worse, lower latency better
+        assert!((regression("throughput") - 20.0).abs() < 1e-9);
+        assert!((regression("p99 latency") + 20.0).abs() < 1e-9);
+        assert_eq!(regression("mean latency"), 0.0);
+    }
+}
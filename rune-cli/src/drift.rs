@@ -0,0 +1,148 @@
+//! `rune drift`: compare a running server with its policy repository
+//!
+//! The configuration file at the repository's HEAD is loaded into a scratch
+//! engine and exported, the server's active generation is fetched from
+//! `GET /v1/export`, and the two are compared with [`Drift::between`], so
+//! rules, policies and flags changed through the management API show up
+//! before an incident does. The repository is anything `git clone`
+//! accepts; a local checkout is read in place, without cloning.
+//!
+//! Exits with status 1 when the server has drifted, so the command can run
+//! as a scheduled check.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use rune_core::{Drift, Export, RUNEEngine};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Response header carrying the generation an export was taken from
+const GENERATION_HEADER: &str = "x-rune-generation";
+
+/// Options of `rune drift`
+pub struct DriftOptions {
+    /// Git repository holding the configuration
+    pub repo: String,
+    /// Path of the configuration file within the repository
+    pub file: String,
+    /// Base URL of the server
+    pub server: String,
+    /// Output format (text, json)
+    pub format: String,
+}
+
+/// Report how the server's active configuration differs from the repository
+pub async fn drift_command(options: DriftOptions) -> Result<()> {
+    let source = read_head(&options.repo, &options.file)?;
+    let config = rune_core::parse_rune_file(&source)
+        .with_context(|| format!("Failed to parse {} at HEAD", options.file))?;
+    let engine = RUNEEngine::new();
+    engine
+        .apply_config(config)
+        .with_context(|| format!("Failed to load {} at HEAD", options.file))?;
+    let expected = engine.export()?;
+
+    let (actual, generation) = fetch_export(&options.server).await?;
+    let drift = Drift::between(&expected, &actual);
+
+    match options.format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&drift)?),
+        _ if drift.is_empty() => println!(
+            "{} No drift: {} generation {} matches {} at HEAD ({} rules, {} policies)",
+            "✓".green(),
+            options.server,
+            generation,
+            options.file,
+            expected.rules.len(),
+            expected.policies.len()
+        ),
+        _ => {
+            println!(
+                "{} {} generation {} has drifted from {} at HEAD ({} differences)",
+                "✗".red(),
+                options.server,
+                generation,
+                options.file,
+                drift.items.len()
+            );
+            print!("{}", drift);
+        }
+    }
+
+    if !drift.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Contents of `file` at the HEAD of `repo`
+fn read_head(repo: &str, file: &str) -> Result<String> {
+    if Path::new(repo).join(".git").exists() {
+        return git_show(Path::new(repo), file);
+    }
+
+    let checkout = Checkout::new();
+    let status = Command::new("git")
+        .args(["clone", "--quiet", "--depth", "1", "--no-checkout", repo])
+        .arg(&checkout.0)
+        .status()
+        .context("Failed to run git")?;
+    if !status.success() {
+        bail!("Failed to clone {}", repo);
+    }
+    git_show(&checkout.0, file)
+}
+
+fn git_show(repo: &Path, file: &str) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["show", &format!("HEAD:{}", file)])
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "{} not found at HEAD of {}: {}",
+            file,
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("{} is not UTF-8", file))
+}
+
+/// Temporary clone, removed when dropped
+struct Checkout(PathBuf);
+
+impl Checkout {
+    fn new() -> Self {
+        Checkout(std::env::temp_dir().join(format!("rune-drift-{}", std::process::id())))
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The server's active configuration and its generation
+async fn fetch_export(server: &str) -> Result<(Export, String)> {
+    let url = format!("{}/v1/export?format=json", server.trim_end_matches('/'));
+    let response = reqwest::get(&url)
+        .await
+        .with_context(|| format!("Failed to reach {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Export failed: {}", url))?;
+    let generation = response
+        .headers()
+        .get(GENERATION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("?")
+        .to_string();
+    let export = response
+        .json()
+        .await
+        .with_context(|| format!("Invalid export from {}", url))?;
+    Ok((export, generation))
+}
@@ -0,0 +1,74 @@
+//! `rune facts`: convert between fact snapshots and NDJSON
+//!
+//! A server started with `RUNE_FACT_SNAPSHOT` saves its facts to a snapshot
+//! on shutdown and loads them back on startup, and `GET`/`PUT
+//! /v1/facts/snapshot` download and replace them on a running one. `rune
+//! facts export` writes such a snapshot from facts given as NDJSON, one
+//! fact per line as `rune query` prints them, to seed a server; `rune facts
+//! import` reads a snapshot in either format and prints its facts as NDJSON
+//! for inspection or further processing. Labels and time-to-live are not
+//! part of the NDJSON form, so a round trip through it drops them.
+
+use anyhow::{Context, Result};
+use colored::*;
+use rune_core::{Fact, FactStore, SnapshotFormat};
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// Write the NDJSON facts of `input` (`-` for stdin) to a snapshot
+pub fn export_command(input: String, output: String, format: String) -> Result<()> {
+    let format: SnapshotFormat = format.parse()?;
+    let reader: Box<dyn Read> = if input == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(fs::File::open(&input).with_context(|| format!("Failed to read file: {}", input))?)
+    };
+
+    let mut facts = Vec::new();
+    for (line, text) in BufReader::new(reader).lines().enumerate() {
+        let text = text.with_context(|| format!("Failed to read {}", input))?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let fact: Fact = serde_json::from_str(&text)
+            .with_context(|| format!("Invalid fact on line {} of {}", line + 1, input))?;
+        facts.push(fact);
+    }
+    let store = FactStore::new();
+    store.add_facts_bulk(facts);
+
+    let snapshot = store.export_snapshot(format)?;
+    fs::write(&output, &snapshot)
+        .with_context(|| format!("Failed to write snapshot: {}", output))?;
+    eprintln!(
+        "{} Wrote {} facts to {} ({}, {} bytes)",
+        "✓".green(),
+        store.len(),
+        output,
+        format.as_str(),
+        snapshot.len()
+    );
+    Ok(())
+}
+
+/// Print the facts of a snapshot as NDJSON
+pub fn import_command(snapshot: String) -> Result<()> {
+    let bytes =
+        fs::read(&snapshot).with_context(|| format!("Failed to read file: {}", snapshot))?;
+    let store = FactStore::new();
+    store
+        .import_snapshot(&bytes)
+        .with_context(|| format!("Failed to load snapshot: {}", snapshot))?;
+
+    // Stop quietly if the reader (e.g. `head`) goes away
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let written = store.all_facts().iter().try_for_each(|fact| {
+        serde_json::to_writer(&mut out, fact)?;
+        writeln!(out)
+    });
+    match written.and_then(|_| out.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
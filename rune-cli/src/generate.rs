@@ -0,0 +1,434 @@
+//! `rune gen`: synthetic policies and facts at a chosen scale
+//!
+//! Writes one RUNE file with Datalog rules, the facts they range over and
+//! Cedar policies, for the model families picked with `--rules`:
+//!
+//! - `rbac`: users belong to groups, groups are granted actions on folders,
+//!   documents sit in folders
+//! - `abac`: users and documents carry a department and a clearance level;
+//!   matching ones grant reads, and Cedar policies compare request
+//!   attributes
+//! - `rebac`: folders nest, documents have owners, and owning a folder
+//!   grants reads on everything beneath it
+//!
+//! Identifiers follow the `rune stress` workload (`user-N`,
+//! `/data/doc-N`), so a generated file can be handed to `rune stress
+//! --config` or `rune benchmark --config` as is. Everything is drawn from
+//! `--seed`, which the file's header records, so the same command always
+//! writes the same file. A summary of what was generated goes to stderr.
+//!
+//! An uncached decision derives the rules over every fact, so decision
+//! latency grows with the fact count; measure it with a release build.
+
+use anyhow::{Context, Result};
+use colored::*;
+use rune_core::seed::{self, SeededRng};
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// Users per group
+const GROUP_SIZE: u64 = 50;
+
+/// Documents per folder
+const FOLDER_SIZE: u64 = 100;
+
+/// Folders per parent folder in `rebac`
+const FOLDER_FANOUT: u64 = 10;
+
+/// Actions grants and policies are drawn from
+const ACTIONS: [&str; 4] = ["read", "list", "write", "delete"];
+
+/// Departments users and documents belong to in `abac`
+const DEPARTMENTS: [&str; 8] = [
+    "engineering",
+    "finance",
+    "legal",
+    "marketing",
+    "operations",
+    "people",
+    "sales",
+    "security",
+];
+
+/// Clearance and classification levels in `abac`
+const LEVELS: [&str; 4] = ["public", "internal", "confidential", "restricted"];
+
+/// Family of authorization model to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Family {
+    /// Groups granted actions on folders
+    Rbac,
+    /// Departments and clearance levels
+    Abac,
+    /// Folder trees and ownership
+    Rebac,
+}
+
+impl Family {
+    /// Name used on the command line
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Family::Rbac => "rbac",
+            Family::Abac => "abac",
+            Family::Rebac => "rebac",
+        }
+    }
+}
+
+/// Families joined with `+`, e.g. `rbac+abac`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Families(Vec<Family>);
+
+impl Families {
+    fn contains(&self, family: Family) -> bool {
+        self.0.contains(&family)
+    }
+}
+
+impl FromStr for Families {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let mut families = Vec::new();
+        for name in s.split('+').map(str::trim) {
+            let family = match name {
+                "rbac" => Family::Rbac,
+                "abac" => Family::Abac,
+                "rebac" => Family::Rebac,
+                _ => {
+                    return Err(format!(
+                        "unknown rule family '{}' (use rbac, abac or rebac, joined with +)",
+                        name
+                    ))
+                }
+            };
+            families.push(family);
+        }
+        families.sort();
+        families.dedup();
+        Ok(Families(families))
+    }
+}
+
+impl std::fmt::Display for Families {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.0.iter().map(|family| family.as_str()).collect();
+        f.write_str(&names.join("+"))
+    }
+}
+
+/// Parse a count such as `500`, `10k`, `2.5m` or `100_000`
+pub fn parse_count(input: &str) -> std::result::Result<u64, String> {
+    let input = input.trim().replace('_', "");
+    let (number, scale) = match input.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&input[..i], 1e3),
+        Some((i, 'm' | 'M')) => (&input[..i], 1e6),
+        _ => (input.as_str(), 1.0),
+    };
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid count '{}'", input))?;
+    let count = value * scale;
+    if !(1.0..=1e9).contains(&count) {
+        return Err(format!("count '{}' must be between 1 and 1000m", input));
+    }
+    Ok(count.round() as u64)
+}
+
+/// Settings for `rune gen`
+pub struct GenOptions {
+    /// Users to generate
+    pub users: u64,
+    /// Documents to generate
+    pub resources: u64,
+    /// Model families to generate
+    pub families: Families,
+    /// File to write instead of stdout
+    pub output: Option<String>,
+}
+
+/// What a generated file contains
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Users
+    pub users: u64,
+    /// Groups, in `rbac`
+    pub groups: u64,
+    /// Documents
+    pub resources: u64,
+    /// Folders, in `rbac` and `rebac`
+    pub folders: u64,
+    /// Datalog rules, not counting facts
+    pub rules: usize,
+    /// Facts written into the `[rules]` section
+    pub facts: usize,
+    /// Cedar policies
+    pub policies: usize,
+}
+
+/// Generate a RUNE file
+pub fn generate(options: &GenOptions, rng: &mut SeededRng) -> (String, Summary) {
+    let families = &options.families;
+    let rbac = families.contains(Family::Rbac);
+    let abac = families.contains(Family::Abac);
+    let rebac = families.contains(Family::Rebac);
+
+    let mut summary = Summary {
+        users: options.users,
+        groups: if rbac {
+            options.users.div_ceil(GROUP_SIZE)
+        } else {
+            0
+        },
+        resources: options.resources,
+        folders: if rbac || rebac {
+            options.resources.div_ceil(FOLDER_SIZE)
+        } else {
+            0
+        },
+        ..Summary::default()
+    };
+
+    let mut rules = String::new();
+    let mut facts = String::new();
+    let mut policies = String::new();
+    let mut fact = |text: std::fmt::Arguments<'_>| {
+        let _ = writeln!(facts, "{}.", text);
+        summary.facts += 1;
+    };
+
+    if rbac {
+        for user in 0..options.users {
+            // Most users are in one group, some in two or three
+            for _ in 0..1 + rng.below(4).saturating_sub(1) {
+                let group = rng.below(summary.groups);
+                fact(format_args!(
+                    "member(\"user-{}\", \"group-{}\")",
+                    user, group
+                ));
+            }
+        }
+        for group in 0..summary.groups {
+            for _ in 0..2 {
+                let action = ACTIONS[rng.below(3) as usize];
+                let folder = rng.below(summary.folders);
+                fact(format_args!(
+                    "grant(\"group-{}\", \"{}\", \"/data/folder-{}\")",
+                    group, action, folder
+                ));
+            }
+        }
+        rules.push_str(
+            "@id(\"rbac-can\")\n\
+             can(U, A, R) :- member(U, G), grant(G, A, F), in_folder(R, F).\n",
+        );
+        summary.rules += 1;
+        policies.push_str(
+            "@id(\"rbac-reads\")\n\
+             permit(principal, action in [Action::\"read\", Action::\"list\"], resource);\n\n\
+             @id(\"rbac-admins\")\n\
+             permit(principal, action, resource)\n    \
+             when { principal has role && principal.role == \"admin\" };\n\n",
+        );
+        summary.policies += 2;
+    }
+
+    if rbac || rebac {
+        for doc in 0..options.resources {
+            fact(format_args!(
+                "in_folder(\"/data/doc-{}\", \"/data/folder-{}\")",
+                doc,
+                doc / FOLDER_SIZE
+            ));
+        }
+    }
+
+    if abac {
+        for user in 0..options.users {
+            let department = DEPARTMENTS[rng.below(DEPARTMENTS.len() as u64) as usize];
+            let level = LEVELS[rng.below(LEVELS.len() as u64) as usize];
+            fact(format_args!(
+                "department(\"user-{}\", \"{}\")",
+                user, department
+            ));
+            fact(format_args!("clearance(\"user-{}\", \"{}\")", user, level));
+        }
+        for doc in 0..options.resources {
+            let department = DEPARTMENTS[rng.below(DEPARTMENTS.len() as u64) as usize];
+            // Most documents are internal; few are restricted
+            let level = LEVELS[[0, 1, 1, 1, 1, 2, 2, 3][rng.below(8) as usize]];
+            fact(format_args!(
+                "department(\"/data/doc-{}\", \"{}\")",
+                doc, department
+            ));
+            fact(format_args!(
+                "classification(\"/data/doc-{}\", \"{}\")",
+                doc, level
+            ));
+        }
+        rules.push_str(
+            "@id(\"abac-department\")\n\
+             can(U, \"read\", R) :- department(U, D), department(R, D), clearance(U, L), classification(R, L).\n\
+             @id(\"abac-public\")\n\
+             can(U, \"read\", R) :- clearance(U, L), classification(R, \"public\").\n",
+        );
+        summary.rules += 2;
+        policies.push_str(
+            "@id(\"abac-department-writes\")\n\
+             permit(principal, action == Action::\"write\", resource)\n    \
+             when { principal has department && resource has department && principal.department == resource.department };\n\n\
+             @id(\"abac-restricted\")\n\
+             forbid(principal, action, resource)\n    \
+             when { resource has classification && resource.classification == \"restricted\" }\n    \
+             unless { principal has clearance && principal.clearance == \"restricted\" };\n\n",
+        );
+        summary.policies += 2;
+    }
+
+    if rebac {
+        // Folders form a tree with FOLDER_FANOUT children each
+        for folder in 1..summary.folders {
+            fact(format_args!(
+                "parent(\"/data/folder-{}\", \"/data/folder-{}\")",
+                folder,
+                (folder - 1) / FOLDER_FANOUT
+            ));
+        }
+        for folder in 0..summary.folders {
+            if rng.below(4) == 0 {
+                let owner = rng.below(options.users);
+                fact(format_args!(
+                    "owner(\"user-{}\", \"/data/folder-{}\")",
+                    owner, folder
+                ));
+            }
+        }
+        for doc in 0..options.resources {
+            let owner = rng.below(options.users);
+            fact(format_args!(
+                "owner(\"user-{}\", \"/data/doc-{}\")",
+                owner, doc
+            ));
+        }
+        rules.push_str(
+            "@id(\"rebac-contains\")\n\
+             contains(F, R) :- in_folder(R, F).\n\
+             @id(\"rebac-contains-nested\")\n\
+             contains(P, R) :- parent(F, P), contains(F, R).\n\
+             @id(\"rebac-owner\")\n\
+             can(U, A, R) :- owner(U, R), grant_owner(A).\n\
+             @id(\"rebac-folder-owner\")\n\
+             can(U, \"read\", R) :- owner(U, F), contains(F, R).\n",
+        );
+        summary.rules += 4;
+        for action in ACTIONS {
+            fact(format_args!("grant_owner(\"{}\")", action));
+        }
+        policies.push_str(
+            "@id(\"rebac-no-shared-deletes\")\n\
+             forbid(principal, action == Action::\"delete\", resource)\n    \
+             when { resource has shared && resource.shared == true };\n\n",
+        );
+        summary.policies += 1;
+    }
+
+    let mut source = format!(
+        "# Generated by: rune gen --users {} --resources {} --rules {} --seed {}\n\
+         version = \"rune/2.0\"\n\n[rules]\n{}\n# Facts\n{}\n[policies]\n{}",
+        options.users,
+        options.resources,
+        families,
+        seed::seed().unwrap_or_default(),
+        rules,
+        facts,
+        policies
+    );
+    source.truncate(source.trim_end().len());
+    source.push('\n');
+    (source, summary)
+}
+
+/// Generate a RUNE file and write it out
+pub fn gen_command(options: GenOptions) -> Result<()> {
+    let mut rng = seed::rng("gen");
+    let (source, summary) = generate(&options, &mut rng);
+
+    match &options.output {
+        Some(path) => std::fs::write(path, &source)
+            .with_context(|| format!("Failed to write file: {}", path))?,
+        None => print!("{}", source),
+    }
+
+    // The file may be on stdout, so the summary goes to stderr
+    eprintln!(
+        "{} Generated {} ({} seed {})",
+        "✓".green(),
+        options.output.as_deref().unwrap_or("configuration"),
+        options.families,
+        seed::seed().unwrap_or_default()
+    );
+    eprintln!("  Users: {}", summary.users);
+    if summary.groups > 0 {
+        eprintln!("  Groups: {}", summary.groups);
+    }
+    eprintln!("  Resources: {}", summary.resources);
+    if summary.folders > 0 {
+        eprintln!("  Folders: {}", summary.folders);
+    }
+    eprintln!("  Rules: {}", summary.rules);
+    eprintln!("  Facts: {}", summary.facts);
+    eprintln!("  Policies: {}", summary.policies);
+    eprintln!("  Size: {:.1} MB", source.len() as f64 / 1_048_576.0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(families: &str) -> GenOptions {
+        GenOptions {
+            users: 120,
+            resources: 450,
+            families: families.parse().unwrap(),
+            output: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_count_and_families() {
+        assert_eq!(parse_count("500"), Ok(500));
+        assert_eq!(parse_count("10k"), Ok(10_000));
+        assert_eq!(parse_count("2.5M"), Ok(2_500_000));
+        assert_eq!(parse_count("100_000"), Ok(100_000));
+        assert!(parse_count("0").is_err());
+        assert!(parse_count("ten").is_err());
+
+        let families: Families = "abac+rbac+abac".parse().unwrap();
+        assert_eq!(families.to_string(), "rbac+abac");
+        assert!("rbac+acl".parse::<Families>().is_err());
+    }
+
+    #[test]
+    fn test_generated_files_load_and_repeat() {
+        for families in ["rbac", "abac", "rebac", "rbac+abac+rebac"] {
+            let (source, summary) = generate(&options(families), &mut SeededRng::new(7));
+            let config = rune_core::parse_rune_file(&source).unwrap();
+            assert_eq!(config.policies.len(), summary.policies, "{}", families);
+            assert_eq!(
+                config.rules.len(),
+                summary.rules + summary.facts,
+                "{}",
+                families
+            );
+            rune_core::RUNEEngine::new().apply_config(config).unwrap();
+
+            let (again, _) = generate(&options(families), &mut SeededRng::new(7));
+            assert_eq!(source, again);
+        }
+
+        let (_, summary) = generate(&options("rbac"), &mut SeededRng::new(7));
+        assert_eq!(summary.groups, 3);
+        assert_eq!(summary.folders, 5);
+    }
+}
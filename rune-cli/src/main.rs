@@ -3,9 +3,22 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use colored::*;
-use rune_core::{Action, Principal, RUNEEngine, Request, RequestBuilder, Resource};
+use rune_core::conformance::Scenario;
+use rune_core::migrate::MigrationNote;
+use rune_core::parser::RUNEConfig;
+use rune_core::{
+    Action, ConfigLayer, ExportFormat, FactQuery, FormatVersion, PolicySet, Principal,
+    PrincipalKind, RUNEEngine, RequestBuilder, Resource, Value,
+};
 use std::fs;
-use std::time::Instant;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+mod benchmark;
+mod drift;
+mod facts;
+mod generate;
+mod stress;
 
 #[derive(Parser)]
 #[command(name = "rune")]
@@ -18,6 +31,11 @@ struct Cli {
     /// Enable verbose output
     #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Seed for everything random, so runs repeat exactly; `test`,
+    /// `stress` and `gen` pick and print one when it is not given
+    #[arg(long, global = true)]
+    seed: Option<u64>,
 }
 
 #[derive(Subcommand)]
@@ -36,6 +54,23 @@ enum Commands {
         #[arg(long, default_value = "agent-1")]
         principal: String,
 
+        /// Principal kind (user, agent, service-account, device, api-client)
+        #[arg(long, default_value = "agent")]
+        principal_type: PrincipalKind,
+
+        /// Principal attribute as KEY=VALUE; service accounts need `owner`,
+        /// devices `platform` and API clients `organization`
+        #[arg(long = "principal-attr", value_name = "KEY=VALUE")]
+        principal_attrs: Vec<String>,
+
+        /// Role granted to the principal for this evaluation (repeatable)
+        #[arg(long = "role")]
+        roles: Vec<String>,
+
+        /// Group the principal belongs to for this evaluation (repeatable)
+        #[arg(long = "group")]
+        groups: Vec<String>,
+
         /// Resource path or ID
         #[arg(long)]
         resource: String,
@@ -49,10 +84,140 @@ enum Commands {
     Validate {
         /// Configuration file path
         file: String,
+
+        /// Overlay composed on top of the file, in order (repeatable)
+        #[arg(long = "overlay")]
+        overlays: Vec<String>,
+
+        /// Also report permit/forbid conflicts between policies and
+        /// between rules
+        #[arg(long)]
+        analyze: bool,
+    },
+
+    /// Upgrade a RUNE file to another format version
+    Migrate {
+        /// Configuration file path
+        file: String,
+
+        /// Target format version (e.g. 2.0)
+        #[arg(long, default_value = "2.0")]
+        to: String,
+
+        /// Rewrite the file instead of printing the result
+        #[arg(short, long)]
+        in_place: bool,
+    },
+
+    /// Derive facts from a RUNE file and stream them out
+    Query {
+        /// Configuration file path
+        file: String,
+
+        /// Only output facts with this predicate
+        #[arg(short, long)]
+        predicate: Option<String>,
+
+        /// Number of matching facts to skip
+        #[arg(long, default_value = "0")]
+        offset: usize,
+
+        /// Maximum number of facts to output
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Only print the number of matching facts
+        #[arg(long)]
+        count: bool,
+
+        /// Output format (ndjson, text)
+        #[arg(short, long, default_value = "ndjson")]
+        format: String,
+    },
+
+    /// Dump a RUNE file's rules, policies and facts as loaded by the engine
+    ///
+    /// The output matches `GET /v1/export` on a server running the same
+    /// configuration, so the two can be diffed to detect drift.
+    Export {
+        /// Configuration file path
+        file: String,
+
+        /// Output format (json, csv, rune)
+        #[arg(short, long, default_value = "json")]
+        format: String,
+    },
+
+    /// Report which policies and rules a corpus of recorded requests
+    /// exercises
+    ///
+    /// The corpus holds one `POST /v1/authorize` request body per line.
+    /// Lists the policies no request matched, the rules that derived nothing
+    /// about any request, and the requests no policy matched at all.
+    Coverage {
+        /// Configuration file path
+        file: String,
+
+        /// Recorded requests, as JSON lines
+        #[arg(long)]
+        requests: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Compare a server's active configuration with a git repository
+    ///
+    /// Loads the configuration file at the repository's HEAD, fetches
+    /// `GET /v1/export` from the server and reports rules, policies, scopes,
+    /// limits and flag overrides that were added, removed or modified out of
+    /// band. Exits with status 1 when the two have drifted.
+    Drift {
+        /// Git repository holding the configuration (URL or local checkout)
+        #[arg(long)]
+        repo: String,
+
+        /// Base URL of the server
+        #[arg(long)]
+        server: String,
+
+        /// Configuration file path within the repository
+        #[arg(long, default_value = "policies.rune")]
+        file: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Convert between fact snapshots and NDJSON facts
+    Facts {
+        #[command(subcommand)]
+        command: FactsCommand,
+    },
+
+    /// Run conformance scenario files
+    ///
+    /// Each path is a scenario YAML file or a directory of them (see
+    /// `conformance/README.md`). The seed is printed so a failing run can be
+    /// repeated with `--seed`.
+    Test {
+        /// Scenario files or directories
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
     },
 
     /// Run benchmark tests
     Benchmark {
+        /// RUNE configuration file to evaluate against, e.g. from `rune gen`
+        #[arg(short, long)]
+        config: Option<String>,
+
         /// Number of requests to generate
         #[arg(short, long, default_value = "10000")]
         requests: usize,
@@ -60,6 +225,90 @@ enum Commands {
         /// Number of parallel threads
         #[arg(short, long, default_value = "8")]
         threads: usize,
+
+        /// Save the results as JSON to this file
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Results saved by an earlier run to compare with
+        #[arg(long)]
+        baseline: Option<String>,
+
+        /// Fail if throughput or latency is worse than the baseline by more
+        /// than this (e.g. 10%)
+        #[arg(long, requires = "baseline", value_parser = benchmark::parse_percent)]
+        fail_on_regression: Option<f64>,
+    },
+
+    /// Run a sustained mixed workload with fault injection
+    Stress {
+        /// RUNE configuration file (defaults to a built-in workload)
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// How long to run (e.g. 90s, 30m, 4h)
+        #[arg(short, long, default_value = "60s", value_parser = stress::parse_duration)]
+        duration: Duration,
+
+        /// Number of authorization threads
+        #[arg(short, long, default_value = "4")]
+        threads: usize,
+
+        /// Fact changes per second
+        #[arg(long, default_value = "100")]
+        churn_rate: u64,
+
+        /// Time between configuration reloads
+        #[arg(long, default_value = "30s", value_parser = stress::parse_duration)]
+        reload_interval: Duration,
+
+        /// Fault to inject (repeatable)
+        #[arg(long = "fault", value_enum)]
+        faults: Vec<stress::Fault>,
+
+        /// Time between injected faults of each kind
+        #[arg(long, default_value = "10s", value_parser = stress::parse_duration)]
+        fault_interval: Duration,
+
+        /// Time between progress reports
+        #[arg(long, default_value = "10s", value_parser = stress::parse_duration)]
+        report_interval: Duration,
+
+        /// Datalog evaluation budget in milliseconds
+        #[arg(long, default_value = "100")]
+        timeout_ms: u64,
+
+        /// Fail if resident memory grows by more than this many MB
+        #[arg(long)]
+        max_rss_growth: Option<u64>,
+
+        /// Output format for the final report (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Generate synthetic policies and facts at a chosen scale
+    ///
+    /// The RUNE file goes to stdout or `--output`, ready for `stress
+    /// --config` and `benchmark --config`; the same `--seed` always writes
+    /// the same file.
+    #[command(name = "gen")]
+    Gen {
+        /// Number of users (e.g. 500, 10k, 1.5m)
+        #[arg(long, default_value = "1k", value_parser = generate::parse_count)]
+        users: u64,
+
+        /// Number of resources (e.g. 500, 100k)
+        #[arg(long, default_value = "10k", value_parser = generate::parse_count)]
+        resources: u64,
+
+        /// Rule families joined with `+` (rbac, abac, rebac)
+        #[arg(long, default_value = "rbac")]
+        rules: generate::Families,
+
+        /// Write the configuration to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
     },
 
     /// Start RUNE server
@@ -71,6 +320,34 @@ enum Commands {
         /// Port to listen on
         #[arg(short, long, default_value = "8080")]
         port: u16,
+
+        /// Load and validate the configuration, then exit without binding
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum FactsCommand {
+    /// Write NDJSON facts, as `rune query` prints them, to a snapshot a
+    /// server can load
+    Export {
+        /// NDJSON file of facts, or `-` for stdin
+        input: String,
+
+        /// Snapshot file to write
+        #[arg(short, long)]
+        output: String,
+
+        /// Snapshot format (binary, json)
+        #[arg(short, long, default_value = "binary")]
+        format: String,
+    },
+
+    /// Print the facts of a snapshot in either format as NDJSON
+    Import {
+        /// Snapshot file
+        snapshot: String,
     },
 }
 
@@ -85,34 +362,190 @@ async fn main() -> Result<()> {
             .init();
     }
 
+    // Runs that report a seed always have one, so they can be repeated
+    let seed = cli.seed.or_else(|| {
+        matches!(
+            cli.command,
+            Commands::Test { .. } | Commands::Stress { .. } | Commands::Gen { .. }
+        )
+        .then(rune_core::seed::entropy)
+    });
+    if let Some(seed) = seed {
+        rune_core::seed::set_seed(seed);
+    }
+
     match cli.command {
         Commands::Eval {
             config,
             action,
             principal,
+            principal_type,
+            principal_attrs,
+            roles,
+            groups,
             resource,
             format,
         } => {
-            eval_command(config, action, principal, resource, format).await?;
+            let principal = principal_attrs.iter().try_fold(
+                Principal::new(principal_type.type_name(), principal),
+                |principal, attr| {
+                    let (key, value) = attr.split_once('=').with_context(|| {
+                        format!("Invalid principal attribute (expected KEY=VALUE): {}", attr)
+                    })?;
+                    anyhow::Ok(principal.with_attribute(key, Value::string(value)))
+                },
+            )?;
+            let memberships = Memberships { roles, groups };
+            eval_command(config, action, principal, memberships, resource, format).await?;
+        }
+        Commands::Validate {
+            file,
+            overlays,
+            analyze,
+        } => {
+            if overlays.is_empty() {
+                validate_command(file, analyze).await?;
+            } else {
+                validate_layers_command(file, overlays, analyze).await?;
+            }
+        }
+        Commands::Migrate { file, to, in_place } => {
+            migrate_command(file, to, in_place).await?;
+        }
+        Commands::Query {
+            file,
+            predicate,
+            offset,
+            limit,
+            count,
+            format,
+        } => {
+            let query = FactQuery {
+                predicate,
+                offset,
+                limit,
+            };
+            query_command(file, query, count, format).await?;
+        }
+        Commands::Export { file, format } => {
+            export_command(file, format).await?;
+        }
+        Commands::Coverage {
+            file,
+            requests,
+            format,
+        } => {
+            coverage_command(file, requests, format).await?;
+        }
+        Commands::Drift {
+            repo,
+            server,
+            file,
+            format,
+        } => {
+            drift::drift_command(drift::DriftOptions {
+                repo,
+                file,
+                server,
+                format,
+            })
+            .await?;
+        }
+        Commands::Facts { command } => match command {
+            FactsCommand::Export {
+                input,
+                output,
+                format,
+            } => facts::export_command(input, output, format)?,
+            FactsCommand::Import { snapshot } => facts::import_command(snapshot)?,
+        },
+        Commands::Test { paths, format } => {
+            test_command(paths, format).await?;
+        }
+        Commands::Benchmark {
+            config,
+            requests,
+            threads,
+            output,
+            baseline,
+            fail_on_regression,
+        } => {
+            benchmark::benchmark_command(benchmark::BenchmarkOptions {
+                config,
+                requests,
+                threads,
+                output,
+                baseline,
+                fail_on_regression,
+            })?;
         }
-        Commands::Validate { file } => {
-            validate_command(file).await?;
+        Commands::Stress {
+            config,
+            duration,
+            threads,
+            churn_rate,
+            reload_interval,
+            faults,
+            fault_interval,
+            report_interval,
+            timeout_ms,
+            max_rss_growth,
+            format,
+        } => {
+            stress::stress_command(stress::StressOptions {
+                config,
+                duration,
+                threads,
+                churn_rate,
+                reload_interval,
+                fault_interval,
+                faults,
+                report_interval,
+                timeout_ms,
+                max_rss_growth_mb: max_rss_growth,
+                format,
+            })?;
         }
-        Commands::Benchmark { requests, threads } => {
-            benchmark_command(requests, threads).await?;
+        Commands::Gen {
+            users,
+            resources,
+            rules,
+            output,
+        } => {
+            generate::gen_command(generate::GenOptions {
+                users,
+                resources,
+                families: rules,
+                output,
+            })?;
         }
-        Commands::Serve { config, port } => {
-            serve_command(config, port).await?;
+        Commands::Serve {
+            config,
+            port,
+            check,
+        } => {
+            if check {
+                check_command(config, port).await?;
+            } else {
+                serve_command(config, port).await?;
+            }
         }
     }
 
     Ok(())
 }
 
+/// Roles and groups `eval` gives its principal
+struct Memberships {
+    roles: Vec<String>,
+    groups: Vec<String>,
+}
+
 async fn eval_command(
     config: Option<String>,
     action: String,
-    principal: String,
+    principal: Principal,
+    memberships: Memberships,
     resource: String,
     format: String,
 ) -> Result<()> {
@@ -120,6 +553,12 @@ async fn eval_command(
 
     // Create engine
     let engine = RUNEEngine::new();
+    for role in &memberships.roles {
+        engine.grant_role(&principal.entity.id, role)?;
+    }
+    for group in &memberships.groups {
+        engine.add_to_group(&principal.entity.id, group)?;
+    }
 
     // Load configuration if provided
     if let Some(config_path) = config {
@@ -134,7 +573,7 @@ async fn eval_command(
 
     // Build request
     let request = RequestBuilder::new()
-        .principal(Principal::agent(principal.clone()))
+        .principal(principal)
         .action(Action::new(action.clone()))
         .resource(Resource::file(resource.clone()))
         .build()?;
@@ -158,7 +597,7 @@ async fn eval_command(
             println!("\n{} Authorization Result", "═".blue().bold());
             println!("{} Status: {}", "▸".blue(), status);
             println!("{} Action: {}", "▸".blue(), action);
-            println!("{} Principal: {}", "▸".blue(), principal);
+            println!("{} Principal: {}", "▸".blue(), request.principal.entity.id);
             println!("{} Resource: {}", "▸".blue(), resource);
             println!("{} Explanation: {}", "▸".blue(), result.explanation);
             println!(
@@ -190,22 +629,32 @@ async fn eval_command(
     Ok(())
 }
 
-async fn validate_command(file: String) -> Result<()> {
+async fn validate_command(file: String, analyze: bool) -> Result<()> {
     println!("{} Validating {}...", "→".blue(), file);
 
     let contents =
         fs::read_to_string(&file).with_context(|| format!("Failed to read file: {}", file))?;
 
+    // Diagnostics follow the same color settings as the rest of the output
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
     match rune_core::parse_rune_file(&contents) {
         Ok(config) => {
             println!("{} Configuration is valid!", "✓".green());
             println!("  Version: {}", config.version);
             println!("  Rules: {}", config.rules.len());
             println!("  Policies: {}", config.policies.len());
+            if !config.warnings.is_empty() {
+                println!();
+                print!("{}", config.warnings.render(Some(&contents), color));
+            }
+            if analyze {
+                analyze_command(config.with_path(&file), color)?;
+            }
         }
         Err(e) => {
             println!("{} Configuration is invalid:", "✗".red());
-            println!("  {}", e);
+            println!();
+            print!("{}", e.render_with_source(Some(&contents), color));
             std::process::exit(1);
         }
     }
@@ -213,80 +662,399 @@ async fn validate_command(file: String) -> Result<()> {
     Ok(())
 }
 
-async fn benchmark_command(requests: usize, threads: usize) -> Result<()> {
-    use rayon::prelude::*;
-    use std::sync::Arc;
+/// Scenario files under `paths`, directories expanded in name order
+fn scenario_files(paths: Vec<String>) -> Result<Vec<std::path::PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths.into_iter().map(std::path::PathBuf::from) {
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+        let mut found: Vec<_> = fs::read_dir(&path)
+            .with_context(|| format!("Failed to read directory: {}", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        found.retain(|file| {
+            file.extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        });
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
 
-    println!("{} Running benchmark...", "→".blue());
-    println!("  Requests: {}", requests);
-    println!("  Threads: {}", threads);
+async fn test_command(paths: Vec<String>, format: String) -> Result<()> {
+    let seed = rune_core::seed::seed().unwrap_or_default();
+    let files = scenario_files(paths)?;
+    let json = format == "json";
 
-    let engine = Arc::new(RUNEEngine::new());
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for file in &files {
+        let contents = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read file: {}", file.display()))?;
+        let (name, checked) = match serde_yaml::from_str::<Scenario>(&contents) {
+            Ok(scenario) => {
+                let checked = scenario
+                    .run()
+                    .map_err(|e| e.to_string())
+                    .and_then(|outcome| scenario.check(&outcome));
+                (scenario.name, checked)
+            }
+            Err(e) => (
+                file.display().to_string(),
+                Err(format!("Invalid scenario: {}", e)),
+            ),
+        };
+        match checked {
+            Ok(()) => {
+                passed += 1;
+                if !json {
+                    println!("{} {}", "✓".green(), name);
+                }
+            }
+            Err(error) => {
+                if !json {
+                    println!("{} {}: {}", "✗".red(), name, error);
+                }
+                failures.push(serde_json::json!({
+                    "file": file.display().to_string(),
+                    "name": name,
+                    "error": error,
+                }));
+            }
+        }
+    }
 
-    // Generate test requests
-    let test_requests: Vec<Request> = (0..requests)
-        .map(|i| {
-            RequestBuilder::new()
-                .principal(Principal::agent(format!("agent-{}", i % 10)))
-                .action(Action::new(if i % 2 == 0 { "read" } else { "write" }))
-                .resource(Resource::file(format!("/tmp/file-{}.txt", i % 100)))
-                .build()
-                .unwrap()
-        })
-        .collect();
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "seed": seed,
+                "passed": passed,
+                "failed": failures,
+            }))?
+        );
+    } else {
+        println!(
+            "\n{} passed, {} failed (seed {})",
+            passed,
+            failures.len(),
+            seed
+        );
+    }
+    if !failures.is_empty() {
+        if !json {
+            println!("Repeat with: rune test --seed {} ...", seed);
+        }
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    println!("{} Warming up cache...", "→".blue());
+/// Validate a base file and its overlays and show where each rule and
+/// policy came from
+async fn validate_layers_command(file: String, overlays: Vec<String>, analyze: bool) -> Result<()> {
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
+    let mut layers = Vec::new();
+    for path in std::iter::once(file).chain(overlays) {
+        println!("{} Validating {}...", "→".blue(), path);
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read file: {}", path))?;
+        match ConfigLayer::parse(path.as_str(), &contents) {
+            Ok(mut layer) => {
+                layer.config = layer.config.with_path(&path);
+                if !layer.config.warnings.is_empty() {
+                    print!("{}", layer.config.warnings.render(Some(&contents), color));
+                }
+                layers.push(layer);
+            }
+            Err(e) => {
+                println!("{} {} is invalid:", "✗".red(), path);
+                println!();
+                print!("{}", e.render_with_source(Some(&contents), color));
+                std::process::exit(1);
+            }
+        }
+    }
 
-    // Warmup
-    for request in test_requests.iter().take(100) {
-        let _ = engine.authorize(request);
+    let layered = match rune_core::compose(layers) {
+        Ok(layered) => layered,
+        Err(e) => {
+            println!("{} Layers conflict: {}", "✗".red(), e);
+            std::process::exit(1);
+        }
+    };
+    let provenance = &layered.provenance;
+    println!("{} Configuration is valid!", "✓".green());
+    println!("  Layers: {}", provenance.layers.join(" → "));
+    println!("  Rules: {}", layered.config.rules.len());
+    for origin in &provenance.rules {
+        println!(
+            "    {} {}",
+            origin.rule,
+            format!("({})", origin.layer).dimmed()
+        );
+    }
+    println!("  Policies: {}", layered.config.policies.len());
+    for (id, layer) in &provenance.policies {
+        println!("    {} {}", id, format!("({})", layer).dimmed());
+    }
+    for (key, origin) in &provenance.enabled {
+        let state = if origin.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        println!(
+            "  {} {} {}",
+            key,
+            state,
+            format!("by {}", origin.layer).dimmed()
+        );
+    }
+    if analyze {
+        analyze_command(layered.config, color)?;
     }
 
-    println!("{} Running benchmark...", "→".blue());
+    Ok(())
+}
 
-    let start = Instant::now();
+/// Load a valid configuration and report its permit/forbid conflicts
+fn analyze_command(config: RUNEConfig, color: bool) -> Result<()> {
+    let engine = RUNEEngine::new();
+    engine.apply_config(config)?;
+    let conflicts = engine.conflicts();
 
-    // Run parallel benchmark
-    let results: Vec<_> = test_requests
-        .par_iter()
-        .map(|request| {
-            let result = engine.authorize(request);
-            result.is_ok()
-        })
-        .collect();
-
-    let duration = start.elapsed();
-
-    // Calculate statistics
-    let successful = results.iter().filter(|&&r| r).count();
-    let failed = requests - successful;
-    let throughput = requests as f64 / duration.as_secs_f64();
-
-    println!("\n{} Benchmark Results", "═".blue().bold());
-    println!("{} Total requests: {}", "▸".blue(), requests);
-    println!("{} Successful: {}", "▸".blue(), successful);
-    println!("{} Failed: {}", "▸".blue(), failed);
-    println!("{} Duration: {:.3}s", "▸".blue(), duration.as_secs_f64());
-    println!("{} Throughput: {:.0} req/sec", "▸".blue(), throughput);
-    println!(
-        "{} Avg latency: {:.3}ms",
-        "▸".blue(),
-        duration.as_secs_f64() * 1000.0 / requests as f64
-    );
+    println!();
+    if conflicts.is_empty() {
+        println!("{} No permit/forbid conflicts", "✓".green());
+    } else {
+        println!(
+            "{} {} permit/forbid conflicts:",
+            "→".blue(),
+            conflicts.len()
+        );
+        println!();
+        print!(
+            "{}",
+            rune_core::conflicts::diagnostics(&conflicts).render(None, color)
+        );
+    }
+    Ok(())
+}
 
-    // Cache stats
-    let cache_stats = engine.cache_stats();
-    println!("\n{} Cache Statistics", "═".blue().bold());
-    println!("{} Cache size: {}", "▸".blue(), cache_stats.size);
-    println!(
-        "{} Hit rate: {:.1}%",
-        "▸".blue(),
-        cache_stats.hit_rate * 100.0
+async fn migrate_command(file: String, to: String, in_place: bool) -> Result<()> {
+    let target = FormatVersion::parse(&to)?;
+    let contents =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read file: {}", file))?;
+    let migration = rune_core::migrate(&contents, target)?;
+    let report = &migration.report;
+
+    // The migrated file goes to stdout, so the report goes to stderr
+    eprintln!(
+        "{} Migrating {} from {} to {}",
+        "→".blue(),
+        file,
+        report.from,
+        report.to
     );
+    for note in &report.rewrites {
+        eprintln!("  {} {}", "✓".green(), describe(note));
+    }
+    for note in &report.manual_steps {
+        eprintln!("  {} {}", "!".yellow(), describe(note));
+    }
+
+    if let Err(e) = rune_core::parse_rune_file(&migration.output) {
+        eprintln!("{} Migrated configuration is invalid:", "✗".red());
+        eprintln!("  {}", e);
+        std::process::exit(1);
+    }
+
+    if report.is_noop() {
+        eprintln!("{} Already at {}", "✓".green(), report.to);
+    }
+
+    if in_place {
+        if !report.is_noop() {
+            fs::write(&file, &migration.output)
+                .with_context(|| format!("Failed to write file: {}", file))?;
+        }
+    } else {
+        print!("{}", migration.output);
+    }
+
+    if !report.manual_steps.is_empty() {
+        eprintln!(
+            "{} {} manual step(s) remaining",
+            "!".yellow(),
+            report.manual_steps.len()
+        );
+    }
 
     Ok(())
 }
 
+/// Format a migration note with its line number
+fn describe(note: &MigrationNote) -> String {
+    match note.line {
+        Some(line) => format!("line {}: {}", line, note.message),
+        None => note.message.clone(),
+    }
+}
+
+async fn query_command(file: String, query: FactQuery, count: bool, format: String) -> Result<()> {
+    let contents =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read file: {}", file))?;
+    let config = rune_core::parse_rune_file(&contents)
+        .with_context(|| format!("Failed to parse file: {}", file))?;
+
+    let engine = RUNEEngine::new();
+    if let Some(scopes) = config.scopes {
+        engine.set_fact_scopes(scopes)?;
+    }
+    engine.reload_datalog_rules(config.rules)?;
+
+    if count {
+        let total = engine.count_facts(&query)?;
+        match format.as_str() {
+            "text" => println!("{}", total),
+            _ => println!("{}", serde_json::json!({ "count": total })),
+        }
+        return Ok(());
+    }
+
+    // Write facts as they come off the stream; stop quietly if the reader
+    // (e.g. `head`) goes away
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let written = engine
+        .query_facts(&query)?
+        .try_for_each(|fact| match format.as_str() {
+            "text" => {
+                let args = fact
+                    .args
+                    .iter()
+                    .map(serde_json::to_string)
+                    .collect::<serde_json::Result<Vec<_>>>()?;
+                writeln!(out, "{}({})", fact.predicate, args.join(", "))
+            }
+            _ => {
+                serde_json::to_writer(&mut out, &fact)?;
+                writeln!(out)
+            }
+        });
+
+    match written.and_then(|_| out.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+async fn export_command(file: String, format: String) -> Result<()> {
+    let format: ExportFormat = format.parse()?;
+    let contents =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read file: {}", file))?;
+    let config = rune_core::parse_rune_file(&contents)
+        .with_context(|| format!("Failed to parse file: {}", file))?;
+
+    let engine = RUNEEngine::new();
+    if let Some(scopes) = config.scopes {
+        engine.set_fact_scopes(scopes)?;
+    }
+    engine.reload_datalog_rules(config.rules)?;
+    let mut policies = PolicySet::new();
+    for policy in &config.policies {
+        policies
+            .add_policy(&policy.id, &policy.content)
+            .with_context(|| format!("Failed to load policy {}", policy.id))?;
+    }
+    engine.reload_policies(policies)?;
+
+    print!("{}", engine.export()?.render(format)?);
+    Ok(())
+}
+
+async fn coverage_command(file: String, requests: String, format: String) -> Result<()> {
+    let contents =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read file: {}", file))?;
+    let config = rune_core::parse_rune_file(&contents)
+        .with_context(|| format!("Failed to parse file: {}", file))?
+        .with_path(&file);
+    let engine = RUNEEngine::new();
+    engine
+        .apply_config(config)
+        .with_context(|| format!("Failed to load file: {}", file))?;
+
+    let corpus = fs::read_to_string(&requests)
+        .with_context(|| format!("Failed to read file: {}", requests))?;
+    let corpus = rune_core::coverage::parse_corpus(&corpus)
+        .with_context(|| format!("Failed to parse requests: {}", requests))?;
+    let report = engine.coverage_report(&corpus)?;
+
+    match format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => {
+            let mark = if report.is_complete() {
+                "✓".green()
+            } else {
+                "→".yellow()
+            };
+            println!("{} Coverage of {} by {}", mark, file, requests);
+            print!("{}", report);
+        }
+    }
+    Ok(())
+}
+
+/// Validate everything `rune serve` would load, without serving
+async fn check_command(config: Option<String>, port: u16) -> Result<()> {
+    let Some(config_path) = config else {
+        println!(
+            "{} No configuration file; the server would start empty on port {}",
+            "✓".green(),
+            port
+        );
+        return Ok(());
+    };
+
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
+    let contents = fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read file: {}", config_path))?;
+    // Policies are only compiled when applied, so load into a scratch engine
+    let result = rune_core::parse_rune_file(&contents).and_then(|parsed| {
+        let warnings = parsed.warnings.clone();
+        let summary = (parsed.rules.len(), parsed.policies.len());
+        RUNEEngine::new().apply_config(parsed.with_path(&config_path))?;
+        Ok((summary, warnings))
+    });
+
+    match result {
+        Ok(((rules, policies), warnings)) => {
+            if !warnings.is_empty() {
+                eprint!("{}", warnings.render(Some(&contents), color));
+            }
+            println!(
+                "{} {}: {} rules, {} policies; would listen on port {}",
+                "✓".green(),
+                config_path,
+                rules,
+                policies,
+                port
+            );
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{} {} is invalid:", "✗".red(), config_path);
+            eprintln!();
+            eprint!("{}", e.render_with_source(Some(&contents), color));
+            std::process::exit(1);
+        }
+    }
+}
+
 async fn serve_command(config: Option<String>, port: u16) -> Result<()> {
     println!("{} Starting RUNE server on port {}...", "→".blue(), port);
 
@@ -0,0 +1,838 @@
+//! `rune stress`: sustained mixed workload with fault injection
+//!
+//! Authorization workers run flat out while a churn thread adds, expires and
+//! retracts facts and a reload thread re-applies the configuration. Faults
+//! can run alongside:
+//!
+//! - `slow-provider`: for a while, Datalog evaluation carries a rule set heavy
+//!   enough to overrun the evaluation timeout, so decisions degrade through
+//!   the failure policy
+//! - `malformed-config`: configuration pushes that do not parse, or whose
+//!   policies do not compile, and must leave the engine untouched
+//! - `cache-clear`: the decision cache is dropped
+//!
+//! Requests and fact changes are drawn from `--seed`, printed with the
+//! results, so a run's workload can be repeated. Latency percentiles and
+//! resident memory are reported every interval and summarized at the end. The run fails if an authorization errors, a
+//! malformed configuration is accepted, or memory grows past
+//! `--max-rss-growth`.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use colored::*;
+use rune_core::engine::EngineConfig;
+use rune_core::seed;
+use rune_core::{
+    parse_rune_file, Action, Decision, Principal, RUNEEngine, Request, Resource, Value,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Workload used when no configuration file is given
+const BUILTIN_CONFIG: &str = r#"version = "rune/2.0"
+
+[rules]
+can_access(U, R) :- member(U, G), grant(G, R).
+active(U) :- member(U, G).
+
+[policies]
+@id("reads")
+permit(principal, action == Action::"read", resource);
+
+@id("lists")
+permit(principal, action == Action::"list", resource);
+
+@id("no-deletes")
+forbid(principal, action == Action::"delete", resource);
+"#;
+
+/// Actions the workers cycle through
+const ACTIONS: [&str; 4] = ["read", "list", "write", "delete"];
+
+/// Distinct principals and resources requests are drawn from
+const PRINCIPALS: u64 = 1_000;
+const RESOURCES: u64 = 1_000;
+
+/// Facts the churn thread keeps before retracting the oldest
+const CHURN_WINDOW: usize = 250;
+
+/// Length of the chain the slow-provider fault derives a closure over
+const SLOW_CHAIN: usize = 50;
+
+/// Fault to inject during a stress run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fault {
+    /// Datalog evaluation overruns its budget for a while
+    SlowProvider,
+    /// Configuration pushes that must be rejected
+    MalformedConfig,
+    /// The decision cache is cleared
+    CacheClear,
+}
+
+/// Settings for a stress run
+pub struct StressOptions {
+    /// RUNE file to load instead of the built-in workload
+    pub config: Option<String>,
+    /// How long to run
+    pub duration: Duration,
+    /// Authorization worker threads
+    pub threads: usize,
+    /// Fact changes per second
+    pub churn_rate: u64,
+    /// Time between configuration reloads
+    pub reload_interval: Duration,
+    /// Time between injected faults of each kind
+    pub fault_interval: Duration,
+    /// Faults to inject
+    pub faults: Vec<Fault>,
+    /// Time between progress reports
+    pub report_interval: Duration,
+    /// Datalog evaluation budget in milliseconds
+    pub timeout_ms: u64,
+    /// Largest acceptable growth in resident memory, in MB
+    pub max_rss_growth_mb: Option<u64>,
+    /// Output format for the final report (text, json)
+    pub format: String,
+}
+
+/// Parse a duration such as `90s`, `15m`, `2h` or `500ms`; a bare number
+/// is seconds
+pub fn parse_duration(input: &str) -> std::result::Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", input))?;
+    let seconds = match unit {
+        "" | "s" => value,
+        "ms" => value / 1000.0,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => {
+            return Err(format!(
+                "unknown duration unit '{}' (use ms, s, m or h)",
+                unit
+            ))
+        }
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Latency histogram with log-linear buckets: exact below 16ns, then eight
+/// buckets per power of two, so percentiles are within 12.5%
+#[derive(Clone)]
+pub struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: vec![0; Self::bucket(u64::MAX) + 1],
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn bucket(nanos: u64) -> usize {
+        if nanos < 16 {
+            return nanos as usize;
+        }
+        let exponent = 63 - nanos.leading_zeros() as usize;
+        let sub = ((nanos >> (exponent - 3)) & 7) as usize;
+        16 + (exponent - 4) * 8 + sub
+    }
+
+    /// Largest value that falls into `bucket`
+    fn upper_bound(bucket: usize) -> u64 {
+        if bucket < 16 {
+            return bucket as u64;
+        }
+        let exponent = (bucket - 16) / 8 + 4;
+        let sub = ((bucket - 16) % 8) as u64;
+        let base = (8 + sub) << (exponent - 3);
+        base.saturating_add((1 << (exponent - 3)) - 1)
+    }
+
+    /// Record one latency
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.buckets[Self::bucket(nanos)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(nanos);
+        self.max = self.max.max(nanos);
+    }
+
+    /// Add another histogram's samples
+    pub fn merge(&mut self, other: &Histogram) {
+        for (mine, theirs) in self.buckets.iter_mut().zip(&other.buckets) {
+            *mine += theirs;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Number of samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Latency at quantile `q` (0.0 to 1.0), rounded up to its bucket
+    pub fn quantile(&self, q: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (bucket, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Duration::from_nanos(Self::upper_bound(bucket).min(self.max));
+            }
+        }
+        Duration::from_nanos(self.max)
+    }
+
+    /// Mean latency
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.sum.checked_div(self.count).unwrap_or(0))
+    }
+
+    /// Slowest latency
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// Percentiles of the samples
+    pub fn summary(&self) -> LatencySummary {
+        let micros = |d: Duration| d.as_secs_f64() * 1e6;
+        LatencySummary {
+            mean_us: micros(self.mean()),
+            p50_us: micros(self.quantile(0.50)),
+            p90_us: micros(self.quantile(0.90)),
+            p99_us: micros(self.quantile(0.99)),
+            p999_us: micros(self.quantile(0.999)),
+            max_us: micros(self.max()),
+        }
+    }
+}
+
+/// Counters shared by every thread of a run
+#[derive(Default)]
+struct Counters {
+    permits: AtomicU64,
+    denials: AtomicU64,
+    degraded: AtomicU64,
+    errors: AtomicU64,
+    facts_added: AtomicU64,
+    facts_retracted: AtomicU64,
+    compactions: AtomicU64,
+    reloads: AtomicU64,
+    reload_failures: AtomicU64,
+    slow_windows: AtomicU64,
+    malformed_rejected: AtomicU64,
+    malformed_accepted: AtomicU64,
+    cache_clears: AtomicU64,
+}
+
+/// Latency percentiles in microseconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub p999_us: f64,
+    pub max_us: f64,
+}
+
+/// Resident memory over the run, in bytes; absent where unsupported
+#[derive(Debug, Serialize)]
+struct MemorySummary {
+    start_rss: Option<u64>,
+    end_rss: Option<u64>,
+    peak_rss: Option<u64>,
+    growth: Option<i64>,
+}
+
+/// Final report of a stress run
+#[derive(Debug, Serialize)]
+struct StressReport {
+    seed: Option<u64>,
+    duration_secs: f64,
+    threads: usize,
+    faults: Vec<Fault>,
+    authorizations: u64,
+    throughput_per_sec: f64,
+    permits: u64,
+    denials: u64,
+    degraded: u64,
+    errors: u64,
+    latency: LatencySummary,
+    facts_added: u64,
+    facts_retracted: u64,
+    compactions: u64,
+    final_fact_store_len: usize,
+    reloads: u64,
+    reload_failures: u64,
+    slow_windows: u64,
+    malformed_rejected: u64,
+    malformed_accepted: u64,
+    cache_clears: u64,
+    memory: MemorySummary,
+    problems: Vec<String>,
+}
+
+/// Run a stress test and report on it
+pub fn stress_command(options: StressOptions) -> Result<()> {
+    let source = match &options.config {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path))?,
+        None => BUILTIN_CONFIG.to_string(),
+    };
+    let engine = Arc::new(RUNEEngine::with_config(EngineConfig {
+        timeout_ms: options.timeout_ms,
+        ..EngineConfig::default()
+    }));
+    engine
+        .apply_config(parse_rune_file(&source)?)
+        .context("Failed to load configuration")?;
+    engine.add_fact(
+        "member",
+        vec![Value::string("user-0"), Value::string("staff")],
+    );
+
+    let json = options.format == "json";
+    if !json {
+        println!("{} Running stress test...", "→".blue());
+        println!("  Duration: {:?}", options.duration);
+        println!("  Threads: {}", options.threads);
+        println!(
+            "  Faults: {}",
+            if options.faults.is_empty() {
+                "none".to_string()
+            } else {
+                options
+                    .faults
+                    .iter()
+                    .map(|f| f.to_possible_value().unwrap().get_name().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        );
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let counters = Arc::new(Counters::default());
+    let start_rss = resident_bytes();
+    let mut peak_rss = start_rss;
+    let start = Instant::now();
+
+    let histograms: Vec<_> = (0..options.threads.max(1))
+        .map(|_| Arc::new(Mutex::new(Histogram::default())))
+        .collect();
+    let mut handles = Vec::new();
+    for (worker, histogram) in histograms.iter().enumerate() {
+        let (engine, stop, counters, histogram) = (
+            engine.clone(),
+            stop.clone(),
+            counters.clone(),
+            histogram.clone(),
+        );
+        handles.push(thread::spawn(move || {
+            authorize_loop(worker as u64, &engine, &stop, &counters, &histogram)
+        }));
+    }
+    {
+        let (engine, stop, counters) = (engine.clone(), stop.clone(), counters.clone());
+        let rate = options.churn_rate;
+        handles.push(thread::spawn(move || {
+            churn_loop(rate, &engine, &stop, &counters)
+        }));
+    }
+    {
+        let (engine, stop, counters) = (engine.clone(), stop.clone(), counters.clone());
+        let (source, interval) = (source.clone(), options.reload_interval);
+        handles.push(thread::spawn(move || {
+            every(interval, &stop, || {
+                match parse_rune_file(&source).and_then(|config| engine.apply_config(config)) {
+                    Ok(()) => counters.reloads.fetch_add(1, Ordering::Relaxed),
+                    Err(_) => counters.reload_failures.fetch_add(1, Ordering::Relaxed),
+                };
+            })
+        }));
+    }
+    for &fault in &options.faults {
+        let (engine, stop, counters) = (engine.clone(), stop.clone(), counters.clone());
+        let interval = options.fault_interval;
+        handles.push(thread::spawn(move || {
+            inject_loop(fault, interval, &engine, &stop, &counters)
+        }));
+    }
+
+    // Report progress until the run is over
+    let mut total = Histogram::default();
+    let mut next_report = options.report_interval;
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= options.duration {
+            break;
+        }
+        let wake = next_report.min(options.duration);
+        thread::sleep(wake.saturating_sub(elapsed).min(Duration::from_millis(250)));
+        if start.elapsed() < next_report {
+            continue;
+        }
+        next_report += options.report_interval;
+
+        let interval = drain(&histograms);
+        total.merge(&interval);
+        let rss = resident_bytes();
+        peak_rss = peak_rss.max(rss);
+        if !json {
+            println!(
+                "[{:>6.0}s] {:>9} ops {:>9.0}/s  p50 {}  p99 {}  max {}  rss {}  facts {}",
+                start.elapsed().as_secs_f64(),
+                total.count(),
+                interval.count() as f64 / options.report_interval.as_secs_f64(),
+                Latency(interval.quantile(0.5)),
+                Latency(interval.quantile(0.99)),
+                Latency(interval.max()),
+                Memory(rss, start_rss),
+                engine.fact_store_len(),
+            );
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("Stress thread panicked"))?;
+    }
+    let duration = start.elapsed();
+    total.merge(&drain(&histograms));
+    let end_rss = resident_bytes();
+    peak_rss = peak_rss.max(end_rss);
+
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let growth = start_rss
+        .zip(end_rss)
+        .map(|(start, end)| end as i64 - start as i64);
+    let mut problems = Vec::new();
+    if load(&counters.errors) > 0 {
+        problems.push(format!(
+            "{} authorizations returned an error",
+            load(&counters.errors)
+        ));
+    }
+    if load(&counters.malformed_accepted) > 0 {
+        problems.push(format!(
+            "{} malformed configurations were accepted",
+            load(&counters.malformed_accepted)
+        ));
+    }
+    if let (Some(limit), Some(growth)) = (options.max_rss_growth_mb, growth) {
+        if growth > (limit * 1024 * 1024) as i64 {
+            problems.push(format!(
+                "resident memory grew by {:.1} MB (limit {} MB)",
+                growth as f64 / 1_048_576.0,
+                limit
+            ));
+        }
+    }
+
+    let report = StressReport {
+        seed: seed::seed(),
+        duration_secs: duration.as_secs_f64(),
+        threads: histograms.len(),
+        faults: options.faults.clone(),
+        authorizations: total.count(),
+        throughput_per_sec: total.count() as f64 / duration.as_secs_f64(),
+        permits: load(&counters.permits),
+        denials: load(&counters.denials),
+        degraded: load(&counters.degraded),
+        errors: load(&counters.errors),
+        latency: total.summary(),
+        facts_added: load(&counters.facts_added),
+        facts_retracted: load(&counters.facts_retracted),
+        compactions: load(&counters.compactions),
+        final_fact_store_len: engine.fact_store_len(),
+        reloads: load(&counters.reloads),
+        reload_failures: load(&counters.reload_failures),
+        slow_windows: load(&counters.slow_windows),
+        malformed_rejected: load(&counters.malformed_rejected),
+        malformed_accepted: load(&counters.malformed_accepted),
+        cache_clears: load(&counters.cache_clears),
+        memory: MemorySummary {
+            start_rss,
+            end_rss,
+            peak_rss,
+            growth,
+        },
+        problems,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report, &total);
+    }
+    if !report.problems.is_empty() {
+        bail!("Stress test failed: {}", report.problems.join("; "));
+    }
+    Ok(())
+}
+
+/// Take every worker's samples since the last drain
+fn drain(histograms: &[Arc<Mutex<Histogram>>]) -> Histogram {
+    let mut merged = Histogram::default();
+    for histogram in histograms {
+        let taken = std::mem::take(&mut *histogram.lock().unwrap());
+        merged.merge(&taken);
+    }
+    merged
+}
+
+/// Issue authorizations until stopped
+fn authorize_loop(
+    worker: u64,
+    engine: &RUNEEngine,
+    stop: &AtomicBool,
+    counters: &Counters,
+    histogram: &Mutex<Histogram>,
+) {
+    let mut rng = seed::rng(&format!("stress-worker-{}", worker));
+    while !stop.load(Ordering::Relaxed) {
+        let request = Request::new(
+            Principal::user(format!("user-{}", rng.below(PRINCIPALS))),
+            Action::new(ACTIONS[rng.below(ACTIONS.len() as u64) as usize]),
+            Resource::file(format!("/data/doc-{}", rng.below(RESOURCES))),
+        );
+
+        let started = Instant::now();
+        let result = engine.authorize(&request);
+        let latency = started.elapsed();
+        histogram.lock().unwrap().record(latency);
+
+        match result {
+            Ok(result) => {
+                let counter = match result.decision {
+                    Decision::Permit => &counters.permits,
+                    Decision::Deny | Decision::Forbid => &counters.denials,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+                if !result.failures.is_empty() {
+                    counters.degraded.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Err(_) => {
+                counters.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Add, expire and retract facts at `rate` changes per second until stopped
+fn churn_loop(rate: u64, engine: &RUNEEngine, stop: &AtomicBool, counters: &Counters) {
+    let mut rng = seed::rng("stress-churn");
+    let mut window = std::collections::VecDeque::with_capacity(CHURN_WINDOW);
+    let pause = Duration::from_secs(1)
+        .checked_div(rate.max(1) as u32)
+        .unwrap_or_default();
+    let mut changes: u64 = 0;
+
+    while !stop.load(Ordering::Relaxed) {
+        let args = vec![
+            Value::string(format!("user-{}", rng.below(PRINCIPALS))),
+            Value::string(format!("group-{}", rng.below(50))),
+        ];
+        if rng.below(4) == 0 {
+            engine.add_fact_with_ttl("member", args, Duration::from_millis(500));
+        } else {
+            engine.add_fact("member", args.clone());
+            window.push_back(args);
+        }
+        counters.facts_added.fetch_add(1, Ordering::Relaxed);
+
+        if window.len() > CHURN_WINDOW {
+            if let Some(oldest) = window.pop_front() {
+                if engine.retract_fact("member", oldest) {
+                    counters.facts_retracted.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        changes += 1;
+        if changes.is_multiple_of(500) {
+            engine.compact_facts();
+            counters.compactions.fetch_add(1, Ordering::Relaxed);
+        }
+        thread::sleep(pause);
+    }
+}
+
+/// Inject one kind of fault every `interval` until stopped
+fn inject_loop(
+    fault: Fault,
+    interval: Duration,
+    engine: &RUNEEngine,
+    stop: &AtomicBool,
+    counters: &Counters,
+) {
+    let mut round = 0u64;
+    every(interval, stop, || {
+        round += 1;
+        match fault {
+            Fault::SlowProvider => slow_window(engine, stop, interval / 2, counters),
+            Fault::MalformedConfig => {
+                let generation = engine.generation();
+                let rejected = malformed_config(round)
+                    .and_then(|config| engine.apply_config(config))
+                    .is_err();
+                if rejected && engine.generation() == generation {
+                    counters.malformed_rejected.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    counters.malformed_accepted.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Fault::CacheClear => {
+                engine.clear_cache();
+                counters.cache_clears.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+}
+
+/// Swap in a rule set too heavy for the evaluation budget for `length`
+fn slow_window(engine: &RUNEEngine, stop: &AtomicBool, length: Duration, counters: &Counters) {
+    let rules = engine.datalog_version().rules().to_vec();
+    let mut heavy_source: String = (0..SLOW_CHAIN)
+        .map(|i| format!("stress_link({}, {}).\n", i, i + 1))
+        .collect();
+    heavy_source.push_str("stress_reach(X, Y) :- stress_link(X, Y).\n");
+    heavy_source.push_str("stress_reach(X, Z) :- stress_reach(X, Y), stress_link(Y, Z).\n");
+    let Ok(heavy) = rune_core::parser::parse_rules(&heavy_source) else {
+        return;
+    };
+
+    let mut slow = rules.clone();
+    slow.extend(heavy);
+    if engine.reload_datalog_rules(slow).is_err() {
+        return;
+    }
+    counters.slow_windows.fetch_add(1, Ordering::Relaxed);
+    let until = Instant::now() + length;
+    while Instant::now() < until && !stop.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(50));
+    }
+    // A reload may have replaced the rules meanwhile; restoring is harmless
+    let _ = engine.reload_datalog_rules(rules);
+}
+
+/// A configuration that must be rejected: alternately unparseable and
+/// carrying a policy that does not compile
+fn malformed_config(round: u64) -> rune_core::Result<rune_core::parser::RUNEConfig> {
+    if round.is_multiple_of(2) {
+        parse_rune_file(
+            "version = \"rune/2.0\"\n\n[rules]\nallowed(U) :-\n    member(U, \"admins\".\n",
+        )
+    } else {
+        parse_rune_file(
+            "version = \"rune/2.0\"\n\n[policies]\npermit(principal, action == , resource);\n",
+        )
+    }
+}
+
+/// Call `f` every `interval` until stopped, checking the flag often
+fn every(interval: Duration, stop: &AtomicBool, mut f: impl FnMut()) {
+    let mut next = Instant::now() + interval;
+    while !stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= next {
+            f();
+            next = now + interval;
+        }
+        thread::sleep((next - Instant::now().min(next)).min(Duration::from_millis(50)));
+    }
+}
+
+/// Resident set size of this process, where the platform reports it
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn print_report(report: &StressReport, latency: &Histogram) {
+    println!("\n{} Stress Test Results", "═".blue().bold());
+    println!("{} Duration: {:.1}s", "▸".blue(), report.duration_secs);
+    if let Some(seed) = report.seed {
+        println!("{} Seed: {}", "▸".blue(), seed);
+    }
+    println!(
+        "{} Authorizations: {} ({:.0}/s)",
+        "▸".blue(),
+        report.authorizations,
+        report.throughput_per_sec
+    );
+    println!(
+        "{} Decisions: {} permit, {} deny, {} degraded, {} errors",
+        "▸".blue(),
+        report.permits,
+        report.denials,
+        report.degraded,
+        report.errors
+    );
+
+    println!("\n{} Latency", "═".blue().bold());
+    for (label, q) in [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p99.9", 0.999)] {
+        println!("{} {}: {}", "▸".blue(), label, Latency(latency.quantile(q)));
+    }
+    println!("{} max: {}", "▸".blue(), Latency(latency.max()));
+
+    println!("\n{} Workload", "═".blue().bold());
+    println!(
+        "{} Facts: {} added, {} retracted, {} compactions, {} stored",
+        "▸".blue(),
+        report.facts_added,
+        report.facts_retracted,
+        report.compactions,
+        report.final_fact_store_len
+    );
+    println!(
+        "{} Reloads: {} applied, {} failed",
+        "▸".blue(),
+        report.reloads,
+        report.reload_failures
+    );
+    if !report.faults.is_empty() {
+        println!(
+            "{} Faults: {} slow windows, {} malformed configs rejected, {} accepted, {} cache clears",
+            "▸".blue(),
+            report.slow_windows,
+            report.malformed_rejected,
+            report.malformed_accepted,
+            report.cache_clears
+        );
+    }
+
+    println!("\n{} Memory", "═".blue().bold());
+    match (report.memory.start_rss, report.memory.end_rss) {
+        (Some(_), Some(end)) => {
+            println!(
+                "{} Resident: {}",
+                "▸".blue(),
+                Memory(Some(end), report.memory.start_rss)
+            );
+            if let Some(peak) = report.memory.peak_rss {
+                println!("{} Peak: {:.1} MB", "▸".blue(), peak as f64 / 1_048_576.0);
+            }
+        }
+        _ => println!(
+            "{} Resident memory is not available on this platform",
+            "▸".blue()
+        ),
+    }
+
+    if report.problems.is_empty() {
+        println!("\n{} Stable", "✓".green().bold());
+    } else {
+        for problem in &report.problems {
+            println!("\n{} {}", "✗".red().bold(), problem);
+        }
+    }
+}
+
+/// Latency formatted with a unit suited to its size
+struct Latency(Duration);
+
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = self.0.as_secs_f64() * 1e6;
+        if micros < 1_000.0 {
+            write!(f, "{:.1}µs", micros)
+        } else if micros < 1_000_000.0 {
+            write!(f, "{:.2}ms", micros / 1_000.0)
+        } else {
+            write!(f, "{:.2}s", micros / 1_000_000.0)
+        }
+    }
+}
+
+/// Resident memory and its growth since the start of the run
+struct Memory(Option<u64>, Option<u64>);
+
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Memory(Some(now), Some(start)) => write!(
+                f,
+                "{:.1} MB ({:+.1})",
+                now as f64 / 1_048_576.0,
+                (now as f64 - start as f64) / 1_048_576.0
+            ),
+            Memory(Some(now), None) => write!(f, "{:.1} MB", now as f64 / 1_048_576.0),
+            _ => write!(f, "n/a"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn test_histogram_quantiles() {
+        let mut histogram = Histogram::default();
+        for micros in 1..=1000 {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.count(), 1000);
+        assert_eq!(histogram.max(), Duration::from_micros(1000));
+
+        // Buckets are at most 12.5% wide
+        for (q, exact) in [(0.5, 500.0), (0.99, 990.0)] {
+            let micros = histogram.quantile(q).as_secs_f64() * 1e6;
+            assert!(
+                micros >= exact && micros <= exact * 1.125,
+                "{} -> {}",
+                q,
+                micros
+            );
+        }
+
+        let mut merged = Histogram::default();
+        merged.merge(&histogram);
+        merged.merge(&histogram);
+        assert_eq!(merged.count(), 2000);
+        assert_eq!(merged.quantile(0.5), histogram.quantile(0.5));
+    }
+}
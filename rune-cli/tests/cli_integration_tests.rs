@@ -61,6 +61,72 @@ fn test_eval_with_principal() {
         .stdout(predicate::str::contains("Evaluating request"));
 }
 
+/// Test eval command with a device principal and its attributes
+#[test]
+fn test_eval_with_principal_type() {
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("eval")
+        .arg("--action")
+        .arg("sync")
+        .arg("--principal")
+        .arg("phone-1")
+        .arg("--principal-type")
+        .arg("device")
+        .arg("--principal-attr")
+        .arg("platform=ios")
+        .arg("--resource")
+        .arg("/notes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Principal: phone-1"));
+
+    // Devices must name their platform
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("eval")
+        .arg("--action")
+        .arg("sync")
+        .arg("--principal-type")
+        .arg("device")
+        .arg("--resource")
+        .arg("/notes")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("platform"));
+}
+
+/// Test eval command with roles and groups for the principal
+#[test]
+fn test_eval_with_roles_and_groups() {
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("eval")
+        .arg("--action")
+        .arg("read")
+        .arg("--principal")
+        .arg("alice")
+        .arg("--role")
+        .arg("reader")
+        .arg("--group")
+        .arg("eng")
+        .arg("--resource")
+        .arg("/docs/guide.md")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Principal: alice"));
+
+    // IDs are checked before anything is evaluated
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("eval")
+        .arg("--action")
+        .arg("read")
+        .arg("--role")
+        .arg(" reader")
+        .arg("--resource")
+        .arg("/docs/guide.md")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("role ID"));
+}
+
 /// Test eval command with JSON format
 #[test]
 fn test_eval_json_format() {
@@ -165,6 +231,190 @@ fn test_validate_invalid_config() {
         .stdout(predicate::str::contains("Configuration is invalid:"));
 }
 
+/// Test validate command shows rule errors against the file
+#[test]
+fn test_validate_shows_source_excerpt() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    write!(
+        temp_file,
+        "version = \"rune/2.0\"\n\n[rules]\nallowed(U) :-\n    member(U, \"admins\".\n"
+    )
+    .unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("validate")
+        .arg(temp_file.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("error[E0101]: Unclosed `(`"))
+        .stdout(predicate::str::contains("--> 5:11"))
+        .stdout(predicate::str::contains("5 |     member(U, \"admins\"."));
+}
+
+/// Test serve --check validates the configuration without serving
+#[test]
+fn test_serve_check() {
+    let mut valid = NamedTempFile::new().unwrap();
+    write!(
+        valid,
+        "version = \"rune/2.0\"\n\n[rules]\nallowed(U) :- member(U, \"admins\").\n"
+    )
+    .unwrap();
+    valid.flush().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("serve")
+        .arg("--check")
+        .arg("--config")
+        .arg(valid.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 rules, 0 policies"));
+
+    let mut invalid = NamedTempFile::new().unwrap();
+    write!(
+        invalid,
+        "version = \"rune/2.0\"\n\n[policies]\n@id(\"p\")\npermit(principal, action, resource) when {{ nope }};\n"
+    )
+    .unwrap();
+    invalid.flush().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("serve")
+        .arg("--check")
+        .arg("--config")
+        .arg(invalid.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is invalid"));
+}
+
+/// Test validate command with overlays
+#[test]
+fn test_validate_with_overlays() {
+    let mut base = NamedTempFile::new().unwrap();
+    write!(
+        base,
+        "version = \"rune/2.0\"\n\n[rules]\ncan_read(U) :- member(U, \"readers\").\n\n[policies]\n@id(\"no-deletes\")\nforbid(principal, action == Action::\"delete\", resource);\n"
+    )
+    .unwrap();
+    base.flush().unwrap();
+
+    let mut team = NamedTempFile::new().unwrap();
+    write!(
+        team,
+        "version = \"rune/2.0\"\n\n[rules]\ncan_deploy(U) :- on_call(U).\n"
+    )
+    .unwrap();
+    team.flush().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("validate")
+        .arg(base.path())
+        .arg("--overlay")
+        .arg(team.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rules: 2"))
+        .stdout(predicate::str::contains(format!(
+            "can_deploy(?U) :- on_call(?U). ({})",
+            team.path().display()
+        )));
+
+    let mut prod = NamedTempFile::new().unwrap();
+    write!(
+        prod,
+        "version = \"rune/2.0\"\n\n[enabled]\nno-deletes = false\n"
+    )
+    .unwrap();
+    prod.flush().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("validate")
+        .arg(base.path())
+        .arg("--overlay")
+        .arg(prod.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "cannot disable forbid policy no-deletes",
+        ));
+}
+
+/// Test validate --analyze reports a forbid shadowing a permit
+#[test]
+fn test_validate_analyze_conflicts() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(
+        temp_file,
+        r#"version = "rune/2.0"
+
+[policies]
+@id("readers")
+permit(principal, action == Action::"read", resource in Folder::"q3");
+
+@id("frozen")
+forbid(principal, action, resource in Folder::"q3");"#
+    )
+    .unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("validate")
+        .arg(temp_file.path())
+        .arg("--analyze")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 permit/forbid conflicts"))
+        .stdout(predicate::str::contains("warning[W0301]"))
+        .stdout(predicate::str::contains(
+            "Permit `readers` never takes effect: forbid `frozen`",
+        ));
+}
+
+/// Test coverage lists unexercised policies and rules and unmatched requests
+#[test]
+fn test_coverage_of_recorded_requests() {
+    let mut config = NamedTempFile::new().unwrap();
+    writeln!(
+        config,
+        r#"version = "rune/2.0"
+
+[rules]
+member("alice", "staff").
+reader(U) :- member(U, "staff").
+auditor(U) :- member(U, "audit").
+
+[policies]
+@id("readers")
+permit(principal, action == Action::"read", resource);
+
+@id("writers")
+permit(principal, action == Action::"write", resource);"#
+    )
+    .unwrap();
+    let mut requests = NamedTempFile::new().unwrap();
+    writeln!(
+        requests,
+        r#"{{"principal": "User:alice", "action": "read", "resource": "Document:q3"}}
+{{"principal": "User:bob", "action": "delete", "resource": "Document:q3"}}"#
+    )
+    .unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("coverage")
+        .arg(config.path())
+        .arg("--requests")
+        .arg(requests.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Policies exercised: 1/2"))
+        .stdout(predicate::str::contains("never matched: writers (permit)"))
+        .stdout(predicate::str::contains("Rules exercised: 1/2"))
+        .stdout(predicate::str::contains("never fired: auditor"))
+        .stdout(predicate::str::contains("#1: User:bob delete Document:q3"));
+}
+
 /// Test validate command with missing file
 #[test]
 fn test_validate_missing_file() {
@@ -223,6 +473,198 @@ fn test_benchmark_verbose() {
         .success();
 }
 
+/// Test benchmark saves its results and gates on a baseline
+#[test]
+fn test_benchmark_baseline_regression() {
+    let dir = tempfile::tempdir().unwrap();
+    let results = dir.path().join("results.json");
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.args([
+        "benchmark",
+        "--requests",
+        "200",
+        "--threads",
+        "2",
+        "--output",
+    ])
+    .arg(&results)
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Results saved to"));
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&results).unwrap()).unwrap();
+    assert_eq!(saved["requests"], 200);
+    assert!(saved["latency"]["p99_us"].as_f64().unwrap() > 0.0);
+
+    // Within any tolerance of itself, give or take noise
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.args([
+        "benchmark",
+        "--requests",
+        "200",
+        "--threads",
+        "2",
+        "--baseline",
+    ])
+    .arg(&results)
+    .args(["--fail-on-regression", "100000%"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Compared with"))
+    .stdout(predicate::str::contains("p99 latency"));
+
+    // A baseline no real run can match
+    let mut fast = saved.clone();
+    fast["throughput_per_sec"] = serde_json::json!(1e12);
+    fast["latency"]["p99_us"] = serde_json::json!(1e-6);
+    let baseline = dir.path().join("baseline.json");
+    std::fs::write(&baseline, fast.to_string()).unwrap();
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.args([
+        "benchmark",
+        "--requests",
+        "200",
+        "--threads",
+        "2",
+        "--baseline",
+    ])
+    .arg(&baseline)
+    .args(["--fail-on-regression", "10%"])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(
+        "Performance regressed by more than 10%",
+    ))
+    .stderr(predicate::str::contains("throughput"));
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.args(["benchmark", "--fail-on-regression", "10%"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--baseline"));
+}
+
+/// Test gen writes a loadable configuration that repeats with its seed
+#[test]
+fn test_gen_is_seeded_and_loadable() {
+    let generate = || {
+        let mut cmd = cargo::cargo_bin_cmd!("rune");
+        cmd.args([
+            "gen",
+            "--users",
+            "20",
+            "--resources",
+            "0.1k",
+            "--rules",
+            "rbac+abac",
+            "--seed",
+            "7",
+        ])
+        .output()
+        .unwrap()
+    };
+    let output = generate();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, generate().stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Users: 20"));
+    assert!(stderr.contains("Resources: 100"));
+
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(&output.stdout).unwrap();
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("benchmark")
+        .arg("--config")
+        .arg(file.path())
+        .arg("--requests")
+        .arg("10")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Successful: 10"));
+}
+
+/// Test gen rejects unknown rule families
+#[test]
+fn test_gen_invalid_rules() {
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.args(["gen", "--rules", "rbac+acl"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown rule family 'acl'"));
+}
+
+/// Test stress command prints progress and a final report
+#[test]
+fn test_stress_text_report() {
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("stress")
+        .arg("--duration")
+        .arg("1500ms")
+        .arg("--threads")
+        .arg("2")
+        .arg("--report-interval")
+        .arg("500ms")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running stress test..."))
+        .stdout(predicate::str::contains("Stress Test Results"))
+        .stdout(predicate::str::contains("p99.9:"))
+        .stdout(predicate::str::contains("Stable"));
+}
+
+/// Test stress command survives injected faults and reports them
+#[test]
+fn test_stress_with_faults_json() {
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    let output = cmd
+        .arg("stress")
+        .arg("--duration")
+        .arg("2s")
+        .arg("--threads")
+        .arg("2")
+        .arg("--reload-interval")
+        .arg("300ms")
+        .arg("--fault-interval")
+        .arg("200ms")
+        .arg("--fault")
+        .arg("malformed-config")
+        .arg("--fault")
+        .arg("cache-clear")
+        .arg("--seed")
+        .arg("7")
+        .arg("--format")
+        .arg("json")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(report["authorizations"].as_u64().unwrap() > 0);
+    assert_eq!(report["errors"], 0);
+    assert!(report["reloads"].as_u64().unwrap() > 0);
+    assert!(report["malformed_rejected"].as_u64().unwrap() > 0);
+    assert_eq!(report["malformed_accepted"], 0);
+    assert!(report["cache_clears"].as_u64().unwrap() > 0);
+    assert_eq!(
+        report["faults"],
+        serde_json::json!(["malformed-config", "cache-clear"])
+    );
+    assert!(report["problems"].as_array().unwrap().is_empty());
+    assert_eq!(report["seed"], 7);
+}
+
+/// Test stress command rejects unparseable durations
+#[test]
+fn test_stress_invalid_duration() {
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("stress")
+        .arg("--duration")
+        .arg("5d")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown duration unit"));
+}
+
 /// Test benchmark command with invalid requests value
 #[test]
 fn test_benchmark_invalid_requests() {
@@ -497,3 +939,336 @@ fn test_global_and_command_flags() {
         .assert()
         .success();
 }
+
+fn transitive_rules() -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(
+        temp_file,
+        r#"version = "rune/1.0"
+
+[rules]
+edge(1, 2).
+edge(2, 3).
+edge(3, 4).
+path(X, Y) :- edge(X, Y).
+path(X, Z) :- path(X, Y), edge(Y, Z)."#
+    )
+    .unwrap();
+    temp_file.flush().unwrap();
+    temp_file
+}
+
+/// Test query command streams a page of facts as NDJSON
+#[test]
+fn test_query_ndjson_page() {
+    let temp_file = transitive_rules();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    let output = cmd
+        .arg("query")
+        .arg(temp_file.path())
+        .arg("--predicate")
+        .arg("path")
+        .arg("--offset")
+        .arg("1")
+        .arg("--limit")
+        .arg("2")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["predicate"], "path");
+    assert_eq!(lines[0]["args"], serde_json::json!([1, 3]));
+    assert_eq!(lines[1]["args"], serde_json::json!([1, 4]));
+}
+
+/// Test query command in count-only mode
+#[test]
+fn test_query_count_only() {
+    let temp_file = transitive_rules();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("query")
+        .arg(temp_file.path())
+        .arg("--predicate")
+        .arg("path")
+        .arg("--count")
+        .assert()
+        .success()
+        .stdout(predicate::str::diff("{\"count\":6}\n"));
+}
+
+/// Test facts export writes a snapshot that facts import reads back
+#[test]
+fn test_facts_snapshot_round_trip() {
+    let mut facts = NamedTempFile::new().unwrap();
+    writeln!(
+        facts,
+        r#"{{"predicate":"member","args":["alice","admins"],"timestamp":0}}
+
+{{"predicate":"member","args":["bob",7],"timestamp":1}}"#
+    )
+    .unwrap();
+    facts.flush().unwrap();
+    let snapshot = NamedTempFile::new().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("facts")
+        .arg("export")
+        .arg(facts.path())
+        .arg("--output")
+        .arg(snapshot.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Wrote 2 facts"));
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    let output = cmd
+        .arg("facts")
+        .arg("import")
+        .arg(snapshot.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1]["args"], serde_json::json!(["bob", 7]));
+
+    // A file that is not a snapshot is refused
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("facts")
+        .arg("import")
+        .arg(facts.path())
+        .assert()
+        .failure();
+}
+
+/// Test migrate command upgrades a 1.0 file in place
+#[test]
+fn test_migrate_in_place() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(
+        temp_file,
+        r#"version = "rune/1.0"
+
+[cedar_policies]
+permit(principal, action, resource);"#
+    )
+    .unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("migrate")
+        .arg(temp_file.path())
+        .arg("--to")
+        .arg("2.0")
+        .arg("--in-place")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains(
+            "Renamed [cedar_policies] to [policies]",
+        ))
+        .stderr(predicate::str::contains("1 manual step(s) remaining"));
+
+    let migrated = std::fs::read_to_string(temp_file.path()).unwrap();
+    assert!(migrated.starts_with("version = \"rune/2.0\""));
+    assert!(migrated.contains("[policies]\n@id(\"policy_0\")\npermit"));
+}
+
+/// Test migrate command rejects unknown target versions
+#[test]
+fn test_migrate_unknown_version() {
+    let temp_file = transitive_rules();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("migrate")
+        .arg(temp_file.path())
+        .arg("--to")
+        .arg("7.0")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported format version"));
+}
+
+/// Test export command writes a file that loads back
+#[test]
+fn test_export_rune_round_trip() {
+    let temp_file = transitive_rules();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    let output = cmd
+        .arg("export")
+        .arg(temp_file.path())
+        .arg("--format")
+        .arg("rune")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let exported = String::from_utf8(output.stdout).unwrap();
+    assert!(exported.starts_with("version = \"rune/2.0\""));
+    assert!(exported.contains("path(X, Z) :- path(X, Y), edge(Y, Z)."));
+
+    let mut reexport_file = NamedTempFile::new().unwrap();
+    reexport_file.write_all(exported.as_bytes()).unwrap();
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("export")
+        .arg(reexport_file.path())
+        .arg("--format")
+        .arg("rune")
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(exported));
+}
+
+/// Test export command rejects unknown formats
+#[test]
+fn test_export_unknown_format() {
+    let temp_file = transitive_rules();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("export")
+        .arg(temp_file.path())
+        .arg("--format")
+        .arg("yaml")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown export format"));
+}
+
+/// Test test command runs the shared conformance scenarios
+#[test]
+fn test_conformance_scenarios_pass() {
+    let scenarios = concat!(env!("CARGO_MANIFEST_DIR"), "/../conformance/scenarios");
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("test")
+        .arg(scenarios)
+        .arg("--seed")
+        .arg("42")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 failed (seed 42)"));
+}
+
+/// Test test command reports failing scenarios with the seed to repeat them
+#[test]
+fn test_failing_scenario_reports_seed() {
+    let mut scenario = NamedTempFile::with_suffix(".yaml").unwrap();
+    write!(
+        scenario,
+        r#"name: nobody may read
+config: |
+  version = "rune/1.0"
+request: {{ principal: "User:alice", action: read, resource: "File:/q3" }}
+expect:
+  decision: permit
+"#
+    )
+    .unwrap();
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    let output = cmd
+        .arg("test")
+        .arg(scenario.path())
+        .arg("--format")
+        .arg("json")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["passed"], 0);
+    assert_eq!(report["failed"][0]["name"], "nobody may read");
+    assert!(report["seed"].is_u64());
+}
+
+/// Serve one HTTP response with `body` as `GET /v1/export`, returning the
+/// base URL
+fn serve_export_once(body: Vec<u8>) -> String {
+    use std::io::Read;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 4096];
+        let _ = stream.read(&mut request).unwrap();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nx-rune-generation: 7\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(&body).unwrap();
+    });
+    url
+}
+
+/// Test drift reports rules edited on the server but not in the repository
+#[test]
+fn test_drift_against_repository_head() {
+    let repo = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(repo.path())
+            .args(["-c", "user.name=rune", "-c", "user.email=rune@example.com"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "--quiet"]);
+    std::fs::write(
+        repo.path().join("policies.rune"),
+        "version = \"rune/1.0\"\n\n[rules]\nreader(U) :- member(U, \"staff\").\n",
+    )
+    .unwrap();
+    git(&["add", "policies.rune"]);
+    git(&["commit", "--quiet", "-m", "Add policies"]);
+
+    // The server runs an extra rule added through the management API
+    let mut running = NamedTempFile::new().unwrap();
+    writeln!(
+        running,
+        "version = \"rune/1.0\"\n\n[rules]\nreader(U) :- member(U, \"staff\").\nwriter(U) :- member(U, \"staff\")."
+    )
+    .unwrap();
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    let export = cmd.arg("export").arg(running.path()).output().unwrap();
+    assert!(export.status.success());
+
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("drift")
+        .arg("--repo")
+        .arg(repo.path())
+        .arg("--server")
+        .arg(serve_export_once(export.stdout))
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("generation 7 has drifted"))
+        .stdout(predicate::str::contains("+ rule writer/1"));
+
+    // A missing file fails before the server is contacted
+    let mut cmd = cargo::cargo_bin_cmd!("rune");
+    cmd.arg("drift")
+        .arg("--repo")
+        .arg(repo.path())
+        .arg("--server")
+        .arg("http://127.0.0.1:9")
+        .arg("--file")
+        .arg("missing.rune")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.rune not found at HEAD"));
+}
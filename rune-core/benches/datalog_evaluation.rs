@@ -90,7 +90,10 @@ fn create_transitive_closure_rules() -> Vec<Rule> {
                 terms: vec![Term::Variable("X".into()), Term::Variable("Y".into())],
                 negated: false,
             }],
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0,
+            annotations: Default::default(),
         },
         // Recursive case: path(X, Z) :- edge(X, Y), path(Y, Z).
         Rule {
@@ -111,7 +114,10 @@ fn create_transitive_closure_rules() -> Vec<Rule> {
                     negated: false,
                 },
             ],
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0,
+            annotations: Default::default(),
         },
     ]
 }
@@ -131,7 +137,10 @@ fn create_ancestor_rules() -> Vec<Rule> {
                 terms: vec![Term::Variable("X".into()), Term::Variable("Y".into())],
                 negated: false,
             }],
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0,
+            annotations: Default::default(),
         },
         // Recursive case: ancestor(X, Z) :- parent(X, Y), ancestor(Y, Z).
         Rule {
@@ -152,7 +161,10 @@ fn create_ancestor_rules() -> Vec<Rule> {
                     negated: false,
                 },
             ],
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0,
+            annotations: Default::default(),
         },
     ]
 }
@@ -311,7 +323,10 @@ fn bench_query_planning(c: &mut Criterion) {
                         terms: vec![Term::Variable("X".into()), Term::Variable("Y".into())],
                         negated: false,
                     }],
+                    guards: Vec::new(),
+                    builtins: Vec::new(),
                     stratum: 0,
+                    annotations: Default::default(),
                 },
             ],
         ),
@@ -337,7 +352,10 @@ fn bench_query_planning(c: &mut Criterion) {
                             negated: false,
                         },
                     ],
+                    guards: Vec::new(),
+                    builtins: Vec::new(),
                     stratum: 0,
+                    annotations: Default::default(),
                 },
             ],
         ),
@@ -372,7 +390,10 @@ fn bench_query_planning(c: &mut Criterion) {
                             negated: false,
                         },
                     ],
+                    guards: Vec::new(),
+                    builtins: Vec::new(),
                     stratum: 0,
+                    annotations: Default::default(),
                 },
             ],
         ),
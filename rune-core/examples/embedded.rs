@@ -0,0 +1,68 @@
+//! Example of RUNE embedded in another program
+//!
+//! The minimal build keeps the Datalog evaluator, the fact store and the
+//! decision cache, and nothing else: no Cedar, hot reload, file watching or
+//! Tokio. A program embedding RUNE that way depends on
+//!
+//! ```toml
+//! rune-core = { version = "*", default-features = false, features = ["minimal"] }
+//! ```
+//!
+//! Run with: cargo run --example embedded --no-default-features --features minimal
+//!
+//! CI builds this example in release mode and fails when the binary grows
+//! past its size budget (see `.github/workflows/ci.yml`).
+
+use rune_core::datalog::{Atom, Term};
+use rune_core::{Action, EmbeddedEngine, Principal, RequestBuilder, Resource, Value};
+use std::time::Duration;
+
+// Members of a group may read what the group owns
+const RULES: &str = r#"can_read(U, D) :- member(U, G), owns(G, D)."#;
+
+fn main() -> rune_core::Result<()> {
+    println!("=== RUNE Embedded Example ===\n");
+
+    // Rules are parsed once; decisions are cached for ten seconds
+    let engine = EmbeddedEngine::from_rules(RULES)?.with_cache(1_000, Duration::from_secs(10));
+
+    // Facts come from the host program, here a few constants
+    let string = |s: &str| Value::string(s);
+    engine.add_fact("member", vec![string("alice"), string("staff")]);
+    engine.add_fact("owns", vec![string("staff"), string("handbook")]);
+
+    let request = RequestBuilder::new()
+        .principal(Principal::user("alice"))
+        .action(Action::new("read"))
+        .resource(Resource::new("Document", "handbook"))
+        .build()?;
+
+    // The first decision is evaluated, the second comes from the cache
+    for _ in 0..2 {
+        let result = engine.authorize(&request)?;
+        println!(
+            "{:?} (cached: {}, {} µs)",
+            result.decision,
+            result.cached,
+            result.evaluation_time_ns / 1_000
+        );
+    }
+
+    // Derived facts are available too, for hosts that decide on their own
+    println!("\nDerived facts:");
+    for fact in engine.derive_facts()? {
+        let terms = fact.args.iter().cloned().map(Term::constant).collect();
+        println!("  {}", Atom::new(fact.predicate.as_ref(), terms));
+    }
+
+    // Changing the facts invalidates the cached decisions built on them
+    engine.retract_fact("member", vec![string("alice"), string("staff")]);
+    engine.retract_fact("owns", vec![string("staff"), string("handbook")]);
+    let result = engine.authorize(&request)?;
+    println!(
+        "\nAfter retracting the membership: {:?} (cached: {})",
+        result.decision, result.cached
+    );
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+//! Just-in-time access requests
+//!
+//! A principal refused access can ask for it instead of going around the
+//! PDP. [`crate::RUNEEngine::request_access`] records a pending
+//! [`AccessRequest`] for the principal, action and resource of a request.
+//! While it is pending, decisions on that request that are not permits
+//! carry the `access_pending` reason code, naming the request, so an
+//! enforcement point can say the access is awaiting review.
+//!
+//! A reviewer approves or denies a pending request. Approval records the
+//! fact `access_approved(principal, action, resource)` (IDs and the action
+//! name), attributed to the reviewer in the fact history; what an approval
+//! grants is up to the rules:
+//!
+//! ```text
+//! allowed(P, A, R) :- access_approved(P, A, R).
+//! ```
+//!
+//! Retracting the fact revokes the access again. Denial records nothing.
+//! Asking again for access that is already pending returns the pending
+//! request. Requests are kept in memory, up to [`MAX_ACCESS_REQUESTS`];
+//! beyond that the oldest reviewed ones are forgotten.
+
+use crate::error::{RUNEError, Result};
+use crate::facts::Fact;
+use crate::request::Request;
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Predicate of the facts approvals record
+pub const APPROVAL_PREDICATE: &str = "access_approved";
+
+/// Most access requests kept, pending or reviewed
+pub const MAX_ACCESS_REQUESTS: usize = 10_000;
+
+/// Where an access request stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessRequestStatus {
+    /// Waiting for review
+    Pending,
+    /// Approved; the approval fact was recorded
+    Approved,
+    /// Denied
+    Denied,
+}
+
+impl fmt::Display for AccessRequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AccessRequestStatus::Pending => "pending",
+            AccessRequestStatus::Approved => "approved",
+            AccessRequestStatus::Denied => "denied",
+        })
+    }
+}
+
+impl std::str::FromStr for AccessRequestStatus {
+    type Err = RUNEError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(AccessRequestStatus::Pending),
+            "approved" => Ok(AccessRequestStatus::Approved),
+            "denied" => Ok(AccessRequestStatus::Denied),
+            _ => Err(RUNEError::InvalidRequest(format!(
+                "Unknown access request status: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// A principal's request for access
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRequest {
+    /// Request ID
+    pub id: u64,
+    /// Principal type
+    pub principal_type: String,
+    /// Principal ID
+    pub principal: String,
+    /// Action asked for
+    pub action: String,
+    /// Resource type
+    pub resource_type: String,
+    /// Resource ID
+    pub resource: String,
+    /// Why the principal needs the access
+    pub justification: Option<String>,
+    /// Where the request stands
+    pub status: AccessRequestStatus,
+    /// When the request was made, in milliseconds since the Unix epoch
+    pub requested_at_ms: u64,
+    /// When it was reviewed
+    pub reviewed_at_ms: Option<u64>,
+    /// Who reviewed it
+    pub reviewer: Option<String>,
+    /// The reviewer's note
+    pub note: Option<String>,
+}
+
+impl AccessRequest {
+    /// The fact approving this request records
+    pub fn approval_fact(&self) -> Fact {
+        Fact::new(
+            APPROVAL_PREDICATE,
+            vec![
+                Value::string(self.principal.as_str()),
+                Value::string(self.action.as_str()),
+                Value::string(self.resource.as_str()),
+            ],
+        )
+    }
+
+    fn is_for(&self, request: &Request) -> bool {
+        let principal = &request.principal.entity;
+        let resource = &request.resource.entity;
+        *self.principal == *principal.id
+            && *self.principal_type == *principal.entity_type
+            && *self.action == *request.action.name
+            && *self.resource == *resource.id
+            && *self.resource_type == *resource.entity_type
+    }
+}
+
+#[derive(Debug, Default)]
+struct Requests {
+    next_id: u64,
+    by_id: BTreeMap<u64, AccessRequest>,
+}
+
+/// Access requests of one engine
+#[derive(Debug, Default)]
+pub(crate) struct AccessRequests {
+    requests: Mutex<Requests>,
+    /// Requests pending, so decisions skip the lock when there are none
+    pending: AtomicUsize,
+}
+
+impl AccessRequests {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Requests> {
+        self.requests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record a pending request for `request`, or return the one already
+    /// pending for it; `true` if the request is new
+    pub(crate) fn open(
+        &self,
+        request: &Request,
+        justification: Option<String>,
+        now_ms: u64,
+    ) -> Result<(AccessRequest, bool)> {
+        let mut requests = self.lock();
+        if let Some(pending) = requests
+            .by_id
+            .values()
+            .find(|r| r.status == AccessRequestStatus::Pending && r.is_for(request))
+        {
+            return Ok((pending.clone(), false));
+        }
+
+        if requests.by_id.len() >= MAX_ACCESS_REQUESTS {
+            let reviewed = requests
+                .by_id
+                .values()
+                .find(|r| r.status != AccessRequestStatus::Pending)
+                .map(|r| r.id)
+                .ok_or_else(|| {
+                    RUNEError::InvalidRequest(format!(
+                        "Too many pending access requests (max {})",
+                        MAX_ACCESS_REQUESTS
+                    ))
+                })?;
+            requests.by_id.remove(&reviewed);
+        }
+
+        requests.next_id += 1;
+        let access = AccessRequest {
+            id: requests.next_id,
+            principal_type: request.principal.entity.entity_type.to_string(),
+            principal: request.principal.entity.id.to_string(),
+            action: request.action.name.to_string(),
+            resource_type: request.resource.entity.entity_type.to_string(),
+            resource: request.resource.entity.id.to_string(),
+            justification,
+            status: AccessRequestStatus::Pending,
+            requested_at_ms: now_ms,
+            reviewed_at_ms: None,
+            reviewer: None,
+            note: None,
+        };
+        requests.by_id.insert(access.id, access.clone());
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        Ok((access, true))
+    }
+
+    /// Move pending request `id` to `status`; `None` if there is no such
+    /// request
+    pub(crate) fn review(
+        &self,
+        id: u64,
+        status: AccessRequestStatus,
+        reviewer: &str,
+        note: Option<String>,
+        now_ms: u64,
+    ) -> Result<Option<AccessRequest>> {
+        let mut requests = self.lock();
+        let Some(access) = requests.by_id.get_mut(&id) else {
+            return Ok(None);
+        };
+        if access.status != AccessRequestStatus::Pending {
+            return Err(RUNEError::InvalidRequest(format!(
+                "Access request {} is already {}",
+                id, access.status
+            )));
+        }
+        access.status = status;
+        access.reviewed_at_ms = Some(now_ms);
+        access.reviewer = Some(reviewer.to_string());
+        access.note = note;
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+        Ok(Some(access.clone()))
+    }
+
+    /// Request `id`
+    pub(crate) fn get(&self, id: u64) -> Option<AccessRequest> {
+        self.lock().by_id.get(&id).cloned()
+    }
+
+    /// Requests with `status`, or all of them, oldest first
+    pub(crate) fn list(&self, status: Option<AccessRequestStatus>) -> Vec<AccessRequest> {
+        self.lock()
+            .by_id
+            .values()
+            .filter(|r| status.is_none_or(|status| r.status == status))
+            .cloned()
+            .collect()
+    }
+
+    /// ID of the request pending for `request`, if any
+    pub(crate) fn pending_for(&self, request: &Request) -> Option<u64> {
+        if self.pending.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        self.lock()
+            .by_id
+            .values()
+            .find(|r| r.status == AccessRequestStatus::Pending && r.is_for(request))
+            .map(|r| r.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, Principal, Resource};
+
+    fn request(path: &str) -> Request {
+        Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file(path),
+        )
+    }
+
+    #[test]
+    fn test_open_and_review() {
+        let requests = AccessRequests::default();
+        let (first, new) = requests
+            .open(&request("/a"), Some("on call".to_string()), 1)
+            .unwrap();
+        assert!(new);
+        assert_eq!(first.status, AccessRequestStatus::Pending);
+        // Asking again returns the pending request
+        let (again, new) = requests.open(&request("/a"), None, 2).unwrap();
+        assert_eq!((again.id, new), (first.id, false));
+        let (second, _) = requests.open(&request("/b"), None, 3).unwrap();
+        assert_eq!(requests.pending_for(&request("/b")), Some(second.id));
+
+        let approved = requests
+            .review(first.id, AccessRequestStatus::Approved, "bob", None, 4)
+            .unwrap()
+            .unwrap();
+        assert_eq!(approved.reviewer.as_deref(), Some("bob"));
+        assert_eq!(
+            approved.approval_fact().args[0],
+            Value::string("alice".to_string())
+        );
+        assert!(requests.pending_for(&request("/a")).is_none());
+        assert!(requests
+            .review(first.id, AccessRequestStatus::Denied, "bob", None, 5)
+            .is_err());
+        assert!(requests
+            .review(99, AccessRequestStatus::Denied, "bob", None, 5)
+            .unwrap()
+            .is_none());
+
+        let pending = requests.list(Some(AccessRequestStatus::Pending));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, second.id);
+        assert_eq!(requests.list(None).len(), 2);
+        assert_eq!(
+            "denied".parse::<AccessRequestStatus>().unwrap(),
+            AccessRequestStatus::Denied
+        );
+    }
+}
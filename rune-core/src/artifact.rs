@@ -0,0 +1,145 @@
+//! Provenance of the loaded policy artifact
+//!
+//! A reviewed configuration ships as a release artifact, and every decision
+//! should be traceable back to it. Each parsed RUNE file carries the SHA-256
+//! digest of its bytes, the same value `sha256sum` prints for the artifact,
+//! and the release pipeline can stamp the commit it was built from and who
+//! signed it into an `[artifact]` section:
+//!
+//! ```text
+//! [artifact]
+//! git_sha = "3f2c9a1e8b7d6c5f4a3b2c1d0e9f8a7b6c5d4e3f"
+//! signer = "release-bot@example.com"
+//! ```
+//!
+//! The digest covers the section too, so a stamped file has a single
+//! digest. The signer is recorded as stated: verifying the signature is the
+//! job of whatever deploys the artifact. A stack of layers (see
+//! [`crate::layers`]) is identified by a digest over its layers' digests,
+//! in order, with later layers' `git_sha` and `signer` winning.
+
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Where the active rules and policies came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyArtifact {
+    /// `sha256:` followed by the hex digest of the artifact
+    pub digest: String,
+    /// Commit the artifact was built from, if stamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_sha: Option<String>,
+    /// Who signed the artifact, if stamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+}
+
+/// Fields of an `[artifact]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArtifactSection {
+    git_sha: Option<String>,
+    signer: Option<String>,
+}
+
+impl PolicyArtifact {
+    /// Artifact of a RUNE file's text, with what its `[artifact]` section
+    /// (if any) states
+    pub fn of_source(source: &str, section: Option<&str>) -> Result<Self> {
+        let stamped: ArtifactSection = section
+            .map(|section| {
+                toml::from_str(section).map_err(|e| {
+                    RUNEError::ParseError(format!("Failed to parse artifact section: {}", e))
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        if let Some(sha) = &stamped.git_sha {
+            if !(7..=64).contains(&sha.len()) || !sha.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(RUNEError::ConfigError(format!(
+                    "Invalid git_sha '{}': expected 7 to 64 hex digits",
+                    sha
+                )));
+            }
+        }
+        if stamped
+            .signer
+            .as_deref()
+            .is_some_and(|s| s.trim().is_empty())
+        {
+            return Err(RUNEError::ConfigError(
+                "Invalid signer: must not be empty".to_string(),
+            ));
+        }
+
+        Ok(PolicyArtifact {
+            digest: digest(source.as_bytes()),
+            git_sha: stamped.git_sha.map(|sha| sha.to_ascii_lowercase()),
+            signer: stamped.signer,
+        })
+    }
+
+    /// Artifact of a stack of layers, base first
+    ///
+    /// A single layer keeps its own artifact.
+    pub fn compose<'a>(layers: impl IntoIterator<Item = &'a PolicyArtifact>) -> Self {
+        let layers: Vec<&PolicyArtifact> = layers.into_iter().collect();
+        if let [single] = layers.as_slice() {
+            return (*single).clone();
+        }
+        let digests: String = layers
+            .iter()
+            .map(|layer| format!("{}\n", layer.digest))
+            .collect();
+        PolicyArtifact {
+            digest: digest(digests.as_bytes()),
+            git_sha: layers.iter().rev().find_map(|layer| layer.git_sha.clone()),
+            signer: layers.iter().rev().find_map(|layer| layer.signer.clone()),
+        }
+    }
+}
+
+fn digest(bytes: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifact_of_source() {
+        let source = "version = \"rune/2.0\"\n";
+        let artifact = PolicyArtifact::of_source(source, None).unwrap();
+        assert_eq!(
+            artifact.digest,
+            "sha256:e2b7d92b3189c28b6fe1e3607086eea600e64a03daf42f5aed160698540a7cad"
+        );
+        assert_eq!(artifact.git_sha, None);
+
+        let stamped =
+            PolicyArtifact::of_source(source, Some("git_sha = \"ABC1234\"\nsigner = \"ci\"\n"))
+                .unwrap();
+        assert_eq!(stamped.git_sha.as_deref(), Some("abc1234"));
+        assert_eq!(stamped.signer.as_deref(), Some("ci"));
+
+        assert!(PolicyArtifact::of_source(source, Some("git_sha = \"main\"\n")).is_err());
+        assert!(PolicyArtifact::of_source(source, Some("signer = \" \"\n")).is_err());
+        assert!(PolicyArtifact::of_source(source, Some("branch = \"main\"\n")).is_err());
+    }
+
+    #[test]
+    fn test_compose_artifacts() {
+        let base = PolicyArtifact::of_source("a", Some("git_sha = \"aaaaaaa\"\n")).unwrap();
+        let team = PolicyArtifact::of_source("b", Some("signer = \"team\"\n")).unwrap();
+
+        assert_eq!(PolicyArtifact::compose([&base]), base);
+
+        let stack = PolicyArtifact::compose([&base, &team]);
+        assert_ne!(stack.digest, base.digest);
+        assert_ne!(stack.digest, PolicyArtifact::compose([&team, &base]).digest);
+        assert_eq!(stack.git_sha.as_deref(), Some("aaaaaaa"));
+        assert_eq!(stack.signer.as_deref(), Some("team"));
+    }
+}
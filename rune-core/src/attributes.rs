@@ -0,0 +1,405 @@
+//! Merging principal attributes supplied by several sources
+//!
+//! The same attribute can reach a request from more than one place: a JWT
+//! claim, a SCIM sync, the request context. Rather than letting whichever
+//! was applied last win, each attribute is resolved with a lattice merge
+//! chosen in the `[attributes]` section of a RUNE file:
+//!
+//! ```toml
+//! priority = ["scim", "jwt", "request"]
+//! default = "source-priority"
+//!
+//! [strategies]
+//! clearance = "max"
+//! risk_score = "max"
+//! session_limit = "min"
+//! department = "newest"
+//! ```
+//!
+//! - `max` and `min` take the greatest or least value in [`Value`]'s order
+//! - `newest` takes the value from the source observed most recently
+//! - `source-priority` takes the value from the highest-ranked source
+//!
+//! Sources rank in `priority` order, followed by unlisted sources in the
+//! order the request supplies them. Ties under every strategy go to the
+//! higher-ranked source. The principal's own attributes take part as the
+//! source named `request`, supplied first.
+//!
+//! Whenever sources disagree, the winning source is noted in the decision's
+//! explanation.
+
+use crate::error::{RUNEError, Result};
+use crate::request::Request;
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Name of the source formed by the principal's own attributes
+pub const REQUEST_SOURCE: &str = "request";
+
+/// How conflicting values for one attribute are resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// Greatest value
+    Max,
+    /// Least value
+    Min,
+    /// Value from the most recently observed source
+    Newest,
+    /// Value from the highest-ranked source
+    #[default]
+    SourcePriority,
+}
+
+impl fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MergeStrategy::Max => "max",
+            MergeStrategy::Min => "min",
+            MergeStrategy::Newest => "newest",
+            MergeStrategy::SourcePriority => "source-priority",
+        })
+    }
+}
+
+/// Principal attributes as supplied by one source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributeSource {
+    /// Source name (e.g. "jwt", "scim")
+    pub name: String,
+    /// Attributes the source supplies
+    pub attributes: BTreeMap<String, Value>,
+    /// When the source last refreshed these values, in Unix milliseconds;
+    /// sources without one lose to any that have one under `newest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_at_ms: Option<u64>,
+}
+
+impl AttributeSource {
+    /// Create a source with no attributes
+    pub fn new(name: impl Into<String>) -> Self {
+        AttributeSource {
+            name: name.into(),
+            attributes: BTreeMap::new(),
+            observed_at_ms: None,
+        }
+    }
+
+    /// Add an attribute
+    pub fn with_attribute(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.attributes.insert(key.into(), value);
+        self
+    }
+
+    /// Set when the source last refreshed its values
+    pub fn observed_at(mut self, unix_ms: u64) -> Self {
+        self.observed_at_ms = Some(unix_ms);
+        self
+    }
+}
+
+/// Merge settings as written in a RUNE file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttributeMergeConfig {
+    /// Source names, highest-ranked first
+    #[serde(default)]
+    pub priority: Vec<String>,
+    /// Strategy for attributes without their own
+    #[serde(default)]
+    pub default: MergeStrategy,
+    /// Strategy per attribute name
+    #[serde(default)]
+    pub strategies: BTreeMap<String, MergeStrategy>,
+}
+
+impl AttributeMergeConfig {
+    /// Parse the TOML body of an `[attributes]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        let config: Self = toml::from_str(input).map_err(|e| {
+            RUNEError::ParseError(format!("Failed to parse attributes section: {}", e))
+        })?;
+        for (i, name) in config.priority.iter().enumerate() {
+            if config.priority[..i].contains(name) {
+                return Err(RUNEError::ConfigError(format!(
+                    "Attribute source '{}' is listed twice in priority",
+                    name
+                )));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Strategy used for `attribute`
+    pub fn strategy(&self, attribute: &str) -> MergeStrategy {
+        self.strategies
+            .get(attribute)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Which source an attribute was taken from when sources disagreed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributeWinner {
+    /// Attribute name
+    pub attribute: String,
+    /// Source whose value was kept
+    pub source: String,
+    /// Strategy that picked it
+    pub strategy: MergeStrategy,
+    /// Number of sources that supplied the attribute
+    pub candidates: usize,
+}
+
+impl fmt::Display for AttributeWinner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attribute '{}' taken from {} ({} of {} sources)",
+            self.attribute, self.source, self.strategy, self.candidates
+        )
+    }
+}
+
+/// A request with its attribute sources folded into the principal
+#[derive(Debug, Clone)]
+pub struct MergedRequest {
+    /// The request, principal attributes merged and sources removed
+    pub request: Request,
+    /// Winners of every attribute the sources disagreed on
+    pub winners: Vec<AttributeWinner>,
+}
+
+/// Applies an [`AttributeMergeConfig`] to requests
+#[derive(Debug, Clone, Default)]
+pub struct AttributeMerger {
+    config: AttributeMergeConfig,
+}
+
+impl AttributeMerger {
+    /// Create a merger from its configuration
+    pub fn new(config: AttributeMergeConfig) -> Self {
+        AttributeMerger { config }
+    }
+
+    /// Configuration in use
+    pub fn config(&self) -> &AttributeMergeConfig {
+        &self.config
+    }
+
+    /// Fold a request's attribute sources into its principal
+    ///
+    /// Returns `None` when the request has no sources, so callers can keep
+    /// using the original without cloning it.
+    pub fn merge(&self, request: &Request) -> Option<MergedRequest> {
+        if request.attribute_sources.is_empty() {
+            return None;
+        }
+
+        let own = AttributeSource {
+            name: REQUEST_SOURCE.to_string(),
+            attributes: (*request.principal.entity.attributes).clone(),
+            observed_at_ms: None,
+        };
+        let mut sources: Vec<&AttributeSource> = std::iter::once(&own)
+            .chain(request.attribute_sources.iter())
+            .collect();
+        // Stable, so unlisted sources keep the order they were supplied in
+        sources.sort_by_key(|source| self.rank(&source.name));
+
+        let mut candidates: BTreeMap<&str, Vec<(&AttributeSource, &Value)>> = BTreeMap::new();
+        for source in &sources {
+            for (attribute, value) in &source.attributes {
+                if *value != Value::Null {
+                    candidates
+                        .entry(attribute)
+                        .or_default()
+                        .push((source, value));
+                }
+            }
+        }
+
+        let mut attributes = BTreeMap::new();
+        let mut winners = Vec::new();
+        for (attribute, candidates) in candidates {
+            let strategy = self.config.strategy(attribute);
+            let (source, value) = pick(strategy, &candidates);
+            if candidates.iter().any(|(_, v)| *v != value) {
+                winners.push(AttributeWinner {
+                    attribute: attribute.to_string(),
+                    source: source.name.clone(),
+                    strategy,
+                    candidates: candidates.len(),
+                });
+            }
+            attributes.insert(attribute.to_string(), value.clone());
+        }
+
+        let mut merged = request.clone();
+        merged.principal.entity.attributes = Arc::new(attributes);
+        merged.attribute_sources = Vec::new();
+        Some(MergedRequest {
+            request: merged,
+            winners,
+        })
+    }
+
+    /// Position of a source in the priority list; unlisted sources share the
+    /// lowest rank
+    fn rank(&self, source: &str) -> usize {
+        self.config
+            .priority
+            .iter()
+            .position(|name| name == source)
+            .unwrap_or(self.config.priority.len())
+    }
+}
+
+/// Choose among candidates ordered by rank, the highest-ranked first
+fn pick<'a>(
+    strategy: MergeStrategy,
+    candidates: &[(&'a AttributeSource, &'a Value)],
+) -> (&'a AttributeSource, &'a Value) {
+    let mut best = candidates[0];
+    for &candidate in &candidates[1..] {
+        let better = match strategy {
+            MergeStrategy::Max => candidate.1 > best.1,
+            MergeStrategy::Min => candidate.1 < best.1,
+            MergeStrategy::Newest => candidate.0.observed_at_ms > best.0.observed_at_ms,
+            MergeStrategy::SourcePriority => false,
+        };
+        if better {
+            best = candidate;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, Principal, Resource};
+
+    fn merger() -> AttributeMerger {
+        AttributeMerger::new(
+            AttributeMergeConfig::from_toml(
+                r#"
+priority = ["scim", "jwt"]
+
+[strategies]
+clearance = "max"
+session_limit = "min"
+department = "newest"
+"#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn request() -> Request {
+        Request::new(
+            Principal::user("alice").with_attribute("clearance", Value::Integer(1)),
+            Action::new("read"),
+            Resource::file("/docs/1"),
+        )
+        .with_attribute_source(
+            AttributeSource::new("jwt")
+                .with_attribute("clearance", Value::Integer(3))
+                .with_attribute("session_limit", Value::Integer(60))
+                .with_attribute("department", Value::string("sales"))
+                .with_attribute("team", Value::string("emea"))
+                .observed_at(2_000),
+        )
+        .with_attribute_source(
+            AttributeSource::new("scim")
+                .with_attribute("clearance", Value::Integer(2))
+                .with_attribute("session_limit", Value::Integer(30))
+                .with_attribute("department", Value::string("finance"))
+                .with_attribute("team", Value::string("apac"))
+                .observed_at(1_000),
+        )
+    }
+
+    #[test]
+    fn test_strategies() {
+        let merged = merger().merge(&request()).unwrap();
+        let attributes = &merged.request.principal.entity.attributes;
+
+        assert_eq!(attributes["clearance"], Value::Integer(3));
+        assert_eq!(attributes["session_limit"], Value::Integer(30));
+        assert_eq!(attributes["department"], Value::string("sales"));
+        // Default strategy: scim outranks jwt
+        assert_eq!(attributes["team"], Value::string("apac"));
+        assert!(merged.request.attribute_sources.is_empty());
+
+        let winners: Vec<_> = merged
+            .winners
+            .iter()
+            .map(|w| (w.attribute.as_str(), w.source.as_str(), w.strategy))
+            .collect();
+        assert_eq!(
+            winners,
+            [
+                ("clearance", "jwt", MergeStrategy::Max),
+                ("department", "jwt", MergeStrategy::Newest),
+                ("session_limit", "scim", MergeStrategy::Min),
+                ("team", "scim", MergeStrategy::SourcePriority),
+            ]
+        );
+        assert_eq!(
+            merged.winners[0].to_string(),
+            "attribute 'clearance' taken from jwt (max of 3 sources)"
+        );
+    }
+
+    #[test]
+    fn test_ties_and_unlisted_sources() {
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/docs/1"),
+        )
+        .with_attribute_source(
+            AttributeSource::new("ldap").with_attribute("team", Value::string("a")),
+        )
+        .with_attribute_source(
+            AttributeSource::new("hr").with_attribute("team", Value::string("b")),
+        )
+        .with_attribute_source(
+            AttributeSource::new("jwt").with_attribute("clearance", Value::Integer(2)),
+        )
+        .with_attribute_source(
+            AttributeSource::new("scim").with_attribute("clearance", Value::Integer(2)),
+        );
+        let merged = merger().merge(&request).unwrap();
+        let attributes = &merged.request.principal.entity.attributes;
+
+        // Unlisted sources rank in the order they were supplied
+        assert_eq!(attributes["team"], Value::string("a"));
+        assert_eq!(merged.winners[0].source, "ldap");
+        // Agreeing sources are not a conflict
+        assert_eq!(attributes["clearance"], Value::Integer(2));
+        assert_eq!(merged.winners.len(), 1);
+    }
+
+    #[test]
+    fn test_requests_without_sources_pass_through() {
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/docs/1"),
+        );
+        assert!(merger().merge(&request).is_none());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(AttributeMergeConfig::from_toml("default = \"loudest\"").is_err());
+        assert!(AttributeMergeConfig::from_toml("priority = [\"jwt\", \"jwt\"]").is_err());
+        assert!(AttributeMergeConfig::from_toml("priorities = [\"jwt\"]").is_err());
+    }
+}
@@ -0,0 +1,678 @@
+//! Custom builtin predicates
+//!
+//! Operators can extend rule bodies with functions RUNE does not ship, such
+//! as a proprietary risk score, without recompiling rune-core or loading
+//! native plugins. Each builtin is a WebAssembly module run in a sandbox:
+//! it cannot import anything from the host, and every call gets a fresh
+//! instance with a fuel (CPU) budget and a memory cap.
+//!
+//! ```text
+//! [builtins]
+//! risk_level = { module = "builtins/risk.wasm", arity = 2, fuel = 5_000_000, memory_mb = 8 }
+//!
+//! [rules]
+//! high_risk(U) :- user(U), risk_level(U, "high").
+//! ```
+//!
+//! A body atom naming a builtin is a call: its last argument is the output,
+//! the others are inputs that positive body atoms must bind. A constant or
+//! already bound output is compared with the result. Calls run after the
+//! body is joined and before guards, and a call that fails, runs out of fuel
+//! or returns nothing drops the match.
+//!
+//! # Module ABI
+//!
+//! Values cross the boundary as JSON, in the same shape as request
+//! attributes. A module exports:
+//!
+//! - `memory`
+//! - `rune_alloc(len: i32) -> i32`, returning a buffer for the host to
+//!   write the inputs into
+//! - the builtin function (named after the builtin unless `function` says
+//!   otherwise) taking `(ptr: i32, len: i32)` of a JSON array of the inputs
+//!   and returning `i64` `(ptr << 32) | len` of the JSON output; `0` or
+//!   `null` means there is no output
+//!
+//! Builtins must be pure, since derived facts and decisions are cached.
+//!
+//! # Module paths
+//!
+//! A relative `module` path resolves against the directory of the RUNE
+//! file naming it (see [`RUNEConfig::with_path`]), or the module directory
+//! for a file that was not read from disk, such as one posted to a reload
+//! endpoint. Modules are only loaded from the module directory
+//! ([`EngineConfig::builtin_module_dir`], by default the RUNE file's
+//! directory): a path that leaves it, including through a symlink, is
+//! refused, and load errors never quote the file they read.
+//!
+//! [`RUNEConfig::with_path`]: crate::parser::RUNEConfig::with_path
+//! [`EngineConfig::builtin_module_dir`]: crate::engine::EngineConfig::builtin_module_dir
+
+use crate::datalog::types::{Atom, BuiltinCall, BuiltinFunction, Rule};
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+/// A registered builtin
+#[derive(Clone)]
+struct Registered {
+    arity: usize,
+    function: Arc<dyn BuiltinFunction>,
+}
+
+/// Builtins available to rule bodies, by name
+#[derive(Clone, Default)]
+pub struct BuiltinRegistry {
+    builtins: HashMap<String, Registered>,
+}
+
+impl std::fmt::Debug for BuiltinRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: Vec<_> = self.builtins.keys().collect();
+        names.sort();
+        f.debug_struct("BuiltinRegistry")
+            .field("builtins", &names)
+            .finish()
+    }
+}
+
+impl BuiltinRegistry {
+    /// Registry with no builtins
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `function` under `name`, taking `arity` arguments including
+    /// the output
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        arity: usize,
+        function: Arc<dyn BuiltinFunction>,
+    ) -> Result<()> {
+        let name = name.into();
+        if arity == 0 {
+            return Err(RUNEError::ConfigError(format!(
+                "Builtin '{}' needs at least an output argument",
+                name
+            )));
+        }
+        self.builtins.insert(name, Registered { arity, function });
+        Ok(())
+    }
+
+    /// Whether no builtins are registered
+    pub fn is_empty(&self) -> bool {
+        self.builtins.is_empty()
+    }
+
+    /// Whether a builtin is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    /// Turn body atoms naming a builtin into calls
+    ///
+    /// Rules that already have calls are rebound to this registry's
+    /// functions; a call whose builtin is no longer registered goes back to
+    /// being an ordinary atom and matches no facts. Fails if a call has the
+    /// wrong number of arguments, is negated, or has an input no positive
+    /// body atom binds.
+    pub fn bind(&self, rules: Vec<Rule>) -> Result<Vec<Rule>> {
+        if self.is_empty() && rules.iter().all(|rule| rule.builtins.is_empty()) {
+            return Ok(rules);
+        }
+        rules.into_iter().map(|rule| self.bind_rule(rule)).collect()
+    }
+
+    fn bind_rule(&self, mut rule: Rule) -> Result<Rule> {
+        // Existing calls go back to being atoms, after the body so later
+        // calls still see the outputs of earlier ones
+        let mut atoms = std::mem::take(&mut rule.body);
+        for call in std::mem::take(&mut rule.builtins) {
+            if !self.contains(&call.name) {
+                warn!(builtin = %call.name, "Builtin is no longer registered");
+            }
+            atoms.push(Atom::new(call.name.to_string(), call.args));
+        }
+
+        let mut body = Vec::with_capacity(atoms.len());
+        let mut calls = Vec::new();
+        for atom in atoms {
+            let Some(registered) = self.builtins.get(atom.predicate.as_ref()) else {
+                body.push(atom);
+                continue;
+            };
+            if atom.negated {
+                return Err(RUNEError::ConfigError(format!(
+                    "Builtin '{}' cannot be negated in rule {}",
+                    atom.predicate, rule.head
+                )));
+            }
+            if atom.terms.len() != registered.arity {
+                return Err(RUNEError::ConfigError(format!(
+                    "Builtin '{}' takes {} arguments but rule {} passes {}",
+                    atom.predicate,
+                    registered.arity,
+                    rule.head,
+                    atom.terms.len()
+                )));
+            }
+            calls.push(BuiltinCall {
+                name: atom.predicate,
+                args: atom.terms,
+                function: registered.function.clone(),
+            });
+        }
+
+        rule.body = body;
+        rule.builtins = calls;
+        if !rule.is_safe() {
+            return Err(RUNEError::ConfigError(format!(
+                "Rule {} calls a builtin with an input no positive body atom binds",
+                rule.head
+            )));
+        }
+        Ok(rule)
+    }
+}
+
+/// `[builtins]` section: WASM modules to load, by builtin name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BuiltinsConfig {
+    /// Module settings keyed on the name rules call the builtin by
+    pub builtins: BTreeMap<String, WasmBuiltinConfig>,
+    /// Directory of the RUNE file the section was read from, if any
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+/// Where a builtin's module is and how much it may use per call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmBuiltinConfig {
+    /// Path to the `.wasm` (or `.wat`) file, relative to the RUNE file's
+    /// directory (see [Module paths](self#module-paths))
+    pub module: PathBuf,
+    /// Exported function to call; defaults to the builtin's name
+    #[serde(default)]
+    pub function: Option<String>,
+    /// Number of arguments, including the output
+    pub arity: usize,
+    /// Fuel (roughly, WASM instructions) a single call may use
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    /// Linear memory a single call may grow to, in MiB
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: usize,
+}
+
+fn default_fuel() -> u64 {
+    10_000_000
+}
+
+fn default_memory_mb() -> usize {
+    16
+}
+
+impl BuiltinsConfig {
+    /// Parse a `[builtins]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        let config: Self = toml::from_str(input).map_err(|e| {
+            RUNEError::ParseError(format!("Failed to parse builtins section: {}", e))
+        })?;
+        for (name, builtin) in &config.builtins {
+            if builtin.arity == 0 {
+                return Err(RUNEError::ConfigError(format!(
+                    "Builtin '{}' needs at least an output argument",
+                    name
+                )));
+            }
+            if builtin.memory_mb == 0 {
+                return Err(RUNEError::ConfigError(format!(
+                    "Builtin '{}' needs memory_mb of at least 1",
+                    name
+                )));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Compile every module, found in `module_dir` (see
+    /// [Module paths](self#module-paths)), and register it
+    ///
+    /// Each module is instantiated once up front, so missing exports fail
+    /// here rather than on the first call.
+    #[cfg(feature = "wasm")]
+    pub fn load(&self, module_dir: Option<&Path>) -> Result<BuiltinRegistry> {
+        let mut registry = BuiltinRegistry::new();
+        for (name, config) in &self.builtins {
+            let path = self.module_path(name, config, module_dir)?;
+            let bytes = std::fs::read(&path).map_err(|e| {
+                RUNEError::ConfigError(format!(
+                    "Failed to read builtin '{}' from {}: {}",
+                    name,
+                    config.module.display(),
+                    e
+                ))
+            })?;
+            let function = config.function.as_deref().unwrap_or(name);
+            let builtin = WasmBuiltin::new(&bytes, function, config.fuel, config.memory_mb)
+                .map_err(|e| {
+                    RUNEError::ConfigError(format!("Builtin '{}' cannot be loaded: {}", name, e))
+                })?;
+            registry.register(name.clone(), config.arity, Arc::new(builtin))?;
+        }
+        Ok(registry)
+    }
+
+    /// Without the `wasm` feature only an empty section can be loaded
+    #[cfg(not(feature = "wasm"))]
+    pub fn load(&self, _module_dir: Option<&Path>) -> Result<BuiltinRegistry> {
+        match self.builtins.keys().next() {
+            Some(name) => Err(RUNEError::ConfigError(format!(
+                "Builtin '{}' needs rune-core built with the wasm feature",
+                name
+            ))),
+            None => Ok(BuiltinRegistry::new()),
+        }
+    }
+
+    /// Resolve builtin `name`'s module, refusing one outside the module
+    /// directory
+    ///
+    /// A missing module and one outside the directory fail alike, so a
+    /// posted configuration cannot probe for files elsewhere.
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    fn module_path(
+        &self,
+        name: &str,
+        config: &WasmBuiltinConfig,
+        module_dir: Option<&Path>,
+    ) -> Result<PathBuf> {
+        let allowed = module_dir.or(self.dir.as_deref()).ok_or_else(|| {
+            RUNEError::ConfigError(format!(
+                "Builtin '{}' needs a module directory: load the RUNE file from disk or set builtin_module_dir",
+                name
+            ))
+        })?;
+        let refused = || {
+            RUNEError::ConfigError(format!(
+                "Builtin '{}' module {} is not a file in the module directory {}",
+                name,
+                config.module.display(),
+                allowed.display()
+            ))
+        };
+        if config
+            .module
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return Err(refused());
+        }
+        let path = self.dir.as_deref().unwrap_or(allowed).join(&config.module);
+        // Symlinks are followed before the check, so none lead out
+        let resolved = path.canonicalize().map_err(|_| refused())?;
+        let allowed = allowed.canonicalize().map_err(|_| refused())?;
+        if resolved.starts_with(&allowed) && resolved.is_file() {
+            Ok(resolved)
+        } else {
+            Err(refused())
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+pub use wasm::WasmBuiltin;
+
+#[cfg(feature = "wasm")]
+mod wasm {
+    use super::BuiltinFunction;
+    use crate::types::Value;
+    use once_cell::sync::Lazy;
+    use wasmtime::{
+        Config, Engine, Instance, InstancePre, Linker, Module, Store, StoreLimits,
+        StoreLimitsBuilder,
+    };
+
+    /// One engine for every module, so compiled code is shared
+    static ENGINE: Lazy<Engine> = Lazy::new(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).expect("wasmtime engine configuration is valid")
+    });
+
+    /// Builtin implemented by a sandboxed WebAssembly module
+    pub struct WasmBuiltin {
+        pre: InstancePre<StoreLimits>,
+        function: String,
+        fuel: u64,
+        memory_bytes: usize,
+    }
+
+    impl WasmBuiltin {
+        /// Compile a module (binary or text format) exporting `function`
+        ///
+        /// Modules may not import anything, so a call can only see its
+        /// inputs.
+        pub fn new(
+            bytes: &[u8],
+            function: &str,
+            fuel: u64,
+            memory_mb: usize,
+        ) -> std::result::Result<Self, String> {
+            // Text format errors quote the offending line, which may be
+            // any file's contents; binary ones only give an offset
+            let module = Module::new(&ENGINE, bytes).map_err(|e| {
+                if bytes.starts_with(b"\0asm") {
+                    e.to_string()
+                } else {
+                    "not a valid WebAssembly module".to_string()
+                }
+            })?;
+            let imports = module.imports().len();
+            if imports > 0 {
+                return Err(format!(
+                    "modules may not import anything, but this one has {} imports",
+                    imports
+                ));
+            }
+            let pre = Linker::new(&ENGINE)
+                .instantiate_pre(&module)
+                .map_err(|e| e.to_string())?;
+            let builtin = WasmBuiltin {
+                pre,
+                function: function.to_string(),
+                fuel,
+                memory_bytes: memory_mb.saturating_mul(1 << 20),
+            };
+            let (mut store, instance) = builtin.instantiate()?;
+            builtin.exports(&mut store, &instance)?;
+            Ok(builtin)
+        }
+
+        fn instantiate(&self) -> std::result::Result<(Store<StoreLimits>, Instance), String> {
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(self.memory_bytes)
+                .instances(1)
+                .build();
+            let mut store = Store::new(&ENGINE, limits);
+            store.limiter(|limits| limits);
+            store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+            let instance = self
+                .pre
+                .instantiate(&mut store)
+                .map_err(|e| format!("{:#}", e))?;
+            Ok((store, instance))
+        }
+
+        #[allow(clippy::type_complexity)]
+        fn exports(
+            &self,
+            store: &mut Store<StoreLimits>,
+            instance: &Instance,
+        ) -> std::result::Result<
+            (
+                wasmtime::Memory,
+                wasmtime::TypedFunc<i32, i32>,
+                wasmtime::TypedFunc<(i32, i32), i64>,
+            ),
+            String,
+        > {
+            let memory = instance
+                .get_memory(&mut *store, "memory")
+                .ok_or("module does not export memory")?;
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&mut *store, "rune_alloc")
+                .map_err(|e| format!("rune_alloc: {}", e))?;
+            let function = instance
+                .get_typed_func::<(i32, i32), i64>(&mut *store, &self.function)
+                .map_err(|e| format!("{}: {}", self.function, e))?;
+            Ok((memory, alloc, function))
+        }
+    }
+
+    impl BuiltinFunction for WasmBuiltin {
+        fn call(&self, inputs: &[Value]) -> std::result::Result<Option<Value>, String> {
+            let input = serde_json::to_vec(inputs).map_err(|e| e.to_string())?;
+            let len = i32::try_from(input.len()).map_err(|_| "inputs are too large")?;
+
+            let (mut store, instance) = self.instantiate()?;
+            let (memory, alloc, function) = self.exports(&mut store, &instance)?;
+            let trap = |e: wasmtime::Error| format!("{:#}", e);
+
+            let ptr = alloc.call(&mut store, len).map_err(trap)?;
+            memory
+                .write(&mut store, ptr as u32 as usize, &input)
+                .map_err(|e| e.to_string())?;
+            let packed = function.call(&mut store, (ptr, len)).map_err(trap)? as u64;
+            if packed == 0 {
+                return Ok(None);
+            }
+
+            let (start, len) = ((packed >> 32) as usize, (packed & 0xFFFF_FFFF) as usize);
+            let output = memory
+                .data(&store)
+                .get(start..start.saturating_add(len))
+                .ok_or("output is outside the module's memory")?;
+            match serde_json::from_slice(output).map_err(|e| e.to_string())? {
+                Value::Null => Ok(None),
+                value => Ok(Some(value)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datalog::types::Term;
+    use crate::parser::parse_rules;
+    use crate::types::Value;
+
+    struct Double;
+
+    impl BuiltinFunction for Double {
+        fn call(&self, inputs: &[Value]) -> std::result::Result<Option<Value>, String> {
+            match inputs {
+                [Value::Integer(n)] => Ok(Some(Value::Integer(n * 2))),
+                _ => Err("expected one integer".into()),
+            }
+        }
+    }
+
+    fn registry() -> BuiltinRegistry {
+        let mut registry = BuiltinRegistry::new();
+        registry.register("double", 2, Arc::new(Double)).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_bind_moves_calls_out_of_the_body() {
+        let rules = parse_rules("big(X, Y) :- size(X), double(X, Y), Y != 10.").unwrap();
+        let bound = registry().bind(rules).unwrap();
+        assert_eq!(bound[0].body.len(), 1);
+        assert_eq!(bound[0].builtins.len(), 1);
+        assert_eq!(bound[0].builtins[0].args[1], Term::Variable("Y".into()));
+
+        // Rebinding without the builtin turns the call back into an atom
+        let unbound = BuiltinRegistry::new().bind(bound).unwrap();
+        assert_eq!(unbound[0].body.len(), 2);
+        assert!(unbound[0].builtins.is_empty());
+    }
+
+    #[test]
+    fn test_bind_rejects_misuse() {
+        for source in [
+            "big(X) :- size(X), double(X).",
+            "small(X) :- size(X), not double(X, 4).",
+            "big(Y) :- double(X, Y).",
+        ] {
+            let rules = parse_rules(source).unwrap();
+            assert!(registry().bind(rules).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config =
+            BuiltinsConfig::from_toml(r#"risk = { module = "risk.wasm", arity = 2 }"#).unwrap();
+        let risk = &config.builtins["risk"];
+        assert_eq!(risk.fuel, 10_000_000);
+        assert_eq!(risk.memory_mb, 16);
+        assert!(risk.function.is_none());
+
+        assert!(
+            BuiltinsConfig::from_toml(r#"risk = { module = "risk.wasm", arity = 0 }"#).is_err()
+        );
+        assert!(BuiltinsConfig::from_toml(
+            r#"risk = { module = "risk.wasm", arity = 2, cpu = 5 }"#
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "wasm")]
+    mod wasm {
+        use super::*;
+
+        /// Module whose `f` runs `body` with the input at (ptr, len) as
+        /// locals 0 and 1, and a bump allocator starting at 1024
+        fn module(body: &str) -> String {
+            format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (global $next (mut i32) (i32.const 1024))
+                    (data (i32.const 0) "42")
+                    (func (export "rune_alloc") (param i32) (result i32)
+                        (local $p i32)
+                        (local.set $p (global.get $next))
+                        (global.set $next (i32.add (global.get $next) (local.get 0)))
+                        (local.get $p))
+                    (func (export "f") (param i32 i32) (result i64)
+                        {}))"#,
+                body
+            )
+        }
+
+        fn builtin(body: &str) -> std::result::Result<WasmBuiltin, String> {
+            WasmBuiltin::new(module(body).as_bytes(), "f", 1_000_000, 1)
+        }
+
+        #[test]
+        fn test_constant_and_echo() {
+            // Returns the two bytes "42" at address 0
+            let constant = builtin("(i64.const 2)").unwrap();
+            assert_eq!(
+                constant.call(&[Value::string("alice")]).unwrap(),
+                Some(Value::Integer(42))
+            );
+
+            // Returns its own input array
+            let echo = builtin(
+                "(i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32))
+                         (i64.extend_i32_u (local.get 1)))",
+            )
+            .unwrap();
+            let inputs = [Value::string("alice"), Value::Integer(3)];
+            assert_eq!(
+                echo.call(&inputs).unwrap(),
+                Some(Value::Array(inputs.to_vec().into()))
+            );
+
+            let nothing = builtin("(i64.const 0)").unwrap();
+            assert_eq!(nothing.call(&[]).unwrap(), None);
+        }
+
+        #[test]
+        fn test_limits() {
+            let spin = builtin("(loop $l (br $l)) (i64.const 0)").unwrap();
+            let error = spin.call(&[]).unwrap_err();
+            assert!(error.contains("fuel"), "{}", error);
+
+            // Growing past the 1 MiB cap fails and returns -1
+            let grow = builtin(
+                "(if (i32.eq (memory.grow (i32.const 32)) (i32.const -1))
+                    (then unreachable))
+                 (i64.const 0)",
+            )
+            .unwrap();
+            assert!(grow.call(&[]).is_err());
+        }
+
+        #[test]
+        fn test_rejects_imports_and_missing_exports() {
+            let imports = r#"(module
+                (import "env" "now" (func (result i64)))
+                (memory (export "memory") 1))"#;
+            let error = WasmBuiltin::new(imports.as_bytes(), "f", 1_000, 1)
+                .err()
+                .unwrap();
+            assert!(error.contains("1 imports"), "{}", error);
+
+            // Nothing of a file that is not a module is repeated
+            let error = WasmBuiltin::new(b"secret=hunter2", "f", 1_000, 1)
+                .err()
+                .unwrap();
+            assert!(!error.contains("hunter2"), "{}", error);
+
+            assert!(WasmBuiltin::new(module("(i64.const 0)").as_bytes(), "g", 1_000, 1).is_err());
+        }
+
+        #[test]
+        fn test_modules_stay_in_the_module_directory() {
+            let root = tempfile::tempdir().unwrap();
+            let modules = root.path().join("modules");
+            std::fs::create_dir(&modules).unwrap();
+            std::fs::write(modules.join("f.wat"), module("(i64.const 0)")).unwrap();
+            std::fs::write(root.path().join("outside.wat"), module("(i64.const 0)")).unwrap();
+            let config = |module: &str, dir: Option<&Path>| {
+                let mut config = BuiltinsConfig::from_toml(&format!(
+                    r#"f = {{ module = "{}", arity = 1 }}"#,
+                    module
+                ))
+                .unwrap();
+                config.dir = dir.map(Path::to_path_buf);
+                config
+            };
+
+            // Relative to the RUNE file's directory, which is the default
+            // module directory
+            assert!(config("f.wat", Some(&modules)).load(None).is_ok());
+            assert!(config("modules/f.wat", Some(root.path()))
+                .load(Some(&modules))
+                .is_ok());
+            // Without a file, relative to the module directory
+            assert!(config("f.wat", None).load(Some(&modules)).is_ok());
+            assert!(config("f.wat", None).load(None).is_err());
+
+            let outside = root.path().join("outside.wat");
+            let missing = modules.join("missing.wat");
+            for module in [
+                "../outside.wat",
+                outside.to_str().unwrap(),
+                missing.to_str().unwrap(),
+            ] {
+                let error = config(module, Some(&modules)).load(None).unwrap_err();
+                assert!(
+                    error
+                        .to_string()
+                        .contains("not a file in the module directory"),
+                    "{}",
+                    error
+                );
+            }
+
+            #[cfg(unix)]
+            {
+                let link = modules.join("link.wat");
+                std::os::unix::fs::symlink(&outside, &link).unwrap();
+                assert!(config("link.wat", Some(&modules)).load(None).is_err());
+            }
+        }
+    }
+}
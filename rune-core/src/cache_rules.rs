@@ -0,0 +1,89 @@
+//! Decisions that are never cached
+//!
+//! Some requests carry context that makes every decision one of a kind: a
+//! transaction amount has too many values for cached decisions ever to be
+//! reused, and a decision made while `otp_verified` was true must not be
+//! replayed for a later transaction. A `[cache]` section names the context
+//! keys whose presence keeps a decision out of the cache:
+//!
+//! ```toml
+//! bypass_context = ["transaction_amount", "otp_verified"]
+//! ```
+//!
+//! A request with any of these keys in its context is always evaluated. It
+//! is not answered from the cache, does not share an in-flight evaluation
+//! with an identical request, and its decision is neither cached nor
+//! reported as valid for any time (`valid_for_ms` is 0). Keys are matched
+//! after identifier canonicalization.
+
+use crate::error::{RUNEError, Result};
+use crate::request::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Rules for what the decision cache keeps
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheRules {
+    /// Context keys whose presence keeps a decision out of the cache
+    pub bypass_context: BTreeSet<String>,
+}
+
+impl CacheRules {
+    /// Parse the TOML body of a `[cache]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        let rules: Self = toml::from_str(input)
+            .map_err(|e| RUNEError::ParseError(format!("Failed to parse cache section: {}", e)))?;
+        if let Some(key) = rules
+            .bypass_context
+            .iter()
+            .find(|key| key.trim().is_empty())
+        {
+            return Err(RUNEError::ConfigError(format!(
+                "Invalid cache bypass context key {:?}",
+                key
+            )));
+        }
+        Ok(rules)
+    }
+
+    /// Check whether `request`'s decision must not be cached
+    pub fn bypasses(&self, request: &Request) -> bool {
+        !self.bypass_context.is_empty()
+            && request
+                .context
+                .keys()
+                .any(|key| self.bypass_context.contains(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, Principal, Resource, Value};
+
+    #[test]
+    fn test_bypass_on_context_keys() {
+        let rules =
+            CacheRules::from_toml(r#"bypass_context = ["transaction_amount", "otp_verified"]"#)
+                .unwrap();
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("transfer"),
+            Resource::new("account", "acme"),
+        );
+        assert!(!rules.bypasses(&request));
+        assert!(rules.bypasses(&request.with_context("transaction_amount", Value::Integer(250))));
+
+        assert!(!CacheRules::default().bypasses(
+            &Request::new(
+                Principal::user("alice"),
+                Action::new("read"),
+                Resource::file("/a"),
+            )
+            .with_context("otp_verified", Value::Bool(true))
+        ));
+        assert!(CacheRules::from_toml(r#"bypass_context = [""]"#).is_err());
+        assert!(CacheRules::from_toml("bypass = []").is_err());
+    }
+}
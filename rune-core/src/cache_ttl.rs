@@ -0,0 +1,152 @@
+//! Workload-aware decision cache TTL
+//!
+//! A fixed TTL is a compromise: too long and decisions about principals
+//! whose facts churn go stale, too short and decisions about principals
+//! nobody touches are recomputed for nothing. In adaptive mode the fact
+//! store keeps a decaying change rate for every entity a fact mentions, and
+//! a cached decision lives for half the expected time until the next change
+//! to its principal or resource, within `min_ttl_secs..=max_ttl_secs`.
+//!
+//! Rates are estimated from the fact change stream with an exponential
+//! decay: a key changing steadily every `t` seconds converges to a rate of
+//! `1 / t`, and a key that stops changing drifts back to zero over a few
+//! `half_life_secs`.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Keys tracked before quiet ones are forgotten
+const MAX_KEYS: usize = 100_000;
+
+/// Decayed counts below this are treated as no recent change
+const NEGLIGIBLE: f64 = 0.01;
+
+/// Adaptive cache TTL settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveTtlConfig {
+    /// Derive each decision's TTL from how often its facts change, instead
+    /// of using `cache_ttl_secs` for all of them
+    pub enabled: bool,
+    /// Shortest TTL, for decisions about constantly changing entities
+    pub min_ttl_secs: u64,
+    /// Longest TTL, for decisions about entities with no recent changes
+    pub max_ttl_secs: u64,
+    /// How quickly a change stops counting towards an entity's rate
+    pub half_life_secs: u64,
+}
+
+impl Default for AdaptiveTtlConfig {
+    fn default() -> Self {
+        AdaptiveTtlConfig {
+            enabled: false,
+            min_ttl_secs: 1,
+            max_ttl_secs: 600,
+            half_life_secs: 300,
+        }
+    }
+}
+
+impl AdaptiveTtlConfig {
+    /// TTL for a decision whose inputs change `rate` times per second
+    pub fn ttl(&self, rate: f64) -> Duration {
+        let min = Duration::from_secs(self.min_ttl_secs);
+        let max = Duration::from_secs(self.max_ttl_secs.max(self.min_ttl_secs));
+        if rate <= 0.0 {
+            return max;
+        }
+        Duration::try_from_secs_f64(0.5 / rate).map_or(max, |ttl| ttl.clamp(min, max))
+    }
+}
+
+/// Decaying change count of one key
+#[derive(Debug, Clone, Copy)]
+struct Rate {
+    count: f64,
+    at: Instant,
+}
+
+/// Per-key change rates, fed from the fact change stream
+#[derive(Debug)]
+pub(crate) struct ChangeRates {
+    half_life: Duration,
+    rates: DashMap<Arc<str>, Rate>,
+}
+
+impl ChangeRates {
+    pub(crate) fn new(half_life: Duration) -> Self {
+        ChangeRates {
+            half_life: half_life.max(Duration::from_secs(1)),
+            rates: DashMap::new(),
+        }
+    }
+
+    /// Fraction of a count left after `elapsed`
+    fn decay(&self, elapsed: Duration) -> f64 {
+        0.5f64.powf(elapsed.as_secs_f64() / self.half_life.as_secs_f64())
+    }
+
+    /// Count one change to `key`
+    pub(crate) fn record(&self, key: &Arc<str>, now: Instant) {
+        if self.rates.len() >= MAX_KEYS && !self.rates.contains_key(key) {
+            self.rates.retain(|_, rate| {
+                rate.count * self.decay(now.saturating_duration_since(rate.at)) >= NEGLIGIBLE
+            });
+        }
+
+        let mut rate = self.rates.entry(key.clone()).or_insert(Rate {
+            count: 0.0,
+            at: now,
+        });
+        rate.count = rate.count * self.decay(now.saturating_duration_since(rate.at)) + 1.0;
+        rate.at = now;
+    }
+
+    /// Changes per second to `key`, 0 if it has not changed lately
+    pub(crate) fn rate(&self, key: &str, now: Instant) -> f64 {
+        self.rates.get(key).map_or(0.0, |rate| {
+            let count = rate.count * self.decay(now.saturating_duration_since(rate.at));
+            if count < NEGLIGIBLE {
+                0.0
+            } else {
+                // A steady rate r settles at count = r * half_life / ln 2
+                count * std::f64::consts::LN_2 / self.half_life.as_secs_f64()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_bounds() {
+        let config = AdaptiveTtlConfig::default();
+        assert_eq!(config.ttl(0.0), Duration::from_secs(600));
+        assert_eq!(config.ttl(0.0001), Duration::from_secs(600));
+        assert_eq!(config.ttl(0.05), Duration::from_secs(10));
+        assert_eq!(config.ttl(100.0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_rates_track_and_decay() {
+        let rates = ChangeRates::new(Duration::from_secs(60));
+        let key: Arc<str> = Arc::from("alice");
+        let start = Instant::now();
+
+        // One change every two seconds for ten minutes
+        for i in 0..300 {
+            rates.record(&key, start + Duration::from_secs(i * 2));
+        }
+        let now = start + Duration::from_secs(600);
+        let rate = rates.rate("alice", now);
+        assert!((rate - 0.5).abs() < 0.05, "rate {}", rate);
+        assert_eq!(rates.rate("bob", now), 0.0);
+
+        // Quiet for an hour, the rate is forgotten
+        assert_eq!(rates.rate("alice", now + Duration::from_secs(3600)), 0.0);
+    }
+}
@@ -0,0 +1,273 @@
+//! Identifier canonicalization applied before evaluation and caching
+//!
+//! Callers rarely agree on how to spell an identifier: `Alice@Example.com`
+//! and `alice@example.com` are the same user, and `/docs/1?page=2` is the same
+//! resource as `/docs/1`. Left alone these produce divergent decisions and
+//! needless cache misses. A [`Canonicalizer`] rewrites principal and resource
+//! IDs according to the `[canonicalize]` section of a RUNE file:
+//!
+//! ```toml
+//! [[rules]]
+//! target = "principal"
+//! entity_type = "User"
+//! transforms = ["trim", "lowercase"]
+//!
+//! [[rules]]
+//! target = "resource"
+//! transforms = ["strip_query", "strip_fragment"]
+//!
+//! [aliases.User]
+//! "legacy-42" = "alice@example.com"
+//! ```
+//!
+//! Transforms run in order, then aliases are looked up on the transformed ID.
+
+use crate::error::{RUNEError, Result};
+use crate::request::Request;
+use crate::types::Entity;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Which side of a request a rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// Principal IDs
+    Principal,
+    /// Resource IDs
+    Resource,
+    /// Both principal and resource IDs
+    Any,
+}
+
+/// A single rewrite step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Lowercase the whole ID (e.g. email addresses)
+    Lowercase,
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Drop everything from the first `?`
+    StripQuery,
+    /// Drop everything from the first `#`
+    StripFragment,
+    /// Drop trailing `/` characters (a lone `/` is kept)
+    StripTrailingSlash,
+}
+
+impl Transform {
+    fn apply(self, id: &str) -> String {
+        match self {
+            Transform::Lowercase => id.to_lowercase(),
+            Transform::Trim => id.trim().to_string(),
+            Transform::StripQuery => id.split('?').next().unwrap_or(id).to_string(),
+            Transform::StripFragment => id.split('#').next().unwrap_or(id).to_string(),
+            Transform::StripTrailingSlash => {
+                let trimmed = id.trim_end_matches('/');
+                if trimmed.is_empty() && !id.is_empty() {
+                    "/".to_string()
+                } else {
+                    trimmed.to_string()
+                }
+            }
+        }
+    }
+}
+
+/// Rewrite rule for one target and, optionally, one entity type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanonicalRule {
+    /// Side of the request the rule applies to
+    pub target: Target,
+    /// Restrict the rule to an entity type (all types when absent)
+    #[serde(default)]
+    pub entity_type: Option<String>,
+    /// Transforms applied in order
+    pub transforms: Vec<Transform>,
+}
+
+/// Canonicalization settings as written in a RUNE file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanonicalizationConfig {
+    /// Rewrite rules, applied in order
+    #[serde(default)]
+    pub rules: Vec<CanonicalRule>,
+    /// Alias tables per entity type: legacy ID -> canonical ID
+    #[serde(default)]
+    pub aliases: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl CanonicalizationConfig {
+    /// Parse the TOML body of a `[canonicalize]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        toml::from_str(input).map_err(|e| {
+            RUNEError::ParseError(format!("Failed to parse canonicalize section: {}", e))
+        })
+    }
+
+    /// Check whether the config rewrites nothing
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.aliases.is_empty()
+    }
+}
+
+/// Applies a [`CanonicalizationConfig`] to requests
+#[derive(Debug, Clone, Default)]
+pub struct Canonicalizer {
+    config: CanonicalizationConfig,
+}
+
+impl Canonicalizer {
+    /// Create a canonicalizer from its configuration
+    pub fn new(config: CanonicalizationConfig) -> Self {
+        Canonicalizer { config }
+    }
+
+    /// Configuration in use
+    pub fn config(&self) -> &CanonicalizationConfig {
+        &self.config
+    }
+
+    /// Check whether requests pass through unchanged
+    pub fn is_empty(&self) -> bool {
+        self.config.is_empty()
+    }
+
+    /// Canonical form of an ID for the given side and entity type
+    pub fn canonical_id(&self, target: Target, entity_type: &str, id: &str) -> String {
+        let mut id = id.to_string();
+        for rule in &self.config.rules {
+            let target_matches = rule.target == Target::Any || rule.target == target;
+            let type_matches = rule.entity_type.as_deref().is_none_or(|t| t == entity_type);
+            if target_matches && type_matches {
+                for transform in &rule.transforms {
+                    id = transform.apply(&id);
+                }
+            }
+        }
+
+        self.config
+            .aliases
+            .get(entity_type)
+            .and_then(|aliases| aliases.get(&id))
+            .cloned()
+            .unwrap_or(id)
+    }
+
+    /// Rewrite the principal and resource of a request
+    ///
+    /// Returns `None` when the request is already canonical, so callers can
+    /// keep using the original without cloning it.
+    pub fn canonicalize(&self, request: &Request) -> Option<Request> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let principal = self.canonical_entity(Target::Principal, &request.principal.entity);
+        let resource = self.canonical_entity(Target::Resource, &request.resource.entity);
+        if principal.is_none() && resource.is_none() {
+            return None;
+        }
+
+        let mut canonical = request.clone();
+        if let Some(entity) = principal {
+            canonical.principal.entity = entity;
+        }
+        if let Some(entity) = resource {
+            canonical.resource.entity = entity;
+        }
+        Some(canonical)
+    }
+
+    fn canonical_entity(&self, target: Target, entity: &Entity) -> Option<Entity> {
+        let id = self.canonical_id(target, &entity.entity_type, &entity.id);
+        if id == *entity.id {
+            return None;
+        }
+        let mut entity = entity.clone();
+        entity.id = Arc::from(id.into_boxed_str());
+        Some(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, Principal, Resource};
+
+    fn canonicalizer() -> Canonicalizer {
+        Canonicalizer::new(
+            CanonicalizationConfig::from_toml(
+                r#"
+[[rules]]
+target = "principal"
+entity_type = "User"
+transforms = ["trim", "lowercase"]
+
+[[rules]]
+target = "resource"
+transforms = ["strip_query", "strip_fragment", "strip_trailing_slash"]
+
+[aliases.User]
+"legacy-42" = "alice@example.com"
+"#,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(
+            Transform::Lowercase.apply("Alice@Example.COM"),
+            "alice@example.com"
+        );
+        assert_eq!(Transform::StripQuery.apply("/docs/1?page=2"), "/docs/1");
+        assert_eq!(Transform::StripFragment.apply("/docs/1#top"), "/docs/1");
+        assert_eq!(Transform::StripTrailingSlash.apply("/docs/"), "/docs");
+        assert_eq!(Transform::StripTrailingSlash.apply("/"), "/");
+    }
+
+    #[test]
+    fn test_canonicalize_request() {
+        let c = canonicalizer();
+        let request = Request::new(
+            Principal::user(" Alice@Example.com "),
+            Action::new("read"),
+            Resource::new("Document", "/docs/1/?page=2#intro"),
+        );
+
+        let canonical = c.canonicalize(&request).unwrap();
+        assert_eq!(&*canonical.principal.entity.id, "alice@example.com");
+        assert_eq!(&*canonical.resource.entity.id, "/docs/1");
+        assert_eq!(canonical.request_id, request.request_id);
+
+        // Already canonical requests are left alone
+        assert!(c.canonicalize(&canonical).is_none());
+    }
+
+    #[test]
+    fn test_aliases_apply_after_transforms() {
+        let c = canonicalizer();
+        assert_eq!(
+            c.canonical_id(Target::Principal, "User", "LEGACY-42"),
+            "alice@example.com"
+        );
+        // Rules scoped to User don't touch other types
+        assert_eq!(c.canonical_id(Target::Principal, "Agent", "Bot"), "Bot");
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(CanonicalizationConfig::from_toml("rules = [{ target = \"nowhere\" }]").is_err());
+        assert!(Canonicalizer::default()
+            .canonicalize(&Request::new(
+                Principal::user("x"),
+                Action::new("read"),
+                Resource::file("/x")
+            ))
+            .is_none());
+    }
+}
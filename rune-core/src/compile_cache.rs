@@ -0,0 +1,166 @@
+//! On-disk cache of parsed rules
+//!
+//! Parsing dominates loading a large RUNE file: tens of thousands of rules
+//! take seconds to parse and check, where installing them takes
+//! milliseconds. A [`CompileCache`] keeps what parsing a file's `[rules]`
+//! section produces, the rules and the warnings found in them, in a
+//! directory, keyed by the SHA-256 of the whole file. Starting again with
+//! an unchanged file reads them back instead of parsing them.
+//!
+//! ```no_run
+//! use rune_core::CompileCache;
+//!
+//! let cache = CompileCache::new("/var/cache/rune");
+//! let source = std::fs::read_to_string("policy.rune")?;
+//! let config = cache.parse(&source)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The key also covers the rune-core version and [`CACHE_FORMAT_VERSION`],
+//! so an upgrade never reads another release's entries. Entries are CBOR
+//! after an 8-byte `RUNEPROG` marker. One that cannot be read is a miss and
+//! is written again, and a cache that cannot be written only costs the next
+//! start its speed, so neither fails a load. Files that do not parse are
+//! not cached.
+//!
+//! Stratification is not cached: it is one pass over the rules, done once
+//! when they are loaded (see [`crate::datalog::DatalogEngine`]). Policies
+//! are compiled by Cedar on every load.
+
+use crate::datalog::{DiagnosticBag, Rule};
+use crate::error::Result;
+use crate::parser::RUNEConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Version of the entries [`CompileCache`] writes, part of every key
+pub const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// Leading bytes of a cache entry
+const CACHE_MAGIC: &[u8; 8] = b"RUNEPROG";
+
+/// What parsing a `[rules]` section produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ParsedRules {
+    /// Rules in source order
+    pub rules: Vec<Rule>,
+    /// Warnings about the rules, with spans into the file
+    pub warnings: DiagnosticBag,
+}
+
+/// Directory of parsed rules, keyed by the file they were parsed from
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    dir: PathBuf,
+}
+
+impl CompileCache {
+    /// Cache entries in `dir`, created on the first write
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CompileCache { dir: dir.into() }
+    }
+
+    /// Directory the entries are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Parse a RUNE file as [`crate::parse_rune_file`] does, reading its
+    /// rules from the cache when `input` was parsed before
+    pub fn parse(&self, input: &str) -> Result<RUNEConfig> {
+        crate::parser::parse_rune_file_with(input, Some(self))
+    }
+
+    /// Rules parsed earlier from exactly `input`
+    pub(crate) fn load(&self, input: &str) -> Option<ParsedRules> {
+        let path = self.entry(input);
+        let bytes = std::fs::read(&path).ok()?;
+        let parsed = bytes
+            .strip_prefix(CACHE_MAGIC)
+            .and_then(|document| ciborium::from_reader(document).ok());
+        match &parsed {
+            Some(_) => debug!("Read parsed rules from {}", path.display()),
+            None => warn!("Ignoring unreadable cache entry {}", path.display()),
+        }
+        parsed
+    }
+
+    /// Keep the rules parsed from `input`
+    ///
+    /// The entry is written to a temporary file and renamed into place, so
+    /// a concurrent load sees all of it or none.
+    pub(crate) fn store(&self, input: &str, parsed: &ParsedRules) {
+        let path = self.entry(input);
+        let mut bytes = CACHE_MAGIC.to_vec();
+        let written = ciborium::into_writer(parsed, &mut bytes)
+            .map_err(|e| std::io::Error::other(e.to_string()))
+            .and_then(|()| std::fs::create_dir_all(&self.dir))
+            .and_then(|()| {
+                let staging = path.with_extension(format!("tmp{}", std::process::id()));
+                std::fs::write(&staging, &bytes)?;
+                std::fs::rename(&staging, &path)
+            });
+        if let Err(e) = written {
+            warn!("Failed to write cache entry {}: {}", path.display(), e);
+        }
+    }
+
+    /// Entry for `input`
+    fn entry(&self, input: &str) -> PathBuf {
+        let mut digest = Sha256::new();
+        digest.update(CACHE_FORMAT_VERSION.to_le_bytes());
+        digest.update(env!("CARGO_PKG_VERSION"));
+        digest.update([0]);
+        digest.update(input);
+        self.dir
+            .join(format!("{}.prog", hex::encode(digest.finalize())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"version = "rune/1.0"
+
+[rules]
+admin(U) :- user(U), role(U, "admin").
+blocked(U) :- user(U), not admn(U).
+"#;
+
+    #[test]
+    fn test_reads_back_what_it_parsed() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CompileCache::new(dir.path().join("cache"));
+        assert!(cache.load(SOURCE).is_none());
+
+        let parsed = cache.parse(SOURCE).unwrap();
+        let cached = cache.load(SOURCE).unwrap();
+        assert_eq!(cached.rules, parsed.rules);
+        assert_eq!(cache.parse(SOURCE).unwrap().rules, parsed.rules);
+        // The misspelled `admn` is still reported, from the cache
+        assert_eq!(cached.warnings.diagnostics().len(), 1);
+        assert_eq!(
+            cache.parse(SOURCE).unwrap().warnings.to_string(),
+            crate::parse_rune_file(SOURCE).unwrap().warnings.to_string()
+        );
+
+        // Any change to the file is a different entry
+        let changed = SOURCE.replace("admin\")", "root\")");
+        assert!(cache.load(&changed).is_none());
+    }
+
+    #[test]
+    fn test_unreadable_entries_are_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CompileCache::new(dir.path());
+        std::fs::write(cache.entry(SOURCE), b"RUNEPROG\xff").unwrap();
+        assert!(cache.load(SOURCE).is_none());
+
+        // Parsing rewrites it
+        let parsed = cache.parse(SOURCE).unwrap();
+        assert_eq!(cache.load(SOURCE).unwrap().rules, parsed.rules);
+    }
+}
@@ -0,0 +1,540 @@
+//! Static detection of permit/forbid conflicts
+//!
+//! A forbid always wins, so a permit whose principal, action and resource
+//! scope overlaps a forbid's grants less than it reads as granting. That
+//! is often intended (a blanket grant with a carve-out), but a forbid
+//! that covers a permit entirely, with no condition, leaves the permit
+//! dead, and that is almost always a mistake. [`detect`] compares every
+//! active permit with every active forbid without evaluating anything:
+//!
+//! - [`ConflictKind::Shadowed`]: the forbid has no `when`/`unless` clause
+//!   and its scope contains the permit's, so the permit never takes effect
+//! - [`ConflictKind::Overlap`]: the scopes may overlap, so requests in the
+//!   overlap are forbidden whenever the forbid's conditions hold
+//!
+//! Entity hierarchies are only known per request, so scopes are disjoint
+//! only when their entity types or `==` entities differ: `principal in
+//! Group::"admins"` overlaps `principal == User::"alice"`, since alice may
+//! be an admin. Actions have no parents, so `action in [...]` is a set of
+//! actions.
+//!
+//! Datalog rules are paired by name: a rule deriving `allow*` or `permit*`
+//! conflicts with one deriving the `deny*` or `forbid*` predicate with the
+//! same suffix and arity when their heads unify, as `allow_read(U, "q3")`
+//! and `deny_read("bob", R)` do.
+//!
+//! [`diagnostics`] turns conflicts into warnings (shadowed permits) and
+//! infos (overlaps), for `rune validate --analyze`.
+
+use crate::datalog::diagnostics::{Diagnostic, DiagnosticBag};
+use crate::datalog::types::{Atom, Rule, Term};
+use crate::export::rule_source;
+use crate::policy::{policy_id, PolicySet};
+use cedar_policy::{
+    ActionConstraint, Effect, EntityTypeName, EntityUid, Policy, PrincipalConstraint,
+    ResourceConstraint,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Codes for the diagnostics conflict detection reports
+pub mod codes {
+    /// A forbid without conditions covers a permit entirely
+    pub const SHADOWED_PERMIT: &str = "W0301";
+    /// A permit and a forbid policy may match the same requests
+    pub const OVERLAPPING_POLICIES: &str = "I0302";
+    /// An allow rule and a deny rule may derive for the same arguments
+    pub const OVERLAPPING_RULES: &str = "I0303";
+}
+
+/// How a permit and a forbid conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictKind {
+    /// The forbid always applies where the permit does
+    Shadowed,
+    /// The two may apply to the same requests
+    Overlap,
+}
+
+/// Where the conflicting pair is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictSource {
+    /// Two Cedar policies
+    Policies,
+    /// Two Datalog rules
+    Rules,
+}
+
+/// A permit and a forbid that may apply to the same requests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// How they conflict
+    pub kind: ConflictKind,
+    /// Whether they are policies or rules
+    pub source: ConflictSource,
+    /// Policy ID, or the rule's `@id` or head
+    pub permit: String,
+    /// Policy ID, or the rule's `@id` or head
+    pub forbid: String,
+    /// Requests in the overlap, e.g. `principal == User::"alice", action,
+    /// resource in Folder::"q3"`, or the unified rule head
+    pub overlap: String,
+}
+
+impl Conflict {
+    /// The conflict as a diagnostic
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match (self.kind, self.source) {
+            (ConflictKind::Shadowed, _) => Diagnostic::warning(format!(
+                "Permit `{}` never takes effect: forbid `{}` always applies to its scope",
+                self.permit, self.forbid
+            ))
+            .with_code(codes::SHADOWED_PERMIT)
+            .with_help("narrow the forbid's scope or give it a `when` condition"),
+            (ConflictKind::Overlap, ConflictSource::Policies) => Diagnostic::info(format!(
+                "Permit `{}` and forbid `{}` overlap on {}",
+                self.permit, self.forbid, self.overlap
+            ))
+            .with_code(codes::OVERLAPPING_POLICIES)
+            .with_help("requests there are forbidden whenever the forbid's conditions hold"),
+            (ConflictKind::Overlap, ConflictSource::Rules) => Diagnostic::info(format!(
+                "Rules `{}` and `{}` can both derive {}",
+                self.permit, self.forbid, self.overlap
+            ))
+            .with_code(codes::OVERLAPPING_RULES),
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_diagnostic().message)
+    }
+}
+
+/// Conflicts between the active policies and between the `rules`, shadowed
+/// permits first
+pub fn detect(policies: &PolicySet, rules: &[Rule]) -> Vec<Conflict> {
+    let (permits, forbids): (Vec<&Policy>, Vec<&Policy>) = policies
+        .active_policies()
+        .partition(|policy| policy.effect() == Effect::Permit);
+
+    let mut conflicts = Vec::new();
+    for permit in &permits {
+        for forbid in &forbids {
+            conflicts.extend(policy_conflict(permit, forbid));
+        }
+    }
+    conflicts.extend(rule_conflicts(rules));
+    conflicts.sort_by(|a, b| {
+        (
+            a.kind != ConflictKind::Shadowed,
+            a.source as u8,
+            &a.permit,
+            &a.forbid,
+        )
+            .cmp(&(
+                b.kind != ConflictKind::Shadowed,
+                b.source as u8,
+                &b.permit,
+                &b.forbid,
+            ))
+    });
+    conflicts
+}
+
+/// The conflicts as diagnostics
+pub fn diagnostics(conflicts: &[Conflict]) -> DiagnosticBag {
+    let mut bag = DiagnosticBag::new();
+    bag.extend(conflicts.iter().map(Conflict::to_diagnostic));
+    bag
+}
+
+/// Principal or resource scope, in the shape both constraints share
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EntityScope {
+    Any,
+    Eq(EntityUid),
+    In(EntityUid),
+    Is(EntityTypeName),
+    IsIn(EntityTypeName, EntityUid),
+}
+
+impl EntityScope {
+    pub(crate) fn principal(constraint: PrincipalConstraint) -> Self {
+        match constraint {
+            PrincipalConstraint::Any => EntityScope::Any,
+            PrincipalConstraint::Eq(uid) => EntityScope::Eq(uid),
+            PrincipalConstraint::In(uid) => EntityScope::In(uid),
+            PrincipalConstraint::Is(name) => EntityScope::Is(name),
+            PrincipalConstraint::IsIn(name, uid) => EntityScope::IsIn(name, uid),
+        }
+    }
+
+    pub(crate) fn resource(constraint: ResourceConstraint) -> Self {
+        match constraint {
+            ResourceConstraint::Any => EntityScope::Any,
+            ResourceConstraint::Eq(uid) => EntityScope::Eq(uid),
+            ResourceConstraint::In(uid) => EntityScope::In(uid),
+            ResourceConstraint::Is(name) => EntityScope::Is(name),
+            ResourceConstraint::IsIn(name, uid) => EntityScope::IsIn(name, uid),
+        }
+    }
+
+    /// Entity type every entity in scope has, if the scope fixes one
+    pub(crate) fn entity_type(&self) -> Option<EntityTypeName> {
+        match self {
+            EntityScope::Eq(uid) => Some(uid.type_name().clone()),
+            EntityScope::Is(name) | EntityScope::IsIn(name, _) => Some(name.clone()),
+            EntityScope::Any | EntityScope::In(_) => None,
+        }
+    }
+
+    /// Ancestor (or self) every entity in scope has, if any
+    fn within(&self) -> Option<&EntityUid> {
+        match self {
+            EntityScope::Eq(uid) | EntityScope::In(uid) | EntityScope::IsIn(_, uid) => Some(uid),
+            EntityScope::Any | EntityScope::Is(_) => None,
+        }
+    }
+
+    /// Whether some entity may be in both scopes
+    fn overlaps(&self, other: &EntityScope) -> bool {
+        if let (Some(a), Some(b)) = (self.entity_type(), other.entity_type()) {
+            if a != b {
+                return false;
+            }
+        }
+        match (self, other) {
+            (EntityScope::Eq(a), EntityScope::Eq(b)) => a == b,
+            _ => true,
+        }
+    }
+
+    /// Whether every entity in `other` is in this scope, whatever the
+    /// hierarchy
+    fn covers(&self, other: &EntityScope) -> bool {
+        let typed = match self.entity_type() {
+            Some(name) => other.entity_type() == Some(name),
+            None => true,
+        };
+        let within = match self {
+            EntityScope::Any | EntityScope::Is(_) => true,
+            EntityScope::Eq(uid) => *other == EntityScope::Eq(uid.clone()),
+            // `in` is reflexive, and transitive through `other`'s ancestor
+            EntityScope::In(uid) | EntityScope::IsIn(_, uid) => other.within() == Some(uid),
+        };
+        typed && within
+    }
+
+    /// The narrower of two overlapping scopes
+    fn narrower<'a>(&'a self, other: &'a EntityScope) -> &'a EntityScope {
+        let rank = |scope: &EntityScope| match scope {
+            EntityScope::Eq(_) => 0,
+            EntityScope::IsIn(..) => 1,
+            EntityScope::In(_) => 2,
+            EntityScope::Is(_) => 3,
+            EntityScope::Any => 4,
+        };
+        if rank(other) < rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn render(&self, var: &str) -> String {
+        match self {
+            EntityScope::Any => var.to_string(),
+            EntityScope::Eq(uid) => format!("{} == {}", var, uid),
+            EntityScope::In(uid) => format!("{} in {}", var, uid),
+            EntityScope::Is(name) => format!("{} is {}", var, name),
+            EntityScope::IsIn(name, uid) => format!("{} is {} in {}", var, name, uid),
+        }
+    }
+}
+
+/// Actions a scope allows, `None` for any
+pub(crate) fn actions(constraint: ActionConstraint) -> Option<Vec<EntityUid>> {
+    match constraint {
+        ActionConstraint::Any => None,
+        ActionConstraint::Eq(uid) => Some(vec![uid]),
+        ActionConstraint::In(uids) => Some(uids),
+    }
+}
+
+fn render_actions(actions: &Option<Vec<EntityUid>>) -> String {
+    match actions.as_deref() {
+        None => "action".to_string(),
+        Some([uid]) => format!("action == {}", uid),
+        Some(uids) => {
+            let uids: Vec<String> = uids.iter().map(ToString::to_string).collect();
+            format!("action in [{}]", uids.join(", "))
+        }
+    }
+}
+
+/// Whether a policy has `when` or `unless` clauses
+fn has_conditions(policy: &Policy) -> bool {
+    // Without a JSON form, assume the worst for shadowing
+    policy
+        .to_json()
+        .ok()
+        .and_then(|json| Some(!json.get("conditions")?.as_array()?.is_empty()))
+        .unwrap_or(true)
+}
+
+fn policy_conflict(permit: &Policy, forbid: &Policy) -> Option<Conflict> {
+    let principals = (
+        EntityScope::principal(permit.principal_constraint()),
+        EntityScope::principal(forbid.principal_constraint()),
+    );
+    let resources = (
+        EntityScope::resource(permit.resource_constraint()),
+        EntityScope::resource(forbid.resource_constraint()),
+    );
+    let (permit_actions, forbid_actions) = (
+        actions(permit.action_constraint()),
+        actions(forbid.action_constraint()),
+    );
+
+    let action_overlap = match (&permit_actions, &forbid_actions) {
+        (None, other) | (other, None) => other.clone(),
+        (Some(a), Some(b)) => {
+            let both: Vec<EntityUid> = a.iter().filter(|uid| b.contains(uid)).cloned().collect();
+            if both.is_empty() {
+                return None;
+            }
+            Some(both)
+        }
+    };
+    if !principals.0.overlaps(&principals.1) || !resources.0.overlaps(&resources.1) {
+        return None;
+    }
+
+    let actions_covered = match (&forbid_actions, &permit_actions) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(forbid), Some(permit)) => permit.iter().all(|uid| forbid.contains(uid)),
+    };
+    let shadowed = actions_covered
+        && principals.1.covers(&principals.0)
+        && resources.1.covers(&resources.0)
+        && !has_conditions(forbid);
+
+    Some(Conflict {
+        kind: if shadowed {
+            ConflictKind::Shadowed
+        } else {
+            ConflictKind::Overlap
+        },
+        source: ConflictSource::Policies,
+        permit: policy_id(permit),
+        forbid: policy_id(forbid),
+        overlap: format!(
+            "{}, {}, {}",
+            principals.0.narrower(&principals.1).render("principal"),
+            render_actions(&action_overlap),
+            resources.0.narrower(&resources.1).render("resource")
+        ),
+    })
+}
+
+/// The part of a predicate after a granting prefix
+fn granted(predicate: &str) -> Option<&str> {
+    ["allow", "permit"]
+        .into_iter()
+        .find_map(|prefix| predicate.strip_prefix(prefix))
+}
+
+/// The part of a predicate after a denying prefix
+fn denied(predicate: &str) -> Option<&str> {
+    ["deny", "forbid"]
+        .into_iter()
+        .find_map(|prefix| predicate.strip_prefix(prefix))
+}
+
+/// A rule's `@id`, or its head
+fn rule_name(rule: &Rule) -> String {
+    match rule.annotations.get("id") {
+        Some(id) => id.clone(),
+        None => head_source(&rule.head),
+    }
+}
+
+fn head_source(head: &Atom) -> String {
+    let bare = Rule::new(head.clone(), Vec::new());
+    rule_source(&bare)
+        .map(|source| source.trim_end_matches('.').to_string())
+        .unwrap_or_else(|_| head.to_string())
+}
+
+/// Unify two heads position by position, keeping the more specific term;
+/// variables are renamed apart, so a repeated variable is not tracked
+fn unify_heads(a: &Atom, b: &Atom) -> Option<Atom> {
+    let terms = a
+        .terms
+        .iter()
+        .zip(&b.terms)
+        .map(|pair| match pair {
+            (Term::Constant(x), Term::Constant(y)) => (x == y).then(|| Term::Constant(x.clone())),
+            (Term::Constant(x), Term::Variable(_)) | (Term::Variable(_), Term::Constant(x)) => {
+                Some(Term::Constant(x.clone()))
+            }
+            (Term::Variable(x), Term::Variable(_)) => Some(Term::Variable(x.clone())),
+        })
+        .collect::<Option<Vec<Term>>>()?;
+    Some(Atom::new(a.predicate.to_string(), terms))
+}
+
+fn rule_conflicts(rules: &[Rule]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for grant in rules {
+        let Some(suffix) = granted(&grant.head.predicate) else {
+            continue;
+        };
+        for deny in rules {
+            if denied(&deny.head.predicate) != Some(suffix)
+                || deny.head.terms.len() != grant.head.terms.len()
+            {
+                continue;
+            }
+            if let Some(head) = unify_heads(&grant.head, &deny.head) {
+                conflicts.push(Conflict {
+                    kind: ConflictKind::Overlap,
+                    source: ConflictSource::Rules,
+                    permit: rule_name(grant),
+                    forbid: rule_name(deny),
+                    overlap: head_source(&head),
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rules;
+
+    fn policies(sources: &[(&str, &str)]) -> PolicySet {
+        let mut set = PolicySet::new();
+        for (id, source) in sources {
+            set.add_policy(id, source).unwrap();
+        }
+        set
+    }
+
+    fn summary(conflicts: &[Conflict]) -> Vec<(ConflictKind, &str, &str)> {
+        conflicts
+            .iter()
+            .map(|c| (c.kind, c.permit.as_str(), c.forbid.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_policy_conflicts() {
+        let set = policies(&[
+            (
+                "readers",
+                r#"permit(principal in Group::"staff", action == Action::"read", resource);"#,
+            ),
+            (
+                "alice-writes",
+                r#"permit(principal == User::"alice", action in [Action::"write", Action::"read"], resource is File);"#,
+            ),
+            (
+                "no-bob",
+                r#"forbid(principal == User::"bob", action, resource);"#,
+            ),
+            (
+                "no-writes",
+                r#"forbid(principal, action == Action::"write", resource is File);"#,
+            ),
+            (
+                "no-deletes",
+                r#"forbid(principal, action == Action::"delete", resource);"#,
+            ),
+            (
+                "quarantine",
+                r#"forbid(principal, action, resource in Folder::"quarantine") when { resource.infected };"#,
+            ),
+        ]);
+        let conflicts = detect(&set, &[]);
+        assert_eq!(
+            summary(&conflicts),
+            [
+                (ConflictKind::Overlap, "alice-writes", "no-writes"),
+                (ConflictKind::Overlap, "alice-writes", "quarantine"),
+                (ConflictKind::Overlap, "readers", "no-bob"),
+                (ConflictKind::Overlap, "readers", "quarantine"),
+            ]
+        );
+        // Bob may be on the staff; alice is not bob
+        assert_eq!(
+            conflicts[2].overlap,
+            r#"principal == User::"bob", action == Action::"read", resource"#
+        );
+        assert_eq!(
+            conflicts[0].overlap,
+            r#"principal == User::"alice", action == Action::"write", resource is File"#
+        );
+
+        // Unconditional and covering the whole permit
+        let set = policies(&[
+            (
+                "alice-reads",
+                r#"permit(principal == User::"alice", action == Action::"read", resource in Folder::"q3");"#,
+            ),
+            (
+                "frozen",
+                r#"forbid(principal, action in [Action::"read", Action::"write"], resource in Folder::"q3");"#,
+            ),
+        ]);
+        let conflicts = detect(&set, &[]);
+        assert_eq!(
+            summary(&conflicts),
+            [(ConflictKind::Shadowed, "alice-reads", "frozen")]
+        );
+        let bag = diagnostics(&conflicts);
+        assert_eq!(bag.warning_count(), 1);
+        assert_eq!(
+            bag.diagnostics()[0].code.as_deref(),
+            Some(codes::SHADOWED_PERMIT)
+        );
+    }
+
+    #[test]
+    fn test_rule_conflicts() {
+        let rules = parse_rules(
+            r#"
+allow_read(U, R) :- member(U, "staff"), document(R).
+@id("bob-blocked")
+deny_read("bob", R) :- document(R).
+deny_read(U, "payroll") :- contractor(U).
+deny_write(U, R) :- contractor(U), document(R).
+allow_read("carol", "q3").
+"#,
+        )
+        .unwrap();
+        let conflicts = detect(&PolicySet::new(), &rules);
+        let pairs: Vec<(&str, &str, &str)> = conflicts
+            .iter()
+            .map(|c| (c.permit.as_str(), c.forbid.as_str(), c.overlap.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("allow_read(U, R)", "bob-blocked", "allow_read(\"bob\", R)"),
+                (
+                    "allow_read(U, R)",
+                    "deny_read(U, \"payroll\")",
+                    "allow_read(U, \"payroll\")"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Conformance scenarios shared by every RUNE surface
+//!
+//! The scenarios under `conformance/scenarios/` describe a configuration,
+//! base facts, one request and the answer it must get. The same files are
+//! run against this crate, the HTTP server and the Python binding, so the
+//! surfaces cannot drift apart without a test failing. This module holds the
+//! scenario schema and the rune-core runner; other surfaces deserialize the
+//! same files and report what they answered as an [`Outcome`], which
+//! [`Scenario::check`] compares against the expectation.
+//!
+//! ```yaml
+//! name: a derived role grant permits a read
+//! config: |
+//!   version = "rune/1.0"
+//!
+//!   [rules]
+//!   can_read(U) :- has_role(U, "reader").
+//!
+//!   [policies]
+//!   permit(principal, action == Action::"read", resource);
+//! facts:
+//!   - { predicate: has_role, args: [alice, reader] }
+//! session:
+//!   - { predicate: mfa_level, args: [2] }
+//! request: { principal: "User:alice", action: read, resource: "File:/q3" }
+//! expect:
+//!   decision: permit
+//!   reasons: ["Permitted by 2 rules"]
+//! ```
+//!
+//! Principals and resources use the `type:id` form of [`Principal::parse`]
+//! and [`Resource::parse`]. Decisions compare case-insensitively, since the
+//! HTTP API spells them in upper case. `reasons` lists strings that must all
+//! appear among the reasons a surface gives; an empty list checks nothing.
+
+use crate::decision::Decision;
+use crate::engine::RUNEEngine;
+use crate::error::Result;
+use crate::facts::Fact;
+use crate::parser::parse_rune_file;
+use crate::request::{Request, RequestBuilder};
+use crate::sessions::SessionAttribute;
+use crate::types::{Action, Principal, Resource, Value};
+use serde::{Deserialize, Serialize};
+
+/// A base fact loaded before the request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioFact {
+    /// Fact predicate
+    pub predicate: String,
+    /// Fact arguments
+    #[serde(default)]
+    pub args: Vec<Value>,
+}
+
+/// The request a scenario sends
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioRequest {
+    /// Principal in `type:id` form
+    pub principal: String,
+    /// Action name
+    pub action: String,
+    /// Resource in `type:id` form
+    pub resource: String,
+}
+
+impl ScenarioRequest {
+    /// Build the core request
+    pub fn to_request(&self) -> Result<Request> {
+        RequestBuilder::new()
+            .principal(Principal::parse(&self.principal))
+            .action(Action::new(&self.action))
+            .resource(Resource::parse(&self.resource))
+            .build()
+    }
+}
+
+/// What a surface must answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// `permit`, `deny` or `forbid`
+    pub decision: String,
+    /// Reasons that must all be given
+    #[serde(default)]
+    pub reasons: Vec<String>,
+}
+
+/// One conformance scenario
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Short description, used in failure messages
+    pub name: String,
+    /// RUNE file contents
+    #[serde(default)]
+    pub config: String,
+    /// Base facts added after the configuration is loaded
+    #[serde(default)]
+    pub facts: Vec<ScenarioFact>,
+    /// Per-login attributes, installed through a session for the principal
+    #[serde(default)]
+    pub session: Vec<SessionAttribute>,
+    /// Request to authorize
+    pub request: ScenarioRequest,
+    /// Expected answer
+    pub expect: Expectation,
+}
+
+/// What a surface answered
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outcome {
+    /// Decision, in any case
+    pub decision: String,
+    /// Reasons given for it
+    pub reasons: Vec<String>,
+}
+
+impl Outcome {
+    /// Outcome of a core decision
+    pub fn new(decision: Decision, explanation: String) -> Self {
+        let decision = match decision {
+            Decision::Permit => "permit",
+            Decision::Deny => "deny",
+            Decision::Forbid => "forbid",
+        };
+        Outcome {
+            decision: decision.to_string(),
+            reasons: vec![explanation],
+        }
+    }
+}
+
+impl Scenario {
+    /// Engine with the scenario's configuration and facts loaded
+    ///
+    /// The session is left to the caller, so other surfaces can open it
+    /// through their own API.
+    pub fn engine(&self) -> Result<RUNEEngine> {
+        let engine = RUNEEngine::new();
+        engine.apply_config(parse_rune_file(&self.config)?)?;
+        engine.add_facts_bulk(
+            self.facts
+                .iter()
+                .map(|fact| Fact::new(fact.predicate.as_str(), fact.args.clone()))
+                .collect(),
+        );
+        Ok(engine)
+    }
+
+    /// Run the scenario against rune-core
+    pub fn run(&self) -> Result<Outcome> {
+        let engine = self.engine()?;
+        let request = self.request.to_request()?;
+        if !self.session.is_empty() {
+            engine.open_session(&request.principal, &self.session, None)?;
+        }
+        let result = engine.authorize(&request)?;
+        Ok(Outcome::new(result.decision, result.explanation))
+    }
+
+    /// Compare a surface's answer with the expectation
+    pub fn check(&self, outcome: &Outcome) -> std::result::Result<(), String> {
+        if !outcome.decision.eq_ignore_ascii_case(&self.expect.decision) {
+            return Err(format!(
+                "{}: expected decision {}, got {}",
+                self.name, self.expect.decision, outcome.decision
+            ));
+        }
+        if let Some(missing) = self
+            .expect
+            .reasons
+            .iter()
+            .find(|reason| !outcome.reasons.contains(reason))
+        {
+            return Err(format!(
+                "{}: expected reason {:?} among {:?}",
+                self.name, missing, outcome.reasons
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        Scenario {
+            name: "admins".to_string(),
+            config: "version = \"rune/1.0\"\n\n[rules]\nallowed(U) :- admin(U).\n\n[policies]\npermit(principal, action, resource);\n".to_string(),
+            facts: vec![ScenarioFact {
+                predicate: "admin".to_string(),
+                args: vec![Value::string("alice")],
+            }],
+            session: Vec::new(),
+            request: ScenarioRequest {
+                principal: "user:alice".to_string(),
+                action: "read".to_string(),
+                resource: "file:/doc".to_string(),
+            },
+            expect: Expectation {
+                decision: "permit".to_string(),
+                reasons: vec!["Permitted by 2 rules".to_string()],
+            },
+        }
+    }
+
+    #[test]
+    fn test_run_and_check() {
+        let scenario = scenario();
+        let outcome = scenario.run().unwrap();
+        assert_eq!(scenario.check(&outcome), Ok(()));
+
+        let shouted = Outcome {
+            decision: "PERMIT".to_string(),
+            reasons: outcome.reasons.clone(),
+        };
+        assert!(scenario.check(&shouted).is_ok());
+
+        let denied = Outcome::new(Decision::Deny, "No matching permit rules".to_string());
+        assert!(scenario
+            .check(&denied)
+            .unwrap_err()
+            .contains("expected decision"));
+
+        let unexplained = Outcome {
+            decision: "permit".to_string(),
+            reasons: Vec::new(),
+        };
+        assert!(scenario.check(&unexplained).is_err());
+    }
+}
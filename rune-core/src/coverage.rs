@@ -0,0 +1,235 @@
+//! Policy coverage over a corpus of recorded requests
+//!
+//! [`RUNEEngine::coverage_report`](crate::RUNEEngine::coverage_report) runs
+//! each request of a corpus against the active configuration and reports
+//! what the corpus leaves untested:
+//!
+//! - policies no request satisfied, found with
+//!   [`matching_policies`](crate::RUNEEngine::matching_policies) so that a
+//!   permit behind a forbid still counts as exercised;
+//! - Datalog rules that derived nothing about any request's principal or
+//!   resource, matched the way near misses are (see
+//!   [`crate::datalog::near_miss`]); scoped rules are left out, since their
+//!   matches depend on the partition they are evaluated in;
+//! - requests no policy matched at all, which only the default deny answers.
+//!
+//! A corpus is JSON lines in the shape of `POST /v1/authorize` bodies, so
+//! requests logged by a server can be replayed as they are:
+//!
+//! ```text
+//! {"principal": "User:alice", "action": "read", "resource": "Document:q3"}
+//! {"principal": "User:bob", "principal_attributes": {"department": "sales"}, "action": "write", "resource": "Document:q3"}
+//! ```
+
+use crate::error::{RUNEError, Result};
+use crate::policy::PolicyEffect;
+use crate::request::{Request, RequestBuilder};
+use crate::types::{Action, Principal, Resource, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// One line of a request corpus
+///
+/// Fields of an authorization request body that do not affect which
+/// policies match, such as `explain`, are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedRequest {
+    /// Principal, as `Type:id`
+    pub principal: String,
+    /// Attributes of the principal
+    #[serde(default)]
+    pub principal_attributes: BTreeMap<String, Value>,
+    /// Action
+    pub action: String,
+    /// Resource, as `Type:id`
+    pub resource: String,
+    /// Request context
+    #[serde(default)]
+    pub context: BTreeMap<String, Value>,
+}
+
+impl RecordedRequest {
+    /// The request as the engine evaluates it
+    pub fn to_request(&self) -> Result<Request> {
+        let principal = self.principal_attributes.iter().fold(
+            Principal::parse(&self.principal),
+            |principal, (key, value)| principal.with_attribute(key, value.clone()),
+        );
+        let builder = RequestBuilder::new()
+            .principal(principal)
+            .action(Action::new(&self.action))
+            .resource(Resource::parse(&self.resource));
+        self.context
+            .iter()
+            .fold(builder, |builder, (key, value)| {
+                builder.context(key, value.clone())
+            })
+            .build()
+    }
+}
+
+/// Parse a corpus of recorded requests, one JSON object per line
+///
+/// Blank lines are skipped; errors name the offending line.
+pub fn parse_corpus(input: &str) -> Result<Vec<Request>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let recorded: RecordedRequest = serde_json::from_str(line)
+                .map_err(|e| RUNEError::ParseError(format!("Request on line {}: {}", i + 1, e)))?;
+            recorded
+                .to_request()
+                .map_err(|e| RUNEError::ParseError(format!("Request on line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+/// How often a policy matched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyCoverage {
+    /// `@id` annotation, or the Cedar policy ID
+    pub id: String,
+    /// Effect of the policy
+    pub effect: PolicyEffect,
+    /// Requests that satisfied the policy
+    pub matched: usize,
+}
+
+/// Whether a rule was exercised
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleCoverage {
+    /// The rule's `@id`, or the rule in Datalog syntax
+    pub rule: String,
+    /// Requests the rule derived something about
+    pub matched: usize,
+}
+
+/// A request no policy matched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnmatchedRequest {
+    /// Position of the request in the corpus, from 0
+    pub index: usize,
+    /// Principal, as `Type:id`
+    pub principal: String,
+    /// Action
+    pub action: String,
+    /// Resource, as `Type:id`
+    pub resource: String,
+}
+
+impl UnmatchedRequest {
+    pub(crate) fn new(index: usize, request: &Request) -> Self {
+        let principal = &request.principal.entity;
+        let resource = &request.resource.entity;
+        UnmatchedRequest {
+            index,
+            principal: format!("{}:{}", principal.entity_type, principal.id),
+            action: request.action.name.to_string(),
+            resource: format!("{}:{}", resource.entity_type, resource.id),
+        }
+    }
+}
+
+/// Which policies and rules a request corpus exercises
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// Requests in the corpus
+    pub requests: usize,
+    /// Active policies, ordered by ID
+    pub policies: Vec<PolicyCoverage>,
+    /// Active unscoped rules, in load order
+    pub rules: Vec<RuleCoverage>,
+    /// Requests no policy matched
+    pub unmatched: Vec<UnmatchedRequest>,
+}
+
+impl CoverageReport {
+    /// Policies no request satisfied
+    pub fn unexercised_policies(&self) -> impl Iterator<Item = &PolicyCoverage> {
+        self.policies.iter().filter(|policy| policy.matched == 0)
+    }
+
+    /// Rules that derived nothing about any request
+    pub fn unexercised_rules(&self) -> impl Iterator<Item = &RuleCoverage> {
+        self.rules.iter().filter(|rule| rule.matched == 0)
+    }
+
+    /// Whether every policy and rule was exercised and every request matched
+    /// some policy
+    pub fn is_complete(&self) -> bool {
+        self.unexercised_policies().next().is_none()
+            && self.unexercised_rules().next().is_none()
+            && self.unmatched.is_empty()
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let exercised = |total: usize, idle: usize| format!("{}/{}", total - idle, total);
+        let idle_policies: Vec<&PolicyCoverage> = self.unexercised_policies().collect();
+        let idle_rules: Vec<&RuleCoverage> = self.unexercised_rules().collect();
+
+        writeln!(f, "Requests: {}", self.requests)?;
+        writeln!(
+            f,
+            "Policies exercised: {}",
+            exercised(self.policies.len(), idle_policies.len())
+        )?;
+        for policy in idle_policies {
+            let effect = match policy.effect {
+                PolicyEffect::Permit => "permit",
+                PolicyEffect::Forbid => "forbid",
+            };
+            writeln!(f, "  never matched: {} ({})", policy.id, effect)?;
+        }
+        writeln!(
+            f,
+            "Rules exercised: {}",
+            exercised(self.rules.len(), idle_rules.len())
+        )?;
+        for rule in idle_rules {
+            writeln!(f, "  never fired: {}", rule.rule)?;
+        }
+        writeln!(f, "Requests matching no policy: {}", self.unmatched.len())?;
+        for request in &self.unmatched {
+            writeln!(
+                f,
+                "  #{}: {} {} {}",
+                request.index, request.principal, request.action, request.resource
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_corpus() {
+        let corpus = r#"
+{"principal": "User:alice", "action": "read", "resource": "Document:q3", "explain": true}
+
+{"principal": "bob", "principal_attributes": {"department": "sales"}, "action": "write", "resource": "Document:q3", "context": {"ip": "10.0.0.1"}}
+"#;
+        let requests = parse_corpus(corpus).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(&*requests[0].principal.entity.id, "alice");
+        assert_eq!(&*requests[1].principal.entity.entity_type, "User");
+        assert_eq!(
+            requests[1].principal.entity.attributes.get("department"),
+            Some(&Value::string("sales"))
+        );
+        assert_eq!(
+            requests[1].context.get("ip"),
+            Some(&Value::string("10.0.0.1"))
+        );
+
+        let error = parse_corpus("{\"principal\": \"User:alice\"}\n{}").unwrap_err();
+        assert!(error.to_string().contains("line 1"), "{}", error);
+    }
+}
@@ -0,0 +1,161 @@
+//! Cooperative cancellation of Datalog evaluation
+//!
+//! A fixpoint over a large fact base can run for seconds. The evaluator
+//! polls a [`CancellationToken`] between iterations and while joining, so a
+//! caller that stops caring (a client that disconnected, a deadline that
+//! passed, a server shutting down) gets its thread back instead of leaving
+//! the computation to finish for nobody.
+//!
+//! Tokens form a tree: cancelling a token cancels every child made from it,
+//! and a child can add a deadline of its own.
+//!
+//! ```text
+//! shutdown ─┬─ request A (cancelled on disconnect)
+//!           └─ request B ── deadline 100ms
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Shared flag telling an evaluation to stop early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    deadline: Option<Instant>,
+    parent: Option<CancellationToken>,
+}
+
+impl CancellationToken {
+    /// Create a token that is cancelled only explicitly
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a token that cancels itself at `deadline`
+    pub fn with_deadline(deadline: Instant) -> Self {
+        CancellationToken {
+            inner: Arc::new(Inner {
+                deadline: Some(deadline),
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Create a token cancelled along with this one, or on its own
+    pub fn child(&self) -> Self {
+        CancellationToken {
+            inner: Arc::new(Inner {
+                parent: Some(self.clone()),
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Create a child token that also cancels itself at `deadline`
+    pub fn child_with_deadline(&self, deadline: Instant) -> Self {
+        CancellationToken {
+            inner: Arc::new(Inner {
+                deadline: Some(deadline),
+                parent: Some(self.clone()),
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Cancel this token and its children
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Whether this token, or one of its ancestors, was cancelled or is
+    /// past its deadline
+    pub fn is_cancelled(&self) -> bool {
+        let mut token = self;
+        loop {
+            let inner = &token.inner;
+            if inner.cancelled.load(Ordering::Acquire)
+                || inner.deadline.is_some_and(|d| Instant::now() >= d)
+            {
+                return true;
+            }
+            match &inner.parent {
+                Some(parent) => token = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Cancel this token when the returned guard is dropped
+    ///
+    /// Holding the guard in a request future ties evaluation to the request:
+    /// when the client goes away and the future is dropped, work started on
+    /// its behalf stops too.
+    pub fn drop_guard(self) -> DropGuard {
+        DropGuard { token: self }
+    }
+}
+
+/// Cancels its token when dropped
+#[derive(Debug)]
+pub struct DropGuard {
+    token: CancellationToken,
+}
+
+impl DropGuard {
+    /// Token the guard cancels
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cancel_propagates_to_children() {
+        let root = CancellationToken::new();
+        let child = root.child();
+        let grandchild = child.child();
+        let sibling = root.child();
+
+        assert!(!grandchild.is_cancelled());
+        child.cancel();
+        assert!(child.is_cancelled());
+        assert!(grandchild.is_cancelled());
+        assert!(!root.is_cancelled());
+        assert!(!sibling.is_cancelled());
+
+        root.cancel();
+        assert!(sibling.is_cancelled());
+    }
+
+    #[test]
+    fn test_deadline_and_drop_guard() {
+        let root = CancellationToken::new();
+        let past = root.child_with_deadline(Instant::now());
+        let future = root.child_with_deadline(Instant::now() + Duration::from_secs(60));
+        assert!(past.is_cancelled());
+        assert!(!future.is_cancelled());
+        assert!(!root.is_cancelled());
+
+        let token = root.child();
+        let guard = token.clone().drop_guard();
+        assert!(!guard.token().is_cancelled());
+        drop(guard);
+        assert!(token.is_cancelled());
+    }
+}
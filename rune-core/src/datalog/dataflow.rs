@@ -0,0 +1,767 @@
+//! Dataflow evaluation backend for very large fact sets
+//!
+//! The interpreter in [`super::evaluation`] recomputes the fixpoint from
+//! scratch on every call. That is cheap for small and medium workloads, but
+//! with tens of millions of facts the rescan dominates. This backend compiles
+//! rules into a DAG of operators (hash joins, anti-joins and aggregations)
+//! whose state persists between runs, so feeding it a [`Delta`] only does
+//! work proportional to the facts the change actually reaches.
+//!
+//! Inserts are propagated stratum by stratum with semi-naive deltas.
+//! Retractions, and inserts that reach a negated atom, cannot be maintained
+//! with plain set semantics; they trigger a rebuild of the arrangements from
+//! the current base facts.
+//!
+//! Output facts are appended to a [`FactVec`] as they are arranged, so
+//! [`DataflowEvaluator::facts`] hands out a shared snapshot instead of
+//! copying every fact, and the output change of an insert is read off the
+//! end of it.
+
+use super::aggregation::evaluate_aggregate;
+use super::evaluation::stratify;
+use super::incremental::Delta;
+use super::types::{
+    complete_match, AggregateAtom, Atom, BuiltinCall, Guard, Rule, Substitution, Term,
+};
+use super::unification::{ground_atom, unify_atom_with_fact};
+use crate::fact_vec::FactVec;
+use crate::facts::Fact;
+use crate::types::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// A single operator in a rule pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operator {
+    /// Join incoming bindings with an arranged relation on the bound columns
+    Join {
+        /// Atom read by the join
+        atom: Atom,
+        /// Argument positions bound when the join runs (hash key)
+        key_columns: Vec<usize>,
+    },
+    /// Drop bindings that match an arranged relation (negated atom)
+    AntiJoin {
+        /// Negated atom
+        atom: Atom,
+        /// Argument positions bound when the anti-join runs
+        key_columns: Vec<usize>,
+    },
+}
+
+impl Operator {
+    fn atom(&self) -> &Atom {
+        match self {
+            Operator::Join { atom, .. } | Operator::AntiJoin { atom, .. } => atom,
+        }
+    }
+
+    fn key_columns(&self) -> &[usize] {
+        match self {
+            Operator::Join { key_columns, .. } | Operator::AntiJoin { key_columns, .. } => {
+                key_columns
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Join { atom, key_columns } => write!(f, "join {} on {:?}", atom, key_columns),
+            Operator::AntiJoin { atom, key_columns } => {
+                write!(f, "antijoin {} on {:?}", atom, key_columns)
+            }
+        }
+    }
+}
+
+/// Compiled rule: a chain of operators feeding a projection onto the head
+#[derive(Debug, Clone)]
+struct Pipeline {
+    head: Atom,
+    operators: Vec<Operator>,
+    /// Comparisons applied to complete bindings before projection
+    guards: Vec<Guard>,
+    /// Builtin calls made on complete bindings, before the guards
+    builtins: Vec<BuiltinCall>,
+}
+
+impl Pipeline {
+    fn compile(rule: &Rule) -> Self {
+        let mut bound: HashSet<&str> = HashSet::new();
+        let mut operators = Vec::with_capacity(rule.body.len());
+
+        // Positive atoms first so negated atoms see their variables bound
+        let (positive, negated): (Vec<_>, Vec<_>) = rule.body.iter().partition(|a| !a.negated);
+
+        for atom in positive.into_iter().chain(negated) {
+            let key_columns = atom
+                .terms
+                .iter()
+                .enumerate()
+                .filter(|(_, term)| match term {
+                    Term::Constant(_) => true,
+                    Term::Variable(v) => bound.contains(v.as_str()),
+                })
+                .map(|(i, _)| i)
+                .collect();
+
+            if atom.negated {
+                operators.push(Operator::AntiJoin {
+                    atom: atom.clone(),
+                    key_columns,
+                });
+            } else {
+                bound.extend(atom.variables());
+                operators.push(Operator::Join {
+                    atom: atom.clone(),
+                    key_columns,
+                });
+            }
+        }
+
+        Pipeline {
+            head: rule.head.clone(),
+            operators,
+            guards: rule.guards.clone(),
+            builtins: rule.builtins.clone(),
+        }
+    }
+}
+
+/// An indexed relation
+#[derive(Debug, Default)]
+struct Relation {
+    facts: HashSet<Fact>,
+    /// Hash indexes keyed by the argument positions bound at lookup time
+    indexes: HashMap<Vec<usize>, HashMap<Vec<Value>, Vec<Fact>>>,
+}
+
+impl Relation {
+    fn insert(&mut self, fact: Fact) -> bool {
+        if self.facts.contains(&fact) {
+            return false;
+        }
+        for (columns, index) in self.indexes.iter_mut() {
+            index
+                .entry(index_key(&fact, columns))
+                .or_default()
+                .push(fact.clone());
+        }
+        self.facts.insert(fact)
+    }
+
+    fn ensure_index(&mut self, columns: &[usize]) {
+        if columns.is_empty() || self.indexes.contains_key(columns) {
+            return;
+        }
+        let mut index: HashMap<Vec<Value>, Vec<Fact>> = HashMap::new();
+        for fact in &self.facts {
+            index
+                .entry(index_key(fact, columns))
+                .or_default()
+                .push(fact.clone());
+        }
+        self.indexes.insert(columns.to_vec(), index);
+    }
+
+    /// Facts whose key columns equal those of the (partially ground) atom
+    fn lookup<'a>(
+        &'a self,
+        columns: &[usize],
+        atom: &Atom,
+    ) -> Box<dyn Iterator<Item = &'a Fact> + 'a> {
+        if columns.is_empty() {
+            return Box::new(self.facts.iter());
+        }
+        let key: Option<Vec<Value>> = columns
+            .iter()
+            .map(|&i| atom.terms.get(i).and_then(|t| t.as_constant()).cloned())
+            .collect();
+        match key.and_then(|k| self.indexes.get(columns).and_then(|index| index.get(&k))) {
+            Some(facts) => Box::new(facts.iter()),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+fn index_key(fact: &Fact, columns: &[usize]) -> Vec<Value> {
+    columns
+        .iter()
+        .map(|&i| fact.args.get(i).cloned().unwrap_or(Value::Null))
+        .collect()
+}
+
+/// Aggregate maintained over the arranged relations
+#[derive(Debug, Clone)]
+struct AggregateView {
+    predicate: Arc<str>,
+    aggregate: AggregateAtom,
+    output: Option<Fact>,
+}
+
+/// Counters describing the work done by a [`DataflowEvaluator`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataflowStats {
+    /// Number of operators in the compiled DAG
+    pub operators: usize,
+    /// Number of deltas applied incrementally
+    pub incremental_updates: u64,
+    /// Number of full rebuilds (initial load, retractions, negation)
+    pub rebuilds: u64,
+    /// Number of facts currently arranged (base and derived)
+    pub arranged_facts: usize,
+}
+
+/// Incremental operator-DAG evaluator
+///
+/// Keeps every relation arranged (hash-indexed on the columns its joins
+/// need) so that applying a delta only touches the affected operators.
+pub struct DataflowEvaluator {
+    strata: Vec<Vec<Pipeline>>,
+    /// Ground facts declared as rules
+    ground: Vec<Fact>,
+    /// Base facts fed in from outside
+    base: HashSet<Fact>,
+    arrangements: HashMap<Arc<str>, Relation>,
+    /// Every arranged fact, in the order it was arranged
+    output: FactVec,
+    aggregates: Vec<AggregateView>,
+    stats: DataflowStats,
+}
+
+impl DataflowEvaluator {
+    /// Compile rules into an operator DAG
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let mut ground = Vec::new();
+        let mut strata = Vec::new();
+
+        for stratum in stratify(&rules) {
+            let mut pipelines = Vec::new();
+            for rule in stratum {
+                if rule.is_fact() {
+                    if let Some(fact) = ground_atom(&rule.head, &Substitution::new()) {
+                        ground.push(fact);
+                    }
+                } else {
+                    pipelines.push(Pipeline::compile(&rule));
+                }
+            }
+            strata.push(pipelines);
+        }
+
+        let operators = strata.iter().flatten().map(|p| p.operators.len()).sum();
+        let mut evaluator = DataflowEvaluator {
+            strata,
+            ground,
+            base: HashSet::new(),
+            arrangements: HashMap::new(),
+            output: FactVec::new(),
+            aggregates: Vec::new(),
+            stats: DataflowStats {
+                operators,
+                ..DataflowStats::default()
+            },
+        };
+        evaluator.rebuild();
+        evaluator
+    }
+
+    /// Maintain an aggregate, exposed as a unary `predicate(value)` fact
+    ///
+    /// Aggregates are outputs of the DAG: they are recomputed only when a
+    /// delta touches one of their input predicates, and they cannot be read
+    /// back by rules.
+    pub fn with_aggregate(
+        mut self,
+        predicate: impl Into<String>,
+        aggregate: AggregateAtom,
+    ) -> Self {
+        let mut view = AggregateView {
+            predicate: Arc::from(predicate.into().into_boxed_str()),
+            aggregate,
+            output: None,
+        };
+        self.refresh_aggregate(&mut view);
+        self.stats.operators += 1;
+        self.aggregates.push(view);
+        self
+    }
+
+    /// Replace all base facts, rebuilding every arrangement
+    pub fn load<'a>(&mut self, facts: impl IntoIterator<Item = &'a Fact>) {
+        self.base = facts.into_iter().cloned().collect();
+        self.rebuild();
+    }
+
+    /// Apply a change to the base facts and return the change in output facts
+    ///
+    /// Inserts that propagate incrementally report the facts they arranged;
+    /// only a rebuild compares the old arrangements with the new ones.
+    pub fn apply(&mut self, delta: &Delta) -> Delta {
+        if delta.is_empty() {
+            return Delta::empty();
+        }
+
+        let aggregates: Vec<Option<Fact>> =
+            self.aggregates.iter().map(|v| v.output.clone()).collect();
+        for fact in &delta.removed {
+            self.base.remove(fact);
+        }
+        self.base.extend(delta.added.iter().cloned());
+
+        let arranged = self.output.len();
+        let mut out = Delta::empty();
+        if delta.removed.is_empty() && self.propagate(delta.added.iter().cloned()) {
+            self.stats.incremental_updates += 1;
+            out.added
+                .extend((arranged..self.output.len()).filter_map(|i| self.output.get(i).cloned()));
+        } else {
+            let previous = std::mem::take(&mut self.arrangements);
+            let output = self.output.clone();
+            self.rebuild();
+            out.removed.extend(
+                output
+                    .iter()
+                    .filter(|fact| !self.is_arranged(fact))
+                    .cloned(),
+            );
+            out.added.extend(
+                self.output
+                    .iter()
+                    .filter(|fact| {
+                        !previous
+                            .get(&fact.predicate)
+                            .is_some_and(|relation| relation.facts.contains(*fact))
+                    })
+                    .cloned(),
+            );
+        }
+
+        for (before, view) in aggregates.into_iter().zip(&self.aggregates) {
+            if before != view.output {
+                out.removed.extend(before);
+                out.added.extend(view.output.clone());
+            }
+        }
+        out
+    }
+
+    /// All arranged facts (base and derived) plus aggregate outputs
+    ///
+    /// The arranged facts are shared with the evaluator, so this is cheap
+    /// however many there are.
+    pub fn facts(&self) -> FactVec {
+        let mut facts = self.output.clone();
+        facts.extend(self.aggregates.iter().filter_map(|v| v.output.clone()));
+        facts
+    }
+
+    /// Number of output facts with `predicate`
+    pub fn count(&self, predicate: &str) -> usize {
+        self.arrangements
+            .get(predicate)
+            .map_or(0, |relation| relation.facts.len())
+            + self
+                .aggregates
+                .iter()
+                .filter(|v| v.output.is_some() && v.predicate.as_ref() == predicate)
+                .count()
+    }
+
+    /// Number of output facts
+    pub fn len(&self) -> usize {
+        self.output.len()
+            + self
+                .aggregates
+                .iter()
+                .filter(|v| v.output.is_some())
+                .count()
+    }
+
+    /// Check whether there are no output facts
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Operators in evaluation order
+    pub fn operators(&self) -> impl Iterator<Item = &Operator> {
+        self.strata
+            .iter()
+            .flatten()
+            .flat_map(|pipeline| pipeline.operators.iter())
+    }
+
+    /// Work counters
+    pub fn stats(&self) -> DataflowStats {
+        DataflowStats {
+            arranged_facts: self.output.len(),
+            ..self.stats.clone()
+        }
+    }
+
+    /// Drop all state and recompute from base and ground facts
+    fn rebuild(&mut self) {
+        self.arrangements.clear();
+        self.output = FactVec::new();
+        for pipeline in self.strata.iter().flatten() {
+            for op in &pipeline.operators {
+                self.arrangements
+                    .entry(op.atom().predicate.clone())
+                    .or_default()
+                    .ensure_index(op.key_columns());
+            }
+        }
+
+        let seed: Vec<Fact> = self
+            .ground
+            .iter()
+            .chain(self.base.iter())
+            .cloned()
+            .collect();
+        let propagated = self.propagate(seed.into_iter());
+        debug_assert!(propagated, "full propagation cannot hit negation");
+        self.stats.rebuilds += 1;
+
+        let mut views = std::mem::take(&mut self.aggregates);
+        for view in &mut views {
+            self.refresh_aggregate(view);
+        }
+        self.aggregates = views;
+    }
+
+    /// Push inserted facts through the strata
+    ///
+    /// Returns `false` when an insert reaches a negated atom in a stratum
+    /// that already holds state, in which case the caller must rebuild.
+    fn propagate(&mut self, inserted: impl Iterator<Item = Fact>) -> bool {
+        let fresh = self.arrangements.values().all(|r| r.facts.is_empty());
+
+        let mut changed: HashMap<Arc<str>, Vec<Fact>> = HashMap::new();
+        for fact in inserted {
+            if self.arrange(fact.clone()) {
+                changed
+                    .entry(fact.predicate.clone())
+                    .or_default()
+                    .push(fact);
+            }
+        }
+
+        for s in 0..self.strata.len() {
+            if !fresh && self.reads_negated(s, &changed) {
+                return false;
+            }
+
+            let mut delta = changed.clone();
+            loop {
+                let derived = self.step(s, &delta);
+                let mut next: HashMap<Arc<str>, Vec<Fact>> = HashMap::new();
+                for fact in derived {
+                    if self.arrange(fact.clone()) {
+                        next.entry(fact.predicate.clone()).or_default().push(fact);
+                    }
+                }
+                if next.is_empty() {
+                    break;
+                }
+                for (pred, facts) in &next {
+                    changed
+                        .entry(pred.clone())
+                        .or_default()
+                        .extend(facts.iter().cloned());
+                }
+                delta = next;
+            }
+        }
+
+        let mut views = std::mem::take(&mut self.aggregates);
+        for view in &mut views {
+            if view
+                .aggregate
+                .body
+                .iter()
+                .any(|atom| changed.contains_key(&atom.predicate))
+            {
+                self.refresh_aggregate(view);
+            }
+        }
+        self.aggregates = views;
+
+        true
+    }
+
+    /// One semi-naive round: derive facts where at least one join reads the delta
+    fn step(&self, stratum: usize, delta: &HashMap<Arc<str>, Vec<Fact>>) -> Vec<Fact> {
+        let mut derived = Vec::new();
+
+        for pipeline in &self.strata[stratum] {
+            for (delta_index, op) in pipeline.operators.iter().enumerate() {
+                let Operator::Join { atom, .. } = op else {
+                    continue;
+                };
+                let Some(delta_facts) = delta.get(&atom.predicate) else {
+                    continue;
+                };
+
+                let mut bindings = vec![Substitution::new()];
+                for (index, op) in pipeline.operators.iter().enumerate() {
+                    let mut next = Vec::new();
+                    for sub in &bindings {
+                        let partial = op.atom().apply_substitution(sub);
+                        match op {
+                            Operator::Join { .. } if index == delta_index => {
+                                extend_bindings(&mut next, sub, &partial, delta_facts.iter());
+                            }
+                            Operator::Join { key_columns, .. } => {
+                                if let Some(relation) = self.arrangements.get(&partial.predicate) {
+                                    extend_bindings(
+                                        &mut next,
+                                        sub,
+                                        &partial,
+                                        relation.lookup(key_columns, &partial),
+                                    );
+                                }
+                            }
+                            Operator::AntiJoin { key_columns, .. } => {
+                                let matched = self
+                                    .arrangements
+                                    .get(&partial.predicate)
+                                    .map(|relation| {
+                                        relation
+                                            .lookup(key_columns, &partial)
+                                            .any(|f| unify_atom_with_fact(&partial, f).is_some())
+                                    })
+                                    .unwrap_or(false);
+                                if !matched {
+                                    next.push(sub.clone());
+                                }
+                            }
+                        }
+                    }
+                    bindings = next;
+                    if bindings.is_empty() {
+                        break;
+                    }
+                }
+
+                derived.extend(
+                    bindings
+                        .iter()
+                        .filter_map(|sub| complete_match(&pipeline.builtins, &pipeline.guards, sub))
+                        .filter_map(|sub| ground_atom(&pipeline.head, &sub)),
+                );
+            }
+        }
+
+        derived
+    }
+
+    /// Whether stratum `s` negates a predicate that has pending changes
+    fn reads_negated(&self, s: usize, changed: &HashMap<Arc<str>, Vec<Fact>>) -> bool {
+        self.strata[s].iter().any(|pipeline| {
+            pipeline.operators.iter().any(|op| {
+                matches!(op, Operator::AntiJoin { atom, .. } if changed.contains_key(&atom.predicate))
+            })
+        })
+    }
+
+    fn arrange(&mut self, fact: Fact) -> bool {
+        let inserted = self
+            .arrangements
+            .entry(fact.predicate.clone())
+            .or_default()
+            .insert(fact.clone());
+        if inserted {
+            self.output.push(fact);
+        }
+        inserted
+    }
+
+    fn is_arranged(&self, fact: &Fact) -> bool {
+        self.arrangements
+            .get(&fact.predicate)
+            .is_some_and(|relation| relation.facts.contains(fact))
+    }
+
+    fn refresh_aggregate(&self, view: &mut AggregateView) {
+        let inputs: Vec<Fact> = view
+            .aggregate
+            .body
+            .iter()
+            .map(|atom| &atom.predicate)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|pred| self.arrangements.get(pred))
+            .flat_map(|relation| relation.facts.iter().cloned())
+            .collect();
+
+        view.output = evaluate_aggregate(&view.aggregate, &inputs)
+            .map(|result| Fact::new(view.predicate.as_ref(), vec![result.value]));
+    }
+}
+
+fn extend_bindings<'a>(
+    out: &mut Vec<Substitution>,
+    sub: &Substitution,
+    atom: &Atom,
+    facts: impl Iterator<Item = &'a Fact>,
+) {
+    for fact in facts {
+        if let Some(bindings) = unify_atom_with_fact(atom, fact) {
+            if let Some(merged) = sub.merge(&bindings) {
+                out.push(merged);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datalog::evaluation::Evaluator;
+    use crate::datalog::types::AggregateOp;
+    use crate::facts::FactStore;
+
+    fn edge(a: i64, b: i64) -> Fact {
+        Fact::binary("edge", Value::Integer(a), Value::Integer(b))
+    }
+
+    fn transitive_closure() -> Vec<Rule> {
+        vec![
+            Rule::new(
+                Atom::new("path", vec![Term::var("X"), Term::var("Y")]),
+                vec![Atom::new("edge", vec![Term::var("X"), Term::var("Y")])],
+            ),
+            Rule::new(
+                Atom::new("path", vec![Term::var("X"), Term::var("Z")]),
+                vec![
+                    Atom::new("edge", vec![Term::var("X"), Term::var("Y")]),
+                    Atom::new("path", vec![Term::var("Y"), Term::var("Z")]),
+                ],
+            ),
+        ]
+    }
+
+    fn sorted(mut facts: Vec<Fact>) -> Vec<String> {
+        let mut out: Vec<String> = facts
+            .drain(..)
+            .map(|f| format!("{}{:?}", f.predicate, f.args))
+            .collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        let facts = vec![edge(1, 2), edge(2, 3), edge(3, 4)];
+        let store = Arc::new(FactStore::new());
+        for fact in &facts {
+            store.add_fact(fact.clone());
+        }
+
+        let expected = Evaluator::new(transitive_closure(), store).evaluate().facts;
+
+        let mut dataflow = DataflowEvaluator::new(transitive_closure());
+        dataflow.load(&facts);
+
+        assert_eq!(sorted(dataflow.facts().to_vec()), sorted(expected));
+    }
+
+    #[test]
+    fn test_incremental_insert() {
+        let mut dataflow = DataflowEvaluator::new(transitive_closure());
+        dataflow.load(&[edge(1, 2), edge(2, 3)]);
+        let rebuilds = dataflow.stats().rebuilds;
+
+        let mut delta = Delta::empty();
+        delta.added.insert(edge(3, 4));
+        let out = dataflow.apply(&delta);
+
+        // edge(3,4), path(3,4), path(2,4), path(1,4)
+        assert_eq!(out.added.len(), 4);
+        assert!(out.removed.is_empty());
+        assert_eq!(dataflow.stats().rebuilds, rebuilds);
+        assert_eq!(dataflow.stats().incremental_updates, 1);
+    }
+
+    #[test]
+    fn test_retraction_rebuilds() {
+        let mut dataflow = DataflowEvaluator::new(transitive_closure());
+        dataflow.load(&[edge(1, 2), edge(2, 3)]);
+
+        let mut delta = Delta::empty();
+        delta.removed.insert(edge(2, 3));
+        let out = dataflow.apply(&delta);
+
+        // edge(2,3), path(2,3), path(1,3)
+        assert_eq!(out.removed.len(), 3);
+        assert_eq!(dataflow.len(), 2);
+    }
+
+    #[test]
+    fn test_negation_falls_back_to_rebuild() {
+        // orphan(X) :- node(X), not edge(X, _)
+        let rules = vec![Rule::new(
+            Atom::new("orphan", vec![Term::var("X")]),
+            vec![
+                Atom::new("node", vec![Term::var("X")]),
+                Atom::negated("edge", vec![Term::var("X"), Term::var("Y")]),
+            ],
+        )];
+        let mut dataflow = DataflowEvaluator::new(rules);
+        dataflow.load(&[
+            Fact::unary("node", Value::Integer(1)),
+            Fact::unary("node", Value::Integer(2)),
+            edge(1, 2),
+        ]);
+        assert!(dataflow
+            .facts()
+            .contains(&Fact::unary("orphan", Value::Integer(2))));
+
+        let mut delta = Delta::empty();
+        delta.added.insert(edge(2, 1));
+        let out = dataflow.apply(&delta);
+
+        assert!(out
+            .removed
+            .contains(&Fact::unary("orphan", Value::Integer(2))));
+        assert_eq!(dataflow.stats().incremental_updates, 0);
+    }
+
+    #[test]
+    fn test_aggregate_tracks_deltas() {
+        let count = AggregateAtom::new(
+            AggregateOp::Count,
+            "X".to_string(),
+            "N".to_string(),
+            vec![Atom::new("edge", vec![Term::var("X"), Term::var("Y")])],
+        );
+        let mut dataflow =
+            DataflowEvaluator::new(transitive_closure()).with_aggregate("edge_count", count);
+        dataflow.load(&[edge(1, 2)]);
+        assert!(dataflow
+            .facts()
+            .contains(&Fact::unary("edge_count", Value::Integer(1))));
+
+        let mut delta = Delta::empty();
+        delta.added.insert(edge(2, 3));
+        dataflow.apply(&delta);
+        assert!(dataflow
+            .facts()
+            .contains(&Fact::unary("edge_count", Value::Integer(2))));
+    }
+
+    #[test]
+    fn test_operators_use_bound_columns() {
+        let dataflow = DataflowEvaluator::new(transitive_closure());
+        let keys: Vec<Vec<usize>> = dataflow
+            .operators()
+            .map(|op| op.key_columns().to_vec())
+            .collect();
+        // Recursive rule joins path on its first column once Y is bound
+        assert_eq!(keys, vec![vec![], vec![], vec![0]]);
+        assert_eq!(dataflow.stats().operators, 3);
+    }
+}
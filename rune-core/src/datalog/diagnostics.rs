@@ -4,7 +4,8 @@
 //! - Source location tracking (spans)
 //! - Multiple error reporting
 //! - Helpful suggestions for common mistakes
-//! - Pretty formatting with code snippets
+//! - Pretty formatting with code snippets, in color for terminals and
+//!   plain or as JSON for everything else
 //!
 //! Design principles:
 //! - Collect multiple errors before failing (don't stop at first error)
@@ -12,10 +13,11 @@
 //! - Show relevant code context
 //! - Use consistent formatting across all error types
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// Source location in input text
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Span {
     /// Starting byte offset
     pub start: usize,
@@ -43,6 +45,23 @@ impl Span {
         Span::new(offset, offset + 1, line, column)
     }
 
+    /// Span of the bytes `start..end` of `source`, with the line and column
+    /// of `start`
+    pub fn locate(source: &str, start: usize, end: usize) -> Self {
+        let start = start.min(source.len());
+        let before = &source.as_bytes()[..start];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let column = String::from_utf8_lossy(&before[line_start..])
+            .chars()
+            .count()
+            + 1;
+        let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+        Span::new(start, end.max(start), line, column)
+    }
+
     /// Combine two spans into one that covers both
     pub fn merge(&self, other: &Span) -> Span {
         Span {
@@ -61,7 +80,8 @@ impl fmt::Display for Span {
 }
 
 /// Severity level for diagnostics
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     /// Informational message
     Info,
@@ -82,19 +102,26 @@ impl fmt::Display for Severity {
 }
 
 /// A diagnostic message with context and suggestions
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Diagnostic {
     /// Severity level
     pub severity: Severity,
+    /// Stable identifier for the kind of problem, e.g. `E0101`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
     /// Primary error message
     pub message: String,
     /// Optional source location
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub span: Option<Span>,
     /// Optional help text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub help: Option<String>,
     /// Suggested fixes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub suggestions: Vec<Suggestion>,
     /// Related diagnostics (e.g., "note: variable first used here")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub related: Vec<Diagnostic>,
 }
 
@@ -103,6 +130,7 @@ impl Diagnostic {
     pub fn error(message: impl Into<String>) -> Self {
         Diagnostic {
             severity: Severity::Error,
+            code: None,
             message: message.into(),
             span: None,
             help: None,
@@ -115,6 +143,7 @@ impl Diagnostic {
     pub fn warning(message: impl Into<String>) -> Self {
         Diagnostic {
             severity: Severity::Warning,
+            code: None,
             message: message.into(),
             span: None,
             help: None,
@@ -127,6 +156,7 @@ impl Diagnostic {
     pub fn info(message: impl Into<String>) -> Self {
         Diagnostic {
             severity: Severity::Info,
+            code: None,
             message: message.into(),
             span: None,
             help: None,
@@ -141,6 +171,12 @@ impl Diagnostic {
         self
     }
 
+    /// Add an error code
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
     /// Add help text
     pub fn with_help(mut self, help: impl Into<String>) -> Self {
         self.help = Some(help.into());
@@ -159,57 +195,78 @@ impl Diagnostic {
         self
     }
 
-    /// Format the diagnostic with optional source code
+    /// Format the diagnostic with optional source code, in color
     pub fn format(&self, source: Option<&str>) -> String {
+        self.render(source, true)
+    }
+
+    /// Format the diagnostic with optional source code
+    ///
+    /// With `source`, spans are shown as excerpts of the lines they cover
+    /// with carets under the spanned text, and suggestions with a
+    /// replacement as the patched line. `color` adds ANSI escapes.
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
+        let paint = Paint(color);
         let mut output = String::new();
 
-        // Header: severity and message
+        // Header: severity, code and message
         let severity_color = match self.severity {
-            Severity::Error => "\x1b[1;31m",   // Bold red
-            Severity::Warning => "\x1b[1;33m", // Bold yellow
-            Severity::Info => "\x1b[1;36m",    // Bold cyan
+            Severity::Error => "1;31",   // Bold red
+            Severity::Warning => "1;33", // Bold yellow
+            Severity::Info => "1;36",    // Bold cyan
+        };
+        let label = match &self.code {
+            Some(code) => format!("{}[{}]", self.severity, code),
+            None => self.severity.to_string(),
         };
-        let reset = "\x1b[0m";
-
         output.push_str(&format!(
-            "{}{}{}: {}\n",
-            severity_color, self.severity, reset, self.message
+            "{}: {}\n",
+            paint.apply(severity_color, &label),
+            paint.apply("1", &self.message)
         ));
 
         // Location
         if let Some(ref span) = self.span {
-            output.push_str(&format!("  --> {}\n", span));
+            output.push_str(&format!("  {} {}\n", paint.apply("1;34", "-->"), span));
 
             // Source code context
             if let Some(src) = source {
-                if let Some(context) = extract_source_context(src, span) {
-                    output.push_str(&format!("   |\n{}\n", context));
+                if let Some(context) = render_excerpt(src, span, '^', severity_color, paint) {
+                    output.push_str(&context);
                 }
             }
         }
 
         // Help text
         if let Some(ref help) = self.help {
-            output.push_str(&format!(
-                "\x1b[1m{} = help:\x1b[0m {}\n",
-                severity_color, help
-            ));
+            output.push_str(&format!("  {} {}\n", paint.apply("1", "= help:"), help));
         }
 
         // Suggestions
         for suggestion in &self.suggestions {
             output.push_str(&format!(
-                "\x1b[1;32msuggestion:\x1b[0m {}\n",
+                "{} {}\n",
+                paint.apply("1;32", "suggestion:"),
                 suggestion.message
             ));
-            if let Some(ref replacement) = suggestion.replacement {
-                output.push_str(&format!("  replace with: {}\n", replacement));
+            let Some(ref replacement) = suggestion.replacement else {
+                continue;
+            };
+            let fix = suggestion
+                .span
+                .as_ref()
+                .or(self.span.as_ref())
+                .zip(source)
+                .and_then(|(span, src)| render_fix(src, span, replacement, paint));
+            match fix {
+                Some(fix) => output.push_str(&fix),
+                None => output.push_str(&format!("  replace with: {}\n", replacement)),
             }
         }
 
         // Related diagnostics
         for related in &self.related {
-            output.push_str(&format!("\n{}", related.format(source)));
+            output.push_str(&format!("\n{}", related.render(source, color)));
         }
 
         output
@@ -218,18 +275,20 @@ impl Diagnostic {
 
 impl fmt::Display for Diagnostic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.format(None))
+        write!(f, "{}", self.render(None, false))
     }
 }
 
 /// A suggested fix for a diagnostic
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Suggestion {
     /// Description of the suggestion
     pub message: String,
     /// Optional replacement text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub replacement: Option<String>,
     /// Span to replace (if different from diagnostic span)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub span: Option<Span>,
 }
 
@@ -256,34 +315,162 @@ impl Suggestion {
     }
 }
 
-/// Extract source code context around a span
-fn extract_source_context(source: &str, span: &Span) -> Option<String> {
-    let lines: Vec<&str> = source.lines().collect();
-    if span.line == 0 || span.line > lines.len() {
-        return None;
+/// ANSI styling that is switched off for plain output
+#[derive(Clone, Copy)]
+struct Paint(bool);
+
+impl Paint {
+    fn apply(self, style: &str, text: &str) -> String {
+        if self.0 {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
     }
+}
 
-    let line_idx = span.line - 1;
-    let line = lines[line_idx];
+/// Most lines an excerpt shows before eliding the middle
+const MAX_EXCERPT_LINES: usize = 8;
+
+/// Lines of `source` with the byte offset each starts at
+fn source_lines(source: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    source
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.trim_end_matches(['\n', '\r']))
+        })
+        .collect()
+}
+
+/// Byte offsets in `source` covered by `span`
+///
+/// The start comes from the span's line and column, so spans built with
+/// offsets into a different text still land on the right line.
+fn span_range(lines: &[(usize, &str)], span: &Span) -> Option<(usize, usize)> {
+    let (line_start, line) = *lines.get(span.line.checked_sub(1)?)?;
+    let column = line
+        .char_indices()
+        .nth(span.column.saturating_sub(1))
+        .map_or(line.len(), |(i, _)| i);
+    let start = line_start + column;
+    Some((start, start + span.end.saturating_sub(span.start)))
+}
 
-    let mut output = String::new();
+/// Extract source code context around a span
+///
+/// Shows every line the span touches, eliding the middle of long spans,
+/// with `mark` under the spanned text.
+fn render_excerpt(
+    source: &str,
+    span: &Span,
+    mark: char,
+    style: &str,
+    paint: Paint,
+) -> Option<String> {
+    let lines = source_lines(source);
+    let (start, end) = span_range(&lines, span)?;
+    let first = span.line - 1;
+    let last = lines
+        .iter()
+        .rposition(|&(line_start, _)| line_start < end.max(start + 1))
+        .unwrap_or(first)
+        .max(first);
+
+    let width = (last + 1).to_string().len();
+    let gutter = |number: &str| paint.apply("1;34", &format!("{:>width$} |", number));
+    let mut output = format!("{}\n", gutter(""));
+
+    for (index, &(line_start, line)) in lines.iter().enumerate().take(last + 1).skip(first) {
+        let count = last - first + 1;
+        let position = index - first;
+        if count > MAX_EXCERPT_LINES && position >= 3 && position < count - 2 {
+            if position == 3 {
+                output.push_str(&format!("{}\n", paint.apply("1;34", "...")));
+            }
+            continue;
+        }
+        output.push_str(&format!("{} {}\n", gutter(&(index + 1).to_string()), line));
+
+        // Underline the spanned part of the line, skipping indentation on
+        // continuation lines
+        let from = if index == first {
+            start - line_start
+        } else {
+            line.len() - line.trim_start().len()
+        };
+        let to = (end.saturating_sub(line_start)).min(line.trim_end().len());
+        let column = line[..from.min(line.len())].chars().count();
+        let length = line
+            .get(from..to.max(from))
+            .map_or(0, |text| text.chars().count())
+            .max(1);
+        output.push_str(&format!(
+            "{} {}{}\n",
+            gutter(""),
+            " ".repeat(column),
+            paint.apply(style, &mark.to_string().repeat(length))
+        ));
+    }
 
-    // Line number and content
-    output.push_str(&format!("{:>4} | {}\n", span.line, line));
+    Some(output)
+}
 
-    // Underline the error span
-    let col_start = span.column.saturating_sub(1);
-    let col_end = col_start + (span.end - span.start).min(line.len());
-    let spaces = " ".repeat(col_start);
-    let underline = "^".repeat((col_end - col_start).max(1));
+/// The line a single-line span is on with `replacement` applied, marked
+/// with `~`
+fn render_fix(source: &str, span: &Span, replacement: &str, paint: Paint) -> Option<String> {
+    let lines = source_lines(source);
+    let (start, end) = span_range(&lines, span)?;
+    let (line_start, line) = lines[span.line - 1];
+    if end - line_start > line.len() || !source.is_char_boundary(end) {
+        return None;
+    }
 
-    output.push_str(&format!("     | {}{}\n", spaces, underline));
+    let patched = format!("{}{}{}", &source[..start], replacement, &source[end..]);
+    let patched_span = Span::new(start, start + replacement.len(), span.line, span.column);
+    render_excerpt(&patched, &patched_span, '~', "1;32", paint)
+}
 
-    Some(output)
+/// Closest of `candidates` to `name`, for "did you mean" suggestions
+///
+/// Only candidates within a third of the name's length in edit distance
+/// count, so unrelated names are never offered.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
 }
 
 /// Collection of diagnostics
-#[derive(Debug, Clone, Default)]
+///
+/// Serializes as a list of diagnostics.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct DiagnosticBag {
     diagnostics: Vec<Diagnostic>,
 }
@@ -316,6 +503,11 @@ impl DiagnosticBag {
         self.add(Diagnostic::info(message));
     }
 
+    /// Check if the bag holds no diagnostics
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
     /// Check if there are any errors
     pub fn has_errors(&self) -> bool {
         self.diagnostics
@@ -351,12 +543,17 @@ impl DiagnosticBag {
             .count()
     }
 
-    /// Format all diagnostics with source code
+    /// Format all diagnostics with source code, in color
     pub fn format(&self, source: Option<&str>) -> String {
+        self.render(source, true)
+    }
+
+    /// Format all diagnostics with source code, in color if `color` is set
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
         let mut output = String::new();
 
         for diagnostic in &self.diagnostics {
-            output.push_str(&diagnostic.format(source));
+            output.push_str(&diagnostic.render(source, color));
             output.push('\n');
         }
 
@@ -366,8 +563,10 @@ impl DiagnosticBag {
 
         if errors > 0 || warnings > 0 {
             output.push_str(&format!(
-                "\x1b[1mSummary:\x1b[0m {} error(s), {} warning(s)\n",
-                errors, warnings
+                "{} {} error(s), {} warning(s)\n",
+                Paint(color).apply("1", "Summary:"),
+                errors,
+                warnings
             ));
         }
 
@@ -382,7 +581,13 @@ impl DiagnosticBag {
 
 impl fmt::Display for DiagnosticBag {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.format(None))
+        write!(f, "{}", self.render(None, false))
+    }
+}
+
+impl Extend<Diagnostic> for DiagnosticBag {
+    fn extend<I: IntoIterator<Item = Diagnostic>>(&mut self, diagnostics: I) {
+        self.diagnostics.extend(diagnostics);
     }
 }
 
@@ -560,7 +765,7 @@ mod tests {
         let source = "path(X, Y) :- edge(X, Y).\npath(X, Z) :- edge(X, Y), path(Y, Z).";
         let span = Span::new(15, 20, 1, 16);
 
-        let context = extract_source_context(source, &span);
+        let context = render_excerpt(source, &span, '^', "1", Paint(false));
         assert!(context.is_some());
         let ctx = context.unwrap();
         assert!(ctx.contains("path(X, Y)"));
@@ -633,4 +838,82 @@ mod tests {
         assert_eq!(main_diag.related.len(), 1);
         assert_eq!(main_diag.related[0].severity, Severity::Info);
     }
+
+    #[test]
+    fn test_span_locate() {
+        let source = "first\nsecond line\n";
+        let span = Span::locate(source, 13, 17);
+        assert_eq!((span.line, span.column), (2, 8));
+        assert_eq!(&source[span.start..span.end], "line");
+    }
+
+    #[test]
+    fn test_render_with_code_and_multiline_span() {
+        let source = "allowed(U) :-\n    user(U),\n    active(U).\n";
+        let diag = Diagnostic::error("bad rule")
+            .with_code("E0105")
+            .with_span(Span::locate(source, 0, source.trim_end().len()));
+
+        let plain = diag.render(Some(source), false);
+        assert!(plain.starts_with("error[E0105]: bad rule\n  --> 1:1\n"));
+        assert!(plain.contains("1 | allowed(U) :-\n  | ^^^^^^^^^^^^^\n"));
+        assert!(plain.contains("2 |     user(U),\n  |     ^^^^^^^^\n"));
+        assert!(plain.contains("3 |     active(U).\n  |     ^^^^^^^^^^\n"));
+        assert!(!plain.contains('\x1b'));
+        assert!(diag.render(Some(source), true).contains("\x1b[1;31m"));
+    }
+
+    #[test]
+    fn test_long_spans_are_elided() {
+        let source: String = (0..12).map(|i| format!("line{}\n", i)).collect();
+        let diag = Diagnostic::error("long").with_span(Span::locate(&source, 0, source.len() - 1));
+        let rendered = diag.render(Some(&source), false);
+        assert!(rendered.contains(" 3 | line2"));
+        assert!(rendered.contains("..."));
+        assert!(!rendered.contains(" 6 | line5"));
+        assert!(rendered.contains("12 | line11"));
+    }
+
+    #[test]
+    fn test_suggestion_renders_patched_line() {
+        let source = "ok(X) :- usr(X).";
+        let span = Span::locate(source, 9, 12);
+        let diag = Diagnostic::warning("unknown predicate")
+            .with_span(span)
+            .with_suggestion(Suggestion::new("did you mean `user`?").with_replacement("user"));
+
+        let rendered = diag.render(Some(source), false);
+        assert!(rendered.contains("1 | ok(X) :- user(X).\n  |          ~~~~\n"));
+        // Without source the replacement is listed instead
+        assert!(diag.render(None, false).contains("replace with: user"));
+    }
+
+    #[test]
+    fn test_diagnostics_serialize_to_json() {
+        let mut bag = DiagnosticBag::new();
+        bag.add(
+            Diagnostic::error("bad")
+                .with_code("E0101")
+                .with_span(Span::new(3, 4, 1, 4)),
+        );
+        let json = serde_json::to_value(&bag).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "severity": "error",
+                "code": "E0101",
+                "message": "bad",
+                "span": {"start": 3, "end": 4, "line": 1, "column": 4}
+            }])
+        );
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let candidates = ["user_tenant", "tenant_active", "member"];
+        assert_eq!(closest_match("user_tenat", candidates), Some("user_tenant"));
+        assert_eq!(closest_match("membr", candidates), Some("member"));
+        assert_eq!(closest_match("resource", candidates), None);
+        assert_eq!(closest_match("member", candidates), None);
+    }
 }
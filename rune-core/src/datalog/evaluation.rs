@@ -4,11 +4,14 @@
 //! fixpoint computation. Based on the semi-naive algorithm from
 //! Datalog research and adapted from patterns in datafrog/ascent.
 
+use super::cancel::CancellationToken;
 use super::magic_sets::{MagicSetsTransformer, Query};
 use super::provenance::ProvenanceTracker;
+use super::scratch;
 use super::types::{Atom, Rule, Substitution};
 use super::unification::{ground_atom, unify_atom_with_fact};
 use crate::facts::{Fact, FactStore};
+use crate::limits::{CardinalityLimits, LimitExceeded};
 use crate::types::Value;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -25,35 +28,102 @@ pub struct EvaluationResult {
     pub evaluation_time_ns: u64,
     /// Provenance tracker for debugging
     pub provenance: ProvenanceTracker,
+    /// Evaluation stopped early because its token was cancelled; `facts`
+    /// is then only part of the fixpoint
+    pub cancelled: bool,
+    /// Evaluation stopped early because a predicate grew past its limit;
+    /// `facts` is then only part of the fixpoint
+    pub exceeded: Option<LimitExceeded>,
 }
 
+/// Substitutions joined between cancellation checks
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
 /// Semi-naive Datalog evaluator
 pub struct Evaluator {
-    /// Rules to evaluate
-    rules: Vec<Rule>,
+    /// Rules to evaluate, grouped by stratum
+    strata: Arc<Vec<Vec<Rule>>>,
     /// Fact store for querying
     fact_store: Arc<FactStore>,
     /// Whether to track provenance
     track_provenance: bool,
+    /// Checked between iterations and while joining
+    cancel: CancellationToken,
+    /// Caps on how many facts predicates may hold
+    limits: Arc<CardinalityLimits>,
+}
+
+/// New facts a rule under a cardinality limit has derived in one
+/// application, and how many more its predicate may take
+struct Quota<'a> {
+    heads: HashSet<Fact>,
+    budget: usize,
+    /// Facts derived earlier in the iteration, already counted
+    pending: &'a HashSet<Fact>,
+}
+
+impl Quota<'_> {
+    /// Ground `subs` into `rule`'s new head facts, returning whether the
+    /// budget still holds
+    fn absorb(
+        &mut self,
+        rule: &Rule,
+        subs: &mut Vec<Substitution>,
+        accumulated: &HashSet<Fact>,
+    ) -> bool {
+        for sub in subs.drain(..) {
+            let head = rule
+                .complete(&sub)
+                .and_then(|sub| ground_atom(&rule.head, &sub));
+            if let Some(fact) =
+                head.filter(|fact| !accumulated.contains(fact) && !self.pending.contains(fact))
+            {
+                self.heads.insert(fact);
+            }
+        }
+        self.heads.len() <= self.budget
+    }
 }
 
 impl Evaluator {
     /// Create a new evaluator
     pub fn new(rules: Vec<Rule>, fact_store: Arc<FactStore>) -> Self {
+        Self::from_strata(Arc::new(stratify(&rules)), fact_store)
+    }
+
+    /// Create an evaluator for rules already grouped by [`stratify`], so
+    /// evaluations of the same rules share the work
+    pub fn from_strata(strata: Arc<Vec<Vec<Rule>>>, fact_store: Arc<FactStore>) -> Self {
         Evaluator {
-            rules,
+            strata,
             fact_store,
             track_provenance: false,
+            cancel: CancellationToken::default(),
+            limits: Arc::default(),
         }
     }
 
     /// Create a new evaluator with provenance tracking
     pub fn with_provenance(rules: Vec<Rule>, fact_store: Arc<FactStore>) -> Self {
-        Evaluator {
-            rules,
-            fact_store,
-            track_provenance: true,
-        }
+        Self::new(rules, fact_store).tracking_provenance(true)
+    }
+
+    /// Record how each fact was derived if `track` is set
+    pub fn tracking_provenance(mut self, track: bool) -> Self {
+        self.track_provenance = track;
+        self
+    }
+
+    /// Stop evaluating once `cancel` is cancelled
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Stop evaluating once a predicate grows past its limit
+    pub fn with_limits(mut self, limits: Arc<CardinalityLimits>) -> Self {
+        self.limits = limits;
+        self
     }
 
     /// Evaluate a specific query using Magic Sets optimization for goal-directed evaluation
@@ -62,11 +132,14 @@ impl Evaluator {
         let start = Instant::now();
 
         // Transform rules using Magic Sets
-        let mut transformer = MagicSetsTransformer::new(self.rules.clone());
+        let rules = self.strata.iter().flatten().cloned().collect();
+        let mut transformer = MagicSetsTransformer::new(rules);
         let transformed_rules = transformer.transform(&query);
 
         // Create a new evaluator with transformed rules
-        let goal_directed_evaluator = Evaluator::new(transformed_rules, self.fact_store.clone());
+        let goal_directed_evaluator = Evaluator::new(transformed_rules, self.fact_store.clone())
+            .with_cancellation(self.cancel.clone())
+            .with_limits(self.limits.clone());
 
         // Run normal evaluation on transformed rules
         let mut result = goal_directed_evaluator.evaluate();
@@ -87,15 +160,22 @@ impl Evaluator {
         let start = Instant::now();
         let mut iteration_count = 0;
         let mut provenance = ProvenanceTracker::new(self.track_provenance);
+        let mut cancelled = false;
+        let mut exceeded = None;
 
-        // Separate rules by stratum for stratified negation
-        let strata = self.stratify_rules();
+        // Rules are grouped by stratum for stratified negation
+        let strata = self.strata.clone();
 
         // All accumulated facts across all strata
         let mut all_accumulated: HashSet<Fact> = HashSet::new();
 
         // Process each stratum in order
         for stratum_rules in strata.iter() {
+            if self.cancel.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+
             // Separate facts from rules
             let (fact_rules, non_fact_rules): (Vec<_>, Vec<_>) =
                 stratum_rules.iter().partition(|r| r.is_fact());
@@ -130,30 +210,76 @@ impl Evaluator {
                 continue;
             }
 
+            // Sizes of the limited predicates, kept up to date as facts are
+            // derived
+            let mut counts: HashMap<Arc<str>, usize> = HashMap::new();
+            if !self.limits.is_empty() {
+                for fact in &accumulated {
+                    if self.limits.get(&fact.predicate).is_some() {
+                        *counts.entry(fact.predicate.clone()).or_default() += 1;
+                    }
+                }
+            }
+
             // Iterate until fixpoint for this stratum
             loop {
+                if self.cancel.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
                 iteration_count += 1;
                 let mut new_delta: HashSet<Fact> = HashSet::new();
 
                 // Apply each non-fact rule in the stratum
                 for (rule_idx, rule) in non_fact_rules.iter().enumerate() {
-                    let derived = self.apply_rule_semi_naive(rule, &accumulated, &delta);
-
-                    // Record provenance for derived facts
-                    for fact in &derived {
-                        // Get premises from the rule body (simplified for now)
-                        // In a full implementation, we'd track which specific facts matched
-                        let rule_name = format!("{}", rule.head.predicate);
-                        let premises: Vec<Fact> =
-                            delta.iter().take(rule.body.len()).cloned().collect();
-                        provenance.record_derived(fact.clone(), rule_name, rule_idx, premises);
+                    let predicate = &rule.head.predicate;
+                    let limit = self.limits.get(predicate);
+                    let budget = limit.map(|limit| {
+                        limit.saturating_sub(counts.get(predicate).copied().unwrap_or(0))
+                    });
+                    let Some(derived) =
+                        self.apply_rule_semi_naive(rule, &accumulated, &delta, &new_delta, budget)
+                    else {
+                        exceeded =
+                            limit.map(|limit| LimitExceeded::by_rule(predicate, limit, rule));
+                        break;
+                    };
+
+                    // Record provenance for newly derived facts, with the
+                    // body facts of one match that derives them
+                    if self.track_provenance {
+                        for fact in &derived {
+                            if accumulated.contains(fact) || new_delta.contains(fact) {
+                                continue;
+                            }
+                            let mut premises = Vec::new();
+                            prove(
+                                rule,
+                                fact,
+                                &rule.body,
+                                &Substitution::new(),
+                                &accumulated,
+                                &mut premises,
+                            );
+                            provenance.record_derived(
+                                fact.clone(),
+                                rule.to_string(),
+                                rule_idx,
+                                premises,
+                            );
+                        }
                     }
 
-                    new_delta.extend(derived);
+                    for fact in derived {
+                        if !accumulated.contains(&fact) && new_delta.insert(fact) && limit.is_some()
+                        {
+                            *counts.entry(predicate.clone()).or_default() += 1;
+                        }
+                    }
+                }
+                if exceeded.is_some() {
+                    break;
                 }
-
-                // Remove facts already in accumulated
-                new_delta.retain(|f| !accumulated.contains(f));
 
                 // Check for fixpoint
                 if new_delta.is_empty() {
@@ -173,6 +299,9 @@ impl Evaluator {
 
             // Update global accumulated facts
             all_accumulated = accumulated;
+            if cancelled || exceeded.is_some() {
+                break;
+            }
         }
 
         EvaluationResult {
@@ -180,71 +309,90 @@ impl Evaluator {
             iterations: iteration_count,
             evaluation_time_ns: start.elapsed().as_nanos() as u64,
             provenance,
+            cancelled,
+            exceeded,
         }
     }
 
     /// Apply a rule using semi-naive evaluation
     /// Only consider atoms where at least one matches facts from delta
+    ///
+    /// With a `budget`, the rule may derive at most that many facts that
+    /// are neither accumulated nor `pending`; `None` means it derived more.
     fn apply_rule_semi_naive(
         &self,
         rule: &Rule,
         accumulated: &HashSet<Fact>,
         delta: &HashSet<Fact>,
-    ) -> Vec<Fact> {
+        pending: &HashSet<Fact>,
+        budget: Option<usize>,
+    ) -> Option<Vec<Fact>> {
         // Facts (no body atoms)
         if rule.is_fact() {
-            if let Some(fact) = self.atom_to_fact(&rule.head) {
-                return vec![fact];
-            }
-            return vec![];
+            return Some(self.atom_to_fact(&rule.head).into_iter().collect());
         }
 
         // Rules with body atoms
         let mut results = Vec::new();
+        let mut quota = budget.map(|budget| Quota {
+            heads: HashSet::new(),
+            budget,
+            pending,
+        });
 
         // Try each combination where at least one body atom uses delta
         for delta_index in 0..rule.body.len() {
-            let derived = self.apply_rule_with_delta_at(rule, accumulated, delta, delta_index);
+            let derived = self.apply_rule_with_delta_at(
+                rule,
+                accumulated,
+                delta,
+                delta_index,
+                quota.as_mut(),
+            )?;
             results.extend(derived);
         }
 
-        results
+        match quota {
+            Some(quota) => Some(quota.heads.into_iter().collect()),
+            None => Some(results),
+        }
     }
 
     /// Apply a rule where the atom at delta_index uses delta facts
+    ///
+    /// Under a `quota`, new head facts go into it instead, grounded while
+    /// the last body atom is joined so an exploding join stops early;
+    /// `None` means the quota ran out.
     fn apply_rule_with_delta_at(
         &self,
         rule: &Rule,
         accumulated: &HashSet<Fact>,
         delta: &HashSet<Fact>,
         delta_index: usize,
-    ) -> Vec<Fact> {
+        mut quota: Option<&mut Quota<'_>>,
+    ) -> Option<Vec<Fact>> {
         // Get all existing facts from fact store
         let all_facts = self.fact_store.all_facts();
-        let fact_vec: Vec<Fact> = all_facts
-            .iter()
-            .chain(accumulated.iter())
-            .cloned()
-            .collect();
+        let known = || all_facts.iter().chain(accumulated.iter());
 
         // Start with empty substitutions
-        let mut current_subs = vec![Substitution::new()];
+        let mut current_subs = scratch::take_substitutions();
+        current_subs.push(Substitution::new());
 
         // Process each body atom
         for (index, body_atom) in rule.body.iter().enumerate() {
-            let mut next_subs = Vec::new();
+            let mut next_subs = scratch::take_substitutions();
 
             // Handle negation
             if body_atom.negated {
                 // For negated atoms, check against ALL facts (not just delta/accumulated)
                 // This ensures negation is checked against the complete knowledge base
-                for sub in current_subs {
+                for sub in current_subs.drain(..) {
                     let grounded = body_atom.apply_substitution(&sub);
 
                     // Check if any fact unifies with this grounded atom
-                    let has_match = fact_vec
-                        .iter()
-                        .any(|fact| unify_atom_with_fact(&grounded, fact).is_some());
+                    let has_match =
+                        known().any(|fact| unify_atom_with_fact(&grounded, fact).is_some());
 
                     if !has_match {
                         // No match found, so negation succeeds
@@ -252,40 +400,80 @@ impl Evaluator {
                     }
                 }
             } else {
-                // Choose fact source based on whether this is the delta index
-                let fact_source: Vec<_> = if index == delta_index {
-                    delta.iter().collect()
-                } else {
-                    fact_vec.iter().collect()
-                };
-
-                // Positive atom: find all unifications
-                for sub in current_subs {
-                    let partial_atom = body_atom.apply_substitution(&sub);
-
-                    for fact in &fact_source {
+                // A limited rule's heads are counted as the last atom is
+                // joined, before the join can grow without bound
+                let mut quota = quota
+                    .as_deref_mut()
+                    .filter(|_| index == rule.body.len() - 1);
+                let mut over = false;
+
+                // Positive atom: find all unifications, drawing from delta
+                // facts at the delta index
+                for (joined, sub) in current_subs.iter().enumerate() {
+                    // Large joins are where a cancelled evaluation lingers;
+                    // dropping the partial join leaves the outer loop to stop
+                    if joined % CANCEL_CHECK_INTERVAL == CANCEL_CHECK_INTERVAL - 1
+                        && self.cancel.is_cancelled()
+                    {
+                        next_subs.clear();
+                        break;
+                    }
+                    let partial_atom = body_atom.apply_substitution(sub);
+                    let mut extend = |fact: &Fact| {
+                        if over {
+                            return;
+                        }
                         if let Some(new_bindings) = unify_atom_with_fact(&partial_atom, fact) {
                             if let Some(merged) = sub.merge(&new_bindings) {
                                 next_subs.push(merged);
                             }
                         }
+                        if let Some(quota) = quota.as_deref_mut() {
+                            if next_subs.len() >= CANCEL_CHECK_INTERVAL {
+                                over = !quota.absorb(rule, &mut next_subs, accumulated);
+                            }
+                        }
+                    };
+
+                    if index == delta_index {
+                        delta.iter().for_each(&mut extend);
+                    } else {
+                        known().for_each(&mut extend);
+                    }
+                    if over {
+                        break;
                     }
                 }
+                if over {
+                    scratch::recycle_substitutions(current_subs);
+                    scratch::recycle_substitutions(next_subs);
+                    return None;
+                }
             }
 
-            current_subs = next_subs;
+            scratch::recycle_substitutions(std::mem::replace(&mut current_subs, next_subs));
 
             // Early termination if no substitutions remain
             if current_subs.is_empty() {
-                return vec![];
+                scratch::recycle_substitutions(current_subs);
+                return Some(vec![]);
             }
         }
 
+        if let Some(quota) = quota {
+            let within = quota.absorb(rule, &mut current_subs, accumulated);
+            scratch::recycle_substitutions(current_subs);
+            return within.then(Vec::new);
+        }
+
         // Generate head facts from successful substitutions
-        current_subs
+        let derived = current_subs
             .iter()
-            .filter_map(|sub| ground_atom(&rule.head, sub))
-            .collect()
+            .filter_map(|sub| rule.complete(sub))
+            .filter_map(|sub| ground_atom(&rule.head, &sub))
+            .collect();
+        scratch::recycle_substitutions(current_subs);
+        Some(derived)
     }
 
     /// Convert an atom to a fact (if it's ground)
@@ -302,76 +490,118 @@ impl Evaluator {
 
         Some(Fact::new(atom.predicate.as_ref().to_string(), args))
     }
+}
+
+/// Find a match of `body`, extending `sub`, under which `rule` derives
+/// `fact`, pushing the facts its positive atoms matched onto `premises`
+///
+/// Only used for provenance, so matches are searched for directly rather
+/// than recorded during evaluation.
+fn prove(
+    rule: &Rule,
+    fact: &Fact,
+    body: &[Atom],
+    sub: &Substitution,
+    known: &HashSet<Fact>,
+    premises: &mut Vec<Fact>,
+) -> bool {
+    let Some((atom, rest)) = body.split_first() else {
+        return rule
+            .complete(sub)
+            .and_then(|sub| ground_atom(&rule.head, &sub))
+            .is_some_and(|head| head == *fact);
+    };
+    let partial = atom.apply_substitution(sub);
+    if atom.negated {
+        return !known
+            .iter()
+            .any(|known| unify_atom_with_fact(&partial, known).is_some())
+            && prove(rule, fact, rest, sub, known, premises);
+    }
+    for candidate in known {
+        let Some(merged) =
+            unify_atom_with_fact(&partial, candidate).and_then(|bindings| sub.merge(&bindings))
+        else {
+            continue;
+        };
+        premises.push(candidate.clone());
+        if prove(rule, fact, rest, &merged, known, premises) {
+            return true;
+        }
+        premises.pop();
+    }
+    false
+}
 
-    /// Stratify rules based on dependencies and negation
-    fn stratify_rules(&self) -> Vec<Vec<Rule>> {
-        // Build dependency graph
-        let mut graph: HashMap<Arc<str>, Vec<Arc<str>>> = HashMap::new();
-        let mut negated_deps: HashSet<(Arc<str>, Arc<str>)> = HashSet::new();
+/// Group rules into strata based on dependencies and negation, setting
+/// each rule's `stratum`
+pub fn stratify(rules: &[Rule]) -> Vec<Vec<Rule>> {
+    // Build dependency graph
+    let mut graph: HashMap<Arc<str>, Vec<Arc<str>>> = HashMap::new();
+    let mut negated_deps: HashSet<(Arc<str>, Arc<str>)> = HashSet::new();
 
-        for rule in &self.rules {
-            let head_pred = rule.head.predicate.clone();
+    for rule in rules {
+        let head_pred = rule.head.predicate.clone();
 
-            for body_atom in &rule.body {
-                let body_pred = body_atom.predicate.clone();
+        for body_atom in &rule.body {
+            let body_pred = body_atom.predicate.clone();
 
-                graph
-                    .entry(head_pred.clone())
-                    .or_default()
-                    .push(body_pred.clone());
+            graph
+                .entry(head_pred.clone())
+                .or_default()
+                .push(body_pred.clone());
 
-                if body_atom.negated {
-                    negated_deps.insert((head_pred.clone(), body_pred));
-                }
+            if body_atom.negated {
+                negated_deps.insert((head_pred.clone(), body_pred));
             }
         }
+    }
 
-        // Compute strata using simple topological sort
-        let mut strata: Vec<Vec<Rule>> = Vec::new();
-        let mut assigned: HashMap<Arc<str>, usize> = HashMap::new();
+    // Compute strata using simple topological sort
+    let mut strata: Vec<Vec<Rule>> = Vec::new();
+    let mut assigned: HashMap<Arc<str>, usize> = HashMap::new();
 
-        // Assign stratum to each predicate
-        for rule in &self.rules {
-            let pred = &rule.head.predicate;
+    // Assign stratum to each predicate
+    for rule in rules {
+        let pred = &rule.head.predicate;
 
-            if assigned.contains_key(pred) {
-                continue;
-            }
+        if assigned.contains_key(pred) {
+            continue;
+        }
 
-            // Compute stratum based on dependencies
-            let mut max_stratum = 0;
+        // Compute stratum based on dependencies
+        let mut max_stratum = 0;
 
-            for body_atom in &rule.body {
-                let dep_pred = &body_atom.predicate;
+        for body_atom in &rule.body {
+            let dep_pred = &body_atom.predicate;
 
-                if let Some(&dep_stratum) = assigned.get(dep_pred) {
-                    let stratum = if body_atom.negated {
-                        dep_stratum + 1 // Negated deps must be in lower stratum
-                    } else {
-                        dep_stratum
-                    };
-                    max_stratum = max_stratum.max(stratum);
-                }
+            if let Some(&dep_stratum) = assigned.get(dep_pred) {
+                let stratum = if body_atom.negated {
+                    dep_stratum + 1 // Negated deps must be in lower stratum
+                } else {
+                    dep_stratum
+                };
+                max_stratum = max_stratum.max(stratum);
             }
-
-            assigned.insert(pred.clone(), max_stratum);
         }
 
-        // Group rules by stratum
-        let max_stratum = assigned.values().max().copied().unwrap_or(0);
+        assigned.insert(pred.clone(), max_stratum);
+    }
 
-        for _ in 0..=max_stratum {
-            strata.push(Vec::new());
-        }
+    // Group rules by stratum
+    let max_stratum = assigned.values().max().copied().unwrap_or(0);
 
-        for mut rule in self.rules.clone() {
-            let stratum = assigned.get(&rule.head.predicate).copied().unwrap_or(0);
-            rule.stratum = stratum;
-            strata[stratum].push(rule);
-        }
+    for _ in 0..=max_stratum {
+        strata.push(Vec::new());
+    }
 
-        strata
+    for mut rule in rules.iter().cloned() {
+        let stratum = assigned.get(&rule.head.predicate).copied().unwrap_or(0);
+        rule.stratum = stratum;
+        strata[stratum].push(rule);
     }
+
+    strata
 }
 
 #[cfg(test)]
@@ -467,6 +697,84 @@ mod tests {
         assert_eq!(path_facts.len(), 3);
     }
 
+    #[test]
+    fn test_evaluation_stops_when_cancelled() {
+        use std::time::Duration;
+
+        let fact_store = Arc::new(FactStore::new());
+        for i in 0..300 {
+            fact_store.add_fact(Fact::binary(
+                "edge",
+                Value::Integer(i),
+                Value::Integer(i + 1),
+            ));
+        }
+        let rules = vec![
+            Rule::new(
+                Atom::new("path", vec![Term::var("X"), Term::var("Y")]),
+                vec![Atom::new("edge", vec![Term::var("X"), Term::var("Y")])],
+            ),
+            Rule::new(
+                Atom::new("path", vec![Term::var("X"), Term::var("Z")]),
+                vec![
+                    Atom::new("path", vec![Term::var("X"), Term::var("Y")]),
+                    Atom::new("edge", vec![Term::var("Y"), Term::var("Z")]),
+                ],
+            ),
+        ];
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let result = Evaluator::new(rules.clone(), fact_store.clone())
+            .with_cancellation(cancel)
+            .evaluate();
+        assert!(result.cancelled);
+        assert_eq!(result.iterations, 0);
+
+        // A deadline cuts the 300-step closure short
+        let deadline = CancellationToken::with_deadline(Instant::now() + Duration::from_millis(5));
+        let result = Evaluator::new(rules, fact_store)
+            .with_cancellation(deadline)
+            .evaluate();
+        assert!(result.cancelled);
+        assert!(result.iterations < 300);
+    }
+
+    #[test]
+    fn test_cardinality_limit_stops_exploding_join() {
+        let fact_store = Arc::new(FactStore::new());
+        for i in 0..300 {
+            fact_store.add_fact(Fact::unary("node", Value::Integer(i)));
+        }
+        // pair(X, Y) :- node(X), node(Y), a 90,000 fact cartesian product
+        let rules = vec![Rule::new(
+            Atom::new("pair", vec![Term::var("X"), Term::var("Y")]),
+            vec![
+                Atom::new("node", vec![Term::var("X")]),
+                Atom::new("node", vec![Term::var("Y")]),
+            ],
+        )];
+        let limits =
+            |limit: usize| Arc::new(CardinalityLimits::new([("pair".to_string(), limit)].into()));
+
+        let result = Evaluator::new(rules.clone(), fact_store.clone())
+            .with_limits(limits(5_000))
+            .evaluate();
+        let exceeded = result.exceeded.unwrap();
+        assert_eq!(exceeded.predicate, "pair");
+        assert_eq!(exceeded.limit, 5_000);
+        assert_eq!(exceeded.rule, "pair(X, Y) :- node(X), node(Y).");
+        // Stopped before deriving the product
+        assert!(result.facts.len() < 10_000);
+
+        // Exactly at the limit is fine
+        let result = Evaluator::new(rules, fact_store)
+            .with_limits(limits(90_000))
+            .evaluate();
+        assert!(result.exceeded.is_none());
+        assert_eq!(result.facts.len(), 90_300);
+    }
+
     #[test]
     fn test_goal_directed_evaluation_with_magic_sets() {
         use super::Query;
@@ -195,6 +195,8 @@ impl IncrementalEvaluator {
                     iterations: 0,
                     evaluation_time_ns: 0,
                     provenance: ProvenanceTracker::new(false),
+                    cancelled: false,
+                    exceeded: None,
                 },
                 delta: Delta::empty(),
                 generation: self.generation,
@@ -273,6 +275,8 @@ impl IncrementalEvaluator {
             iterations: delta_result.iterations,
             evaluation_time_ns: delta_result.evaluation_time_ns,
             provenance: delta_result.provenance,
+            cancelled: false,
+            exceeded: None,
         };
 
         (result, derived_delta)
@@ -331,9 +335,12 @@ pub struct IncrementalStats {
 }
 
 /// Compute difference between two fact sets
-pub fn compute_fact_diff(old: &[Fact], new: &[Fact]) -> Delta {
-    let old_set: HashSet<_> = old.iter().cloned().collect();
-    let new_set: HashSet<_> = new.iter().cloned().collect();
+pub fn compute_fact_diff<'a>(
+    old: impl IntoIterator<Item = &'a Fact>,
+    new: impl IntoIterator<Item = &'a Fact>,
+) -> Delta {
+    let old_set: HashSet<_> = old.into_iter().cloned().collect();
+    let new_set: HashSet<_> = new.into_iter().cloned().collect();
     Delta::from_sets(&old_set, &new_set)
 }
 
@@ -359,7 +366,10 @@ mod tests {
                 terms: vec![Term::Variable("X".to_string())],
                 negated: false,
             }],
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0,
+            annotations: Default::default(),
         }
     }
 
@@ -171,7 +171,11 @@ impl MagicSetsTransformer {
         };
 
         // Body atoms remain the same for now (will be adorned later)
-        Rule::new(adorned_head, rule.body.clone())
+        Rule {
+            guards: rule.guards.clone(),
+            builtins: rule.builtins.clone(),
+            ..Rule::new(adorned_head, rule.body.clone())
+        }
     }
 
     /// Compute binding pattern for a body atom
@@ -331,7 +335,11 @@ impl MagicSetsTransformer {
             let mut new_body = vec![magic_atom];
             new_body.extend(rule.body.clone());
 
-            result.push(Rule::new(rule.head.clone(), new_body));
+            result.push(Rule {
+                guards: rule.guards.clone(),
+                builtins: rule.builtins.clone(),
+                ..Rule::new(rule.head.clone(), new_body)
+            });
         }
         result
     }
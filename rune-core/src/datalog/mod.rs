@@ -24,14 +24,19 @@
 pub mod aggregation;
 pub mod backends;
 pub mod bridge;
+pub mod cancel;
+pub mod dataflow;
 pub mod diagnostics;
 pub mod evaluation;
 pub mod incremental;
 pub mod lattice;
 pub mod magic_sets;
+pub mod near_miss;
 pub mod planner;
 pub mod provenance;
+pub mod scratch;
 pub mod semi_naive;
+pub mod stream;
 pub mod types;
 pub mod unification;
 pub mod wcoj;
@@ -42,8 +47,10 @@ pub use backends::{
     BackendType, HashBackend, RelationBackend, TrieBackend, UnionFindBackend, VecBackend,
 };
 pub use bridge::CedarDatalogBridge;
+pub use cancel::{CancellationToken, DropGuard};
+pub use dataflow::{DataflowEvaluator, DataflowStats, Operator};
 pub use diagnostics::{DatalogDiagnostics, Diagnostic, DiagnosticBag, Severity, Span, Suggestion};
-pub use evaluation::{EvaluationResult, Evaluator};
+pub use evaluation::{stratify, EvaluationResult, Evaluator};
 pub use incremental::{
     compute_fact_diff, Delta, IncrementalEvaluator, IncrementalResult, IncrementalStats,
 };
@@ -51,33 +58,244 @@ pub use lattice::{
     BoolLattice, CounterLattice, Lattice, LatticeValue, MaxLattice, MinLattice, SetLattice,
 };
 pub use magic_sets::{MagicSetsTransformer, Query};
+pub use near_miss::{near_misses, MissReason, NearMiss};
 pub use planner::{AtomAnalysis, PredicateStats, QueryPlan, QueryPlanner};
-pub use provenance::{ProofTree, ProvenanceQuery, ProvenanceTracker};
-pub use types::{AggregateAtom, AggregateOp, Atom, Rule, Substitution, Term};
+pub use provenance::{ProofNode, ProofTree, ProvenanceQuery, ProvenanceTracker};
+pub use stream::{FactQuery, FactStream};
+pub use types::{AggregateAtom, AggregateOp, Atom, CompareOp, Guard, Rule, Substitution, Term};
 pub use unification::{find_matching_facts, ground_atom, unify_atom_with_fact, unify_atoms};
 pub use wcoj::{LeapfrogIterator, LeapfrogJoin, TrieNode, WCOJIndex};
 
-use crate::engine::{AuthorizationResult, Decision};
-use crate::error::Result;
-use crate::facts::FactStore;
+use crate::decision::{AuthorizationResult, Decision};
+use crate::error::{RUNEError, Result};
+use crate::fact_vec::FactVec;
+use crate::facts::{Fact, FactStore};
+use crate::flags::RuleFlags;
+use crate::limits::{CardinalityLimits, LimitExceeded};
+use crate::replica::{ChangePosition, FactChange};
 use crate::request::Request;
+use crate::scopes::FactScopes;
+use crate::types::Value;
+use arc_swap::ArcSwapOption;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::sync::Arc;
 use std::time::Instant;
 
+/// Strategy used to compute the Datalog fixpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvaluationBackend {
+    /// Re-run semi-naive evaluation on every request (small/medium fact sets)
+    #[default]
+    Interpreter,
+    /// Keep an incremental operator DAG and feed it the changes recorded in
+    /// the fact store's change log, which selecting it enables
+    Dataflow,
+}
+
+/// Settings from an `[evaluation]` section
+///
+/// ```toml
+/// backend = "dataflow"
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvaluationConfig {
+    /// Backend computing the fixpoint
+    #[serde(default)]
+    pub backend: EvaluationBackend,
+}
+
+impl EvaluationConfig {
+    /// Parse the TOML body of an `[evaluation]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        toml::from_str(input).map_err(|e| {
+            RUNEError::ParseError(format!("Failed to parse evaluation section: {}", e))
+        })
+    }
+}
+
+/// Changes the dataflow backend can fall behind the fact store by before
+/// it has to reload every fact, when it enables the change log itself
+pub const DATAFLOW_CHANGE_LOG: usize = 65_536;
+
+/// Operator DAG and the change log position it was last synced to
+struct DataflowState {
+    evaluator: DataflowEvaluator,
+    position: ChangePosition,
+}
+
+/// Output of the operator DAG as of a fact store version
+struct DataflowOutput {
+    version: u64,
+    facts: FactVec,
+    exceeded: Option<LimitExceeded>,
+}
+
+/// Dataflow backend state shared by the engines evaluating the same rules
+///
+/// Evaluations read the published output without locking; only the one
+/// that finds it behind the fact store takes the lock to sync it.
+#[derive(Default)]
+struct Dataflow {
+    state: Mutex<Option<DataflowState>>,
+    output: ArcSwapOption<DataflowOutput>,
+}
+
+impl Dataflow {
+    /// Output that covers fact store version `version`, if published
+    fn current(&self, version: u64) -> Option<Arc<DataflowOutput>> {
+        self.output
+            .load_full()
+            .filter(|output| output.version >= version)
+    }
+}
+
+/// Facts of a computed fixpoint
+enum Derived {
+    /// Derived by one interpreter run
+    Owned(Vec<Fact>),
+    /// Published by the dataflow backend and shared between evaluations
+    Shared(FactVec),
+}
+
+impl Derived {
+    fn len(&self) -> usize {
+        match self {
+            Derived::Owned(facts) => facts.len(),
+            Derived::Shared(facts) => facts.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Fact> + '_> {
+        match self {
+            Derived::Owned(facts) => Box::new(facts.iter()),
+            Derived::Shared(facts) => Box::new(facts.iter()),
+        }
+    }
+
+    fn into_vec(self) -> Vec<Fact> {
+        match self {
+            Derived::Owned(facts) => facts,
+            Derived::Shared(facts) => facts.to_vec(),
+        }
+    }
+}
+
+/// A fixpoint computed by either backend
+struct Fixpoint {
+    facts: Derived,
+    iterations: usize,
+    provenance: ProvenanceTracker,
+}
+
 /// Datalog evaluation engine
 pub struct DatalogEngine {
     /// Compiled Datalog rules
     rules: Arc<Vec<Rule>>,
+    /// Rules that are switched on (shares `rules` when none are disabled)
+    active: Arc<Vec<Rule>>,
+    /// `active` grouped by stratum, once when the rules are loaded rather
+    /// than per evaluation
+    strata: Arc<Vec<Vec<Rule>>>,
     /// Fact store reference
     fact_store: Arc<FactStore>,
+    /// Selected evaluation backend
+    backend: EvaluationBackend,
+    /// Dataflow state, built on first use when that backend is selected
+    dataflow: Arc<Dataflow>,
+    /// Fact views that `@scope` rules are confined to
+    scopes: Arc<FactScopes>,
+    /// Stops evaluation early when cancelled
+    cancel: CancellationToken,
+    /// Caps on how many facts predicates may hold
+    limits: Arc<CardinalityLimits>,
 }
 
 impl DatalogEngine {
     /// Create a new Datalog engine with rules
     pub fn new(rules: Vec<Rule>, fact_store: Arc<FactStore>) -> Self {
+        let rules = Arc::new(rules);
+        let active = active_rules(&rules, &RuleFlags::new());
         DatalogEngine {
-            rules: Arc::new(rules),
+            strata: Arc::new(stratify(&active)),
+            active,
+            rules,
             fact_store,
+            backend: EvaluationBackend::default(),
+            dataflow: Arc::default(),
+            scopes: Arc::new(FactScopes::default()),
+            cancel: CancellationToken::default(),
+            limits: Arc::default(),
+        }
+    }
+
+    /// Select the evaluation backend
+    ///
+    /// Selecting [`EvaluationBackend::Dataflow`] enables the fact store's
+    /// change log, keeping [`DATAFLOW_CHANGE_LOG`] changes unless it was
+    /// already enabled.
+    pub fn with_backend(mut self, backend: EvaluationBackend) -> Self {
+        if backend == EvaluationBackend::Dataflow {
+            self.fact_store.enable_change_log(DATAFLOW_CHANGE_LOG);
+        }
+        self.backend = backend;
+        self
+    }
+
+    /// Evaluation backend in use
+    pub fn backend(&self) -> EvaluationBackend {
+        self.backend
+    }
+
+    /// Work counters of the dataflow backend, once it has evaluated
+    pub fn dataflow_stats(&self) -> Option<DataflowStats> {
+        let state = self.dataflow.state.lock();
+        state.as_ref().map(|state| state.evaluator.stats())
+    }
+
+    /// Confine `@scope` rules to the given fact views
+    pub fn with_scopes(mut self, scopes: Arc<FactScopes>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// Fact scopes in use
+    pub fn scopes(&self) -> &FactScopes {
+        &self.scopes
+    }
+
+    /// Fail evaluations in which a predicate grows past its limit (see
+    /// [`crate::limits`])
+    pub fn with_limits(mut self, limits: Arc<CardinalityLimits>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Cardinality limits in use
+    pub fn limits(&self) -> &Arc<CardinalityLimits> {
+        &self.limits
+    }
+
+    /// Create an engine sharing these rules and state whose evaluations
+    /// stop with [`RUNEError::Cancelled`] once `cancel` is cancelled
+    pub fn with_cancellation(&self, cancel: CancellationToken) -> Self {
+        DatalogEngine {
+            rules: self.rules.clone(),
+            active: self.active.clone(),
+            strata: self.strata.clone(),
+            fact_store: self.fact_store.clone(),
+            backend: self.backend,
+            dataflow: self.dataflow.clone(),
+            scopes: self.scopes.clone(),
+            cancel,
+            limits: self.limits.clone(),
         }
     }
 
@@ -87,15 +305,14 @@ impl DatalogEngine {
     }
 
     /// Evaluate a request against Datalog rules
-    pub fn evaluate(&self, _request: &Request, _facts: &FactStore) -> Result<AuthorizationResult> {
+    ///
+    /// An explained request (see [`Request::explain`]) also gets the proof
+    /// tree of every derived fact and the near misses of the other rules.
+    pub fn evaluate(&self, request: &Request, _facts: &FactStore) -> Result<AuthorizationResult> {
         let start = Instant::now();
 
-        // Create evaluator with current rules
-        // Use the engine's fact store which is already Arc-wrapped
-        let evaluator = Evaluator::new((*self.rules).clone(), self.fact_store.clone());
-
-        // Run evaluation
-        let result = evaluator.evaluate();
+        // Run evaluation with the configured backend
+        let result = self.run(request.explain)?;
 
         // Convert to AuthorizationResult
         // For now, always permit if we have derived facts
@@ -111,7 +328,21 @@ impl DatalogEngine {
             result.facts.len()
         );
 
-        let evaluated_rules: Vec<String> = self.rules.iter().map(|r| format!("{}", r)).collect();
+        let evaluated_rules: Vec<String> = self.active.iter().map(|r| format!("{}", r)).collect();
+
+        let near_misses = if request.explain {
+            let focus = [
+                Value::String(request.principal.entity.id.clone()),
+                Value::String(request.resource.entity.id.clone()),
+            ];
+            near_misses(
+                &self.active,
+                &result.facts.iter().cloned().collect::<Vec<_>>(),
+                &focus,
+            )
+        } else {
+            Vec::new()
+        };
 
         let facts_used: Vec<String> = result
             .facts
@@ -124,25 +355,278 @@ impl DatalogEngine {
             explanation,
             evaluated_rules,
             facts_used,
+            policies_evaluated: 0,
             evaluation_time_ns: start.elapsed().as_nanos() as u64,
             cached: false,
+            coalesced: false,
+            failures: Vec::new(),
+            obligations: Vec::new(),
+            warnings: Vec::new(),
+            reason_codes: Vec::new(),
+            valid_for_ms: 0,
+            proofs: result.provenance.derived_proofs(),
+            near_misses,
         })
     }
 
+    /// Create an engine sharing these rules but reading from another fact store
+    pub fn with_fact_store(&self, fact_store: Arc<FactStore>) -> Self {
+        if self.backend == EvaluationBackend::Dataflow {
+            fact_store.enable_change_log(DATAFLOW_CHANGE_LOG);
+        }
+        DatalogEngine {
+            rules: self.rules.clone(),
+            active: self.active.clone(),
+            strata: self.strata.clone(),
+            fact_store,
+            backend: self.backend,
+            dataflow: Arc::default(),
+            scopes: self.scopes.clone(),
+            cancel: self.cancel.clone(),
+            limits: self.limits.clone(),
+        }
+    }
+
+    /// Create an engine sharing these rules with runtime flags applied
+    pub fn with_flags(&self, flags: &RuleFlags) -> Self {
+        let active = active_rules(&self.rules, flags);
+        let strata = if Arc::ptr_eq(&active, &self.active) {
+            self.strata.clone()
+        } else {
+            Arc::new(stratify(&active))
+        };
+        DatalogEngine {
+            rules: self.rules.clone(),
+            active,
+            strata,
+            fact_store: self.fact_store.clone(),
+            backend: self.backend,
+            dataflow: Arc::default(),
+            scopes: self.scopes.clone(),
+            cancel: self.cancel.clone(),
+            limits: self.limits.clone(),
+        }
+    }
+
     /// Add rules to the engine (for hot-reload)
     pub fn update_rules(&mut self, rules: Vec<Rule>) {
         self.rules = Arc::new(rules);
+        self.active = active_rules(&self.rules, &RuleFlags::new());
+        self.strata = Arc::new(stratify(&self.active));
+        self.dataflow = Arc::default();
     }
 
-    /// Get current rules
+    /// Get current rules, including disabled ones
     pub fn rules(&self) -> &[Rule] {
         &self.rules
     }
 
+    /// Get the rules that take part in evaluation
+    pub fn active_rules(&self) -> &[Rule] {
+        &self.active
+    }
+
     /// Evaluate rules and return derived facts
-    pub fn derive_facts(&self) -> Result<Vec<crate::facts::Fact>> {
-        let evaluator = Evaluator::new((*self.rules).clone(), self.fact_store.clone());
-        let result = evaluator.evaluate();
-        Ok(result.facts)
+    pub fn derive_facts(&self) -> Result<Vec<Fact>> {
+        Ok(self.run(false)?.facts.into_vec())
+    }
+
+    /// Evaluate rules and stream the derived facts matching `query`
+    pub fn stream_facts(&self, query: &FactQuery) -> Result<FactStream> {
+        Ok(match self.run(false)?.facts {
+            Derived::Owned(facts) => FactStream::new(facts, query),
+            // Copy only the facts the query can yield out of the shared set
+            Derived::Shared(facts) => {
+                FactStream::from_matching(facts.iter().filter(|f| query.matches(f)).cloned(), query)
+            }
+        })
+    }
+
+    /// Count the derived facts matching `query` without yielding them
+    pub fn count_facts(&self, query: &FactQuery) -> Result<usize> {
+        let facts = self.run(false)?.facts;
+        let total = facts.iter().filter(|f| query.matches(f)).count();
+        Ok(query.page_len(total))
+    }
+
+    /// Compute the fixpoint with the configured backend, recording how each
+    /// fact was derived if `explain` is set
+    ///
+    /// Scoped rules always go through the interpreter, one partition at a
+    /// time, and so do explained evaluations, since the dataflow backend
+    /// keeps no derivations.
+    fn run(&self, explain: bool) -> Result<Fixpoint> {
+        let result = if self.active.iter().any(|rule| rule.scope().is_some()) {
+            self.run_scoped(explain)
+        } else {
+            match self.backend {
+                _ if explain => self.evaluator(self.strata.clone(), self.fact_store.clone(), true),
+                EvaluationBackend::Interpreter => {
+                    self.evaluator(self.strata.clone(), self.fact_store.clone(), false)
+                }
+                // Dataflow state is updated in place and cannot stop midway
+                EvaluationBackend::Dataflow if self.cancel.is_cancelled() => {
+                    return Err(RUNEError::Cancelled)
+                }
+                EvaluationBackend::Dataflow => return self.run_dataflow(),
+            }
+        };
+        if result.cancelled {
+            return Err(RUNEError::Cancelled);
+        }
+        if let Some(exceeded) = result.exceeded {
+            return Err(exceeded.into());
+        }
+        Ok(Fixpoint {
+            facts: Derived::Owned(result.facts),
+            iterations: result.iterations,
+            provenance: result.provenance,
+        })
+    }
+
+    /// Evaluate stratified rules over `store` with the interpreter
+    fn evaluator(
+        &self,
+        strata: Arc<Vec<Vec<Rule>>>,
+        store: Arc<FactStore>,
+        explain: bool,
+    ) -> EvaluationResult {
+        Evaluator::from_strata(strata, store)
+            .tracking_provenance(explain)
+            .with_cancellation(self.cancel.clone())
+            .with_limits(self.limits.clone())
+            .evaluate()
+    }
+
+    /// Evaluate each scope's rules per partition, then the unscoped rules
+    /// over the stored facts plus everything the scoped rules derived
+    ///
+    /// When explaining, only the unscoped pass records derivations; facts
+    /// the scoped rules derived appear in proofs as base facts.
+    fn run_scoped(&self, explain: bool) -> EvaluationResult {
+        let start = Instant::now();
+        let base = self.fact_store.all_facts();
+
+        let mut scoped: BTreeMap<&str, Vec<Rule>> = BTreeMap::new();
+        let mut unscoped = Vec::new();
+        for rule in self.active.iter() {
+            match rule.scope() {
+                Some(name) => scoped.entry(name).or_default().push(rule.clone()),
+                None => unscoped.push(rule.clone()),
+            }
+        }
+
+        let stored: HashSet<&Fact> = base.iter().collect();
+        let mut derived = HashSet::new();
+        let mut iterations = 0;
+        for (name, rules) in scoped {
+            // Rules naming an undeclared scope see no facts at all
+            let Some(scope) = self.scopes.get(name) else {
+                continue;
+            };
+            let strata = Arc::new(stratify(&rules));
+            for view in scope.partition(&base).into_values() {
+                let store = Arc::new(FactStore::from_facts(view));
+                let result = self.evaluator(strata.clone(), store, false);
+                iterations += result.iterations;
+                if result.cancelled || result.exceeded.is_some() {
+                    return result;
+                }
+                derived.extend(result.facts.into_iter().filter(|f| !stored.contains(f)));
+            }
+        }
+
+        let mut facts = base.to_vec();
+        facts.extend(derived);
+        let store = Arc::new(FactStore::from_facts(facts));
+        let mut result = self.evaluator(Arc::new(stratify(&unscoped)), store, explain);
+        result.iterations += iterations;
+        result.evaluation_time_ns = start.elapsed().as_nanos() as u64;
+        result
+    }
+
+    /// Read the operator DAG's output, first syncing it with the fact store
+    /// if the store changed since it was published
+    fn run_dataflow(&self) -> Result<Fixpoint> {
+        let version = self.fact_store.version();
+        let output = match self.dataflow.current(version) {
+            Some(output) => output,
+            None => self.sync_dataflow(version),
+        };
+        if let Some(exceeded) = &output.exceeded {
+            return Err(exceeded.clone().into());
+        }
+        Ok(Fixpoint {
+            facts: Derived::Shared(output.facts.clone()),
+            iterations: 0,
+            provenance: ProvenanceTracker::new(false),
+        })
+    }
+
+    /// Feed the operator DAG the changes logged since it was last synced
+    /// and publish its output
+    ///
+    /// When the change log no longer reaches back that far, every fact is
+    /// reloaded.
+    fn sync_dataflow(&self, version: u64) -> Arc<DataflowOutput> {
+        let mut guard = self.dataflow.state.lock();
+        // Another evaluation may have synced while this one waited
+        if let Some(output) = self.dataflow.current(version) {
+            return output;
+        }
+
+        let position = guard.as_ref().map(|state| state.position);
+        let (batch, version) = self
+            .fact_store
+            .changes_with_version(position, DATAFLOW_CHANGE_LOG);
+        let state = guard.get_or_insert_with(|| DataflowState {
+            evaluator: DataflowEvaluator::new((*self.active).clone()),
+            position: batch.end(),
+        });
+
+        let mut delta = Delta::empty();
+        let mut reload = batch.reset;
+        for change in &batch.changes {
+            match change {
+                FactChange::Add(fact) => {
+                    delta.removed.remove(fact);
+                    delta.added.insert(fact.clone());
+                }
+                FactChange::Retract(fact) => {
+                    delta.added.remove(fact);
+                    delta.removed.insert(fact.clone());
+                }
+                FactChange::Clear => {
+                    delta = Delta::empty();
+                    reload = true;
+                }
+            }
+        }
+        if reload {
+            state.evaluator.load(&delta.added);
+        } else {
+            state.evaluator.apply(&delta);
+        }
+        state.position = batch.end();
+
+        let evaluator = &state.evaluator;
+        let output = Arc::new(DataflowOutput {
+            version,
+            facts: evaluator.facts(),
+            exceeded: self
+                .limits
+                .check_counts(|predicate| evaluator.count(predicate), &self.active),
+        });
+        self.dataflow.output.store(Some(output.clone()));
+        output
+    }
+}
+
+/// Filter out rules switched off by their annotations or runtime flags
+fn active_rules(rules: &Arc<Vec<Rule>>, flags: &RuleFlags) -> Arc<Vec<Rule>> {
+    let is_active = |rule: &Rule| flags.is_enabled(rule.flag_key(), rule.enabled_by_default());
+    if rules.iter().all(is_active) {
+        return rules.clone();
     }
+    Arc::new(rules.iter().filter(|r| is_active(r)).cloned().collect())
 }
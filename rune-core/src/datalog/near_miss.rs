@@ -0,0 +1,325 @@
+//! Negative provenance: why rules did not fire
+//!
+//! Proof trees ([`super::provenance`]) explain what was derived; for a
+//! denial the question is what was not. [`near_misses`] takes the fixpoint
+//! and, for every rule that derived nothing for the request, searches its
+//! body for the match that got furthest and reports the condition that
+//! stopped it:
+//!
+//! - a positive atom with no matching fact, e.g. `member("alice", "staff")`
+//! - a negated atom whose fact is present
+//! - a builtin call that gave no output
+//! - a comparison that failed, e.g. `"contractor" != "contractor"`
+//!
+//! Matches are steered towards the request: each head variable is tried
+//! bound to the principal and resource IDs, so `reader(U) :- member(U, G)`
+//! reports the missing `member("alice", ?G)` rather than a match for some
+//! other user. A rule whose head can be derived for the request is not a
+//! miss. Scoped rules are left out, since their matches depend on the
+//! partition they are evaluated in.
+
+use super::provenance::fact_text;
+use super::types::{Guard, Rule, Substitution};
+use super::unification::unify_atom_with_fact;
+use crate::facts::Fact;
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Body matches tried per rule and seed before settling for the best so far
+const SEARCH_BUDGET: usize = 10_000;
+
+/// Why the furthest match of a rule body stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissReason {
+    /// No fact matches a positive atom
+    MissingFact,
+    /// A fact matches a negated atom
+    FactPresent,
+    /// A builtin call gave no output
+    FailedBuiltin,
+    /// A comparison does not hold
+    FailedComparison,
+}
+
+/// A rule body that almost matched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NearMiss {
+    /// The rule, in Datalog syntax
+    pub rule: String,
+    /// Facts matched by the body atoms before the failure
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matched: Vec<String>,
+    /// The failed atom, call or comparison, with the variables bound so far
+    /// replaced by their values
+    pub failed: String,
+    /// Why it failed
+    pub reason: MissReason,
+    /// Conditions (atoms, calls and comparisons) satisfied before the failure
+    pub satisfied: usize,
+    /// Conditions in the rule body
+    pub conditions: usize,
+}
+
+impl NearMiss {
+    /// Order by how close the match came, closest first
+    fn closeness(&self, other: &NearMiss) -> Ordering {
+        (other.satisfied * self.conditions)
+            .cmp(&(self.satisfied * other.conditions))
+            .then_with(|| self.rule.cmp(&other.rule))
+    }
+}
+
+/// Near misses of the `rules` that derive nothing for a request about the
+/// `focus` values, given the fixpoint `facts`, closest first
+pub fn near_misses(rules: &[Rule], facts: &[Fact], focus: &[Value]) -> Vec<NearMiss> {
+    let mut misses: Vec<NearMiss> = rules
+        .iter()
+        .filter(|rule| !rule.is_fact() && rule.scope().is_none())
+        .filter_map(|rule| rule_miss(rule, facts, focus))
+        .collect();
+    misses.sort_by(NearMiss::closeness);
+    misses
+}
+
+/// The furthest failed match of `rule`, or `None` if it matches for the
+/// focus
+fn rule_miss(rule: &Rule, facts: &[Fact], focus: &[Value]) -> Option<NearMiss> {
+    let mut best: Option<Search> = None;
+    for seed in seeds(rule, focus) {
+        let mut search = Search::new(rule, facts);
+        search.walk(0, &seed, &mut Vec::new());
+        if search.matched {
+            return None;
+        }
+        if best
+            .as_ref()
+            .is_none_or(|best| search.progress > best.progress)
+        {
+            best = Some(search);
+        }
+    }
+
+    let best = best?;
+    let (failed, reason) = best.failure?;
+    Some(NearMiss {
+        rule: rule.to_string(),
+        matched: best.used,
+        failed,
+        reason,
+        satisfied: best.progress,
+        conditions: rule.body.len() + rule.builtins.len() + rule.guards.len(),
+    })
+}
+
+/// Whether the body of `rule` matches in the fixpoint `facts` with a head
+/// variable bound to one of the `focus` values
+pub(crate) fn matches_focus(rule: &Rule, facts: &[Fact], focus: &[Value]) -> bool {
+    seeds(rule, focus).into_iter().any(|seed| {
+        let mut search = Search::new(rule, facts);
+        search.walk(0, &seed, &mut Vec::new());
+        search.matched
+    })
+}
+
+/// Starting substitutions binding each head variable to each focus value
+fn seeds(rule: &Rule, focus: &[Value]) -> Vec<Substitution> {
+    let mut seeds = Vec::new();
+    for variable in rule.head.variables() {
+        for value in focus {
+            let mut seed = Substitution::new();
+            seed.bind(variable.to_string(), value.clone());
+            seeds.push(seed);
+        }
+    }
+    // Without anything to steer by, any match will do
+    if seeds.is_empty() {
+        seeds.push(Substitution::new());
+    }
+    seeds
+}
+
+/// Depth-first search for a match of one rule body
+struct Search<'a> {
+    rule: &'a Rule,
+    facts: &'a [Fact],
+    budget: usize,
+    /// A full match was found
+    matched: bool,
+    /// Conditions satisfied by the furthest failed match
+    progress: usize,
+    /// Facts the furthest failed match used, rendered
+    used: Vec<String>,
+    /// The condition it failed on
+    failure: Option<(String, MissReason)>,
+}
+
+impl<'a> Search<'a> {
+    fn new(rule: &'a Rule, facts: &'a [Fact]) -> Self {
+        Search {
+            rule,
+            facts,
+            budget: SEARCH_BUDGET,
+            matched: false,
+            progress: 0,
+            used: Vec::new(),
+            failure: None,
+        }
+    }
+
+    /// Match the body from atom `index` on, extending `sub`
+    fn walk(&mut self, index: usize, sub: &Substitution, used: &mut Vec<&'a Fact>) {
+        if self.matched || self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+
+        let Some(atom) = self.rule.body.get(index) else {
+            return self.complete(sub, used);
+        };
+        let partial = atom.apply_substitution(sub);
+        let mut found = self
+            .facts
+            .iter()
+            .filter_map(|fact| Some((fact, unify_atom_with_fact(&partial, fact)?)));
+
+        if atom.negated {
+            if found.next().is_some() {
+                self.fail(index, used, partial.to_string(), MissReason::FactPresent);
+            } else {
+                self.walk(index + 1, sub, used);
+            }
+            return;
+        }
+
+        let mut any = false;
+        for (fact, bindings) in found {
+            let Some(merged) = sub.merge(&bindings) else {
+                continue;
+            };
+            any = true;
+            used.push(fact);
+            self.walk(index + 1, &merged, used);
+            used.pop();
+            if self.matched || self.budget == 0 {
+                return;
+            }
+        }
+        if !any {
+            self.fail(index, used, partial.to_string(), MissReason::MissingFact);
+        }
+    }
+
+    /// Run the builtin calls and comparisons on a body match
+    fn complete(&mut self, sub: &Substitution, used: &[&Fact]) {
+        let atoms = self.rule.body.len();
+        let mut sub = sub.clone();
+        for (i, call) in self.rule.builtins.iter().enumerate() {
+            if !call.apply(&mut sub) {
+                let args: Vec<String> = call
+                    .args
+                    .iter()
+                    .map(|term| sub.apply_to_term(term).to_string())
+                    .collect();
+                let failed = format!("{}({})", call.name, args.join(", "));
+                return self.fail(atoms + i, used, failed, MissReason::FailedBuiltin);
+            }
+        }
+        let calls = atoms + self.rule.builtins.len();
+        for (i, guard) in self.rule.guards.iter().enumerate() {
+            if !guard.holds(&sub) {
+                let failed = Guard::new(
+                    sub.apply_to_term(&guard.left),
+                    guard.op,
+                    sub.apply_to_term(&guard.right),
+                );
+                return self.fail(
+                    calls + i,
+                    used,
+                    failed.to_string(),
+                    MissReason::FailedComparison,
+                );
+            }
+        }
+        self.matched = true;
+    }
+
+    /// Note a match that failed after satisfying `progress` conditions,
+    /// keeping the first of the furthest
+    fn fail(&mut self, progress: usize, used: &[&Fact], failed: String, reason: MissReason) {
+        if self.failure.is_some() && progress <= self.progress {
+            return;
+        }
+        self.progress = progress;
+        self.used = used.iter().map(|fact| fact_text(fact)).collect();
+        self.failure = Some((failed, reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rules;
+
+    fn fact(predicate: &str, args: &[&str]) -> Fact {
+        Fact::new(
+            predicate.to_string(),
+            args.iter().map(|arg| Value::string(*arg)).collect(),
+        )
+    }
+
+    #[test]
+    fn test_near_misses() {
+        let rules = parse_rules(
+            r#"
+reader(U) :- member(U, G), grants(G, "read").
+writer(U) :- member(U, G), grants(G, "write"), not suspended(U).
+editor(U) :- member(U, G), G != "contractors".
+admin(U) :- member(U, "admins").
+"#,
+        )
+        .unwrap();
+        let facts = vec![
+            fact("member", &["alice", "contractors"]),
+            fact("member", &["bob", "admins"]),
+            fact("grants", &["contractors", "read"]),
+            fact("grants", &["contractors", "write"]),
+            fact("suspended", &["alice"]),
+            fact("reader", &["alice"]),
+        ];
+        let misses = near_misses(&rules, &facts, &[Value::string("alice")]);
+
+        // Reader matches for alice; admin matches only for bob
+        let rules: Vec<&str> = misses.iter().map(|m| m.rule.as_str()).collect();
+        assert_eq!(
+            rules,
+            [
+                "writer(?U) :- member(?U, ?G), grants(?G, \"write\"), not suspended(?U).",
+                "editor(?U) :- member(?U, ?G), ?G != \"contractors\".",
+                "admin(?U) :- member(?U, \"admins\")."
+            ]
+        );
+
+        let writer = &misses[0];
+        assert_eq!(writer.reason, MissReason::FactPresent);
+        assert_eq!(writer.failed, "not suspended(\"alice\")");
+        assert_eq!(
+            writer.matched,
+            [
+                "member(\"alice\", \"contractors\")",
+                "grants(\"contractors\", \"write\")"
+            ]
+        );
+        assert_eq!((writer.satisfied, writer.conditions), (2, 3));
+
+        let editor = &misses[1];
+        assert_eq!(editor.reason, MissReason::FailedComparison);
+        assert_eq!(editor.failed, "\"contractors\" != \"contractors\"");
+
+        let admin = &misses[2];
+        assert_eq!(admin.reason, MissReason::MissingFact);
+        assert_eq!(admin.failed, "member(\"alice\", \"admins\")");
+        assert!(admin.matched.is_empty());
+    }
+}
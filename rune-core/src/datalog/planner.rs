@@ -44,7 +44,7 @@
 use crate::datalog::backends::BackendType;
 use crate::datalog::types::{Atom, Rule, Term};
 use crate::facts::FactStore;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 
 /// Statistics about predicate distributions in the fact store
@@ -259,7 +259,8 @@ impl QueryPlanner {
             return Vec::new();
         }
 
-        let mut remaining: HashSet<usize> = (0..analyses.len()).collect();
+        // Ordered, so ties go to the earlier atom instead of hash order
+        let mut remaining: BTreeSet<usize> = (0..analyses.len()).collect();
         let mut ordered = Vec::new();
         let mut bound_variables = HashSet::new();
 
@@ -512,7 +513,10 @@ mod tests {
         Rule {
             head,
             body,
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0,
+            annotations: Default::default(),
         }
     }
 
@@ -9,7 +9,9 @@
 //! - Query interface: find all derivations of a fact
 //! - Explanation generation: produce human-readable explanations
 
+use super::types::{Atom, Term};
 use crate::facts::Fact;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
@@ -177,6 +179,19 @@ impl ProvenanceTracker {
         }
     }
 
+    /// Proof trees of every fact derived by a rule, ordered by fact
+    pub fn derived_proofs(&self) -> Vec<ProofNode> {
+        let mut proofs: Vec<ProofNode> = self
+            .derivations
+            .keys()
+            .filter_map(|fact| self.get_proof_tree(fact))
+            .filter(|proof| matches!(proof.root.source, DerivationSource::Rule { .. }))
+            .map(|proof| proof.to_node())
+            .collect();
+        proofs.sort_by(|a, b| a.fact.cmp(&b.fact));
+        proofs
+    }
+
     /// Get statistics about provenance tracking
     pub fn stats(&self) -> ProvenanceStats {
         ProvenanceStats {
@@ -228,6 +243,11 @@ impl ProofTree {
         }
     }
 
+    /// Convert the proof tree to its serializable form
+    pub fn to_node(&self) -> ProofNode {
+        node(&self.root)
+    }
+
     /// Get the depth of the proof tree
     pub fn depth(&self) -> usize {
         self.compute_depth(&self.root)
@@ -270,6 +290,45 @@ impl ProofTree {
     }
 }
 
+/// A fact in Datalog syntax, e.g. `member("alice", "staff")`
+pub(crate) fn fact_text(fact: &Fact) -> String {
+    let terms = fact.args.iter().cloned().map(Term::constant).collect();
+    Atom::new(fact.predicate.as_ref(), terms).to_string()
+}
+
+/// Serializable form of a derivation and everything under it
+fn node(derivation: &Derivation) -> ProofNode {
+    let (rule, premises) = match &derivation.source {
+        DerivationSource::Base => (None, Vec::new()),
+        DerivationSource::Rule {
+            rule_name,
+            premises,
+            ..
+        } => (
+            Some(rule_name.clone()),
+            premises.iter().map(|p| node(p)).collect(),
+        ),
+    };
+    ProofNode {
+        fact: fact_text(&derivation.fact),
+        rule,
+        premises,
+    }
+}
+
+/// Serializable proof tree, as returned with explained decisions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofNode {
+    /// The fact, in Datalog syntax (e.g. `can_read("alice", "doc1")`)
+    pub fact: String,
+    /// Rule that derived the fact; absent for base facts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Proofs of the body facts the rule matched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub premises: Vec<ProofNode>,
+}
+
 /// Statistics about provenance tracking
 #[derive(Debug, Clone)]
 pub struct ProvenanceStats {
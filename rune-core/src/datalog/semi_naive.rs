@@ -375,7 +375,7 @@ impl OptimizedEvaluator {
             }
 
             // Generate head facts
-            for sub in substitutions {
+            for sub in substitutions.iter().filter_map(|sub| rule.complete(sub)) {
                 if let Some(fact) = ground_atom(&rule.head, &sub) {
                     results.push(fact);
                 }
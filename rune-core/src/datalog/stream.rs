@@ -0,0 +1,232 @@
+//! Streaming access to derived facts
+//!
+//! Large rule sets can derive millions of facts. Rather than handing callers a
+//! sorted `Vec` they then have to slice and re-serialize, [`FactStream`]
+//! yields facts one at a time, so the CLI and HTTP layers can write each fact
+//! out as it arrives.
+//!
+//! The fixpoint itself is computed up front: the stream holds the facts
+//! matching the query's predicate, but puts them in order only as they are
+//! yielded. A page of `limit` facts at `offset` costs one pass over the
+//! matching facts plus `offset + limit` steps of a heap, not a sort of all
+//! of them, and a caller that stops reading early never orders the rest.
+
+use crate::facts::Fact;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Filter and paging options for a fact stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FactQuery {
+    /// Only yield facts with this predicate
+    pub predicate: Option<String>,
+    /// Number of matching facts to skip
+    pub offset: usize,
+    /// Maximum number of facts to yield
+    pub limit: Option<usize>,
+}
+
+impl FactQuery {
+    /// Query matching every derived fact
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict the query to one predicate
+    pub fn with_predicate(mut self, predicate: impl Into<String>) -> Self {
+        self.predicate = Some(predicate.into());
+        self
+    }
+
+    /// Skip the first `offset` matching facts
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Stop after `limit` facts
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Number of facts a page yields when `total` facts match the filter
+    pub fn page_len(&self, total: usize) -> usize {
+        let available = total.saturating_sub(self.offset);
+        self.limit.map_or(available, |limit| available.min(limit))
+    }
+
+    /// Check whether a fact passes the predicate filter
+    pub fn matches(&self, fact: &Fact) -> bool {
+        self.predicate
+            .as_deref()
+            .is_none_or(|p| &*fact.predicate == p)
+    }
+}
+
+/// Iterator over derived facts in `(predicate, args)` order
+///
+/// Ordering is stable across runs over the same facts, so consecutive pages
+/// obtained with increasing offsets neither overlap nor skip facts.
+#[derive(Debug)]
+pub struct FactStream {
+    facts: BinaryHeap<Next>,
+    skip: usize,
+    remaining: Option<usize>,
+}
+
+impl FactStream {
+    /// Build a stream over an evaluation result
+    pub fn new(facts: Vec<Fact>, query: &FactQuery) -> Self {
+        Self::from_matching(facts.into_iter().filter(|f| query.matches(f)), query)
+    }
+
+    /// Build a stream over facts already known to match `query`
+    pub(crate) fn from_matching(facts: impl IntoIterator<Item = Fact>, query: &FactQuery) -> Self {
+        FactStream {
+            facts: facts.into_iter().map(Next).collect(),
+            skip: query.offset,
+            remaining: query.limit,
+        }
+    }
+
+    /// Number of facts the stream will still yield, without materializing them
+    pub fn remaining(&self) -> usize {
+        let available = self.facts.len().saturating_sub(self.skip);
+        self.remaining
+            .map_or(available, |limit| available.min(limit))
+    }
+}
+
+impl Iterator for FactStream {
+    type Item = Fact;
+
+    fn next(&mut self) -> Option<Fact> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        while self.skip > 0 {
+            self.facts.pop()?;
+            self.skip -= 1;
+        }
+
+        let Next(fact) = self.facts.pop()?;
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        Some(fact)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+}
+
+/// A fact ordered so that the heap yields the smallest `(predicate, args)`
+/// first
+#[derive(Debug)]
+struct Next(Fact);
+
+impl Ord for Next {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .0
+            .predicate
+            .cmp(&self.0.predicate)
+            .then_with(|| other.0.args.cmp(&self.0.args))
+    }
+}
+
+impl PartialOrd for Next {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Next {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Next {}
+
+impl ExactSizeIterator for FactStream {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn facts() -> Vec<Fact> {
+        vec![
+            Fact::new("path", vec![Value::Integer(2), Value::Integer(3)]),
+            Fact::new("edge", vec![Value::Integer(1), Value::Integer(2)]),
+            Fact::new("path", vec![Value::Integer(1), Value::Integer(2)]),
+            Fact::new("path", vec![Value::Integer(1), Value::Integer(3)]),
+        ]
+    }
+
+    #[test]
+    fn test_stream_is_sorted_and_filtered() {
+        let stream = FactStream::new(facts(), &FactQuery::new().with_predicate("path"));
+        assert_eq!(stream.len(), 3);
+
+        let args: Vec<_> = stream.map(|f| f.args.to_vec()).collect();
+        assert_eq!(
+            args,
+            vec![
+                vec![Value::Integer(1), Value::Integer(2)],
+                vec![Value::Integer(1), Value::Integer(3)],
+                vec![Value::Integer(2), Value::Integer(3)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pages_do_not_overlap() {
+        let page = |offset| {
+            FactStream::new(facts(), &FactQuery::new().with_offset(offset).with_limit(2))
+                .collect::<Vec<_>>()
+        };
+
+        let first = page(0);
+        let second = page(2);
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 2);
+        assert_eq!(&*first[0].predicate, "edge");
+        assert!(first.iter().all(|f| !second.contains(f)));
+        assert_eq!(page(4).len(), 0);
+    }
+
+    #[test]
+    fn test_remaining_counts_without_consuming() {
+        let query = FactQuery::new().with_offset(1).with_limit(10);
+        let mut stream = FactStream::new(facts(), &query);
+        assert_eq!(stream.remaining(), 3);
+        stream.next();
+        assert_eq!(stream.remaining(), 2);
+
+        let beyond = FactStream::new(facts(), &FactQuery::new().with_offset(10));
+        assert_eq!(beyond.remaining(), 0);
+    }
+
+    #[test]
+    fn test_pages_follow_the_full_order() {
+        // Enough facts, out of order, that the heap does real work
+        let many: Vec<Fact> = (0..500)
+            .map(|i| Fact::new("n", vec![Value::Integer((i * 7919) % 500)]))
+            .collect();
+        let all: Vec<_> = FactStream::new(many.clone(), &FactQuery::new()).collect();
+        assert!(all.windows(2).all(|w| w[0].args < w[1].args));
+
+        let mut paged = Vec::new();
+        for offset in (0..500).step_by(64) {
+            let query = FactQuery::new().with_offset(offset).with_limit(64);
+            paged.extend(FactStream::new(many.clone(), &query));
+        }
+        assert_eq!(paged.len(), all.len());
+        assert!(paged.iter().zip(&all).all(|(a, b)| a.args == b.args));
+    }
+}
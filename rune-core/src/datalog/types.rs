@@ -4,6 +4,8 @@
 //! - Terms (variables and constants)
 //! - Atoms (predicates with terms)
 //! - Rules (Horn clauses)
+//! - Guards (comparisons between terms in rule bodies)
+//! - Builtin calls (host functions in rule bodies)
 //! - Substitutions (variable bindings)
 //!
 //! Design principles:
@@ -11,13 +13,18 @@
 //! - Compatible with existing FactStore
 //! - Support for lock-free concurrent reads
 
+use super::scratch;
 use crate::types::Value;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use tracing::warn;
 
 /// A term in Datalog (variable or constant)
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Term {
     /// Variable (e.g., X, Person, ?x)
     Variable(String),
@@ -77,7 +84,7 @@ impl fmt::Display for Term {
 }
 
 /// An atom in Datalog (predicate with terms)
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Atom {
     /// Predicate name
     pub predicate: Arc<str>,
@@ -147,15 +154,240 @@ impl fmt::Display for Atom {
     }
 }
 
+/// Comparison operator in a [`Guard`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompareOp {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareOp::Eq => write!(f, "=="),
+            CompareOp::Ne => write!(f, "!="),
+        }
+    }
+}
+
+/// A comparison that filters the bindings of a rule body, e.g. `Role != "ceo"`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Guard {
+    /// Left-hand term
+    pub left: Term,
+    /// Comparison operator
+    pub op: CompareOp,
+    /// Right-hand term
+    pub right: Term,
+}
+
+impl Guard {
+    /// Create a guard
+    pub fn new(left: Term, op: CompareOp, right: Term) -> Self {
+        Guard { left, op, right }
+    }
+
+    /// Get all variables in this guard
+    pub fn variables(&self) -> Vec<&str> {
+        [&self.left, &self.right]
+            .into_iter()
+            .filter_map(|t| t.as_variable())
+            .collect()
+    }
+
+    /// Evaluate the guard under a substitution
+    ///
+    /// Returns false when either side is still unbound; safe rules bind every
+    /// guard variable in a positive body atom, so that only happens for
+    /// rules that would be rejected anyway.
+    pub fn holds(&self, sub: &Substitution) -> bool {
+        match (
+            sub.apply_to_term(&self.left),
+            sub.apply_to_term(&self.right),
+        ) {
+            (Term::Constant(left), Term::Constant(right)) => match self.op {
+                CompareOp::Eq => left == right,
+                CompareOp::Ne => left != right,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.left, self.op, self.right)
+    }
+}
+
+/// A host function that rule bodies can call, e.g. a risk score
+///
+/// Functions must be pure: the same inputs always give the same output, since
+/// derived facts and decisions are cached.
+pub trait BuiltinFunction: Send + Sync {
+    /// Compute the output for the input arguments; `Ok(None)` means there
+    /// is none and the body match is dropped
+    fn call(&self, inputs: &[Value]) -> std::result::Result<Option<Value>, String>;
+}
+
+/// Call of a builtin in a rule body, e.g. `risk_score(U, Score)`
+///
+/// The last argument is the output: the call binds it, or compares the
+/// result with it when it is already bound. The others are inputs, bound by
+/// positive body atoms or earlier calls.
+#[derive(Clone)]
+pub struct BuiltinCall {
+    /// Name the builtin is registered under
+    pub name: Arc<str>,
+    /// Input arguments followed by the output
+    pub args: Vec<Term>,
+    /// Function to call
+    pub function: Arc<dyn BuiltinFunction>,
+}
+
+impl BuiltinCall {
+    /// Create a call
+    pub fn new(
+        name: impl Into<String>,
+        args: Vec<Term>,
+        function: Arc<dyn BuiltinFunction>,
+    ) -> Self {
+        BuiltinCall {
+            name: Arc::from(name.into().into_boxed_str()),
+            args,
+            function,
+        }
+    }
+
+    /// Input variables and the output variable, if the output is one
+    fn split_variables(&self) -> (Vec<&str>, Option<&str>) {
+        match self.args.split_last() {
+            Some((output, inputs)) => (
+                inputs.iter().filter_map(|t| t.as_variable()).collect(),
+                output.as_variable(),
+            ),
+            None => (Vec::new(), None),
+        }
+    }
+
+    /// Call the function on a body match, binding the output
+    ///
+    /// Returns false when an input is unbound, the function has no output
+    /// or fails, or the output disagrees with an already bound value.
+    /// Failures are logged; they never fail the evaluation as a whole.
+    pub fn apply(&self, sub: &mut Substitution) -> bool {
+        let Some((output, inputs)) = self.args.split_last() else {
+            return false;
+        };
+        let inputs: Option<Vec<Value>> = inputs
+            .iter()
+            .map(|term| match sub.apply_to_term(term) {
+                Term::Constant(value) => Some(value),
+                Term::Variable(_) => None,
+            })
+            .collect();
+        let Some(inputs) = inputs else {
+            return false;
+        };
+
+        let value = match self.function.call(&inputs) {
+            Ok(Some(value)) => value,
+            Ok(None) => return false,
+            Err(e) => {
+                warn!(builtin = %self.name, "Builtin call failed: {}", e);
+                return false;
+            }
+        };
+        match sub.apply_to_term(output) {
+            Term::Constant(bound) => bound == value,
+            Term::Variable(name) => {
+                sub.bind(name, value);
+                true
+            }
+        }
+    }
+}
+
+impl fmt::Debug for BuiltinCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuiltinCall")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for BuiltinCall {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.args == other.args
+            && Arc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+impl Eq for BuiltinCall {}
+
+impl Hash for BuiltinCall {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.args.hash(state);
+    }
+}
+
+impl fmt::Display for BuiltinCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, term) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", term)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Run builtin calls, then guards, on a body match
+///
+/// Matches are only copied when there are calls to bind outputs into.
+pub(crate) fn complete_match<'a>(
+    builtins: &[BuiltinCall],
+    guards: &[Guard],
+    sub: &'a Substitution,
+) -> Option<Cow<'a, Substitution>> {
+    let sub = if builtins.is_empty() {
+        Cow::Borrowed(sub)
+    } else {
+        let mut extended = sub.clone();
+        if !builtins.iter().all(|call| call.apply(&mut extended)) {
+            return None;
+        }
+        Cow::Owned(extended)
+    };
+    guards.iter().all(|guard| guard.holds(&sub)).then_some(sub)
+}
+
 /// A Datalog rule (Horn clause): head :- body
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Serializes without its builtin calls, which are bound to functions when
+/// the rule is loaded (see [`crate::builtins`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Rule {
     /// Head of the rule (consequent)
     pub head: Atom,
     /// Body of the rule (antecedents)
     pub body: Vec<Atom>,
+    /// Comparisons every body match must satisfy
+    pub guards: Vec<Guard>,
+    /// Builtin calls made on every body match, before the guards
+    #[serde(skip)]
+    pub builtins: Vec<BuiltinCall>,
     /// Stratification level (for negation)
     pub stratum: usize,
+    /// Annotations attached in the source (e.g. `@id("admin")`)
+    pub annotations: BTreeMap<String, String>,
 }
 
 impl Rule {
@@ -164,10 +396,59 @@ impl Rule {
         Rule {
             head,
             body,
+            guards: Vec::new(),
+            builtins: Vec::new(),
             stratum: 0, // Will be computed during stratification
+            annotations: BTreeMap::new(),
         }
     }
 
+    /// Add a guard to the rule body
+    pub fn with_guard(mut self, guard: Guard) -> Self {
+        self.guards.push(guard);
+        self
+    }
+
+    /// Check whether a body match satisfies every guard
+    pub fn guards_hold(&self, sub: &Substitution) -> bool {
+        self.guards.iter().all(|guard| guard.holds(sub))
+    }
+
+    /// Run the builtin calls and guards on a body match
+    ///
+    /// Returns the match with the calls' outputs bound, or `None` when a
+    /// call or guard rejects it.
+    pub fn complete<'a>(&self, sub: &'a Substitution) -> Option<Cow<'a, Substitution>> {
+        complete_match(&self.builtins, &self.guards, sub)
+    }
+
+    /// Attach an annotation to the rule
+    pub fn with_annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.annotations.insert(key.into(), value.into());
+        self
+    }
+
+    /// Key used to toggle this rule at runtime
+    ///
+    /// An explicit `@flag` annotation wins, so several rules can share one
+    /// switch; otherwise the rule's `@id` is used.
+    pub fn flag_key(&self) -> Option<&str> {
+        self.annotations
+            .get("flag")
+            .or_else(|| self.annotations.get("id"))
+            .map(String::as_str)
+    }
+
+    /// Whether the rule is enabled before any runtime override (`@enabled("false")`)
+    pub fn enabled_by_default(&self) -> bool {
+        self.annotations.get("enabled").map(String::as_str) != Some("false")
+    }
+
+    /// Fact scope the rule is confined to (`@scope("tenant")`)
+    pub fn scope(&self) -> Option<&str> {
+        self.annotations.get("scope").map(String::as_str)
+    }
+
     /// Create a fact (rule with empty body)
     pub fn fact(head: Atom) -> Self {
         Rule::new(head, vec![])
@@ -200,21 +481,46 @@ impl Rule {
                 vars.insert(var.to_string());
             }
         }
+        for guard in &self.guards {
+            for var in guard.variables() {
+                vars.insert(var.to_string());
+            }
+        }
+        for call in &self.builtins {
+            for var in call.args.iter().filter_map(|t| t.as_variable()) {
+                vars.insert(var.to_string());
+            }
+        }
 
         vars.into_iter().collect()
     }
 
-    /// Check if rule is safe (all head variables appear in positive body atoms)
+    /// Check if rule is safe (all head and guard variables appear in positive
+    /// body atoms)
     pub fn is_safe(&self) -> bool {
-        let head_vars: std::collections::HashSet<_> = self.head.variables().into_iter().collect();
+        let head_vars: std::collections::HashSet<_> = self
+            .head
+            .variables()
+            .into_iter()
+            .chain(self.guards.iter().flat_map(|g| g.variables()))
+            .collect();
 
-        let positive_body_vars: std::collections::HashSet<_> = self
+        let mut positive_body_vars: std::collections::HashSet<_> = self
             .body
             .iter()
             .filter(|a| !a.negated)
             .flat_map(|a| a.variables())
             .collect();
 
+        // Builtin calls bind their output once their inputs are bound
+        for call in &self.builtins {
+            let (inputs, output) = call.split_variables();
+            if !inputs.iter().all(|var| positive_body_vars.contains(var)) {
+                return false;
+            }
+            positive_body_vars.extend(output);
+        }
+
         // All head variables must appear in positive body atoms
         head_vars.is_subset(&positive_body_vars)
     }
@@ -239,13 +545,22 @@ impl fmt::Display for Rule {
                 }
                 write!(f, "{}", atom)?;
             }
+            for call in &self.builtins {
+                write!(f, ", {}", call)?;
+            }
+            for guard in &self.guards {
+                write!(f, ", {}", guard)?;
+            }
         }
         write!(f, ".")
     }
 }
 
 /// Variable substitution (binding)
-#[derive(Debug, Clone, Default)]
+///
+/// Binding maps come from the calling thread's scratch pool and go back to
+/// it on drop, so the evaluator's short-lived substitutions reuse storage.
+#[derive(Debug, Default)]
 pub struct Substitution {
     /// Variable bindings
     bindings: HashMap<String, Value>,
@@ -255,7 +570,7 @@ impl Substitution {
     /// Create an empty substitution
     pub fn new() -> Self {
         Substitution {
-            bindings: HashMap::new(),
+            bindings: scratch::take_bindings(),
         }
     }
 
@@ -322,6 +637,20 @@ impl Substitution {
     }
 }
 
+impl Clone for Substitution {
+    fn clone(&self) -> Self {
+        let mut bindings = scratch::take_bindings();
+        bindings.extend(self.bindings.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Substitution { bindings }
+    }
+}
+
+impl Drop for Substitution {
+    fn drop(&mut self) {
+        scratch::recycle_bindings(std::mem::take(&mut self.bindings));
+    }
+}
+
 impl fmt::Display for Substitution {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{{")?;
@@ -0,0 +1,88 @@
+//! Authorization decisions and their details
+//!
+//! The types every evaluator answers with: the Datalog evaluator alone in a
+//! minimal build (see [`crate::embedded`]), and Datalog combined with Cedar
+//! in [`RUNEEngine`](crate::RUNEEngine).
+
+use crate::datalog::{NearMiss, ProofNode};
+use crate::explain::Reason;
+use crate::failure::FailureOutcome;
+use crate::obligations::Obligation;
+use crate::warnings::Warning;
+use serde::{Deserialize, Serialize};
+
+/// Authorization decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+    /// Request is permitted
+    Permit,
+    /// Request is denied (no matching permit)
+    Deny,
+    /// Request is explicitly forbidden
+    Forbid,
+}
+
+impl Decision {
+    /// Check if decision allows the action
+    pub fn is_permitted(&self) -> bool {
+        matches!(self, Decision::Permit)
+    }
+
+    /// Combine decisions (forbid > deny > permit)
+    pub fn combine(self, other: Decision) -> Decision {
+        match (self, other) {
+            (Decision::Forbid, _) | (_, Decision::Forbid) => Decision::Forbid,
+            (Decision::Deny, _) | (_, Decision::Deny) => Decision::Deny,
+            (Decision::Permit, Decision::Permit) => Decision::Permit,
+        }
+    }
+}
+
+/// Authorization result with details
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationResult {
+    /// The decision
+    pub decision: Decision,
+    /// Explanation for the decision
+    pub explanation: String,
+    /// Rules that were evaluated
+    pub evaluated_rules: Vec<String>,
+    /// Facts that were used
+    pub facts_used: Vec<String>,
+    /// Cedar policies the request was evaluated against
+    #[serde(default)]
+    pub policies_evaluated: usize,
+    /// Evaluation time in nanoseconds
+    pub evaluation_time_ns: u64,
+    /// Whether result was cached
+    pub cached: bool,
+    /// Whether result was shared from a concurrent evaluation of the same request
+    #[serde(default)]
+    pub coalesced: bool,
+    /// Dependency failures handled by the failure policy, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<FailureOutcome>,
+    /// Obligations of the policies that permitted the request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obligations: Vec<Obligation>,
+    /// Warnings of the policies that permitted the request, for the caller
+    /// to surface (see [`crate::warnings`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+    /// Stable codes behind `explanation`, for rendering it in other
+    /// languages (see [`crate::explain`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reason_codes: Vec<Reason>,
+    /// How long, in milliseconds, an enforcement point may reuse this
+    /// decision without asking again; 0 when it should not be reused
+    #[serde(default)]
+    pub valid_for_ms: u64,
+    /// How each fact behind the decision was derived, for requests with
+    /// [`Request::explain`](crate::Request::explain) set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proofs: Vec<ProofNode>,
+    /// For a denial of a request with [`Request::explain`](crate::Request::explain) set, the rules
+    /// that almost matched and the condition each failed on, closest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub near_misses: Vec<NearMiss>,
+}
@@ -0,0 +1,286 @@
+//! Size-bounded decision cache
+//!
+//! Decisions are cached by request key, and at most
+//! `EngineConfig::cache_size` of them are kept. Under high-cardinality
+//! workloads (one principal per request, say) the cache would otherwise
+//! grow with every distinct request until the TTL sweeps it.
+//!
+//! A full cache evicts the entries least worth keeping: those cheap to
+//! recompute, rarely hit and close to expiry. Each entry's value is the
+//! evaluation time it saves per hit, times one more than its hits so far,
+//! times the seconds of TTL it has left, so a slow recursive-rule decision
+//! outlives a stream of cheap one-off ones. Only lookups that answer a
+//! caller count as hits; freshness checks and lookups of expired entries
+//! do not. Entries of equal value go least
+//! recently used first. An insert into a full cache drops the sixteenth of
+//! the entries with the lowest values in one pass, so the scan that finds
+//! them is paid for once per many inserts rather than on each. Expired
+//! entries are still dropped when they are looked up; those do not count as
+//! evictions.
+//!
+//! With stale-while-revalidate, an expired entry is still answered for a
+//! while; the first request to find it claims its re-evaluation, so one
+//! request queues it however many arrive at once.
+
+use crate::decision::AuthorizationResult;
+use dashmap::mapref::one::Ref;
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Fraction of a full cache evicted at once, as a divisor
+const EVICTION_BATCH: usize = 16;
+
+/// Cached decision
+pub(crate) struct CacheEntry {
+    pub(crate) result: AuthorizationResult,
+    /// When the decision was made
+    pub(crate) timestamp: Instant,
+    /// When the decision's TTL, as of its insert, runs out
+    expires: Instant,
+    /// Callers answered from the entry since the insert
+    hits: AtomicU64,
+    /// Clock reading at the latest hit or insert
+    last_used: AtomicU64,
+    /// Whether a re-evaluation of the expired decision is queued or running
+    revalidating: AtomicBool,
+}
+
+impl CacheEntry {
+    /// How much keeping the entry is worth at `now`
+    fn worth(&self, now: Instant) -> f64 {
+        let cost = self.result.evaluation_time_ns.max(1) as f64;
+        let hits = self.hits.load(Ordering::Relaxed) as f64;
+        let remaining = self.expires.saturating_duration_since(now).as_secs_f64();
+        cost * (hits + 1.0) * remaining
+    }
+
+    /// Claim the re-evaluation of an expired decision; `false` when another
+    /// request already has
+    pub(crate) fn claim_revalidation(&self) -> bool {
+        !self.revalidating.swap(true, Ordering::AcqRel)
+    }
+
+    /// Give up a claimed re-evaluation, so a later request claims it again
+    pub(crate) fn release_revalidation(&self) {
+        self.revalidating.store(false, Ordering::Release);
+    }
+}
+
+/// Decisions by request key, holding at most `capacity`
+pub(crate) struct DecisionCache {
+    entries: DashMap<u64, CacheEntry>,
+    capacity: usize,
+    clock: AtomicU64,
+}
+
+impl DecisionCache {
+    /// Empty cache for at most `capacity` decisions; 0 caches nothing
+    pub(crate) fn new(capacity: usize) -> Self {
+        DecisionCache {
+            entries: DashMap::new(),
+            capacity,
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Entry for `key`, left as it is; see [`hit`](Self::hit)
+    ///
+    /// The entry is locked while the reference is held; drop it before
+    /// inserting.
+    pub(crate) fn get(&self, key: u64) -> Option<Ref<'_, u64, CacheEntry>> {
+        self.entries.get(&key)
+    }
+
+    /// Count `entry`'s decision as returned to a caller, and mark it used
+    pub(crate) fn hit(&self, entry: &CacheEntry) {
+        entry.hits.fetch_add(1, Ordering::Relaxed);
+        entry.last_used.store(self.tick(), Ordering::Relaxed);
+    }
+
+    /// Cache `result`, decided at `timestamp` and valid for `ttl`, under
+    /// `key`
+    ///
+    /// Returns how many entries were evicted to make room.
+    pub(crate) fn insert(
+        &self,
+        key: u64,
+        result: AuthorizationResult,
+        timestamp: Instant,
+        ttl: Duration,
+    ) -> usize {
+        if self.capacity == 0 {
+            return 0;
+        }
+        let evicted = if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.evict()
+        } else {
+            0
+        };
+        self.entries.insert(
+            key,
+            CacheEntry {
+                result,
+                timestamp,
+                expires: timestamp + ttl,
+                hits: AtomicU64::new(0),
+                last_used: AtomicU64::new(self.tick()),
+                revalidating: AtomicBool::new(false),
+            },
+        );
+        evicted
+    }
+
+    /// Drop the entries least worth keeping, enough for one more and at
+    /// least a batch
+    fn evict(&self) -> usize {
+        let now = Instant::now();
+        let mut values: Vec<(f64, u64, u64)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let last_used = entry.last_used.load(Ordering::Relaxed);
+                (entry.worth(now), last_used, *entry.key())
+            })
+            .collect();
+        let excess = (values.len() + 1).saturating_sub(self.capacity);
+        let count = excess
+            .max(self.capacity / EVICTION_BATCH)
+            .max(1)
+            .min(values.len());
+        if count == 0 {
+            return 0;
+        }
+        values.select_nth_unstable_by(count - 1, |a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        values[..count]
+            .iter()
+            .filter(|(_, _, key)| self.entries.remove(key).is_some())
+            .count()
+    }
+
+    /// Remove the entry for `key`
+    pub(crate) fn remove(&self, key: u64) -> Option<CacheEntry> {
+        self.entries.remove(&key).map(|(_, entry)| entry)
+    }
+
+    /// Remove every entry
+    pub(crate) fn clear(&self) {
+        self.entries.clear();
+    }
+
+    /// Number of cached decisions
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision::Decision;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    /// Answer a caller from `key`'s entry, if there is one
+    fn hit(cache: &DecisionCache, key: u64) -> bool {
+        cache.get(key).map(|entry| cache.hit(&entry)).is_some()
+    }
+
+    fn result() -> AuthorizationResult {
+        costing(0)
+    }
+
+    fn costing(evaluation_time_ns: u64) -> AuthorizationResult {
+        AuthorizationResult {
+            decision: Decision::Permit,
+            explanation: String::new(),
+            evaluated_rules: Vec::new(),
+            facts_used: Vec::new(),
+            policies_evaluated: 0,
+            evaluation_time_ns,
+            cached: false,
+            coalesced: false,
+            failures: Vec::new(),
+            obligations: Vec::new(),
+            warnings: Vec::new(),
+            reason_codes: Vec::new(),
+            valid_for_ms: 0,
+            proofs: Vec::new(),
+            near_misses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = DecisionCache::new(4);
+        let now = Instant::now();
+        for key in 0..4 {
+            assert_eq!(cache.insert(key, result(), now, TTL), 0);
+        }
+        // Key 0 is the oldest insert, but was just used
+        assert!(hit(&cache, 0));
+        assert_eq!(cache.insert(4, result(), now, TTL), 1);
+        assert_eq!(cache.len(), 4);
+        assert!(!hit(&cache, 1));
+        assert!(hit(&cache, 0));
+
+        // Replacing an entry makes no room
+        assert_eq!(cache.insert(4, result(), now, TTL), 0);
+
+        let batched = DecisionCache::new(64);
+        for key in 0..65 {
+            batched.insert(key, result(), now, TTL);
+        }
+        assert_eq!(batched.len(), 61);
+        assert!((0..4).all(|key| !hit(&batched, key)));
+
+        let disabled = DecisionCache::new(0);
+        disabled.insert(0, result(), now, TTL);
+        assert_eq!(disabled.len(), 0);
+    }
+
+    #[test]
+    fn test_evicts_low_value_entries_first() {
+        let cache = DecisionCache::new(4);
+        let now = Instant::now();
+        // An expensive decision, used once long ago
+        cache.insert(0, costing(5_000_000), now, TTL);
+        assert!(hit(&cache, 0));
+        // A cheap one hit often, and a cheap one about to expire
+        cache.insert(1, costing(1_000), now, TTL);
+        for _ in 0..10 {
+            hit(&cache, 1);
+        }
+        cache.insert(2, costing(1_000), now, Duration::from_millis(1));
+        cache.insert(3, costing(1_000), now, TTL);
+
+        assert_eq!(cache.insert(4, costing(1_000), now, TTL), 1);
+        assert!(!hit(&cache, 2));
+        assert_eq!(cache.insert(5, costing(1_000), now, TTL), 1);
+        // The cheap entry never hit goes before the popular and the
+        // expensive ones, though both were used earlier
+        assert!(!hit(&cache, 3));
+        assert!(hit(&cache, 0));
+        assert!(hit(&cache, 1));
+    }
+
+    #[test]
+    fn test_lookups_are_not_hits() {
+        let cache = DecisionCache::new(2);
+        let now = Instant::now();
+        cache.insert(0, result(), now, TTL);
+        cache.insert(1, result(), now, TTL);
+        // Looked up often, but never answered from
+        for _ in 0..10 {
+            assert!(cache.get(0).is_some());
+        }
+        hit(&cache, 1);
+        assert_eq!(cache.insert(2, result(), now, TTL), 1);
+        assert!(cache.get(0).is_none());
+        assert!(cache.get(1).is_some());
+    }
+}
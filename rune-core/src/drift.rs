@@ -0,0 +1,344 @@
+//! Configuration drift between two exports
+//!
+//! [`Drift::between`] compares the [`Export`] of the configuration a server
+//! should be running, such as the HEAD of the policy repository, with the
+//! export of the one it is running, and reports what changed out of band:
+//! rules and policies added, removed or modified, changed fact scopes and
+//! cardinality limits, and runtime flag overrides. Base facts are not
+//! compared, since they change at runtime by design.
+//!
+//! The comparison is semantic rather than textual:
+//!
+//! - rules and policies with an `@id` are matched by it, so an edited rule
+//!   is reported as modified rather than as one removal and one addition;
+//! - other rules that differ are paired by head predicate and arity, so
+//!   `reader(U) :- member(U, "staff").` becoming
+//!   `reader(U) :- member(U, "staff"), active(U).` is one modification;
+//! - policy sources are compared with whitespace collapsed, and rules in
+//!   the normalized source form exports use, so reformatting is not drift.
+
+use crate::datalog::Rule;
+use crate::export::Export;
+use crate::parser::parse_rules;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What kind of item drifted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftTarget {
+    /// A Datalog rule
+    Rule,
+    /// A Cedar policy
+    Policy,
+    /// A fact scope
+    Scope,
+    /// A cardinality limit
+    Limit,
+    /// A runtime flag override
+    Flag,
+}
+
+impl fmt::Display for DriftTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DriftTarget::Rule => "rule",
+            DriftTarget::Policy => "policy",
+            DriftTarget::Scope => "scope",
+            DriftTarget::Limit => "limit",
+            DriftTarget::Flag => "flag",
+        })
+    }
+}
+
+/// How an item drifted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftChange {
+    /// Running but not expected
+    Added,
+    /// Expected but not running
+    Removed,
+    /// Running in another form than expected
+    Modified,
+}
+
+/// One drifted item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriftItem {
+    /// Kind of item
+    pub target: DriftTarget,
+    /// How it drifted
+    pub change: DriftChange,
+    /// `@id`, head signature (`reader/1`), policy ID, scope, predicate or
+    /// flag key
+    pub key: String,
+    /// The expected form, absent for additions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    /// The running form, absent for removals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for DriftItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = match self.change {
+            DriftChange::Added => '+',
+            DriftChange::Removed => '-',
+            DriftChange::Modified => '~',
+        };
+        write!(f, "{} {} {}", sign, self.target, self.key)?;
+        if let Some(expected) = &self.expected {
+            write!(f, "\n    expected: {}", expected)?;
+        }
+        if let Some(actual) = &self.actual {
+            write!(f, "\n    running:  {}", actual)?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences between an expected and a running configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Drift {
+    /// Drifted items, ordered by target and key
+    pub items: Vec<DriftItem>,
+}
+
+impl Drift {
+    /// Compare the `expected` configuration with the `actual` one
+    pub fn between(expected: &Export, actual: &Export) -> Drift {
+        let mut items = rule_drift(&expected.rules, &actual.rules);
+        items.extend(keyed_drift(
+            DriftTarget::Policy,
+            policy_map(expected),
+            policy_map(actual),
+        ));
+        items.extend(keyed_drift(
+            DriftTarget::Scope,
+            json_map(&expected.scopes),
+            json_map(&actual.scopes),
+        ));
+        items.extend(keyed_drift(
+            DriftTarget::Limit,
+            json_map(&expected.limits),
+            json_map(&actual.limits),
+        ));
+        let overrides = |export: &Export| -> BTreeMap<String, String> {
+            export
+                .overrides
+                .iter()
+                .map(|(key, enabled)| (key.clone(), enabled.to_string()))
+                .collect()
+        };
+        items.extend(keyed_drift(
+            DriftTarget::Flag,
+            overrides(expected),
+            overrides(actual),
+        ));
+        items.sort_by(|a, b| (a.target, &a.key).cmp(&(b.target, &b.key)));
+        Drift { items }
+    }
+
+    /// Whether the configurations agree
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+/// A rule's `@id` and head signature; unparsable rules are keyed by
+/// their text
+fn rule_key(source: &str) -> (Option<String>, String) {
+    let rule: Option<Rule> = parse_rules(source)
+        .ok()
+        .and_then(|rules| rules.into_iter().next());
+    match rule {
+        Some(rule) => {
+            let signature = format!("{}/{}", rule.head.predicate, rule.head.arity());
+            (rule.annotations.get("id").cloned(), signature)
+        }
+        None => (None, source.to_string()),
+    }
+}
+
+/// Added, removed and modified rules
+fn rule_drift(expected: &[String], actual: &[String]) -> Vec<DriftItem> {
+    // Rules present on both sides have not drifted
+    let removed: Vec<&String> = expected.iter().filter(|r| !actual.contains(r)).collect();
+    let mut added: Vec<&String> = actual.iter().filter(|r| !expected.contains(r)).collect();
+    let mut added_keys: Vec<(Option<String>, String)> = added.iter().map(|r| rule_key(r)).collect();
+
+    // Pair by `@id`, then rules without one by head signature
+    let mut items = Vec::new();
+    for rule in removed {
+        let (id, signature) = rule_key(rule);
+        let found = added_keys
+            .iter()
+            .position(|(other_id, other_signature)| match &id {
+                Some(id) => other_id.as_ref() == Some(id),
+                None => other_id.is_none() && *other_signature == signature,
+            });
+        let actual = found.map(|i| {
+            added_keys.remove(i);
+            added.remove(i).clone()
+        });
+        items.push(DriftItem {
+            target: DriftTarget::Rule,
+            change: if actual.is_some() {
+                DriftChange::Modified
+            } else {
+                DriftChange::Removed
+            },
+            key: id.unwrap_or(signature),
+            expected: Some(rule.clone()),
+            actual,
+        });
+    }
+    for (rule, (id, signature)) in added.into_iter().zip(added_keys) {
+        items.push(DriftItem {
+            target: DriftTarget::Rule,
+            change: DriftChange::Added,
+            key: id.unwrap_or(signature),
+            expected: None,
+            actual: Some(rule.clone()),
+        });
+    }
+    items
+}
+
+/// Policies by ID, with whitespace collapsed
+fn policy_map(export: &Export) -> BTreeMap<String, String> {
+    export
+        .policies
+        .iter()
+        .map(|policy| {
+            let content = policy.content.split_whitespace().collect::<Vec<_>>();
+            (policy.id.clone(), content.join(" "))
+        })
+        .collect()
+}
+
+/// Top-level entries of a map-like value, each rendered as JSON
+fn json_map(value: &impl Serialize) -> BTreeMap<String, String> {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::Object(map)) => map
+            .into_iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+/// Added, removed and modified entries between two keyed maps
+fn keyed_drift(
+    target: DriftTarget,
+    expected: BTreeMap<String, String>,
+    mut actual: BTreeMap<String, String>,
+) -> Vec<DriftItem> {
+    let mut items = Vec::new();
+    for (key, value) in expected {
+        let change = match actual.remove(&key) {
+            Some(running) if running == value => continue,
+            Some(running) => (DriftChange::Modified, Some(running)),
+            None => (DriftChange::Removed, None),
+        };
+        items.push(DriftItem {
+            target,
+            change: change.0,
+            key,
+            expected: Some(value),
+            actual: change.1,
+        });
+    }
+    items.extend(actual.into_iter().map(|(key, value)| DriftItem {
+        target,
+        change: DriftChange::Added,
+        key,
+        expected: None,
+        actual: Some(value),
+    }));
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::ExportedPolicy;
+
+    fn export(rules: &[&str], policies: &[(&str, &str)]) -> Export {
+        Export {
+            generation: 0,
+            rules: rules.iter().map(|r| r.to_string()).collect(),
+            policies: policies
+                .iter()
+                .map(|(id, content)| ExportedPolicy {
+                    id: id.to_string(),
+                    content: content.to_string(),
+                })
+                .collect(),
+            facts: Vec::new(),
+            scopes: Default::default(),
+            limits: Default::default(),
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_drift_between_exports() {
+        let expected = export(
+            &[
+                "reader(U) :- member(U, \"staff\").",
+                "@id(\"admins\")\nadmin(U) :- member(U, \"admins\").",
+                "auditor(U) :- member(U, \"audit\").",
+            ],
+            &[
+                ("refunds", "permit(principal,\n  action, resource);"),
+                ("legacy", "forbid(principal, action, resource);"),
+            ],
+        );
+        let mut actual = export(
+            &[
+                "@id(\"admins\")\nadmin(U) :- member(U, \"ops\").",
+                "reader(U) :- member(U, \"staff\"), active(U).",
+                "auditor(U) :- member(U, \"audit\").",
+                "writer(U) :- member(U, \"staff\").",
+            ],
+            &[("refunds", "permit(principal, action, resource);")],
+        );
+        actual.overrides.insert("legacy".to_string(), false);
+
+        let drift = Drift::between(&expected, &actual);
+        let summary: Vec<(DriftTarget, DriftChange, &str)> = drift
+            .items
+            .iter()
+            .map(|item| (item.target, item.change, item.key.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (DriftTarget::Rule, DriftChange::Modified, "admins"),
+                (DriftTarget::Rule, DriftChange::Modified, "reader/1"),
+                (DriftTarget::Rule, DriftChange::Added, "writer/1"),
+                (DriftTarget::Policy, DriftChange::Removed, "legacy"),
+                (DriftTarget::Flag, DriftChange::Added, "legacy"),
+            ]
+        );
+        assert_eq!(
+            drift.items[1].actual.as_deref(),
+            Some("reader(U) :- member(U, \"staff\"), active(U).")
+        );
+
+        assert!(Drift::between(&expected, &expected).is_empty());
+    }
+}
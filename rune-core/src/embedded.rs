@@ -0,0 +1,190 @@
+//! Embedded mode: Datalog authorization without the full engine
+//!
+//! [`EmbeddedEngine`] is the part of RUNE that fits on an edge device or
+//! inside another program: rules evaluated by the Datalog evaluator over a
+//! fact store, with decisions kept in the size-bounded decision cache. It
+//! is all a `minimal` build offers (`default-features = false, features =
+//! ["minimal"]`), which leaves out Cedar policies, hot reload, file
+//! watching, background workers and Tokio; the full build has it too.
+//!
+//! A request is permitted when the rules derive anything, as the Datalog
+//! half of the full engine decides. Cached decisions are tied to the fact
+//! store version, so a fact added or retracted is seen by the next request
+//! rather than once the TTL runs out.
+
+use crate::datalog::{DatalogEngine, Rule};
+use crate::decision::AuthorizationResult;
+use crate::decision_cache::DecisionCache;
+use crate::error::Result;
+use crate::facts::{Fact, FactStore};
+use crate::parser::parse_rules;
+use crate::request::Request;
+use crate::types::Value;
+use ahash::AHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Cached decisions kept by default
+pub const DEFAULT_CACHE_SIZE: usize = 10_000;
+
+/// How long a cached decision is answered by default
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Datalog rules, their facts and a decision cache
+pub struct EmbeddedEngine {
+    datalog: DatalogEngine,
+    facts: Arc<FactStore>,
+    cache: DecisionCache,
+    ttl: Duration,
+}
+
+impl EmbeddedEngine {
+    /// Engine evaluating `rules` over an empty fact store
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let facts = Arc::new(FactStore::new());
+        EmbeddedEngine {
+            datalog: DatalogEngine::new(rules, facts.clone()),
+            facts,
+            cache: DecisionCache::new(DEFAULT_CACHE_SIZE),
+            ttl: DEFAULT_CACHE_TTL,
+        }
+    }
+
+    /// Engine evaluating the rules in Datalog `source`
+    pub fn from_rules(source: &str) -> Result<Self> {
+        Ok(Self::new(parse_rules(source)?))
+    }
+
+    /// Keep at most `capacity` decisions, each for `ttl`; a capacity of 0
+    /// caches nothing
+    pub fn with_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = DecisionCache::new(capacity);
+        self.ttl = ttl;
+        self
+    }
+
+    /// Add a fact
+    pub fn add_fact(&self, predicate: impl Into<String>, args: Vec<Value>) {
+        self.facts.add_fact(Fact::new(predicate, args));
+    }
+
+    /// Remove a fact; `false` if it was not there
+    pub fn retract_fact(&self, predicate: impl Into<String>, args: Vec<Value>) -> bool {
+        self.facts.retract_fact(&Fact::new(predicate, args))
+    }
+
+    /// The fact store the rules read
+    pub fn facts(&self) -> &Arc<FactStore> {
+        &self.facts
+    }
+
+    /// Derive every fact the rules imply
+    pub fn derive_facts(&self) -> Result<Vec<Fact>> {
+        self.datalog.derive_facts()
+    }
+
+    /// Decide a request, from the cache when it holds a decision made
+    /// against the current facts
+    ///
+    /// Explained requests (see [`Request::explain`]) are always evaluated.
+    pub fn authorize(&self, request: &Request) -> Result<AuthorizationResult> {
+        if request.explain {
+            return self.datalog.evaluate(request, &self.facts);
+        }
+
+        let start = Instant::now();
+        let key = self.cache_key(request);
+        if let Some(entry) = self.cache.get(key) {
+            if start.duration_since(entry.timestamp) < self.ttl {
+                self.cache.hit(&entry);
+                let mut result = entry.result.clone();
+                result.cached = true;
+                return Ok(result);
+            }
+        }
+
+        let result = self.datalog.evaluate(request, &self.facts)?;
+        self.cache.insert(key, result.clone(), start, self.ttl);
+        Ok(result)
+    }
+
+    /// Number of cached decisions
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Drop every cached decision
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Cache key of a request against the current facts
+    fn cache_key(&self, request: &Request) -> u64 {
+        let mut hasher = AHasher::default();
+        request.cache_key().hash(&mut hasher);
+        self.facts.version().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::RequestBuilder;
+    use crate::types::{Action, Principal, Resource};
+
+    fn request() -> Request {
+        RequestBuilder::new()
+            .principal(Principal::user("alice"))
+            .action(Action::new("read"))
+            .resource(Resource::new("Document", "q3"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_embedded_engine() {
+        let engine = EmbeddedEngine::from_rules("reader(U) :- member(U, \"staff\").").unwrap();
+        assert!(!engine
+            .authorize(&request())
+            .unwrap()
+            .decision
+            .is_permitted());
+
+        // A new fact is seen at once, not when the cached denial expires
+        engine.add_fact(
+            "member",
+            vec![Value::string("alice"), Value::string("staff")],
+        );
+        let first = engine.authorize(&request()).unwrap();
+        assert!(first.decision.is_permitted());
+        assert!(!first.cached);
+        assert!(engine.authorize(&request()).unwrap().cached);
+
+        let explained = RequestBuilder::new()
+            .principal(Principal::user("alice"))
+            .action(Action::new("read"))
+            .resource(Resource::new("Document", "q3"))
+            .explain(true)
+            .build()
+            .unwrap();
+        let result = engine.authorize(&explained).unwrap();
+        assert!(!result.cached);
+        assert!(!result.proofs.is_empty());
+
+        assert!(engine.retract_fact(
+            "member",
+            vec![Value::string("alice"), Value::string("staff")],
+        ));
+        assert!(!engine
+            .authorize(&request())
+            .unwrap()
+            .decision
+            .is_permitted());
+
+        let uncached = EmbeddedEngine::new(Vec::new()).with_cache(0, DEFAULT_CACHE_TTL);
+        uncached.authorize(&request()).unwrap();
+        assert_eq!(uncached.cached(), 0);
+    }
+}
@@ -1,73 +1,109 @@
 //! Core RUNE engine with high-performance authorization
 
-use crate::datalog::DatalogEngine;
-use crate::error::Result;
-use crate::facts::FactStore;
-use crate::policy::PolicySet;
+use crate::access_requests::{AccessRequest, AccessRequestStatus, AccessRequests};
+use crate::artifact::PolicyArtifact;
+use crate::attributes::{AttributeMerger, MergedRequest};
+use crate::builtins::BuiltinRegistry;
+use crate::cache_rules::CacheRules;
+use crate::cache_ttl::AdaptiveTtlConfig;
+use crate::canonical::Canonicalizer;
+use crate::conflicts::Conflict;
+use crate::coverage::{CoverageReport, PolicyCoverage, RuleCoverage, UnmatchedRequest};
+use crate::datalog::{
+    unify_atom_with_fact, Atom, CancellationToken, DatalogEngine, EvaluationBackend, FactQuery,
+    FactStream, Rule,
+};
+use crate::decision::{AuthorizationResult, Decision};
+use crate::decision_cache::DecisionCache;
+use crate::error::{RUNEError, Result};
+use crate::explain::{ExplanationRenderer, Reason, ReasonCode};
+use crate::export::{rule_source, Export, ExportedPolicy};
+use crate::facts::{CompactionStats, Fact, FactSnapshot, FactStore, FactWriter, SnapshotFormat};
+use crate::failure::{FailureClass, FailureMode, FailureOutcome, FailurePolicy};
+use crate::flags::{FlagStatus, RuleFlags};
+use crate::history::{ChangeOrigin, FactEvent, HistoryQuery, ANONYMOUS_ACTOR};
+use crate::labels::{LabelSelector, Labeled, Labels};
+use crate::layers::{ConfigLayer, Provenance};
+use crate::limits::CardinalityLimits;
+use crate::ownership::{changed_owners, flag_owners, owned_items};
+use crate::parser::RUNEConfig;
+use crate::permissions::PermissionSummary;
+use crate::policy::{PolicyEffect, PolicyMatches, PolicySet};
+use crate::policy_index::PolicyIndex;
+use crate::replica::{ChangeBatch, ChangePosition, Replica, ReplicaConfig, ReplicaStatus};
 use crate::request::Request;
-use crate::types::Value;
-use arc_swap::ArcSwap;
+use crate::routes::RouteTable;
+use crate::scopes::FactScopes;
+use crate::sessions::{SessionAttribute, SessionInfo, SessionTable};
+use crate::speculation::{DecisionProfile, SpeculationConfig, SpeculationStats};
+use crate::types::{Action, Principal, Resource, Value};
+use crate::vocabulary::{ObservedNames, Vocabulary};
+use crate::workers::{WorkerConfig, WorkerPool};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
 use std::time::{Duration, Instant};
-use tracing::{instrument, trace};
-
-/// Authorization decision
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Decision {
-    /// Request is permitted
-    Permit,
-    /// Request is denied (no matching permit)
-    Deny,
-    /// Request is explicitly forbidden
-    Forbid,
-}
-
-impl Decision {
-    /// Check if decision allows the action
-    pub fn is_permitted(&self) -> bool {
-        matches!(self, Decision::Permit)
-    }
-
-    /// Combine decisions (forbid > deny > permit)
-    pub fn combine(self, other: Decision) -> Decision {
-        match (self, other) {
-            (Decision::Forbid, _) | (_, Decision::Forbid) => Decision::Forbid,
-            (Decision::Deny, _) | (_, Decision::Deny) => Decision::Deny,
-            (Decision::Permit, Decision::Permit) => Decision::Permit,
-        }
-    }
-}
-
-/// Authorization result with details
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuthorizationResult {
-    /// The decision
-    pub decision: Decision,
-    /// Explanation for the decision
-    pub explanation: String,
-    /// Rules that were evaluated
-    pub evaluated_rules: Vec<String>,
-    /// Facts that were used
-    pub facts_used: Vec<String>,
-    /// Evaluation time in nanoseconds
-    pub evaluation_time_ns: u64,
-    /// Whether result was cached
-    pub cached: bool,
-}
+use tracing::{instrument, trace, warn};
 
 /// Engine configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EngineConfig {
-    /// Maximum cache size
+    /// Maximum number of cached decisions; beyond it those cheapest to
+    /// recompute, least hit and nearest expiry are evicted (see
+    /// [`EngineMetrics::cache_evictions`])
     pub cache_size: usize,
     /// Cache TTL in seconds
     pub cache_ttl_secs: u64,
+    /// How long past its TTL a cached decision is still answered, while
+    /// one request re-evaluates it in the background, in milliseconds
+    /// (stale-while-revalidate; 0 disables it)
+    #[serde(default)]
+    pub stale_while_revalidate_ms: u64,
     /// Enable parallel evaluation
     pub parallel_eval: bool,
-    /// Evaluation timeout in milliseconds
+    /// Datalog evaluation budget in milliseconds (0 disables the check)
     pub timeout_ms: u64,
+    /// Datalog evaluation backend, until a RUNE file's `[evaluation]`
+    /// section selects one
+    #[serde(default)]
+    pub evaluation_backend: EvaluationBackend,
+    /// Profile-guided speculative policy evaluation
+    #[serde(default)]
+    pub speculation: SpeculationConfig,
+    /// What to answer when Datalog or Cedar evaluation fails
+    #[serde(default)]
+    pub failure_policy: FailurePolicy,
+    /// Fact changes kept for replicas to catch up from (0 disables the log)
+    #[serde(default)]
+    pub change_log: usize,
+    /// Attributed fact changes kept for audits (0 disables the history; see
+    /// [`crate::history`])
+    #[serde(default)]
+    pub fact_history: usize,
+    /// Follow a primary's fact store instead of owning one
+    #[serde(default)]
+    pub replica: Option<ReplicaConfig>,
+    /// Scale each decision's TTL by how often its entities' facts change
+    #[serde(default)]
+    pub adaptive_ttl: AdaptiveTtlConfig,
+    /// Permit every request while no rules or policies are loaded, instead
+    /// of denying them; for tests and development only
+    #[serde(default)]
+    pub allow_all_bootstrap: bool,
+    /// Threads and queue of [`RUNEEngine::authorize_async`] (see
+    /// [`crate::workers`])
+    #[serde(default)]
+    pub workers: WorkerConfig,
+    /// Directory WASM builtin modules are loaded from; without one, the
+    /// directory of the RUNE file naming them (see [`crate::builtins`])
+    #[serde(default)]
+    pub builtin_module_dir: Option<PathBuf>,
 }
 
 impl Default for EngineConfig {
@@ -75,18 +111,180 @@ impl Default for EngineConfig {
         EngineConfig {
             cache_size: 10_000,
             cache_ttl_secs: 60,
+            stale_while_revalidate_ms: 0,
             parallel_eval: true,
             timeout_ms: 100,
+            evaluation_backend: EvaluationBackend::Interpreter,
+            speculation: SpeculationConfig::default(),
+            failure_policy: FailurePolicy::default(),
+            change_log: 0,
+            fact_history: 0,
+            replica: None,
+            adaptive_ttl: AdaptiveTtlConfig::default(),
+            allow_all_bootstrap: false,
+            workers: WorkerConfig::default(),
+            builtin_module_dir: None,
+        }
+    }
+}
+
+/// Cached answer to a goal, valid for one fact version and generation
+struct QueryCacheEntry {
+    fact_version: u64,
+    generation: u64,
+    facts: Arc<Vec<Fact>>,
+}
+
+/// Facts answering a goal, from [`RUNEEngine::query`]
+#[derive(Debug, Clone)]
+pub struct QueryAnswer {
+    /// Base and derived facts matching the goal, in argument order
+    pub facts: Arc<Vec<Fact>>,
+    /// Whether the answer came from the query cache
+    pub cached: bool,
+}
+
+type FlightKey = (u64, u64);
+
+/// State of an evaluation other requests may be waiting on
+#[derive(Default)]
+enum FlightState {
+    #[default]
+    Running,
+    Done(Box<AuthorizationResult>),
+    Abandoned,
+}
+
+/// One in-flight evaluation shared by identical concurrent requests
+#[derive(Default)]
+struct Flight {
+    state: Mutex<FlightState>,
+    finished: Condvar,
+}
+
+impl Flight {
+    /// Block until the evaluation finishes; `None` if it was abandoned
+    fn wait(&self) -> Option<AuthorizationResult> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = self
+            .finished
+            .wait_while(state, |state| matches!(state, FlightState::Running))
+            .unwrap_or_else(PoisonError::into_inner);
+        match &*state {
+            FlightState::Done(result) => Some((**result).clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Held by the request running an evaluation
+///
+/// Dropping it unregisters the flight and wakes the waiters, who evaluate on
+/// their own if no result was published (e.g. the evaluation panicked).
+struct FlightGuard<'a> {
+    inflight: &'a DashMap<FlightKey, Arc<Flight>>,
+    key: FlightKey,
+    flight: Arc<Flight>,
+}
+
+impl FlightGuard<'_> {
+    fn complete(&self, result: &AuthorizationResult) {
+        *self
+            .flight
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = FlightState::Done(Box::new(result.clone()));
+    }
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.inflight
+            .remove_if(&self.key, |_, flight| Arc::ptr_eq(flight, &self.flight));
+
+        let mut state = self
+            .flight
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if matches!(*state, FlightState::Running) {
+            *state = FlightState::Abandoned;
         }
+        drop(state);
+        self.flight.finished.notify_all();
     }
 }
 
-/// Cache entry for authorization decisions
-struct CacheEntry {
-    result: AuthorizationResult,
-    timestamp: Instant,
+/// Most configurations kept staged at once
+pub const MAX_STAGED: usize = 8;
+
+/// A configuration staged with [`RUNEEngine::stage_config`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedConfig {
+    /// Identifier of the staged configuration, from 1
+    pub id: u64,
+    /// Number of Datalog rules
+    pub rules: usize,
+    /// Number of Cedar policies
+    pub policies: usize,
+    /// Artifact the configuration was parsed from
+    pub artifact: PolicyArtifact,
+    /// When it was staged, in milliseconds since the Unix epoch
+    pub staged_at_ms: u64,
+}
+
+/// Most installed configurations kept for rollback
+pub const MAX_VERSIONS: usize = 16;
+
+/// A configuration the engine installed, kept for
+/// [`RUNEEngine::rollback_to`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigVersion {
+    /// Version ID, from 1, in installation order
+    pub version: u64,
+    /// Generation the configuration was installed as
+    pub generation: u64,
+    /// Number of Datalog rules
+    pub rules: usize,
+    /// Number of Cedar policies
+    pub policies: usize,
+    /// Artifact the configuration was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<PolicyArtifact>,
+    /// When it was installed, in milliseconds since the Unix epoch
+    pub installed_at_ms: u64,
+    /// Version this one restored, for rollbacks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_of: Option<u64>,
+}
+
+/// Everything a configuration install swaps in, kept so that it can be
+/// swapped in again
+#[derive(Clone)]
+struct InstalledConfig {
+    scopes: Arc<FactScopes>,
+    builtins: Arc<BuiltinRegistry>,
+    datalog: Arc<DatalogEngine>,
+    policies: Arc<PolicySet>,
+    canonicalizer: Arc<Canonicalizer>,
+    attribute_merger: Arc<AttributeMerger>,
+    explanation_renderer: Arc<ExplanationRenderer>,
+    routes: Arc<RouteTable>,
+    cache_rules: Arc<CacheRules>,
+    artifact: Option<Arc<PolicyArtifact>>,
 }
 
+/// Parts of a configuration built by [`RUNEEngine::build_config`]
+struct BuiltConfig {
+    scopes: Arc<FactScopes>,
+    builtins: Arc<BuiltinRegistry>,
+    datalog: DatalogEngine,
+    policies: PolicySet,
+}
+
+/// Datalog and Cedar results, each of which may have failed
+type Evaluation = (Result<AuthorizationResult>, Result<AuthorizationResult>);
+
 /// Main RUNE engine
 pub struct RUNEEngine {
     /// Datalog evaluation engine (lock-free with ArcSwap for hot-reload)
@@ -95,8 +293,66 @@ pub struct RUNEEngine {
     policies: Arc<ArcSwap<PolicySet>>,
     /// Fact store
     facts: Arc<FactStore>,
-    /// Decision cache
-    cache: DashMap<u64, CacheEntry>,
+    /// Runtime kill switches for rules and policies
+    flags: Arc<RuleFlags>,
+    /// Identifier rewriting applied before evaluation and caching
+    canonicalizer: Arc<ArcSwap<Canonicalizer>>,
+    /// Resolution of principal attributes supplied by several sources
+    attribute_merger: Arc<ArcSwap<AttributeMerger>>,
+    /// Message catalogs for explanations in the caller's language
+    explanation_renderer: Arc<ArcSwap<ExplanationRenderer>>,
+    /// HTTP route mappings used by forward-auth front ends
+    routes: Arc<ArcSwap<RouteTable>>,
+    /// Decisions kept out of the cache
+    cache_rules: Arc<ArcSwap<CacheRules>>,
+    /// Fact views that `@scope` rules are confined to
+    scopes: Arc<ArcSwap<FactScopes>>,
+    /// Custom builtin predicates rule bodies may call
+    builtins: Arc<ArcSwap<BuiltinRegistry>>,
+    /// Artifact the active configuration was loaded from, if any
+    artifact: ArcSwapOption<PolicyArtifact>,
+    /// Scopes of the loaded policies and rules, built on first use in each
+    /// generation
+    policy_index: ArcSwapOption<PolicyIndex>,
+    /// Entity types, actions and context keys seen in requests
+    observed: ObservedNames,
+    /// Configurations staged for what-if evaluation, by id
+    staged: DashMap<u64, (StagedConfig, EngineSnapshot)>,
+    /// Id of the latest staged configuration
+    staged_ids: AtomicU64,
+    /// Recently installed configurations, oldest first
+    versions: Mutex<VecDeque<(ConfigVersion, InstalledConfig)>>,
+    /// Version of the latest installed configuration
+    version_ids: AtomicU64,
+    /// Version the active configuration was installed as; 0 once rules or
+    /// policies were reloaded on their own
+    current_version: AtomicU64,
+    /// Open principal sessions and the facts they installed
+    sessions: SessionTable,
+    /// Access requests awaiting or past review
+    access_requests: AccessRequests,
+    /// Replication progress when following a primary
+    replica: Option<Replica>,
+    /// Decision cache, bounded by `config.cache_size`
+    cache: DecisionCache,
+    /// Canonical requests answered with an expired decision, waiting to be
+    /// re-evaluated
+    revalidations: Mutex<Vec<Request>>,
+    /// Goal query answers, keyed on the goal's text
+    query_cache: DashMap<String, QueryCacheEntry>,
+    /// Evaluations in progress, keyed on (generation, cache key)
+    inflight: DashMap<FlightKey, Arc<Flight>>,
+    /// Bumped whenever rules, policies, flags or canonicalization change
+    generation: AtomicU64,
+    /// Bumped before and after each combined configuration swap, so it is
+    /// odd while one is in progress (see [`RUNEEngine::consistently`])
+    swaps: AtomicU64,
+    /// Held while a combined configuration is swapped in
+    swap_lock: Mutex<()>,
+    /// Which policies decide each (action, resource type) pair
+    profile: Arc<DecisionProfile>,
+    /// Threads evaluating asynchronous requests
+    workers: WorkerPool,
     /// Engine configuration
     config: Arc<EngineConfig>,
     /// Metrics
@@ -112,170 +368,1803 @@ impl RUNEEngine {
     /// Create a new engine with specified configuration
     pub fn with_config(config: EngineConfig) -> Self {
         let facts = Arc::new(FactStore::new());
+        if config.change_log > 0 {
+            facts.enable_change_log(config.change_log);
+        }
+        if config.fact_history > 0 {
+            facts.enable_history(config.fact_history);
+        }
+        if config.adaptive_ttl.enabled {
+            facts.enable_change_rates(Duration::from_secs(config.adaptive_ttl.half_life_secs));
+        }
         RUNEEngine {
-            datalog: Arc::new(ArcSwap::new(Arc::new(DatalogEngine::empty(facts.clone())))),
+            datalog: Arc::new(ArcSwap::new(Arc::new(
+                DatalogEngine::empty(facts.clone()).with_backend(config.evaluation_backend),
+            ))),
             policies: Arc::new(ArcSwap::new(Arc::new(PolicySet::new()))),
             facts,
-            cache: DashMap::new(),
+            flags: Arc::new(RuleFlags::new()),
+            canonicalizer: Arc::new(ArcSwap::from_pointee(Canonicalizer::default())),
+            attribute_merger: Arc::new(ArcSwap::from_pointee(AttributeMerger::default())),
+            explanation_renderer: Arc::new(ArcSwap::from_pointee(ExplanationRenderer::default())),
+            routes: Arc::new(ArcSwap::from_pointee(RouteTable::default())),
+            cache_rules: Arc::new(ArcSwap::from_pointee(CacheRules::default())),
+            scopes: Arc::new(ArcSwap::from_pointee(FactScopes::default())),
+            builtins: Arc::new(ArcSwap::from_pointee(BuiltinRegistry::default())),
+            artifact: ArcSwapOption::empty(),
+            policy_index: ArcSwapOption::empty(),
+            observed: ObservedNames::new(),
+            staged: DashMap::new(),
+            staged_ids: AtomicU64::new(0),
+            versions: Mutex::new(VecDeque::new()),
+            version_ids: AtomicU64::new(0),
+            current_version: AtomicU64::new(0),
+            sessions: SessionTable::new(),
+            access_requests: AccessRequests::default(),
+            replica: config.replica.clone().map(Replica::new),
+            cache: DecisionCache::new(config.cache_size),
+            revalidations: Mutex::new(Vec::new()),
+            query_cache: DashMap::new(),
+            inflight: DashMap::new(),
+            generation: AtomicU64::new(0),
+            swaps: AtomicU64::new(0),
+            swap_lock: Mutex::new(()),
+            profile: Arc::new(DecisionProfile::new()),
+            workers: WorkerPool::new(config.workers.clone()),
             config: Arc::new(config),
             metrics: Arc::new(EngineMetrics::new()),
         }
     }
 
     /// Authorize a request
+    ///
+    /// Attribute sources on the request are merged into its principal first
+    /// (see [`crate::attributes`]), and the winner of every conflict is
+    /// noted in the explanation.
     #[instrument(skip(self), fields(request_id = %request.request_id))]
     pub fn authorize(&self, request: &Request) -> Result<AuthorizationResult> {
+        self.observed.observe(request);
+        let result = match self.attribute_merger.load().merge(request) {
+            Some(merged) => {
+                let result = self.authorize_merged(&merged.request)?;
+                note_winners(result, &merged)
+            }
+            None => self.authorize_merged(request)?,
+        };
+        Ok(self.note_pending(request, result))
+    }
+
+    /// Authorize a request on the worker pool (see [`crate::workers`]),
+    /// without blocking the calling thread
+    ///
+    /// Fails with [`RUNEError::Timeout`] once `deadline`, if given, passes;
+    /// the request is only evaluated if a worker picks it up before then.
+    pub async fn authorize_async(
+        self: &Arc<Self>,
+        request: &Request,
+        deadline: Option<Instant>,
+    ) -> Result<AuthorizationResult> {
+        let engine = self.clone();
+        let request = request.clone();
+        let result = self
+            .workers
+            .run(deadline, move || engine.authorize(&request))
+            .await;
+        self.spawn_revalidation();
+        result
+    }
+
+    /// [`authorize_batch`](Self::authorize_batch) on the worker pool, as
+    /// [`authorize_async`](Self::authorize_async) decides one request
+    pub async fn authorize_batch_async(
+        self: &Arc<Self>,
+        requests: &[Request],
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Result<AuthorizationResult>>> {
+        let engine = self.clone();
+        let requests = requests.to_vec();
+        let results = self
+            .workers
+            .run(deadline, move || Ok(engine.authorize_batch(&requests)))
+            .await;
+        self.spawn_revalidation();
+        results
+    }
+
+    /// [`authorize_resources`](Self::authorize_resources) on the worker
+    /// pool
+    pub async fn authorize_resources_async(
+        self: &Arc<Self>,
+        request: &Request,
+        resources: &[Resource],
+        deadline: Option<Instant>,
+    ) -> Result<Vec<AuthorizationResult>> {
+        let engine = self.clone();
+        let request = request.clone();
+        let resources = resources.to_vec();
+        let results = self
+            .workers
+            .run(deadline, move || {
+                engine.authorize_resources(&request, &resources)
+            })
+            .await;
+        self.spawn_revalidation();
+        results
+    }
+
+    /// [`authorize_actions`](Self::authorize_actions) on the worker pool
+    pub async fn authorize_actions_async(
+        self: &Arc<Self>,
+        request: &Request,
+        actions: &[Action],
+        deadline: Option<Instant>,
+    ) -> Result<Vec<AuthorizationResult>> {
+        let engine = self.clone();
+        let request = request.clone();
+        let actions = actions.to_vec();
+        let results = self
+            .workers
+            .run(deadline, move || {
+                engine.authorize_actions(&request, &actions)
+            })
+            .await;
+        self.spawn_revalidation();
+        results
+    }
+
+    /// [`list_permitted_principals`](Self::list_permitted_principals) on
+    /// the worker pool
+    pub async fn list_permitted_principals_async(
+        self: &Arc<Self>,
+        action: &Action,
+        resource: &Resource,
+        candidates: &[Principal],
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Principal>> {
+        let engine = self.clone();
+        let (action, resource) = (action.clone(), resource.clone());
+        let candidates = candidates.to_vec();
+        self.workers
+            .run(deadline, move || {
+                engine.list_permitted_principals(&action, &resource, &candidates)
+            })
+            .await
+    }
+
+    /// [`prefetch`](Self::prefetch) each of `requests` on the worker pool,
+    /// returning how many new decisions were cached
+    ///
+    /// A request that cannot be evaluated is skipped.
+    pub async fn prefetch_async(self: &Arc<Self>, requests: Vec<Request>) -> Result<usize> {
+        let engine = self.clone();
+        self.workers
+            .run(None, move || {
+                let mut cached = 0;
+                for request in &requests {
+                    match engine.prefetch(request) {
+                        Ok(true) => cached += 1,
+                        Ok(false) => {}
+                        Err(e) => trace!("Prefetch skipped: {}", e),
+                    }
+                }
+                Ok(cached)
+            })
+            .await
+    }
+
+    /// Evaluations running on or queued for the worker pool right now
+    ///
+    /// Callers still waiting for room in the queue are not counted, so this
+    /// never exceeds [`WorkerConfig::threads`] plus [`WorkerConfig::queue`].
+    pub fn pending_evaluations(&self) -> usize {
+        self.workers.pending()
+    }
+
+    /// Re-evaluate the decisions answered past their TTL on a spare worker,
+    /// if there are any and a worker is free
+    fn spawn_revalidation(self: &Arc<Self>) {
+        if self.revalidation_queue().is_empty() {
+            return;
+        }
+        let engine = self.clone();
+        self.workers.spawn(move || {
+            engine.revalidate_stale();
+        });
+    }
+
+    fn revalidation_queue(&self) -> std::sync::MutexGuard<'_, Vec<Request>> {
+        self.revalidations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Re-evaluate every decision answered past its TTL since the last
+    /// call, refreshing its cache entry (see
+    /// [`EngineConfig::stale_while_revalidate_ms`])
+    ///
+    /// [`authorize_async`](Self::authorize_async) runs this on the worker
+    /// pool by itself; callers of [`authorize`](Self::authorize) call it
+    /// from a background thread of their own. Returns the number of
+    /// decisions refreshed. A re-evaluation that fails, or that a reload
+    /// overtakes, leaves the expired entry to be retried by a later request.
+    pub fn revalidate_stale(&self) -> usize {
+        let requests = std::mem::take(&mut *self.revalidation_queue());
+        let mut refreshed = 0;
+        for request in requests {
+            let start = Instant::now();
+            let generation = self.generation();
+            let cache_key = request.cache_key();
+            match self.evaluate(&request) {
+                (Ok(datalog_result), Ok(cedar_result)) if self.generation() == generation => {
+                    let mut result = combine_results(datalog_result, cedar_result, start);
+                    result.valid_for_ms = self.valid_for(&request, start, start);
+                    let evicted =
+                        self.cache
+                            .insert(cache_key, result, start, self.cache_ttl(&request));
+                    self.metrics.record_cache_evictions(evicted);
+                    self.metrics.record_revalidation();
+                    refreshed += 1;
+                }
+                _ => {
+                    if let Some(entry) = self.cache.get(cache_key) {
+                        entry.release_revalidation();
+                    }
+                }
+            }
+        }
+        refreshed
+    }
+
+    /// Authorize a request whose principal attributes are already merged
+    fn authorize_merged(&self, request: &Request) -> Result<AuthorizationResult> {
         let start = Instant::now();
 
+        // Logouts and facts past their TTL take effect before the decision,
+        // not at the next compaction
+        self.expire_due(start);
+
+        // A replica too far behind answers nothing, not even from the cache
+        if let Some(replica) = &self.replica {
+            replica.check(request.max_staleness, start)?;
+        }
+
+        // Nothing is evaluated before a configuration is loaded
+        if !self.is_configured() {
+            let result = unconfigured_result(self.config.allow_all_bootstrap, start);
+            self.metrics
+                .record_authorization(result.decision, start.elapsed());
+            return Ok(result);
+        }
+
+        // Canonicalize identifiers so equivalent requests share a cache entry
+        let canonical = self.canonicalizer.load().canonicalize(request);
+        let request = canonical.as_ref().unwrap_or(request);
+
+        // Decisions on per-transaction context are never reused (see
+        // `crate::cache_rules`), and explained ones carry proofs only the
+        // asking caller wants
+        if request.explain || self.cache_rules.load().bypasses(request) {
+            return self.authorize_uncached(request, start);
+        }
+
         // Check cache first
         let cache_key = request.cache_key();
-        if let Some(entry) = self.cache.get(&cache_key) {
-            if start.duration_since(entry.timestamp).as_secs() < self.config.cache_ttl_secs {
-                self.metrics.record_cache_hit();
+        let mut stale = None;
+        if let Some(entry) = self.cache.get(cache_key) {
+            let age = start.duration_since(entry.timestamp);
+            let ttl = self.cache_ttl(request);
+            if age < ttl {
+                self.cache.hit(&entry);
+                self.metrics
+                    .record_cache_hit(entry.result.evaluation_time_ns);
                 trace!("Cache hit for request");
 
                 let mut result = entry.result.clone();
                 result.cached = true;
+                result.valid_for_ms = self.valid_for(request, entry.timestamp, start);
+                return Ok(result);
+            } else if age < ttl + Duration::from_millis(self.config.stale_while_revalidate_ms) {
+                // Answer from the expired entry; the first request to find
+                // it queues the re-evaluation
+                if entry.claim_revalidation() {
+                    self.revalidation_queue().push(request.clone());
+                }
+                self.cache.hit(&entry);
+                self.metrics
+                    .record_cache_hit(entry.result.evaluation_time_ns);
+                self.metrics.record_stale_hit();
+                trace!("Stale cache hit for request");
+
+                let mut result = entry.result.clone();
+                result.cached = true;
+                result.valid_for_ms = 0;
                 return Ok(result);
             } else {
-                // Remove stale entry
+                // Remove stale entry, keeping it for a fallback-to-cache failure
                 drop(entry);
-                self.cache.remove(&cache_key);
+                stale = self.cache.remove(cache_key).map(|entry| entry.result);
             }
         }
 
         self.metrics.record_cache_miss();
+
+        // Identical requests that miss together share one evaluation. The
+        // generation is part of the key so requests arriving after a reload
+        // never receive a decision made under the old configuration.
+        let flight_key = (self.generation(), cache_key);
+        let flight = match self.inflight.entry(flight_key) {
+            Entry::Occupied(entry) => {
+                let flight = entry.get().clone();
+                drop(entry);
+                if let Some(mut result) = flight.wait() {
+                    trace!("Coalesced with in-flight evaluation");
+                    self.metrics.record_coalesced();
+                    result.coalesced = true;
+                    self.metrics
+                        .record_authorization(result.decision, start.elapsed());
+                    return Ok(result);
+                }
+                // The evaluation was abandoned, so run our own
+                None
+            }
+            Entry::Vacant(entry) => {
+                let flight = Arc::new(Flight::default());
+                entry.insert(flight.clone());
+                Some(FlightGuard {
+                    inflight: &self.inflight,
+                    key: flight_key,
+                    flight,
+                })
+            }
+        };
         trace!("Cache miss, evaluating request");
 
-        // Evaluate in parallel if configured
-        let (datalog_result, cedar_result) = if self.config.parallel_eval {
-            self.evaluate_parallel(request)?
-        } else {
-            self.evaluate_sequential(request)?
+        let (datalog_result, cedar_result) = self.evaluate(request);
+        let result = match (datalog_result, cedar_result) {
+            (Ok(datalog_result), Ok(cedar_result)) => {
+                let mut result = combine_results(datalog_result, cedar_result, start);
+                result.valid_for_ms = self.valid_for(request, start, start);
+
+                // Cache the result
+                let evicted =
+                    self.cache
+                        .insert(cache_key, result.clone(), start, self.cache_ttl(request));
+                self.metrics.record_cache_evictions(evicted);
+                result
+            }
+            // Degraded answers are not cached, so the next request retries
+            (datalog_result, cedar_result) => {
+                let result = degrade(
+                    &self.config.failure_policy,
+                    datalog_result,
+                    cedar_result,
+                    stale,
+                    start,
+                );
+                for failure in &result.failures {
+                    self.metrics.record_failure(failure.class);
+                }
+                result
+            }
         };
 
-        // Combine results
-        let decision = datalog_result.decision.combine(cedar_result.decision);
+        if let Some(flight) = &flight {
+            flight.complete(&result);
+        }
 
-        let explanation = match decision {
-            Decision::Permit => format!(
-                "Permitted by {} rules",
-                datalog_result.evaluated_rules.len() + cedar_result.evaluated_rules.len()
-            ),
-            Decision::Deny => "No matching permit rules".to_string(),
-            Decision::Forbid => {
-                if cedar_result.decision == Decision::Forbid {
-                    cedar_result.explanation
-                } else {
-                    datalog_result.explanation
+        // Record metrics
+        self.metrics
+            .record_authorization(result.decision, start.elapsed());
+
+        Ok(result)
+    }
+
+    /// Decide canonical `request` without the decision cache
+    fn authorize_uncached(&self, request: &Request, start: Instant) -> Result<AuthorizationResult> {
+        trace!("Cache bypassed, evaluating request");
+        let (datalog_result, cedar_result) = self.evaluate(request);
+        let result = match (datalog_result, cedar_result) {
+            (Ok(datalog_result), Ok(cedar_result)) => {
+                combine_results(datalog_result, cedar_result, start)
+            }
+            (datalog_result, cedar_result) => {
+                let result = degrade(
+                    &self.config.failure_policy,
+                    datalog_result,
+                    cedar_result,
+                    None,
+                    start,
+                );
+                for failure in &result.failures {
+                    self.metrics.record_failure(failure.class);
                 }
+                result
             }
         };
+        self.metrics
+            .record_authorization(result.decision, start.elapsed());
+        Ok(result)
+    }
 
-        let mut evaluated_rules = datalog_result.evaluated_rules;
-        evaluated_rules.extend(cedar_result.evaluated_rules);
+    /// Evaluate `request` against one configuration, in parallel if
+    /// configured
+    fn evaluate(&self, request: &Request) -> Evaluation {
+        self.consistently(|| {
+            if self.config.speculation.enabled {
+                self.evaluate_speculative(request)
+            } else if self.config.parallel_eval {
+                self.evaluate_parallel(request)
+            } else {
+                self.evaluate_sequential(request)
+            }
+        })
+    }
 
-        let mut facts_used = datalog_result.facts_used;
-        facts_used.extend(cedar_result.facts_used);
+    /// Evaluate `request` ahead of time so a later identical request is
+    /// answered from the decision cache
+    ///
+    /// Returns whether a new decision was cached. Requests with a fresh
+    /// cached decision are left alone, and degraded decisions and those the
+    /// cache rules bypass are never cached, so those return `false` too.
+    pub fn prefetch(&self, request: &Request) -> Result<bool> {
+        let merged = self.attribute_merger.load().merge(request);
+        let request = merged.as_ref().map_or(request, |m| &m.request);
+        let canonical = self.canonicalizer.load().canonicalize(request);
+        let canonical = canonical.as_ref().unwrap_or(request);
+        if self.cache_rules.load().bypasses(canonical) {
+            return Ok(false);
+        }
+        let fresh = self
+            .cache
+            .get(canonical.cache_key())
+            .is_some_and(|entry| entry.timestamp.elapsed() < self.cache_ttl(canonical));
+        if fresh {
+            return Ok(false);
+        }
 
-        let result = AuthorizationResult {
-            decision,
-            explanation,
-            evaluated_rules,
-            facts_used,
-            evaluation_time_ns: start.elapsed().as_nanos() as u64,
-            cached: false,
+        let result = self.authorize(request)?;
+        Ok(!result.cached && !result.coalesced && result.failures.is_empty())
+    }
+
+    /// Authorize `request`'s principal and action against each of
+    /// `resources`, returning one result per resource in the same order
+    ///
+    /// Equivalent to calling [`authorize`](Self::authorize) with each
+    /// resource substituted, but cheaper for long candidate lists: fresh
+    /// cached decisions are reused, and the Datalog fixpoint, which does not
+    /// depend on the resource, is evaluated once for all the misses while
+    /// their Cedar evaluations run in parallel. Candidates whose evaluation
+    /// fails go through [`authorize`](Self::authorize), so the failure
+    /// policy applies to them as usual.
+    pub fn authorize_resources(
+        &self,
+        request: &Request,
+        resources: &[Resource],
+    ) -> Result<Vec<AuthorizationResult>> {
+        self.authorize_variants(request, resources.len(), |request, i| {
+            request.resource = resources[i].clone();
+        })
+    }
+
+    /// Authorize `request`'s principal on its resource for each of
+    /// `actions`, returning one result per action in the same order
+    ///
+    /// As [`authorize_resources`](Self::authorize_resources), with the
+    /// action varying instead: a UI deciding which of several buttons to
+    /// enable for one resource gets every answer from one fixpoint.
+    pub fn authorize_actions(
+        &self,
+        request: &Request,
+        actions: &[Action],
+    ) -> Result<Vec<AuthorizationResult>> {
+        self.authorize_variants(request, actions.len(), |request, i| {
+            request.action = actions[i].clone();
+        })
+    }
+
+    /// Authorize each of `principals` to perform `request`'s action on its
+    /// resource, returning one result per principal in the same order
+    ///
+    /// As [`authorize_resources`](Self::authorize_resources), with the
+    /// principal varying instead.
+    pub fn authorize_principals(
+        &self,
+        request: &Request,
+        principals: &[Principal],
+    ) -> Result<Vec<AuthorizationResult>> {
+        self.authorize_variants(request, principals.len(), |request, i| {
+            request.principal = principals[i].clone();
+        })
+    }
+
+    /// Which of `candidates` `principal` may perform `action` on, in the
+    /// order given
+    ///
+    /// The reverse of [`authorize`](Self::authorize), for rendering lists
+    /// filtered by permission. A resource is only permitted when a Cedar
+    /// permit policy matches, so candidates outside the scope of every
+    /// enabled permit (see [`policy_index`](Self::policy_index)) are left
+    /// out without evaluating them; the rest are decided together, as by
+    /// [`authorize_resources`](Self::authorize_resources), with one Datalog
+    /// fixpoint. Candidates are only pruned when Cedar failures are not
+    /// answered by failing open, and are all decided if a reload lands
+    /// meanwhile.
+    pub fn list_permitted_resources(
+        &self,
+        principal: &Principal,
+        action: &Action,
+        candidates: &[Resource],
+    ) -> Result<Vec<Resource>> {
+        let Some(first) = candidates.first() else {
+            return Ok(Vec::new());
         };
+        let request = Request::new(principal.clone(), action.clone(), first.clone());
+        self.list_permitted(&request, candidates, |request, resource| {
+            request.resource = resource.clone();
+        })
+    }
 
-        // Cache the result
-        self.cache.insert(
-            cache_key,
-            CacheEntry {
-                result: result.clone(),
-                timestamp: start,
-            },
-        );
+    /// Which of `candidates` may perform `action` on `resource`, in the
+    /// order given
+    ///
+    /// The principal counterpart of
+    /// [`list_permitted_resources`](Self::list_permitted_resources), for
+    /// audit and administration: "who can delete this bucket?". Candidates
+    /// whose type no enabled permit's principal scope admits are left out
+    /// the same way, and the rest decided together with one fixpoint.
+    pub fn list_permitted_principals(
+        &self,
+        action: &Action,
+        resource: &Resource,
+        candidates: &[Principal],
+    ) -> Result<Vec<Principal>> {
+        let Some(first) = candidates.first() else {
+            return Ok(Vec::new());
+        };
+        let request = Request::new(first.clone(), action.clone(), resource.clone());
+        self.list_permitted(&request, candidates, |request, principal| {
+            request.principal = principal.clone();
+        })
+    }
 
-        // Record metrics
-        self.metrics.record_authorization(decision, start.elapsed());
+    /// The candidates permitted when `vary` puts each into `request`,
+    /// skipping those no enabled permit policy's scope reaches
+    fn list_permitted<T: Clone>(
+        &self,
+        request: &Request,
+        candidates: &[T],
+        vary: impl Fn(&mut Request, &T),
+    ) -> Result<Vec<T>> {
+        let prune = self.is_configured()
+            && self.config.failure_policy.mode(FailureClass::CedarError) != FailureMode::FailOpen;
+        let index = self.policy_index();
+        let canonicalizer = self.canonicalizer.load();
+        let reachable: Vec<T> = candidates
+            .iter()
+            .filter(|candidate| {
+                if !prune {
+                    return true;
+                }
+                let mut variant = request.clone();
+                vary(&mut variant, candidate);
+                let variant = canonicalizer.canonicalize(&variant).unwrap_or(variant);
+                index.policies.values().any(|policy| {
+                    policy.enabled
+                        && policy.effect == Some(PolicyEffect::Permit)
+                        && policy.scope.contains(
+                            &variant.action.name,
+                            &variant.resource.entity.entity_type,
+                            &variant.principal.entity.entity_type,
+                        )
+                })
+            })
+            .cloned()
+            .collect();
+        let candidates = if prune && self.generation() != index.generation {
+            candidates.to_vec()
+        } else {
+            reachable
+        };
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        Ok(result)
+        let results = self.authorize_variants(request, candidates.len(), |request, i| {
+            vary(request, &candidates[i]);
+        })?;
+        Ok(candidates
+            .into_iter()
+            .zip(results)
+            .filter(|(_, result)| result.decision.is_permitted())
+            .map(|(candidate, _)| candidate)
+            .collect())
     }
 
-    /// Evaluate in parallel using rayon
-    fn evaluate_parallel(
+    /// Decide `count` variants of `request` together, `vary` rewriting the
+    /// merged request into the `i`th
+    fn authorize_variants(
         &self,
         request: &Request,
-    ) -> Result<(AuthorizationResult, AuthorizationResult)> {
+        count: usize,
+        vary: impl Fn(&mut Request, usize),
+    ) -> Result<Vec<AuthorizationResult>> {
+        let start = Instant::now();
+        self.expire_due(start);
+        if let Some(replica) = &self.replica {
+            replica.check(request.max_staleness, start)?;
+        }
+        if !self.is_configured() {
+            return Ok((0..count)
+                .map(|_| unconfigured_result(self.config.allow_all_bootstrap, start))
+                .collect());
+        }
+
+        let merged = self.attribute_merger.load().merge(request);
+        let template = merged.as_ref().map_or(request, |m| &m.request);
+        let canonicalizer = self.canonicalizer.load();
+        let candidates: Vec<Request> = (0..count)
+            .map(|i| {
+                let mut request = template.clone();
+                vary(&mut request, i);
+                canonicalizer.canonicalize(&request).unwrap_or(request)
+            })
+            .collect();
+
+        self.decide_together(&candidates, start)
+            .into_iter()
+            .map(|result| match &merged {
+                Some(merged) => result.map(|result| note_winners(result, merged)),
+                None => result,
+            })
+            .collect()
+    }
+
+    /// Authorize each of `requests`, returning one result per request in
+    /// the same order
+    ///
+    /// Equivalent to calling [`authorize`](Self::authorize) on each in turn,
+    /// but cheaper for batches: the Datalog fixpoint depends only on the
+    /// facts, which the whole batch shares, so it is evaluated once for every
+    /// request without a fresh cached decision while their Cedar evaluations
+    /// run in parallel. A request repeated within the batch is evaluated
+    /// once, and its repeats are answered as cache hits, as they would be one
+    /// after another. Requests whose evaluation fails go through
+    /// [`authorize`](Self::authorize), so the failure policy applies to them
+    /// as usual.
+    pub fn authorize_batch(&self, requests: &[Request]) -> Vec<Result<AuthorizationResult>> {
+        let start = Instant::now();
+        self.expire_due(start);
+        let configured = self.is_configured();
+        let merger = self.attribute_merger.load();
+        let canonicalizer = self.canonicalizer.load();
+        let merged: Vec<_> = requests
+            .iter()
+            .map(|request| merger.merge(request))
+            .collect();
+
+        let mut results: Vec<Option<Result<AuthorizationResult>>> =
+            requests.iter().map(|_| None).collect();
+        let mut pending = Vec::new();
+        let mut candidates = Vec::new();
+        for (i, request) in requests.iter().enumerate() {
+            let request = merged[i].as_ref().map_or(request, |m| &m.request);
+            if let Some(replica) = &self.replica {
+                if let Err(e) = replica.check(request.max_staleness, start) {
+                    results[i] = Some(Err(e));
+                    continue;
+                }
+            }
+            if !configured {
+                let result = unconfigured_result(self.config.allow_all_bootstrap, start);
+                self.metrics
+                    .record_authorization(result.decision, start.elapsed());
+                results[i] = Some(Ok(result));
+                continue;
+            }
+            candidates.push(
+                canonicalizer
+                    .canonicalize(request)
+                    .unwrap_or_else(|| request.clone()),
+            );
+            pending.push(i);
+        }
+        for (i, result) in pending
+            .into_iter()
+            .zip(self.decide_together(&candidates, start))
+        {
+            results[i] = Some(result);
+        }
+
+        results
+            .into_iter()
+            .zip(&merged)
+            .zip(requests)
+            .map(|((result, merged), request)| {
+                let result = result.expect("every request is decided");
+                let result = match merged {
+                    Some(merged) => result.map(|result| note_winners(result, merged)),
+                    None => result,
+                };
+                result.map(|result| self.note_pending(request, result))
+            })
+            .collect()
+    }
+
+    /// Decide merged, canonical `requests` with one Datalog fixpoint
+    ///
+    /// Fresh cached decisions are reused, and each distinct remaining
+    /// request is evaluated once, its repeats sharing the decision. Requests
+    /// the cache rules bypass are evaluated every time.
+    fn decide_together(
+        &self,
+        requests: &[Request],
+        start: Instant,
+    ) -> Vec<Result<AuthorizationResult>> {
+        let cache_rules = self.cache_rules.load();
+        let bypassed: Vec<bool> = requests.iter().map(|r| cache_rules.bypasses(r)).collect();
+        let mut results: Vec<Option<Result<AuthorizationResult>>> = requests
+            .iter()
+            .zip(&bypassed)
+            .map(|(request, &bypassed)| {
+                // Explained requests need a fixpoint of their own
+                if request.explain {
+                    return Some(self.authorize_merged(request));
+                }
+                if bypassed {
+                    return None;
+                }
+                let entry = self.cache.get(request.cache_key())?;
+                (start.duration_since(entry.timestamp) < self.cache_ttl(request)).then(|| {
+                    self.cache.hit(&entry);
+                    self.metrics
+                        .record_cache_hit(entry.result.evaluation_time_ns);
+                    let mut result = entry.result.clone();
+                    result.cached = true;
+                    result.valid_for_ms = self.valid_for(request, entry.timestamp, start);
+                    Ok(result)
+                })
+            })
+            .collect();
+
+        let mut firsts = HashMap::new();
+        let mut misses = Vec::new();
+        let mut repeats = Vec::new();
+        for (i, request) in requests.iter().enumerate() {
+            if results[i].is_some() {
+                continue;
+            }
+            if bypassed[i] {
+                misses.push(i);
+                continue;
+            }
+            match *firsts.entry(request.cache_key()).or_insert(i) {
+                first if first == i => misses.push(i),
+                first => repeats.push((i, first)),
+            }
+        }
+
+        if let Some(&first) = misses.first() {
+            let (datalog, evaluated) = self.consistently(|| {
+                let datalog = evaluate_datalog(
+                    &self.datalog.load(),
+                    &requests[first],
+                    &self.facts,
+                    self.config.timeout_ms,
+                );
+                let policies = self.policies.load();
+                let evaluated: Vec<(usize, Result<AuthorizationResult>)> = misses
+                    .par_iter()
+                    .map(|&i| (i, policies.evaluate(&requests[i])))
+                    .collect();
+                (datalog, evaluated)
+            });
+
+            for (i, cedar) in evaluated {
+                let request = &requests[i];
+                let result = match (&datalog, cedar) {
+                    (Ok(datalog), Ok(cedar)) if bypassed[i] => {
+                        let result = combine_results(datalog.clone(), cedar, start);
+                        self.metrics
+                            .record_authorization(result.decision, start.elapsed());
+                        Ok(result)
+                    }
+                    (Ok(datalog), Ok(cedar)) => {
+                        self.metrics.record_cache_miss();
+                        let mut result = combine_results(datalog.clone(), cedar, start);
+                        result.valid_for_ms = self.valid_for(request, start, start);
+                        let evicted = self.cache.insert(
+                            request.cache_key(),
+                            result.clone(),
+                            start,
+                            self.cache_ttl(request),
+                        );
+                        self.metrics.record_cache_evictions(evicted);
+                        self.metrics
+                            .record_authorization(result.decision, start.elapsed());
+                        Ok(result)
+                    }
+                    _ => self.authorize_merged(request),
+                };
+                results[i] = Some(result);
+            }
+        }
+
+        // Degraded decisions and errors are not cached, so their repeats
+        // are evaluated again
+        for (i, first) in repeats {
+            let result = match &results[first] {
+                Some(Ok(result)) if result.failures.is_empty() => {
+                    self.metrics.record_cache_hit(result.evaluation_time_ns);
+                    let mut result = result.clone();
+                    result.cached = true;
+                    Ok(result)
+                }
+                _ => self.authorize_merged(&requests[i]),
+            };
+            results[i] = Some(result);
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every request is decided"))
+            .collect()
+    }
+
+    /// Whether any rules or policies are loaded, counting disabled ones
+    ///
+    /// Until then every request is denied, or permitted when
+    /// [`EngineConfig::allow_all_bootstrap`] is set, without evaluation.
+    pub fn is_configured(&self) -> bool {
+        !self.datalog.load().rules().is_empty() || !self.policies.load().is_empty()
+    }
+
+    /// How long a decision for `request` stays in the cache
+    ///
+    /// `cache_ttl_secs`, unless adaptive TTL is enabled: then the TTL follows
+    /// how often facts about the request's principal and resource have been
+    /// changing (see [`crate::cache_ttl`]). It is worked out on every lookup,
+    /// so a burst of changes also shortens the life of decisions already
+    /// cached.
+    pub fn cache_ttl(&self, request: &Request) -> Duration {
+        let adaptive = &self.config.adaptive_ttl;
+        if !adaptive.enabled {
+            return Duration::from_secs(self.config.cache_ttl_secs);
+        }
+        let rate = self.facts.change_rate(&request.principal.entity.id)
+            + self.facts.change_rate(&request.resource.entity.id);
+        adaptive.ttl(rate)
+    }
+
+    /// Milliseconds an enforcement point may reuse a decision for `request`
+    /// made at `decided`, counted from `now`
+    ///
+    /// The rest of the decision's life in the cache (so adaptive TTL's
+    /// account of fact volatility carries over), cut short by the next fact
+    /// or session expiry, either of which may change it. Fact and
+    /// configuration changes made through the engine cannot be foreseen;
+    /// callers that need them sooner should keep the hint short with a low
+    /// `cache_ttl_secs`.
+    fn valid_for(&self, request: &Request, decided: Instant, now: Instant) -> u64 {
+        let mut until = decided + self.cache_ttl(request);
+        if let Some(expiry) = self.facts.next_expiry() {
+            until = until.min(expiry);
+        }
+        if let Some(deadline) = self.sessions.next_deadline() {
+            until = until.min(deadline);
+        }
+        u64::try_from(until.saturating_duration_since(now).as_millis()).unwrap_or(u64::MAX)
+    }
+
+    /// Evaluate in parallel using rayon
+    fn evaluate_parallel(&self, request: &Request) -> Evaluation {
         let datalog = self.datalog.clone();
         let policies = self.policies.clone();
         let facts = self.facts.clone();
         let req_clone = request.clone();
+        let timeout_ms = self.config.timeout_ms;
 
         // Use rayon's parallel join for two tasks
-        let (datalog_result, cedar_result) = rayon::join(
+        rayon::join(
             || -> Result<AuthorizationResult> {
                 let engine = datalog.load();
-                engine.evaluate(&req_clone, &facts)
+                evaluate_datalog(&engine, &req_clone, &facts, timeout_ms)
             },
             || -> Result<AuthorizationResult> {
                 let policy_set = policies.load();
                 policy_set.evaluate(&req_clone)
             },
-        );
+        )
+    }
+
+    /// Evaluate with the policies that usually decide this kind of request first
+    ///
+    /// Sequentially, a Cedar result other than permit settles the combined
+    /// decision, so the Datalog fixpoint is skipped. In parallel mode the
+    /// fixpoint runs alongside the speculative check instead.
+    fn evaluate_speculative(&self, request: &Request) -> Evaluation {
+        let timeout_ms = self.config.timeout_ms;
+        if self.config.parallel_eval {
+            return rayon::join(
+                || evaluate_datalog(&self.datalog.load(), request, &self.facts, timeout_ms),
+                || self.evaluate_cedar_speculative(request),
+            );
+        }
+
+        let cedar_result = self.evaluate_cedar_speculative(request);
+        if matches!(&cedar_result, Ok(result) if result.decision != Decision::Permit) {
+            self.profile.record_skipped_fixpoint();
+            return (Ok(skipped_datalog_result()), cedar_result);
+        }
+
+        let datalog_result =
+            evaluate_datalog(&self.datalog.load(), request, &self.facts, timeout_ms);
+        (datalog_result, cedar_result)
+    }
+
+    /// Try the profile's reduced policy set, falling back to the full set
+    fn evaluate_cedar_speculative(&self, request: &Request) -> Result<AuthorizationResult> {
+        let policies = self.policies.load();
+        let subset = self.profile.speculative_set(
+            request,
+            &policies,
+            self.generation(),
+            &self.config.speculation,
+        )?;
+
+        let result = match subset {
+            Some(subset) => {
+                let result = subset.evaluate(request)?;
+                // Conclusive unless it is a default deny (nothing matched)
+                if result.decision == Decision::Permit || !result.evaluated_rules.is_empty() {
+                    self.profile.record_hit();
+                    result
+                } else {
+                    self.profile.record_fallback();
+                    policies.evaluate(request)?
+                }
+            }
+            None => policies.evaluate(request)?,
+        };
 
-        Ok((datalog_result?, cedar_result?))
+        self.profile.record(request, &result.evaluated_rules);
+        Ok(result)
     }
 
     /// Evaluate sequentially
-    fn evaluate_sequential(
-        &self,
-        request: &Request,
-    ) -> Result<(AuthorizationResult, AuthorizationResult)> {
+    fn evaluate_sequential(&self, request: &Request) -> Evaluation {
         let datalog_result = {
             let engine = self.datalog.load();
-            engine.evaluate(request, &self.facts)?
+            evaluate_datalog(&engine, request, &self.facts, self.config.timeout_ms)
         };
 
         let cedar_result = {
             let policy_set = self.policies.load();
-            policy_set.evaluate(request)?
+            policy_set.evaluate(request)
         };
 
-        Ok((datalog_result, cedar_result))
+        (datalog_result, cedar_result)
     }
 
-    /// Load configuration from a RUNE file
-    pub fn load_configuration(&self, _config_path: &str) -> Result<()> {
-        // This will be implemented with the parser
-        todo!("Implement configuration loading")
+    /// Load configuration from a RUNE file (see [`RUNEEngine::apply_config`])
+    pub fn load_configuration(&self, config_path: &str) -> Result<()> {
+        let contents = std::fs::read_to_string(config_path)?;
+        self.apply_config(crate::parser::parse_rune_file(&contents)?.with_path(config_path))
     }
 
-    /// Add a fact to the engine
-    pub fn add_fact(&self, predicate: impl Into<String>, args: Vec<Value>) {
-        self.facts
-            .add_fact(crate::facts::Fact::new(predicate, args));
+    /// Install a parsed RUNE file's scopes, builtins, rules, policies,
+    /// canonicalization, routes and cache rules
+    ///
+    /// Rules and policies are replaced; scopes, limits, builtins,
+    /// canonicalization, routes and cache rules only when the file has those
+    /// sections. Everything is built and checked before anything is swapped in, so a file with a bad
+    /// policy, module or rule leaves the engine as it was. The parts are
+    /// then swapped in as one generation: no decision sees the new rules
+    /// with the old policies. The result is kept as a version to roll back
+    /// to (see [`RUNEEngine::rollback_to`]).
+    pub fn apply_config(&self, config: RUNEConfig) -> Result<()> {
+        let (config, built) = self.build_config(config)?;
+        self.install_config(config, built);
+        Ok(())
     }
 
-    /// Clear the decision cache
-    pub fn clear_cache(&self) {
-        self.cache.clear();
+    /// Install a parsed RUNE file on behalf of `actor`
+    ///
+    /// As [`apply_config`](Self::apply_config), but fails with
+    /// [`RUNEError::AuthorizationDenied`], leaving the engine as it was,
+    /// when the file adds, removes or rewrites a rule or policy owned by a
+    /// team `actor` is not a member of (see [`crate::ownership`]).
+    pub fn apply_config_as(&self, config: RUNEConfig, actor: &str) -> Result<()> {
+        let (config, built) = self.build_config(config)?;
+        let before = owned_items(self.datalog.load().rules(), &self.policies.load());
+        let after = owned_items(built.datalog.rules(), &built.policies);
+        self.check_ownership(actor, changed_owners(&before, &after))?;
+        self.install_config(config, built);
+        Ok(())
     }
 
-    /// Get cache statistics
-    pub fn cache_stats(&self) -> CacheStats {
-        CacheStats {
-            size: self.cache.len(),
-            hit_rate: self.metrics.cache_hit_rate(),
+    /// Swap in a built configuration as one generation, and keep it for
+    /// rollback
+    fn install_config(&self, config: RUNEConfig, built: BuiltConfig) {
+        let rules = built.datalog.rules().len();
+        let policies = config.policies.len();
+        let mut installed = None;
+        let generation = self.install(|| {
+            self.scopes.store(built.scopes);
+            self.builtins.store(built.builtins);
+            self.datalog.store(Arc::new(built.datalog));
+            self.policies.store(Arc::new(built.policies));
+            if let Some(canonicalize) = config.canonicalize {
+                self.canonicalizer
+                    .store(Arc::new(Canonicalizer::new(canonicalize)));
+            }
+            if let Some(attributes) = config.attributes {
+                self.attribute_merger
+                    .store(Arc::new(AttributeMerger::new(attributes)));
+            }
+            if let Some(messages) = config.messages {
+                self.explanation_renderer
+                    .store(Arc::new(ExplanationRenderer::new(messages)));
+            }
+            if let Some(routes) = config.routes {
+                self.routes.store(Arc::new(routes));
+            }
+            if let Some(cache) = config.cache {
+                self.cache_rules.store(Arc::new(cache));
+            }
+            self.artifact.store(Some(Arc::new(config.artifact)));
+            installed = Some(self.installed());
+        });
+        if let Some(installed) = installed {
+            self.record_version(installed, generation, rules, policies, None);
         }
     }
 
-    /// Get engine metrics
-    pub fn metrics(&self) -> Arc<EngineMetrics> {
-        self.metrics.clone()
+    /// Everything the active configuration consists of
+    fn installed(&self) -> InstalledConfig {
+        InstalledConfig {
+            scopes: self.scopes.load_full(),
+            builtins: self.builtins.load_full(),
+            datalog: self.datalog.load_full(),
+            policies: self.policies.load_full(),
+            canonicalizer: self.canonicalizer.load_full(),
+            attribute_merger: self.attribute_merger.load_full(),
+            explanation_renderer: self.explanation_renderer.load_full(),
+            routes: self.routes.load_full(),
+            cache_rules: self.cache_rules.load_full(),
+            artifact: self.artifact.load_full(),
+        }
     }
 
-    /// Hot-reload Datalog rules (zero-downtime atomic swap)
+    /// Keep an installed configuration, dropping the oldest beyond
+    /// [`MAX_VERSIONS`]
+    fn record_version(
+        &self,
+        installed: InstalledConfig,
+        generation: u64,
+        rules: usize,
+        policies: usize,
+        rollback_of: Option<u64>,
+    ) -> ConfigVersion {
+        let info = ConfigVersion {
+            version: self.version_ids.fetch_add(1, Ordering::Relaxed) + 1,
+            generation,
+            rules,
+            policies,
+            artifact: installed.artifact.as_deref().cloned(),
+            installed_at_ms: crate::history::now_ms(),
+            rollback_of,
+        };
+        let mut versions = self.versions.lock().unwrap_or_else(PoisonError::into_inner);
+        while versions.len() >= MAX_VERSIONS {
+            versions.pop_front();
+        }
+        versions.push_back((info.clone(), installed));
+        self.current_version.store(info.version, Ordering::Release);
+        info
+    }
+
+    /// Recently installed configurations, oldest first; only the latest
+    /// [`MAX_VERSIONS`] are kept
+    pub fn config_versions(&self) -> Vec<ConfigVersion> {
+        self.versions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(info, _)| info.clone())
+            .collect()
+    }
+
+    /// Version the active configuration was installed as; `None` before
+    /// any install, or once rules or policies were reloaded on their own
+    pub fn current_version(&self) -> Option<u64> {
+        match self.current_version.load(Ordering::Acquire) {
+            0 => None,
+            version => Some(version),
+        }
+    }
+
+    /// Swap a recently installed configuration back in, as a new
+    /// generation and a new version
+    ///
+    /// Everything the install swapped in is restored: rules, policies,
+    /// scopes, builtins, canonicalization, attribute merging, messages,
+    /// routes and cache rules. Rule flags are not part of a version; the
+    /// current ones apply to the restored rules and policies. Facts are
+    /// untouched. Fails with [`RUNEError::ConfigError`] when `version` is
+    /// not among the [`config_versions`](Self::config_versions).
+    pub fn rollback_to(&self, version: u64) -> Result<ConfigVersion> {
+        let (info, installed) = self.config_version(version)?;
+        self.reinstall(version, info, installed)
+    }
+
+    /// Swap a recently installed configuration back in on behalf of `actor`
+    ///
+    /// As [`rollback_to`](Self::rollback_to), but fails with
+    /// [`RUNEError::AuthorizationDenied`], leaving the engine as it was,
+    /// when the rollback would add, remove or rewrite a rule or policy owned
+    /// by a team `actor` is not a member of, as for
+    /// [`apply_config_as`](Self::apply_config_as).
+    pub fn rollback_to_as(&self, version: u64, actor: &str) -> Result<ConfigVersion> {
+        let (info, installed) = self.config_version(version)?;
+        let before = owned_items(self.datalog.load().rules(), &self.policies.load());
+        let after = owned_items(installed.datalog.rules(), &installed.policies);
+        self.check_ownership(actor, changed_owners(&before, &after))?;
+        self.reinstall(version, info, installed)
+    }
+
+    /// A kept version and the configuration installed as it
+    fn config_version(&self, version: u64) -> Result<(ConfigVersion, InstalledConfig)> {
+        self.versions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|(info, _)| info.version == version)
+            .cloned()
+            .ok_or_else(|| {
+                RUNEError::ConfigError(format!("Unknown configuration version: {}", version))
+            })
+    }
+
+    /// Swap in the configuration kept as `version`, recording a new version
+    fn reinstall(
+        &self,
+        version: u64,
+        info: ConfigVersion,
+        installed: InstalledConfig,
+    ) -> Result<ConfigVersion> {
+        let datalog = Arc::new(installed.datalog.with_flags(&self.flags));
+        let policies = Arc::new(installed.policies.with_flags(&self.flags)?);
+
+        let generation = self.install(|| {
+            self.scopes.store(installed.scopes.clone());
+            self.builtins.store(installed.builtins.clone());
+            self.datalog.store(datalog);
+            self.policies.store(policies);
+            self.canonicalizer.store(installed.canonicalizer.clone());
+            self.attribute_merger
+                .store(installed.attribute_merger.clone());
+            self.explanation_renderer
+                .store(installed.explanation_renderer.clone());
+            self.routes.store(installed.routes.clone());
+            self.cache_rules.store(installed.cache_rules.clone());
+            self.artifact.store(installed.artifact.clone());
+        });
+        Ok(self.record_version(
+            installed,
+            generation,
+            info.rules,
+            info.policies,
+            Some(version),
+        ))
+    }
+
+    /// Build and check a parsed RUNE file's rules and policies, with the
+    /// sections they depend on, without installing anything
+    ///
+    /// Returns the rest of the file alongside.
+    fn build_config(&self, config: RUNEConfig) -> Result<(RUNEConfig, BuiltConfig)> {
+        let mut config = crate::layers::resolve_enabled(config)?;
+        let mut policies = PolicySet::new();
+        for policy in &config.policies {
+            policies.add_policy(&policy.id, &policy.content)?;
+        }
+        let policies = self.with_flags(policies)?;
+        let builtins = match (&config.builtins, &config.strings) {
+            (None, None) => self.builtins.load_full(),
+            (builtins, strings) => {
+                let mut registry = match builtins {
+                    Some(builtins) => builtins.load(self.config.builtin_module_dir.as_deref())?,
+                    None => (*self.builtins.load_full()).clone(),
+                };
+                if let Some(strings) = strings {
+                    strings.register(&mut registry)?;
+                }
+                Arc::new(registry)
+            }
+        };
+        let scopes = match config.scopes.take() {
+            Some(scopes) => Arc::new(scopes),
+            None => self.scopes.load_full(),
+        };
+        let limits = match config.limits.take() {
+            Some(limits) => Arc::new(limits),
+            None => self.datalog.load().limits().clone(),
+        };
+        let backend = match config.evaluation.take() {
+            Some(evaluation) => evaluation.backend,
+            None => self.datalog.load().backend(),
+        };
+        let rules = std::mem::take(&mut config.rules);
+        let datalog = self.build_datalog(rules, &builtins, scopes.clone(), limits, backend)?;
+        Ok((
+            config,
+            BuiltConfig {
+                scopes,
+                builtins,
+                datalog,
+                policies,
+            },
+        ))
+    }
+
+    /// Build a parsed RUNE file and keep it next to the active
+    /// configuration, for what-if evaluation against live facts
+    ///
+    /// The file is checked as [`RUNEEngine::apply_config`] would check it,
+    /// and sections it lacks are taken from the active configuration at
+    /// staging time. Staging never changes a decision: evaluate against a
+    /// staged configuration with [`RUNEEngine::staged_handle`]. Only the
+    /// latest [`MAX_STAGED`] configurations are kept.
+    pub fn stage_config(&self, config: RUNEConfig) -> Result<StagedConfig> {
+        self.expire_due(Instant::now());
+        let policies = config.policies.len();
+        let (config, built) = self.build_config(config)?;
+        let canonicalizer = match config.canonicalize {
+            Some(canonicalize) => Arc::new(Canonicalizer::new(canonicalize)),
+            None => self.canonicalizer.load_full(),
+        };
+        let attribute_merger = match config.attributes {
+            Some(attributes) => Arc::new(AttributeMerger::new(attributes)),
+            None => self.attribute_merger.load_full(),
+        };
+
+        let info = StagedConfig {
+            id: self.staged_ids.fetch_add(1, Ordering::Relaxed) + 1,
+            rules: built.datalog.rules().len(),
+            policies,
+            artifact: config.artifact,
+            staged_at_ms: crate::history::now_ms(),
+        };
+        let snapshot = EngineSnapshot {
+            datalog: Arc::new(built.datalog),
+            policies: Arc::new(built.policies),
+            facts: self.facts.clone(),
+            canonicalizer,
+            attribute_merger,
+            generation: self.generation(),
+            failure_policy: self.config.failure_policy,
+            timeout_ms: self.config.timeout_ms,
+            allow_all_bootstrap: self.config.allow_all_bootstrap,
+        };
+
+        while self.staged.len() >= MAX_STAGED {
+            let oldest = self.staged.iter().map(|entry| *entry.key()).min();
+            if let Some(oldest) = oldest {
+                self.staged.remove(&oldest);
+            }
+        }
+        self.staged.insert(info.id, (info.clone(), snapshot));
+        Ok(info)
+    }
+
+    /// Staged configurations, oldest first
+    pub fn staged_configs(&self) -> Vec<StagedConfig> {
+        let mut staged: Vec<StagedConfig> =
+            self.staged.iter().map(|entry| entry.0.clone()).collect();
+        staged.sort_by_key(|info| info.id);
+        staged
+    }
+
+    /// Handle evaluating requests against staged configuration `id`
+    ///
+    /// Unlike [`RUNEEngine::snapshot_handle`], it reads the live fact store,
+    /// so it decides as the active configuration would if `id` replaced it.
+    /// Facts past their time-to-live are removed first; later ones are
+    /// removed as the engine authorizes requests.
+    pub fn staged_handle(&self, id: u64) -> Option<EngineSnapshot> {
+        self.expire_due(Instant::now());
+        self.staged.get(&id).map(|entry| entry.1.clone())
+    }
+
+    /// Decide `request` as the engine would with `candidate` in place of
+    /// its Cedar policies, for a dry run of a policy change
+    ///
+    /// The active rules and live facts take part as usual. The decision is
+    /// neither cached nor counted in the engine's metrics, so evaluating a
+    /// candidate alongside live traffic changes nothing callers see. To try
+    /// a whole configuration instead, stage it with
+    /// [`RUNEEngine::stage_config`].
+    pub fn evaluate_shadow(
+        &self,
+        request: &Request,
+        candidate: &PolicySet,
+    ) -> Result<AuthorizationResult> {
+        self.expire_due(Instant::now());
+        let (datalog, policies) =
+            self.consistently(|| (self.datalog.load_full(), self.policies.load_full()));
+        let live = EngineSnapshot {
+            datalog,
+            policies,
+            facts: self.facts.clone(),
+            canonicalizer: self.canonicalizer.load_full(),
+            attribute_merger: self.attribute_merger.load_full(),
+            generation: self.generation(),
+            failure_policy: self.config.failure_policy,
+            timeout_ms: self.config.timeout_ms,
+            allow_all_bootstrap: self.config.allow_all_bootstrap,
+        };
+        live.authorize_with(request, candidate)
+    }
+
+    /// Drop staged configuration `id`; false if there was none
+    pub fn discard_staged(&self, id: u64) -> bool {
+        self.staged.remove(&id).is_some()
+    }
+
+    /// Compose a base configuration and its overlays and install the result
+    /// (see [`crate::layers`])
+    ///
+    /// Nothing is installed when the layers conflict. Returns which layer
+    /// each rule and policy came from.
+    pub fn apply_layers(
+        &self,
+        layers: impl IntoIterator<Item = ConfigLayer>,
+    ) -> Result<Provenance> {
+        let layered = crate::layers::compose(layers)?;
+        self.apply_config(layered.config)?;
+        Ok(layered.provenance)
+    }
+
+    /// Add a fact to the engine
+    pub fn add_fact(&self, predicate: impl Into<String>, args: Vec<Value>) {
+        self.facts
+            .add_fact(crate::facts::Fact::new(predicate, args));
+    }
+
+    /// Add many facts with one fact store update (see
+    /// [`FactStore::add_facts_bulk`])
+    pub fn add_facts_bulk(&self, facts: Vec<Fact>) {
+        self.facts.add_facts_bulk(facts);
+    }
+
+    /// Add a fact that is removed by the first compaction after `ttl`
+    pub fn add_fact_with_ttl(&self, predicate: impl Into<String>, args: Vec<Value>, ttl: Duration) {
+        self.facts
+            .add_fact_with_ttl(Fact::new(predicate, args), ttl);
+    }
+
+    /// Add a fact carrying governance labels (see [`crate::labels`])
+    pub fn add_fact_with_labels(
+        &self,
+        predicate: impl Into<String>,
+        args: Vec<Value>,
+        labels: Labels,
+    ) {
+        self.facts
+            .add_fact_with_labels(Fact::new(predicate, args), labels);
+    }
+
+    /// Fact changes recorded in the history as made by `origin`
+    pub fn attributed(&self, origin: ChangeOrigin) -> AttributedFacts<'_> {
+        AttributedFacts {
+            engine: self,
+            facts: self.facts.attributed(origin),
+        }
+    }
+
+    /// Recorded fact changes matching `query`, oldest first
+    pub fn fact_history(&self, query: &HistoryQuery) -> Result<Vec<FactEvent>> {
+        self.facts.history(query).ok_or_else(history_disabled)
+    }
+
+    /// Base facts as of `at_ms` milliseconds since the Unix epoch: those
+    /// added by then and still valid at it (see [`crate::history`])
+    pub fn facts_as_of(&self, at_ms: u64) -> Result<Vec<Fact>> {
+        self.facts.as_of(at_ms).ok_or_else(history_disabled)?
+    }
+
+    /// Labelled facts, rules and policies matching `selector`, ordered by
+    /// kind and then ID
+    ///
+    /// Disabled rules and policies are included: their labels still say
+    /// where they came from.
+    pub fn labeled(&self, selector: &LabelSelector) -> Vec<Labeled> {
+        let mut items: Vec<Labeled> = self
+            .facts
+            .labeled_facts()
+            .into_iter()
+            .map(|(fact, labels)| Labeled::fact(&fact, labels))
+            .chain(self.datalog.load().rules().iter().map(Labeled::rule))
+            .chain(self.policies.load().labels())
+            .filter(|item| selector.matches(&item.labels))
+            .collect();
+        items.sort_by(|a, b| (a.target, &a.id).cmp(&(b.target, &b.id)));
+        items
+    }
+
+    /// Labels of the policies that decided `result`, for audit records
+    ///
+    /// Policies without labels, and policies since removed by a reload, are
+    /// left out.
+    pub fn decision_labels(&self, result: &AuthorizationResult) -> Vec<Labeled> {
+        let policies = self.policies.load();
+        result
+            .evaluated_rules
+            .iter()
+            .filter_map(|id| policies.labels_of(id))
+            .filter(|labeled| !labeled.labels.is_empty())
+            .collect()
+    }
+
+    /// Retract a fact, returning whether it was present
+    pub fn retract_fact(&self, predicate: impl Into<String>, args: Vec<Value>) -> bool {
+        self.retracted(self.facts.retract_fact(&Fact::new(predicate, args)))
+    }
+
+    /// Grant `role` to `holder`, a principal or group ID, as a
+    /// [`ROLE_PREDICATE`](crate::permissions::ROLE_PREDICATE) fact
+    ///
+    /// Returns whether the role is new to the holder. Fails without changing
+    /// anything when either ID is empty or malformed.
+    pub fn grant_role(&self, holder: &str, role: &str) -> Result<bool> {
+        self.attributed(ChangeOrigin::default())
+            .grant_role(holder, role)
+    }
+
+    /// Revoke `role` from `holder`, returning whether it was held
+    pub fn revoke_role(&self, holder: &str, role: &str) -> Result<bool> {
+        self.attributed(ChangeOrigin::default())
+            .revoke_role(holder, role)
+    }
+
+    /// Add `member`, a principal or group ID, to `group` as a
+    /// [`GROUP_PREDICATE`](crate::permissions::GROUP_PREDICATE) fact
+    ///
+    /// Returns whether the membership is new. Fails without changing
+    /// anything when either ID is malformed or they are the same.
+    pub fn add_to_group(&self, member: &str, group: &str) -> Result<bool> {
+        self.attributed(ChangeOrigin::default())
+            .add_to_group(member, group)
+    }
+
+    /// Remove `member` from `group`, returning whether it was a member
+    pub fn remove_from_group(&self, member: &str, group: &str) -> Result<bool> {
+        self.attributed(ChangeOrigin::default())
+            .remove_from_group(member, group)
+    }
+
+    /// Record that `request`'s principal asks for the access it was refused
+    /// (see [`crate::access_requests`])
+    ///
+    /// Returns the pending request, which is the earlier one if the same
+    /// access is already pending, and whether it is new.
+    pub fn request_access(
+        &self,
+        request: &Request,
+        justification: Option<String>,
+    ) -> Result<(AccessRequest, bool)> {
+        let canonical = self.canonicalizer.load().canonicalize(request);
+        let request = canonical.as_ref().unwrap_or(request);
+        self.access_requests
+            .open(request, justification, crate::history::now_ms())
+    }
+
+    /// Approve pending access request `id` on behalf of `origin`, recording
+    /// its approval fact
+    ///
+    /// Returns `None` if there is no such request, and fails if it was
+    /// already reviewed. Cached decisions are dropped so the approval takes
+    /// effect at once.
+    pub fn approve_access(
+        &self,
+        id: u64,
+        origin: ChangeOrigin,
+        note: Option<String>,
+    ) -> Result<Option<AccessRequest>> {
+        let reviewed = self.access_requests.review(
+            id,
+            AccessRequestStatus::Approved,
+            &origin.actor,
+            note,
+            crate::history::now_ms(),
+        )?;
+        if let Some(access) = &reviewed {
+            self.attributed(origin).add_new(access.approval_fact());
+            self.clear_cache();
+        }
+        Ok(reviewed)
+    }
+
+    /// Deny pending access request `id` on behalf of `origin`
+    ///
+    /// Returns `None` if there is no such request, and fails if it was
+    /// already reviewed.
+    pub fn deny_access(
+        &self,
+        id: u64,
+        origin: ChangeOrigin,
+        note: Option<String>,
+    ) -> Result<Option<AccessRequest>> {
+        self.access_requests.review(
+            id,
+            AccessRequestStatus::Denied,
+            &origin.actor,
+            note,
+            crate::history::now_ms(),
+        )
+    }
+
+    /// Access request `id`
+    pub fn access_request(&self, id: u64) -> Option<AccessRequest> {
+        self.access_requests.get(id)
+    }
+
+    /// Access requests with `status`, or all of them, oldest first
+    pub fn access_requests(&self, status: Option<AccessRequestStatus>) -> Vec<AccessRequest> {
+        self.access_requests.list(status)
+    }
+
+    /// Add the `access_pending` reason to `result` if it is not a permit
+    /// and access to `request` is pending review
+    fn note_pending(
+        &self,
+        request: &Request,
+        mut result: AuthorizationResult,
+    ) -> AuthorizationResult {
+        if result.decision == Decision::Permit {
+            return result;
+        }
+        let canonical = self.canonicalizer.load().canonicalize(request);
+        let request = canonical.as_ref().unwrap_or(request);
+        if let Some(id) = self.access_requests.pending_for(request) {
+            result
+                .reason_codes
+                .push(Reason::new(ReasonCode::AccessPending).with_param("id", id));
+        }
+        result
+    }
+
+    /// Drop cached decisions after a retraction that `removed` a fact
+    fn retracted(&self, removed: bool) -> bool {
+        if removed {
+            self.clear_cache();
+        }
+        removed
+    }
+
+    /// Compact the fact store (see [`FactStore::compact`])
+    ///
+    /// Cached decisions are dropped when expired facts were removed, since
+    /// they may have depended on them.
+    pub fn compact_facts(&self) -> CompactionStats {
+        self.expire_sessions();
+        let stats = self.facts.compact();
+        if stats.expired_removed > 0 {
+            self.clear_cache();
+        }
+        stats
+    }
+
+    /// Snapshot of the fact store (see [`FactStore::export_snapshot`])
+    pub fn export_facts(&self, format: SnapshotFormat) -> Result<Vec<u8>> {
+        self.expire_due(Instant::now());
+        self.facts.export_snapshot(format)
+    }
+
+    /// Replace every fact with those of a snapshot (see
+    /// [`FactStore::import_snapshot`]), returning how many were loaded
+    ///
+    /// Cached decisions are dropped, since they were made on the old facts.
+    pub fn import_facts(&self, snapshot: &[u8]) -> Result<usize> {
+        let loaded = self.facts.import_snapshot(snapshot)?;
+        self.clear_cache();
+        Ok(loaded)
+    }
+
+    /// Open a session installing `attributes` for `principal` until it is
+    /// closed or `ttl` runs out (see [`crate::sessions`])
+    pub fn open_session(
+        &self,
+        principal: &Principal,
+        attributes: &[SessionAttribute],
+        ttl: Option<Duration>,
+    ) -> Result<SessionInfo> {
+        if let Some(attribute) = attributes
+            .iter()
+            .find(|attribute| attribute.predicate.trim().is_empty())
+        {
+            return Err(RUNEError::InvalidRequest(format!(
+                "Session attribute with arguments {:?} has no predicate",
+                attribute.args
+            )));
+        }
+
+        let session = self
+            .sessions
+            .open(&self.facts, &principal.entity.id, attributes, ttl);
+        self.clear_cache();
+        trace!(session = %session.id, "Session opened");
+        Ok(session)
+    }
+
+    /// Close a session, retracting its facts; returns whether it was open
+    pub fn close_session(&self, id: &str) -> bool {
+        let closed = self.sessions.close(&self.facts, id);
+        if closed {
+            self.clear_cache();
+        }
+        closed
+    }
+
+    /// Close every session whose TTL has run out, returning how many
+    pub fn expire_sessions(&self) -> usize {
+        let expired = self.sessions.expire(&self.facts, Instant::now());
+        if expired > 0 {
+            self.clear_cache();
+        }
+        expired
+    }
+
+    /// Remove every fact whose TTL has run out (see
+    /// [`FactStore::expire`]), returning how many
+    ///
+    /// Cached decisions are dropped when any were removed. Decisions and
+    /// queries run this themselves when a fact is due; hosts call it
+    /// periodically so expired facts do not linger between them. Returns at
+    /// once, without locking, when no deadline has passed.
+    pub fn expire_facts(&self) -> usize {
+        if !self.facts.expiry_due(Instant::now()) {
+            return 0;
+        }
+        let expired = self.facts.expire();
+        if expired > 0 {
+            self.clear_cache();
+        }
+        expired
+    }
+
+    /// End sessions and remove facts whose deadlines passed by `now`, if any
+    /// did
+    fn expire_due(&self, now: Instant) {
+        if self.sessions.due(now) {
+            self.expire_sessions();
+        }
+        if self.facts.expiry_due(now) {
+            self.expire_facts();
+        }
+    }
+
+    /// Open sessions, after closing expired ones
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.expire_sessions();
+        self.sessions.list(Instant::now())
+    }
+
+    /// Fact changes after `since` for a replica, at most `limit` of them
+    /// (see [`crate::replica`])
+    pub fn changes_since(
+        &self,
+        since: Option<ChangePosition>,
+        limit: usize,
+    ) -> Result<ChangeBatch> {
+        self.facts
+            .changes_since(since, limit)
+            .ok_or_else(|| RUNEError::ConfigError("The fact change log is disabled".to_string()))
+    }
+
+    /// Apply a batch of the primary's fact changes to this replica
+    pub fn apply_changes(&self, batch: &ChangeBatch) -> Result<ReplicaStatus> {
+        let replica = self
+            .replica
+            .as_ref()
+            .ok_or_else(|| RUNEError::ConfigError("The engine is not a replica".to_string()))?;
+        let status = replica.apply(&self.facts, batch)?;
+        if !batch.changes.is_empty() || batch.reset {
+            self.clear_cache();
+        }
+        Ok(status)
+    }
+
+    /// How far this replica is behind its primary; `None` for a primary
+    pub fn replica_status(&self) -> Option<ReplicaStatus> {
+        self.replica
+            .as_ref()
+            .map(|replica| replica.status(Instant::now()))
+    }
+
+    /// Number of entries in the fact store, duplicates included
+    pub fn fact_store_len(&self) -> usize {
+        self.facts.len()
+    }
+
+    /// Clear the decision and query caches
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+        self.query_cache.clear();
+    }
+
+    /// Configuration generation
+    ///
+    /// Starts at 0 and increases on every rule, policy, flag or
+    /// canonicalization change, so a decision can be tied to the
+    /// configuration that produced it. Fact updates do not bump it.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Fact store version
+    ///
+    /// Increases on every fact added, retracted or expired. With
+    /// [`generation`](Self::generation) it tells whether any decision may
+    /// have changed since both were read.
+    pub fn fact_version(&self) -> u64 {
+        self.facts.version()
+    }
+
+    /// Start a new configuration generation and drop stale decisions
+    fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.clear_cache();
+    }
+
+    /// Swap in several parts of a configuration as one generation,
+    /// returning that generation
+    fn install(&self, swap: impl FnOnce()) -> u64 {
+        let _guard = self
+            .swap_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.swaps.fetch_add(1, Ordering::SeqCst);
+        swap();
+        self.swaps.fetch_add(1, Ordering::SeqCst);
+        self.invalidate();
+        self.generation()
+    }
+
+    /// Run `evaluate` against a single configuration
+    ///
+    /// Evaluations load the rules and the policies separately, so one that
+    /// overlaps a combined swap may have seen half of it; it is run again
+    /// once the swap is done. Swaps are rare, and an evaluation is repeated
+    /// at most once per swap it overlaps.
+    fn consistently<T>(&self, mut evaluate: impl FnMut() -> T) -> T {
+        loop {
+            let before = self.swaps.load(Ordering::SeqCst);
+            if before % 2 == 1 {
+                std::thread::yield_now();
+                continue;
+            }
+            let result = evaluate();
+            if self.swaps.load(Ordering::SeqCst) == before {
+                return result;
+            }
+        }
+    }
+
+    /// Get cache statistics
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            size: self.cache.len(),
+            hit_rate: self.metrics.cache_hit_rate(),
+        }
+    }
+
+    /// Get engine metrics
+    pub fn metrics(&self) -> Arc<EngineMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Hot-reload Datalog rules (zero-downtime atomic swap)
     ///
     /// This method atomically replaces the DatalogEngine with a new one containing
     /// updated rules. Ongoing authorization requests continue using the old engine
@@ -289,14 +2178,23 @@ impl RUNEEngine {
     /// * `Ok(())` on success
     /// * `Err(_)` if the new engine cannot be created
     pub fn reload_datalog_rules(&self, rules: Vec<crate::datalog::types::Rule>) -> Result<()> {
-        // Create new DatalogEngine with updated rules
-        let new_engine = DatalogEngine::new(rules, self.facts.clone());
+        // Create new DatalogEngine with updated rules, keeping runtime flags
+        let new_engine = self.build_datalog(
+            rules,
+            &self.builtins.load(),
+            self.scopes.load_full(),
+            self.datalog.load().limits().clone(),
+            self.datalog.load().backend(),
+        )?;
 
         // Atomically swap the engine (lock-free!)
         self.datalog.store(Arc::new(new_engine));
+        // The rules no longer come from the loaded artifact
+        self.artifact.store(None);
+        self.current_version.store(0, Ordering::Release);
 
         // Clear cache since old decisions may be based on old rules
-        self.clear_cache();
+        self.invalidate();
 
         trace!("Datalog rules reloaded successfully");
         Ok(())
@@ -315,56 +2213,1111 @@ impl RUNEEngine {
     /// * `Ok(())` on success
     /// * `Err(_)` if the new policy set cannot be created
     pub fn reload_policies(&self, policies: PolicySet) -> Result<()> {
+        let policies = self.with_flags(policies)?;
+
         // Atomically swap the policy set (lock-free!)
         self.policies.store(Arc::new(policies));
+        // The policies no longer come from the loaded artifact
+        self.artifact.store(None);
+        self.current_version.store(0, Ordering::Release);
 
         // Clear cache since old decisions may be based on old policies
-        self.clear_cache();
+        self.invalidate();
 
         trace!("Cedar policies reloaded successfully");
         Ok(())
     }
 
+    /// Datalog engine for `rules`, bound to `builtins`, confined to
+    /// `scopes`, capped by `limits` and evaluated by `backend`, with runtime
+    /// flags applied
+    fn build_datalog(
+        &self,
+        rules: Vec<crate::datalog::types::Rule>,
+        builtins: &BuiltinRegistry,
+        scopes: Arc<FactScopes>,
+        limits: Arc<CardinalityLimits>,
+        backend: EvaluationBackend,
+    ) -> Result<DatalogEngine> {
+        let rules = builtins.bind(rules)?;
+        let undeclared = scopes.undeclared(&rules);
+        if !undeclared.is_empty() {
+            warn!(
+                ?undeclared,
+                "Rules name undeclared fact scopes and will derive nothing"
+            );
+        }
+
+        Ok(DatalogEngine::new(rules, self.facts.clone())
+            .with_backend(backend)
+            .with_scopes(scopes)
+            .with_limits(limits)
+            .with_flags(&self.flags))
+    }
+
+    /// `policies` with runtime flags applied; flags outlive config pushes
+    fn with_flags(&self, policies: PolicySet) -> Result<PolicySet> {
+        if self.flags.is_empty() {
+            Ok(policies)
+        } else {
+            policies.with_flags(&self.flags)
+        }
+    }
+
     /// Get current Datalog engine version (for testing/debugging)
     pub fn datalog_version(&self) -> Arc<DatalogEngine> {
         self.datalog.load_full()
     }
 
-    /// Get current PolicySet version (for testing/debugging)
-    pub fn policies_version(&self) -> Arc<PolicySet> {
-        self.policies.load_full()
+    /// Stream derived facts from the current rule set
+    ///
+    /// The stream owns its facts, so it stays valid across concurrent reloads.
+    pub fn query_facts(&self, query: &FactQuery) -> Result<FactStream> {
+        self.query_facts_cancellable(query, &CancellationToken::new())
     }
-}
 
-impl Default for RUNEEngine {
-    fn default() -> Self {
-        Self::new()
+    /// Like [`query_facts`](Self::query_facts), failing with
+    /// [`RUNEError::Cancelled`] once `cancel` is cancelled
+    pub fn query_facts_cancellable(
+        &self,
+        query: &FactQuery,
+        cancel: &CancellationToken,
+    ) -> Result<FactStream> {
+        self.expire_due(Instant::now());
+        self.datalog
+            .load()
+            .with_cancellation(cancel.clone())
+            .stream_facts(query)
     }
-}
 
-/// Cache statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CacheStats {
-    /// Current cache size
-    pub size: usize,
-    /// Cache hit rate (0.0 to 1.0)
-    pub hit_rate: f64,
-}
+    /// Count derived facts matching `query`
+    pub fn count_facts(&self, query: &FactQuery) -> Result<usize> {
+        self.count_facts_cancellable(query, &CancellationToken::new())
+    }
 
-/// Engine metrics
-#[derive(Debug, Clone)]
-pub struct EngineMetrics {
-    cache_hits: Arc<std::sync::atomic::AtomicU64>,
-    cache_misses: Arc<std::sync::atomic::AtomicU64>,
-    total_authorizations: Arc<std::sync::atomic::AtomicU64>,
-    total_permits: Arc<std::sync::atomic::AtomicU64>,
-    total_denies: Arc<std::sync::atomic::AtomicU64>,
-    total_forbids: Arc<std::sync::atomic::AtomicU64>,
-}
+    /// Like [`count_facts`](Self::count_facts), failing with
+    /// [`RUNEError::Cancelled`] once `cancel` is cancelled
+    pub fn count_facts_cancellable(
+        &self,
+        query: &FactQuery,
+        cancel: &CancellationToken,
+    ) -> Result<usize> {
+        self.expire_due(Instant::now());
+        self.datalog
+            .load()
+            .with_cancellation(cancel.clone())
+            .count_facts(query)
+    }
 
-impl EngineMetrics {
-    fn new() -> Self {
-        use std::sync::atomic::AtomicU64;
+    /// Answer a goal such as `can_access("alice", R)` with the base and
+    /// derived facts it matches
+    ///
+    /// Answers are cached until the fact store or the configuration
+    /// generation moves on, so repeated polling of the same goal costs one
+    /// evaluation per change rather than one per request. At most
+    /// `cache_size` goals are kept.
+    pub fn query(&self, goal: &Atom) -> Result<QueryAnswer> {
+        self.query_cancellable(goal, &CancellationToken::new())
+    }
+
+    /// Like [`query`](Self::query), failing with [`RUNEError::Cancelled`]
+    /// once `cancel` is cancelled; a cancelled evaluation is not cached
+    pub fn query_cancellable(
+        &self,
+        goal: &Atom,
+        cancel: &CancellationToken,
+    ) -> Result<QueryAnswer> {
+        self.expire_due(Instant::now());
+        let key = goal.to_string();
+        // Read both before evaluating: a change that lands mid-evaluation
+        // leaves the entry stale rather than wrongly fresh
+        let fact_version = self.facts.version();
+        let generation = self.generation();
+
+        if let Some(entry) = self.query_cache.get(&key) {
+            if entry.fact_version == fact_version && entry.generation == generation {
+                return Ok(QueryAnswer {
+                    facts: entry.facts.clone(),
+                    cached: true,
+                });
+            }
+        }
+
+        let mut facts: Vec<Fact> = self
+            .datalog
+            .load()
+            .with_cancellation(cancel.clone())
+            .derive_facts()?
+            .into_iter()
+            .filter(|fact| unify_atom_with_fact(goal, fact).is_some())
+            .collect();
+        facts.sort_unstable_by(|a, b| a.args.cmp(&b.args));
+        let facts = Arc::new(facts);
+
+        if self.query_cache.len() >= self.config.cache_size {
+            self.query_cache.retain(|_, entry| {
+                entry.fact_version == fact_version && entry.generation == generation
+            });
+        }
+        if self.query_cache.len() < self.config.cache_size {
+            self.query_cache.insert(
+                key,
+                QueryCacheEntry {
+                    fact_version,
+                    generation,
+                    facts: facts.clone(),
+                },
+            );
+        }
+
+        Ok(QueryAnswer {
+            facts,
+            cached: false,
+        })
+    }
+
+    /// Roles, groups, derived checks and scoped policies of `principal`
+    /// (see [`crate::permissions`])
+    pub fn permission_summary(&self, principal: &Principal) -> Result<PermissionSummary> {
+        self.expire_due(Instant::now());
+        let datalog = self.datalog.load();
+        let facts = datalog.derive_facts()?;
+        Ok(crate::permissions::summarize(
+            principal,
+            &facts,
+            datalog.active_rules(),
+            &self.policies.load(),
+        ))
+    }
+
+    /// Every policy `request` satisfies, permits and forbids alike
+    /// (see [`PolicyMatches`])
+    ///
+    /// Attributes are merged and identifiers canonicalized as for
+    /// [`authorize`](Self::authorize); nothing is cached.
+    pub fn matching_policies(&self, request: &Request) -> Result<PolicyMatches> {
+        let merged = self.attribute_merger.load().merge(request);
+        let request = merged.as_ref().map_or(request, |m| &m.request);
+        let canonical = self.canonicalizer.load().canonicalize(request);
+        self.policies
+            .load()
+            .matching_policies(canonical.as_ref().unwrap_or(request))
+    }
+
+    /// Get current PolicySet version (for testing/debugging)
+    pub fn policies_version(&self) -> Arc<PolicySet> {
+        self.policies.load_full()
+    }
+
+    /// Replace the identifier canonicalization rules
+    ///
+    /// Clears the decision cache, since cached entries were keyed on the
+    /// previous canonical forms.
+    pub fn set_canonicalizer(&self, canonicalizer: Canonicalizer) {
+        self.canonicalizer.store(Arc::new(canonicalizer));
+        self.invalidate();
+    }
+
+    /// Current identifier canonicalization rules
+    pub fn canonicalizer(&self) -> Arc<Canonicalizer> {
+        self.canonicalizer.load_full()
+    }
+
+    /// Replace the principal attribute merge settings
+    ///
+    /// Decisions are cached under the merged attributes, so the decision
+    /// cache stays valid and is left intact.
+    pub fn set_attribute_merger(&self, merger: AttributeMerger) {
+        self.attribute_merger.store(Arc::new(merger));
+    }
+
+    /// Current principal attribute merge settings
+    pub fn attribute_merger(&self) -> Arc<AttributeMerger> {
+        self.attribute_merger.load_full()
+    }
+
+    /// Replace the message catalogs explanations are rendered from
+    ///
+    /// Rendering happens after a decision is made, so the decision cache is
+    /// left intact.
+    pub fn set_explanation_renderer(&self, renderer: ExplanationRenderer) {
+        self.explanation_renderer.store(Arc::new(renderer));
+    }
+
+    /// Current message catalogs for explanations
+    pub fn explanation_renderer(&self) -> Arc<ExplanationRenderer> {
+        self.explanation_renderer.load_full()
+    }
+
+    /// Replace the HTTP route mappings
+    ///
+    /// Routes only decide which request is built, not how it is evaluated,
+    /// so the decision cache is left intact.
+    pub fn set_routes(&self, routes: RouteTable) {
+        self.routes.store(Arc::new(routes));
+    }
+
+    /// Replace the fact scopes `@scope` rules are evaluated in
+    ///
+    /// The current rules are re-evaluated under the new scopes, so cached
+    /// decisions are dropped.
+    pub fn set_fact_scopes(&self, scopes: FactScopes) -> Result<()> {
+        self.scopes.store(Arc::new(scopes));
+        let rules = self.datalog.load().rules().to_vec();
+        self.reload_datalog_rules(rules)
+    }
+
+    /// Current fact scopes
+    pub fn fact_scopes(&self) -> Arc<FactScopes> {
+        self.scopes.load_full()
+    }
+
+    /// Replace the builtin predicates rule bodies may call
+    ///
+    /// The current rules are rebound to the new builtins, so cached
+    /// decisions are dropped. Fails, leaving the old builtins in place, if
+    /// a current rule calls one with the wrong number of arguments.
+    pub fn set_builtins(&self, builtins: BuiltinRegistry) -> Result<()> {
+        let rules = self.datalog.load().rules().to_vec();
+        let previous = self.builtins.swap(Arc::new(builtins));
+        let result = self.reload_datalog_rules(rules);
+        if result.is_err() {
+            self.builtins.store(previous);
+        }
+        result
+    }
+
+    /// Current builtin predicates
+    pub fn builtins(&self) -> Arc<BuiltinRegistry> {
+        self.builtins.load_full()
+    }
+
+    /// Permit/forbid conflicts between the active policies and between the
+    /// active rules (see [`crate::conflicts`])
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        crate::conflicts::detect(&self.policies.load(), self.datalog.load().active_rules())
+    }
+
+    /// Which active policies and rules a corpus of `requests` exercises, and
+    /// which requests match no policy (see [`crate::coverage`])
+    pub fn coverage_report(&self, requests: &[Request]) -> Result<CoverageReport> {
+        let mut policies: Vec<PolicyCoverage> = self
+            .policies
+            .load()
+            .active_policies()
+            .map(|policy| PolicyCoverage {
+                id: crate::policy::policy_id(policy),
+                effect: policy.effect().into(),
+                matched: 0,
+            })
+            .collect();
+        policies.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let datalog = self.datalog_version();
+        let rules: Vec<&Rule> = datalog
+            .active_rules()
+            .iter()
+            .filter(|rule| !rule.is_fact() && rule.scope().is_none())
+            .collect();
+        let facts = if rules.is_empty() {
+            Vec::new()
+        } else {
+            datalog.derive_facts()?
+        };
+        let mut report = CoverageReport {
+            requests: requests.len(),
+            policies,
+            rules: rules
+                .iter()
+                .map(|rule| RuleCoverage {
+                    rule: rule
+                        .annotations
+                        .get("id")
+                        .cloned()
+                        .unwrap_or_else(|| rule.to_string()),
+                    matched: 0,
+                })
+                .collect(),
+            unmatched: Vec::new(),
+        };
+
+        for (index, request) in requests.iter().enumerate() {
+            let matches = self.matching_policies(request)?;
+            if matches.permits.is_empty() && matches.forbids.is_empty() {
+                report.unmatched.push(UnmatchedRequest::new(index, request));
+            }
+            for matched in matches.permits.iter().chain(&matches.forbids) {
+                if let Some(policy) = report.policies.iter_mut().find(|p| p.id == matched.id) {
+                    policy.matched += 1;
+                }
+            }
+
+            let focus = [
+                Value::String(request.principal.entity.id.clone()),
+                Value::String(request.resource.entity.id.clone()),
+            ];
+            for (rule, coverage) in rules.iter().zip(&mut report.rules) {
+                if crate::datalog::near_miss::matches_focus(rule, &facts, &focus) {
+                    coverage.matched += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Which traffic each loaded policy and rule can affect (see
+    /// [`crate::policy_index`])
+    ///
+    /// The index is built from the current configuration the first time it
+    /// is asked for, and kept until the generation changes.
+    pub fn policy_index(&self) -> Arc<PolicyIndex> {
+        let generation = self.generation();
+        if let Some(index) = self.policy_index.load_full() {
+            if index.generation == generation {
+                return index;
+            }
+        }
+        let index = Arc::new(self.consistently(|| {
+            let datalog = self.datalog.load();
+            PolicyIndex::build(
+                generation,
+                &self.policies.load(),
+                datalog.rules(),
+                datalog.active_rules(),
+            )
+        }));
+        self.policy_index.store(Some(index.clone()));
+        index
+    }
+
+    /// Predicates, entity types, actions and context keys the configuration
+    /// declares or the engine has seen (see [`crate::vocabulary`])
+    pub fn vocabulary(&self) -> Vocabulary {
+        let generation = self.generation();
+        let facts = self.facts.all_facts();
+        self.consistently(|| {
+            Vocabulary::build(
+                generation,
+                &self.policies.load(),
+                self.datalog.load().rules(),
+                &facts,
+                &self.observed,
+            )
+        })
+    }
+
+    /// Dump the active rules, policies, base facts and scopes, leaving out
+    /// facts past their time-to-live
+    ///
+    /// The dump is taken from a single configuration generation: if a reload
+    /// lands while it is being assembled, it is taken again.
+    pub fn export(&self) -> Result<Export> {
+        self.expire_due(Instant::now());
+        loop {
+            let generation = self.generation();
+            let rules = self
+                .datalog
+                .load()
+                .rules()
+                .iter()
+                .map(rule_source)
+                .collect::<Result<Vec<_>>>()?;
+            let policies = self
+                .policies
+                .load()
+                .sources()
+                .into_iter()
+                .map(|(id, content)| ExportedPolicy { id, content })
+                .collect();
+            let scopes = (*self.scopes.load_full()).clone();
+            let limits = (**self.datalog.load().limits()).clone();
+            let overrides = self
+                .rule_flags()
+                .into_iter()
+                .filter(|flag| flag.overridden)
+                .map(|flag| (flag.key, flag.enabled))
+                .collect();
+
+            if self.generation() != generation {
+                continue;
+            }
+
+            let mut seen = std::collections::HashSet::new();
+            let facts = self
+                .facts
+                .all_facts()
+                .iter()
+                .filter(|fact| seen.insert(*fact))
+                .cloned()
+                .collect();
+
+            return Ok(Export {
+                generation,
+                rules,
+                policies,
+                facts,
+                scopes,
+                limits,
+                overrides,
+            });
+        }
+    }
+
+    /// Policies that most often decide each (action, resource type) pair
+    pub fn decision_profile(&self) -> Arc<DecisionProfile> {
+        self.profile.clone()
+    }
+
+    /// How often speculative evaluation settled requests early
+    pub fn speculation_stats(&self) -> SpeculationStats {
+        self.profile.stats()
+    }
+
+    /// Artifact the active rules and policies were loaded from
+    ///
+    /// Set by [`apply_config`](Self::apply_config) and
+    /// [`apply_layers`](Self::apply_layers), and cleared when rules or
+    /// policies are replaced directly, so it never names an artifact the
+    /// engine no longer runs.
+    pub fn artifact(&self) -> Option<Arc<PolicyArtifact>> {
+        self.artifact.load_full()
+    }
+
+    /// Current HTTP route mappings
+    pub fn routes(&self) -> Arc<RouteTable> {
+        self.routes.load_full()
+    }
+
+    /// Switch the rules and policies carrying a flag on or off at runtime
+    ///
+    /// The key matches a `@flag("...")` annotation, or `@id("...")` when no
+    /// flag is given. The override survives later reloads. Returns the number
+    /// of rules and policies controlled by the flag; nothing is recorded when
+    /// the key is unknown.
+    pub fn set_rule_enabled(&self, key: &str, enabled: bool) -> Result<usize> {
+        let matched = self.flag_matches(key);
+        if matched == 0 {
+            return Ok(0);
+        }
+
+        self.flags.set(key, enabled);
+        self.apply_flags()?;
+        trace!(flag = key, enabled, "Rule flag updated");
+        Ok(matched)
+    }
+
+    /// Drop a runtime override so the flagged rules use their source default
+    ///
+    /// Returns `false` if no override was set for the key.
+    pub fn reset_rule_flag(&self, key: &str) -> Result<bool> {
+        if self.flags.reset(key).is_none() {
+            return Ok(false);
+        }
+        self.apply_flags()?;
+        Ok(true)
+    }
+
+    /// Switch a flag on or off on behalf of `actor`
+    ///
+    /// As [`set_rule_enabled`](Self::set_rule_enabled), but fails with
+    /// [`RUNEError::AuthorizationDenied`] when the flag switches a rule or
+    /// policy owned by a team `actor` is not a member of.
+    pub fn set_rule_enabled_as(&self, key: &str, enabled: bool, actor: &str) -> Result<usize> {
+        self.check_flag_ownership(key, actor)?;
+        self.set_rule_enabled(key, enabled)
+    }
+
+    /// Drop a flag's runtime override on behalf of `actor`, with the
+    /// ownership check of [`set_rule_enabled_as`](Self::set_rule_enabled_as)
+    pub fn reset_rule_flag_as(&self, key: &str, actor: &str) -> Result<bool> {
+        self.check_flag_ownership(key, actor)?;
+        self.reset_rule_flag(key)
+    }
+
+    /// Fail unless `actor` may switch the rules and policies behind `key`
+    fn check_flag_ownership(&self, key: &str, actor: &str) -> Result<()> {
+        let owned = owned_items(self.datalog.load().rules(), &self.policies.load());
+        self.check_ownership(actor, flag_owners(&owned, key))
+    }
+
+    /// Fail unless `actor` is a member of every team in `owners`
+    ///
+    /// Membership is read from the engine's own fixpoint, as for
+    /// [`permission_summary`](Self::permission_summary).
+    fn check_ownership(&self, actor: &str, owners: BTreeSet<String>) -> Result<()> {
+        if owners.is_empty() {
+            return Ok(());
+        }
+        if actor == ANONYMOUS_ACTOR {
+            return Err(RUNEError::AuthorizationDenied {
+                reason: format!(
+                    "An anonymous caller may not change rules or policies owned by {}",
+                    owners.into_iter().collect::<Vec<_>>().join(", ")
+                ),
+            });
+        }
+        let groups = self.permission_summary(&Principal::user(actor))?.groups;
+        let foreign: Vec<String> = owners
+            .into_iter()
+            .filter(|team| !groups.contains(team))
+            .collect();
+        if foreign.is_empty() {
+            return Ok(());
+        }
+        Err(RUNEError::AuthorizationDenied {
+            reason: format!(
+                "{} is not a member of {}, which owns rules or policies this change touches",
+                actor,
+                foreign.join(", ")
+            ),
+        })
+    }
+
+    /// List every flag known from annotations or runtime overrides
+    pub fn rule_flags(&self) -> Vec<FlagStatus> {
+        // key -> (source default, rules, policies)
+        let mut known: BTreeMap<String, (bool, usize, usize)> = BTreeMap::new();
+
+        for rule in self.datalog.load().rules() {
+            if let Some(key) = rule.flag_key() {
+                let entry =
+                    known
+                        .entry(key.to_string())
+                        .or_insert((rule.enabled_by_default(), 0, 0));
+                entry.1 += 1;
+            }
+        }
+        for (key, default) in self.policies.load().flag_keys() {
+            let entry = known.entry(key).or_insert((default, 0, 0));
+            entry.2 += 1;
+        }
+
+        known
+            .into_iter()
+            .map(|(key, (default, rules, policies))| {
+                let overridden = self.flags.get(&key);
+                FlagStatus {
+                    enabled: overridden.unwrap_or(default),
+                    overridden: overridden.is_some(),
+                    key,
+                    rules,
+                    policies,
+                }
+            })
+            .collect()
+    }
+
+    /// Count the rules and policies controlled by a flag
+    fn flag_matches(&self, key: &str) -> usize {
+        let rules = self
+            .datalog
+            .load()
+            .rules()
+            .iter()
+            .filter(|r| r.flag_key() == Some(key))
+            .count();
+        let policies = self
+            .policies
+            .load()
+            .flag_keys()
+            .filter(|(k, _)| k == key)
+            .count();
+        rules + policies
+    }
+
+    /// Rebuild the active rule and policy sets after a flag change
+    fn apply_flags(&self) -> Result<()> {
+        let policies = self.policies.load().with_flags(&self.flags)?;
+        let datalog = self.datalog.load().with_flags(&self.flags);
+
+        self.install(|| {
+            self.datalog.store(Arc::new(datalog));
+            self.policies.store(Arc::new(policies));
+        });
+        Ok(())
+    }
+
+    /// Capture an immutable view of the current rules, policies and facts
+    ///
+    /// The returned handle is cheap to clone and evaluates every request
+    /// against the same generation, even if a reload or fact update happens
+    /// while a batch is in progress. Snapshot evaluations bypass the decision
+    /// cache, since cached entries may belong to a newer generation.
+    pub fn snapshot_handle(&self) -> EngineSnapshot {
+        self.expire_due(Instant::now());
+        let facts = Arc::new(FactStore::from_snapshot(&FactSnapshot::from_store(
+            &self.facts,
+        )));
+        let (datalog, policies) =
+            self.consistently(|| (self.datalog.load_full(), self.policies.load_full()));
+        let datalog = Arc::new(datalog.with_fact_store(facts.clone()));
+
+        EngineSnapshot {
+            datalog,
+            policies,
+            facts,
+            canonicalizer: self.canonicalizer.load_full(),
+            attribute_merger: self.attribute_merger.load_full(),
+            generation: self.generation(),
+            failure_policy: self.config.failure_policy,
+            timeout_ms: self.config.timeout_ms,
+            allow_all_bootstrap: self.config.allow_all_bootstrap,
+        }
+    }
+}
+
+/// Decision of an engine with no rules or policies loaded
+///
+/// Deny, unless the allow-all bootstrap override is set. Nothing was
+/// evaluated, so nothing is cached either.
+fn unconfigured_result(allow_all_bootstrap: bool, start: Instant) -> AuthorizationResult {
+    let (decision, explanation, code) = if allow_all_bootstrap {
+        (
+            Decision::Permit,
+            "Permitted by the allow-all bootstrap override",
+            ReasonCode::Bootstrap,
+        )
+    } else {
+        (
+            Decision::Deny,
+            "No rules or policies are loaded",
+            ReasonCode::Unconfigured,
+        )
+    };
+    AuthorizationResult {
+        decision,
+        explanation: explanation.to_string(),
+        evaluated_rules: Vec::new(),
+        facts_used: Vec::new(),
+        policies_evaluated: 0,
+        evaluation_time_ns: start.elapsed().as_nanos() as u64,
+        cached: false,
+        coalesced: false,
+        failures: Vec::new(),
+        obligations: Vec::new(),
+        warnings: Vec::new(),
+        reason_codes: vec![Reason::new(code)],
+        valid_for_ms: 0,
+        proofs: Vec::new(),
+        near_misses: Vec::new(),
+    }
+}
+
+/// Stand-in for a Datalog result that cannot change the combined decision
+///
+/// Permit is the neutral element of [`Decision::combine`].
+fn skipped_datalog_result() -> AuthorizationResult {
+    AuthorizationResult {
+        decision: Decision::Permit,
+        explanation: "Datalog evaluation skipped".to_string(),
+        evaluated_rules: Vec::new(),
+        facts_used: Vec::new(),
+        policies_evaluated: 0,
+        evaluation_time_ns: 0,
+        cached: false,
+        coalesced: false,
+        failures: Vec::new(),
+        obligations: Vec::new(),
+        warnings: Vec::new(),
+        reason_codes: Vec::new(),
+        valid_for_ms: 0,
+        proofs: Vec::new(),
+        near_misses: Vec::new(),
+    }
+}
+
+/// Run the Datalog fixpoint, reporting a timeout if it overran `timeout_ms`
+///
+/// The interpreter stops once the budget is spent; the dataflow backend
+/// cannot stop midway, so its overrun is detected once it returns. Either
+/// way the result is then handled as a failure.
+fn evaluate_datalog(
+    engine: &DatalogEngine,
+    request: &Request,
+    facts: &FactStore,
+    timeout_ms: u64,
+) -> Result<AuthorizationResult> {
+    if timeout_ms == 0 {
+        return engine.evaluate(request, facts);
+    }
+    let start = Instant::now();
+    let budget = Duration::from_millis(timeout_ms);
+    let result = engine
+        .with_cancellation(CancellationToken::with_deadline(start + budget))
+        .evaluate(request, facts);
+    match result {
+        Err(RUNEError::Cancelled) => Err(RUNEError::Timeout(timeout_ms)),
+        Ok(_) if start.elapsed() > budget => Err(RUNEError::Timeout(timeout_ms)),
+        result => result,
+    }
+}
+
+/// Answer a request whose Datalog or Cedar evaluation failed
+///
+/// Each failed component is replaced according to the failure policy. If any
+/// failure falls back to the cache and a cached decision exists, that decision
+/// answers the request; without one the failure is handled as fail-closed.
+fn degrade(
+    policy: &FailurePolicy,
+    datalog_result: Result<AuthorizationResult>,
+    cedar_result: Result<AuthorizationResult>,
+    stale: Option<AuthorizationResult>,
+    start: Instant,
+) -> AuthorizationResult {
+    let mut failures = Vec::new();
+    let mut substitute = |class: FailureClass, error: RUNEError| {
+        let mut mode = policy.mode(class);
+        if mode == FailureMode::FallbackToCache && stale.is_none() {
+            mode = FailureMode::FailClosed;
+        }
+        warn!(class = %class, mode = %mode, error = %error, "Evaluation failed");
+
+        failures.push(FailureOutcome {
+            class,
+            mode,
+            error: error.to_string(),
+        });
+        AuthorizationResult {
+            decision: match mode {
+                FailureMode::FailOpen => Decision::Permit,
+                FailureMode::FailClosed | FailureMode::FallbackToCache => Decision::Deny,
+            },
+            explanation: format!("{} handled as {}", class, mode),
+            evaluated_rules: Vec::new(),
+            facts_used: Vec::new(),
+            policies_evaluated: 0,
+            evaluation_time_ns: 0,
+            cached: false,
+            coalesced: false,
+            failures: Vec::new(),
+            obligations: Vec::new(),
+            warnings: Vec::new(),
+            reason_codes: Vec::new(),
+            valid_for_ms: 0,
+            proofs: Vec::new(),
+            near_misses: Vec::new(),
+        }
+    };
+
+    let datalog_result =
+        datalog_result.unwrap_or_else(|e| substitute(FailureClass::of_datalog(&e), e));
+    let cedar_result = cedar_result.unwrap_or_else(|e| substitute(FailureClass::CedarError, e));
+
+    let from_cache = failures
+        .iter()
+        .any(|f| f.mode == FailureMode::FallbackToCache);
+    let mut result = match stale {
+        Some(mut cached) if from_cache => {
+            cached.cached = true;
+            cached.evaluation_time_ns = start.elapsed().as_nanos() as u64;
+            cached
+        }
+        _ => combine_results(datalog_result, cedar_result, start),
+    };
+
+    let notes: Vec<_> = failures
+        .iter()
+        .map(|f| format!("{} handled as {}", f.class, f.mode))
+        .collect();
+    result.explanation = format!("{}; {}", notes.join("; "), result.explanation);
+    let reasons = failures.iter().map(|f| {
+        Reason::new(ReasonCode::DependencyFailure)
+            .with_param("class", f.class)
+            .with_param("mode", f.mode)
+    });
+    result.reason_codes = reasons.chain(result.reason_codes).collect();
+    result.failures = failures;
+    // A degraded answer is only good for this request
+    result.valid_for_ms = 0;
+    result
+}
+
+/// Append the source that won each attribute conflict to the explanation
+fn note_winners(mut result: AuthorizationResult, merged: &MergedRequest) -> AuthorizationResult {
+    for winner in &merged.winners {
+        result.explanation = format!("{}; {}", result.explanation, winner);
+        result.reason_codes.push(
+            Reason::new(ReasonCode::AttributeSource)
+                .with_param("attribute", &winner.attribute)
+                .with_param("source", &winner.source)
+                .with_param("strategy", winner.strategy)
+                .with_param("candidates", winner.candidates),
+        );
+    }
+    result
+}
+
+/// Merge Datalog and Cedar results into a single authorization result
+fn combine_results(
+    datalog_result: AuthorizationResult,
+    cedar_result: AuthorizationResult,
+    start: Instant,
+) -> AuthorizationResult {
+    let decision = datalog_result.decision.combine(cedar_result.decision);
+
+    let (explanation, reason) = match decision {
+        Decision::Permit => {
+            let rules = datalog_result.evaluated_rules.len() + cedar_result.evaluated_rules.len();
+            (
+                format!("Permitted by {} rules", rules),
+                Reason::new(ReasonCode::Permitted).with_param("rules", rules),
+            )
+        }
+        Decision::Deny => (
+            "No matching permit rules".to_string(),
+            Reason::new(ReasonCode::NoMatchingPermit),
+        ),
+        Decision::Forbid => {
+            if cedar_result.decision == Decision::Forbid {
+                (
+                    cedar_result.explanation,
+                    Reason::new(ReasonCode::Forbidden).with_param("source", "cedar"),
+                )
+            } else {
+                (
+                    datalog_result.explanation,
+                    Reason::new(ReasonCode::Forbidden).with_param("source", "datalog"),
+                )
+            }
+        }
+    };
+
+    let mut evaluated_rules = datalog_result.evaluated_rules;
+    evaluated_rules.extend(cedar_result.evaluated_rules);
+
+    let mut facts_used = datalog_result.facts_used;
+    facts_used.extend(cedar_result.facts_used);
+
+    let mut obligations = Vec::new();
+    let mut warnings = Vec::new();
+    let mut near_misses = Vec::new();
+    if decision != Decision::Permit {
+        near_misses = datalog_result.near_misses;
+    } else {
+        obligations = datalog_result.obligations;
+        obligations.extend(cedar_result.obligations);
+        warnings = datalog_result.warnings;
+        warnings.extend(cedar_result.warnings);
+    }
+
+    AuthorizationResult {
+        decision,
+        explanation,
+        evaluated_rules,
+        facts_used,
+        policies_evaluated: cedar_result.policies_evaluated,
+        evaluation_time_ns: start.elapsed().as_nanos() as u64,
+        cached: false,
+        coalesced: false,
+        failures: Vec::new(),
+        obligations,
+        warnings,
+        reason_codes: vec![reason],
+        valid_for_ms: 0,
+        proofs: datalog_result.proofs,
+        near_misses,
+    }
+}
+
+/// Read-only, point-in-time view of an engine's rules, policies and facts
+///
+/// Obtained from [`RUNEEngine::snapshot_handle`]. Cloning only bumps
+/// reference counts, so a handle can be shared freely across threads.
+#[derive(Clone)]
+pub struct EngineSnapshot {
+    datalog: Arc<DatalogEngine>,
+    policies: Arc<PolicySet>,
+    facts: Arc<FactStore>,
+    canonicalizer: Arc<Canonicalizer>,
+    attribute_merger: Arc<AttributeMerger>,
+    generation: u64,
+    failure_policy: FailurePolicy,
+    timeout_ms: u64,
+    allow_all_bootstrap: bool,
+}
+
+impl EngineSnapshot {
+    /// Authorize a request against the captured generation
+    ///
+    /// Snapshots have no decision cache, so fallback-to-cache failures are
+    /// handled as fail-closed.
+    pub fn authorize(&self, request: &Request) -> Result<AuthorizationResult> {
+        self.authorize_with(request, &self.policies)
+    }
+
+    /// Authorize a request with `policies` in place of the captured ones
+    fn authorize_with(
+        &self,
+        request: &Request,
+        policies: &PolicySet,
+    ) -> Result<AuthorizationResult> {
+        let start = Instant::now();
+        if self.datalog.rules().is_empty() && policies.is_empty() {
+            return Ok(unconfigured_result(self.allow_all_bootstrap, start));
+        }
+        let merged = self.attribute_merger.merge(request);
+        let request = merged.as_ref().map_or(request, |m| &m.request);
+        let canonical = self.canonicalizer.canonicalize(request);
+        let request = canonical.as_ref().unwrap_or(request);
+
+        let datalog_result = evaluate_datalog(&self.datalog, request, &self.facts, self.timeout_ms);
+        let cedar_result = policies.evaluate(request);
+
+        let result = match (datalog_result, cedar_result) {
+            (Ok(datalog_result), Ok(cedar_result)) => {
+                combine_results(datalog_result, cedar_result, start)
+            }
+            (datalog_result, cedar_result) => degrade(
+                &self.failure_policy,
+                datalog_result,
+                cedar_result,
+                None,
+                start,
+            ),
+        };
+        Ok(match &merged {
+            Some(merged) => note_winners(result, merged),
+            None => result,
+        })
+    }
+
+    /// Datalog engine captured by this snapshot
+    pub fn datalog(&self) -> &DatalogEngine {
+        &self.datalog
+    }
+
+    /// Policy set captured by this snapshot
+    pub fn policies(&self) -> &PolicySet {
+        &self.policies
+    }
+
+    /// Fact store version at the time the snapshot was taken
+    pub fn fact_version(&self) -> u64 {
+        self.facts.version()
+    }
+
+    /// Number of facts visible to this snapshot
+    pub fn fact_count(&self) -> usize {
+        self.facts.len()
+    }
+
+    /// Configuration generation the snapshot was taken at
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl Default for RUNEEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fact changes made through an engine on behalf of one [`ChangeOrigin`]
+pub struct AttributedFacts<'a> {
+    engine: &'a RUNEEngine,
+    facts: FactWriter<'a>,
+}
+
+impl AttributedFacts<'_> {
+    /// See [`RUNEEngine::add_fact`]
+    pub fn add_fact(&self, predicate: impl Into<String>, args: Vec<Value>) {
+        self.facts.add_fact(Fact::new(predicate, args));
+    }
+
+    /// See [`RUNEEngine::add_facts_bulk`]
+    pub fn add_facts_bulk(&self, facts: Vec<Fact>) {
+        self.facts.add_facts_bulk(facts);
+    }
+
+    /// See [`RUNEEngine::add_fact_with_ttl`]
+    pub fn add_fact_with_ttl(&self, predicate: impl Into<String>, args: Vec<Value>, ttl: Duration) {
+        self.facts
+            .add_fact_with_ttl(Fact::new(predicate, args), ttl);
+    }
+
+    /// See [`RUNEEngine::add_fact_with_labels`]
+    pub fn add_fact_with_labels(
+        &self,
+        predicate: impl Into<String>,
+        args: Vec<Value>,
+        labels: Labels,
+    ) {
+        self.facts
+            .add_fact_with_labels(Fact::new(predicate, args), labels);
+    }
+
+    /// See [`RUNEEngine::retract_fact`]
+    pub fn retract_fact(&self, predicate: impl Into<String>, args: Vec<Value>) -> bool {
+        self.engine
+            .retracted(self.facts.retract_fact(&Fact::new(predicate, args)))
+    }
+
+    /// See [`RUNEEngine::grant_role`]
+    pub fn grant_role(&self, holder: &str, role: &str) -> Result<bool> {
+        Ok(self.add_new(crate::permissions::role_fact(holder, role)?))
+    }
+
+    /// See [`RUNEEngine::revoke_role`]
+    pub fn revoke_role(&self, holder: &str, role: &str) -> Result<bool> {
+        let fact = crate::permissions::role_fact(holder, role)?;
+        Ok(self.engine.retracted(self.facts.retract_fact(&fact)))
+    }
+
+    /// See [`RUNEEngine::add_to_group`]
+    pub fn add_to_group(&self, member: &str, group: &str) -> Result<bool> {
+        Ok(self.add_new(crate::permissions::membership_fact(member, group)?))
+    }
+
+    /// See [`RUNEEngine::remove_from_group`]
+    pub fn remove_from_group(&self, member: &str, group: &str) -> Result<bool> {
+        let fact = crate::permissions::membership_fact(member, group)?;
+        Ok(self.engine.retracted(self.facts.retract_fact(&fact)))
+    }
+
+    /// Add `fact` unless the store already holds it
+    fn add_new(&self, fact: Fact) -> bool {
+        let held = self.engine.facts.contains(&fact);
+        if !held {
+            self.facts.add_fact(fact);
+        }
+        !held
+    }
+}
+
+fn history_disabled() -> RUNEError {
+    RUNEError::ConfigError("The fact history is disabled".to_string())
+}
+
+/// Cache statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Current cache size
+    pub size: usize,
+    /// Cache hit rate (0.0 to 1.0)
+    pub hit_rate: f64,
+}
+
+/// Engine metrics
+#[derive(Debug, Clone)]
+pub struct EngineMetrics {
+    cache_hits: Arc<std::sync::atomic::AtomicU64>,
+    cache_misses: Arc<std::sync::atomic::AtomicU64>,
+    total_authorizations: Arc<std::sync::atomic::AtomicU64>,
+    total_permits: Arc<std::sync::atomic::AtomicU64>,
+    total_denies: Arc<std::sync::atomic::AtomicU64>,
+    total_forbids: Arc<std::sync::atomic::AtomicU64>,
+    failures: Arc<[std::sync::atomic::AtomicU64; FailureClass::ALL.len()]>,
+    coalesced: Arc<std::sync::atomic::AtomicU64>,
+    stale_hits: Arc<std::sync::atomic::AtomicU64>,
+    revalidations: Arc<std::sync::atomic::AtomicU64>,
+    cache_evictions: Arc<std::sync::atomic::AtomicU64>,
+    evaluation_time_saved_ns: Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl EngineMetrics {
+    fn new() -> Self {
+        use std::sync::atomic::AtomicU64;
 
         EngineMetrics {
             cache_hits: Arc::new(AtomicU64::new(0)),
@@ -373,411 +3326,3318 @@ impl EngineMetrics {
             total_permits: Arc::new(AtomicU64::new(0)),
             total_denies: Arc::new(AtomicU64::new(0)),
             total_forbids: Arc::new(AtomicU64::new(0)),
+            failures: Arc::new(Default::default()),
+            coalesced: Arc::new(AtomicU64::new(0)),
+            stale_hits: Arc::new(AtomicU64::new(0)),
+            revalidations: Arc::new(AtomicU64::new(0)),
+            cache_evictions: Arc::new(AtomicU64::new(0)),
+            evaluation_time_saved_ns: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Count a hit on a decision that took `saved_ns` to evaluate
+    fn record_cache_hit(&self, saved_ns: u64) {
+        use std::sync::atomic::Ordering;
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        self.evaluation_time_saved_ns
+            .fetch_add(saved_ns, Ordering::Relaxed);
+    }
+
+    /// Evaluation time cache hits have saved: the sum of the time each
+    /// decision answered from the cache originally took
+    pub fn evaluation_time_saved(&self) -> Duration {
+        Duration::from_nanos(
+            self.evaluation_time_saved_ns
+                .load(std::sync::atomic::Ordering::Relaxed),
+        )
+    }
+
+    fn record_cache_miss(&self) {
+        use std::sync::atomic::Ordering;
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_authorization(&self, decision: Decision, _duration: Duration) {
+        use std::sync::atomic::Ordering;
+
+        self.total_authorizations.fetch_add(1, Ordering::Relaxed);
+
+        match decision {
+            Decision::Permit => self.total_permits.fetch_add(1, Ordering::Relaxed),
+            Decision::Deny => self.total_denies.fetch_add(1, Ordering::Relaxed),
+            Decision::Forbid => self.total_forbids.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    fn record_failure(&self, class: FailureClass) {
+        self.failures[class.index()].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Dependency failures of one class handled by the failure policy
+    pub fn failures(&self, class: FailureClass) -> u64 {
+        self.failures[class.index()].load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn record_coalesced(&self) {
+        self.coalesced
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Requests answered by waiting on an identical in-flight evaluation
+    pub fn coalesced_requests(&self) -> u64 {
+        self.coalesced.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn record_stale_hit(&self) {
+        self.stale_hits
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Requests answered with a decision past its TTL while it was
+    /// re-evaluated (see [`EngineConfig::stale_while_revalidate_ms`])
+    pub fn stale_hits(&self) -> u64 {
+        self.stale_hits.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn record_revalidation(&self) {
+        self.revalidations
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Expired decisions refreshed in the background
+    pub fn revalidations(&self) -> u64 {
+        self.revalidations
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn record_cache_evictions(&self, count: usize) {
+        if count > 0 {
+            self.cache_evictions
+                .fetch_add(count as u64, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Decisions evicted from a full cache to make room for new ones
+    pub fn cache_evictions(&self) -> u64 {
+        self.cache_evictions
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Authorization requests handled since the engine was created
+    pub fn total_authorizations(&self) -> u64 {
+        self.total_authorizations
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn cache_hit_rate(&self) -> f64 {
+        use std::sync::atomic::Ordering;
+
+        let hits = self.cache_hits.load(Ordering::Relaxed) as f64;
+        let misses = self.cache_misses.load(Ordering::Relaxed) as f64;
+
+        if hits + misses == 0.0 {
+            0.0
+        } else {
+            hits / (hits + misses)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datalog::types::Rule;
+    use crate::types::{Action, Principal, Resource};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Engine with a policy loaded, so requests are evaluated and cached
+    fn configured_engine() -> RUNEEngine {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies("forbid(principal, action == Action::\"purge\", resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_engine_creation() {
+        let engine = RUNEEngine::new();
+        assert_eq!(engine.cache_stats().size, 0);
+    }
+
+    #[test]
+    fn test_engine_with_custom_config() {
+        let config = EngineConfig {
+            cache_size: 5000,
+            cache_ttl_secs: 30,
+            stale_while_revalidate_ms: 0,
+            parallel_eval: false,
+            timeout_ms: 200,
+            evaluation_backend: EvaluationBackend::Interpreter,
+            speculation: SpeculationConfig::default(),
+            failure_policy: FailurePolicy::default(),
+            change_log: 0,
+            fact_history: 0,
+            replica: None,
+            adaptive_ttl: AdaptiveTtlConfig::default(),
+            allow_all_bootstrap: false,
+            workers: WorkerConfig::default(),
+            builtin_module_dir: None,
+        };
+        let engine = RUNEEngine::with_config(config.clone());
+        assert_eq!(engine.config.cache_size, 5000);
+        assert_eq!(engine.config.cache_ttl_secs, 30);
+        assert!(!engine.config.parallel_eval);
+        assert_eq!(engine.config.timeout_ms, 200);
+    }
+
+    #[test]
+    fn test_engine_default() {
+        let engine = RUNEEngine::default();
+        assert_eq!(engine.cache_stats().size, 0);
+    }
+
+    #[test]
+    fn test_config_default() {
+        let config = EngineConfig::default();
+        assert_eq!(config.cache_size, 10_000);
+        assert_eq!(config.cache_ttl_secs, 60);
+        assert!(config.parallel_eval);
+        assert_eq!(config.timeout_ms, 100);
+    }
+
+    #[test]
+    fn test_decision_is_permitted() {
+        assert!(Decision::Permit.is_permitted());
+        assert!(!Decision::Deny.is_permitted());
+        assert!(!Decision::Forbid.is_permitted());
+    }
+
+    #[test]
+    fn test_decision_combination() {
+        // Forbid takes priority over everything
+        assert_eq!(Decision::Forbid.combine(Decision::Permit), Decision::Forbid);
+        assert_eq!(Decision::Forbid.combine(Decision::Deny), Decision::Forbid);
+        assert_eq!(Decision::Forbid.combine(Decision::Forbid), Decision::Forbid);
+        assert_eq!(Decision::Permit.combine(Decision::Forbid), Decision::Forbid);
+        assert_eq!(Decision::Deny.combine(Decision::Forbid), Decision::Forbid);
+
+        // Deny takes priority over Permit
+        assert_eq!(Decision::Deny.combine(Decision::Permit), Decision::Deny);
+        assert_eq!(Decision::Permit.combine(Decision::Deny), Decision::Deny);
+        assert_eq!(Decision::Deny.combine(Decision::Deny), Decision::Deny);
+
+        // Both Permit results in Permit
+        assert_eq!(Decision::Permit.combine(Decision::Permit), Decision::Permit);
+    }
+
+    #[test]
+    fn test_cache_key_generation() {
+        let request1 = Request::new(
+            Principal::agent("agent1"),
+            Action::new("read"),
+            Resource::file("/tmp/test.txt"),
+        );
+
+        let request2 = Request::new(
+            Principal::agent("agent1"),
+            Action::new("read"),
+            Resource::file("/tmp/test.txt"),
+        );
+
+        assert_eq!(request1.cache_key(), request2.cache_key());
+
+        let request3 = Request::new(
+            Principal::agent("agent2"),
+            Action::new("read"),
+            Resource::file("/tmp/test.txt"),
+        );
+
+        assert_ne!(request1.cache_key(), request3.cache_key());
+    }
+
+    #[test]
+    fn test_basic_authorization() {
+        let engine = RUNEEngine::new();
+        let request = Request::new(
+            Principal::agent("alice"),
+            Action::new("read"),
+            Resource::file("/data/public.txt"),
+        );
+
+        let result = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result.cached);
+        assert!(result.evaluation_time_ns > 0);
+    }
+
+    #[test]
+    fn test_authorization_with_context() {
+        let engine = RUNEEngine::new();
+        let request = Request::new(
+            Principal::agent("alice"),
+            Action::new("read"),
+            Resource::file("/data/public.txt"),
+        )
+        .with_context("ip_address", Value::string("192.168.1.1"))
+        .with_context("time", Value::string("2024-01-01T12:00:00Z"));
+
+        let result = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result.cached);
+    }
+
+    #[test]
+    fn test_cache_hit() {
+        let engine = configured_engine();
+        let request = Request::new(
+            Principal::agent("bob"),
+            Action::new("write"),
+            Resource::file("/data/private.txt"),
+        );
+
+        // First authorization - should miss cache
+        let result1 = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result1.cached);
+
+        // Second authorization - should hit cache
+        let result2 = engine.authorize(&request).expect("Authorization failed");
+        assert!(result2.cached);
+
+        // Cache stats should reflect this
+        let stats = engine.cache_stats();
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.hit_rate, 0.5); // 1 hit out of 2 requests
+
+        // The hit saved the first evaluation's time
+        assert_eq!(
+            engine.metrics().evaluation_time_saved(),
+            Duration::from_nanos(result1.evaluation_time_ns)
+        );
+    }
+
+    #[test]
+    fn test_cache_ttl_expiry() {
+        let config = EngineConfig {
+            cache_size: 100,
+            cache_ttl_secs: 1, // Very short TTL
+            stale_while_revalidate_ms: 0,
+            parallel_eval: true,
+            timeout_ms: 100,
+            evaluation_backend: EvaluationBackend::Interpreter,
+            speculation: SpeculationConfig::default(),
+            failure_policy: FailurePolicy::default(),
+            change_log: 0,
+            fact_history: 0,
+            replica: None,
+            adaptive_ttl: AdaptiveTtlConfig::default(),
+            allow_all_bootstrap: false,
+            workers: WorkerConfig::default(),
+            builtin_module_dir: None,
+        };
+        let engine = RUNEEngine::with_config(config);
+
+        let request = Request::new(
+            Principal::agent("charlie"),
+            Action::new("execute"),
+            Resource::file("/bin/script.sh"),
+        );
+
+        // First authorization
+        let result1 = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result1.cached);
+
+        // Wait for TTL to expire
+        thread::sleep(Duration::from_secs(2));
+
+        // Second authorization - cache should be expired
+        let result2 = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result2.cached);
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        let engine = Arc::new(RUNEEngine::with_config(EngineConfig {
+            cache_ttl_secs: 1,
+            stale_while_revalidate_ms: 60_000,
+            ..EngineConfig::default()
+        }));
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies("forbid(principal, action == Action::\"purge\", resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        let request = Request::new(
+            Principal::agent("frank"),
+            Action::new("purge"),
+            Resource::file("/data/old.txt"),
+        );
+        assert!(!engine.authorize(&request).unwrap().cached);
+        thread::sleep(Duration::from_millis(1100));
+
+        // Expired, but answered from the cache; one re-evaluation is queued
+        let stale = engine.authorize(&request).unwrap();
+        assert!(stale.cached);
+        assert_eq!(stale.valid_for_ms, 0);
+        assert!(engine.authorize(&request).unwrap().cached);
+        assert_eq!(engine.metrics().stale_hits(), 2);
+        assert_eq!(engine.revalidate_stale(), 1);
+        assert_eq!(engine.revalidate_stale(), 0);
+        let fresh = engine.authorize(&request).unwrap();
+        assert!(fresh.cached && fresh.valid_for_ms > 0);
+
+        // Async callers leave the re-evaluation to a worker
+        thread::sleep(Duration::from_millis(1100));
+        assert!(engine.authorize_async(&request, None).await.unwrap().cached);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while engine.metrics().revalidations() < 2 {
+            assert!(Instant::now() < deadline, "revalidation never ran");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(engine.metrics().stale_hits(), 3);
+    }
+
+    #[test]
+    fn test_cache_bypass_context() {
+        let engine = RUNEEngine::new();
+        let source = "version = \"rune/2.0\"\n\n[cache]\nbypass_context = [\"transaction_amount\"]\n\n[policies]\n@id(\"all\")\npermit(principal, action, resource);\n";
+        engine
+            .apply_config(crate::parser::parse_rune_file(source).unwrap())
+            .unwrap();
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("transfer"),
+            Resource::new("account", "acme"),
+        );
+        let transfer = request
+            .clone()
+            .with_context("transaction_amount", Value::Integer(250));
+
+        for _ in 0..2 {
+            let result = engine.authorize(&transfer).unwrap();
+            assert!(!result.cached);
+            assert_eq!(result.valid_for_ms, 0);
+        }
+        assert!(!engine.prefetch(&transfer).unwrap());
+        let batch = engine.authorize_batch(&[transfer.clone(), transfer]);
+        assert!(batch.iter().all(|result| !result.as_ref().unwrap().cached));
+
+        engine.authorize(&request).unwrap();
+        assert!(engine.authorize(&request).unwrap().cached);
+        assert_eq!(engine.cache_stats().size, 1);
+    }
+
+    #[test]
+    fn test_adaptive_cache_ttl() {
+        let engine = RUNEEngine::with_config(EngineConfig {
+            adaptive_ttl: AdaptiveTtlConfig {
+                enabled: true,
+                ..AdaptiveTtlConfig::default()
+            },
+            ..EngineConfig::default()
+        });
+        let request_for = |principal: &str| {
+            Request::new(
+                Principal::agent(principal),
+                Action::new("read"),
+                Resource::file("/tmp/report.txt"),
+            )
+        };
+
+        // Nothing about either principal has changed: cache for the maximum
+        assert_eq!(
+            engine.cache_ttl(&request_for("bob")),
+            Duration::from_secs(600)
+        );
+
+        // Churning facts about alice shorten her decisions' TTL only
+        for i in 0..50 {
+            engine.add_fact("clearance", vec![Value::string("alice"), Value::Integer(i)]);
+        }
+        let churning = engine.cache_ttl(&request_for("alice"));
+        assert!(churning < Duration::from_secs(10), "{:?}", churning);
+        assert!(churning >= Duration::from_secs(1));
+        assert_eq!(
+            engine.cache_ttl(&request_for("bob")),
+            Duration::from_secs(600)
+        );
+
+        // Disabled, the fixed TTL applies to everyone
+        let fixed = RUNEEngine::new();
+        fixed.add_fact("clearance", vec![Value::string("alice"), Value::Integer(1)]);
+        assert_eq!(
+            fixed.cache_ttl(&request_for("alice")),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_engine_records_loaded_artifact() {
+        let engine = RUNEEngine::new();
+        assert!(engine.artifact().is_none());
+
+        let source = "version = \"rune/2.0\"\n\n[rules]\nuser(alice).\n\n[artifact]\ngit_sha = \"0123abc\"\n";
+        engine
+            .apply_config(crate::parser::parse_rune_file(source).unwrap())
+            .unwrap();
+        let artifact = engine.artifact().unwrap();
+        assert_eq!(
+            *artifact,
+            PolicyArtifact::of_source(source, Some("git_sha = \"0123abc\"\n")).unwrap()
+        );
+
+        // Policies swapped in directly are not part of any artifact
+        engine.reload_policies(PolicySet::new()).unwrap();
+        assert!(engine.artifact().is_none());
+    }
+
+    #[test]
+    fn test_cardinality_limit_names_exploding_rule() {
+        let source = "version = \"rune/2.0\"\n\n[rules]\npair(X, Y) :- node(X), node(Y).\n\n[limits]\npair = 100\n";
+        for backend in [EvaluationBackend::Interpreter, EvaluationBackend::Dataflow] {
+            let engine = RUNEEngine::with_config(EngineConfig {
+                evaluation_backend: backend,
+                ..EngineConfig::default()
+            });
+            engine
+                .apply_config(crate::parser::parse_rune_file(source).unwrap())
+                .unwrap();
+            for i in 0..10 {
+                engine.add_fact("node", vec![Value::Integer(i)]);
+            }
+            assert_eq!(engine.datalog_version().derive_facts().unwrap().len(), 110);
+
+            // An 11th node makes 121 pairs
+            engine.add_fact("node", vec![Value::Integer(10)]);
+            let err = engine.datalog_version().derive_facts().unwrap_err();
+            assert!(matches!(
+                &err,
+                RUNEError::CardinalityExceeded { predicate, limit: 100, rule }
+                    if predicate == "pair" && rule == "pair(X, Y) :- node(X), node(Y)."
+            ));
+
+            // Rules swapped in directly keep the limits
+            engine
+                .reload_datalog_rules(crate::parser::parse_rules("pair(X, X) :- node(X).").unwrap())
+                .unwrap();
+            assert_eq!(engine.datalog_version().limits().get("pair"), Some(100));
+            assert_eq!(engine.export().unwrap().limits.get("pair"), Some(100));
+        }
+    }
+
+    #[test]
+    fn test_decisions_carry_reuse_hint() {
+        let engine = configured_engine();
+        let request = Request::new(
+            Principal::agent("erin"),
+            Action::new("read"),
+            Resource::file("/tmp/report.txt"),
+        );
+
+        // A fresh decision may be reused for the whole cache TTL
+        let fresh = engine.authorize(&request).unwrap();
+        assert_eq!(fresh.valid_for_ms, 60_000);
+
+        // A cached one for what is left of it
+        thread::sleep(Duration::from_millis(20));
+        let cached = engine.authorize(&request).unwrap();
+        assert!(cached.cached);
+        assert!(cached.valid_for_ms <= 59_980, "{}", cached.valid_for_ms);
+
+        // A fact about to expire may change the decision sooner
+        engine.add_fact_with_ttl("temp", vec![Value::string("x")], Duration::from_secs(5));
+        let expiring = engine.authorize(&request).unwrap();
+        assert!(expiring.valid_for_ms <= 5_000, "{}", expiring.valid_for_ms);
+
+        // Unconfigured decisions are not to be reused
+        let unconfigured = RUNEEngine::new().authorize(&request).unwrap();
+        assert_eq!(unconfigured.valid_for_ms, 0);
+    }
+
+    #[test]
+    fn test_cache_clear() {
+        let engine = configured_engine();
+        let request = Request::new(
+            Principal::agent("dave"),
+            Action::new("delete"),
+            Resource::file("/data/temp.txt"),
+        );
+
+        // Populate cache
+        engine.authorize(&request).expect("Authorization failed");
+        assert_eq!(engine.cache_stats().size, 1);
+
+        // Clear cache
+        engine.clear_cache();
+        assert_eq!(engine.cache_stats().size, 0);
+
+        // Next request should miss cache
+        let result = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result.cached);
+    }
+
+    #[tokio::test]
+    async fn test_authorize_async() {
+        let engine = Arc::new(configured_engine());
+        let request = Request::new(
+            Principal::agent("erin"),
+            Action::new("purge"),
+            Resource::file("/data/old.txt"),
+        );
+        let result = engine.authorize_async(&request, None).await.unwrap();
+        assert!(!result.cached);
+
+        // Answered from the cache the async call filled
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let result = engine
+            .authorize_async(&request, Some(deadline))
+            .await
+            .unwrap();
+        assert!(result.cached);
+
+        let expired = engine.authorize_async(&request, Some(Instant::now())).await;
+        assert!(matches!(expired, Err(RUNEError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_batch_and_candidates_async() {
+        let engine = Arc::new(configured_engine());
+        let request = Request::new(
+            Principal::agent("erin"),
+            Action::new("purge"),
+            Resource::file("/data/old.txt"),
+        );
+        let results = engine
+            .authorize_batch_async(&[request.clone(), request.clone()], None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        let result = results[0].as_ref().unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.policies_evaluated, 1);
+
+        let resources = [Resource::file("/data/a.txt"), Resource::file("/data/b.txt")];
+        let results = engine
+            .authorize_resources_async(&request, &resources, None)
+            .await
+            .unwrap();
+        assert!(results.iter().all(|r| r.decision == Decision::Deny));
+
+        let expired = engine
+            .authorize_batch_async(&[request], Some(Instant::now()))
+            .await;
+        assert!(matches!(expired, Err(RUNEError::Timeout(_))));
+    }
+
+    #[test]
+    fn test_cache_size_is_enforced() {
+        let engine = RUNEEngine::with_config(EngineConfig {
+            cache_size: 10,
+            ..EngineConfig::default()
+        });
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies("forbid(principal, action == Action::\"purge\", resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+
+        let request_for = |n: usize| {
+            Request::new(
+                Principal::agent(format!("agent-{}", n)),
+                Action::new("read"),
+                Resource::file("/data/report.txt"),
+            )
+        };
+        engine.authorize(&request_for(0)).unwrap();
+        // A decision never asked for again, made the cheapest so its
+        // eviction does not hang on how long the others took
+        let once = engine.authorize(&request_for(1)).unwrap();
+        engine.cache.insert(
+            request_for(1).cache_key(),
+            AuthorizationResult {
+                evaluation_time_ns: 1,
+                ..once
+            },
+            Instant::now(),
+            engine.cache_ttl(&request_for(1)),
+        );
+        for n in 2..50 {
+            engine.authorize(&request_for(n)).unwrap();
+            // Keep the first request hit so it survives eviction
+            engine.authorize(&request_for(0)).unwrap();
+        }
+
+        assert!(engine.cache_stats().size <= 10);
+        assert!(engine.metrics().cache_evictions() >= 40);
+        assert!(engine.authorize(&request_for(0)).unwrap().cached);
+        assert!(!engine.authorize(&request_for(1)).unwrap().cached);
+    }
+
+    #[test]
+    fn test_metrics_tracking() {
+        let engine = configured_engine();
+
+        let request1 = Request::new(
+            Principal::agent("eve_metrics"),
+            Action::new("read"),
+            Resource::file("/data/file1_metrics.txt"),
+        );
+        let request2 = Request::new(
+            Principal::agent("frank_metrics"),
+            Action::new("read"),
+            Resource::file("/data/file2_metrics.txt"),
+        );
+
+        // Perform some authorizations
+        let result1 = engine.authorize(&request1).expect("Authorization failed");
+        assert!(!result1.cached); // First request should not be cached
+
+        let result2 = engine.authorize(&request1).expect("Authorization failed");
+        assert!(result2.cached); // Second identical request should be cached
+
+        let result3 = engine.authorize(&request2).expect("Authorization failed");
+        assert!(!result3.cached); // Different request should not be cached
+
+        // Check metrics
+        let metrics = engine.metrics();
+        use std::sync::atomic::Ordering;
+
+        // Cache hits and misses
+        let hits = metrics.cache_hits.load(Ordering::Relaxed);
+        let misses = metrics.cache_misses.load(Ordering::Relaxed);
+        assert_eq!(hits, 1, "Expected 1 cache hit");
+        assert_eq!(misses, 2, "Expected 2 cache misses");
+
+        // Total authorizations only counts non-cached evaluations (cache misses)
+        let total = metrics.total_authorizations.load(Ordering::Relaxed);
+        assert_eq!(
+            total, 2,
+            "Expected 2 total authorizations (only cache misses are evaluated)"
+        );
+    }
+
+    #[test]
+    fn test_cache_hit_rate_zero_requests() {
+        let metrics = EngineMetrics::new();
+        assert_eq!(metrics.cache_hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_cache_hit_rate_calculation() {
+        let metrics = EngineMetrics::new();
+
+        // 3 misses, 2 hits = 0.4 hit rate
+        metrics.record_cache_miss();
+        metrics.record_cache_miss();
+        metrics.record_cache_miss();
+        metrics.record_cache_hit(1_000);
+        metrics.record_cache_hit(2_000);
+
+        assert_eq!(metrics.cache_hit_rate(), 0.4);
+        assert_eq!(metrics.evaluation_time_saved(), Duration::from_micros(3));
+    }
+
+    #[test]
+    fn test_add_fact() {
+        let engine = RUNEEngine::new();
+        engine.add_fact("user", vec![Value::string("alice")]);
+        engine.add_fact("role", vec![Value::string("alice"), Value::string("admin")]);
+
+        // Facts should be in the store (we can't easily verify without exposing the fact store)
+        // but at least ensure it doesn't panic
+    }
+
+    #[test]
+    fn test_sequential_evaluation() {
+        let config = EngineConfig {
+            cache_size: 100,
+            cache_ttl_secs: 60,
+            stale_while_revalidate_ms: 0,
+            parallel_eval: false, // Force sequential
+            timeout_ms: 100,
+            evaluation_backend: EvaluationBackend::Interpreter,
+            speculation: SpeculationConfig::default(),
+            failure_policy: FailurePolicy::default(),
+            change_log: 0,
+            fact_history: 0,
+            replica: None,
+            adaptive_ttl: AdaptiveTtlConfig::default(),
+            allow_all_bootstrap: false,
+            workers: WorkerConfig::default(),
+            builtin_module_dir: None,
+        };
+        let engine = RUNEEngine::with_config(config);
+
+        let request = Request::new(
+            Principal::agent("grace"),
+            Action::new("read"),
+            Resource::file("/data/sequential.txt"),
+        );
+
+        let result = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result.cached);
+        assert!(result.evaluation_time_ns > 0);
+    }
+
+    #[test]
+    fn test_parallel_evaluation() {
+        let config = EngineConfig {
+            cache_size: 100,
+            cache_ttl_secs: 60,
+            stale_while_revalidate_ms: 0,
+            parallel_eval: true, // Force parallel
+            timeout_ms: 100,
+            evaluation_backend: EvaluationBackend::Interpreter,
+            speculation: SpeculationConfig::default(),
+            failure_policy: FailurePolicy::default(),
+            change_log: 0,
+            fact_history: 0,
+            replica: None,
+            adaptive_ttl: AdaptiveTtlConfig::default(),
+            allow_all_bootstrap: false,
+            workers: WorkerConfig::default(),
+            builtin_module_dir: None,
+        };
+        let engine = RUNEEngine::with_config(config);
+
+        let request = Request::new(
+            Principal::agent("heidi"),
+            Action::new("read"),
+            Resource::file("/data/parallel.txt"),
+        );
+
+        let result = engine.authorize(&request).expect("Authorization failed");
+        assert!(!result.cached);
+        assert!(result.evaluation_time_ns > 0);
+    }
+
+    #[test]
+    fn test_reload_datalog_rules() {
+        let engine = RUNEEngine::new();
+
+        // Add some facts
+        engine.add_fact("user", vec![Value::string("alice")]);
+
+        // Create new rules (empty for this test)
+        let new_rules: Vec<Rule> = vec![];
+
+        // Reload rules
+        engine
+            .reload_datalog_rules(new_rules)
+            .expect("Failed to reload rules");
+
+        // Cache should be cleared
+        assert_eq!(engine.cache_stats().size, 0);
+    }
+
+    #[test]
+    fn test_reload_policies() {
+        let engine = configured_engine();
+
+        // Populate cache first
+        let request = Request::new(
+            Principal::agent("iris"),
+            Action::new("read"),
+            Resource::file("/data/test.txt"),
+        );
+        engine.authorize(&request).expect("Authorization failed");
+        assert_eq!(engine.cache_stats().size, 1);
+
+        // Reload policies with new set
+        let new_policies = PolicySet::new();
+        engine
+            .reload_policies(new_policies)
+            .expect("Failed to reload policies");
+
+        // Cache should be cleared
+        assert_eq!(engine.cache_stats().size, 0);
+    }
+
+    #[test]
+    fn test_datalog_version() {
+        let engine = RUNEEngine::new();
+        let version = engine.datalog_version();
+        assert_eq!(version.rules().len(), 0); // Empty engine
+    }
+
+    #[test]
+    fn test_policies_version() {
+        let engine = RUNEEngine::new();
+        let version = engine.policies_version();
+        // Just ensure we can get the version
+        let _ = version;
+    }
+
+    #[test]
+    fn test_snapshot_isolated_from_later_changes() {
+        let engine = RUNEEngine::new();
+        engine.add_fact("user", vec![Value::string("alice")]);
+
+        let snapshot = engine.snapshot_handle();
+        let version = snapshot.fact_version();
+
+        // Changes after the snapshot must not leak into it
+        engine.add_fact("user", vec![Value::string("bob")]);
+        engine
+            .reload_datalog_rules(vec![Rule::fact(crate::datalog::types::Atom::new(
+                "admin",
+                vec![crate::datalog::types::Term::constant(Value::string("bob"))],
+            ))])
+            .expect("Failed to reload rules");
+
+        assert_eq!(snapshot.fact_count(), 1);
+        assert_eq!(snapshot.fact_version(), version);
+        assert_eq!(snapshot.datalog().rules().len(), 0);
+        assert_eq!(engine.datalog_version().rules().len(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_authorize_is_consistent() {
+        let engine = RUNEEngine::new();
+        let snapshot = engine.snapshot_handle();
+        let cloned = snapshot.clone();
+
+        let request = Request::new(
+            Principal::agent("kate"),
+            Action::new("read"),
+            Resource::file("/data/snapshot.txt"),
+        );
+
+        let before = snapshot.authorize(&request).expect("Authorization failed");
+        engine.add_fact("allow", vec![Value::string("kate")]);
+        let after = cloned.authorize(&request).expect("Authorization failed");
+
+        assert_eq!(before.decision, after.decision);
+        assert!(!after.cached);
+        // Snapshot evaluations never populate the shared cache
+        assert_eq!(engine.cache_stats().size, 0);
+    }
+
+    #[test]
+    fn test_dataflow_backend_matches_interpreter() {
+        use crate::datalog::types::{Atom, Term};
+
+        let rules = vec![Rule::new(
+            Atom::new("can_read", vec![Term::var("U")]),
+            vec![Atom::new("member", vec![Term::var("U")])],
+        )];
+        let request = Request::new(
+            Principal::agent("lena"),
+            Action::new("read"),
+            Resource::file("/data/big.txt"),
+        );
+
+        let mut decisions = Vec::new();
+        for backend in [EvaluationBackend::Interpreter, EvaluationBackend::Dataflow] {
+            let engine = RUNEEngine::with_config(EngineConfig {
+                evaluation_backend: backend,
+                ..EngineConfig::default()
+            });
+            engine
+                .reload_datalog_rules(rules.clone())
+                .expect("Failed to reload rules");
+            assert_eq!(engine.datalog_version().backend(), backend);
+
+            let before = engine.datalog_version().derive_facts().unwrap().len();
+            engine.add_fact("member", vec![Value::string("lena")]);
+            let after = engine.datalog_version().derive_facts().unwrap().len();
+            assert_eq!((before, after), (0, 2));
+
+            decisions.push(engine.authorize(&request).unwrap().decision);
+        }
+        assert_eq!(decisions[0], decisions[1]);
+    }
+
+    #[test]
+    fn test_dataflow_backend_follows_change_log() {
+        let engine = RUNEEngine::with_config(EngineConfig {
+            evaluation_backend: EvaluationBackend::Dataflow,
+            ..EngineConfig::default()
+        });
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules(
+                    "path(X, Y) :- edge(X, Y).\npath(X, Z) :- edge(X, Y), path(Y, Z).",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let edge = |a: i64, b: i64| vec![Value::Integer(a), Value::Integer(b)];
+        let paths = |engine: &RUNEEngine| {
+            engine
+                .datalog_version()
+                .derive_facts()
+                .unwrap()
+                .iter()
+                .filter(|f| f.predicate.as_ref() == "path")
+                .count()
+        };
+
+        engine.add_fact("edge", edge(1, 2));
+        engine.add_fact("edge", edge(2, 3));
+        assert_eq!(paths(&engine), 3);
+        let loaded = engine.datalog_version().dataflow_stats().unwrap();
+
+        // Inserts are read from the change log and propagated in place
+        engine.add_fact("edge", edge(3, 4));
+        assert_eq!(paths(&engine), 6);
+        let stats = engine.datalog_version().dataflow_stats().unwrap();
+        assert_eq!(stats.rebuilds, loaded.rebuilds);
+        assert_eq!(stats.incremental_updates, loaded.incremental_updates + 1);
+
+        // An unchanged store reuses the published output
+        assert_eq!(paths(&engine), 6);
+        assert_eq!(engine.datalog_version().dataflow_stats().unwrap(), stats);
+
+        assert!(engine.retract_fact("edge", edge(2, 3)));
+        assert_eq!(paths(&engine), 2);
+        engine.facts.clear();
+        assert_eq!(paths(&engine), 0);
+    }
+
+    #[test]
+    fn test_evaluation_section_selects_backend() {
+        let source = |evaluation: &str| {
+            format!(
+                "version = \"rune/1.0\"\n{}\n[rules]\nmember(U) :- user(U).\n",
+                evaluation
+            )
+        };
+        let engine = RUNEEngine::new();
+        engine
+            .apply_config(
+                crate::parser::parse_rune_file(&source("[evaluation]\nbackend = \"dataflow\"\n"))
+                    .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            engine.datalog_version().backend(),
+            EvaluationBackend::Dataflow
+        );
+        engine.add_fact("user", vec![Value::string("alice")]);
+        assert_eq!(engine.datalog_version().derive_facts().unwrap().len(), 2);
+
+        // A file without the section keeps the active backend
+        engine
+            .apply_config(crate::parser::parse_rune_file(&source("")).unwrap())
+            .unwrap();
+        assert_eq!(
+            engine.datalog_version().backend(),
+            EvaluationBackend::Dataflow
+        );
+
+        assert!(
+            crate::parser::parse_rune_file(&source("[evaluation]\nbackend = \"magic\"\n")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_speculative_evaluation_matches_full() {
+        let speculative = RUNEEngine::with_config(EngineConfig {
+            parallel_eval: false,
+            speculation: SpeculationConfig {
+                enabled: true,
+                top_k: 1,
+                min_samples: 1,
+            },
+            ..EngineConfig::default()
+        });
+        let full = RUNEEngine::new();
+
+        for engine in [&speculative, &full] {
+            let mut policies = PolicySet::new();
+            policies
+                .load_policies(
+                    r#"
+permit(principal, action == Action::"read", resource);
+permit(principal, action == Action::"write", resource);
+forbid(principal, action == Action::"read", resource == File::"/secret");
+"#,
+                )
+                .unwrap();
+            engine.reload_policies(policies).unwrap();
+            engine
+                .reload_datalog_rules(crate::parser::parse_rules("service(files).").unwrap())
+                .unwrap();
+        }
+
+        let requests: Vec<_> = ["/a", "/b", "/secret", "/c"]
+            .into_iter()
+            .flat_map(|path| {
+                ["read", "write", "delete"].map(|action| {
+                    Request::new(
+                        Principal::agent("lena"),
+                        Action::new(action),
+                        Resource::file(path),
+                    )
+                })
+            })
+            .collect();
+
+        for request in &requests {
+            assert_eq!(
+                speculative.authorize(request).unwrap().decision,
+                full.authorize(request).unwrap().decision,
+                "{} {}",
+                request.action.name,
+                request.resource.entity.id
+            );
+        }
+
+        let stats = speculative.speculation_stats();
+        assert!(stats.hits > 0);
+        // Unmatched deletes fall back to the full set, then skip the fixpoint
+        assert!(stats.fallbacks > 0);
+        assert!(stats.skipped_fixpoints > 0);
+    }
+
+    #[test]
+    fn test_generation_tracks_config_changes() {
+        let engine = RUNEEngine::new();
+        assert_eq!(engine.generation(), 0);
+
+        engine.reload_datalog_rules(Vec::new()).unwrap();
+        engine.reload_policies(PolicySet::new()).unwrap();
+        assert_eq!(engine.generation(), 2);
+
+        // Facts are data, not configuration
+        engine.add_fact("member", vec![Value::string("lena")]);
+        assert_eq!(engine.generation(), 2);
+
+        let snapshot = engine.snapshot_handle();
+        engine.set_canonicalizer(Canonicalizer::default());
+        assert_eq!(engine.generation(), 3);
+        assert_eq!(snapshot.generation(), 2);
+    }
+
+    #[test]
+    fn test_query_facts_pages_derived_facts() {
+        use crate::datalog::types::{Atom, Term};
+
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(vec![Rule::new(
+                Atom::new("reader", vec![Term::var("U")]),
+                vec![Atom::new("member", vec![Term::var("U")])],
+            )])
+            .unwrap();
+        for user in ["carol", "alice", "bob"] {
+            engine.add_fact("member", vec![Value::string(user)]);
+        }
+
+        let readers = FactQuery::new().with_predicate("reader");
+        assert_eq!(engine.count_facts(&readers).unwrap(), 3);
+
+        let page: Vec<_> = engine
+            .query_facts(&readers.clone().with_offset(1).with_limit(1))
+            .unwrap()
+            .collect();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].args[0], Value::string("bob"));
+        assert_eq!(engine.count_facts(&readers.with_offset(2)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_cancelled_queries_stop() {
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(crate::parser::parse_rules("reader(U) :- member(U).").unwrap())
+            .unwrap();
+        engine.add_fact("member", vec![Value::string("alice")]);
+        let goal = crate::parser::parse_goal("reader(U)").unwrap();
+        let readers = FactQuery::new().with_predicate("reader");
+
+        let cancel = CancellationToken::new();
+        let request = cancel.child();
+        cancel.cancel();
+        assert!(matches!(
+            engine.query_cancellable(&goal, &request),
+            Err(RUNEError::Cancelled)
+        ));
+        assert!(matches!(
+            engine.count_facts_cancellable(&readers, &request),
+            Err(RUNEError::Cancelled)
+        ));
+        assert!(engine.query_facts_cancellable(&readers, &request).is_err());
+
+        // Nothing was cached for the cancelled query
+        let answer = engine.query(&goal).unwrap();
+        assert!(!answer.cached);
+        assert_eq!(answer.facts.len(), 1);
+        assert_eq!(engine.count_facts(&readers).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_compact_facts_expires_derived_facts() {
+        use crate::datalog::types::{Atom, Term};
+
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(vec![Rule::new(
+                Atom::new("reader", vec![Term::var("U")]),
+                vec![Atom::new("member", vec![Term::var("U")])],
+            )])
+            .unwrap();
+        engine.add_fact("member", vec![Value::string("alice")]);
+        engine.add_fact("member", vec![Value::string("alice")]);
+        engine.add_fact_with_ttl("member", vec![Value::string("bob")], Duration::ZERO);
+
+        let readers = FactQuery::new().with_predicate("reader");
+        assert_eq!(engine.fact_store_len(), 3);
+
+        let stats = engine.compact_facts();
+        assert_eq!(stats.duplicates_removed, 1);
+        assert_eq!(stats.expired_removed, 1);
+        assert_eq!(engine.fact_store_len(), 1);
+        assert_eq!(engine.count_facts(&readers).unwrap(), 1);
+
+        assert!(engine.retract_fact("member", vec![Value::string("alice")]));
+        assert_eq!(engine.count_facts(&readers).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_expired_facts_are_not_evaluated() {
+        use crate::datalog::types::{Atom, Term};
+
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(vec![Rule::new(
+                Atom::new("reader", vec![Term::var("U")]),
+                vec![Atom::new("member", vec![Term::var("U")])],
+            )])
+            .unwrap();
+        engine.add_fact("member", vec![Value::string("alice")]);
+        engine.add_fact_with_ttl("member", vec![Value::string("bob")], Duration::ZERO);
+        engine.add_fact_with_ttl(
+            "member",
+            vec![Value::string("carol")],
+            Duration::from_secs(3600),
+        );
+        assert_eq!(engine.fact_store_len(), 3);
+
+        // The expired fact is swept before evaluation, without compaction
+        let readers = FactQuery::new().with_predicate("reader");
+        assert_eq!(engine.count_facts(&readers).unwrap(), 2);
+        assert_eq!(engine.fact_store_len(), 2);
+        assert_eq!(engine.expire_facts(), 0);
+    }
+
+    /// An engine permitting reads only while an already expired fact is
+    /// visible, and such a read
+    fn engine_with_expired_fact() -> (RUNEEngine, Request) {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(r#"permit(principal, action == Action::"read", resource);"#)
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine.add_fact_with_ttl("member", vec![Value::string("alice")], Duration::ZERO);
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/report"),
+        );
+        (engine, request)
+    }
+
+    #[test]
+    fn test_snapshots_do_not_see_expired_facts() {
+        let (engine, request) = engine_with_expired_fact();
+        let snapshot = engine.snapshot_handle();
+        assert_eq!(
+            snapshot.authorize(&request).unwrap().decision,
+            Decision::Deny
+        );
+    }
+
+    #[test]
+    fn test_shadow_evaluation_does_not_see_expired_facts() {
+        let (engine, request) = engine_with_expired_fact();
+        let candidate = engine.policies_version();
+        let result = engine.evaluate_shadow(&request, &candidate).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+    }
+
+    #[test]
+    fn test_staged_configs_do_not_see_expired_facts() {
+        let (engine, request) = engine_with_expired_fact();
+        let staged = engine
+            .stage_config(
+                crate::parser::parse_rune_file(
+                    "version = \"rune/2.0\"\n\n[policies]\n@id(\"p\")\npermit(principal, action, resource);\n",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        engine.add_fact_with_ttl("member", vec![Value::string("bob")], Duration::ZERO);
+        let handle = engine.staged_handle(staged.id).unwrap();
+        assert_eq!(handle.authorize(&request).unwrap().decision, Decision::Deny);
+    }
+
+    #[test]
+    fn test_export_leaves_out_expired_facts() {
+        let (engine, _) = engine_with_expired_fact();
+        assert!(engine.export().unwrap().facts.is_empty());
+    }
+
+    #[test]
+    fn test_load_configuration() {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "version = \"rune/1.0\"\n\n[rules]\nactive(\"alice\").\n\n[policies]\npermit(principal, action == Action::\"read\", resource);\n"
+        )
+        .unwrap();
+
+        let engine = RUNEEngine::new();
+        engine
+            .load_configuration(file.path().to_str().unwrap())
+            .unwrap();
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/tmp/report"),
+        );
+        assert_eq!(
+            engine.authorize(&request).unwrap().decision,
+            Decision::Permit
+        );
+
+        // A bad policy leaves the loaded configuration in place
+        write!(file, "\npermit(principal, action, resource").unwrap();
+        let generation = engine.generation();
+        assert!(engine
+            .load_configuration(file.path().to_str().unwrap())
+            .is_err());
+        assert_eq!(engine.generation(), generation);
+        assert!(engine
+            .load_configuration("/nonexistent/config.rune")
+            .is_err());
+    }
+
+    #[test]
+    fn test_prefetch_warms_cache() {
+        let engine = configured_engine();
+        engine.add_fact("registered", vec![Value::string("alice")]);
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/tmp/report"),
+        );
+
+        assert!(engine.prefetch(&request).unwrap());
+        // Already cached, so nothing new to do
+        assert!(!engine.prefetch(&request).unwrap());
+        assert!(engine.authorize(&request).unwrap().cached);
+    }
+
+    #[test]
+    fn test_labels_on_facts_rules_and_policies() {
+        use crate::labels::LabelTarget;
+
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules(
+                    "@id(\"admins\")\n@owner(\"security\")\nadmin(U) :- member(U, \"ops\").\n\
+                     @owner(\"hr\")\n@ticket(\"HR-7\")\nmember(\"carol\", \"ops\").\n\
+                     reader(U) :- member(U, G).",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+@id("grant-ops") @owner("security") @ticket("SEC-1")
+permit(principal == User::"alice", action, resource);
+permit(principal == User::"bob", action, resource);
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        let labels = |pairs: &[(&str, &str)]| -> Labels {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        engine.add_fact_with_labels(
+            "member",
+            vec![Value::string("alice"), Value::string("ops")],
+            labels(&[("owner", "security"), ("source", "scim")]),
+        );
+
+        let owned = engine.labeled(&"owner=security".parse().unwrap());
+        let found: Vec<_> = owned.iter().map(|l| (l.target, l.id.as_str())).collect();
+        assert_eq!(
+            found,
+            [
+                (LabelTarget::Fact, "member(\"alice\", \"ops\")"),
+                (LabelTarget::Rule, "admins"),
+                (LabelTarget::Policy, "grant-ops"),
+            ]
+        );
+        let ticketed = engine.labeled(&"ticket".parse().unwrap());
+        assert_eq!(ticketed.len(), 2);
+        assert_eq!(ticketed[0].id, "member(\"carol\", \"ops\")");
+        // Unlabelled rules and policies are never listed
+        assert_eq!(engine.labeled(&LabelSelector::all()).len(), 4);
+
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/a"),
+        );
+        let result = engine.authorize(&request).unwrap();
+        let sources = engine.decision_labels(&result);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].labels["ticket"], "SEC-1");
+
+        assert!(engine.retract_fact("member", vec![Value::string("alice"), Value::string("ops")]));
+        assert_eq!(engine.labeled(&"source=scim".parse().unwrap()).len(), 0);
+    }
+
+    #[test]
+    fn test_matching_policies_lists_every_match() {
+        use crate::policy::PolicyEffect;
+
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+@id("readers")
+permit(principal, action == Action::"read", resource);
+@id("alice") @priority("10")
+permit(principal == User::"alice", action, resource);
+@id("bob")
+permit(principal == User::"bob", action, resource);
+@id("no-secrets") @priority("5")
+forbid(principal, action, resource == File::"/secret");
+@id("freeze")
+forbid(principal, action, resource);
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/secret"),
+        );
+        let matches = engine.matching_policies(&request).unwrap();
+        let ids = |list: &[crate::policy::PolicyMatch]| -> Vec<String> {
+            list.iter().map(|m| m.id.clone()).collect()
+        };
+        assert_eq!(ids(&matches.permits), ["alice", "readers"]);
+        assert_eq!(ids(&matches.forbids), ["no-secrets", "freeze"]);
+        assert_eq!(matches.permits[0].priority, 10);
+        assert_eq!(matches.forbids[1].effect, PolicyEffect::Forbid);
+        assert!(matches.errors.is_empty());
+        assert!(!matches.is_permitted());
+
+        // The decision itself only needs one forbid
+        assert_ne!(
+            engine.authorize(&request).unwrap().decision,
+            Decision::Permit
+        );
+
+        let mut invalid = PolicySet::new();
+        assert!(invalid
+            .load_policies("@priority(\"high\")\npermit(principal, action, resource);")
+            .is_err());
+    }
+
+    #[test]
+    fn test_list_permitted_resources() {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+permit(principal, action == Action::"read", resource is Document);
+forbid(principal, action, resource == Document::"secret");
+permit(principal, action == Action::"write", resource is Folder);
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+            .reload_datalog_rules(crate::parser::parse_rules("service(docs).").unwrap())
+            .unwrap();
+
+        let candidates: Vec<Resource> =
+            ["Document:q3", "Folder:q3", "Document:secret", "Document:q4"]
+                .into_iter()
+                .map(Resource::parse)
+                .collect();
+        let permitted = engine
+            .list_permitted_resources(&Principal::user("alice"), &Action::new("read"), &candidates)
+            .unwrap();
+        let ids: Vec<&str> = permitted.iter().map(|r| &*r.entity.id).collect();
+        assert_eq!(ids, ["q3", "q4"]);
+        // No read permit reaches folders, so the folder was never evaluated
+        assert_eq!(engine.cache.len(), 3);
+
+        // The same answers as one authorization per candidate
+        for resource in &candidates {
+            let request = Request::new(
+                Principal::user("alice"),
+                Action::new("read"),
+                resource.clone(),
+            );
+            assert_eq!(
+                engine.authorize(&request).unwrap().decision.is_permitted(),
+                permitted.contains(resource)
+            );
+        }
+        assert!(engine
+            .list_permitted_resources(&Principal::user("alice"), &Action::new("read"), &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_list_permitted_principals() {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+permit(principal is User, action == Action::"delete", resource is Bucket);
+forbid(principal == User::"mallory", action, resource);
+permit(principal is Service, action == Action::"read", resource);
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+            .reload_datalog_rules(crate::parser::parse_rules("service(storage).").unwrap())
+            .unwrap();
+
+        let candidates: Vec<Principal> =
+            ["User:alice", "Service:backup", "User:mallory", "User:bob"]
+                .into_iter()
+                .map(Principal::parse)
+                .collect();
+        let bucket = Resource::parse("Bucket:logs");
+        let permitted = engine
+            .list_permitted_principals(&Action::new("delete"), &bucket, &candidates)
+            .unwrap();
+        let ids: Vec<&str> = permitted.iter().map(|p| &*p.entity.id).collect();
+        assert_eq!(ids, ["alice", "bob"]);
+        // No delete permit admits services, so the service was never evaluated
+        assert_eq!(engine.cache.len(), 3);
+
+        for principal in &candidates {
+            let request = Request::new(principal.clone(), Action::new("delete"), bucket.clone());
+            assert_eq!(
+                engine.authorize(&request).unwrap().decision.is_permitted(),
+                permitted.contains(principal)
+            );
+        }
+        let results = engine
+            .authorize_principals(
+                &Request::new(candidates[0].clone(), Action::new("delete"), bucket.clone()),
+                &candidates,
+            )
+            .unwrap();
+        assert!(results.iter().all(|result| result.cached));
+    }
+
+    #[test]
+    fn test_authorize_resources() {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+permit(principal, action == Action::"read", resource);
+forbid(principal, action == Action::"read", resource == File::"/secret");
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+            .reload_datalog_rules(crate::parser::parse_rules("service(files).").unwrap())
+            .unwrap();
+
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/a"),
+        );
+        let resources: Vec<Resource> = ["/a", "/secret", "/b"]
+            .into_iter()
+            .map(Resource::file)
+            .collect();
+        // One candidate is already cached
+        engine.authorize(&request).unwrap();
+
+        let results = engine.authorize_resources(&request, &resources).unwrap();
+        let decisions: Vec<Decision> = results.iter().map(|r| r.decision).collect();
+        assert_eq!(
+            decisions,
+            [Decision::Permit, Decision::Deny, Decision::Permit]
+        );
+        assert!(results[0].cached);
+        assert!(!results[1].cached);
+
+        // Same answers as one request per resource, now from the cache
+        for (resource, result) in resources.iter().zip(&results) {
+            let mut single = request.clone();
+            single.resource = resource.clone();
+            let answer = engine.authorize(&single).unwrap();
+            assert!(answer.cached);
+            assert_eq!(answer.decision, result.decision);
         }
+        assert!(engine
+            .authorize_resources(&request, &[])
+            .unwrap()
+            .is_empty());
     }
 
-    fn record_cache_hit(&self) {
-        use std::sync::atomic::Ordering;
-        self.cache_hits.fetch_add(1, Ordering::Relaxed);
-    }
+    #[test]
+    fn test_authorize_actions() {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+permit(principal, action in [Action::"read", Action::"write"], resource);
+forbid(principal, action == Action::"write", resource == File::"/locked");
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+            .reload_datalog_rules(crate::parser::parse_rules("service(files).").unwrap())
+            .unwrap();
 
-    fn record_cache_miss(&self) {
-        use std::sync::atomic::Ordering;
-        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/locked"),
+        );
+        let actions: Vec<Action> = ["read", "write", "delete"]
+            .into_iter()
+            .map(Action::new)
+            .collect();
+        let results = engine.authorize_actions(&request, &actions).unwrap();
+        let decisions: Vec<Decision> = results.iter().map(|r| r.decision).collect();
+        assert_eq!(
+            decisions,
+            [Decision::Permit, Decision::Deny, Decision::Deny]
+        );
+
+        // Each answer was cached as a single request would have been
+        let mut delete = request.clone();
+        delete.action = Action::new("delete");
+        assert!(engine.authorize(&delete).unwrap().cached);
     }
 
-    fn record_authorization(&self, decision: Decision, _duration: Duration) {
-        use std::sync::atomic::Ordering;
+    #[test]
+    fn test_explained_decisions_carry_proofs() {
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules(
+                    "member(\"alice\", \"staff\").\n\
+                     reader(U) :- member(U, \"staff\").\n\
+                     can_read(U) :- reader(U).",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/doc"),
+        );
 
-        self.total_authorizations.fetch_add(1, Ordering::Relaxed);
+        let plain = engine.authorize(&request).unwrap();
+        assert!(plain.proofs.is_empty());
 
-        match decision {
-            Decision::Permit => self.total_permits.fetch_add(1, Ordering::Relaxed),
-            Decision::Deny => self.total_denies.fetch_add(1, Ordering::Relaxed),
-            Decision::Forbid => self.total_forbids.fetch_add(1, Ordering::Relaxed),
-        };
-    }
+        let mut explained = request.clone();
+        explained.explain = true;
+        let result = engine.authorize(&explained).unwrap();
+        assert!(!result.cached);
+        let facts: Vec<&str> = result.proofs.iter().map(|p| p.fact.as_str()).collect();
+        assert_eq!(facts, [r#"can_read("alice")"#, r#"reader("alice")"#]);
 
-    fn cache_hit_rate(&self) -> f64 {
-        use std::sync::atomic::Ordering;
+        let can_read = &result.proofs[0];
+        assert_eq!(
+            can_read.rule.as_deref(),
+            Some("can_read(?U) :- reader(?U).")
+        );
+        let reader = &can_read.premises[0];
+        assert_eq!(reader.fact, r#"reader("alice")"#);
+        assert_eq!(reader.premises[0].fact, r#"member("alice", "staff")"#);
+        assert_eq!(reader.premises[0].rule, None);
+
+        // Explained requests are evaluated every time, batched or not
+        assert!(!engine.authorize(&explained).unwrap().cached);
+        let batch = engine.authorize_batch(&[request, explained]);
+        assert!(batch[0].as_ref().unwrap().proofs.is_empty());
+        assert_eq!(batch[1].as_ref().unwrap().proofs, result.proofs);
+    }
 
-        let hits = self.cache_hits.load(Ordering::Relaxed) as f64;
-        let misses = self.cache_misses.load(Ordering::Relaxed) as f64;
+    #[test]
+    fn test_explained_denials_carry_near_misses() {
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules("reader(U) :- member(U, \"staff\").").unwrap(),
+            )
+            .unwrap();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(r#"permit(principal == User::"bob", action, resource);"#)
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine.add_fact("member", vec![Value::string("bob"), Value::string("staff")]);
+
+        let mut request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/doc"),
+        );
+        request.explain = true;
+        let result = engine.authorize(&request).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.near_misses.len(), 1);
+        let miss = &result.near_misses[0];
+        assert_eq!(miss.failed, r#"member("alice", "staff")"#);
+        assert_eq!(miss.reason, crate::datalog::MissReason::MissingFact);
+
+        // Permitted requests have nothing missing
+        request.principal = Principal::user("bob");
+        let result = engine.authorize(&request).unwrap();
+        assert_eq!(result.decision, Decision::Permit);
+        assert!(result.near_misses.is_empty());
+    }
 
-        if hits + misses == 0.0 {
-            0.0
-        } else {
-            hits / (hits + misses)
+    #[test]
+    fn test_authorize_batch() {
+        let load = |engine: &RUNEEngine| {
+            let mut policies = PolicySet::new();
+            policies
+                .load_policies(
+                    r#"
+permit(principal, action == Action::"read", resource);
+forbid(principal, action == Action::"read", resource == File::"/secret");
+"#,
+                )
+                .unwrap();
+            engine.reload_policies(policies).unwrap();
+            engine
+                .reload_datalog_rules(crate::parser::parse_rules("service(files).").unwrap())
+                .unwrap();
+        };
+        let engine = RUNEEngine::new();
+        let reference = RUNEEngine::new();
+        load(&engine);
+        load(&reference);
+
+        let request = |principal: &str, action: &str, path: &str| {
+            Request::new(
+                Principal::user(principal),
+                Action::new(action),
+                Resource::file(path),
+            )
+        };
+        let requests = [
+            request("alice", "read", "/a"),
+            request("bob", "read", "/secret"),
+            request("alice", "read", "/a"),
+            request("bob", "write", "/b"),
+        ];
+        let results: Vec<AuthorizationResult> = engine
+            .authorize_batch(&requests)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        // Same answers as one request at a time
+        for (request, result) in requests.iter().zip(&results) {
+            let single = reference.authorize(request).unwrap();
+            assert_eq!(single.decision, result.decision);
+            assert_eq!(single.cached, result.cached);
         }
+        assert!(!results[0].cached);
+        assert!(results[2].cached);
+        assert_eq!(engine.cache_stats().size, 3);
+
+        // Now all from the cache
+        assert!(engine
+            .authorize_batch(&requests)
+            .into_iter()
+            .all(|result| result.unwrap().cached));
+        assert!(engine.authorize_batch(&[]).is_empty());
+
+        let unconfigured = RUNEEngine::new().authorize_batch(&requests[..2]);
+        assert!(unconfigured
+            .into_iter()
+            .all(|result| result.unwrap().decision == Decision::Deny));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::datalog::types::Rule;
-    use crate::types::{Action, Principal, Resource};
-    use std::thread;
-    use std::time::Duration;
+    #[test]
+    fn test_query_cache_follows_facts_and_rules() {
+        let engine = RUNEEngine::new();
+        let config = crate::parse_rune_file(
+            r#"version = "rune/2.0"
+
+[rules]
+can_read(U, D) :- member(U, G), grant(G, D).
+"#,
+        )
+        .unwrap();
+        engine.apply_config(config).unwrap();
+        engine.add_fact("member", vec![Value::string("alice"), Value::string("eng")]);
+        engine.add_fact("grant", vec![Value::string("eng"), Value::string("doc1")]);
+
+        let goal = crate::parse_goal(r#"can_read("alice", D)"#).unwrap();
+        let first = engine.query(&goal).unwrap();
+        assert!(!first.cached);
+        assert_eq!(first.facts.len(), 1);
+        assert_eq!(first.facts[0].args[1], Value::string("doc1"));
+        assert!(engine.query(&goal).unwrap().cached);
+
+        // A new fact moves the store version on
+        engine.add_fact("grant", vec![Value::string("eng"), Value::string("doc2")]);
+        let updated = engine.query(&goal).unwrap();
+        assert!(!updated.cached);
+        assert_eq!(updated.facts.len(), 2);
+
+        // So does a rule reload, through the generation
+        engine.reload_datalog_rules(Vec::new()).unwrap();
+        let reloaded = engine.query(&goal).unwrap();
+        assert!(!reloaded.cached);
+        assert!(reloaded.facts.is_empty());
+    }
 
     #[test]
-    fn test_engine_creation() {
+    fn test_attribute_sources_are_merged() {
+        use crate::attributes::AttributeSource;
+
         let engine = RUNEEngine::new();
-        assert_eq!(engine.cache_stats().size, 0);
+        engine.add_fact("registered", vec![Value::string("alice")]);
+        engine
+            .apply_config(
+                crate::parser::parse_rune_file(
+                    r#"version = "rune/2.0"
+
+[attributes]
+priority = ["scim", "jwt"]
+
+[strategies]
+clearance = "max"
+
+[policies]
+@id("cleared-reads")
+permit(principal, action == Action::"read", resource)
+    when { principal.clearance >= 3 && principal.team == "ops" };
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let request = |jwt_clearance: i64| {
+            Request::new(
+                Principal::user("alice").with_attribute("team", Value::string("sales")),
+                Action::new("read"),
+                Resource::file("/tmp/report"),
+            )
+            .with_attribute_source(
+                AttributeSource::new("jwt")
+                    .with_attribute("clearance", Value::Integer(jwt_clearance))
+                    .with_attribute("team", Value::string("dev")),
+            )
+            .with_attribute_source(
+                AttributeSource::new("scim")
+                    .with_attribute("clearance", Value::Integer(1))
+                    .with_attribute("team", Value::string("ops")),
+            )
+        };
+
+        // Highest clearance from jwt, team from the higher-ranked scim
+        let result = engine.authorize(&request(3)).unwrap();
+        assert_eq!(result.decision, Decision::Permit);
+        assert!(result
+            .explanation
+            .contains("attribute 'clearance' taken from jwt (max of 2 sources)"));
+        assert!(result
+            .explanation
+            .contains("attribute 'team' taken from scim (source-priority of 3 sources)"));
+
+        // Cached decisions still name the winners
+        let cached = engine.authorize(&request(3)).unwrap();
+        assert!(cached.cached);
+        assert!(cached.explanation.contains("taken from jwt"));
+
+        assert_eq!(
+            engine.authorize(&request(2)).unwrap().decision,
+            Decision::Deny
+        );
+        assert_eq!(
+            engine
+                .snapshot_handle()
+                .authorize(&request(3))
+                .unwrap()
+                .decision,
+            Decision::Permit
+        );
     }
 
     #[test]
-    fn test_engine_with_custom_config() {
-        let config = EngineConfig {
-            cache_size: 5000,
-            cache_ttl_secs: 30,
-            parallel_eval: false,
-            timeout_ms: 200,
+    fn test_decisions_carry_reason_codes() {
+        let engine = RUNEEngine::new();
+        engine.add_fact("registered", vec![Value::string("alice")]);
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(r#"permit(principal, action == Action::"read", resource);"#)
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        let decide = |action: &str| {
+            engine
+                .authorize(&Request::new(
+                    Principal::user("alice"),
+                    Action::new(action),
+                    Resource::file("/tmp/report"),
+                ))
+                .unwrap()
         };
-        let engine = RUNEEngine::with_config(config.clone());
-        assert_eq!(engine.config.cache_size, 5000);
-        assert_eq!(engine.config.cache_ttl_secs, 30);
-        assert!(!engine.config.parallel_eval);
-        assert_eq!(engine.config.timeout_ms, 200);
+
+        let read = decide("read");
+        assert_eq!(read.reason_codes.len(), 1);
+        assert_eq!(read.reason_codes[0].code, ReasonCode::Permitted);
+        // The English catalog reproduces the explanation
+        let rendered = engine.explanation_renderer().render(&read, None);
+        assert_eq!(rendered.explanation, read.explanation);
+        assert_eq!(
+            engine
+                .explanation_renderer()
+                .render(&read, Some("es"))
+                .explanation,
+            format!(
+                "Permitido por {} reglas",
+                read.reason_codes[0].params["rules"]
+            )
+        );
+
+        let write = decide("write");
+        assert_eq!(write.reason_codes[0].code, ReasonCode::NoMatchingPermit);
+        assert_eq!(
+            engine
+                .explanation_renderer()
+                .render(&write, None)
+                .explanation,
+            write.explanation
+        );
     }
 
     #[test]
-    fn test_engine_default() {
-        let engine = RUNEEngine::default();
-        assert_eq!(engine.cache_stats().size, 0);
+    fn test_permits_carry_obligations() {
+        let engine = RUNEEngine::new();
+        engine.add_fact("registered", vec![Value::string("alice")]);
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"@id("reads")
+@obligation("X-Allowed-Fields: name,email; X-Rate-Remaining: 100")
+permit(principal, action == Action::"read", resource);
+
+@id("lists")
+@obligation("X-Allowed-Fields: name")
+permit(principal, action in [Action::"read", Action::"list"], resource);
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        let decide = |action: &str| {
+            engine
+                .authorize(&Request::new(
+                    Principal::user("alice"),
+                    Action::new(action),
+                    Resource::file("/tmp/report"),
+                ))
+                .unwrap()
+        };
+
+        let read = decide("read");
+        assert_eq!(read.decision, Decision::Permit);
+        let headers: Vec<_> = read
+            .obligations
+            .iter()
+            .map(|o| format!("{}: {}", o.header, o.value))
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                "X-Allowed-Fields: name",
+                "X-Allowed-Fields: name,email",
+                "X-Rate-Remaining: 100"
+            ]
+        );
+        assert_eq!(decide("list").obligations.len(), 1);
+        assert!(decide("delete").obligations.is_empty());
+
+        // Bad obligations are rejected when the policy is loaded
+        let mut invalid = PolicySet::new();
+        assert!(invalid
+            .load_policies(
+                "@obligation(\"X-Rune-Decision: PERMIT\")\npermit(principal, action, resource);"
+            )
+            .is_err());
+        assert!(invalid
+            .add_policy(
+                "deny",
+                "@obligation(\"X-A: 1\")\nforbid(principal, action, resource);"
+            )
+            .is_err());
     }
 
     #[test]
-    fn test_config_default() {
-        let config = EngineConfig::default();
-        assert_eq!(config.cache_size, 10_000);
-        assert_eq!(config.cache_ttl_secs, 60);
-        assert!(config.parallel_eval);
-        assert_eq!(config.timeout_ms, 100);
+    fn test_unconfigured_engine_denies() {
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/tmp/report"),
+        );
+
+        // Facts alone configure nothing
+        let engine = RUNEEngine::new();
+        engine.add_fact("admin", vec![Value::string("alice")]);
+        assert!(!engine.is_configured());
+        let result = engine.authorize(&request).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.reason_codes[0].code, ReasonCode::Unconfigured);
+        assert_eq!(
+            engine
+                .snapshot_handle()
+                .authorize(&request)
+                .unwrap()
+                .decision,
+            Decision::Deny
+        );
+
+        let bootstrap = RUNEEngine::with_config(EngineConfig {
+            allow_all_bootstrap: true,
+            ..EngineConfig::default()
+        });
+        let result = bootstrap.authorize(&request).unwrap();
+        assert_eq!(result.decision, Decision::Permit);
+        assert_eq!(result.reason_codes[0].code, ReasonCode::Bootstrap);
+        let resources = bootstrap
+            .authorize_resources(&request, &[Resource::file("/a"), Resource::file("/b")])
+            .unwrap();
+        assert!(resources.iter().all(|r| r.decision == Decision::Permit));
+
+        // Once anything is loaded the override no longer applies
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies("forbid(principal, action == Action::\"delete\", resource);")
+            .unwrap();
+        bootstrap.reload_policies(policies).unwrap();
+        assert!(bootstrap.is_configured());
+        assert_eq!(
+            bootstrap.authorize(&request).unwrap().decision,
+            Decision::Deny
+        );
     }
 
     #[test]
-    fn test_decision_is_permitted() {
-        assert!(Decision::Permit.is_permitted());
-        assert!(!Decision::Deny.is_permitted());
-        assert!(!Decision::Forbid.is_permitted());
+    fn test_soft_limits_permit_with_warnings() {
+        let engine = RUNEEngine::new();
+        engine.add_fact("registered", vec![Value::string("alice")]);
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"@id("uploads")
+permit(principal, action == Action::"upload", resource)
+    when { principal.storage_used < 100 };
+
+@id("quota-soft-limit")
+@warning("quota_near: Storage is over 90% of your quota")
+permit(principal, action == Action::"upload", resource)
+    when { principal.storage_used >= 90 && principal.storage_used < 100 };
+"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        let decide = |used: i64| {
+            engine
+                .authorize(&Request::new(
+                    Principal::user("alice").with_attribute("storage_used", Value::Integer(used)),
+                    Action::new("upload"),
+                    Resource::file("/tmp/report"),
+                ))
+                .unwrap()
+        };
+
+        let under = decide(50);
+        assert_eq!(under.decision, Decision::Permit);
+        assert!(under.warnings.is_empty());
+
+        let near = decide(95);
+        assert_eq!(near.decision, Decision::Permit);
+        let codes: Vec<_> = near.warnings.iter().map(|w| w.code.as_str()).collect();
+        assert_eq!(codes, ["quota_near"]);
+
+        // Denials carry no warnings
+        let over = decide(100);
+        assert_eq!(over.decision, Decision::Deny);
+        assert!(over.warnings.is_empty());
+
+        // Bad warnings are rejected when the policy is loaded
+        let mut invalid = PolicySet::new();
+        assert!(invalid
+            .load_policies("@warning(\"no message\")\npermit(principal, action, resource);")
+            .is_err());
+        assert!(invalid
+            .add_policy(
+                "deny",
+                "@warning(\"quota: over\")\nforbid(principal, action, resource);"
+            )
+            .is_err());
     }
 
     #[test]
-    fn test_decision_combination() {
-        // Forbid takes priority over everything
-        assert_eq!(Decision::Forbid.combine(Decision::Permit), Decision::Forbid);
-        assert_eq!(Decision::Forbid.combine(Decision::Deny), Decision::Forbid);
-        assert_eq!(Decision::Forbid.combine(Decision::Forbid), Decision::Forbid);
-        assert_eq!(Decision::Permit.combine(Decision::Forbid), Decision::Forbid);
-        assert_eq!(Decision::Deny.combine(Decision::Forbid), Decision::Forbid);
+    fn test_apply_layers() {
+        let base = r#"version = "rune/2.0"
 
-        // Deny takes priority over Permit
-        assert_eq!(Decision::Deny.combine(Decision::Permit), Decision::Deny);
-        assert_eq!(Decision::Permit.combine(Decision::Deny), Decision::Deny);
-        assert_eq!(Decision::Deny.combine(Decision::Deny), Decision::Deny);
+[rules]
+active("alice").
 
-        // Both Permit results in Permit
-        assert_eq!(Decision::Permit.combine(Decision::Permit), Decision::Permit);
+[policies]
+@id("no-deletes")
+forbid(principal, action == Action::"delete", resource);
+
+@id("reads")
+permit(principal, action == Action::"read", resource);
+"#;
+        let team = r#"version = "rune/2.0"
+
+[policies]
+@id("team-deletes")
+permit(principal, action == Action::"delete", resource);
+
+[enabled]
+reads = false
+"#;
+        let engine = RUNEEngine::new();
+        let decide = |action: &str| {
+            let request = Request::new(
+                Principal::user("alice"),
+                Action::new(action),
+                Resource::file("/tmp/report"),
+            );
+            engine.authorize(&request).unwrap().decision
+        };
+
+        let generation = engine.generation();
+        let provenance = engine
+            .apply_layers([
+                ConfigLayer::parse("base", base).unwrap(),
+                ConfigLayer::parse("team", team).unwrap(),
+            ])
+            .unwrap();
+        assert!(engine.generation() > generation);
+        assert_eq!(provenance.policy_layer("team-deletes"), Some("team"));
+        // The base forbid still wins over the team's permit
+        assert_eq!(decide("delete"), Decision::Deny);
+        assert_eq!(decide("read"), Decision::Deny);
+
+        // Conflicting layers leave the engine as it was
+        let generation = engine.generation();
+        let prod = "version = \"rune/2.0\"\n\n[enabled]\nno-deletes = false\n";
+        assert!(engine
+            .apply_layers([
+                ConfigLayer::parse("base", base).unwrap(),
+                ConfigLayer::parse("prod", prod).unwrap(),
+            ])
+            .is_err());
+        assert_eq!(engine.generation(), generation);
     }
 
     #[test]
-    fn test_cache_key_generation() {
-        let request1 = Request::new(
-            Principal::agent("agent1"),
-            Action::new("read"),
-            Resource::file("/tmp/test.txt"),
-        );
+    fn test_access_request_approval() {
+        use crate::datalog::types::{Atom, Term};
 
-        let request2 = Request::new(
-            Principal::agent("agent1"),
+        let engine = RUNEEngine::new();
+        let vars = || vec![Term::var("P"), Term::var("A"), Term::var("R")];
+        engine
+            .reload_datalog_rules(vec![Rule::new(
+                Atom::new("allowed", vars()),
+                vec![Atom::new(
+                    crate::access_requests::APPROVAL_PREDICATE,
+                    vars(),
+                )],
+            )])
+            .unwrap();
+        let mut policies = PolicySet::new();
+        policies
+            .add_policy("all", "permit(principal, action, resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+
+        let request = Request::new(
+            Principal::user("alice"),
             Action::new("read"),
-            Resource::file("/tmp/test.txt"),
+            Resource::file("/tmp/report"),
         );
+        let (access, _) = engine
+            .request_access(&request, Some("incident".to_string()))
+            .unwrap();
+        let pending = engine.authorize(&request).unwrap();
+        assert_eq!(pending.decision, Decision::Deny);
+        assert!(pending
+            .reason_codes
+            .iter()
+            .any(|r| r.code == ReasonCode::AccessPending));
+
+        let origin = ChangeOrigin::new("bob", "test");
+        let approved = engine
+            .approve_access(access.id, origin.clone(), None)
+            .unwrap();
+        assert_eq!(approved.unwrap().status, AccessRequestStatus::Approved);
+        let result = engine.authorize(&request).unwrap();
+        assert_eq!(result.decision, Decision::Permit);
+        assert!(result
+            .reason_codes
+            .iter()
+            .all(|r| r.code != ReasonCode::AccessPending));
+
+        assert!(engine.deny_access(access.id, origin.clone(), None).is_err());
+        assert!(engine.approve_access(99, origin, None).unwrap().is_none());
+        assert_eq!(engine.access_requests(None).len(), 1);
+    }
 
-        assert_eq!(request1.cache_key(), request2.cache_key());
+    #[test]
+    fn test_session_facts_follow_login_lifecycle() {
+        use crate::datalog::types::{Atom, Term};
+        use crate::sessions::SessionAttribute;
 
-        let request3 = Request::new(
-            Principal::agent("agent2"),
+        let engine = RUNEEngine::new();
+        engine
+            .reload_datalog_rules(vec![Rule::new(
+                Atom::new("strong_auth", vec![Term::var("U")]),
+                vec![Atom::new(
+                    "mfa_level",
+                    vec![Term::var("U"), Term::constant(Value::Integer(2))],
+                )],
+            )])
+            .unwrap();
+        let mut policies = PolicySet::new();
+        policies
+            .add_policy("all", "permit(principal, action, resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+
+        let request = Request::new(
+            Principal::user("alice"),
             Action::new("read"),
-            Resource::file("/tmp/test.txt"),
+            Resource::file("/tmp/report"),
         );
+        let mfa = [SessionAttribute::new("mfa_level", vec![Value::Integer(2)])];
+        assert_eq!(engine.authorize(&request).unwrap().decision, Decision::Deny);
 
-        assert_ne!(request1.cache_key(), request3.cache_key());
+        let session = engine.open_session(&request.principal, &mfa, None).unwrap();
+        assert_eq!(session.facts[0].args[0], Value::string("alice"));
+        assert_eq!(
+            engine.authorize(&request).unwrap().decision,
+            Decision::Permit
+        );
+        assert_eq!(engine.sessions()[0].principal, "alice");
+
+        assert!(engine.close_session(&session.id));
+        assert_eq!(engine.authorize(&request).unwrap().decision, Decision::Deny);
+        assert!(engine.sessions().is_empty());
+
+        // An expired session is logged out before the next decision
+        engine
+            .open_session(&request.principal, &mfa, Some(Duration::ZERO))
+            .unwrap();
+        assert_eq!(engine.authorize(&request).unwrap().decision, Decision::Deny);
+        assert_eq!(engine.fact_store_len(), 0);
+
+        let unnamed = [SessionAttribute::new(" ", vec![])];
+        assert!(engine
+            .open_session(&request.principal, &unnamed, None)
+            .is_err());
     }
 
     #[test]
-    fn test_basic_authorization() {
-        let engine = RUNEEngine::new();
+    fn test_replica_follows_primary_facts() {
+        use crate::replica::ReplicaConfig;
+
+        let primary = RUNEEngine::with_config(EngineConfig {
+            change_log: 100,
+            ..EngineConfig::default()
+        });
+        let replica = RUNEEngine::with_config(EngineConfig {
+            replica: Some(ReplicaConfig {
+                max_staleness: Duration::from_secs(60),
+            }),
+            ..EngineConfig::default()
+        });
+        for engine in [&primary, &replica] {
+            let mut policies = PolicySet::new();
+            policies
+                .add_policy("all", "permit(principal, action, resource);")
+                .unwrap();
+            engine.reload_policies(policies).unwrap();
+        }
+        assert!(replica.changes_since(None, 10).is_err());
+        assert!(primary.replica_status().is_none());
+
         let request = Request::new(
-            Principal::agent("alice"),
+            Principal::user("alice"),
             Action::new("read"),
-            Resource::file("/data/public.txt"),
+            Resource::file("/tmp/report"),
+        );
+        // Never synced: nothing is decided
+        assert!(matches!(
+            replica.authorize(&request),
+            Err(RUNEError::ReplicaStale { lag_ms: None, .. })
+        ));
+
+        primary.add_fact("member", vec![Value::string("alice")]);
+        let batch = primary.changes_since(None, 10).unwrap();
+        let status = replica.apply_changes(&batch).unwrap();
+        assert_eq!(status.pending, 0);
+        assert_eq!(replica.fact_store_len(), 1);
+        assert_eq!(
+            replica.authorize(&request).unwrap().decision,
+            Decision::Permit
         );
 
-        let result = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result.cached);
-        assert!(result.evaluation_time_ns > 0);
+        primary.retract_fact("member", vec![Value::string("alice")]);
+        let batch = primary.changes_since(status.position, 10).unwrap();
+        replica.apply_changes(&batch).unwrap();
+        assert_eq!(replica.fact_store_len(), 0);
+
+        // A request can demand fresher data than the replica's default
+        std::thread::sleep(Duration::from_millis(5));
+        let strict = crate::request::RequestBuilder::new()
+            .principal(Principal::user("alice"))
+            .action(Action::new("read"))
+            .resource(Resource::file("/tmp/report"))
+            .max_staleness(Duration::from_millis(1))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            replica.authorize(&strict),
+            Err(RUNEError::ReplicaStale { max_ms: 1, .. })
+        ));
+        assert!(primary.authorize(&strict).is_ok());
     }
 
     #[test]
-    fn test_authorization_with_context() {
-        let engine = RUNEEngine::new();
-        let request = Request::new(
-            Principal::agent("alice"),
-            Action::new("read"),
-            Resource::file("/data/public.txt"),
-        )
-        .with_context("ip_address", Value::string("192.168.1.1"))
-        .with_context("time", Value::string("2024-01-01T12:00:00Z"));
+    fn test_role_and_group_helpers() {
+        use crate::history::{ChangeOrigin, HistoryQuery};
+
+        let engine = RUNEEngine::with_config(EngineConfig {
+            fact_history: 100,
+            ..EngineConfig::default()
+        });
+        assert!(engine.grant_role("alice", "reader").unwrap());
+        assert!(!engine.grant_role("alice", "reader").unwrap());
+        assert!(engine
+            .attributed(ChangeOrigin::new("carol", "console"))
+            .add_to_group("alice", "eng")
+            .unwrap());
+        assert!(engine.grant_role("eng", "deployer").unwrap());
+        assert!(engine.grant_role("alice", "").is_err());
+        assert!(engine.add_to_group("eng", "eng").is_err());
+
+        let summary = engine
+            .permission_summary(&Principal::user("alice"))
+            .unwrap();
+        assert_eq!(summary.roles, ["deployer", "reader"]);
+        assert_eq!(summary.groups, ["eng"]);
+        let memberships = engine
+            .fact_history(&HistoryQuery {
+                predicate: Some("member".to_string()),
+                ..HistoryQuery::default()
+            })
+            .unwrap();
+        assert_eq!(memberships[0].origin.actor, "carol");
+
+        assert!(engine.revoke_role("alice", "reader").unwrap());
+        assert!(!engine.revoke_role("alice", "reader").unwrap());
+        assert!(engine.remove_from_group("alice", "eng").unwrap());
+        let summary = engine
+            .permission_summary(&Principal::user("alice"))
+            .unwrap();
+        assert!(summary.roles.is_empty() && summary.groups.is_empty());
+    }
 
-        let result = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result.cached);
+    #[test]
+    fn test_fact_history_records_who_changed_what() {
+        use crate::history::{ChangeOrigin, FactOp, HistoryQuery};
+
+        assert!(RUNEEngine::new()
+            .fact_history(&HistoryQuery::default())
+            .is_err());
+
+        let engine = RUNEEngine::with_config(EngineConfig {
+            fact_history: 100,
+            ..EngineConfig::default()
+        });
+        let role = || vec![Value::string("bob"), Value::string("admin")];
+        engine.add_fact("team", vec![Value::string("bob")]);
+        engine
+            .attributed(ChangeOrigin::new("carol", "api"))
+            .add_fact("role", role());
+        std::thread::sleep(Duration::from_millis(5));
+        let granted = crate::history::now_ms();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(engine
+            .attributed(ChangeOrigin::new("dave", "console"))
+            .retract_fact("role", role()));
+
+        let events = engine
+            .fact_history(&HistoryQuery {
+                predicate: Some("role".to_string()),
+                ..HistoryQuery::default()
+            })
+            .unwrap();
+        let changes: Vec<(FactOp, &str, &str)> = events
+            .iter()
+            .map(|e| (e.op, e.origin.actor.as_str(), e.origin.source.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (FactOp::Add, "carol", "api"),
+                (FactOp::Retract, "dave", "console")
+            ]
+        );
+        let all = engine.fact_history(&HistoryQuery::default()).unwrap();
+        assert_eq!(all[0].origin, ChangeOrigin::default());
+
+        let then = engine.facts_as_of(granted).unwrap();
+        assert_eq!(then.len(), 2);
+        assert!(then.contains(&Fact::new("role", role())));
+        assert_eq!(
+            engine.facts_as_of(crate::history::now_ms()).unwrap().len(),
+            1
+        );
+        assert!(engine.facts_as_of(0).is_err());
     }
 
     #[test]
-    fn test_cache_hit() {
-        let engine = RUNEEngine::new();
+    fn test_cedar_failure_modes() {
+        use crate::failure::{FailureClass, FailureMode, FailurePolicy};
+
+        // Cedar rejects entity types containing spaces
         let request = Request::new(
-            Principal::agent("bob"),
-            Action::new("write"),
-            Resource::file("/data/private.txt"),
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::new("bad type", "1"),
         );
 
-        // First authorization - should miss cache
-        let result1 = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result1.cached);
+        let closed = RUNEEngine::new();
+        let open = RUNEEngine::with_config(EngineConfig {
+            failure_policy: FailurePolicy {
+                cedar_error: FailureMode::FailOpen,
+                ..FailurePolicy::default()
+            },
+            ..EngineConfig::default()
+        });
 
-        // Second authorization - should hit cache
-        let result2 = engine.authorize(&request).expect("Authorization failed");
-        assert!(result2.cached);
+        for engine in [&closed, &open] {
+            engine
+                .reload_datalog_rules(crate::parser::parse_rules("service(files).").unwrap())
+                .unwrap();
+        }
 
-        // Cache stats should reflect this
-        let stats = engine.cache_stats();
-        assert_eq!(stats.size, 1);
-        assert_eq!(stats.hit_rate, 0.5); // 1 hit out of 2 requests
+        let result = closed.authorize(&request).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.failures[0].class, FailureClass::CedarError);
+        assert_eq!(result.failures[0].mode, FailureMode::FailClosed);
+
+        // Degraded decisions are not cached
+        for _ in 0..2 {
+            let result = open.authorize(&request).unwrap();
+            assert_eq!(result.decision, Decision::Permit);
+            assert!(!result.cached);
+            assert!(result
+                .explanation
+                .starts_with("cedar_error handled as fail_open"));
+        }
+        assert_eq!(open.cache_stats().size, 0);
+        assert_eq!(open.metrics().failures(FailureClass::CedarError), 2);
+        assert_eq!(open.metrics().failures(FailureClass::DatalogTimeout), 0);
     }
 
     #[test]
-    fn test_cache_ttl_expiry() {
-        let config = EngineConfig {
-            cache_size: 100,
-            cache_ttl_secs: 1, // Very short TTL
-            parallel_eval: true,
-            timeout_ms: 100,
+    fn test_datalog_timeout_falls_back_to_cache() {
+        use crate::failure::{FailureClass, FailureMode, FailurePolicy};
+
+        let engine = RUNEEngine::with_config(EngineConfig {
+            // Every entry is stale immediately, but kept for the fallback
+            cache_ttl_secs: 0,
+            timeout_ms: 5,
+            failure_policy: FailurePolicy::uniform(FailureMode::FallbackToCache),
+            ..EngineConfig::default()
+        });
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies("permit(principal, action, resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules(
+                    "path(X, Y) :- edge(X, Y).\npath(X, Z) :- path(X, Y), edge(Y, Z).",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        engine.add_fact("edge", vec![Value::Integer(0), Value::Integer(1)]);
+
+        let request = |id: &str| {
+            Request::new(
+                Principal::user("alice"),
+                Action::new("read"),
+                Resource::file(id),
+            )
         };
-        let engine = RUNEEngine::with_config(config);
+        assert_eq!(
+            engine.authorize(&request("/a")).unwrap().decision,
+            Decision::Permit
+        );
 
-        let request = Request::new(
-            Principal::agent("charlie"),
-            Action::new("execute"),
-            Resource::file("/bin/script.sh"),
+        // A long chain makes the transitive closure overrun the budget
+        for i in 1..60 {
+            engine.add_fact("edge", vec![Value::Integer(i), Value::Integer(i + 1)]);
+        }
+
+        let result = engine.authorize(&request("/a")).unwrap();
+        assert_eq!(result.decision, Decision::Permit);
+        assert!(result.cached);
+        assert_eq!(result.failures[0].class, FailureClass::DatalogTimeout);
+        assert_eq!(result.failures[0].mode, FailureMode::FallbackToCache);
+        let codes: Vec<_> = result.reason_codes.iter().map(|r| r.code).collect();
+        assert_eq!(
+            codes,
+            [ReasonCode::DependencyFailure, ReasonCode::Permitted]
         );
+        assert_eq!(result.reason_codes[0].params["class"], "datalog_timeout");
 
-        // First authorization
-        let result1 = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result1.cached);
+        // Nothing cached for this request, so it fails closed
+        let result = engine.authorize(&request("/b")).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.failures[0].mode, FailureMode::FailClosed);
+        assert_eq!(engine.metrics().failures(FailureClass::DatalogTimeout), 2);
+    }
+
+    #[test]
+    fn test_concurrent_identical_requests_coalesce() {
+        let engine = RUNEEngine::with_config(EngineConfig {
+            timeout_ms: 0,
+            ..EngineConfig::default()
+        });
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies("permit(principal, action, resource);")
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules(
+                    "path(X, Y) :- edge(X, Y).\npath(X, Z) :- path(X, Y), edge(Y, Z).",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        // Slow enough that every thread arrives while the first evaluates
+        for i in 0..40 {
+            engine.add_fact("edge", vec![Value::Integer(i), Value::Integer(i + 1)]);
+        }
 
-        // Wait for TTL to expire
-        thread::sleep(Duration::from_secs(2));
+        let request = Request::new(
+            Principal::user("alice"),
+            Action::new("read"),
+            Resource::file("/shared"),
+        );
+        let barrier = std::sync::Barrier::new(8);
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        engine.authorize(&request).unwrap()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(results.iter().all(|r| r.decision == Decision::Permit));
+        let coalesced = results.iter().filter(|r| r.coalesced).count() as u64;
+        assert!(coalesced > 0);
+        assert_eq!(engine.metrics().coalesced_requests(), coalesced);
+        assert!(engine.inflight.is_empty());
+    }
 
-        // Second authorization - cache should be expired
-        let result2 = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result2.cached);
+    #[test]
+    fn test_abandoned_flight_releases_waiters() {
+        let inflight = DashMap::new();
+        let flight = Arc::new(Flight::default());
+        inflight.insert((0, 1), flight.clone());
+
+        let guard = FlightGuard {
+            inflight: &inflight,
+            key: (0, 1),
+            flight: flight.clone(),
+        };
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| flight.wait());
+            drop(guard);
+            assert!(waiter.join().unwrap().is_none());
+        });
+        assert!(inflight.is_empty());
     }
 
     #[test]
-    fn test_cache_clear() {
+    fn test_scoped_rules_cannot_join_across_scopes() {
+        use crate::scopes::{FactScope, FactScopes};
+
         let engine = RUNEEngine::new();
-        let request = Request::new(
-            Principal::agent("dave"),
-            Action::new("delete"),
-            Resource::file("/data/temp.txt"),
+        // Forgets to tie the grant to the member's tenant
+        engine
+            .reload_datalog_rules(
+                crate::parser::parse_rules(
+                    "@scope(\"tenant\")\ncan_read(U, D) :- member(T1, U, G), grant(T2, G, D).\n\
+                     readable(D) :- can_read(U, D).",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        for (predicate, args) in [
+            ("member", ["acme", "alice", "eng"]),
+            ("grant", ["acme", "eng", "wiki"]),
+            ("grant", ["globex", "eng", "payroll"]),
+        ] {
+            engine.add_fact(predicate, args.map(Value::string).to_vec());
+        }
+
+        let derived = |engine: &RUNEEngine| {
+            let mut docs: Vec<_> = engine
+                .datalog_version()
+                .derive_facts()
+                .unwrap()
+                .into_iter()
+                .filter(|f| f.predicate.as_ref() == "readable")
+                .map(|f| f.args[0].clone())
+                .collect();
+            docs.sort();
+            docs
+        };
+
+        // Undeclared scope: the rule sees nothing
+        assert!(derived(&engine).is_empty());
+
+        engine
+            .set_fact_scopes(FactScopes::new(
+                [(
+                    "tenant".to_string(),
+                    FactScope {
+                        column: 0,
+                        shared: Default::default(),
+                    },
+                )]
+                .into(),
+            ))
+            .unwrap();
+        assert_eq!(derived(&engine), vec![Value::string("wiki")]);
+
+        // Without the scope the same rule leaks the other tenant's grant
+        engine.set_fact_scopes(FactScopes::default()).unwrap();
+        let rules = crate::parser::parse_rules(
+            "can_read(U, D) :- member(T1, U, G), grant(T2, G, D).\nreadable(D) :- can_read(U, D).",
+        )
+        .unwrap();
+        engine.reload_datalog_rules(rules).unwrap();
+        assert_eq!(
+            derived(&engine),
+            vec![Value::string("payroll"), Value::string("wiki")]
         );
+    }
 
-        // Populate cache
-        engine.authorize(&request).expect("Authorization failed");
-        assert_eq!(engine.cache_stats().size, 1);
+    #[test]
+    fn test_rules_call_builtins() {
+        use crate::datalog::types::BuiltinFunction;
+
+        struct Score;
+        impl BuiltinFunction for Score {
+            fn call(&self, inputs: &[Value]) -> std::result::Result<Option<Value>, String> {
+                Ok(match inputs {
+                    [Value::String(user)] if user.as_ref() == "mallory" => Some(Value::Integer(90)),
+                    [Value::String(_)] => Some(Value::Integer(10)),
+                    _ => None,
+                })
+            }
+        }
 
-        // Clear cache
-        engine.clear_cache();
-        assert_eq!(engine.cache_stats().size, 0);
+        for backend in [EvaluationBackend::Interpreter, EvaluationBackend::Dataflow] {
+            let engine = RUNEEngine::with_config(EngineConfig {
+                evaluation_backend: backend,
+                ..EngineConfig::default()
+            });
+            engine
+                .reload_datalog_rules(
+                    crate::parser::parse_rules(
+                        "scored(U, S) :- user(U), risk(U, S).\nrisky(U) :- user(U), risk(U, 90).",
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+            for user in ["alice", "mallory"] {
+                engine.add_fact("user", vec![Value::string(user)]);
+            }
+            let derived = |engine: &RUNEEngine, predicate: &str| {
+                let mut facts = engine
+                    .datalog_version()
+                    .derive_facts()
+                    .unwrap()
+                    .into_iter()
+                    .filter(|f| f.predicate.as_ref() == predicate)
+                    .map(|f| f.args.to_vec())
+                    .collect::<Vec<_>>();
+                facts.sort();
+                facts
+            };
+
+            // Unregistered, `risk` is an ordinary predicate with no facts
+            assert!(derived(&engine, "scored").is_empty());
+
+            let mut builtins = BuiltinRegistry::new();
+            builtins.register("risk", 2, Arc::new(Score)).unwrap();
+            engine.set_builtins(builtins).unwrap();
+            assert_eq!(
+                derived(&engine, "scored"),
+                vec![
+                    vec![Value::string("alice"), Value::Integer(10)],
+                    vec![Value::string("mallory"), Value::Integer(90)],
+                ]
+            );
+            assert_eq!(
+                derived(&engine, "risky"),
+                vec![vec![Value::string("mallory")]]
+            );
 
-        // Next request should miss cache
-        let result = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result.cached);
+            // A builtin with a different arity is refused and the old one kept
+            let mut wrong = BuiltinRegistry::new();
+            wrong.register("risk", 3, Arc::new(Score)).unwrap();
+            assert!(engine.set_builtins(wrong).is_err());
+            assert!(engine.builtins().contains("risk"));
+            assert_eq!(derived(&engine, "risky").len(), 1);
+        }
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
-    fn test_metrics_tracking() {
-        let engine = RUNEEngine::new();
+    fn test_apply_config_loads_wasm_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().join("risk.wat");
+        // Always answers 80
+        std::fs::write(
+            &module,
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "80")
+                (func (export "rune_alloc") (param i32) (result i32) (i32.const 64))
+                (func (export "risk_score") (param i32 i32) (result i64) (i64.const 2)))"#,
+        )
+        .unwrap();
 
-        let request1 = Request::new(
-            Principal::agent("eve_metrics"),
-            Action::new("read"),
-            Resource::file("/data/file1_metrics.txt"),
-        );
-        let request2 = Request::new(
-            Principal::agent("frank_metrics"),
-            Action::new("read"),
-            Resource::file("/data/file2_metrics.txt"),
-        );
+        let source = r#"version = "rune/1.0"
 
-        // Perform some authorizations
-        let result1 = engine.authorize(&request1).expect("Authorization failed");
-        assert!(!result1.cached); // First request should not be cached
+[builtins]
+risk_score = { module = "risk.wat", arity = 2, fuel = 100000, memory_mb = 1 }
 
-        let result2 = engine.authorize(&request1).expect("Authorization failed");
-        assert!(result2.cached); // Second identical request should be cached
+[rules]
+risky(U) :- user(U), risk_score(U, 80).
+"#;
+        let path = dir.path().join("config.rune");
+        std::fs::write(&path, source).unwrap();
 
-        let result3 = engine.authorize(&request2).expect("Authorization failed");
-        assert!(!result3.cached); // Different request should not be cached
+        // The module is found next to the file, not in the working directory
+        let engine = RUNEEngine::new();
+        assert!(engine
+            .apply_config(crate::parser::parse_rune_file(source).unwrap())
+            .is_err());
+        engine.load_configuration(path.to_str().unwrap()).unwrap();
+        engine.add_fact("user", vec![Value::string("alice")]);
 
-        // Check metrics
-        let metrics = engine.metrics();
-        use std::sync::atomic::Ordering;
+        let derived = engine.datalog_version().derive_facts().unwrap();
+        assert!(derived
+            .iter()
+            .any(|f| f.predicate.as_ref() == "risky" && f.args[0] == Value::string("alice")));
+        assert!(rule_source(&engine.datalog_version().rules()[0])
+            .unwrap()
+            .contains("risk_score(U, 80)"));
+    }
 
-        // Cache hits and misses
-        let hits = metrics.cache_hits.load(Ordering::Relaxed);
-        let misses = metrics.cache_misses.load(Ordering::Relaxed);
-        assert_eq!(hits, 1, "Expected 1 cache hit");
-        assert_eq!(misses, 2, "Expected 2 cache misses");
+    #[test]
+    fn test_apply_config_registers_string_builtins() {
+        let config = crate::parser::parse_rune_file(
+            r#"version = "rune/1.0"
 
-        // Total authorizations only counts non-cached evaluations (cache misses)
-        let total = metrics.total_authorizations.load(Ordering::Relaxed);
+[strings]
+max_input_bytes = 32
+
+[rules]
+staff(U) :- email(U, E), regex_match(E, "@example[.]com$", true).
+handle(U, H) :- email(U, E), regex_capture(E, "^([^@]+)@", H).
+"#,
+        )
+        .unwrap();
+        let engine = RUNEEngine::new();
+        engine.apply_config(config).unwrap();
+        for (user, email) in [
+            ("alice", "alice@example.com"),
+            ("mallory", "mallory@example.org"),
+            // Over the input limit, so it matches nothing
+            ("bob", "bob.with.a.very.long.name@example.com"),
+        ] {
+            engine.add_fact("email", vec![Value::string(user), Value::string(email)]);
+        }
+
+        let derived = engine.datalog_version().derive_facts().unwrap();
+        let args = |predicate: &str| {
+            let mut args: Vec<Vec<Value>> = derived
+                .iter()
+                .filter(|f| f.predicate.as_ref() == predicate)
+                .map(|f| f.args.to_vec())
+                .collect();
+            args.sort();
+            args
+        };
+        assert_eq!(args("staff"), vec![vec![Value::string("alice")]]);
         assert_eq!(
-            total, 2,
-            "Expected 2 total authorizations (only cache misses are evaluated)"
+            args("handle"),
+            vec![
+                vec![Value::string("alice"), Value::string("alice")],
+                vec![Value::string("mallory"), Value::string("mallory")],
+            ]
         );
     }
 
     #[test]
-    fn test_cache_hit_rate_zero_requests() {
-        let metrics = EngineMetrics::new();
-        assert_eq!(metrics.cache_hit_rate(), 0.0);
+    fn test_conflicts_of_the_active_configuration() {
+        let engine = RUNEEngine::new();
+        engine
+            .apply_config(
+                crate::parser::parse_rune_file(
+                    r#"version = "rune/2.0"
+
+[rules]
+allow_read(U, R) :- member(U, "staff"), document(R).
+deny_read(U, R) :- contractor(U), document(R).
+
+[policies]
+@id("readers")
+permit(principal, action == Action::"read", resource in Folder::"q3");
+
+@id("frozen")
+@flag("freeze")
+forbid(principal, action, resource in Folder::"q3");
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let conflicts = engine.conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].kind, crate::ConflictKind::Shadowed);
+        assert_eq!(
+            (conflicts[0].permit.as_str(), conflicts[0].forbid.as_str()),
+            ("readers", "frozen")
+        );
+        assert_eq!(conflicts[1].source, crate::ConflictSource::Rules);
+
+        // Disabled policies do not take part
+        engine.set_rule_enabled("freeze", false).unwrap();
+        assert_eq!(engine.conflicts().len(), 1);
     }
 
     #[test]
-    fn test_cache_hit_rate_calculation() {
-        let metrics = EngineMetrics::new();
+    fn test_coverage_report() {
+        let engine = RUNEEngine::new();
+        engine
+            .apply_config(
+                crate::parser::parse_rune_file(
+                    r#"version = "rune/2.0"
+
+[rules]
+member("alice", "staff").
+member("bob", "admins").
+reader(U) :- member(U, "staff").
+@id("admins")
+admin(U) :- member(U, "admins").
+auditor(U) :- member(U, "audit").
+
+[policies]
+@id("readers")
+permit(principal, action == Action::"read", resource);
+
+@id("writers")
+permit(principal, action == Action::"write", resource);
+
+@id("no-secrets")
+forbid(principal, action, resource == Document::"secret");
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let requests = crate::coverage::parse_corpus(
+            r#"{"principal": "User:alice", "action": "read", "resource": "Document:q3"}
+{"principal": "User:alice", "action": "read", "resource": "Document:secret"}
+{"principal": "User:carol", "action": "delete", "resource": "Document:q3"}"#,
+        )
+        .unwrap();
+        let report = engine.coverage_report(&requests).unwrap();
+
+        assert_eq!(report.requests, 3);
+        let policies: Vec<(&str, usize)> = report
+            .policies
+            .iter()
+            .map(|p| (p.id.as_str(), p.matched))
+            .collect();
+        assert_eq!(
+            policies,
+            [("no-secrets", 1), ("readers", 2), ("writers", 0)]
+        );
+        let idle: Vec<&str> = report
+            .unexercised_rules()
+            .map(|r| r.rule.as_str())
+            .collect();
+        assert_eq!(idle, ["admins", "auditor(?U) :- member(?U, \"audit\")."]);
+        assert_eq!(report.rules[0].matched, 2);
+        assert_eq!(report.unmatched.len(), 1);
+        assert_eq!(report.unmatched[0].index, 2);
+        assert_eq!(report.unmatched[0].principal, "User:carol");
+        assert!(!report.is_complete());
+    }
 
-        // 3 misses, 2 hits = 0.4 hit rate
-        metrics.record_cache_miss();
-        metrics.record_cache_miss();
-        metrics.record_cache_miss();
-        metrics.record_cache_hit();
-        metrics.record_cache_hit();
+    #[test]
+    fn test_policy_index_follows_the_generation() {
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .add_policy(
+                "readers",
+                r#"@flag("reads") permit(principal, action == Action::"read", resource is Document);"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+
+        let index = engine.policy_index();
+        assert!(Arc::ptr_eq(&index, &engine.policy_index()));
+        let readers = index.get("readers").unwrap();
+        assert!(readers.enabled);
+        assert_eq!(
+            readers.scope.actions.as_deref(),
+            Some(&["read".to_string()][..])
+        );
 
-        assert_eq!(metrics.cache_hit_rate(), 0.4);
+        // A flag change starts a new generation, and a new index
+        engine.set_rule_enabled("reads", false).unwrap();
+        let index = engine.policy_index();
+        assert_eq!(index.generation, engine.generation());
+        assert!(!index.get("readers").unwrap().enabled);
     }
 
     #[test]
-    fn test_add_fact() {
+    fn test_apply_config_swaps_one_generation() {
         let engine = RUNEEngine::new();
-        engine.add_fact("user", vec![Value::string("alice")]);
-        engine.add_fact("role", vec![Value::string("alice"), Value::string("admin")]);
+        let config = |policy: &str| {
+            crate::parser::parse_rune_file(&format!(
+                "version = \"rune/2.0\"\n\n[rules]\nuser(\"alice\").\n\n[policies]\n@id(\"p\")\n{}\n",
+                policy
+            ))
+            .unwrap()
+        };
 
-        // Facts should be in the store (we can't easily verify without exposing the fact store)
-        // but at least ensure it doesn't panic
+        engine
+            .apply_config(config(
+                "permit(principal, action == Action::\"read\", resource);",
+            ))
+            .unwrap();
+        assert_eq!(engine.generation(), 1);
+
+        // A bad policy leaves rules, policies and generation untouched
+        assert!(engine.apply_config(config("permit(principal,")).is_err());
+        assert_eq!(engine.generation(), 1);
+        assert_eq!(engine.datalog_version().rules().len(), 1);
+        assert_eq!(engine.policies_version().active_count(), 1);
     }
 
     #[test]
-    fn test_sequential_evaluation() {
-        let config = EngineConfig {
-            cache_size: 100,
-            cache_ttl_secs: 60,
-            parallel_eval: false, // Force sequential
-            timeout_ms: 100,
+    fn test_owned_policies_need_the_owning_team() {
+        let engine = RUNEEngine::new();
+        let config = |action: &str| {
+            crate::parser::parse_rune_file(&format!(
+                "version = \"rune/2.0\"\n\n[rules]\nmember(\"carol\", \"payments\").\n\n[policies]\n@id(\"refunds\") @owner(\"payments\") @flag(\"refunds\")\npermit(principal, action == Action::\"{}\", resource);\n",
+                action
+            ))
+            .unwrap()
         };
-        let engine = RUNEEngine::with_config(config);
-
-        let request = Request::new(
-            Principal::agent("grace"),
-            Action::new("read"),
-            Resource::file("/data/sequential.txt"),
+        engine.apply_config(config("refund")).unwrap();
+        engine.add_to_group("dave", "support").unwrap();
+
+        // Another team can neither rewrite nor switch off the policy
+        let denied = engine.apply_config_as(config("read"), "dave");
+        assert!(matches!(denied, Err(RUNEError::AuthorizationDenied { .. })));
+        assert!(engine
+            .set_rule_enabled_as("refunds", false, "dave")
+            .is_err());
+        assert_eq!(engine.generation(), 1);
+
+        // An unchanged policy is no one's business
+        engine.apply_config_as(config("refund"), "dave").unwrap();
+
+        // A caller nobody identified cannot, even once added to the team
+        engine.add_to_group(ANONYMOUS_ACTOR, "payments").unwrap();
+        let denied = engine.apply_config_as(config("read"), ANONYMOUS_ACTOR);
+        assert!(matches!(denied, Err(RUNEError::AuthorizationDenied { .. })));
+
+        // Members of the owning team may, including through nested groups
+        engine.apply_config_as(config("read"), "carol").unwrap();
+        engine.add_to_group("erin", "refund-desk").unwrap();
+        engine.add_to_group("refund-desk", "payments").unwrap();
+        assert_eq!(
+            engine
+                .set_rule_enabled_as("refunds", false, "erin")
+                .unwrap(),
+            1
         );
-
-        let result = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result.cached);
-        assert!(result.evaluation_time_ns > 0);
+        assert!(engine.reset_rule_flag_as("refunds", "erin").unwrap());
+
+        // Rolling the rewrite back touches the policy again
+        let first = engine.config_versions()[0].version;
+        let denied = engine.rollback_to_as(first, "dave");
+        assert!(matches!(denied, Err(RUNEError::AuthorizationDenied { .. })));
+        let restored = engine.rollback_to_as(first, "carol").unwrap();
+        assert_eq!(restored.rollback_of, Some(first));
     }
 
     #[test]
-    fn test_parallel_evaluation() {
-        let config = EngineConfig {
-            cache_size: 100,
-            cache_ttl_secs: 60,
-            parallel_eval: true, // Force parallel
-            timeout_ms: 100,
+    fn test_staged_config_decides_without_taking_over() {
+        let engine = RUNEEngine::new();
+        let config = |action: &str| {
+            crate::parser::parse_rune_file(&format!(
+                "version = \"rune/2.0\"\n\n[rules]\nactive(\"alice\").\n\n[policies]\n@id(\"p\")\npermit(principal, action == Action::\"{}\", resource);\n",
+                action
+            ))
+            .unwrap()
         };
-        let engine = RUNEEngine::with_config(config);
+        engine.apply_config(config("read")).unwrap();
+        let generation = engine.generation();
 
-        let request = Request::new(
-            Principal::agent("heidi"),
-            Action::new("read"),
-            Resource::file("/data/parallel.txt"),
+        let staged = engine.stage_config(config("write")).unwrap();
+        assert_eq!((staged.id, staged.rules, staged.policies), (1, 1, 1));
+        assert_eq!(engine.generation(), generation);
+
+        let write = Request::new(
+            Principal::user("alice"),
+            Action::new("write"),
+            Resource::file("/tmp/report"),
         );
+        assert_eq!(engine.authorize(&write).unwrap().decision, Decision::Deny);
+        let handle = engine.staged_handle(staged.id).unwrap();
+        assert_eq!(handle.authorize(&write).unwrap().decision, Decision::Permit);
+
+        // Staged configurations read live facts
+        let facts = handle.fact_count();
+        engine.add_fact("member", vec![Value::string("alice")]);
+        assert_eq!(handle.fact_count(), facts + 1);
+
+        // A bad policy is not staged
+        let bad = crate::parser::parse_rune_file(
+            "version = \"rune/2.0\"\n\n[policies]\n@id(\"p\")\npermit(principal,\n",
+        )
+        .unwrap();
+        assert!(engine.stage_config(bad).is_err());
+        assert_eq!(engine.staged_configs().len(), 1);
 
-        let result = engine.authorize(&request).expect("Authorization failed");
-        assert!(!result.cached);
-        assert!(result.evaluation_time_ns > 0);
+        for _ in 0..MAX_STAGED {
+            engine.stage_config(config("read")).unwrap();
+        }
+        let ids: Vec<u64> = engine.staged_configs().iter().map(|s| s.id).collect();
+        assert_eq!(ids.len(), MAX_STAGED);
+        assert_eq!(ids[0], 2);
+        assert!(engine.staged_handle(staged.id).is_none());
+
+        assert!(engine.discard_staged(2));
+        assert!(!engine.discard_staged(2));
     }
 
     #[test]
-    fn test_reload_datalog_rules() {
+    fn test_rollback_to_installed_version() {
         let engine = RUNEEngine::new();
+        let config = |action: &str| {
+            crate::parser::parse_rune_file(&format!(
+                "version = \"rune/2.0\"\n\n[rules]\nactive(\"alice\").\n\n[policies]\n@id(\"p\")\npermit(principal, action == Action::\"{}\", resource);\n",
+                action
+            ))
+            .unwrap()
+        };
+        let request = |action: &str| {
+            Request::new(
+                Principal::user("alice"),
+                Action::new(action),
+                Resource::file("/tmp/report"),
+            )
+        };
+        assert_eq!(engine.current_version(), None);
+        engine.apply_config(config("read")).unwrap();
+        engine.apply_config(config("write")).unwrap();
+        assert_eq!(engine.current_version(), Some(2));
+        assert_eq!(
+            engine.authorize(&request("read")).unwrap().decision,
+            Decision::Deny
+        );
 
-        // Add some facts
-        engine.add_fact("user", vec![Value::string("alice")]);
+        let generation = engine.generation();
+        let restored = engine.rollback_to(1).unwrap();
+        assert_eq!(restored.version, 3);
+        assert_eq!(restored.rollback_of, Some(1));
+        assert_eq!(restored.generation, engine.generation());
+        assert!(restored.generation > generation);
+        assert_eq!(engine.current_version(), Some(3));
+        assert_eq!(
+            engine.authorize(&request("read")).unwrap().decision,
+            Decision::Permit
+        );
 
-        // Create new rules (empty for this test)
-        let new_rules: Vec<Rule> = vec![];
+        // Today's flags apply to the restored policies
+        engine.set_rule_enabled("p", false).unwrap();
+        engine.rollback_to(2).unwrap();
+        assert_eq!(
+            engine.authorize(&request("write")).unwrap().decision,
+            Decision::Deny
+        );
 
-        // Reload rules
-        engine
-            .reload_datalog_rules(new_rules)
-            .expect("Failed to reload rules");
+        let versions: Vec<u64> = engine.config_versions().iter().map(|v| v.version).collect();
+        assert_eq!(versions, [1, 2, 3, 4]);
+        assert!(engine.rollback_to(9).is_err());
 
-        // Cache should be cleared
+        for _ in 0..MAX_VERSIONS {
+            engine.apply_config(config("read")).unwrap();
+        }
+        assert_eq!(engine.config_versions().len(), MAX_VERSIONS);
+        assert!(engine.rollback_to(1).is_err());
+
+        // Policies reloaded on their own are no installed version
+        engine.reload_policies(PolicySet::new()).unwrap();
+        assert_eq!(engine.current_version(), None);
+    }
+
+    #[test]
+    fn test_evaluate_shadow() {
+        let engine = RUNEEngine::new();
+        engine
+            .apply_config(
+                crate::parser::parse_rune_file(
+                    "version = \"rune/2.0\"\n\n[rules]\nactive(\"alice\").\n\n[policies]\n@id(\"p\")\npermit(principal, action == Action::\"read\", resource);\n",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let mut candidate = PolicySet::new();
+        candidate
+            .load_policies(r#"permit(principal, action == Action::"write", resource);"#)
+            .unwrap();
+
+        let request = |action: &str| {
+            Request::new(
+                Principal::user("alice"),
+                Action::new(action),
+                Resource::file("/tmp/report"),
+            )
+        };
+        let write = engine
+            .evaluate_shadow(&request("write"), &candidate)
+            .unwrap();
+        assert_eq!(write.decision, Decision::Permit);
+        let read = engine
+            .evaluate_shadow(&request("read"), &candidate)
+            .unwrap();
+        assert_eq!(read.decision, Decision::Deny);
+
+        // The active configuration still decides, and nothing was cached
         assert_eq!(engine.cache_stats().size, 0);
+        assert_eq!(
+            engine.authorize(&request("write")).unwrap().decision,
+            Decision::Deny
+        );
     }
 
     #[test]
-    fn test_reload_policies() {
+    fn test_export_reloads_to_same_dump() {
+        use crate::export::ExportFormat;
+
+        let config = crate::parser::parse_rune_file(
+            r#"version = "rune/2.0"
+
+[rules]
+@id("admins")
+can_write(U) :- admin(U), not suspended(U).
+
+[policies]
+@id("read")
+permit(principal, action == Action::"read", resource);
+
+@id("no-delete")
+forbid(principal, action == Action::"delete", resource);
+"#,
+        )
+        .unwrap();
+        let mut policies = PolicySet::new();
+        for policy in &config.policies {
+            policies.add_policy(&policy.id, &policy.content).unwrap();
+        }
+
         let engine = RUNEEngine::new();
+        engine.reload_datalog_rules(config.rules).unwrap();
+        engine.reload_policies(policies).unwrap();
+        engine.add_fact("admin", vec![Value::string("alice")]);
+        engine.add_fact("admin", vec![Value::string("alice")]);
+        engine.set_rule_enabled("admins", false).unwrap();
+
+        let dump = engine.export().unwrap();
+        assert_eq!(dump.generation, engine.generation());
+        assert_eq!(dump.facts.len(), 1);
+        assert_eq!(dump.overrides.get("admins"), Some(&false));
+        let ids: Vec<_> = dump.policies.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, vec!["no-delete", "read"]);
+
+        // Loading the RUNE dump into a fresh engine reproduces it
+        let reloaded = crate::parser::parse_rune_file(&dump.to_rune().unwrap()).unwrap();
+        let mut policies = PolicySet::new();
+        for policy in &reloaded.policies {
+            policies.add_policy(&policy.id, &policy.content).unwrap();
+        }
+        let copy = RUNEEngine::new();
+        copy.reload_datalog_rules(reloaded.rules).unwrap();
+        copy.reload_policies(policies).unwrap();
+
+        let copied = copy.export().unwrap();
+        assert_eq!(copied.policies, dump.policies);
+        // Base facts come back as ground facts among the rules
+        assert_eq!(copied.rules.len(), dump.rules.len() + 1);
+        assert_eq!(copied.rules[0], dump.rules[0]);
+        assert!(copied
+            .render(ExportFormat::Json)
+            .unwrap()
+            .contains("can_write(U) :- admin(U), not suspended(U)."));
+    }
 
-        // Populate cache first
-        let request = Request::new(
-            Principal::agent("iris"),
+    #[test]
+    fn test_canonicalized_requests_share_cache_entry() {
+        use crate::canonical::{CanonicalRule, CanonicalizationConfig, Target, Transform};
+
+        let engine = configured_engine();
+        engine.set_canonicalizer(Canonicalizer::new(CanonicalizationConfig {
+            rules: vec![CanonicalRule {
+                target: Target::Principal,
+                entity_type: None,
+                transforms: vec![Transform::Lowercase],
+            }],
+            ..CanonicalizationConfig::default()
+        }));
+
+        let resource = Resource::file("/data/report.txt");
+        let first = Request::new(
+            Principal::user("Mia@Example.com"),
             Action::new("read"),
-            Resource::file("/data/test.txt"),
+            resource.clone(),
+        );
+        let second = Request::new(
+            Principal::user("mia@example.com"),
+            Action::new("read"),
+            resource,
         );
-        engine.authorize(&request).expect("Authorization failed");
-        assert_eq!(engine.cache_stats().size, 1);
-
-        // Reload policies with new set
-        let new_policies = PolicySet::new();
-        engine
-            .reload_policies(new_policies)
-            .expect("Failed to reload policies");
 
-        // Cache should be cleared
-        assert_eq!(engine.cache_stats().size, 0);
+        assert!(!engine.authorize(&first).unwrap().cached);
+        assert!(engine.authorize(&second).unwrap().cached);
+        assert_eq!(engine.cache_stats().size, 1);
     }
 
     #[test]
-    fn test_datalog_version() {
+    fn test_rule_kill_switch() {
+        use crate::datalog::types::{Atom, Term};
+
         let engine = RUNEEngine::new();
-        let version = engine.datalog_version();
-        assert_eq!(version.rules().len(), 0); // Empty engine
+        let admin = Rule::fact(Atom::new(
+            "admin",
+            vec![Term::constant(Value::string("alice"))],
+        ))
+        .with_annotation("id", "bootstrap_admin");
+        engine
+            .reload_datalog_rules(vec![admin.clone()])
+            .expect("Failed to reload rules");
+        assert_eq!(engine.datalog_version().active_rules().len(), 1);
+
+        assert_eq!(
+            engine.set_rule_enabled("bootstrap_admin", false).unwrap(),
+            1
+        );
+        assert_eq!(engine.datalog_version().active_rules().len(), 0);
+        assert_eq!(engine.datalog_version().rules().len(), 1);
+
+        // Override survives a config push
+        engine
+            .reload_datalog_rules(vec![admin])
+            .expect("Failed to reload rules");
+        assert_eq!(engine.datalog_version().active_rules().len(), 0);
+
+        let flags = engine.rule_flags();
+        assert_eq!(flags.len(), 1);
+        assert!(!flags[0].enabled);
+        assert!(flags[0].overridden);
+
+        assert!(engine.reset_rule_flag("bootstrap_admin").unwrap());
+        assert_eq!(engine.datalog_version().active_rules().len(), 1);
+        assert!(!engine.reset_rule_flag("bootstrap_admin").unwrap());
+
+        // Unknown keys are not recorded
+        assert_eq!(engine.set_rule_enabled("missing", false).unwrap(), 0);
+        assert_eq!(engine.rule_flags().len(), 1);
     }
 
     #[test]
-    fn test_policies_version() {
+    fn test_policy_disabled_by_annotation() {
+        let mut policies = PolicySet::new();
+        policies
+            .add_policy(
+                "allow_all",
+                "@id(\"allow_all\")\n@enabled(\"false\")\npermit(principal, action, resource);",
+            )
+            .expect("Failed to add policy");
+        assert_eq!(policies.active_count(), 0);
+
         let engine = RUNEEngine::new();
-        let version = engine.policies_version();
-        // Just ensure we can get the version
-        let _ = version;
+        engine
+            .reload_policies(policies)
+            .expect("Failed to reload policies");
+        assert_eq!(engine.set_rule_enabled("allow_all", true).unwrap(), 1);
+        assert_eq!(engine.policies_version().active_count(), 1);
     }
 
     #[test]
@@ -872,7 +6732,7 @@ mod tests {
 
     #[test]
     fn test_multiple_cache_entries() {
-        let engine = RUNEEngine::new();
+        let engine = configured_engine();
 
         // Create multiple different requests
         for i in 0..5 {
@@ -969,4 +6829,70 @@ mod tests {
         // (though with empty rules, actual decision depends on evaluation)
         assert!(!result.explanation.is_empty());
     }
+
+    #[test]
+    fn test_principal_kinds_in_cedar() {
+        use crate::request::RequestBuilder;
+        use crate::types::PrincipalKind;
+
+        let engine = RUNEEngine::new();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"permit(principal is Device, action == Action::"sync", resource)
+                    when { principal.platform == "ios" };
+                permit(principal is ServiceAccount, action == Action::"deploy", resource)
+                    when { principal.owner == "platform-team" };"#,
+            )
+            .unwrap();
+        engine.reload_policies(policies).unwrap();
+        // Leave the decision to Cedar: Datalog permits once it has facts
+        engine.add_fact("registered", vec![Value::string("phone-1")]);
+
+        let decide = |principal: Principal, action: &str| {
+            let request = RequestBuilder::new()
+                .principal(principal)
+                .action(Action::new(action))
+                .resource(Resource::new("Repo", "infra"))
+                .build()
+                .unwrap();
+            engine.authorize(&request).unwrap().decision
+        };
+        assert_eq!(
+            decide(Principal::device("phone-1", "ios"), "sync"),
+            Decision::Permit
+        );
+        assert_eq!(
+            decide(Principal::device("phone-2", "android"), "sync"),
+            Decision::Deny
+        );
+        // Same ID and attributes, different type
+        assert_eq!(
+            decide(Principal::api_client("phone-1", "ios"), "sync"),
+            Decision::Deny
+        );
+        assert_eq!(
+            decide(Principal::service_account("ci", "platform-team"), "deploy"),
+            Decision::Permit
+        );
+
+        // Kinds need their attributes; other types are unchecked
+        let missing = RequestBuilder::new()
+            .principal(Principal::parse("Device:phone-3"))
+            .action(Action::new("sync"))
+            .resource(Resource::new("Repo", "infra"))
+            .build();
+        assert!(matches!(missing, Err(RUNEError::InvalidRequest(msg)) if msg.contains("platform")));
+        assert!(Principal::parse("Robot:r2").validate().is_ok());
+
+        assert_eq!(
+            Principal::parse("ServiceAccount:ci").kind(),
+            Some(PrincipalKind::ServiceAccount)
+        );
+        assert_eq!(
+            "api-client".parse::<PrincipalKind>().unwrap(),
+            PrincipalKind::ApiClient
+        );
+        assert!("robot".parse::<PrincipalKind>().is_err());
+    }
 }
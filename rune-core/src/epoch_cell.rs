@@ -0,0 +1,491 @@
+//! Epoch-reclaimed cell behind the fact store's lock-free fact vector
+//!
+//! [`EpochCell`] holds a value that readers load without locking and writers
+//! replace with a compare-and-swap loop. Replaced values are freed through
+//! crossbeam's epoch-based reclamation once no reader can still see them.
+//! All of the crate's `unsafe` epoch code lives here.
+//!
+//! Debug builds count every node the cell allocates, retires and frees (see
+//! [`EpochCell::audit`]), so tests can check that nothing leaks.
+//!
+//! The CAS loop is model-checked with loom. Crossbeam's own loom mode cannot
+//! be used here (it breaks crossbeam-channel), so under `--cfg rune_loom` the
+//! slot is a loom `AtomicPtr` that keeps retired nodes until the cell drops
+//! and checks that each one was retired exactly once:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg rune_loom" cargo test -p rune-core --lib epoch_cell::loom_tests
+//! ```
+
+#![allow(unsafe_code)] // Required for crossbeam epoch-based memory reclamation
+
+use slot::{pin, Slot};
+
+#[cfg(debug_assertions)]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// A value shared lock-free between readers and writers
+pub(crate) struct EpochCell<T> {
+    slot: Slot<Node<T>>,
+    #[cfg(debug_assertions)]
+    audit: Arc<Audit>,
+}
+
+/// Heap node holding one version of the value
+struct Node<T> {
+    value: T,
+    #[cfg(debug_assertions)]
+    audit: Arc<Audit>,
+}
+
+#[cfg(debug_assertions)]
+impl<T> Drop for Node<T> {
+    fn drop(&mut self) {
+        self.audit.destroyed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Allocation counters for one cell
+#[cfg(debug_assertions)]
+#[derive(Debug, Default)]
+struct Audit {
+    allocated: AtomicUsize,
+    retired: AtomicUsize,
+    destroyed: AtomicUsize,
+}
+
+/// Snapshot of a cell's allocation counters (debug builds only)
+#[cfg(debug_assertions)]
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AuditCounts {
+    /// Nodes created, including ones that lost a CAS race
+    pub allocated: usize,
+    /// Replaced nodes handed to the collector
+    pub retired: usize,
+    /// Nodes freed, by the collector or directly
+    pub destroyed: usize,
+}
+
+#[cfg(debug_assertions)]
+#[cfg_attr(not(test), allow(dead_code))]
+impl AuditCounts {
+    /// Nodes not yet freed; one while the cell is alive and every retired
+    /// node has been collected
+    pub fn live(&self) -> usize {
+        self.allocated - self.destroyed
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> EpochCell<T> {
+    /// Create a cell holding `value`
+    pub fn new(value: T) -> Self {
+        #[cfg(debug_assertions)]
+        let audit = Arc::new(Audit::default());
+        let node = Node {
+            value,
+            #[cfg(debug_assertions)]
+            audit: audit.clone(),
+        };
+        #[cfg(debug_assertions)]
+        audit.allocated.fetch_add(1, Ordering::Relaxed);
+
+        EpochCell {
+            slot: Slot::new(Box::new(node)),
+            #[cfg(debug_assertions)]
+            audit,
+        }
+    }
+
+    /// Copy out the current value
+    pub fn load(&self) -> T {
+        let pin = &pin();
+        self.slot.load(pin).value.clone()
+    }
+
+    /// Replace the value with `update(current)`, retrying on contention
+    ///
+    /// Returns false, leaving the cell untouched, when `update` returns
+    /// None. `update` may run more than once.
+    pub fn update(&self, mut update: impl FnMut(&T) -> Option<T>) -> bool {
+        let pin = &pin();
+        loop {
+            let current = self.slot.load(pin);
+            let Some(value) = update(&current.value) else {
+                return false;
+            };
+
+            // A node that loses the race comes back and is dropped here
+            if self
+                .slot
+                .compare_exchange(current, self.node(value), pin)
+                .is_ok()
+            {
+                self.retired();
+                return true;
+            }
+        }
+    }
+
+    /// Replace the value unconditionally
+    pub fn replace(&self, value: T) {
+        let pin = &pin();
+        self.slot.swap(self.node(value), pin);
+        self.retired();
+    }
+
+    /// Allocation counters (debug builds only)
+    #[cfg(debug_assertions)]
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn audit(&self) -> AuditCounts {
+        AuditCounts {
+            allocated: self.audit.allocated.load(Ordering::Relaxed),
+            retired: self.audit.retired.load(Ordering::Relaxed),
+            destroyed: self.audit.destroyed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn node(&self, value: T) -> Box<Node<T>> {
+        #[cfg(debug_assertions)]
+        self.audit.allocated.fetch_add(1, Ordering::Relaxed);
+        Box::new(Node {
+            value,
+            #[cfg(debug_assertions)]
+            audit: self.audit.clone(),
+        })
+    }
+
+    fn retired(&self) {
+        #[cfg(debug_assertions)]
+        self.audit.retired.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Pointer slot reclaimed by crossbeam's epoch collector
+#[cfg(not(rune_loom))]
+mod slot {
+    use crossbeam::epoch::{self, Atomic, Guard, Owned, Shared};
+    use std::sync::atomic::Ordering;
+
+    /// Keeps loaded nodes alive while held
+    pub(super) type Pin = Guard;
+
+    pub(super) fn pin() -> Pin {
+        epoch::pin()
+    }
+
+    /// Atomic pointer to a node; never null
+    pub(super) struct Slot<N>(Atomic<N>);
+
+    impl<N> Slot<N> {
+        pub fn new(node: Box<N>) -> Self {
+            Slot(Atomic::from(Owned::from(node)))
+        }
+
+        pub fn load<'g>(&self, pin: &'g Pin) -> &'g N {
+            // SAFETY: the slot is never null, and the node cannot be freed
+            // while `pin` keeps this thread pinned
+            unsafe { self.0.load(Ordering::Acquire, pin).deref() }
+        }
+
+        /// Install `new` if the slot still holds `current`, retiring
+        /// `current`; otherwise hand `new` back
+        pub fn compare_exchange(&self, current: &N, new: Box<N>, pin: &Pin) -> Result<(), Box<N>> {
+            let current = Shared::from(current as *const N);
+            match self.0.compare_exchange(
+                current,
+                Owned::from(new),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                pin,
+            ) {
+                Ok(_) => {
+                    // SAFETY: the CAS unlinked `current`, so no new reader
+                    // can reach it and only this thread retires it
+                    unsafe { pin.defer_destroy(current) };
+                    Ok(())
+                }
+                Err(e) => Err(e.new.into_box()),
+            }
+        }
+
+        /// Install `new`, retiring whatever the slot held
+        pub fn swap(&self, new: Box<N>, pin: &Pin) {
+            let previous = self.0.swap(Owned::from(new), Ordering::AcqRel, pin);
+            // SAFETY: the swap unlinked `previous`, so no new reader can
+            // reach it and only this thread retires it
+            unsafe { pin.defer_destroy(previous) };
+        }
+    }
+
+    impl<N> Drop for Slot<N> {
+        fn drop(&mut self) {
+            // SAFETY: `&mut self` means no other thread can access the slot,
+            // and the current node was never retired, so it is freed once
+            unsafe {
+                let current = self.0.load(Ordering::Relaxed, epoch::unprotected());
+                drop(current.into_owned());
+            }
+        }
+    }
+}
+
+/// Pointer slot for loom: retired nodes are kept until the slot drops,
+/// which checks that none was retired twice
+#[cfg(rune_loom)]
+mod slot {
+    use loom::sync::atomic::{AtomicPtr, Ordering};
+    use loom::sync::Mutex;
+    use std::marker::PhantomData;
+
+    pub(super) struct Pin;
+
+    pub(super) fn pin() -> Pin {
+        Pin
+    }
+
+    pub(super) struct Slot<N> {
+        current: AtomicPtr<N>,
+        retired: Mutex<Vec<*mut N>>,
+        _owns: PhantomData<Box<N>>,
+    }
+
+    // SAFETY: the raw pointers are owned boxes shared the same way as the
+    // crossbeam slot's nodes
+    unsafe impl<N: Send + Sync> Send for Slot<N> {}
+    unsafe impl<N: Send + Sync> Sync for Slot<N> {}
+
+    impl<N> Slot<N> {
+        pub fn new(node: Box<N>) -> Self {
+            Slot {
+                current: AtomicPtr::new(Box::into_raw(node)),
+                retired: Mutex::new(Vec::new()),
+                _owns: PhantomData,
+            }
+        }
+
+        pub fn load<'g>(&self, _pin: &'g Pin) -> &'g N {
+            // SAFETY: nodes are only freed when the slot drops
+            unsafe { &*self.current.load(Ordering::Acquire) }
+        }
+
+        pub fn compare_exchange(&self, current: &N, new: Box<N>, _pin: &Pin) -> Result<(), Box<N>> {
+            let new = Box::into_raw(new);
+            match self.current.compare_exchange(
+                current as *const N as *mut N,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(previous) => {
+                    self.retired.lock().unwrap().push(previous);
+                    Ok(())
+                }
+                // SAFETY: `new` was never published
+                Err(_) => Err(unsafe { Box::from_raw(new) }),
+            }
+        }
+
+        pub fn swap(&self, new: Box<N>, _pin: &Pin) {
+            let previous = self.current.swap(Box::into_raw(new), Ordering::AcqRel);
+            self.retired.lock().unwrap().push(previous);
+        }
+    }
+
+    impl<N> Drop for Slot<N> {
+        fn drop(&mut self) {
+            let mut nodes = std::mem::take(&mut *self.retired.lock().unwrap());
+            nodes.push(self.current.load(Ordering::Relaxed));
+            nodes.sort_unstable();
+            let count = nodes.len();
+            nodes.dedup();
+            assert_eq!(nodes.len(), count, "node retired twice");
+            for node in nodes {
+                // SAFETY: each node is distinct and no longer reachable
+                drop(unsafe { Box::from_raw(node) });
+            }
+        }
+    }
+}
+
+#[cfg(all(test, debug_assertions, not(rune_loom)))]
+mod tests {
+    use super::*;
+    use crossbeam::epoch;
+
+    /// Updates per thread; miri runs far fewer
+    const ROUNDS: u32 = if cfg!(miri) { 5 } else { 100 };
+
+    /// Advance the global epoch until every retired node is freed
+    fn collect(cell: &EpochCell<impl Clone + Send + Sync + 'static>) -> AuditCounts {
+        for _ in 0..10_000 {
+            if cell.audit().live() == 1 {
+                break;
+            }
+            epoch::pin().flush();
+            std::thread::yield_now();
+        }
+        cell.audit()
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let cell = Arc::new(EpochCell::new(Vec::new()));
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let cell = cell.clone();
+                std::thread::spawn(move || {
+                    for i in 0..ROUNDS {
+                        cell.update(|v: &Vec<u32>| {
+                            let mut v = v.clone();
+                            v.push(t * ROUNDS + i);
+                            Some(v)
+                        });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut values = cell.load();
+        values.sort_unstable();
+        assert_eq!(values, (0..4 * ROUNDS).collect::<Vec<_>>());
+
+        // Every replaced node and every node that lost a race is freed
+        let counts = collect(&cell);
+        assert_eq!(counts.live(), 1, "{:?}", counts);
+        assert_eq!(counts.retired, 4 * ROUNDS as usize);
+    }
+
+    #[test]
+    fn test_replace_races_with_update() {
+        let cell = Arc::new(EpochCell::new(vec![0u32]));
+        let writer = {
+            let cell = cell.clone();
+            std::thread::spawn(move || {
+                for i in 0..ROUNDS {
+                    cell.update(|v| Some(v.iter().map(|x| x + i).collect()));
+                }
+            })
+        };
+        for _ in 0..ROUNDS {
+            cell.replace(vec![1]);
+        }
+        writer.join().unwrap();
+
+        let counts = collect(&cell);
+        assert_eq!(counts.live(), 1, "{:?}", counts);
+        assert_eq!(counts.retired, 2 * ROUNDS as usize);
+    }
+
+    #[test]
+    fn test_declined_update_allocates_nothing() {
+        let cell = EpochCell::new(1);
+        assert!(!cell.update(|_| None));
+        assert_eq!(cell.load(), 1);
+        assert_eq!(cell.audit().allocated, 1);
+    }
+
+    #[test]
+    fn test_drop_frees_current_value() {
+        let value = Arc::new(());
+        let cell = EpochCell::new(value.clone());
+        cell.replace(value.clone());
+        drop(cell);
+
+        for _ in 0..10_000 {
+            if Arc::strong_count(&value) == 1 {
+                break;
+            }
+            epoch::pin().flush();
+            std::thread::yield_now();
+        }
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
+
+#[cfg(all(test, rune_loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+
+    /// Under loom nothing retired is freed before the cell drops, so every
+    /// node is current, retired or a discarded CAS loser
+    fn assert_accounted(cell: &EpochCell<Vec<u32>>) {
+        let counts = cell.audit();
+        assert_eq!(
+            counts.allocated,
+            1 + counts.retired + counts.destroyed,
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        loom::model(|| {
+            let cell = loom::sync::Arc::new(EpochCell::new(Vec::new()));
+            let threads: Vec<_> = (0..2)
+                .map(|t| {
+                    let cell = cell.clone();
+                    thread::spawn(move || {
+                        cell.update(|v: &Vec<u32>| {
+                            let mut v = v.clone();
+                            v.push(t);
+                            Some(v)
+                        });
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+
+            let mut values = cell.load();
+            values.sort_unstable();
+            assert_eq!(values, [0, 1]);
+            assert_accounted(&cell);
+        });
+    }
+
+    #[test]
+    fn replace_and_update_retire_once() {
+        loom::model(|| {
+            let cell = loom::sync::Arc::new(EpochCell::new(vec![0u32]));
+            let writer = {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    cell.update(|v| Some(v.iter().map(|x| x + 1).collect()));
+                })
+            };
+            cell.replace(vec![10]);
+            writer.join().unwrap();
+
+            // Either the update saw the replacement or the replacement won
+            let value = cell.load();
+            assert!(value == [11] || value == [10], "{:?}", value);
+            assert_accounted(&cell);
+        });
+    }
+
+    #[test]
+    fn readers_see_whole_values() {
+        loom::model(|| {
+            let cell = loom::sync::Arc::new(EpochCell::new(vec![1u32, 1]));
+            let reader = {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    let value = cell.load();
+                    assert_eq!(value[0], value[1]);
+                })
+            };
+            cell.update(|v| Some(v.iter().map(|x| x + 1).collect()));
+            reader.join().unwrap();
+        });
+    }
+}
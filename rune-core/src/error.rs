@@ -22,6 +22,7 @@ pub enum RUNEError {
     DatalogError(String),
 
     /// Cedar policy error
+    #[cfg(feature = "full")]
     #[error("Cedar policy error: {0}")]
     CedarError(#[from] Box<cedar_policy::PolicySetError>),
 
@@ -56,8 +57,36 @@ pub enum RUNEError {
     #[error("Operation timed out after {0}ms")]
     Timeout(u64),
 
+    /// Evaluation was cancelled before it finished
+    #[error("Evaluation cancelled")]
+    Cancelled,
+
+    /// A replica has not caught up with its primary recently enough
+    #[error("Replica data is older than the {max_ms}ms allowed")]
+    ReplicaStale {
+        /// Time since the replica last caught up; `None` if it never has
+        lag_ms: Option<u64>,
+        /// Staleness the request allowed
+        max_ms: u64,
+    },
+
+    /// Replication changes could not be applied
+    #[error("Replication error: {0}")]
+    Replication(String),
+
+    /// A derived predicate grew past its `[limits]` entry
+    #[error("Derived predicate {predicate} exceeded its limit of {limit} facts; rule: {rule}")]
+    CardinalityExceeded {
+        /// The predicate
+        predicate: String,
+        /// Its limit
+        limit: usize,
+        /// The rule deriving the excess
+        rule: String,
+    },
+
     /// Rich diagnostic error with multiple messages and suggestions
-    #[error("{}", .0.format(None))]
+    #[error("{0}")]
     DiagnosticError(DiagnosticBag),
 }
 
@@ -89,8 +118,14 @@ impl RUNEError {
 
     /// Format the error with optional source code context
     pub fn format_with_source(&self, source: Option<&str>) -> String {
+        self.render_with_source(source, true)
+    }
+
+    /// Format the error with optional source code context, in color if
+    /// `color` is set
+    pub fn render_with_source(&self, source: Option<&str>, color: bool) -> String {
         match self {
-            RUNEError::DiagnosticError(bag) => bag.format(source),
+            RUNEError::DiagnosticError(bag) => bag.render(source, color),
             _ => self.to_string(),
         }
     }
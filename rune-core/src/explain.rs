@@ -0,0 +1,489 @@
+//! Locale-aware rendering of decision explanations
+//!
+//! Every decision carries stable [`Reason`] codes alongside its English
+//! `explanation`. Product teams show these to end users, so an
+//! [`ExplanationRenderer`] turns the codes into text in the caller's
+//! language while the codes themselves never change.
+//!
+//! English, Spanish, French and German catalogs are built in. A
+//! `[messages]` section in a RUNE file overrides or adds messages per
+//! locale; `{name}` placeholders are filled from the reason's parameters:
+//!
+//! ```toml
+//! [fr]
+//! no_matching_permit = "Accès refusé : aucune règle ne vous y autorise"
+//!
+//! [pt-BR]
+//! permitted = "Permitido por {rules} regras"
+//! ```
+//!
+//! Locales are negotiated from an `Accept-Language` header: the highest
+//! weighted tag with a catalog wins, matching `fr-CH` to `fr` when there is
+//! no `fr-CH` catalog. Messages a catalog lacks fall back to English.
+
+use crate::decision::AuthorizationResult;
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Locale used when the caller accepts none that has a catalog
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Stable code for one part of a decision's explanation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    /// Permitted; `rules` is the number of contributing rules and policies
+    Permitted,
+    /// Denied because nothing permitted the request
+    NoMatchingPermit,
+    /// Explicitly forbidden; `source` is `datalog` or `cedar`
+    Forbidden,
+    /// A dependency failed and was handled by the failure policy; `class`
+    /// and `mode` name the failure and how it was handled
+    DependencyFailure,
+    /// Attribute sources disagreed; `attribute`, `source`, `strategy` and
+    /// `candidates` describe the resolution
+    AttributeSource,
+    /// Denied because the engine has no rules or policies loaded
+    Unconfigured,
+    /// Permitted by the allow-all bootstrap override of an unconfigured engine
+    Bootstrap,
+    /// Not permitted, but access was requested and awaits review; `id` is
+    /// the access request
+    AccessPending,
+}
+
+impl ReasonCode {
+    /// Every code, in declaration order
+    pub const ALL: [ReasonCode; 8] = [
+        ReasonCode::Permitted,
+        ReasonCode::NoMatchingPermit,
+        ReasonCode::Forbidden,
+        ReasonCode::DependencyFailure,
+        ReasonCode::AttributeSource,
+        ReasonCode::Unconfigured,
+        ReasonCode::Bootstrap,
+        ReasonCode::AccessPending,
+    ];
+
+    /// Code as it appears in responses and catalogs
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReasonCode::Permitted => "permitted",
+            ReasonCode::NoMatchingPermit => "no_matching_permit",
+            ReasonCode::Forbidden => "forbidden",
+            ReasonCode::DependencyFailure => "dependency_failure",
+            ReasonCode::AttributeSource => "attribute_source",
+            ReasonCode::Unconfigured => "unconfigured",
+            ReasonCode::Bootstrap => "bootstrap",
+            ReasonCode::AccessPending => "access_pending",
+        }
+    }
+}
+
+impl fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReasonCode {
+    type Err = RUNEError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ReasonCode::ALL
+            .into_iter()
+            .find(|code| code.as_str() == s)
+            .ok_or_else(|| RUNEError::ConfigError(format!("Unknown reason code: {}", s)))
+    }
+}
+
+/// A reason code with the values its message refers to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reason {
+    /// Stable code
+    pub code: ReasonCode,
+    /// Values for the message's placeholders; never localized
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+impl Reason {
+    /// Create a reason without parameters
+    pub fn new(code: ReasonCode) -> Self {
+        Reason {
+            code,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Add a placeholder value
+    pub fn with_param(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.params.insert(name.into(), value.to_string());
+        self
+    }
+}
+
+/// Messages per locale from a `[messages]` section: locale -> code -> text
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MessageCatalogs(BTreeMap<String, BTreeMap<String, String>>);
+
+impl MessageCatalogs {
+    /// Parse the TOML body of a `[messages]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        let catalogs: Self = toml::from_str(input).map_err(|e| {
+            RUNEError::ParseError(format!("Failed to parse messages section: {}", e))
+        })?;
+        for (locale, messages) in &catalogs.0 {
+            let valid_tag = !locale.is_empty()
+                && locale.split('-').all(|part| {
+                    !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric())
+                });
+            if !valid_tag {
+                return Err(RUNEError::ConfigError(format!(
+                    "Invalid locale in messages section: {}",
+                    locale
+                )));
+            }
+            for code in messages.keys() {
+                code.parse::<ReasonCode>()?;
+            }
+        }
+        Ok(catalogs)
+    }
+
+    /// Add or replace one message
+    pub fn insert(&mut self, locale: &str, code: ReasonCode, message: impl Into<String>) {
+        self.0
+            .entry(locale.to_string())
+            .or_default()
+            .insert(code.as_str().to_string(), message.into());
+    }
+}
+
+/// Explanation rendered in one locale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderedExplanation {
+    /// Locale the text is in
+    pub locale: String,
+    /// Whole explanation, the descriptions joined with `; `
+    pub explanation: String,
+    /// Description of each reason, in the decision's order
+    pub descriptions: Vec<String>,
+}
+
+/// Renders reason codes from message catalogs
+#[derive(Debug, Clone)]
+pub struct ExplanationRenderer {
+    /// Lowercased locale -> code -> template
+    catalogs: BTreeMap<String, BTreeMap<ReasonCode, String>>,
+}
+
+impl Default for ExplanationRenderer {
+    fn default() -> Self {
+        Self::new(MessageCatalogs::default())
+    }
+}
+
+impl ExplanationRenderer {
+    /// Create a renderer from the built-in catalogs overlaid with `custom`
+    pub fn new(custom: MessageCatalogs) -> Self {
+        let mut catalogs: BTreeMap<String, BTreeMap<ReasonCode, String>> = BTreeMap::new();
+        for (locale, messages) in BUILTIN_CATALOGS {
+            let catalog = catalogs.entry(locale.to_string()).or_default();
+            for (code, message) in messages.iter() {
+                catalog.insert(*code, message.to_string());
+            }
+        }
+        for (locale, messages) in custom.0 {
+            let catalog = catalogs.entry(locale.to_ascii_lowercase()).or_default();
+            for (code, message) in messages {
+                // Codes were checked when the catalogs were parsed
+                if let Ok(code) = code.parse() {
+                    catalog.insert(code, message);
+                }
+            }
+        }
+        ExplanationRenderer { catalogs }
+    }
+
+    /// Locales with a catalog
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.catalogs.keys().map(String::as_str)
+    }
+
+    /// Best locale for an `Accept-Language` header value
+    pub fn negotiate(&self, accept_language: Option<&str>) -> &str {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let weight = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && weight > 0.0).then_some((tag, weight))
+            })
+            .collect();
+        // Stable, so equal weights keep the caller's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        for (tag, _) in ranges {
+            let tag = tag.to_ascii_lowercase();
+            let primary = tag.split('-').next().unwrap_or(&tag);
+            for candidate in [tag.as_str(), primary] {
+                if let Some((locale, _)) = self.catalogs.get_key_value(candidate) {
+                    return locale;
+                }
+            }
+        }
+        DEFAULT_LOCALE
+    }
+
+    /// Text for one reason in `locale`, falling back to English
+    pub fn describe(&self, locale: &str, reason: &Reason) -> String {
+        let template = self
+            .template(&locale.to_ascii_lowercase(), reason.code)
+            .or_else(|| self.template(DEFAULT_LOCALE, reason.code))
+            .unwrap_or(reason.code.as_str());
+        let mut text = template.to_string();
+        for (name, value) in &reason.params {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    /// Render a decision's reasons for an `Accept-Language` header value
+    ///
+    /// Decisions without reason codes keep their English explanation.
+    pub fn render(
+        &self,
+        result: &AuthorizationResult,
+        accept_language: Option<&str>,
+    ) -> RenderedExplanation {
+        let locale = self.negotiate(accept_language);
+        let descriptions: Vec<String> = result
+            .reason_codes
+            .iter()
+            .map(|reason| self.describe(locale, reason))
+            .collect();
+        let explanation = if descriptions.is_empty() {
+            result.explanation.clone()
+        } else {
+            descriptions.join("; ")
+        };
+        RenderedExplanation {
+            locale: locale.to_string(),
+            explanation,
+            descriptions,
+        }
+    }
+
+    fn template(&self, locale: &str, code: ReasonCode) -> Option<&str> {
+        self.catalogs.get(locale)?.get(&code).map(String::as_str)
+    }
+}
+
+/// Messages shipped with RUNE; English matches the engine's explanations
+const BUILTIN_CATALOGS: [(&str, [(ReasonCode, &str); 8]); 4] = [
+    (
+        "en",
+        [
+            (ReasonCode::Permitted, "Permitted by {rules} rules"),
+            (ReasonCode::NoMatchingPermit, "No matching permit rules"),
+            (ReasonCode::Forbidden, "Forbidden by {source} policy"),
+            (ReasonCode::DependencyFailure, "{class} handled as {mode}"),
+            (
+                ReasonCode::AttributeSource,
+                "attribute '{attribute}' taken from {source} ({strategy} of {candidates} sources)",
+            ),
+            (ReasonCode::Unconfigured, "No rules or policies are loaded"),
+            (
+                ReasonCode::Bootstrap,
+                "Permitted by the allow-all bootstrap override",
+            ),
+            (ReasonCode::AccessPending, "Access was requested (request {id}) and awaits review"),
+        ],
+    ),
+    (
+        "es",
+        [
+            (ReasonCode::Permitted, "Permitido por {rules} reglas"),
+            (
+                ReasonCode::NoMatchingPermit,
+                "Ninguna regla de permiso coincide",
+            ),
+            (ReasonCode::Forbidden, "Prohibido por una política de {source}"),
+            (ReasonCode::DependencyFailure, "{class} gestionado como {mode}"),
+            (
+                ReasonCode::AttributeSource,
+                "atributo '{attribute}' tomado de {source} ({strategy} de {candidates} fuentes)",
+            ),
+            (
+                ReasonCode::Unconfigured,
+                "No hay reglas ni políticas cargadas",
+            ),
+            (
+                ReasonCode::Bootstrap,
+                "Permitido por la excepción de arranque que lo permite todo",
+            ),
+            (ReasonCode::AccessPending, "Acceso solicitado (solicitud {id}), pendiente de revisión"),
+        ],
+    ),
+    (
+        "fr",
+        [
+            (ReasonCode::Permitted, "Autorisé par {rules} règles"),
+            (
+                ReasonCode::NoMatchingPermit,
+                "Aucune règle d'autorisation ne correspond",
+            ),
+            (ReasonCode::Forbidden, "Interdit par une politique {source}"),
+            (ReasonCode::DependencyFailure, "{class} traité comme {mode}"),
+            (
+                ReasonCode::AttributeSource,
+                "attribut '{attribute}' pris de {source} ({strategy} parmi {candidates} sources)",
+            ),
+            (
+                ReasonCode::Unconfigured,
+                "Aucune règle ni politique n'est chargée",
+            ),
+            (
+                ReasonCode::Bootstrap,
+                "Autorisé par le mode d'amorçage qui autorise tout",
+            ),
+            (ReasonCode::AccessPending, "Accès demandé (demande {id}), en attente de validation"),
+        ],
+    ),
+    (
+        "de",
+        [
+            (ReasonCode::Permitted, "Erlaubt durch {rules} Regeln"),
+            (ReasonCode::NoMatchingPermit, "Keine passende Erlaubnisregel"),
+            (ReasonCode::Forbidden, "Verboten durch eine {source}-Richtlinie"),
+            (ReasonCode::DependencyFailure, "{class} behandelt als {mode}"),
+            (
+                ReasonCode::AttributeSource,
+                "Attribut '{attribute}' übernommen von {source} ({strategy} aus {candidates} Quellen)",
+            ),
+            (
+                ReasonCode::Unconfigured,
+                "Es sind keine Regeln oder Richtlinien geladen",
+            ),
+            (
+                ReasonCode::Bootstrap,
+                "Erlaubt durch die Bootstrap-Ausnahme, die alles erlaubt",
+            ),
+            (ReasonCode::AccessPending, "Zugriff beantragt (Antrag {id}), Prüfung ausstehend"),
+        ],
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision::Decision;
+
+    fn result(reasons: Vec<Reason>) -> AuthorizationResult {
+        AuthorizationResult {
+            decision: Decision::Permit,
+            explanation: "Permitted by 2 rules".to_string(),
+            evaluated_rules: Vec::new(),
+            facts_used: Vec::new(),
+            policies_evaluated: 0,
+            evaluation_time_ns: 0,
+            cached: false,
+            coalesced: false,
+            failures: Vec::new(),
+            obligations: Vec::new(),
+            warnings: Vec::new(),
+            reason_codes: reasons,
+            valid_for_ms: 0,
+            proofs: Vec::new(),
+            near_misses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let renderer = ExplanationRenderer::default();
+        assert_eq!(renderer.negotiate(None), "en");
+        assert_eq!(renderer.negotiate(Some("fr-CH, fr;q=0.9, en;q=0.8")), "fr");
+        assert_eq!(renderer.negotiate(Some("ja, de;q=0.5, es;q=0.7")), "es");
+        assert_eq!(renderer.negotiate(Some("es;q=0, DE")), "de");
+        assert_eq!(renderer.negotiate(Some("ja, *;q=0.1")), "en");
+    }
+
+    #[test]
+    fn test_render_in_locale() {
+        let renderer = ExplanationRenderer::default();
+        let result = result(vec![
+            Reason::new(ReasonCode::DependencyFailure)
+                .with_param("class", "datalog_timeout")
+                .with_param("mode", "fail_open"),
+            Reason::new(ReasonCode::Permitted).with_param("rules", 2),
+        ]);
+
+        let english = renderer.render(&result, None);
+        assert_eq!(
+            english.explanation,
+            "datalog_timeout handled as fail_open; Permitted by 2 rules"
+        );
+
+        let german = renderer.render(&result, Some("de-AT"));
+        assert_eq!(german.locale, "de");
+        assert_eq!(
+            german.descriptions,
+            [
+                "datalog_timeout behandelt als fail_open",
+                "Erlaubt durch 2 Regeln"
+            ]
+        );
+
+        // Without codes the engine's explanation is kept
+        assert_eq!(
+            renderer
+                .render(&self::result(Vec::new()), Some("fr"))
+                .explanation,
+            "Permitted by 2 rules"
+        );
+    }
+
+    #[test]
+    fn test_custom_catalogs() {
+        let catalogs = MessageCatalogs::from_toml(
+            r#"
+[fr]
+no_matching_permit = "Accès refusé"
+
+[pt-BR]
+permitted = "Permitido por {rules} regras"
+"#,
+        )
+        .unwrap();
+        let renderer = ExplanationRenderer::new(catalogs);
+
+        let denied = Reason::new(ReasonCode::NoMatchingPermit);
+        assert_eq!(renderer.describe("fr", &denied), "Accès refusé");
+        let permitted = Reason::new(ReasonCode::Permitted).with_param("rules", 1);
+        assert_eq!(renderer.negotiate(Some("pt-BR")), "pt-br");
+        assert_eq!(
+            renderer.describe("pt-br", &permitted),
+            "Permitido por 1 regras"
+        );
+        // Messages a catalog lacks fall back to English
+        assert_eq!(
+            renderer.describe("pt-br", &denied),
+            "No matching permit rules"
+        );
+
+        assert!(MessageCatalogs::from_toml("[fr]\nmaybe = \"Peut-être\"").is_err());
+        assert!(MessageCatalogs::from_toml("[\"fr_FR\"]\npermitted = \"Oui\"").is_err());
+    }
+}
@@ -0,0 +1,452 @@
+//! Dumps of the active configuration
+//!
+//! An [`Export`] captures one engine generation: Datalog rules, Cedar
+//! policies, base facts from the fact store, fact scopes, cardinality
+//! limits and runtime flag overrides. It renders as
+//!
+//! - `json`: the whole dump as one document, for tooling;
+//! - `csv`: one `kind,key,value` row per item, for spreadsheets and diffs;
+//! - `rune`: a RUNE file that loads back into an engine. Base facts become
+//!   ground facts in `[rules]` and flag overrides are listed as comments,
+//!   since neither has a place of its own in the file format.
+//!
+//! Rules are written in source form, so a dump of a running server can be
+//! diffed against an export of the file it was deployed from. The rule
+//! parser has no string escapes, which means a constant that is not a plain
+//! string, integer or boolean, or a string holding quotes, commas,
+//! parentheses or semicolons, cannot be written as RUNE source; the `rune`
+//! format refuses such dumps rather than produce a file that loads
+//! differently, while `json` and `csv` carry facts as JSON.
+
+use crate::datalog::types::{Atom, Rule, Term};
+use crate::error::{RUNEError, Result};
+use crate::facts::Fact;
+use crate::limits::CardinalityLimits;
+use crate::migrate::FormatVersion;
+use crate::scopes::FactScopes;
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// Output format of an [`Export`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Single JSON document
+    #[default]
+    Json,
+    /// `kind,key,value` rows
+    Csv,
+    /// Re-loadable RUNE file
+    Rune,
+}
+
+impl ExportFormat {
+    /// Name used on the command line and in query strings
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Rune => "rune",
+        }
+    }
+
+    /// MIME type of the rendered dump
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Rune => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = RUNEError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "rune" => Ok(ExportFormat::Rune),
+            _ => Err(RUNEError::ConfigError(format!(
+                "Unknown export format '{}' (expected json, csv or rune)",
+                s
+            ))),
+        }
+    }
+}
+
+/// A Cedar policy as loaded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedPolicy {
+    /// `@id` annotation, or the Cedar policy ID without one
+    pub id: String,
+    /// Cedar source, annotations included
+    pub content: String,
+}
+
+/// Complete dump of one configuration generation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    /// Generation the dump was taken from; not part of the rendered output,
+    /// so dumps of identical configurations compare equal
+    #[serde(skip)]
+    pub generation: u64,
+    /// Datalog rules in source form, disabled ones included
+    pub rules: Vec<String>,
+    /// Cedar policies ordered by ID, disabled ones included
+    pub policies: Vec<ExportedPolicy>,
+    /// Base facts from the fact store, without duplicates
+    pub facts: Vec<Fact>,
+    /// Fact scopes for `@scope` rules
+    #[serde(default, skip_serializing_if = "FactScopes::is_empty")]
+    pub scopes: FactScopes,
+    /// Caps on derived predicates
+    #[serde(default, skip_serializing_if = "CardinalityLimits::is_empty")]
+    pub limits: CardinalityLimits,
+    /// Runtime flag overrides: flag key -> enabled
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, bool>,
+}
+
+impl Export {
+    /// Render the dump in the given format
+    pub fn render(&self, format: ExportFormat) -> Result<String> {
+        match format {
+            ExportFormat::Json => self.to_json(),
+            ExportFormat::Csv => self.to_csv(),
+            ExportFormat::Rune => self.to_rune(),
+        }
+    }
+
+    /// Pretty-printed JSON document
+    pub fn to_json(&self) -> Result<String> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// `kind,key,value` rows with a header line
+    ///
+    /// Kinds are `scope`, `limit`, `rule`, `policy`, `fact` and `override`.
+    /// Facts are keyed by predicate with their arguments as a JSON array;
+    /// scopes carry their settings as JSON.
+    pub fn to_csv(&self) -> Result<String> {
+        let mut out = String::from("kind,key,value\n");
+        let mut row = |kind: &str, key: &str, value: &str| {
+            out.push_str(&format!(
+                "{},{},{}\n",
+                csv_field(kind),
+                csv_field(key),
+                csv_field(value)
+            ));
+        };
+
+        for name in self.scopes.names() {
+            let scope = serde_json::to_string(&self.scopes.get(name))?;
+            row("scope", name, &scope);
+        }
+        for (predicate, limit) in self.limits.iter() {
+            row("limit", predicate, &limit.to_string());
+        }
+        for rule in &self.rules {
+            row("rule", "", rule);
+        }
+        for policy in &self.policies {
+            row("policy", &policy.id, policy.content.trim());
+        }
+        for fact in &self.facts {
+            row("fact", &fact.predicate, &serde_json::to_string(&fact.args)?);
+        }
+        for (key, enabled) in &self.overrides {
+            row("override", key, if *enabled { "true" } else { "false" });
+        }
+        Ok(out)
+    }
+
+    /// RUNE file that reproduces the dump when loaded
+    pub fn to_rune(&self) -> Result<String> {
+        let mut out = format!("version = \"{}\"\n", FormatVersion::LATEST);
+
+        if !self.overrides.is_empty() {
+            out.push_str("\n# Runtime flag overrides (not part of the file format):\n");
+            for (key, enabled) in &self.overrides {
+                let state = if *enabled { "enabled" } else { "disabled" };
+                let _ = writeln!(out, "#   {} = {}", key, state);
+            }
+        }
+
+        if !self.scopes.is_empty() {
+            let scopes = toml::to_string(&self.scopes)
+                .map_err(|e| RUNEError::ConfigError(format!("Failed to write scopes: {}", e)))?;
+            out.push_str("\n[scopes]\n");
+            out.push_str(&scopes);
+        }
+
+        if !self.limits.is_empty() {
+            let limits = toml::to_string(&self.limits)
+                .map_err(|e| RUNEError::ConfigError(format!("Failed to write limits: {}", e)))?;
+            out.push_str("\n[limits]\n");
+            out.push_str(&limits);
+        }
+
+        if !self.rules.is_empty() || !self.facts.is_empty() {
+            out.push_str("\n[rules]\n");
+            for rule in &self.rules {
+                out.push_str(rule);
+                out.push('\n');
+            }
+            if !self.facts.is_empty() {
+                out.push_str("\n# Base facts\n");
+                for fact in &self.facts {
+                    out.push_str(&fact_source(fact)?);
+                    out.push('\n');
+                }
+            }
+        }
+
+        if !self.policies.is_empty() {
+            out.push_str("\n[policies]\n");
+            for policy in &self.policies {
+                let content = policy.content.trim();
+                // rune/2.0 requires an explicit ID on every policy
+                if !content
+                    .lines()
+                    .any(|line| line.trim_start().starts_with("@id("))
+                {
+                    let _ = writeln!(out, "@id(\"{}\")", policy.id);
+                }
+                out.push_str(content);
+                out.push_str("\n\n");
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// Write a rule as RUNE source, annotations first
+///
+/// Fails if a constant or annotation cannot be written so that the parser
+/// reads it back unchanged.
+pub fn rule_source(rule: &Rule) -> Result<String> {
+    let mut out = String::new();
+    for (key, value) in &rule.annotations {
+        if !is_plain_string(value) {
+            return Err(unrepresentable(&format!("annotation @{}", key), value));
+        }
+        let _ = writeln!(out, "@{}(\"{}\")", key, value);
+    }
+
+    out.push_str(&atom_source(&rule.head)?);
+    if !rule.body.is_empty() {
+        out.push_str(" :- ");
+        let mut parts = rule
+            .body
+            .iter()
+            .map(atom_source)
+            .collect::<Result<Vec<_>>>()?;
+        for call in &rule.builtins {
+            let args = call
+                .args
+                .iter()
+                .map(term_source)
+                .collect::<Result<Vec<_>>>()?;
+            parts.push(format!("{}({})", call.name, args.join(", ")));
+        }
+        for guard in &rule.guards {
+            parts.push(format!(
+                "{} {} {}",
+                term_source(&guard.left)?,
+                guard.op,
+                term_source(&guard.right)?
+            ));
+        }
+        out.push_str(&parts.join(", "));
+    }
+    out.push('.');
+    Ok(out)
+}
+
+fn atom_source(atom: &Atom) -> Result<String> {
+    let terms = atom
+        .terms
+        .iter()
+        .map(term_source)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!(
+        "{}{}({})",
+        if atom.negated { "not " } else { "" },
+        atom.predicate,
+        terms.join(", ")
+    ))
+}
+
+fn term_source(term: &Term) -> Result<String> {
+    match term {
+        Term::Variable(name) => Ok(name.clone()),
+        Term::Constant(value) => value_source(value),
+    }
+}
+
+fn fact_source(fact: &Fact) -> Result<String> {
+    let args = fact
+        .args
+        .iter()
+        .map(value_source)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("{}({}).", fact.predicate, args.join(", ")))
+}
+
+fn value_source(value: &Value) -> Result<String> {
+    match value {
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::String(s) if is_plain_string(s) => Ok(format!("\"{}\"", s)),
+        Value::String(s) if !s.contains(char::is_control) => Ok(raw_source(s)),
+        other => Err(unrepresentable(
+            "value",
+            &serde_json::to_string(other).unwrap_or_default(),
+        )),
+    }
+}
+
+/// Whether the parser reads `"<s>"` back as exactly `s`
+fn is_plain_string(s: &str) -> bool {
+    s.trim() == s
+        && !s.contains(":-")
+        && !s
+            .chars()
+            .any(|c| matches!(c, '"' | '\'' | ',' | '(' | ')' | ';') || c.is_control())
+}
+
+/// `s` as a raw string, fenced with enough `#` that its quotes do not
+/// close it
+fn raw_source(s: &str) -> String {
+    let fence = (0..)
+        .map(|n| "#".repeat(n))
+        .find(|fence| !s.contains(&format!("\"{}", fence)))
+        .unwrap_or_default();
+    format!("r{0}\"{1}\"{0}", fence, s)
+}
+
+fn unrepresentable(what: &str, value: &str) -> RUNEError {
+    RUNEError::ConfigError(format!(
+        "Cannot write {} {} as RUNE source; export as json or csv instead",
+        what, value
+    ))
+}
+
+/// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_rules, parse_rune_file};
+
+    fn export() -> Export {
+        Export {
+            generation: 3,
+            rules: parse_rules(
+                "@id(\"admins\")\ncan(U, \"write\") :- admin(U), not suspended(U), U != \"root\".",
+            )
+            .unwrap()
+            .iter()
+            .map(|r| rule_source(r).unwrap())
+            .collect(),
+            policies: vec![ExportedPolicy {
+                id: "read".to_string(),
+                content: "permit(principal, action, resource);".to_string(),
+            }],
+            facts: vec![Fact::new(
+                "admin",
+                vec![Value::string("alice"), Value::Integer(2)],
+            )],
+            scopes: FactScopes::default(),
+            limits: CardinalityLimits::new([("can".to_string(), 100)].into()),
+            overrides: [("admins".to_string(), false)].into(),
+        }
+    }
+
+    #[test]
+    fn test_rule_source_round_trips() {
+        let source = "@id(\"a\")\n@scope(\"tenant\")\nreach(X, Y) :- edge(X, Z), not blocked(Z), path(Z, Y), X != Y.";
+        let rules = parse_rules(source).unwrap();
+        let written = rule_source(&rules[0]).unwrap();
+        assert_eq!(parse_rules(&written).unwrap(), rules);
+    }
+
+    #[test]
+    fn test_awkward_strings_written_raw() {
+        for value in ["a, b", " padded ", "say \"hi\"", "r\"#", "x :- y", "(it's)"] {
+            let fact = Fact::new("note", vec![Value::string(value)]);
+            let written = fact_source(&fact).unwrap();
+            let rules = parse_rules(&written).unwrap();
+            assert_eq!(
+                rules[0].head.terms[0],
+                Term::Constant(Value::string(value)),
+                "{}",
+                written
+            );
+        }
+        assert_eq!(raw_source("say \"hi\""), "r#\"say \"hi\"\"#");
+    }
+
+    #[test]
+    fn test_rune_output_loads() {
+        let rune = export().render(ExportFormat::Rune).unwrap();
+        assert!(rune.contains("#   admins = disabled"));
+
+        let config = parse_rune_file(&rune).unwrap();
+        assert_eq!(config.format, FormatVersion::LATEST);
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.policies[0].id, "read");
+        assert_eq!(config.rules[0].flag_key(), Some("admins"));
+        assert_eq!(config.limits.unwrap().get("can"), Some(100));
+    }
+
+    #[test]
+    fn test_unrepresentable_values() {
+        let mut dump = export();
+        dump.facts
+            .push(Fact::new("note", vec![Value::string("a\nb")]));
+        assert!(dump.to_rune().is_err());
+        // CSV and JSON carry it
+        assert!(dump.to_csv().unwrap().contains("\"[\"\"a\\nb\"\"]\""));
+        assert!(dump.to_json().unwrap().contains("a\\nb"));
+
+        dump.facts.pop();
+        dump.facts.push(Fact::new("note", vec![Value::Null]));
+        assert!(dump.to_rune().is_err());
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let csv = export().to_csv().unwrap();
+        assert!(
+            csv.starts_with("kind,key,value\nlimit,can,100\nrule,,\"@id(\"\"admins\"\")\ncan(U, ")
+        );
+        assert!(csv.ends_with(
+            "policy,read,\"permit(principal, action, resource);\"\n\
+             fact,admin,\"[\"\"alice\"\",2]\"\n\
+             override,admins,false\n"
+        ));
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Persistent fact vector behind the fact store
+//!
+//! [`FactVec`] is an append-friendly persistent vector: a 32-way trie of
+//! full leaves plus a tail holding the newest facts. Cloning one copies two
+//! pointers, and pushing onto a clone copies only the tail and the path from
+//! the root to the rightmost leaf, so adding a fact to a store of millions
+//! costs O(log n) instead of a copy of every fact. Versions share every node
+//! they have in common, which lets readers keep the vector they loaded while
+//! writers publish new ones.
+//!
+//! Removals are rare and rebuild the vector from an iterator.
+
+use crate::facts::Fact;
+use std::sync::Arc;
+
+/// Bits of an index consumed per trie level
+const BITS: u32 = 5;
+/// Children per branch and facts per leaf
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+/// Persistent vector of facts with cheap clones and appends
+#[derive(Clone)]
+pub struct FactVec {
+    len: usize,
+    /// Depth of the trie in bits; leaves sit at level 0
+    shift: u32,
+    root: Arc<Node>,
+    /// Newest facts, not yet in the trie; never more than `WIDTH`
+    tail: Arc<Vec<Fact>>,
+}
+
+#[derive(Clone)]
+enum Node {
+    Branch(Vec<Arc<Node>>),
+    Leaf(Vec<Fact>),
+}
+
+impl FactVec {
+    /// Create an empty vector
+    pub fn new() -> Self {
+        FactVec {
+            len: 0,
+            shift: BITS,
+            root: Arc::new(Node::Branch(Vec::new())),
+            tail: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Number of facts
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the vector holds no facts
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append a fact, copying only the nodes other versions still share
+    pub fn push(&mut self, fact: Fact) {
+        if self.tail.len() == WIDTH {
+            self.push_tail();
+        }
+        Arc::make_mut(&mut self.tail).push(fact);
+        self.len += 1;
+    }
+
+    /// Move the full tail into the trie as its rightmost leaf
+    fn push_tail(&mut self) {
+        let tail = std::mem::replace(&mut self.tail, Arc::new(Vec::with_capacity(WIDTH)));
+        let leaf = Node::Leaf(Arc::unwrap_or_clone(tail));
+        let offset = self.len - WIDTH;
+
+        if (offset >> BITS) >= 1 << self.shift {
+            // The trie is full: grow a level
+            let old = std::mem::replace(&mut self.root, Arc::new(Node::Branch(Vec::new())));
+            self.root = Arc::new(Node::Branch(vec![old, Arc::new(path(self.shift, leaf))]));
+            self.shift += BITS;
+        } else {
+            push_leaf(Arc::make_mut(&mut self.root), self.shift, offset, leaf);
+        }
+    }
+
+    /// Fact at `index`, if in bounds
+    pub fn get(&self, index: usize) -> Option<&Fact> {
+        if index >= self.len {
+            return None;
+        }
+        let offset = self.len - self.tail.len();
+        if index >= offset {
+            return self.tail.get(index - offset);
+        }
+
+        let mut node = &*self.root;
+        let mut level = self.shift;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = &children[(index >> level) & MASK];
+                    level -= BITS;
+                }
+                Node::Leaf(facts) => return facts.get(index & MASK),
+            }
+        }
+    }
+
+    /// Iterate over the facts in insertion order
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            stack: vec![std::slice::from_ref(&self.root).iter()],
+            leaf: [].iter(),
+            tail: Some(self.tail.as_slice()),
+            remaining: self.len,
+        }
+    }
+
+    /// Whether the vector holds a fact equal to `fact`
+    pub fn contains(&self, fact: &Fact) -> bool {
+        self.iter().any(|f| f == fact)
+    }
+
+    /// Copy the facts out into a contiguous vector
+    pub fn to_vec(&self) -> Vec<Fact> {
+        self.iter().cloned().collect()
+    }
+
+    /// Fact slots allocated, including spare capacity in the leaves
+    pub fn capacity(&self) -> usize {
+        fn leaf_capacity(node: &Node) -> usize {
+            match node {
+                Node::Branch(children) => children.iter().map(|c| leaf_capacity(c)).sum(),
+                Node::Leaf(facts) => facts.capacity(),
+            }
+        }
+        leaf_capacity(&self.root) + self.tail.capacity()
+    }
+
+    /// Whether two vectors are the same version, without comparing facts
+    pub fn ptr_eq(a: &FactVec, b: &FactVec) -> bool {
+        a.len == b.len && Arc::ptr_eq(&a.root, &b.root) && Arc::ptr_eq(&a.tail, &b.tail)
+    }
+
+    /// Whether no other clone shares this vector's root and tail
+    #[cfg(test)]
+    pub(crate) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.root) == 1 && Arc::strong_count(&self.tail) == 1
+    }
+}
+
+/// Chain of single-child branches from `level` down to `leaf`
+fn path(level: u32, leaf: Node) -> Node {
+    if level == 0 {
+        leaf
+    } else {
+        Node::Branch(vec![Arc::new(path(level - BITS, leaf))])
+    }
+}
+
+/// Insert `leaf` as the leaf for `index` under `node`, which sits at `level`
+fn push_leaf(node: &mut Node, level: u32, index: usize, leaf: Node) {
+    let Node::Branch(children) = node else {
+        unreachable!("leaves only sit at level 0");
+    };
+    let slot = (index >> level) & MASK;
+    if level == BITS {
+        children.push(Arc::new(leaf));
+    } else if slot < children.len() {
+        push_leaf(
+            Arc::make_mut(&mut children[slot]),
+            level - BITS,
+            index,
+            leaf,
+        );
+    } else {
+        children.push(Arc::new(path(level - BITS, leaf)));
+    }
+}
+
+impl Default for FactVec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for FactVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl From<Vec<Fact>> for FactVec {
+    fn from(facts: Vec<Fact>) -> Self {
+        facts.into_iter().collect()
+    }
+}
+
+impl FromIterator<Fact> for FactVec {
+    fn from_iter<I: IntoIterator<Item = Fact>>(iter: I) -> Self {
+        let mut facts = FactVec::new();
+        for fact in iter {
+            facts.push(fact);
+        }
+        facts
+    }
+}
+
+impl Extend<Fact> for FactVec {
+    fn extend<I: IntoIterator<Item = Fact>>(&mut self, iter: I) {
+        for fact in iter {
+            self.push(fact);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a FactVec {
+    type Item = &'a Fact;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over a [`FactVec`], in insertion order
+pub struct Iter<'a> {
+    /// Unvisited children of each branch on the path to the current leaf
+    stack: Vec<std::slice::Iter<'a, Arc<Node>>>,
+    leaf: std::slice::Iter<'a, Fact>,
+    tail: Option<&'a [Fact]>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Fact;
+
+    fn next(&mut self) -> Option<&'a Fact> {
+        loop {
+            if let Some(fact) = self.leaf.next() {
+                self.remaining -= 1;
+                return Some(fact);
+            }
+            if let Some(children) = self.stack.last_mut() {
+                match children.next().map(|node| &**node) {
+                    Some(Node::Branch(grandchildren)) => self.stack.push(grandchildren.iter()),
+                    Some(Node::Leaf(facts)) => self.leaf = facts.iter(),
+                    None => {
+                        self.stack.pop();
+                    }
+                }
+                continue;
+            }
+            self.leaf = self.tail.take()?.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn fact(i: usize) -> Fact {
+        Fact::unary("n", Value::Integer(i as i64))
+    }
+
+    #[test]
+    fn test_push_get_and_iterate_across_levels() {
+        // Three levels of trie plus a partial tail
+        let count = WIDTH * WIDTH * 2 + WIDTH + 7;
+        let facts: FactVec = (0..count).map(fact).collect();
+        assert_eq!(facts.len(), count);
+        assert_eq!(facts.iter().len(), count);
+        assert!(facts.iter().zip(0..).all(|(f, i)| *f == fact(i)));
+        for i in [0, WIDTH - 1, WIDTH, WIDTH * WIDTH, count - 1] {
+            assert_eq!(facts.get(i), Some(&fact(i)));
+        }
+        assert_eq!(facts.get(count), None);
+        assert!(facts.contains(&fact(WIDTH * WIDTH + 3)));
+    }
+
+    #[test]
+    fn test_versions_share_nothing_they_change() {
+        let mut old: FactVec = (0..100).map(fact).collect();
+        let mut new = old.clone();
+        assert!(FactVec::ptr_eq(&old, &new));
+
+        new.push(fact(100));
+        assert!(!FactVec::ptr_eq(&old, &new));
+        assert_eq!(old.len(), 100);
+        assert_eq!(new.to_vec(), (0..101).map(fact).collect::<Vec<_>>());
+
+        old.push(fact(7));
+        assert_eq!(old.get(100), Some(&fact(7)));
+        assert_eq!(new.get(100), Some(&fact(100)));
+    }
+}
@@ -1,13 +1,21 @@
 //! Lock-free fact store for high-performance concurrent access
 
-#![allow(unsafe_code)] // Required for crossbeam epoch-based memory reclamation
-
+use crate::cache_ttl::ChangeRates;
+use crate::epoch_cell::EpochCell;
+use crate::error::{RUNEError, Result};
+use crate::fact_vec::FactVec;
+use crate::history::{
+    self, ChangeOrigin, FactEvent, FactHistory, FactOp, HistoryQuery, HistoryState,
+};
+use crate::labels::Labels;
+use crate::replica::{ChangeBatch, ChangeLog, ChangePosition, FactChange};
 use crate::types::Value;
-use crossbeam::epoch::{self, Atomic, Owned};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
 /// A fact in the system
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,11 +113,129 @@ pub enum PatternArg {
 /// Lock-free fact store using crossbeam epoch-based memory reclamation
 pub struct FactStore {
     /// Facts indexed by predicate
-    facts_by_predicate: DashMap<Arc<str>, Arc<Vec<Fact>>>,
+    facts_by_predicate: DashMap<Arc<str>, FactVec>,
     /// All facts (for full scans)
-    all_facts: Atomic<Arc<Vec<Fact>>>,
+    all_facts: EpochCell<FactVec>,
     /// Version counter for change detection
     version: AtomicU64,
+    /// Deadlines for facts added with a time-to-live
+    expirations: DashMap<Fact, Instant>,
+    /// When the store was created; deadlines are counted from it
+    epoch: Instant,
+    /// Earliest deadline in nanoseconds since `epoch`, `u64::MAX` for none,
+    /// so readers can check for expired facts without scanning
+    next_deadline: AtomicU64,
+    /// Governance labels of facts added with them
+    labels: DashMap<Fact, Labels>,
+    /// Recent changes for replicas, once enabled
+    change_log: OnceLock<ChangeLog>,
+    /// Attributed changes for audits, once enabled
+    history: OnceLock<FactHistory>,
+    /// How often the entities facts mention change, once enabled
+    change_rates: OnceLock<ChangeRates>,
+}
+
+/// Outcome of a [`FactStore::compact`] pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionStats {
+    /// Stored entries before compaction
+    pub facts_before: usize,
+    /// Stored entries after compaction
+    pub facts_after: usize,
+    /// Logically equal copies that were merged
+    pub duplicates_removed: usize,
+    /// Facts dropped because their time-to-live had passed
+    pub expired_removed: usize,
+    /// Predicate index entries left empty by retractions
+    pub predicates_removed: usize,
+    /// Spare vector capacity released, in facts
+    pub capacity_reclaimed: usize,
+}
+
+/// Version of the format [`FactStore::export_snapshot`] writes
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Leading bytes of a binary snapshot; JSON snapshots start with `{`
+const SNAPSHOT_MAGIC: &[u8; 8] = b"RUNEFACT";
+
+/// Encoding of a fact snapshot
+///
+/// Both carry the same document: the format version, when the snapshot was
+/// taken, and every fact with its labels and, for facts added with a
+/// time-to-live, the wall-clock time it runs out. `binary` is that document
+/// as CBOR after an 8-byte `RUNEFACT` marker, compact and quick to load;
+/// `json` is readable and diffable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    /// CBOR after a `RUNEFACT` marker
+    #[default]
+    Binary,
+    /// Single JSON document
+    Json,
+}
+
+impl SnapshotFormat {
+    /// Name used on the command line and in query strings
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SnapshotFormat::Binary => "binary",
+            SnapshotFormat::Json => "json",
+        }
+    }
+
+    /// MIME type of an encoded snapshot
+    pub fn content_type(self) -> &'static str {
+        match self {
+            SnapshotFormat::Binary => "application/octet-stream",
+            SnapshotFormat::Json => "application/json",
+        }
+    }
+
+    /// Format of an encoded snapshot, told by its first bytes
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(SNAPSHOT_MAGIC) {
+            SnapshotFormat::Binary
+        } else {
+            SnapshotFormat::Json
+        }
+    }
+}
+
+impl std::str::FromStr for SnapshotFormat {
+    type Err = RUNEError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "binary" => Ok(SnapshotFormat::Binary),
+            "json" => Ok(SnapshotFormat::Json),
+            _ => Err(RUNEError::InvalidRequest(format!(
+                "Unknown snapshot format '{}' (expected binary or json)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Contents of an encoded snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotDocument {
+    format_version: u32,
+    /// When the snapshot was taken, in milliseconds since the Unix epoch
+    taken_at_ms: u64,
+    facts: Vec<SnapshotEntry>,
+}
+
+/// A fact in a snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEntry {
+    fact: Fact,
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    labels: Labels,
+    /// When the fact's time-to-live runs out, in milliseconds since the
+    /// Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    valid_until_ms: Option<u64>,
 }
 
 impl FactStore {
@@ -117,69 +243,392 @@ impl FactStore {
     pub fn new() -> Self {
         FactStore {
             facts_by_predicate: DashMap::new(),
-            all_facts: Atomic::new(Arc::new(Vec::new())),
+            all_facts: EpochCell::new(FactVec::new()),
             version: AtomicU64::new(0),
+            expirations: DashMap::new(),
+            epoch: Instant::now(),
+            next_deadline: AtomicU64::new(u64::MAX),
+            labels: DashMap::new(),
+            change_log: OnceLock::new(),
+            history: OnceLock::new(),
+            change_rates: OnceLock::new(),
+        }
+    }
+
+    /// Create a read-only store from a snapshot, sharing its fact vector
+    pub fn from_snapshot(snapshot: &FactSnapshot) -> Self {
+        Self::indexed(snapshot.facts.clone(), snapshot.version)
+    }
+
+    /// Create a store holding `facts`, indexed in one pass
+    pub fn from_facts(facts: Vec<Fact>) -> Self {
+        Self::indexed(FactVec::from(facts), 0)
+    }
+
+    fn indexed(facts: FactVec, version: u64) -> Self {
+        let mut by_predicate: std::collections::HashMap<Arc<str>, FactVec> =
+            std::collections::HashMap::new();
+        for fact in facts.iter() {
+            by_predicate
+                .entry(fact.predicate.clone())
+                .or_default()
+                .push(fact.clone());
+        }
+
+        let facts_by_predicate = DashMap::with_capacity(by_predicate.len());
+        for (predicate, facts) in by_predicate {
+            facts_by_predicate.insert(predicate, facts);
+        }
+
+        FactStore {
+            facts_by_predicate,
+            all_facts: EpochCell::new(facts),
+            version: AtomicU64::new(version),
+            expirations: DashMap::new(),
+            epoch: Instant::now(),
+            next_deadline: AtomicU64::new(u64::MAX),
+            labels: DashMap::new(),
+            change_log: OnceLock::new(),
+            history: OnceLock::new(),
+            change_rates: OnceLock::new(),
+        }
+    }
+
+    /// Changes made on behalf of `origin`, as the history records them
+    pub fn attributed(&self, origin: ChangeOrigin) -> FactWriter<'_> {
+        FactWriter {
+            store: self,
+            origin,
         }
     }
 
     /// Add a fact to the store
+    ///
+    /// Re-adding a fact that was added with a time-to-live makes it permanent.
     pub fn add_fact(&self, fact: Fact) {
+        self.insert(fact, None, None);
+    }
+
+    /// Add a fact, expiring after `ttl` if given, on behalf of `origin`
+    fn insert(&self, fact: Fact, ttl: Option<Duration>, origin: Option<&ChangeOrigin>) {
+        let mut log = self.change_log.get().map(ChangeLog::lock);
+        let mut history = self.history.get().map(FactHistory::lock);
+        if let Some(log) = &mut log {
+            log.push(FactChange::Add(fact.clone()));
+        }
+        if let Some(history) = &mut history {
+            let valid_until = ttl.map(|ttl| history::now_ms() + ttl.as_millis() as u64);
+            history.push(FactOp::Add, Some(&fact), valid_until, origin);
+        }
+
+        if !self.expirations.is_empty() {
+            self.expirations.remove(&fact);
+        }
+        if let Some(ttl) = ttl {
+            let deadline = Instant::now() + ttl;
+            self.expirations.insert(fact.clone(), deadline);
+            self.next_deadline
+                .fetch_min(self.nanos(deadline), Ordering::AcqRel);
+        }
+        self.record_change(&fact);
+
         // Update predicate index
         self.facts_by_predicate
             .entry(fact.predicate.clone())
-            .and_modify(|facts| {
-                let mut new_facts = (**facts).clone();
-                new_facts.push(fact.clone());
-                *facts = Arc::new(new_facts);
-            })
-            .or_insert_with(|| Arc::new(vec![fact.clone()]));
+            .or_default()
+            .push(fact.clone());
+
+        // Cloning shares the current version; the push copies one path
+        self.update_all_facts(|facts| {
+            let mut facts = facts.clone();
+            facts.push(fact.clone());
+            Some(facts)
+        });
+        self.version.fetch_add(1, Ordering::Release);
+    }
 
-        // Update all facts using epoch-based reclamation with CAS loop
-        let guard = &epoch::pin();
+    /// Add multiple facts atomically (see
+    /// [`add_facts_bulk`](FactStore::add_facts_bulk))
+    pub fn add_facts(&self, facts: Vec<Fact>) {
+        self.add_facts_bulk(facts);
+    }
+
+    /// Add every fact of `facts` with a single version bump
+    ///
+    /// Equivalent to [`add_fact`](FactStore::add_fact) on each in turn, but
+    /// the predicate index and fact vector are each updated once, so readers
+    /// see either none of the facts or all of them and large loads avoid a
+    /// publish per fact.
+    pub fn add_facts_bulk(&self, facts: Vec<Fact>) {
+        self.insert_bulk(facts, None);
+    }
+
+    /// Add `facts` in one update on behalf of `origin`
+    fn insert_bulk(&self, facts: Vec<Fact>, origin: Option<&ChangeOrigin>) {
+        if facts.is_empty() {
+            return;
+        }
+        let mut log = self.change_log.get().map(ChangeLog::lock);
+        let mut history = self.history.get().map(FactHistory::lock);
+        for fact in &facts {
+            if let Some(log) = &mut log {
+                log.push(FactChange::Add(fact.clone()));
+            }
+            if let Some(history) = &mut history {
+                history.push(FactOp::Add, Some(fact), None, origin);
+            }
+            if !self.expirations.is_empty() {
+                self.expirations.remove(fact);
+            }
+            self.record_change(fact);
+        }
 
-        loop {
-            let current = self.all_facts.load(Ordering::Acquire, guard);
+        let mut by_predicate: std::collections::HashMap<Arc<str>, Vec<Fact>> =
+            std::collections::HashMap::new();
+        for fact in &facts {
+            by_predicate
+                .entry(fact.predicate.clone())
+                .or_default()
+                .push(fact.clone());
+        }
+        for (predicate, added) in by_predicate {
+            self.facts_by_predicate
+                .entry(predicate)
+                .or_default()
+                .extend(added);
+        }
 
-            let mut new_facts = if let Some(current_ref) = unsafe { current.as_ref() } {
-                (**current_ref).clone()
+        self.update_all_facts(|current| {
+            let mut current = current.clone();
+            current.extend(facts.iter().cloned());
+            Some(current)
+        });
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Add a fact that expires after `ttl`
+    ///
+    /// The fact is removed by the first [`expire`] or [`compact`] pass after
+    /// its deadline. The engine runs [`expire`] before every decision that
+    /// finds one due, so expired facts are never evaluated; long-running
+    /// hosts also sweep in the background so the store does not hold on to
+    /// them between decisions.
+    ///
+    /// [`expire`]: FactStore::expire
+    /// [`compact`]: FactStore::compact
+    pub fn add_fact_with_ttl(&self, fact: Fact, ttl: Duration) {
+        self.insert(fact, Some(ttl), None);
+    }
+
+    /// Earliest deadline of a fact added with a time-to-live, if any
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.expirations.iter().map(|entry| *entry.value()).min()
+    }
+
+    /// Whether a fact may have expired by `now`, without scanning
+    pub fn expiry_due(&self, now: Instant) -> bool {
+        let deadline = self.next_deadline.load(Ordering::Acquire);
+        deadline != u64::MAX && self.nanos(now) >= deadline
+    }
+
+    /// Remove every fact whose time-to-live has passed, returning how many
+    ///
+    /// Unlike [`compact`](FactStore::compact), only the expired facts'
+    /// entries are touched, so this is cheap enough to run on every
+    /// deadline. Removals are recorded as retractions by the `expiry`
+    /// system origin.
+    pub fn expire(&self) -> usize {
+        let mut log = self.change_log.get().map(ChangeLog::lock);
+        let mut history = self.history.get().map(FactHistory::lock);
+        let expired = self.take_expired(Instant::now());
+        if expired.is_empty() {
+            return 0;
+        }
+
+        self.update_all_facts(|facts| {
+            Some(
+                facts
+                    .iter()
+                    .filter(|f| !expired.contains(*f))
+                    .cloned()
+                    .collect(),
+            )
+        });
+        let predicates: HashSet<&Arc<str>> = expired.iter().map(|f| &f.predicate).collect();
+        for predicate in predicates {
+            if let Some(mut facts) = self.facts_by_predicate.get_mut(predicate) {
+                *facts = facts
+                    .iter()
+                    .filter(|f| !expired.contains(*f))
+                    .cloned()
+                    .collect();
+            }
+        }
+        self.version.fetch_add(1, Ordering::Release);
+        for fact in &expired {
+            self.record_change(fact);
+        }
+
+        let count = expired.len();
+        if let Some(history) = &mut history {
+            record_expired(history, &expired);
+        }
+        if let Some(log) = &mut log {
+            for fact in expired {
+                log.push(FactChange::Retract(fact));
+            }
+        }
+        count
+    }
+
+    /// Forget the deadlines that passed by `now`, returning their facts
+    fn take_expired(&self, now: Instant) -> HashSet<Fact> {
+        // Reset before scanning, so a deadline added meanwhile lowers the
+        // new minimum rather than being overwritten by it
+        self.next_deadline.store(u64::MAX, Ordering::Release);
+        let mut expired = HashSet::new();
+        self.expirations.retain(|fact, deadline| {
+            let live = *deadline > now;
+            if live {
+                self.next_deadline
+                    .fetch_min(self.nanos(*deadline), Ordering::AcqRel);
             } else {
-                Vec::new()
-            };
+                expired.insert(fact.clone());
+            }
+            live
+        });
+        for fact in &expired {
+            self.labels.remove(fact);
+        }
+        expired
+    }
 
-            new_facts.push(fact.clone());
-            let new_arc = Arc::new(new_facts);
-            let new_shared = Owned::new(new_arc).into_shared(guard);
-
-            // Try to swap - if it fails, someone else updated, retry
-            match self.all_facts.compare_exchange(
-                current,
-                new_shared,
-                Ordering::Release,
-                Ordering::Acquire,
-                guard,
-            ) {
-                Ok(_) => {
-                    // Success! Increment version and clean up
-                    self.version.fetch_add(1, Ordering::Release);
-                    unsafe {
-                        guard.defer_destroy(current);
-                    }
-                    break;
-                }
-                Err(_) => {
-                    // CAS failed, retry the loop
-                    // The new_shared we created will be dropped
-                    continue;
-                }
+    fn nanos(&self, instant: Instant) -> u64 {
+        let nanos = instant.saturating_duration_since(self.epoch).as_nanos();
+        u64::try_from(nanos).unwrap_or(u64::MAX - 1)
+    }
+
+    /// Add a fact carrying governance labels, replacing any it had
+    pub fn add_fact_with_labels(&self, fact: Fact, labels: Labels) {
+        self.insert_with_labels(fact, labels, None);
+    }
+
+    fn insert_with_labels(&self, fact: Fact, labels: Labels, origin: Option<&ChangeOrigin>) {
+        self.insert(fact.clone(), None, origin);
+        if labels.is_empty() {
+            self.labels.remove(&fact);
+        } else {
+            self.labels.insert(fact, labels);
+        }
+    }
+
+    /// Labels of a fact, if it was added with any
+    pub fn fact_labels(&self, fact: &Fact) -> Option<Labels> {
+        self.labels.get(fact).map(|labels| labels.clone())
+    }
+
+    /// Every labelled fact with its labels, in no particular order
+    pub fn labeled_facts(&self) -> Vec<(Fact, Labels)> {
+        self.labels
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Remove every copy of a fact, returning whether it was present
+    pub fn retract_fact(&self, fact: &Fact) -> bool {
+        self.remove(fact, None)
+    }
+
+    /// Remove every copy of a fact on behalf of `origin`
+    fn remove(&self, fact: &Fact, origin: Option<&ChangeOrigin>) -> bool {
+        let mut log = self.change_log.get().map(ChangeLog::lock);
+        let mut history = self.history.get().map(FactHistory::lock);
+        self.expirations.remove(fact);
+        self.labels.remove(fact);
+
+        if let Some(mut facts) = self.facts_by_predicate.get_mut(&fact.predicate) {
+            if facts.contains(fact) {
+                // The emptied entry is left for compaction to drop
+                *facts = facts.iter().filter(|f| *f != fact).cloned().collect();
+            }
+        }
+
+        let removed = self.update_all_facts(|facts| {
+            facts
+                .contains(fact)
+                .then(|| facts.iter().filter(|f| *f != fact).cloned().collect())
+        });
+        if removed {
+            self.version.fetch_add(1, Ordering::Release);
+            self.record_change(fact);
+            if let Some(log) = &mut log {
+                log.push(FactChange::Retract(fact.clone()));
+            }
+            if let Some(history) = &mut history {
+                history.push(FactOp::Retract, Some(fact), None, origin);
             }
         }
+        removed
     }
 
-    /// Add multiple facts atomically
-    pub fn add_facts(&self, facts: Vec<Fact>) {
-        for fact in facts {
-            self.add_fact(fact);
+    /// Rewrite the store: merge duplicates, drop expired facts and empty
+    /// predicate entries, and release spare capacity
+    ///
+    /// Compaction runs concurrently with readers and writers. Duplicates keep
+    /// their newest copy, so the surviving timestamps are the latest ones.
+    /// The version only changes when expired facts were dropped, since
+    /// merging duplicates leaves the logical fact set as it was.
+    pub fn compact(&self) -> CompactionStats {
+        let mut log = self.change_log.get().map(ChangeLog::lock);
+        let mut history = self.history.get().map(FactHistory::lock);
+        let expired = self.take_expired(Instant::now());
+
+        let mut stats = CompactionStats::default();
+        self.update_all_facts(|facts| {
+            let (compacted, duplicates, dropped) = compact_facts(facts, &expired);
+            stats = CompactionStats {
+                facts_before: facts.len(),
+                facts_after: compacted.len(),
+                duplicates_removed: duplicates,
+                expired_removed: dropped,
+                capacity_reclaimed: facts.capacity().saturating_sub(compacted.capacity()),
+                ..CompactionStats::default()
+            };
+            Some(compacted)
+        });
+
+        for mut entry in self.facts_by_predicate.iter_mut() {
+            let (compacted, _, _) = compact_facts(entry.value(), &expired);
+            stats.capacity_reclaimed += entry
+                .value()
+                .capacity()
+                .saturating_sub(compacted.capacity());
+            *entry.value_mut() = compacted;
+        }
+        let predicates = self.facts_by_predicate.len();
+        self.facts_by_predicate.retain(|_, facts| !facts.is_empty());
+        stats.predicates_removed = predicates - self.facts_by_predicate.len();
+
+        if stats.expired_removed > 0 {
+            self.version.fetch_add(1, Ordering::Release);
         }
+        if let Some(history) = &mut history {
+            record_expired(history, &expired);
+        }
+        if let Some(log) = &mut log {
+            for fact in expired {
+                log.push(FactChange::Retract(fact));
+            }
+        }
+        stats
+    }
+
+    /// Replace the fact vector with `update(current)`, retrying on contention
+    ///
+    /// Returns false, leaving the store untouched, when `update` returns None.
+    fn update_all_facts(&self, update: impl FnMut(&FactVec) -> Option<FactVec>) -> bool {
+        self.all_facts.update(update)
     }
 
     /// Query facts matching a pattern
@@ -200,20 +649,20 @@ impl FactStore {
     pub fn get_by_predicate(&self, predicate: &str) -> Vec<Fact> {
         self.facts_by_predicate
             .get(predicate)
-            .map(|facts| (**facts).clone())
+            .map(|facts| facts.to_vec())
             .unwrap_or_default()
     }
 
-    /// Get all facts
-    pub fn all_facts(&self) -> Arc<Vec<Fact>> {
-        let guard = &epoch::pin();
-        let shared = self.all_facts.load(Ordering::Acquire, guard);
+    /// Check whether the store holds `fact`
+    pub fn contains(&self, fact: &Fact) -> bool {
+        self.facts_by_predicate
+            .get(fact.predicate.as_ref())
+            .is_some_and(|facts| facts.contains(fact))
+    }
 
-        if let Some(facts_ref) = unsafe { shared.as_ref() } {
-            facts_ref.clone()
-        } else {
-            Arc::new(Vec::new())
-        }
+    /// Get all facts
+    pub fn all_facts(&self) -> FactVec {
+        self.all_facts.load()
     }
 
     /// Get current version
@@ -228,22 +677,241 @@ impl FactStore {
 
     /// Clear all facts
     pub fn clear(&self) {
-        self.facts_by_predicate.clear();
+        self.clear_by(None);
+    }
 
-        let guard = &epoch::pin();
-        let current = self.all_facts.load(Ordering::Acquire, guard);
-        self.all_facts.store(
-            Owned::new(Arc::new(Vec::new())).into_shared(guard),
-            Ordering::Release,
-        );
+    fn clear_by(&self, origin: Option<&ChangeOrigin>) {
+        let log = self.change_log.get().map(ChangeLog::lock);
+        self.replace(Vec::new(), origin);
+        if let Some(mut log) = log {
+            log.push(FactChange::Clear);
+        }
+    }
 
-        unsafe {
-            guard.defer_destroy(current);
+    /// Replace every fact with `facts`, indexed in one pass, on behalf of
+    /// `origin`
+    pub(crate) fn replace(&self, facts: Vec<Fact>, origin: Option<&ChangeOrigin>) {
+        let mut history = self.history.get().map(FactHistory::lock);
+        if let Some(history) = &mut history {
+            history.push(FactOp::Clear, None, None, origin);
+            for fact in &facts {
+                history.push(FactOp::Add, Some(fact), None, origin);
+            }
         }
 
+        let mut by_predicate: std::collections::HashMap<Arc<str>, FactVec> =
+            std::collections::HashMap::new();
+        for fact in &facts {
+            by_predicate
+                .entry(fact.predicate.clone())
+                .or_default()
+                .push(fact.clone());
+        }
+
+        self.facts_by_predicate.clear();
+        for (predicate, facts) in by_predicate {
+            self.facts_by_predicate.insert(predicate, facts);
+        }
+        self.expirations.clear();
+        self.next_deadline.store(u64::MAX, Ordering::Release);
+        self.labels.clear();
+
+        self.all_facts.replace(FactVec::from(facts));
         self.version.fetch_add(1, Ordering::Release);
     }
 
+    /// Record changes so replicas can follow this store, keeping the last
+    /// `capacity`; later calls have no effect
+    pub fn enable_change_log(&self, capacity: usize) {
+        self.change_log
+            .get_or_init(|| ChangeLog::new(capacity.max(1)));
+    }
+
+    /// Up to `limit` changes after `since`, or the whole fact set when
+    /// `since` is `None` or no longer in the log; `None` without a change log
+    pub fn changes_since(
+        &self,
+        since: Option<ChangePosition>,
+        limit: usize,
+    ) -> Option<ChangeBatch> {
+        let log = self.change_log.get()?;
+        let state = log.lock();
+        Some(log.read(&state, since, limit, || self.all_facts().to_vec()))
+    }
+
+    /// Every change after `since`, as [`changes_since`](Self::changes_since)
+    /// reads them, with the store version they bring a reader to; enables
+    /// the change log with `capacity` if it was not
+    pub(crate) fn changes_with_version(
+        &self,
+        since: Option<ChangePosition>,
+        capacity: usize,
+    ) -> (ChangeBatch, u64) {
+        let log = self
+            .change_log
+            .get_or_init(|| ChangeLog::new(capacity.max(1)));
+        // Writers bump the version under the log lock, so it matches the batch
+        let state = log.lock();
+        let batch = log.read(&state, since, usize::MAX, || self.all_facts().to_vec());
+        (batch, self.version())
+    }
+
+    /// Replace every fact with `facts` on behalf of `origin`, recorded as a
+    /// clear followed by the additions
+    pub(crate) fn reset(&self, facts: Vec<Fact>, origin: Option<&ChangeOrigin>) {
+        let log = self.change_log.get().map(ChangeLog::lock);
+        self.replace(facts.clone(), origin);
+        if let Some(mut log) = log {
+            log.push(FactChange::Clear);
+            for fact in facts {
+                log.push(FactChange::Add(fact));
+            }
+        }
+    }
+
+    /// Record attributed changes for audits, keeping the last `capacity`;
+    /// later calls have no effect
+    pub fn enable_history(&self, capacity: usize) {
+        self.history.get_or_init(|| {
+            let now = Instant::now();
+            let facts = self.all_facts();
+            let facts = facts.iter().map(|fact| {
+                let valid_until = self.expirations.get(fact).map(|deadline| {
+                    history::now_ms() + deadline.saturating_duration_since(now).as_millis() as u64
+                });
+                (fact.clone(), valid_until)
+            });
+            FactHistory::new(capacity.max(1), facts)
+        });
+    }
+
+    /// Recorded changes matching `query`, oldest first; `None` without a
+    /// history
+    pub fn history(&self, query: &HistoryQuery) -> Option<Vec<FactEvent>> {
+        Some(self.history.get()?.events(query))
+    }
+
+    /// Facts as of `at_ms` milliseconds since the Unix epoch, rebuilt from
+    /// the history; `None` without one
+    pub fn as_of(&self, at_ms: u64) -> Option<Result<Vec<Fact>>> {
+        Some(self.history.get()?.as_of(at_ms))
+    }
+
+    /// Encode every fact, with its labels and time-to-live, as a snapshot
+    /// that [`import_snapshot`](FactStore::import_snapshot) loads back
+    ///
+    /// Deadlines are written as wall-clock times, so a fact still expires
+    /// on schedule when the snapshot is loaded by another process later.
+    pub fn export_snapshot(&self, format: SnapshotFormat) -> Result<Vec<u8>> {
+        let now = Instant::now();
+        let now_ms = history::now_ms();
+        let facts = self.all_facts();
+        let mut seen = HashSet::with_capacity(facts.len());
+        let document = SnapshotDocument {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            taken_at_ms: now_ms,
+            facts: facts
+                .iter()
+                .filter(|fact| seen.insert(*fact))
+                .map(|fact| SnapshotEntry {
+                    fact: fact.clone(),
+                    labels: self.fact_labels(fact).unwrap_or_default(),
+                    valid_until_ms: self.expirations.get(fact).map(|deadline| {
+                        now_ms + deadline.saturating_duration_since(now).as_millis() as u64
+                    }),
+                })
+                .collect(),
+        };
+
+        match format {
+            SnapshotFormat::Json => Ok(serde_json::to_vec(&document)?),
+            SnapshotFormat::Binary => {
+                let mut bytes = SNAPSHOT_MAGIC.to_vec();
+                ciborium::into_writer(&document, &mut bytes).map_err(|e| {
+                    RUNEError::InvalidRequest(format!("Failed to encode snapshot: {}", e))
+                })?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Replace every fact with those of a snapshot in either format,
+    /// returning how many were loaded
+    ///
+    /// Facts whose time-to-live ran out since the snapshot was taken are
+    /// left out. A snapshot written by a newer format version is refused
+    /// rather than half understood. The replacement is recorded as made by
+    /// the `snapshot` system origin, and replicas following the store are
+    /// sent the new fact set.
+    pub fn import_snapshot(&self, bytes: &[u8]) -> Result<usize> {
+        let document: SnapshotDocument = match SnapshotFormat::detect(bytes) {
+            SnapshotFormat::Json => serde_json::from_slice(bytes)?,
+            SnapshotFormat::Binary => ciborium::from_reader(&bytes[SNAPSHOT_MAGIC.len()..])
+                .map_err(|e| RUNEError::ParseError(format!("Invalid snapshot: {}", e)))?,
+        };
+        if document.format_version > SNAPSHOT_FORMAT_VERSION {
+            return Err(RUNEError::ParseError(format!(
+                "Snapshot format version {} is newer than the supported version {}",
+                document.format_version, SNAPSHOT_FORMAT_VERSION
+            )));
+        }
+
+        let now = Instant::now();
+        let now_ms = history::now_ms();
+        let entries: Vec<SnapshotEntry> = document
+            .facts
+            .into_iter()
+            .filter(|entry| entry.valid_until_ms.is_none_or(|until| until > now_ms))
+            .collect();
+        let facts: Vec<Fact> = entries.iter().map(|entry| entry.fact.clone()).collect();
+
+        let log = self.change_log.get().map(ChangeLog::lock);
+        self.replace(facts.clone(), Some(&ChangeOrigin::system("snapshot")));
+        for entry in entries {
+            if let Some(until) = entry.valid_until_ms {
+                let deadline = now + Duration::from_millis(until - now_ms);
+                self.next_deadline
+                    .fetch_min(self.nanos(deadline), Ordering::AcqRel);
+                self.expirations.insert(entry.fact.clone(), deadline);
+            }
+            if !entry.labels.is_empty() {
+                self.labels.insert(entry.fact, entry.labels);
+            }
+        }
+        if let Some(mut log) = log {
+            log.push(FactChange::Clear);
+            for fact in &facts {
+                log.push(FactChange::Add(fact.clone()));
+            }
+        }
+        Ok(facts.len())
+    }
+
+    /// Track how often each entity named by a fact argument changes (see
+    /// [`crate::cache_ttl`]); later calls have no effect
+    pub(crate) fn enable_change_rates(&self, half_life: Duration) {
+        self.change_rates
+            .get_or_init(|| ChangeRates::new(half_life));
+    }
+
+    /// Changes per second to facts mentioning `entity`, 0 when untracked
+    pub(crate) fn change_rate(&self, entity: &str) -> f64 {
+        self.change_rates
+            .get()
+            .map_or(0.0, |rates| rates.rate(entity, Instant::now()))
+    }
+
+    fn record_change(&self, fact: &Fact) {
+        if let Some(rates) = self.change_rates.get() {
+            let now = Instant::now();
+            for arg in fact.args.iter() {
+                if let Value::String(entity) = arg {
+                    rates.record(entity, now);
+                }
+            }
+        }
+    }
+
     /// Get fact count
     pub fn len(&self) -> usize {
         self.all_facts().len()
@@ -261,9 +929,82 @@ impl Default for FactStore {
     }
 }
 
+/// Fact store changes recorded as made by one [`ChangeOrigin`]
+pub struct FactWriter<'a> {
+    store: &'a FactStore,
+    origin: ChangeOrigin,
+}
+
+impl FactWriter<'_> {
+    /// See [`FactStore::add_fact`]
+    pub fn add_fact(&self, fact: Fact) {
+        self.store.insert(fact, None, Some(&self.origin));
+    }
+
+    /// See [`FactStore::add_fact_with_ttl`]
+    pub fn add_fact_with_ttl(&self, fact: Fact, ttl: Duration) {
+        self.store.insert(fact, Some(ttl), Some(&self.origin));
+    }
+
+    /// See [`FactStore::add_facts_bulk`]
+    pub fn add_facts_bulk(&self, facts: Vec<Fact>) {
+        self.store.insert_bulk(facts, Some(&self.origin));
+    }
+
+    /// See [`FactStore::add_fact_with_labels`]
+    pub fn add_fact_with_labels(&self, fact: Fact, labels: Labels) {
+        self.store
+            .insert_with_labels(fact, labels, Some(&self.origin));
+    }
+
+    /// See [`FactStore::retract_fact`]
+    pub fn retract_fact(&self, fact: &Fact) -> bool {
+        self.store.remove(fact, Some(&self.origin))
+    }
+
+    /// See [`FactStore::clear`]
+    pub fn clear(&self) {
+        self.store.clear_by(Some(&self.origin));
+    }
+}
+
+/// Record the removal of `expired` facts in the history
+fn record_expired(history: &mut HistoryState, expired: &HashSet<Fact>) {
+    let expiry = ChangeOrigin::system("expiry");
+    for fact in expired {
+        history.push(FactOp::Retract, Some(fact), None, Some(&expiry));
+    }
+}
+
+/// Deduplicate `facts`, keeping the newest copy of each, and drop `expired`
+///
+/// Returns the compacted vector with the number of duplicates and expired
+/// facts removed.
+fn compact_facts(facts: &FactVec, expired: &HashSet<Fact>) -> (FactVec, usize, usize) {
+    let mut seen = HashSet::with_capacity(facts.len());
+    let mut duplicates = 0;
+    let mut dropped = 0;
+
+    let mut kept: Vec<&Fact> = facts.iter().collect();
+    kept.reverse();
+    kept.retain(|fact| {
+        if !seen.insert(*fact) {
+            duplicates += 1;
+            false
+        } else if expired.contains(*fact) {
+            dropped += 1;
+            false
+        } else {
+            true
+        }
+    });
+    let compacted = kept.into_iter().rev().cloned().collect();
+    (compacted, duplicates, dropped)
+}
+
 /// Fact store snapshot for consistent reads
 pub struct FactSnapshot {
-    facts: Arc<Vec<Fact>>,
+    facts: FactVec,
     version: u64,
 }
 
@@ -277,7 +1018,7 @@ impl FactSnapshot {
     }
 
     /// Get all facts in the snapshot
-    pub fn facts(&self) -> &[Fact] {
+    pub fn facts(&self) -> &FactVec {
         &self.facts
     }
 
@@ -532,6 +1273,36 @@ mod tests {
         assert_eq!(store.get_by_predicate("follows").len(), 1);
     }
 
+    #[test]
+    fn test_add_facts_bulk_bumps_version_once() {
+        let store = FactStore::new();
+        store.enable_change_log(16);
+        store.add_fact(Fact::unary("user", Value::string("alice")));
+        store.add_fact_with_ttl(
+            Fact::unary("user", Value::string("bob")),
+            Duration::from_secs(60),
+        );
+        let version = store.version();
+
+        let facts: Vec<Fact> = (0..100)
+            .map(|i| Fact::binary("edge", Value::Integer(i), Value::Integer(i + 1)))
+            .chain([Fact::unary("user", Value::string("bob"))])
+            .collect();
+        store.add_facts_bulk(facts.clone());
+
+        assert_eq!(store.version(), version + 1);
+        assert_eq!(store.len(), 103);
+        assert_eq!(store.get_by_predicate("edge"), facts[..100]);
+        assert_eq!(store.get_by_predicate("user").len(), 3);
+        // Re-adding makes the bob fact permanent, as add_fact would
+        assert_eq!(store.next_expiry(), None);
+        let batch = store.changes_since(None, 1000).unwrap();
+        assert_eq!(batch.head, 103);
+
+        store.add_facts_bulk(Vec::new());
+        assert_eq!(store.version(), version + 1);
+    }
+
     #[test]
     fn test_fact_store_version_tracking() {
         let store = FactStore::new();
@@ -809,4 +1580,165 @@ mod tests {
         // Final state should have all facts
         assert_eq!(store.len(), 101); // 1 initial + 100 concurrent
     }
+
+    #[test]
+    fn test_expire_removes_only_expired_facts() {
+        let store = FactStore::new();
+        store.add_fact(Fact::unary("user", Value::string("alice")));
+        store.add_fact_with_ttl(Fact::unary("session", Value::Integer(1)), Duration::ZERO);
+        store.add_fact_with_ttl(
+            Fact::unary("session", Value::Integer(2)),
+            Duration::from_secs(3600),
+        );
+        assert!(store.expiry_due(Instant::now()));
+        let version = store.version();
+
+        assert_eq!(store.expire(), 1);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get_by_predicate("session").len(), 1);
+        assert!(store.version() > version);
+        // The next deadline is an hour away
+        assert!(!store.expiry_due(Instant::now()));
+        assert_eq!(store.expire(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let store = FactStore::new();
+        store.add_fact(Fact::unary("user", Value::string("alice")));
+        store.add_fact(Fact::unary("user", Value::string("alice")));
+        let granted = Fact::binary("role", Value::string("bob"), Value::string("admin"));
+        let labels = Labels::from([("ticket".to_string(), "OPS-1".to_string())]);
+        store.add_fact_with_labels(granted.clone(), labels.clone());
+        store.add_fact_with_ttl(
+            Fact::unary("session", Value::Integer(1)),
+            Duration::from_secs(3600),
+        );
+        store.add_fact_with_ttl(Fact::unary("session", Value::Integer(2)), Duration::ZERO);
+
+        for format in [SnapshotFormat::Binary, SnapshotFormat::Json] {
+            let bytes = store.export_snapshot(format).unwrap();
+            assert_eq!(SnapshotFormat::detect(&bytes), format);
+
+            let restored = FactStore::new();
+            restored.add_fact(Fact::unary("stale", Value::Bool(true)));
+            // Duplicates are written once and the lapsed session is dropped
+            assert_eq!(restored.import_snapshot(&bytes).unwrap(), 3);
+            assert!(restored.get_by_predicate("stale").is_empty());
+            assert_eq!(restored.fact_labels(&granted), Some(labels.clone()));
+            assert_eq!(restored.get_by_predicate("session").len(), 1);
+            let expiry = restored.next_expiry().unwrap();
+            assert!(expiry > Instant::now() + Duration::from_secs(3500));
+        }
+
+        let newer = br#"{"format_version": 99, "taken_at_ms": 0, "facts": []}"#;
+        assert!(FactStore::new().import_snapshot(newer).is_err());
+        assert!(FactStore::new().import_snapshot(b"RUNEFACT\xff").is_err());
+    }
+
+    #[test]
+    fn test_compact_merges_duplicates_and_drops_expired() {
+        let store = FactStore::new();
+        store.add_fact(Fact::unary("user", Value::string("alice")));
+        store.add_fact(Fact::unary("user", Value::string("bob")));
+        let latest = Fact::unary("user", Value::string("alice"));
+        store.add_fact(latest.clone());
+        store.add_fact_with_ttl(Fact::unary("session", Value::Integer(1)), Duration::ZERO);
+        store.add_fact_with_ttl(
+            Fact::unary("session", Value::Integer(2)),
+            Duration::from_secs(3600),
+        );
+
+        // Expiry is applied by compaction, not on read
+        assert_eq!(store.len(), 5);
+        let version = store.version();
+
+        let stats = store.compact();
+        assert_eq!(stats.facts_before, 5);
+        assert_eq!(stats.facts_after, 3);
+        assert_eq!(stats.duplicates_removed, 1);
+        assert_eq!(stats.expired_removed, 1);
+        assert!(store.has_changed_since(version));
+
+        let users = store.get_by_predicate("user");
+        assert_eq!(users.len(), 2);
+        let alice = users.iter().find(|f| **f == latest).unwrap();
+        assert_eq!(alice.timestamp, latest.timestamp);
+        assert_eq!(store.get_by_predicate("session").len(), 1);
+
+        // Nothing left to do: the fact set and version are unchanged
+        let version = store.version();
+        let again = store.compact();
+        assert_eq!(again.facts_after, 3);
+        assert_eq!(again.duplicates_removed + again.expired_removed, 0);
+        assert_eq!(store.version(), version);
+    }
+
+    #[test]
+    fn test_retract_and_readd_clear_expiry() {
+        let store = FactStore::new();
+        let temp = Fact::unary("temp", Value::Integer(1));
+        store.add_fact_with_ttl(temp.clone(), Duration::ZERO);
+        store.add_fact(temp.clone());
+
+        // Re-adding without a TTL made the fact permanent
+        assert_eq!(store.compact().expired_removed, 0);
+        assert_eq!(store.len(), 1);
+
+        assert!(store.retract_fact(&temp));
+        assert!(!store.retract_fact(&temp));
+        assert!(store.is_empty());
+        assert!(store.get_by_predicate("temp").is_empty());
+
+        // The emptied predicate entry goes on the next pass
+        assert_eq!(store.compact().predicates_removed, 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_mixed_writers_reclaim_every_vector() {
+        use std::thread;
+
+        let rounds = if cfg!(miri) { 10 } else { 50 };
+        let store = Arc::new(FactStore::new());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for j in 0..rounds {
+                        let fact = Fact::binary("edge", Value::Integer(i), Value::Integer(j));
+                        store.add_fact(fact.clone());
+                        if j % 3 == 0 {
+                            store.retract_fact(&fact);
+                        }
+                        if j % 10 == 0 {
+                            store.compact();
+                        }
+                        if i == 0 && j % 5 == 0 {
+                            store.replace(Vec::new(), None);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Every replaced vector is freed once the epoch advances
+        for _ in 0..10_000 {
+            if store.all_facts.audit().live() == 1 {
+                break;
+            }
+            crossbeam::epoch::pin().flush();
+            thread::yield_now();
+        }
+        let counts = store.all_facts.audit();
+        assert_eq!(counts.live(), 1, "{:?}", counts);
+
+        // Dropping the store frees the current vector too
+        let facts = store.all_facts();
+        drop(Arc::into_inner(store).unwrap());
+        assert!(facts.is_unique());
+    }
 }
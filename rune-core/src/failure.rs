@@ -0,0 +1,207 @@
+//! Failure handling per evaluation dependency
+//!
+//! An authorization combines a Datalog fixpoint with a Cedar policy check,
+//! and either can fail: Datalog evaluation can error or overrun the engine's
+//! `timeout_ms`, and Cedar can reject a request it cannot evaluate. A
+//! [`FailurePolicy`] decides, for each [`FailureClass`], what the engine
+//! answers instead of returning an error:
+//!
+//! - [`FailureMode::FailClosed`] substitutes a deny for the failed component,
+//!   so the request is denied;
+//! - [`FailureMode::FailOpen`] substitutes a permit for the failed component,
+//!   leaving the decision to the component that succeeded (a Cedar forbid
+//!   still wins);
+//! - [`FailureMode::FallbackToCache`] answers with the last cached decision
+//!   for the request, even past its TTL, and fails closed when there is none.
+//!
+//! Degraded decisions are never written to the cache, and each one is
+//! reported in [`AuthorizationResult::failures`](crate::AuthorizationResult).
+
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// What to answer when a dependency fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureMode {
+    /// Deny the request
+    #[default]
+    FailClosed,
+    /// Treat the failed component as permitting
+    FailOpen,
+    /// Reuse the last cached decision, failing closed without one
+    FallbackToCache,
+}
+
+impl FailureMode {
+    /// Name used in configuration and metric labels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureMode::FailClosed => "fail_closed",
+            FailureMode::FailOpen => "fail_open",
+            FailureMode::FallbackToCache => "fallback_to_cache",
+        }
+    }
+}
+
+impl fmt::Display for FailureMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FailureMode {
+    type Err = RUNEError;
+
+    /// Accepts `fail_closed`, `fail-closed` and `closed` (likewise for the
+    /// other modes), ignoring case
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "fail_closed" | "closed" | "deny" => Ok(FailureMode::FailClosed),
+            "fail_open" | "open" | "permit" => Ok(FailureMode::FailOpen),
+            "fallback_to_cache" | "cache" => Ok(FailureMode::FallbackToCache),
+            _ => Err(RUNEError::ConfigError(format!(
+                "Unknown failure mode '{}' (expected fail_closed, fail_open or fallback_to_cache)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Kind of dependency failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// Datalog evaluation returned an error
+    DatalogError,
+    /// Datalog evaluation took longer than the engine timeout
+    DatalogTimeout,
+    /// Cedar policy evaluation returned an error
+    CedarError,
+}
+
+impl FailureClass {
+    /// Every class, in metric order
+    pub const ALL: [FailureClass; 3] = [
+        FailureClass::DatalogError,
+        FailureClass::DatalogTimeout,
+        FailureClass::CedarError,
+    ];
+
+    /// Classify a Datalog evaluation error
+    pub fn of_datalog(error: &RUNEError) -> Self {
+        match error {
+            RUNEError::Timeout(_) => FailureClass::DatalogTimeout,
+            _ => FailureClass::DatalogError,
+        }
+    }
+
+    /// Name used in metric labels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureClass::DatalogError => "datalog_error",
+            FailureClass::DatalogTimeout => "datalog_timeout",
+            FailureClass::CedarError => "cedar_error",
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Failure mode for each dependency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailurePolicy {
+    /// Datalog evaluation errors
+    pub datalog_error: FailureMode,
+    /// Datalog evaluation exceeding `timeout_ms`
+    pub datalog_timeout: FailureMode,
+    /// Cedar evaluation errors
+    pub cedar_error: FailureMode,
+}
+
+impl FailurePolicy {
+    /// Use the same mode for every dependency
+    pub fn uniform(mode: FailureMode) -> Self {
+        FailurePolicy {
+            datalog_error: mode,
+            datalog_timeout: mode,
+            cedar_error: mode,
+        }
+    }
+
+    /// Mode configured for a failure class
+    pub fn mode(&self, class: FailureClass) -> FailureMode {
+        match class {
+            FailureClass::DatalogError => self.datalog_error,
+            FailureClass::DatalogTimeout => self.datalog_timeout,
+            FailureClass::CedarError => self.cedar_error,
+        }
+    }
+}
+
+/// A dependency failure and how the engine handled it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureOutcome {
+    /// What failed
+    pub class: FailureClass,
+    /// Mode actually applied; a cache fallback without an entry fails closed
+    pub mode: FailureMode,
+    /// Error reported by the dependency
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(
+            "fail-open".parse::<FailureMode>().unwrap(),
+            FailureMode::FailOpen
+        );
+        assert_eq!(
+            "CLOSED".parse::<FailureMode>().unwrap(),
+            FailureMode::FailClosed
+        );
+        assert_eq!(
+            "fallback_to_cache".parse::<FailureMode>().unwrap(),
+            FailureMode::FallbackToCache
+        );
+        assert!("retry".parse::<FailureMode>().is_err());
+
+        for mode in [
+            FailureMode::FailClosed,
+            FailureMode::FailOpen,
+            FailureMode::FallbackToCache,
+        ] {
+            assert_eq!(mode.as_str().parse::<FailureMode>().unwrap(), mode);
+        }
+    }
+
+    #[test]
+    fn test_policy_defaults_to_fail_closed() {
+        let policy: FailurePolicy =
+            serde_json::from_str(r#"{"cedar_error": "fail_open"}"#).unwrap();
+        assert_eq!(policy.mode(FailureClass::CedarError), FailureMode::FailOpen);
+        assert_eq!(
+            policy.mode(FailureClass::DatalogTimeout),
+            FailureMode::FailClosed
+        );
+        assert_eq!(
+            FailureClass::of_datalog(&RUNEError::Timeout(5)),
+            FailureClass::DatalogTimeout
+        );
+    }
+}
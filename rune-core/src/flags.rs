@@ -0,0 +1,95 @@
+//! Runtime kill switches for individual rules and policies
+//!
+//! Rules and policies can carry an `@id("...")` or `@flag("...")` annotation
+//! and may be disabled in source with `@enabled("false")`. The switches held
+//! here override those defaults at runtime, so a misbehaving rule can be
+//! turned off without pushing a new configuration.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+/// Runtime overrides keyed by rule/policy flag
+#[derive(Debug, Default)]
+pub struct RuleFlags {
+    overrides: DashMap<String, bool>,
+}
+
+impl RuleFlags {
+    /// Create an empty flag registry (every rule keeps its source default)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the state of a flag
+    pub fn set(&self, key: impl Into<String>, enabled: bool) {
+        self.overrides.insert(key.into(), enabled);
+    }
+
+    /// Drop an override, returning the flag to its source default
+    pub fn reset(&self, key: &str) -> Option<bool> {
+        self.overrides.remove(key).map(|(_, enabled)| enabled)
+    }
+
+    /// Current override for a flag, if any
+    pub fn get(&self, key: &str) -> Option<bool> {
+        self.overrides.get(key).map(|entry| *entry)
+    }
+
+    /// Resolve whether a rule with the given key and source default is active
+    pub fn is_enabled(&self, key: Option<&str>, default: bool) -> bool {
+        key.and_then(|k| self.get(k)).unwrap_or(default)
+    }
+
+    /// Number of active overrides
+    pub fn len(&self) -> usize {
+        self.overrides.len()
+    }
+
+    /// Check whether no overrides are set
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+}
+
+/// State of a single flag as reported by [`crate::RUNEEngine::rule_flags`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagStatus {
+    /// Flag key (`@flag` or `@id` annotation)
+    pub key: String,
+    /// Whether the flagged rules/policies are currently evaluated
+    pub enabled: bool,
+    /// Whether the state comes from a runtime override
+    pub overridden: bool,
+    /// Number of Datalog rules controlled by the flag
+    pub rules: usize,
+    /// Number of Cedar policies controlled by the flag
+    pub policies: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_apply_without_override() {
+        let flags = RuleFlags::new();
+        assert!(flags.is_enabled(Some("a"), true));
+        assert!(!flags.is_enabled(Some("a"), false));
+        assert!(!flags.is_enabled(None, false));
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn test_override_and_reset() {
+        let flags = RuleFlags::new();
+        flags.set("a", false);
+        assert!(!flags.is_enabled(Some("a"), true));
+        assert!(flags.is_enabled(Some("b"), true));
+        // Unkeyed rules cannot be overridden
+        assert!(flags.is_enabled(None, true));
+
+        assert_eq!(flags.reset("a"), Some(false));
+        assert!(flags.is_enabled(Some("a"), true));
+        assert_eq!(flags.reset("a"), None);
+    }
+}
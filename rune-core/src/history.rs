@@ -0,0 +1,358 @@
+//! Bitemporal history of fact changes
+//!
+//! Auditors ask when a principal got a role and who granted it. With a
+//! history enabled (`EngineConfig::fact_history`) the fact store records
+//! every change with the time it was made, who made it and through what,
+//! keeping the last `fact_history` changes:
+//!
+//! ```text
+//! seq 41  2026-03-02T10:15:00Z  add      role("bob", "admin")  actor=carol source=api
+//! seq 57  2026-03-09T16:40:12Z  retract  role("bob", "admin")  actor=system source=expiry
+//! ```
+//!
+//! History has two time axes. Each change is stamped with when it was
+//! recorded, and a fact added with a time-to-live also carries when it stops
+//! being valid, which is earlier than the compaction that eventually records
+//! its removal. [`crate::RUNEEngine::facts_as_of`] combines both: the facts
+//! that had been recorded by a given time and were still valid at it.
+//!
+//! Changes that fall out of the bounded history are folded into a baseline
+//! rather than lost, so the store can be reconstructed as of any time since
+//! the latest of them, the span the retained changes cover. Callers name
+//! themselves with [`crate::RUNEEngine::attributed`]; changes made through
+//! the plain engine methods are recorded with [`ChangeOrigin::default`].
+//! Labels are not part of the history.
+
+use crate::error::{RUNEError, Result};
+use crate::facts::Fact;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Actor of changes by a caller that did not, or could not, name itself
+///
+/// It never owns anything: the ownership checks of [`crate::ownership`]
+/// refuse its changes to owned rules and policies.
+pub const ANONYMOUS_ACTOR: &str = "unknown";
+
+/// Who made a change, and through what
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChangeOrigin {
+    /// User or service that made the change
+    pub actor: String,
+    /// How the change was made, e.g. `api`, `replication` or `expiry`
+    pub source: String,
+}
+
+impl ChangeOrigin {
+    /// Changes by `actor` through `source`
+    pub fn new(actor: impl Into<String>, source: impl Into<String>) -> Self {
+        ChangeOrigin {
+            actor: actor.into(),
+            source: source.into(),
+        }
+    }
+
+    /// Changes the engine makes on its own through `source`
+    pub fn system(source: impl Into<String>) -> Self {
+        Self::new("system", source)
+    }
+}
+
+impl Default for ChangeOrigin {
+    /// An unnamed caller of the engine API
+    fn default() -> Self {
+        Self::new(ANONYMOUS_ACTOR, "api")
+    }
+}
+
+/// Kind of change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactOp {
+    /// A fact was added
+    Add,
+    /// Every copy of a fact was removed
+    Retract,
+    /// The store was emptied
+    Clear,
+}
+
+/// One recorded change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FactEvent {
+    /// Position in the history, from 1
+    pub seq: u64,
+    /// When the change was made, in milliseconds since the Unix epoch
+    pub recorded_at_ms: u64,
+    /// Kind of change
+    pub op: FactOp,
+    /// Fact added or retracted; `None` for a clear
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fact: Option<Fact>,
+    /// When an added fact stops being valid, for facts with a time-to-live
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until_ms: Option<u64>,
+    /// Who made the change, and through what
+    #[serde(flatten)]
+    pub origin: ChangeOrigin,
+}
+
+/// Which recorded changes to list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// Only changes to facts of this predicate; clears are always included
+    pub predicate: Option<String>,
+    /// Only changes recorded at or after this time
+    pub since_ms: Option<u64>,
+    /// Only changes recorded at or before this time
+    pub until_ms: Option<u64>,
+    /// At most this many changes, the most recent ones
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, event: &FactEvent) -> bool {
+        let predicate = match (&self.predicate, &event.fact) {
+            (Some(predicate), Some(fact)) => fact.predicate.as_ref() == predicate,
+            _ => true,
+        };
+        predicate
+            && self
+                .since_ms
+                .is_none_or(|since| event.recorded_at_ms >= since)
+            && self
+                .until_ms
+                .is_none_or(|until| event.recorded_at_ms <= until)
+    }
+}
+
+/// Bounded record of a store's changes
+pub(crate) struct FactHistory {
+    state: Mutex<HistoryState>,
+}
+
+pub(crate) struct HistoryState {
+    /// Sequence number of the latest change
+    seq: u64,
+    /// Retained changes, oldest first
+    events: VecDeque<FactEvent>,
+    capacity: usize,
+    /// Facts, with their validity, before the oldest retained change
+    baseline: HashMap<Fact, Option<u64>>,
+    /// Since when the baseline holds: when the history was enabled, or the
+    /// latest change folded into it
+    baseline_at_ms: u64,
+}
+
+impl HistoryState {
+    /// Record a change made now
+    pub(crate) fn push(
+        &mut self,
+        op: FactOp,
+        fact: Option<&Fact>,
+        valid_until_ms: Option<u64>,
+        origin: Option<&ChangeOrigin>,
+    ) {
+        self.push_at(now_ms(), op, fact, valid_until_ms, origin);
+    }
+
+    fn push_at(
+        &mut self,
+        recorded_at_ms: u64,
+        op: FactOp,
+        fact: Option<&Fact>,
+        valid_until_ms: Option<u64>,
+        origin: Option<&ChangeOrigin>,
+    ) {
+        self.seq += 1;
+        if self.events.len() == self.capacity {
+            if let Some(oldest) = self.events.pop_front() {
+                apply(&mut self.baseline, &oldest);
+                self.baseline_at_ms = oldest.recorded_at_ms;
+            }
+        }
+        self.events.push_back(FactEvent {
+            seq: self.seq,
+            recorded_at_ms,
+            op,
+            fact: fact.cloned(),
+            valid_until_ms,
+            origin: origin.cloned().unwrap_or_default(),
+        });
+    }
+}
+
+impl FactHistory {
+    /// Start a history of a store currently holding `facts`, each valid
+    /// until the given time, if any
+    pub(crate) fn new(
+        capacity: usize,
+        facts: impl IntoIterator<Item = (Fact, Option<u64>)>,
+    ) -> Self {
+        FactHistory {
+            state: Mutex::new(HistoryState {
+                seq: 0,
+                events: VecDeque::with_capacity(capacity.min(1024)),
+                capacity,
+                baseline: facts.into_iter().collect(),
+                baseline_at_ms: now_ms(),
+            }),
+        }
+    }
+
+    /// Lock the history; stores hold it across a change and its record so
+    /// the history order matches the order changes were made in
+    pub(crate) fn lock(&self) -> MutexGuard<'_, HistoryState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Retained changes matching `query`, oldest first
+    pub(crate) fn events(&self, query: &HistoryQuery) -> Vec<FactEvent> {
+        let state = self.lock();
+        let mut events: Vec<FactEvent> = state
+            .events
+            .iter()
+            .rev()
+            .filter(|event| query.matches(event))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        events.reverse();
+        events
+    }
+
+    /// Facts recorded by `at_ms` and still valid at it, ordered by
+    /// predicate and arguments
+    pub(crate) fn as_of(&self, at_ms: u64) -> Result<Vec<Fact>> {
+        let state = self.lock();
+        if at_ms < state.baseline_at_ms {
+            return Err(RUNEError::ConfigError(format!(
+                "Fact history reaches back to {} ms, not {} ms",
+                state.baseline_at_ms, at_ms
+            )));
+        }
+
+        let mut facts = state.baseline.clone();
+        for event in state
+            .events
+            .iter()
+            .take_while(|event| event.recorded_at_ms <= at_ms)
+        {
+            apply(&mut facts, event);
+        }
+        let mut facts: Vec<Fact> = facts
+            .into_iter()
+            .filter(|(_, until)| until.is_none_or(|until| until > at_ms))
+            .map(|(fact, _)| fact)
+            .collect();
+        facts.sort_by(|a, b| (&a.predicate, &a.args).cmp(&(&b.predicate, &b.args)));
+        Ok(facts)
+    }
+}
+
+/// Replay one change onto a fact set
+fn apply(facts: &mut HashMap<Fact, Option<u64>>, event: &FactEvent) {
+    match (event.op, &event.fact) {
+        (FactOp::Add, Some(fact)) => {
+            facts.insert(fact.clone(), event.valid_until_ms);
+        }
+        (FactOp::Retract, Some(fact)) => {
+            facts.remove(fact);
+        }
+        (FactOp::Clear, _) => facts.clear(),
+        _ => {}
+    }
+}
+
+/// Milliseconds since the Unix epoch
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn role(user: &str) -> Fact {
+        Fact::binary("role", Value::string(user), Value::string("admin"))
+    }
+
+    fn history(capacity: usize) -> FactHistory {
+        let history = FactHistory::new(capacity, [(role("alice"), None)]);
+        history.lock().baseline_at_ms = 1_000;
+        history
+    }
+
+    #[test]
+    fn test_reconstruct_as_of() {
+        let history = history(10);
+        let carol = ChangeOrigin::new("carol", "api");
+        {
+            let mut state = history.lock();
+            state.push_at(2_000, FactOp::Add, Some(&role("bob")), None, Some(&carol));
+            state.push_at(3_000, FactOp::Add, Some(&role("erin")), Some(3_500), None);
+            state.push_at(
+                4_000,
+                FactOp::Retract,
+                Some(&role("alice")),
+                None,
+                Some(&carol),
+            );
+        }
+
+        assert_eq!(history.as_of(1_500).unwrap(), [role("alice")]);
+        assert_eq!(
+            history.as_of(3_000).unwrap(),
+            [role("alice"), role("bob"), role("erin")]
+        );
+        // Erin's grant lapsed before anyone removed it
+        assert_eq!(history.as_of(3_500).unwrap(), [role("alice"), role("bob")]);
+        assert_eq!(history.as_of(4_000).unwrap(), [role("bob")]);
+        assert!(history.as_of(500).is_err());
+
+        let grants = history.events(&HistoryQuery {
+            predicate: Some("role".to_string()),
+            until_ms: Some(2_000),
+            ..HistoryQuery::default()
+        });
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].origin, carol);
+        assert_eq!(grants[0].fact, Some(role("bob")));
+        assert_eq!(
+            history.events(&HistoryQuery {
+                limit: Some(1),
+                ..HistoryQuery::default()
+            })[0]
+                .op,
+            FactOp::Retract
+        );
+    }
+
+    #[test]
+    fn test_evicted_changes_fold_into_baseline() {
+        let history = history(2);
+        {
+            let mut state = history.lock();
+            state.push_at(2_000, FactOp::Add, Some(&role("bob")), None, None);
+            state.push_at(3_000, FactOp::Clear, None, None, None);
+            state.push_at(4_000, FactOp::Add, Some(&role("carol")), None, None);
+        }
+
+        // Only the last two changes are listed
+        let events = history.events(&HistoryQuery::default());
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(events[0].origin, ChangeOrigin::default());
+
+        // The store can still be rebuilt from the latest folded change on
+        assert_eq!(history.as_of(2_500).unwrap(), [role("alice"), role("bob")]);
+        assert!(history.as_of(3_000).unwrap().is_empty());
+        assert_eq!(history.as_of(4_000).unwrap(), [role("carol")]);
+        assert!(history.as_of(1_500).is_err());
+    }
+}
@@ -0,0 +1,487 @@
+//! Layered configuration
+//!
+//! An organization-wide base file can be refined by team- and
+//! environment-level overlays. [`compose`] resolves an ordered stack of
+//! layers into one [`RUNEConfig`], applied as a single configuration, and a
+//! [`Provenance`] map recording which layer each rule and policy came from.
+//!
+//! Each layer may:
+//!
+//! - add rules and policies; redefining an `@id` an earlier layer already
+//!   uses is an error, so nothing is silently replaced
+//! - switch rules and policies on or off in an `[enabled]` section, keyed
+//!   like runtime flags (`@flag`, then `@id`)
+//! - replace the `[canonicalize]`, `[routes]`, `[scopes]`, `[limits]`,
+//!   `[attributes]`, `[messages]`, `[builtins]`, `[strings]` and
+//!   `[evaluation]` sections and extend `[data]`, with later layers winning
+//!
+//! No layer may disable a `forbid` policy defined by an earlier one.
+//!
+//! ```text
+//! # team.rune
+//! version = "rune/2.0"
+//!
+//! [rules]
+//! allow(U, "deploy", R) :- on_call(U).
+//!
+//! [enabled]
+//! legacy-readers = false
+//! ```
+
+use crate::artifact::PolicyArtifact;
+use crate::datalog::diagnostics::DiagnosticBag;
+use crate::datalog::types::Rule;
+use crate::error::{RUNEError, Result};
+use crate::parser::{has_explicit_id, parse_rune_file, policy_annotation, Policy, RUNEConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One named configuration in a stack of layers
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    /// Name used in provenance and errors, e.g. `base` or `team-payments`
+    pub name: String,
+    /// The layer's parsed configuration
+    pub config: RUNEConfig,
+}
+
+impl ConfigLayer {
+    /// Wrap a parsed configuration as a layer
+    pub fn new(name: impl Into<String>, config: RUNEConfig) -> Self {
+        ConfigLayer {
+            name: name.into(),
+            config,
+        }
+    }
+
+    /// Parse a RUNE file as a layer
+    pub fn parse(name: impl Into<String>, source: &str) -> Result<Self> {
+        Ok(Self::new(name, parse_rune_file(source)?))
+    }
+}
+
+/// Where a rule of a composed configuration came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleOrigin {
+    /// The rule, with variables shown as `?X`
+    pub rule: String,
+    /// Its `@flag` or `@id`, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Layer that defined it
+    pub layer: String,
+}
+
+/// A switch set in an `[enabled]` section
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnabledOrigin {
+    /// Whether the rule or policy is on
+    pub enabled: bool,
+    /// Last layer to set it
+    pub layer: String,
+}
+
+/// Which layer each part of a composed configuration came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Layer names, base first
+    pub layers: Vec<String>,
+    /// One entry per rule, in the composed configuration's order
+    pub rules: Vec<RuleOrigin>,
+    /// Defining layer of each policy, by policy ID
+    pub policies: BTreeMap<String, String>,
+    /// Switches set by `[enabled]` sections, by flag key
+    pub enabled: BTreeMap<String, EnabledOrigin>,
+}
+
+impl Provenance {
+    /// Layer that defined the rule at `index`
+    pub fn rule_layer(&self, index: usize) -> Option<&str> {
+        self.rules.get(index).map(|origin| origin.layer.as_str())
+    }
+
+    /// Layer that defined a policy
+    pub fn policy_layer(&self, id: &str) -> Option<&str> {
+        self.policies.get(id).map(String::as_str)
+    }
+}
+
+/// A stack of layers resolved into one configuration
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    /// The configuration to apply
+    pub config: RUNEConfig,
+    /// Where each rule and policy came from
+    pub provenance: Provenance,
+}
+
+/// Resolve `layers`, base first, into one configuration
+///
+/// The result carries the base layer's version, an artifact identifying the
+/// whole stack (see [`PolicyArtifact::compose`]), and has every `[enabled]`
+/// switch folded into the rules and policies it names, so its own
+/// `enabled` map is empty.
+pub fn compose(layers: impl IntoIterator<Item = ConfigLayer>) -> Result<LayeredConfig> {
+    let mut layers = layers.into_iter();
+    let base = layers
+        .next()
+        .ok_or_else(|| RUNEError::ConfigError("No configuration layers to compose".into()))?;
+
+    let mut provenance = Provenance::default();
+    let mut config = RUNEConfig {
+        version: base.config.version.clone(),
+        format: base.config.format,
+        data: toml::Value::Table(toml::map::Map::new()),
+        rules: Vec::new(),
+        policies: Vec::new(),
+        canonicalize: None,
+        routes: None,
+        scopes: None,
+        limits: None,
+        cache: None,
+        attributes: None,
+        messages: None,
+        builtins: None,
+        strings: None,
+        evaluation: None,
+        enabled: BTreeMap::new(),
+        artifact: PolicyArtifact::default(),
+        warnings: DiagnosticBag::new(),
+    };
+    let mut artifacts = Vec::new();
+    for layer in std::iter::once(base).chain(layers) {
+        artifacts.push(layer.config.artifact.clone());
+        add_layer(&mut config, &mut provenance, layer)?;
+    }
+    config.artifact = PolicyArtifact::compose(&artifacts);
+    Ok(LayeredConfig { config, provenance })
+}
+
+/// Fold `[enabled]` switches into a single configuration's own rules and
+/// policies
+pub(crate) fn resolve_enabled(config: RUNEConfig) -> Result<RUNEConfig> {
+    if config.enabled.is_empty() {
+        return Ok(config);
+    }
+    Ok(compose([ConfigLayer::new("config", config)])?.config)
+}
+
+fn add_layer(
+    config: &mut RUNEConfig,
+    provenance: &mut Provenance,
+    layer: ConfigLayer,
+) -> Result<()> {
+    let ConfigLayer { name, config: next } = layer;
+    if provenance.layers.contains(&name) {
+        return Err(RUNEError::ConfigError(format!(
+            "Configuration layer {} is listed twice",
+            name
+        )));
+    }
+
+    for rule in next.rules {
+        let key = rule.flag_key().map(str::to_string);
+        if let Some(key) = &key {
+            if let Some(origin) = provenance
+                .rules
+                .iter()
+                .find(|origin| origin.key.as_ref() == Some(key))
+            {
+                return Err(RUNEError::ConfigError(if origin.layer == name {
+                    format!("Layer {} has duplicate rule ID {}", name, key)
+                } else {
+                    format!(
+                        "Layer {} redefines rule {} from layer {}; layers can only add rules",
+                        name, key, origin.layer
+                    )
+                }));
+            }
+        }
+        provenance.rules.push(RuleOrigin {
+            rule: rule.to_string(),
+            key,
+            layer: name.clone(),
+        });
+        config.rules.push(rule);
+    }
+
+    for mut policy in next.policies {
+        if let Some(layer) = provenance.policies.get(&policy.id) {
+            if has_explicit_id(&policy) {
+                return Err(RUNEError::ConfigError(if *layer == name {
+                    format!("Layer {} has duplicate policy ID {}", name, policy.id)
+                } else {
+                    format!(
+                        "Layer {} redefines policy {} from layer {}; layers can only add policies",
+                        name, policy.id, layer
+                    )
+                }));
+            }
+            // Unnamed 1.0 policies are numbered per file
+            policy.id = format!("{}/{}", name, policy.id);
+        }
+        provenance.policies.insert(policy.id.clone(), name.clone());
+        config.policies.push(policy);
+    }
+
+    for (key, enabled) in next.enabled {
+        set_enabled(config, provenance, &name, &key, enabled)?;
+        provenance.enabled.insert(
+            key,
+            EnabledOrigin {
+                enabled,
+                layer: name.clone(),
+            },
+        );
+    }
+
+    merge_data(&mut config.data, next.data);
+    if next.canonicalize.is_some() {
+        config.canonicalize = next.canonicalize;
+    }
+    if next.routes.is_some() {
+        config.routes = next.routes;
+    }
+    if next.scopes.is_some() {
+        config.scopes = next.scopes;
+    }
+    if next.limits.is_some() {
+        config.limits = next.limits;
+    }
+    if next.cache.is_some() {
+        config.cache = next.cache;
+    }
+    if next.attributes.is_some() {
+        config.attributes = next.attributes;
+    }
+    if next.messages.is_some() {
+        config.messages = next.messages;
+    }
+    if next.builtins.is_some() {
+        config.builtins = next.builtins;
+    }
+    if next.strings.is_some() {
+        config.strings = next.strings;
+    }
+    if next.evaluation.is_some() {
+        config.evaluation = next.evaluation;
+    }
+    config.warnings.extend(next.warnings.diagnostics().to_vec());
+
+    provenance.layers.push(name);
+    Ok(())
+}
+
+/// Switch every rule and policy with flag key `key`
+fn set_enabled(
+    config: &mut RUNEConfig,
+    provenance: &Provenance,
+    layer: &str,
+    key: &str,
+    enabled: bool,
+) -> Result<()> {
+    let mut found = false;
+
+    for rule in config
+        .rules
+        .iter_mut()
+        .filter(|r| r.flag_key() == Some(key))
+    {
+        set_rule_enabled(rule, enabled);
+        found = true;
+    }
+
+    for policy in &mut config.policies {
+        if policy_flag_key(policy) != key {
+            continue;
+        }
+        let origin = provenance.policy_layer(&policy.id).unwrap_or(layer);
+        if !enabled && origin != layer && is_forbid(policy) {
+            return Err(RUNEError::ConfigError(format!(
+                "Layer {} cannot disable forbid policy {} from layer {}",
+                layer, key, origin
+            )));
+        }
+        set_policy_enabled(policy, enabled);
+        found = true;
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(RUNEError::ConfigError(format!(
+            "Layer {} enables or disables {}, which no rule or policy uses as @flag or @id",
+            layer, key
+        )))
+    }
+}
+
+fn set_rule_enabled(rule: &mut Rule, enabled: bool) {
+    if enabled {
+        rule.annotations.remove("enabled");
+    } else {
+        rule.annotations
+            .insert("enabled".to_string(), "false".to_string());
+    }
+}
+
+/// Rewrite a policy's `@enabled` annotation
+fn set_policy_enabled(policy: &mut Policy, enabled: bool) {
+    let mut content = String::new();
+    if !enabled {
+        content.push_str("@enabled(\"false\")\n");
+    }
+    let mut in_annotations = true;
+    for line in policy.content.lines() {
+        in_annotations &= line.trim_start().starts_with('@');
+        if in_annotations && line.trim_start().starts_with("@enabled") {
+            continue;
+        }
+        content.push_str(line);
+        content.push('\n');
+    }
+    policy.content = content;
+}
+
+/// Key used to toggle a policy: `@flag`, then `@id`, then its ID
+fn policy_flag_key(policy: &Policy) -> String {
+    policy_annotation(policy, "flag").unwrap_or_else(|| policy.id.clone())
+}
+
+fn is_forbid(policy: &Policy) -> bool {
+    policy
+        .content
+        .lines()
+        .map(str::trim_start)
+        .find(|line| !line.starts_with('@') && !line.is_empty())
+        .is_some_and(|line| line.starts_with("forbid"))
+}
+
+/// Merge `next` into `data`, recursing into tables; other values are
+/// replaced
+fn merge_data(data: &mut toml::Value, next: toml::Value) {
+    match (data, next) {
+        (toml::Value::Table(table), toml::Value::Table(next)) => {
+            for (key, value) in next {
+                match table.get_mut(&key) {
+                    Some(existing) => merge_data(existing, value),
+                    None => {
+                        table.insert(key, value);
+                    }
+                }
+            }
+        }
+        (data, next) => *data = next,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"version = "rune/2.0"
+
+[data]
+limits = { requests = 100, burst = 10 }
+
+[rules]
+@id("readers")
+can_read(U) :- member(U, "readers").
+
+[policies]
+@id("no-deletes")
+forbid(principal, action == Action::"delete", resource);
+
+@id("legacy")
+permit(principal, action == Action::"read", resource);
+"#;
+
+    fn layers(overlays: &[(&str, &str)]) -> Result<LayeredConfig> {
+        let mut stack = vec![ConfigLayer::parse("base", BASE).unwrap()];
+        for (name, source) in overlays {
+            stack.push(ConfigLayer::parse(*name, source).unwrap());
+        }
+        compose(stack)
+    }
+
+    #[test]
+    fn test_overlays_add_rules_with_provenance() {
+        let team = r#"version = "rune/2.0"
+
+[data]
+limits = { requests = 500 }
+
+[rules]
+can_deploy(U) :- on_call(U).
+
+[policies]
+@id("team-writes")
+permit(principal, action == Action::"write", resource);
+"#;
+        let layered = layers(&[("team", team)]).unwrap();
+        let provenance = &layered.provenance;
+
+        assert_eq!(provenance.layers, ["base", "team"]);
+        assert_eq!(layered.config.rules.len(), 2);
+        assert_eq!(provenance.rule_layer(0), Some("base"));
+        assert_eq!(provenance.rule_layer(1), Some("team"));
+        assert_eq!(provenance.rules[0].key.as_deref(), Some("readers"));
+        assert_eq!(provenance.policy_layer("no-deletes"), Some("base"));
+        assert_eq!(provenance.policy_layer("team-writes"), Some("team"));
+
+        let limits = &layered.config.data["limits"];
+        assert_eq!(limits["requests"].as_integer(), Some(500));
+        assert_eq!(limits["burst"].as_integer(), Some(10));
+    }
+
+    #[test]
+    fn test_later_layers_switch_rules_and_policies() {
+        let team = "version = \"rune/2.0\"\n\n[enabled]\nlegacy = false\nreaders = false\n";
+        let prod = "version = \"rune/2.0\"\n\n[enabled]\nlegacy = true\n";
+        let layered = layers(&[("team", team), ("prod", prod)]).unwrap();
+
+        assert!(!layered.config.rules[0].enabled_by_default());
+        let legacy = &layered.config.policies[1];
+        assert!(!legacy.content.contains("@enabled"));
+        assert_eq!(
+            layered.provenance.enabled["legacy"],
+            EnabledOrigin {
+                enabled: true,
+                layer: "prod".into()
+            }
+        );
+        assert!(!layered.provenance.enabled["readers"].enabled);
+        assert!(layered.config.enabled.is_empty());
+    }
+
+    #[test]
+    fn test_overlays_cannot_disable_base_forbids() {
+        let team = "version = \"rune/2.0\"\n\n[enabled]\nno-deletes = false\n";
+        let err = layers(&[("team", team)]).unwrap_err().to_string();
+        assert!(err.contains("Layer team cannot disable forbid policy no-deletes from layer base"));
+
+        let team = r#"version = "rune/2.0"
+
+[policies]
+@id("no-deletes")
+permit(principal, action, resource);
+"#;
+        let err = layers(&[("team", team)]).unwrap_err().to_string();
+        assert!(err.contains("redefines policy no-deletes from layer base"));
+    }
+
+    #[test]
+    fn test_unknown_switch_is_rejected() {
+        let team = "version = \"rune/2.0\"\n\n[enabled]\nreadres = false\n";
+        let err = layers(&[("team", team)]).unwrap_err().to_string();
+        assert!(err.contains("readres"));
+    }
+
+    #[test]
+    fn test_duplicate_rule_ids_are_rejected() {
+        let team =
+            "version = \"rune/2.0\"\n\n[rules]\n@id(\"readers\")\ncan_read(U) :- guest(U).\n";
+        let err = layers(&[("team", team)]).unwrap_err().to_string();
+        assert!(err.contains("redefines rule readers from layer base"));
+    }
+}
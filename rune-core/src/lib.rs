@@ -2,9 +2,16 @@
 //!
 //! This crate provides the core RUNE engine with sub-millisecond authorization
 //! decisions and high-throughput policy evaluation.
+//!
+//! The `full` feature, on by default, builds the whole engine. Without it,
+//! the `minimal` build keeps the Datalog evaluator, the fact store and the
+//! decision cache, behind [`EmbeddedEngine`], and leaves out Cedar, hot
+//! reload, file watching and Tokio (see `examples/embedded.rs`).
 
 #![warn(missing_docs)]
 #![deny(unsafe_code)] // Most modules should not use unsafe code
+// Crate-internal helpers only the full engine calls
+#![cfg_attr(not(feature = "full"), allow(dead_code))]
 // Temporary clippy allows to get CI passing
 #![allow(clippy::only_used_in_recursion)]
 #![allow(clippy::should_implement_trait)]
@@ -14,25 +21,132 @@
 #![allow(clippy::while_let_loop)]
 #![allow(missing_docs)]
 
+pub mod access_requests;
+pub mod artifact;
+pub mod attributes;
+pub mod builtins;
+pub mod cache_rules;
+pub mod cache_ttl;
+pub mod canonical;
+pub mod compile_cache;
+#[cfg(feature = "full")]
+pub mod conflicts;
+#[cfg(feature = "full")]
+pub mod conformance;
+#[cfg(feature = "full")]
+pub mod coverage;
 pub mod datalog;
+pub mod decision;
+mod decision_cache;
+pub mod drift;
+pub mod embedded;
+#[cfg(feature = "full")]
 pub mod engine;
+mod epoch_cell;
 pub mod error;
+pub mod explain;
+pub mod export;
+pub mod fact_vec;
 pub mod facts;
+pub mod failure;
+pub mod flags;
+pub mod history;
+pub mod labels;
+pub mod layers;
+pub mod limits;
+pub mod migrate;
+pub mod obligations;
+#[cfg(feature = "full")]
+pub mod ownership;
 // pub mod monitoring;  // Temporarily disabled to fix CI - needs refactoring to match metrics crate API
 pub mod parser;
+#[cfg(feature = "full")]
+pub mod permissions;
+#[cfg(feature = "full")]
 pub mod policy;
+#[cfg(feature = "full")]
+pub mod policy_index;
+#[cfg(feature = "full")]
 pub mod reload;
+pub mod replica;
 pub mod request;
+pub mod routes;
+pub mod scopes;
+pub mod seed;
+pub mod sessions;
+#[cfg(feature = "full")]
+pub mod speculation;
+pub mod strings;
 pub mod types;
+#[cfg(feature = "full")]
+pub mod vocabulary;
+pub mod warnings;
+#[cfg(feature = "full")]
 pub mod watcher;
+#[cfg(feature = "full")]
+pub mod workers;
 
-pub use engine::{AuthorizationResult, Decision, RUNEEngine};
+pub use access_requests::{AccessRequest, AccessRequestStatus};
+pub use artifact::PolicyArtifact;
+pub use attributes::{
+    AttributeMergeConfig, AttributeMerger, AttributeSource, AttributeWinner, MergeStrategy,
+};
+pub use builtins::{BuiltinRegistry, BuiltinsConfig};
+pub use cache_rules::CacheRules;
+pub use cache_ttl::AdaptiveTtlConfig;
+pub use canonical::{CanonicalizationConfig, Canonicalizer};
+pub use compile_cache::CompileCache;
+#[cfg(feature = "full")]
+pub use conflicts::{Conflict, ConflictKind, ConflictSource};
+#[cfg(feature = "full")]
+pub use coverage::CoverageReport;
+pub use datalog::{
+    CancellationToken, Diagnostic, DiagnosticBag, DropGuard, FactQuery, FactStream, MissReason,
+    NearMiss, ProofNode, Severity,
+};
+pub use decision::{AuthorizationResult, Decision};
+pub use drift::{Drift, DriftChange, DriftItem, DriftTarget};
+pub use embedded::EmbeddedEngine;
+#[cfg(feature = "full")]
+pub use engine::{
+    AttributedFacts, ConfigVersion, EngineSnapshot, QueryAnswer, RUNEEngine, StagedConfig,
+};
 pub use error::{RUNEError, Result};
-pub use facts::{Fact, FactStore};
-pub use parser::parse_rune_file;
-pub use policy::PolicySet;
+pub use explain::{ExplanationRenderer, MessageCatalogs, Reason, ReasonCode, RenderedExplanation};
+pub use export::{Export, ExportFormat};
+pub use fact_vec::FactVec;
+pub use facts::{CompactionStats, Fact, FactStore, SnapshotFormat};
+pub use failure::{FailureClass, FailureMode, FailureOutcome, FailurePolicy};
+pub use flags::{FlagStatus, RuleFlags};
+pub use history::{ChangeOrigin, FactEvent, FactOp, HistoryQuery, ANONYMOUS_ACTOR};
+pub use labels::{LabelSelector, LabelTarget, Labeled, Labels};
+pub use layers::{compose, ConfigLayer, LayeredConfig, Provenance};
+pub use limits::CardinalityLimits;
+pub use migrate::{migrate, FormatVersion};
+pub use obligations::Obligation;
+#[cfg(feature = "full")]
+pub use parser::{check_sources, SourceCheck};
+pub use parser::{parse_goal, parse_rune_file};
+#[cfg(feature = "full")]
+pub use permissions::PermissionSummary;
+#[cfg(feature = "full")]
+pub use policy::{PolicyEffect, PolicyMatch, PolicyMatches, PolicySet};
+#[cfg(feature = "full")]
+pub use policy_index::{IndexedScope, PolicyIndex, TrafficScope};
+pub use replica::{ChangeBatch, ChangePosition, FactChange, ReplicaConfig, ReplicaStatus};
 pub use request::{Request, RequestBuilder};
-pub use types::{Action, Entity, Principal, Resource, Value};
+pub use routes::{RouteMatch, RouteTable};
+pub use scopes::{FactScope, FactScopes};
+pub use sessions::{SessionAttribute, SessionInfo};
+#[cfg(feature = "full")]
+pub use speculation::{SpeculationConfig, SpeculationStats};
+pub use strings::StringLimits;
+pub use types::{Action, Entity, Principal, PrincipalKind, Resource, Value};
+#[cfg(feature = "full")]
+pub use vocabulary::{KnownName, KnownPredicate, Vocabulary};
+pub use warnings::Warning;
+#[cfg(feature = "full")]
+pub use workers::WorkerConfig;
 
 /// Version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
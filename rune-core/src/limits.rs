@@ -0,0 +1,160 @@
+//! Cardinality limits on derived predicates
+//!
+//! One rule missing a join condition turns into a cartesian product, and
+//! after a reload it can eat all the memory the server has. A `[limits]`
+//! section caps how many facts a predicate may hold:
+//!
+//! ```toml
+//! can_access_resource = 5_000_000
+//! ```
+//!
+//! Evaluation stops with [`RUNEError::CardinalityExceeded`] naming the rule
+//! that pushed the predicate over its limit, and the failure policy decides
+//! the request as for any other Datalog error. The count includes stored
+//! facts of the predicate. The interpreter checks while the rule's last body
+//! atom is being joined, so an exploding rule is stopped long before it has
+//! derived everything; the dataflow backend cannot stop midway and checks
+//! its output once it is complete.
+
+use crate::datalog::types::Rule;
+use crate::error::{RUNEError, Result};
+use crate::export::rule_source;
+use crate::facts::Fact;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Maximum number of facts per predicate
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardinalityLimits {
+    limits: BTreeMap<String, usize>,
+}
+
+impl CardinalityLimits {
+    /// Create limits from a predicate -> maximum map
+    pub fn new(limits: BTreeMap<String, usize>) -> Self {
+        CardinalityLimits { limits }
+    }
+
+    /// Parse the TOML body of a `[limits]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        let limits: Self = toml::from_str(input)
+            .map_err(|e| RUNEError::ParseError(format!("Failed to parse limits section: {}", e)))?;
+        if let Some((predicate, _)) = limits.limits.iter().find(|(_, limit)| **limit == 0) {
+            return Err(RUNEError::ConfigError(format!(
+                "Limit for {} must be at least 1",
+                predicate
+            )));
+        }
+        Ok(limits)
+    }
+
+    /// Limit on `predicate`, if it has one
+    pub fn get(&self, predicate: &str) -> Option<usize> {
+        self.limits.get(predicate).copied()
+    }
+
+    /// Limited predicates and their limits, ordered by predicate
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.limits.iter().map(|(p, limit)| (p.as_str(), *limit))
+    }
+
+    /// Check whether no predicate is limited
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// First limit `facts` exceed, blaming the first of `rules` deriving
+    /// the predicate
+    pub fn check(&self, facts: &[Fact], rules: &[Rule]) -> Option<LimitExceeded> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for fact in facts {
+            *counts.entry(fact.predicate.as_ref()).or_default() += 1;
+        }
+        self.check_counts(
+            |predicate| counts.get(predicate).copied().unwrap_or(0),
+            rules,
+        )
+    }
+
+    /// First limit exceeded by the fact counts `count` reports per
+    /// predicate, blaming the first of `rules` deriving the predicate
+    pub fn check_counts(
+        &self,
+        count: impl Fn(&str) -> usize,
+        rules: &[Rule],
+    ) -> Option<LimitExceeded> {
+        self.iter().find_map(|(predicate, limit)| {
+            (count(predicate) > limit).then(|| {
+                match rules
+                    .iter()
+                    .find(|rule| rule.head.predicate.as_ref() == predicate)
+                {
+                    Some(rule) => LimitExceeded::by_rule(predicate, limit, rule),
+                    None => LimitExceeded {
+                        predicate: predicate.to_string(),
+                        limit,
+                        rule: "stored facts".to_string(),
+                    },
+                }
+            })
+        })
+    }
+}
+
+/// A predicate that grew past its limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// The predicate
+    pub predicate: String,
+    /// Its limit
+    pub limit: usize,
+    /// The rule deriving the excess, in source form
+    pub rule: String,
+}
+
+impl LimitExceeded {
+    /// `predicate` pushed past `limit` by `rule`
+    pub(crate) fn by_rule(predicate: &str, limit: usize, rule: &Rule) -> Self {
+        // Annotations would spread the source over several lines
+        let bare = Rule {
+            annotations: Default::default(),
+            ..rule.clone()
+        };
+        LimitExceeded {
+            predicate: predicate.to_string(),
+            limit,
+            rule: rule_source(&bare).unwrap_or_else(|_| rule.to_string()),
+        }
+    }
+}
+
+impl From<LimitExceeded> for RUNEError {
+    fn from(exceeded: LimitExceeded) -> Self {
+        RUNEError::CardinalityExceeded {
+            predicate: exceeded.predicate,
+            limit: exceeded.limit,
+            rule: exceeded.rule,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = CardinalityLimits::from_toml("can_access = 5_000_000\nmember = 10\n").unwrap();
+        assert_eq!(limits.get("can_access"), Some(5_000_000));
+        assert_eq!(limits.get("member"), Some(10));
+        assert_eq!(limits.get("other"), None);
+
+        assert!(CardinalityLimits::from_toml("can_access = 0\n").is_err());
+        assert!(CardinalityLimits::from_toml("can_access = -1\n").is_err());
+        assert!(CardinalityLimits::from_toml("can_access = \"many\"\n").is_err());
+    }
+}
@@ -0,0 +1,361 @@
+//! Format versions and configuration migration
+//!
+//! RUNE files declare their format with `version = "rune/<major>.<minor>"`.
+//! Format 2.0 tightens a few things that 1.0 left implicit:
+//!
+//! - every Cedar policy carries an explicit `@id`, since the positional
+//!   `policy_N` IDs 1.0 assigns shift whenever policies are reordered, which
+//!   silently breaks rule flags and audit trails keyed on them;
+//! - the `[cedar_policies]` header used by early examples is rejected in
+//!   favour of `[policies]` (1.0 ignores that section entirely).
+//!
+//! During the deprecation window the parser accepts both formats and logs a
+//! warning for 1.0 files. [`migrate`] rewrites a file to a target format and
+//! reports what it changed and what still needs a human.
+
+use crate::error::{RUNEError, Result};
+use crate::parser::{parse_annotation, section_header};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Configuration file format version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FormatVersion {
+    /// `rune/1.0` (deprecated)
+    V1,
+    /// `rune/2.0`
+    V2,
+}
+
+impl FormatVersion {
+    /// Newest supported format
+    pub const LATEST: FormatVersion = FormatVersion::V2;
+
+    /// Parse a version declaration
+    ///
+    /// Accepts `rune/2.0`, `2.0` and `2`. Pre-1.0 declarations such as
+    /// `rune/0.3` are read as 1.0, which is what the parser did before
+    /// versions were checked.
+    pub fn parse(version: &str) -> Result<Self> {
+        let number = version.trim().trim_matches('"');
+        let number = number.strip_prefix("rune/").unwrap_or(number);
+        let major = number
+            .split('.')
+            .next()
+            .and_then(|major| major.parse::<u32>().ok())
+            .ok_or_else(|| RUNEError::ParseError(format!("Invalid format version: {}", version)))?;
+
+        match major {
+            0 | 1 => Ok(FormatVersion::V1),
+            2 => Ok(FormatVersion::V2),
+            _ => Err(RUNEError::ParseError(format!(
+                "Unsupported format version: {} (latest is {})",
+                version,
+                Self::LATEST
+            ))),
+        }
+    }
+
+    /// Canonical declaration value, e.g. `rune/2.0`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FormatVersion::V1 => "rune/1.0",
+            FormatVersion::V2 => "rune/2.0",
+        }
+    }
+
+    /// Check whether the format is scheduled for removal
+    pub fn is_deprecated(self) -> bool {
+        self < Self::LATEST
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FormatVersion {
+    type Err = RUNEError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// One entry in a migration report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationNote {
+    /// 1-based line in the original file, when the note is tied to one
+    pub line: Option<usize>,
+    /// What was changed or needs doing
+    pub message: String,
+}
+
+impl MigrationNote {
+    fn at(line: usize, message: impl Into<String>) -> Self {
+        MigrationNote {
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+}
+
+/// Summary of a migration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// Format the input was written in
+    pub from: FormatVersion,
+    /// Format the output is written in
+    pub to: FormatVersion,
+    /// Rewrites applied automatically
+    pub rewrites: Vec<MigrationNote>,
+    /// Issues that need manual attention
+    pub manual_steps: Vec<MigrationNote>,
+}
+
+impl MigrationReport {
+    /// Check whether the migration left the file untouched
+    pub fn is_noop(&self) -> bool {
+        self.rewrites.is_empty()
+    }
+}
+
+/// Migrated file contents with the accompanying report
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// Rewritten configuration
+    pub output: String,
+    /// What changed and what is left to do
+    pub report: MigrationReport,
+}
+
+/// Rewrite a RUNE file into the target format version
+///
+/// Comments, ordering and formatting are preserved; only the lines that
+/// differ between formats are touched. Downgrades only rewrite the version
+/// declaration, since 2.0 files are already valid 1.0 input.
+pub fn migrate(input: &str, to: FormatVersion) -> Result<Migration> {
+    let mut rewrites = Vec::new();
+    let mut manual_steps = Vec::new();
+    let mut output = String::with_capacity(input.len() + 64);
+
+    let declared = input
+        .lines()
+        .find(|line| line.starts_with("version"))
+        .and_then(|line| line.split('=').nth(1));
+    let from = match declared {
+        Some(version) => FormatVersion::parse(version)?,
+        None => FormatVersion::V1,
+    };
+    let upgrade = from < FormatVersion::V2 && to >= FormatVersion::V2;
+
+    if declared.is_none() {
+        output.push_str(&version_line(to));
+        rewrites.push(MigrationNote {
+            line: None,
+            message: format!("Added missing version declaration ({})", to),
+        });
+    }
+
+    let mut version_seen = false;
+    let mut section = None;
+    let mut policy_sections = 0;
+    let mut policy_index = 0;
+    let mut pending_id = false;
+
+    for (index, line) in input.lines().enumerate() {
+        let number = index + 1;
+
+        if line.starts_with("version") {
+            if version_seen {
+                manual_steps.push(MigrationNote::at(
+                    number,
+                    "Line is also read as the format version declaration; rename the key",
+                ));
+                push_line(&mut output, line);
+            } else if from == to {
+                push_line(&mut output, line);
+            } else {
+                if let Some(name) = section {
+                    manual_steps.push(MigrationNote::at(
+                        number,
+                        format!(
+                            "`version` key in [{}] is read as the format declaration; \
+                             move the declaration to the top of the file",
+                            name
+                        ),
+                    ));
+                }
+                output.push_str(&version_line(to));
+                rewrites.push(MigrationNote::at(
+                    number,
+                    format!("Version declaration changed from {} to {}", from, to),
+                ));
+            }
+            version_seen = true;
+            continue;
+        }
+
+        if let Some(header) = section_header(line) {
+            section = Some(header);
+            if matches!(header, "policies" | "cedar_policies") {
+                policy_sections += 1;
+                policy_index = 0;
+                pending_id = false;
+                if policy_sections == 2 {
+                    manual_steps.push(MigrationNote::at(
+                        number,
+                        "Only the last policy section is loaded; merge the policy sections",
+                    ));
+                }
+            }
+            if upgrade && header == "cedar_policies" {
+                output.push_str("[policies]\n");
+                rewrites.push(MigrationNote::at(
+                    number,
+                    "Renamed [cedar_policies] to [policies]",
+                ));
+                manual_steps.push(MigrationNote::at(
+                    number,
+                    "Policies under [cedar_policies] were ignored by the 1.0 parser and \
+                     take effect after migration; review them before deploying",
+                ));
+                continue;
+            }
+            push_line(&mut output, line);
+            continue;
+        }
+
+        if upgrade && matches!(section, Some("policies" | "cedar_policies")) {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with('@') {
+                if let Ok((key, _)) = parse_annotation(trimmed) {
+                    pending_id |= key == "id";
+                }
+            } else if line.starts_with("permit") || line.starts_with("forbid") {
+                if !pending_id {
+                    // Pin the ID 1.0 would have generated so flags keep working
+                    let id = format!("policy_{}", policy_index);
+                    output.push_str(&format!("@id(\"{}\")\n", id));
+                    rewrites.push(MigrationNote::at(
+                        number,
+                        format!("Added @id(\"{}\") to policy", id),
+                    ));
+                }
+                policy_index += 1;
+                pending_id = false;
+            }
+        }
+
+        push_line(&mut output, line);
+    }
+
+    Ok(Migration {
+        output,
+        report: MigrationReport {
+            from,
+            to,
+            rewrites,
+            manual_steps,
+        },
+    })
+}
+
+fn version_line(version: FormatVersion) -> String {
+    format!("version = \"{}\"\n", version)
+}
+
+fn push_line(output: &mut String, line: &str) {
+    output.push_str(line);
+    output.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rune_file;
+
+    const V1_CONFIG: &str = r#"version = "rune/1.0"
+
+[rules]
+user(alice).
+
+[cedar_policies]
+@id("read-docs")
+permit(principal, action, resource);
+
+forbid(principal, action, resource)
+when { context.locked };
+"#;
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(FormatVersion::parse("rune/1.0").unwrap(), FormatVersion::V1);
+        assert_eq!(FormatVersion::parse("\"1.0\"").unwrap(), FormatVersion::V1);
+        assert_eq!(FormatVersion::parse("rune/0.3").unwrap(), FormatVersion::V1);
+        assert_eq!(FormatVersion::parse("2.0").unwrap(), FormatVersion::V2);
+        assert!(FormatVersion::parse("rune/3.0").is_err());
+        assert!(FormatVersion::parse("latest").is_err());
+        assert!(FormatVersion::V1.is_deprecated());
+        assert!(!FormatVersion::LATEST.is_deprecated());
+    }
+
+    #[test]
+    fn test_upgrade_to_v2() {
+        let migration = migrate(V1_CONFIG, FormatVersion::V2).unwrap();
+        let report = &migration.report;
+        assert_eq!(report.from, FormatVersion::V1);
+        assert_eq!(report.rewrites.len(), 3);
+        assert_eq!(report.manual_steps.len(), 1);
+
+        assert!(migration.output.starts_with("version = \"rune/2.0\"\n"));
+        assert!(migration.output.contains("[policies]\n"));
+        assert!(migration
+            .output
+            .contains("@id(\"policy_1\")\nforbid(principal, action, resource)"));
+        // Explicit IDs are left alone
+        assert_eq!(migration.output.matches("@id(\"read-docs\")").count(), 1);
+
+        let config = parse_rune_file(&migration.output).unwrap();
+        let ids: Vec<_> = config.policies.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, vec!["read-docs", "policy_1"]);
+    }
+
+    #[test]
+    fn test_migration_is_idempotent() {
+        let once = migrate(V1_CONFIG, FormatVersion::V2).unwrap();
+        let twice = migrate(&once.output, FormatVersion::V2).unwrap();
+        assert!(twice.report.is_noop());
+        assert_eq!(twice.output, once.output);
+    }
+
+    #[test]
+    fn test_downgrade_only_touches_version() {
+        let upgraded = migrate(V1_CONFIG, FormatVersion::V2).unwrap().output;
+        let downgraded = migrate(&upgraded, FormatVersion::V1).unwrap();
+        assert_eq!(downgraded.report.rewrites.len(), 1);
+        assert_eq!(downgraded.output.replace("rune/1.0", "rune/2.0"), upgraded);
+    }
+
+    #[test]
+    fn test_version_declarations_outside_header() {
+        let missing = migrate("[rules]\nuser(alice).\n", FormatVersion::V2).unwrap();
+        assert!(missing
+            .output
+            .starts_with("version = \"rune/2.0\"\n[rules]"));
+        assert!(missing.report.rewrites[0].line.is_none());
+
+        // 1.0 reads a `version` key inside a section as the declaration
+        let nested = migrate(
+            "[data]\nversion = \"0.1.0\"\n\n[rules]\nuser(alice).\n",
+            FormatVersion::V2,
+        )
+        .unwrap();
+        assert_eq!(nested.report.from, FormatVersion::V1);
+        assert_eq!(nested.report.manual_steps[0].line, Some(2));
+        assert!(nested.output.contains("[data]\nversion = \"rune/2.0\"\n"));
+    }
+}
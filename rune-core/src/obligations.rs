@@ -0,0 +1,116 @@
+//! Obligations attached to permit policies
+//!
+//! A permit policy can carry obligations for whoever enforces the decision.
+//! They are declared with an `@obligation` annotation listing
+//! `Header: value` pairs separated by `;`:
+//!
+//! ```text
+//! @id("reads")
+//! @obligation("X-Allowed-Fields: name,email; X-Rate-Remaining: 100")
+//! permit(principal, action == Action::"read", resource);
+//! ```
+//!
+//! A permitted request carries the obligations of every policy that
+//! permitted it. In forward-auth mode rune-server injects them as headers on
+//! its 200 response, which the fronting proxy copies onto the upstream
+//! request. Denials carry none.
+
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Policy annotation declaring obligations
+pub const OBLIGATION_ANNOTATION: &str = "obligation";
+
+/// Header prefix reserved for rune-server's own forward-auth headers
+const RESERVED_PREFIX: &str = "x-rune-";
+
+/// A header to set when enforcing a permit
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Obligation {
+    /// Header name, as written in the policy
+    pub header: String,
+    /// Header value
+    pub value: String,
+}
+
+impl Obligation {
+    /// Parse one `Header: value` pair
+    pub fn parse(input: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            RUNEError::ConfigError(format!("Invalid obligation '{}': {}", input, reason))
+        };
+
+        let (header, value) = input
+            .split_once(':')
+            .ok_or_else(|| invalid("expected 'Header: value'"))?;
+        let header = header.trim();
+        let value = value.trim();
+
+        if header.is_empty() || !header.bytes().all(is_token_byte) {
+            return Err(invalid("header name must be a non-empty HTTP token"));
+        }
+        if header.to_ascii_lowercase().starts_with(RESERVED_PREFIX) {
+            return Err(invalid("X-Rune-* headers are reserved"));
+        }
+        if !value
+            .bytes()
+            .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+        {
+            return Err(invalid("header value must be printable ASCII"));
+        }
+
+        Ok(Obligation {
+            header: header.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// Parse the `;`-separated pairs of an `@obligation` annotation
+pub fn parse_obligations(annotation: &str) -> Result<Vec<Obligation>> {
+    annotation
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(Obligation::parse)
+        .collect()
+}
+
+/// Characters allowed in an HTTP header name (RFC 9110 `tchar`)
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_obligations() {
+        let obligations =
+            parse_obligations("X-Allowed-Fields: name,email; X-Rate-Remaining: 100;").unwrap();
+        assert_eq!(
+            obligations,
+            vec![
+                Obligation {
+                    header: "X-Allowed-Fields".into(),
+                    value: "name,email".into(),
+                },
+                Obligation {
+                    header: "X-Rate-Remaining".into(),
+                    value: "100".into(),
+                },
+            ]
+        );
+        assert!(parse_obligations("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_obligations() {
+        assert!(parse_obligations("X-Allowed-Fields").is_err());
+        assert!(parse_obligations("Bad Header: 1").is_err());
+        assert!(parse_obligations(": 1").is_err());
+        assert!(parse_obligations("X-Rune-Decision: PERMIT").is_err());
+        assert!(parse_obligations("X-Note: caf\u{e9}").is_err());
+    }
+}
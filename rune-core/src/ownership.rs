@@ -0,0 +1,154 @@
+//! Rule and policy ownership
+//!
+//! A rule or policy annotated `@owner("team")` belongs to that team:
+//!
+//! ```text
+//! @id("payments-refunds") @owner("payments")
+//! permit(principal in Group::"payments", action == Action::"refund", resource);
+//! ```
+//!
+//! The `_as` management methods of [`crate::RUNEEngine`] take the actor
+//! making the change and refuse it unless the actor is a member of every
+//! team owning something the change touches. A reload touches an owned
+//! rule or policy when it adds, removes or rewrites it, including adding or
+//! dropping the `@owner` annotation; a flag touches every rule and policy
+//! it switches. Unowned rules and policies may be changed by anyone. The
+//! anonymous actor ([`crate::history::ANONYMOUS_ACTOR`]) belongs to no
+//! team, so callers must establish who the actor is, e.g. from a
+//! credential, before changing owned rules and policies on its behalf.
+//!
+//! Membership is decided by the engine itself, from its own fixpoint, as
+//! for [`crate::permissions`]: groups granted with
+//! [`crate::RUNEEngine::add_to_group`], nested groups and group rules all
+//! count, so the teams that own policies are managed like any other group.
+
+use crate::datalog::Rule;
+use crate::labels::{LabelTarget, Labeled};
+use crate::policy::PolicySet;
+use std::collections::BTreeSet;
+
+/// Annotation naming the team that owns a rule or policy
+pub const OWNER_ANNOTATION: &str = "owner";
+
+/// A rule or policy with an owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OwnedItem {
+    /// Whether it is a rule or a policy
+    pub target: LabelTarget,
+    /// Its `@id`, the Cedar policy ID, or a rule's text
+    pub id: String,
+    /// Owning team
+    pub owner: String,
+    /// Flag key switching it on and off, if any
+    pub flag: Option<String>,
+    /// Source text, annotations included, to tell rewrites apart
+    pub source: String,
+}
+
+impl OwnedItem {
+    /// Ownership of a rule, if it has an owner
+    pub(crate) fn rule(rule: &Rule) -> Option<Self> {
+        let owner = rule.annotations.get(OWNER_ANNOTATION)?.clone();
+        Some(OwnedItem {
+            target: LabelTarget::Rule,
+            id: Labeled::rule(rule).id,
+            owner,
+            flag: rule.flag_key().map(str::to_string),
+            source: format!("{:?} {}", rule.annotations, rule),
+        })
+    }
+}
+
+/// Owned rules and policies of a configuration
+pub(crate) fn owned_items(rules: &[Rule], policies: &PolicySet) -> Vec<OwnedItem> {
+    rules
+        .iter()
+        .filter_map(OwnedItem::rule)
+        .chain(policies.owned())
+        .collect()
+}
+
+/// Teams owning a rule or policy that differs between `before` and `after`
+pub(crate) fn changed_owners(before: &[OwnedItem], after: &[OwnedItem]) -> BTreeSet<String> {
+    let removed = before.iter().filter(|item| !after.contains(item));
+    let added = after.iter().filter(|item| !before.contains(item));
+    removed
+        .chain(added)
+        .map(|item| item.owner.clone())
+        .collect()
+}
+
+/// Teams owning a rule or policy switched by the flag `key`
+pub(crate) fn flag_owners(items: &[OwnedItem], key: &str) -> BTreeSet<String> {
+    items
+        .iter()
+        .filter(|item| item.flag.as_deref() == Some(key))
+        .map(|item| item.owner.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rules;
+
+    fn items(rules: &str, policies: &str) -> Vec<OwnedItem> {
+        let mut set = PolicySet::new();
+        set.load_policies(policies).unwrap();
+        owned_items(&parse_rules(rules).unwrap(), &set)
+    }
+
+    #[test]
+    fn test_changed_owners() {
+        let before = items(
+            "@owner(\"hr\")\nstaff(U) :- member(U, \"hr\").\nreader(U) :- staff(U).",
+            r#"
+@id("refunds") @owner("payments")
+permit(principal, action == Action::"refund", resource);
+@id("open") @flag("open-read")
+permit(principal, action == Action::"read", resource);
+"#,
+        );
+        // Unchanged owned items and changed unowned ones touch no one
+        let after = items(
+            "@owner(\"hr\")\nstaff(U) :- member(U, \"hr\").\nreader(U) :- member(U, G).",
+            r#"
+@id("refunds") @owner("payments")
+permit(principal, action == Action::"refund", resource);
+@id("open") @flag("open-read")
+permit(principal, action, resource);
+"#,
+        );
+        assert!(changed_owners(&before, &after).is_empty());
+
+        // Rewriting a payments policy and dropping the hr rule
+        let after = items(
+            "reader(U) :- member(U, G).",
+            r#"
+@id("refunds") @owner("payments")
+permit(principal, action, resource);
+"#,
+        );
+        assert_eq!(
+            changed_owners(&before, &after),
+            BTreeSet::from(["hr".to_string(), "payments".to_string()])
+        );
+
+        // Claiming a policy needs the claiming team
+        let after = items(
+            "@owner(\"hr\")\nstaff(U) :- member(U, \"hr\").",
+            r#"
+@id("refunds") @owner("payments")
+permit(principal, action == Action::"refund", resource);
+@id("open") @flag("open-read") @owner("security")
+permit(principal, action == Action::"read", resource);
+"#,
+        );
+        assert_eq!(
+            changed_owners(&before, &after),
+            BTreeSet::from(["security".to_string()])
+        );
+        assert!(flag_owners(&after, "open-read").contains("security"));
+        assert!(flag_owners(&before, "open-read").is_empty());
+    }
+}
@@ -1,22 +1,118 @@
 //! Parser for RUNE configuration files
 
-use crate::datalog::types::{Atom as DatalogAtom, Rule as DatalogRule, Term as DatalogTerm};
+use crate::artifact::PolicyArtifact;
+use crate::attributes::AttributeMergeConfig;
+use crate::builtins::BuiltinsConfig;
+use crate::cache_rules::CacheRules;
+use crate::canonical::CanonicalizationConfig;
+use crate::compile_cache::{CompileCache, ParsedRules};
+use crate::datalog::diagnostics::{closest_match, Diagnostic, DiagnosticBag, Span, Suggestion};
+use crate::datalog::types::{
+    Atom as DatalogAtom, CompareOp, Guard, Rule as DatalogRule, Term as DatalogTerm,
+};
+use crate::datalog::EvaluationConfig;
 use crate::error::{RUNEError, Result};
+use crate::explain::MessageCatalogs;
+use crate::limits::CardinalityLimits;
+use crate::migrate::FormatVersion;
+#[cfg(feature = "full")]
+use crate::policy::{parse_error_range, PolicySet};
+use crate::routes::RouteTable;
+use crate::scopes::FactScopes;
+use crate::strings::StringLimits;
 use crate::types::Value;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tracing::warn;
+
+/// Codes for the diagnostics the parser reports
+///
+/// `E` codes are errors that reject the file; `W` codes are warnings kept
+/// in [`RUNEConfig::warnings`].
+pub mod codes {
+    /// The file has no `version = ...` line
+    pub const MISSING_VERSION: &str = "E0001";
+    /// A section uses a header its format version renamed
+    pub const RENAMED_SECTION: &str = "E0002";
+    /// The `[data]` section is not valid TOML
+    pub const INVALID_DATA: &str = "E0003";
+    /// A rule has a `(` without a `)` or the other way round
+    pub const UNBALANCED_PARENS: &str = "E0101";
+    /// Two body elements are not separated by `,` or `;`
+    pub const MISSING_SEPARATOR: &str = "E0102";
+    /// An atom has an empty predicate or one with spaces in it
+    pub const INVALID_PREDICATE: &str = "E0103";
+    /// An annotation line cannot be read
+    pub const INVALID_ANNOTATION: &str = "E0104";
+    /// Any other malformed rule
+    pub const INVALID_RULE: &str = "E0105";
+    /// A 2.0 policy without an `@id` annotation
+    pub const MISSING_POLICY_ID: &str = "E0201";
+    /// A rule body uses a predicate no rule defines, close to one that is
+    pub const UNDEFINED_PREDICATE: &str = "W0101";
+}
 
 /// Parsed RUNE configuration
 #[derive(Debug, Clone)]
 pub struct RUNEConfig {
     /// Version string
     pub version: String,
+    /// File format the version string declares
+    pub format: FormatVersion,
     /// Data section (TOML-style)
     pub data: toml::Value,
     /// Datalog rules (not serializable as they're parsed at runtime)
     pub rules: Vec<DatalogRule>,
     /// Cedar policies
     pub policies: Vec<Policy>,
+    /// Identifier canonicalization, if a `[canonicalize]` section is present
+    pub canonicalize: Option<CanonicalizationConfig>,
+    /// HTTP route mappings for forward-auth, if a `[routes]` section is present
+    pub routes: Option<RouteTable>,
+    /// Fact views for `@scope` rules, if a `[scopes]` section is present
+    pub scopes: Option<FactScopes>,
+    /// Caps on derived predicates, if a `[limits]` section is present
+    pub limits: Option<CardinalityLimits>,
+    /// Decisions kept out of the cache, if a `[cache]` section is present
+    pub cache: Option<CacheRules>,
+    /// Principal attribute merging, if an `[attributes]` section is present
+    pub attributes: Option<AttributeMergeConfig>,
+    /// Explanation message catalogs, if a `[messages]` section is present
+    pub messages: Option<MessageCatalogs>,
+    /// WASM-hosted builtin predicates, if a `[builtins]` section is present
+    pub builtins: Option<BuiltinsConfig>,
+    /// String and regex builtin limits, if a `[strings]` section is present
+    pub strings: Option<StringLimits>,
+    /// Evaluation backend, if an `[evaluation]` section is present
+    pub evaluation: Option<EvaluationConfig>,
+    /// Rule and policy switches from an `[enabled]` section, keyed like
+    /// runtime flags (see [`crate::layers`])
+    pub enabled: BTreeMap<String, bool>,
+    /// Digest of the file, with the commit and signer its `[artifact]`
+    /// section states (see [`crate::artifact`])
+    pub artifact: PolicyArtifact,
+    /// Likely mistakes that do not stop the file from loading
+    pub warnings: DiagnosticBag,
+}
+
+impl RUNEConfig {
+    /// Mark the configuration as read from `path`, so relative module paths
+    /// in its `[builtins]` section resolve against the file's directory
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        if let Some(builtins) = &mut self.builtins {
+            let dir = path.as_ref().parent().unwrap_or(Path::new(""));
+            builtins.dir = Some(if dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                dir.to_path_buf()
+            });
+        }
+        self
+    }
 }
 
 /// A Cedar policy in the RUNE file
@@ -29,100 +125,447 @@ pub struct Policy {
 }
 
 /// Parse a RUNE configuration file
+///
+/// Syntax errors come back as [`RUNEError::DiagnosticError`] with spans
+/// into `input`, so they can be shown against the file.
 pub fn parse_rune_file(input: &str) -> Result<RUNEConfig> {
+    parse_rune_file_with(input, None)
+}
+
+/// Parse a RUNE configuration file, reading and keeping its rules in
+/// `cache` if given (see [`crate::compile_cache`])
+pub(crate) fn parse_rune_file_with(
+    input: &str,
+    cache: Option<&CompileCache>,
+) -> Result<RUNEConfig> {
     // Split file into sections
     let sections = split_sections(input)?;
 
     // Parse version
-    let version = sections
-        .version
-        .ok_or_else(|| RUNEError::ParseError("Missing version declaration".into()))?;
+    let version = sections.version.clone().ok_or_else(|| {
+        RUNEError::from_diagnostic(
+            Diagnostic::error("Missing version declaration")
+                .with_code(codes::MISSING_VERSION)
+                .with_help("a RUNE file declares its format before any section")
+                .with_suggestion(
+                    Suggestion::new("declare the current format on the first line")
+                        .with_replacement(format!("version = \"{}\"", FormatVersion::LATEST)),
+                ),
+        )
+    })?;
+    let format = FormatVersion::parse(&version)?;
+    match format {
+        FormatVersion::V1 => warn!(
+            "Format {} is deprecated; upgrade with `rune migrate --to 2.0`",
+            version
+        ),
+        FormatVersion::V2 if sections.cedar_policies.is_some() => {
+            let mut diagnostic =
+                Diagnostic::error("[cedar_policies] was renamed to [policies] in rune/2.0")
+                    .with_code(codes::RENAMED_SECTION);
+            if let Some(header) = sections.header_span(input, "cedar_policies") {
+                diagnostic = diagnostic.with_span(header.clone()).with_suggestion(
+                    Suggestion::new("rename the section")
+                        .with_replacement("[policies]")
+                        .with_span(header),
+                );
+            }
+            return Err(RUNEError::from_diagnostic(diagnostic));
+        }
+        FormatVersion::V2 => {}
+    }
 
     // Parse data section as TOML
-    let data = if let Some(data_str) = sections.data {
-        toml::from_str(&data_str)
-            .map_err(|e| RUNEError::ParseError(format!("Failed to parse data section: {}", e)))?
+    let data = if let Some(data_str) = &sections.data {
+        toml::from_str(data_str).map_err(|e| {
+            let mut diagnostic =
+                Diagnostic::error(format!("Failed to parse data section: {}", e.message()))
+                    .with_code(codes::INVALID_DATA);
+            if let (Some(range), Some(offset)) = (e.span(), sections.content_offset("data")) {
+                diagnostic = diagnostic.with_span(Span::locate(
+                    input,
+                    offset + range.start,
+                    offset + range.end,
+                ));
+            }
+            RUNEError::from_diagnostic(diagnostic)
+        })?
     } else {
         toml::Value::Table(toml::map::Map::new())
     };
 
-    // Parse rules (simplified for now)
-    let rules = if let Some(rules_str) = sections.rules {
-        parse_rules(&rules_str)?
+    // Parse rules
+    let mut warnings = DiagnosticBag::new();
+    let rules = if let Some(rules_str) = &sections.rules {
+        let parsed = match cache.and_then(|cache| cache.load(input)) {
+            Some(parsed) => parsed,
+            None => {
+                let offset = sections.content_offset("rules").unwrap_or(0);
+                let rules_str = blank_comments(rules_str, Comments::Rules);
+                let rules = parse_rules_at(&rules_str, input, offset)?;
+                let mut found = DiagnosticBag::new();
+                found.extend(undefined_predicates(&rules, &rules_str, input, offset));
+                let parsed = ParsedRules {
+                    rules,
+                    warnings: found,
+                };
+                if let Some(cache) = cache {
+                    cache.store(input, &parsed);
+                }
+                parsed
+            }
+        };
+        warnings.extend(parsed.warnings.diagnostics().iter().cloned());
+        parsed.rules
     } else {
         Vec::new()
     };
 
     // Parse policies
-    let policies = if let Some(policies_str) = sections.policies {
-        parse_policies(&policies_str)?
+    let policies_str = sections
+        .policies
+        .as_deref()
+        .map(|text| blank_comments(text, Comments::Cedar));
+    let policies = if let Some(policies_str) = &policies_str {
+        parse_policies(policies_str)?
     } else {
         Vec::new()
     };
+    if format >= FormatVersion::V2 {
+        let mut missing = DiagnosticBag::new();
+        let text = policies_str.as_deref().unwrap_or_default();
+        let offset = sections.content_offset("policies").unwrap_or(0);
+        let mut cursor = 0;
+        for policy in &policies {
+            // Policies appear in the section in order, so search on from
+            // the previous one
+            let first_line = policy.content.lines().next().unwrap_or_default();
+            let found = text[cursor..].find(first_line).map(|i| cursor + i);
+            if let Some(at) = found {
+                cursor = at + first_line.len();
+            }
+            if has_explicit_id(policy) {
+                continue;
+            }
+            let mut diagnostic = Diagnostic::error(format!(
+                "Policy {} needs an @id annotation in {}",
+                policy.id, format
+            ))
+            .with_code(codes::MISSING_POLICY_ID)
+            .with_help("rune/2.0 names every policy explicitly so IDs survive reordering");
+            if let Some(at) = found {
+                let span = Span::locate(input, offset + at, offset + cursor);
+                let indent = &first_line[..first_line.len() - first_line.trim_start().len()];
+                let insert_at = Span::locate(input, offset + at, offset + at);
+                diagnostic = diagnostic.with_span(span).with_suggestion(
+                    Suggestion::new("add an @id annotation")
+                        .with_replacement(format!("{}@id(\"{}\")\n", indent, policy.id))
+                        .with_span(insert_at),
+                );
+            }
+            missing.add(diagnostic);
+        }
+        if missing.has_errors() {
+            return Err(RUNEError::from_diagnostics(missing));
+        }
+    }
+
+    // Parse canonicalization rules
+    let canonicalize = sections
+        .canonicalize
+        .map(|section| CanonicalizationConfig::from_toml(&section))
+        .transpose()?;
+
+    // Parse forward-auth route mappings
+    let routes = sections
+        .routes
+        .map(|section| RouteTable::parse(&section))
+        .transpose()?;
+
+    // Parse fact scopes
+    let scopes = sections
+        .scopes
+        .map(|section| FactScopes::from_toml(&section))
+        .transpose()?;
+
+    // Parse derived predicate limits
+    let limits = sections
+        .limits
+        .map(|section| CardinalityLimits::from_toml(&section))
+        .transpose()?;
+
+    // Parse decision cache rules
+    let cache = sections
+        .cache
+        .map(|section| CacheRules::from_toml(&section))
+        .transpose()?;
+
+    // Parse principal attribute merging
+    let attributes = sections
+        .attributes
+        .map(|section| AttributeMergeConfig::from_toml(&section))
+        .transpose()?;
+
+    // Parse explanation message catalogs
+    let messages = sections
+        .messages
+        .map(|section| MessageCatalogs::from_toml(&section))
+        .transpose()?;
+
+    // Parse builtin predicate modules
+    let builtins = sections
+        .builtins
+        .map(|section| BuiltinsConfig::from_toml(&section))
+        .transpose()?;
+
+    // Parse string builtin limits
+    let strings = sections
+        .strings
+        .map(|section| StringLimits::from_toml(&section))
+        .transpose()?;
+
+    // Parse evaluation settings
+    let evaluation = sections
+        .evaluation
+        .map(|section| EvaluationConfig::from_toml(&section))
+        .transpose()?;
+
+    // Parse rule and policy switches
+    let enabled = sections
+        .enabled
+        .map(|section| {
+            toml::from_str(&section).map_err(|e| {
+                RUNEError::ParseError(format!("Failed to parse enabled section: {}", e))
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    // Identify the artifact
+    let artifact = PolicyArtifact::of_source(input, sections.artifact.as_deref())?;
 
     Ok(RUNEConfig {
         version,
+        format,
         data,
         rules,
         policies,
+        canonicalize,
+        routes,
+        scopes,
+        limits,
+        cache,
+        attributes,
+        messages,
+        builtins,
+        strings,
+        evaluation,
+        enabled,
+        artifact,
+        warnings,
     })
 }
 
+/// Problems found in policy and rule text without loading it
+#[cfg(feature = "full")]
+#[derive(Debug, Clone, Default)]
+pub struct SourceCheck {
+    /// Policies found in the policy text
+    pub policies: usize,
+    /// Rules found in the rule text, after disjunctions are expanded
+    pub rules: usize,
+    /// Problems with the policies, with spans into the policy text
+    pub policy_diagnostics: DiagnosticBag,
+    /// Problems with the rules, with spans into the rule text
+    pub rule_diagnostics: DiagnosticBag,
+}
+
+#[cfg(feature = "full")]
+impl SourceCheck {
+    /// Whether both texts would load, warnings aside
+    pub fn is_valid(&self) -> bool {
+        !self.policy_diagnostics.has_errors() && !self.rule_diagnostics.has_errors()
+    }
+}
+
+/// Check the contents of a `[policies]` section and, optionally, a
+/// `[rules]` section the way loading a file would, without loading them
+///
+/// The same checks run, with the same messages, as when a file holding the
+/// texts is reloaded. Every problem is reported rather than only the first,
+/// and Cedar errors point at the offending text.
+#[cfg(feature = "full")]
+pub fn check_sources(policies: &str, rules: Option<&str>) -> SourceCheck {
+    let mut check = SourceCheck::default();
+
+    if let Some(source) = rules {
+        let text = blank_comments(source, Comments::Rules);
+        match parse_rules_at(&text, source, 0) {
+            Ok(parsed) => {
+                check.rules = parsed.len();
+                check
+                    .rule_diagnostics
+                    .extend(undefined_predicates(&parsed, &text, source, 0));
+            }
+            Err(error) => check.rule_diagnostics.extend(error_diagnostics(error)),
+        }
+    }
+
+    let source = policies;
+    let policies = &*blank_comments(source, Comments::Cedar);
+    let parsed = match parse_policies(policies) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            check.policy_diagnostics.extend(error_diagnostics(error));
+            return check;
+        }
+    };
+    check.policies = parsed.len();
+    let mut set = PolicySet::new();
+    let mut cursor = 0;
+    for policy in &parsed {
+        // Policies appear in the text in order, so search on from the
+        // previous one
+        let first_line = policy.content.lines().next().unwrap_or_default();
+        let start = policies[cursor..].find(first_line).map(|i| cursor + i);
+        if let Some(at) = start {
+            cursor = at + first_line.len();
+        }
+
+        if let Err(error) = set.add_policy(&policy.id, &policy.content) {
+            let mut diagnostic = Diagnostic::error(error.to_string());
+            if let Some(at) = start {
+                let range = parse_error_range(&policy.content).unwrap_or(0..first_line.len());
+                diagnostic =
+                    diagnostic.with_span(Span::locate(source, at + range.start, at + range.end));
+            }
+            check.policy_diagnostics.add(diagnostic);
+        }
+    }
+    check
+}
+
+/// Diagnostics carried by an error, or one made from its message
+#[cfg(feature = "full")]
+fn error_diagnostics(error: RUNEError) -> Vec<Diagnostic> {
+    match error {
+        RUNEError::DiagnosticError(bag) => bag.diagnostics().to_vec(),
+        other => vec![Diagnostic::error(other.to_string())],
+    }
+}
+
 /// Sections in a RUNE file
 struct Sections {
     version: Option<String>,
     data: Option<String>,
     rules: Option<String>,
     policies: Option<String>,
+    canonicalize: Option<String>,
+    routes: Option<String>,
+    scopes: Option<String>,
+    limits: Option<String>,
+    cache: Option<String>,
+    attributes: Option<String>,
+    messages: Option<String>,
+    builtins: Option<String>,
+    strings: Option<String>,
+    evaluation: Option<String>,
+    enabled: Option<String>,
+    artifact: Option<String>,
+    /// Pre-2.0 policy header, ignored by the 1.0 format
+    cedar_policies: Option<String>,
+    /// Byte ranges of each saved section's header line and where its
+    /// content starts
+    offsets: BTreeMap<&'static str, (Range<usize>, usize)>,
+}
+
+impl Sections {
+    /// Where a section's content starts in the file
+    fn content_offset(&self, name: &str) -> Option<usize> {
+        self.offsets.get(name).map(|(_, content)| *content)
+    }
+
+    /// Span of a section's header line
+    fn header_span(&self, input: &str, name: &str) -> Option<Span> {
+        let (header, _) = self.offsets.get(name)?;
+        Some(Span::locate(input, header.start, header.end))
+    }
 }
 
 /// Split input into sections
+///
+/// Section content is kept verbatim, so offsets into it plus the section's
+/// content offset are offsets into the file.
 fn split_sections(input: &str) -> Result<Sections> {
     let mut sections = Sections {
         version: None,
         data: None,
         rules: None,
         policies: None,
+        canonicalize: None,
+        routes: None,
+        scopes: None,
+        limits: None,
+        cache: None,
+        attributes: None,
+        messages: None,
+        builtins: None,
+        strings: None,
+        evaluation: None,
+        enabled: None,
+        artifact: None,
+        cedar_policies: None,
+        offsets: BTreeMap::new(),
     };
 
     let mut current_section = None;
     let mut section_content = String::new();
+    let mut offset = 0;
+
+    for raw in input.split_inclusive('\n') {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.trim_end_matches(['\n', '\r']);
 
-    for line in input.lines() {
         if line.starts_with("version") {
             // Save previous section
-            save_section(&mut sections, current_section, &section_content);
+            save_located(&mut sections, current_section.take(), &section_content);
             section_content.clear();
 
             // Extract version
             if let Some(version) = line.split('=').nth(1) {
                 sections.version = Some(version.trim().trim_matches('"').to_string());
             }
-            current_section = None;
-        } else if line.starts_with("[data]") {
-            save_section(&mut sections, current_section, &section_content);
+        } else if let Some(header) = section_header(line) {
+            save_located(&mut sections, current_section.take(), &section_content);
             section_content.clear();
-            current_section = Some("data");
-        } else if line.starts_with("[rules]") {
-            save_section(&mut sections, current_section, &section_content);
-            section_content.clear();
-            current_section = Some("rules");
-        } else if line.starts_with("[policies]") {
-            save_section(&mut sections, current_section, &section_content);
-            section_content.clear();
-            current_section = Some("policies");
+            current_section = Some((header, (start..start + line.len(), offset)));
         } else if current_section.is_some() {
-            section_content.push_str(line);
-            section_content.push('\n');
+            section_content.push_str(raw);
         }
     }
 
     // Save last section
-    save_section(&mut sections, current_section, &section_content);
+    save_located(&mut sections, current_section, &section_content);
 
     Ok(sections)
 }
 
+/// Save section content along with where it was in the file
+fn save_located(
+    sections: &mut Sections,
+    section: Option<(&'static str, (Range<usize>, usize))>,
+    content: &str,
+) {
+    let Some((name, location)) = section else {
+        return;
+    };
+    save_section(sections, Some(name), content);
+    if !content.is_empty() {
+        sections.offsets.insert(name, location);
+    }
+}
+
 /// Save section content
 fn save_section(sections: &mut Sections, section_name: Option<&str>, content: &str) {
     if content.is_empty() {
@@ -133,21 +576,86 @@ fn save_section(sections: &mut Sections, section_name: Option<&str>, content: &s
         Some("data") => sections.data = Some(content.to_string()),
         Some("rules") => sections.rules = Some(content.to_string()),
         Some("policies") => sections.policies = Some(content.to_string()),
+        Some("canonicalize") => sections.canonicalize = Some(content.to_string()),
+        Some("routes") => sections.routes = Some(content.to_string()),
+        Some("scopes") => sections.scopes = Some(content.to_string()),
+        Some("limits") => sections.limits = Some(content.to_string()),
+        Some("cache") => sections.cache = Some(content.to_string()),
+        Some("attributes") => sections.attributes = Some(content.to_string()),
+        Some("messages") => sections.messages = Some(content.to_string()),
+        Some("builtins") => sections.builtins = Some(content.to_string()),
+        Some("strings") => sections.strings = Some(content.to_string()),
+        Some("evaluation") => sections.evaluation = Some(content.to_string()),
+        Some("enabled") => sections.enabled = Some(content.to_string()),
+        Some("artifact") => sections.artifact = Some(content.to_string()),
+        Some("cedar_policies") => sections.cedar_policies = Some(content.to_string()),
         _ => {}
     }
 }
 
+/// Name of the section a header line opens, if it is one the parser knows
+pub(crate) fn section_header(line: &str) -> Option<&'static str> {
+    [
+        "data",
+        "rules",
+        "policies",
+        "canonicalize",
+        "routes",
+        "scopes",
+        "limits",
+        "cache",
+        "attributes",
+        "messages",
+        "builtins",
+        "strings",
+        "evaluation",
+        "enabled",
+        "artifact",
+        "cedar_policies",
+    ]
+    .into_iter()
+    .find(|name| {
+        line.strip_prefix('[')
+            .and_then(|rest| rest.strip_prefix(name))
+            .is_some_and(|rest| rest.starts_with(']'))
+    })
+}
+
+/// Check whether a policy's ID comes from an `@id` annotation
+pub(crate) fn has_explicit_id(policy: &Policy) -> bool {
+    policy_annotation(policy, "id").is_some()
+}
+
+/// Value of one of the annotations heading a policy
+pub(crate) fn policy_annotation(policy: &Policy, name: &str) -> Option<String> {
+    policy
+        .content
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.starts_with('@'))
+        .find_map(|line| match parse_annotation(line) {
+            Ok((key, value)) if key == name => Some(value),
+            _ => None,
+        })
+}
+
 /// Split a string by commas, but only at the top level (not inside parentheses)
 fn split_preserving_parens(input: &str) -> Vec<&str> {
+    split_top_level(input, ',')
+}
+
+/// Split a string on `separator` wherever it is outside parentheses and
+/// string literals
+fn split_top_level(input: &str, separator: char) -> Vec<&str> {
     let mut parts = Vec::new();
     let mut current_start = 0;
     let mut depth = 0;
 
-    for (i, ch) in input.char_indices() {
+    for (i, ch) in outside_literals(input) {
         match ch {
             '(' => depth += 1,
             ')' => depth -= 1,
-            ',' if depth == 0 => {
+            c if c == separator && depth == 0 => {
                 parts.push(&input[current_start..i]);
                 current_start = i + 1;
             }
@@ -163,59 +671,679 @@ fn split_preserving_parens(input: &str) -> Vec<&str> {
     parts
 }
 
+/// `parts` without a final blank one, left by a trailing separator
+fn without_trailing_blank(mut parts: Vec<&str>) -> impl Iterator<Item = &str> {
+    if parts.last().is_some_and(|part| part.trim().is_empty()) {
+        parts.pop();
+    }
+    parts.into_iter()
+}
+
+/// Comment syntax of a section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comments {
+    /// `//`, line-leading `#` and `/* */`, all blanked out
+    Rules,
+    /// `/* */` blanked out; Cedar reads `//` comments itself. Quoted strings
+    /// take `\` escapes.
+    Cedar,
+}
+
+/// `text` with its comments replaced by spaces
+///
+/// Line breaks inside block comments are kept, so offsets and line
+/// numbers in the result are those of `text`.
+fn blank_comments(text: &str, comments: Comments) -> Cow<'_, str> {
+    let escapes = comments == Comments::Cedar;
+    let mut blanked = Vec::new();
+    let mut at = 0;
+    while at < text.len() {
+        if let Some(end) = literal_end(text, at, escapes) {
+            at = end;
+            continue;
+        }
+        let rest = &text[at..];
+        let line_comment = rest.starts_with("//")
+            || (comments == Comments::Rules
+                && rest.starts_with('#')
+                && text[..at]
+                    .rsplit('\n')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .is_empty());
+        let end = if rest.starts_with("/*") {
+            rest.find("*/").map_or(text.len(), |i| at + i + 2)
+        } else if line_comment {
+            rest.find('\n').map_or(text.len(), |i| at + i)
+        } else {
+            at += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        // Cedar reads its own line comments
+        if comments == Comments::Rules || !line_comment {
+            blanked.push(at..end);
+        }
+        at = end;
+    }
+
+    if blanked.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for range in blanked {
+        out.push_str(&text[copied..range.start]);
+        for ch in text[range.clone()].chars() {
+            if ch == '\n' {
+                out.push('\n');
+            } else {
+                out.extend(std::iter::repeat_n(' ', ch.len_utf8()));
+            }
+        }
+        copied = range.end;
+    }
+    out.push_str(&text[copied..]);
+    Cow::Owned(out)
+}
+
+/// End of the string literal starting at byte `at` of `text`, if one does
+///
+/// Literals are quoted with `"` or `'`, or raw: `r"..."`, or `r#"..."#`
+/// with any number of `#`. With `escapes`, `\` in a quoted literal escapes
+/// the next character. An unterminated literal runs to the end of `text`.
+fn literal_end(text: &str, at: usize, escapes: bool) -> Option<usize> {
+    let rest = &text[at..];
+    if let Some(raw) = rest.strip_prefix('r') {
+        let in_word = text[..at]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        if in_word || !raw[hashes..].starts_with('"') {
+            return None;
+        }
+        let close = format!("\"{}", "#".repeat(hashes));
+        let body = at + 1 + hashes + 1;
+        return Some(
+            text[body..]
+                .find(&close)
+                .map_or(text.len(), |i| body + i + close.len()),
+        );
+    }
+
+    let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, ch)) = chars.next() {
+        if escapes && ch == '\\' {
+            chars.next();
+        } else if ch == quote {
+            return Some(at + i + 1);
+        }
+    }
+    Some(text.len())
+}
+
+/// Characters of `text` outside string literals, with their offsets
+fn outside_literals(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut at = 0;
+    std::iter::from_fn(move || {
+        while let Some(end) = literal_end(text, at, false) {
+            at = end;
+        }
+        let ch = text[at..].chars().next()?;
+        at += ch.len_utf8();
+        Some((at - ch.len_utf8(), ch))
+    })
+}
+
+/// Contents of a raw string literal that makes up all of `input`
+fn raw_string(input: &str) -> Option<&str> {
+    let raw = input.strip_prefix('r')?;
+    let hashes = raw.len() - raw.trim_start_matches('#').len();
+    let fence = &raw[..hashes];
+    raw[hashes..]
+        .strip_prefix('"')?
+        .strip_suffix(fence)?
+        .strip_suffix('"')
+}
+
+/// Largest number of rules a single disjunctive rule may expand into
+const MAX_DISJUNCTS: usize = 256;
+
+/// Body element before disjunctions are expanded
+#[derive(Debug)]
+enum BodyItem {
+    Atom(DatalogAtom),
+    Guard(Guard),
+    /// Alternatives separated by `;`, each a conjunction
+    Or(Vec<Vec<BodyItem>>),
+}
+
+/// Parse a rule body: `;`-separated alternatives of `,`-separated elements
+///
+/// `;` binds looser than `,`, so `a(X), b(X) ; c(X)` reads as
+/// `(a(X), b(X)) ; c(X)`. Parentheses group alternatives inside a
+/// conjunction.
+fn parse_body(input: &str) -> Result<Vec<BodyItem>> {
+    let alternatives = split_top_level(input, ';');
+    if alternatives.len() == 1 {
+        return parse_conjunction(input);
+    }
+
+    let alternatives = alternatives
+        .into_iter()
+        .map(parse_conjunction)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(vec![BodyItem::Or(alternatives)])
+}
+
+fn parse_conjunction(input: &str) -> Result<Vec<BodyItem>> {
+    let mut items = Vec::new();
+    for part in without_trailing_blank(split_preserving_parens(input)) {
+        let s = part.trim();
+
+        if let Some(inner) = strip_group(s) {
+            items.extend(parse_body(inner)?);
+        } else if let Some(guard) = parse_guard(s)? {
+            items.push(BodyItem::Guard(guard));
+        } else {
+            // Check for negation
+            let negated = s.starts_with("not ");
+            let atom_str = if negated { &s[4..] } else { s };
+            if negated && strip_group(atom_str.trim()).is_some() {
+                return Err(RUNEError::ParseError(format!(
+                    "Negated groups are not supported: {}",
+                    s
+                )));
+            }
+            items.push(BodyItem::Atom(parse_atom(atom_str.trim(), negated)?));
+        }
+    }
+    Ok(items)
+}
+
+/// Inner text of `( ... )` when the parentheses enclose the whole element
+fn strip_group(s: &str) -> Option<&str> {
+    let inner = s.strip_prefix('(')?.strip_suffix(')')?;
+    let mut depth = 0;
+    for (_, ch) in outside_literals(inner) {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    Some(inner)
+}
+
+/// Parse `Left == Right` or `Left != Right`, if the element is a comparison
+fn parse_guard(s: &str) -> Result<Option<Guard>> {
+    let mut depth = 0;
+    let bytes = s.as_bytes();
+
+    for (i, ch) in outside_literals(s) {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '=' | '!' if depth == 0 && bytes.get(i + 1) == Some(&b'=') => {
+                let op = if ch == '=' {
+                    CompareOp::Eq
+                } else {
+                    CompareOp::Ne
+                };
+                let (left, right) = (s[..i].trim(), s[i + 2..].trim());
+                if left.is_empty() || right.is_empty() {
+                    return Err(RUNEError::ParseError(format!(
+                        "Incomplete comparison: {}",
+                        s
+                    )));
+                }
+                return Ok(Some(Guard::new(parse_term(left)?, op, parse_term(right)?)));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Expand disjunctions into one (body, guards) pair per alternative
+fn expand_body(items: Vec<BodyItem>) -> Result<Vec<(Vec<DatalogAtom>, Vec<Guard>)>> {
+    let mut expanded = vec![(Vec::new(), Vec::new())];
+    for item in items {
+        match item {
+            BodyItem::Atom(atom) => {
+                for (body, _) in &mut expanded {
+                    body.push(atom.clone());
+                }
+            }
+            BodyItem::Guard(guard) => {
+                for (_, guards) in &mut expanded {
+                    guards.push(guard.clone());
+                }
+            }
+            BodyItem::Or(alternatives) => {
+                let mut next = Vec::new();
+                for alternative in alternatives {
+                    for (alt_body, alt_guards) in expand_body(alternative)? {
+                        for (body, guards) in &expanded {
+                            next.push((
+                                [body.as_slice(), &alt_body].concat(),
+                                [guards.as_slice(), &alt_guards].concat(),
+                            ));
+                        }
+                    }
+                }
+                if next.len() > MAX_DISJUNCTS {
+                    return Err(RUNEError::ParseError(format!(
+                        "Rule expands into more than {} alternatives",
+                        MAX_DISJUNCTS
+                    )));
+                }
+                expanded = next;
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// Build a rule, resolving `==` guards by unification
+///
+/// `X == t` replaces `X` with `t` throughout the rule, which lets joins and
+/// indexes use the constant and keeps variables bound only through an
+/// equality safe. Comparisons between constants are decided here; the
+/// result is None when the rule can never fire.
+fn build_rule(
+    mut head: DatalogAtom,
+    mut body: Vec<DatalogAtom>,
+    guards: Vec<Guard>,
+) -> Option<DatalogRule> {
+    let mut pending = guards;
+    let mut remaining = Vec::new();
+
+    while let Some(guard) = pending.pop() {
+        let (variable, value) = match (&guard.left, &guard.right) {
+            (DatalogTerm::Constant(left), DatalogTerm::Constant(right)) => {
+                let equal = left == right;
+                if equal != (guard.op == CompareOp::Eq) {
+                    return None;
+                }
+                continue;
+            }
+            _ if guard.op == CompareOp::Ne => {
+                remaining.push(guard);
+                continue;
+            }
+            (DatalogTerm::Variable(a), DatalogTerm::Variable(b)) if a == b => continue,
+            (DatalogTerm::Variable(variable), value) | (value, DatalogTerm::Variable(variable)) => {
+                (variable.clone(), value.clone())
+            }
+        };
+
+        let replace = |term: &mut DatalogTerm| {
+            if term.as_variable() == Some(variable.as_str()) {
+                *term = value.clone();
+            }
+        };
+        head.terms.iter_mut().for_each(replace);
+        for atom in &mut body {
+            atom.terms.iter_mut().for_each(replace);
+        }
+        for guard in pending.iter_mut().chain(remaining.iter_mut()) {
+            replace(&mut guard.left);
+            replace(&mut guard.right);
+        }
+        // Inequalities that became ground are decided on the next pass
+        pending.append(&mut remaining);
+    }
+
+    let mut rule = DatalogRule::new(head, body);
+    rule.guards = remaining;
+    Some(rule)
+}
+
 /// Parse Datalog rules
+///
+/// Rule bodies are comma-separated conjunctions of:
+///
+/// - atoms, `member(User, Group)`, optionally negated with `not `;
+/// - comparisons, `Role == "head"` or `Unit != Other`;
+/// - disjunctions, `(Role == "head" ; Role == "ceo")`. A rule with
+///   alternatives becomes one rule per alternative, each keeping the rule's
+///   annotations, so a flag on it switches all of them.
+///
+/// `;` also works at the top level of a body, where it binds looser than `,`.
+/// Argument lists and bodies may end with a trailing comma.
+///
+/// Constants are integers, which may group digits with `_` (`1_000_000`),
+/// `true` and `false`, and strings: quoted with `"` or `'`, bare lowercase
+/// words, or raw, `r"C:\dir"` or `r#"say "hi""#` with as many `#` as it
+/// takes to hold a `"`. Raw strings keep everything between their quotes,
+/// including commas, parentheses and surrounding spaces.
+///
+/// Comments run from `//` or a line-leading `#` to the end of the line, or
+/// from `/*` to `*/` across lines; neither starts inside a string.
+///
+/// Errors come back as [`RUNEError::DiagnosticError`], one diagnostic per
+/// bad rule or annotation, with spans into `input`.
 pub fn parse_rules(input: &str) -> Result<Vec<DatalogRule>> {
-    let mut rules = Vec::new();
-    let mut current_rule = String::new();
+    parse_rules_at(&blank_comments(input, Comments::Rules), input, 0)
+}
 
-    for line in input.lines() {
-        let line = line.trim();
+/// Parse a query goal: one atom such as `can_access("alice", R)`, with an
+/// optional trailing `.` or `?`
+pub fn parse_goal(input: &str) -> Result<DatalogAtom> {
+    let goal = input.trim().trim_end_matches(['.', '?']).trim_end();
+    let predicate = goal.split('(').next().unwrap_or_default().trim();
+    let balanced = match goal.find('(') {
+        Some(_) => goal.ends_with(')') && goal.matches('(').count() == 1,
+        None => true,
+    };
+    if predicate.is_empty()
+        || !predicate.chars().all(|c| c.is_alphanumeric() || c == '_')
+        || !balanced
+    {
+        return Err(RUNEError::ParseError(format!("Invalid goal: {}", input)));
+    }
+    parse_atom(goal, false)
+}
+
+/// Parse rules that start at byte `offset` of `source`, for spans into it
+///
+/// Comments must already be blanked out of `input`.
+fn parse_rules_at(input: &str, source: &str, offset: usize) -> Result<Vec<DatalogRule>> {
+    let mut rules = Vec::new();
+    let mut statement = Statement::default();
+    let mut annotations = BTreeMap::new();
+    let mut diagnostics = DiagnosticBag::new();
+    let span = |range: Range<usize>| Span::locate(source, offset + range.start, offset + range.end);
+
+    let mut line_offset = 0;
+    for raw in input.split_inclusive('\n') {
+        let start = line_offset + (raw.len() - raw.trim_start().len());
+        line_offset += raw.len();
+        let line = raw.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
-        // Accumulate lines for the current rule
-        if !current_rule.is_empty() {
-            current_rule.push(' ');
+        // Annotations apply to the next rule
+        if statement.text.is_empty() && line.starts_with('@') {
+            match parse_annotation(line) {
+                Ok((key, value)) => {
+                    annotations.insert(key, value);
+                }
+                Err(e) => diagnostics.add(
+                    Diagnostic::error(parse_message(e))
+                        .with_code(codes::INVALID_ANNOTATION)
+                        .with_span(span(start..start + line.len()))
+                        .with_help("annotations look like `@name` or `@name(\"value\")`"),
+                ),
+            }
+            continue;
         }
-        current_rule.push_str(line);
+
+        // Accumulate lines for the current rule
+        statement.push(line, start);
 
         // Check if rule is complete (ends with period)
-        if current_rule.trim_end().ends_with('.') {
-            // Parse the complete rule
-            let rule_str = current_rule.trim();
-
-            // Check if this is a fact (no body) or a rule (has :-)
-            if let Some((head, body)) = rule_str.split_once(":-") {
-                // Rule with head and body
-                let head_atom = parse_atom(head.trim(), false)?;
-                let body_str = body.trim().trim_end_matches('.');
-                let body_atoms = split_preserving_parens(body_str)
-                    .into_iter()
-                    .map(|s| {
-                        let s = s.trim();
-                        // Check for negation
-                        let negated = s.starts_with("not ");
-                        let atom_str = if negated { &s[4..] } else { s };
-                        parse_atom(atom_str.trim(), negated)
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-
-                rules.push(DatalogRule::new(head_atom, body_atoms));
-            } else {
-                // Fact (ground atom with no body)
-                let fact_atom = parse_atom(rule_str.trim_end_matches('.'), false)?;
-                rules.push(DatalogRule::fact(fact_atom));
+        if statement.text.ends_with('.') {
+            let annotations = std::mem::take(&mut annotations);
+            match parse_statement(&statement.text, annotations) {
+                Ok(parsed) => rules.extend(parsed),
+                Err(error) => {
+                    let (range, diagnostic) = *error;
+                    let range = range.unwrap_or(0..statement.text.len());
+                    let located = statement.locate(range.start)..statement.locate(range.end);
+                    diagnostics.add(diagnostic.with_span(span(located)));
+                }
             }
 
             // Reset for next rule
-            current_rule.clear();
+            statement = Statement::default();
+        }
+    }
+
+    if diagnostics.has_errors() {
+        return Err(RUNEError::from_diagnostics(diagnostics));
+    }
+    Ok(rules)
+}
+
+/// A rule's text, joined from its trimmed lines
+#[derive(Default)]
+struct Statement {
+    text: String,
+    /// Offset in the text and in the input where each line starts
+    lines: Vec<(usize, usize)>,
+}
+
+impl Statement {
+    fn push(&mut self, line: &str, offset: usize) {
+        if !self.text.is_empty() {
+            self.text.push(' ');
         }
+        self.lines.push((self.text.len(), offset));
+        self.text.push_str(line);
     }
 
+    /// Input offset of a text offset
+    fn locate(&self, offset: usize) -> usize {
+        let (text, input) = self
+            .lines
+            .iter()
+            .rev()
+            .find(|(text, _)| *text <= offset)
+            .copied()
+            .unwrap_or_default();
+        input + (offset - text)
+    }
+}
+
+/// A rule that failed to parse: the part of its text to point at (the
+/// whole rule when None) and what went wrong
+type StatementError = Box<(Option<Range<usize>>, Diagnostic)>;
+
+/// Parse one complete rule or fact
+fn parse_statement(
+    text: &str,
+    annotations: BTreeMap<String, String>,
+) -> std::result::Result<Vec<DatalogRule>, StatementError> {
+    check_syntax(text)?;
+    let invalid = |e: RUNEError| {
+        Box::new((
+            None,
+            Diagnostic::error(parse_message(e)).with_code(codes::INVALID_RULE),
+        ))
+    };
+
+    // Check if this is a fact (no body) or a rule (has :-)
+    let neck = outside_literals(text)
+        .find(|&(i, ch)| ch == ':' && text[i + 1..].starts_with('-'))
+        .map(|(i, _)| i);
+    let rules = if let Some((head, body)) = neck.map(|i| (&text[..i], &text[i + 2..])) {
+        // Rule with head and body
+        let head_atom = parse_atom(head.trim(), false).map_err(invalid)?;
+        let body_str = body.trim().trim_end_matches('.');
+        let alternatives = expand_body(parse_body(body_str).map_err(invalid)?).map_err(invalid)?;
+
+        let mut rules = Vec::new();
+        for (body_atoms, guards) in alternatives {
+            if let Some(mut rule) = build_rule(head_atom.clone(), body_atoms, guards) {
+                rule.annotations = annotations.clone();
+                rules.push(rule);
+            }
+        }
+        rules
+    } else {
+        // Fact (ground atom with no body)
+        let fact_atom = parse_atom(text.trim_end_matches('.'), false).map_err(invalid)?;
+        let mut rule = DatalogRule::fact(fact_atom);
+        rule.annotations = annotations;
+        vec![rule]
+    };
+
+    for atom in rules
+        .iter()
+        .flat_map(|rule| std::iter::once(&rule.head).chain(&rule.body))
+    {
+        let predicate = atom.predicate.as_ref();
+        if predicate.is_empty() || predicate.contains(char::is_whitespace) {
+            let range = (!predicate.is_empty())
+                .then(|| text.find(predicate).map(|at| at..at + predicate.len()))
+                .flatten();
+            return Err(Box::new((
+                range,
+                Diagnostic::error(format!("Invalid predicate name `{}`", predicate))
+                    .with_code(codes::INVALID_PREDICATE)
+                    .with_help("predicate names are a single word, e.g. `user_tenant(U, T)`"),
+            )));
+        }
+    }
     Ok(rules)
 }
 
+/// Check a rule's parentheses and separators before parsing it
+///
+/// The element parsers are lenient about both, so mistakes here would
+/// otherwise turn into odd terms rather than errors.
+fn check_syntax(text: &str) -> std::result::Result<(), StatementError> {
+    let mut open = Vec::new();
+
+    for (i, ch) in outside_literals(text) {
+        match ch {
+            '(' => open.push(i),
+            ')' => {
+                if open.pop().is_none() {
+                    return Err(Box::new((
+                        Some(i..i + 1),
+                        Diagnostic::error("Unmatched `)`")
+                            .with_code(codes::UNBALANCED_PARENS)
+                            .with_help("this `)` closes nothing"),
+                    )));
+                }
+                // An element right after `)` means a missing separator
+                let rest = text[i + 1..].trim_start();
+                if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    return Err(Box::new((
+                        Some(i..i + 1),
+                        Diagnostic::error("Expected `,` or `;` after this element")
+                            .with_code(codes::MISSING_SEPARATOR)
+                            .with_suggestion(
+                                Suggestion::new("separate body elements with a comma")
+                                    .with_replacement("),"),
+                            ),
+                    )));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match open.last() {
+        Some(&i) => Err(Box::new((
+            Some(i..i + 1),
+            Diagnostic::error("Unclosed `(`")
+                .with_code(codes::UNBALANCED_PARENS)
+                .with_help("every `(` needs a matching `)` before the rule's final `.`"),
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Message of a parse error, without the error kind prefix
+fn parse_message(error: RUNEError) -> String {
+    match error {
+        RUNEError::ParseError(message) => message,
+        other => other.to_string(),
+    }
+}
+
+/// Warnings for body predicates that no rule defines but that are a typo
+/// away from one that is
+///
+/// Predicates no rule defines are usually base facts loaded at runtime, so
+/// only near misses are reported.
+fn undefined_predicates(
+    rules: &[DatalogRule],
+    text: &str,
+    source: &str,
+    offset: usize,
+) -> Vec<Diagnostic> {
+    let defined: BTreeSet<&str> = rules
+        .iter()
+        .map(|rule| rule.head.predicate.as_ref())
+        .collect();
+    let used: BTreeSet<&str> = rules
+        .iter()
+        .flat_map(|rule| &rule.body)
+        .map(|atom| atom.predicate.as_ref())
+        .filter(|predicate| !defined.contains(predicate))
+        .collect();
+
+    used.into_iter()
+        .filter_map(|predicate| {
+            let candidate = closest_match(predicate, defined.iter().copied())?;
+            let mut diagnostic = Diagnostic::warning(format!(
+                "Predicate `{}` is not defined by any rule",
+                predicate
+            ))
+            .with_code(codes::UNDEFINED_PREDICATE)
+            .with_help("it can only match facts added at runtime")
+            .with_suggestion(
+                Suggestion::new(format!("did you mean predicate `{}`?", candidate))
+                    .with_replacement(candidate),
+            );
+            if let Some(at) = find_predicate(text, predicate) {
+                diagnostic = diagnostic.with_span(Span::locate(
+                    source,
+                    offset + at,
+                    offset + at + predicate.len(),
+                ));
+            }
+            Some(diagnostic)
+        })
+        .collect()
+}
+
+/// Offset of the first use of `predicate` as an atom in `text`
+fn find_predicate(text: &str, predicate: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(predicate).map(|(at, _)| at).find(|&at| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + predicate.len()..].trim_start().chars().next();
+        !before.is_some_and(is_word) && after == Some('(')
+    })
+}
+
+/// Parse an annotation line such as `@id("admin")`, `@enabled(false)` or `@deprecated`
+pub(crate) fn parse_annotation(line: &str) -> Result<(String, String)> {
+    // Policies keep their `//` comments for Cedar
+    let comment =
+        outside_literals(line).find(|&(i, ch)| ch == '/' && line[i + 1..].starts_with('/'));
+    let line = comment.map_or(line, |(i, _)| line[..i].trim_end());
+    let body = line.trim_start_matches('@').trim();
+    let (key, value) = match body.split_once('(') {
+        Some((key, rest)) => {
+            let value = rest.strip_suffix(')').ok_or_else(|| {
+                RUNEError::ParseError(format!("Unterminated annotation: {}", line))
+            })?;
+            (key.trim(), value.trim().trim_matches('"'))
+        }
+        None => (body, ""),
+    };
+
+    if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(RUNEError::ParseError(format!(
+            "Invalid annotation name: {}",
+            line
+        )));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
 /// Parse a single atom
 fn parse_atom(input: &str, negated: bool) -> Result<DatalogAtom> {
     // Extract predicate and arguments
@@ -225,12 +1353,17 @@ fn parse_atom(input: &str, negated: bool) -> Result<DatalogAtom> {
             .trim_end_matches('.')
             .trim_end_matches(')');
 
-        let terms: Vec<DatalogTerm> = if args_str.is_empty() {
+        let terms: Vec<DatalogTerm> = if args_str.trim().is_empty() {
             Vec::new()
         } else {
-            args_str
-                .split(',')
-                .map(|s| parse_term(s.trim()))
+            without_trailing_blank(split_top_level(args_str, ','))
+                .map(|s| match s.trim() {
+                    "" => Err(RUNEError::ParseError(format!(
+                        "Missing argument in {}",
+                        input
+                    ))),
+                    s => parse_term(s),
+                })
                 .collect::<Result<Vec<_>>>()?
         };
 
@@ -258,10 +1391,20 @@ fn parse_term(input: &str) -> Result<DatalogTerm> {
         return Ok(DatalogTerm::Variable(input.to_string()));
     }
 
+    // Raw string, kept exactly
+    if let Some(raw) = raw_string(input) {
+        return Ok(DatalogTerm::Constant(Value::String(Arc::from(raw))));
+    }
+
     // Constant: try to parse as different types
-    // Integer
-    if let Ok(i) = input.parse::<i64>() {
-        return Ok(DatalogTerm::Constant(Value::Integer(i)));
+    // Integer, with optional `_` digit separators
+    let digits = input.strip_prefix('-').unwrap_or(input);
+    if digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '_')
+    {
+        if let Ok(i) = input.replace('_', "").parse::<i64>() {
+            return Ok(DatalogTerm::Constant(Value::Integer(i)));
+        }
     }
 
     // Boolean
@@ -284,9 +1427,28 @@ fn parse_policies(input: &str) -> Result<Vec<Policy>> {
     let mut policies = Vec::new();
     let mut current_policy_id = None;
     let mut policy_content = String::new();
+    // Cedar annotations preceding the next policy; kept in its content
+    let mut pending_annotations = String::new();
+    let mut pending_id = None;
 
     for line in input.lines() {
-        if line.starts_with("permit") || line.starts_with("forbid") {
+        if line.trim_start().starts_with('@') {
+            // Annotations start the next policy, so close the current one
+            if let Some(id) = current_policy_id.take() {
+                policies.push(Policy {
+                    id,
+                    content: policy_content.clone(),
+                });
+                policy_content.clear();
+            }
+
+            let (key, value) = parse_annotation(line.trim())?;
+            if key == "id" {
+                pending_id = Some(value);
+            }
+            pending_annotations.push_str(line);
+            pending_annotations.push('\n');
+        } else if line.starts_with("permit") || line.starts_with("forbid") {
             // Save previous policy if exists
             if let Some(id) = current_policy_id.take() {
                 policies.push(Policy {
@@ -297,7 +1459,13 @@ fn parse_policies(input: &str) -> Result<Vec<Policy>> {
             }
 
             // Start new policy
-            current_policy_id = Some(format!("policy_{}", policies.len()));
+            current_policy_id = Some(
+                pending_id
+                    .take()
+                    .unwrap_or_else(|| format!("policy_{}", policies.len())),
+            );
+            policy_content.push_str(&pending_annotations);
+            pending_annotations.clear();
             policy_content.push_str(line);
             policy_content.push('\n');
         } else if current_policy_id.is_some() {
@@ -321,6 +1489,13 @@ fn parse_policies(input: &str) -> Result<Vec<Policy>> {
 mod tests {
     use super::*;
 
+    fn first_diagnostic(error: RUNEError) -> Diagnostic {
+        match error {
+            RUNEError::DiagnosticError(bag) => bag.diagnostics()[0].clone(),
+            other => panic!("expected diagnostics, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_simple_atom() {
         let atom = parse_atom("user(X)", false).unwrap();
@@ -427,9 +1602,9 @@ user(alice).
 "#;
         let result = parse_rune_file(input);
         assert!(result.is_err());
-        assert!(
-            matches!(result.unwrap_err(), RUNEError::ParseError(msg) if msg.contains("Missing version"))
-        );
+        let diagnostic = first_diagnostic(result.unwrap_err());
+        assert!(diagnostic.message.contains("Missing version"));
+        assert_eq!(diagnostic.code.as_deref(), Some(codes::MISSING_VERSION));
     }
 
     #[test]
@@ -446,9 +1621,122 @@ user(alice).
 "#;
         let result = parse_rune_file(input);
         assert!(result.is_err());
-        assert!(
-            matches!(result.unwrap_err(), RUNEError::ParseError(msg) if msg.contains("Failed to parse data section"))
+        let diagnostic = first_diagnostic(result.unwrap_err());
+        assert!(diagnostic.message.contains("Failed to parse data section"));
+        assert_eq!(diagnostic.code.as_deref(), Some(codes::INVALID_DATA));
+        // Points into the file, not the section
+        assert_eq!(diagnostic.span.unwrap().line, 5);
+    }
+
+    #[test]
+    fn test_rule_errors_point_into_the_file() {
+        let input = r#"version = "rune/2.0"
+
+[rules]
+member(U, G) :- in_group(U, G).
+allowed(U) :-
+    member(U, "admins",
+    active(U).
+shown(U) :- allowed(U) visible(U).
+"#;
+        let error = parse_rune_file(input).unwrap_err();
+        let bag = error.diagnostics().unwrap();
+        // Both bad rules are reported, not just the first
+        assert_eq!(bag.error_count(), 2);
+
+        let unclosed = &bag.diagnostics()[0];
+        assert_eq!(unclosed.code.as_deref(), Some(codes::UNBALANCED_PARENS));
+        let span = unclosed.span.as_ref().unwrap();
+        assert_eq!((span.line, span.column), (6, 11));
+        assert_eq!(&input[span.start..span.end], "(");
+
+        let separator = &bag.diagnostics()[1];
+        assert_eq!(separator.code.as_deref(), Some(codes::MISSING_SEPARATOR));
+        let span = separator.span.as_ref().unwrap();
+        assert_eq!((span.line, span.column), (8, 22));
+        assert_eq!(separator.suggestions[0].replacement.as_deref(), Some("),"));
+
+        let rendered = error.render_with_source(Some(input), false);
+        assert!(rendered.contains("error[E0101]: Unclosed `(`"));
+        assert!(rendered.contains("6 |     member(U, \"admins\","));
+        assert!(rendered.contains("8 | shown(U) :- allowed(U), visible(U)."));
+        assert!(!rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_invalid_rule_spans_every_line() {
+        let input = "allowed(U) :-\n    user(U),\n    not (a(U), b(U)).\n";
+        let error = parse_rules(input).unwrap_err();
+        let diagnostic = &error.diagnostics().unwrap().diagnostics()[0];
+        assert_eq!(diagnostic.code.as_deref(), Some(codes::INVALID_RULE));
+        assert!(diagnostic.message.contains("Negated groups"));
+
+        let rendered = error.render_with_source(Some(input), false);
+        assert!(rendered.contains("1 | allowed(U) :-"));
+        assert!(rendered.contains("2 |     user(U),"));
+        assert!(rendered.contains("3 |     not (a(U), b(U))."));
+    }
+
+    #[test]
+    fn test_invalid_predicate_and_annotation() {
+        let error = parse_rules("@id(\"a\"\nuser tenant(U, T) :- member(U, T).\n").unwrap_err();
+        let codes: Vec<_> = error
+            .diagnostics()
+            .unwrap()
+            .diagnostics()
+            .iter()
+            .map(|d| d.code.clone().unwrap())
+            .collect();
+        assert_eq!(codes, [codes::INVALID_ANNOTATION, codes::INVALID_PREDICATE]);
+    }
+
+    #[test]
+    fn test_undefined_predicate_suggestion() {
+        let input = r#"version = "rune/2.0"
+
+[rules]
+user_tenant(U, T) :- member(U, T).
+allowed(U) :- user_tenat(U, T), tenant_active(T).
+"#;
+        let config = parse_rune_file(input).unwrap();
+        assert_eq!(config.warnings.warning_count(), 1);
+        let warning = &config.warnings.diagnostics()[0];
+        assert_eq!(warning.code.as_deref(), Some(codes::UNDEFINED_PREDICATE));
+        assert_eq!(
+            warning.suggestions[0].message,
+            "did you mean predicate `user_tenant`?"
         );
+        let span = warning.span.as_ref().unwrap();
+        assert_eq!(&input[span.start..span.end], "user_tenat");
+
+        let rendered = config.warnings.render(Some(input), false);
+        assert!(rendered.contains("warning[W0101]"));
+        assert!(rendered.contains("5 | allowed(U) :- user_tenant(U, T), tenant_active(T)."));
+    }
+
+    #[test]
+    fn test_missing_policy_ids_reported_together() {
+        let input = r#"version = "rune/2.0"
+
+[policies]
+@id("readers")
+permit(principal, action, resource);
+
+permit(principal, action == Action::"write", resource);
+forbid(principal, action, resource);
+"#;
+        let error = parse_rune_file(input).unwrap_err();
+        let bag = error.diagnostics().unwrap();
+        assert_eq!(bag.error_count(), 2);
+        let lines: Vec<_> = bag
+            .diagnostics()
+            .iter()
+            .map(|d| d.span.as_ref().unwrap().line)
+            .collect();
+        assert_eq!(lines, [7, 8]);
+        assert!(error
+            .render_with_source(Some(input), false)
+            .contains("7 | @id(\"policy_1\")"));
     }
 
     #[test]
@@ -711,6 +1999,20 @@ version = "1.0.0"
             data: None,
             rules: None,
             policies: None,
+            canonicalize: None,
+            routes: None,
+            scopes: None,
+            limits: None,
+            cache: None,
+            attributes: None,
+            messages: None,
+            builtins: None,
+            strings: None,
+            evaluation: None,
+            enabled: None,
+            artifact: None,
+            cedar_policies: None,
+            offsets: BTreeMap::new(),
         };
 
         // Save empty content (should do nothing)
@@ -808,4 +2110,328 @@ permit (
         let term = parse_term("99999999999999999999").unwrap();
         assert!(matches!(term, DatalogTerm::Constant(Value::String(_))));
     }
+
+    #[test]
+    fn test_parse_rule_annotations() {
+        let input = r#"
+@id("admin_access")
+@enabled(false)
+can_access(U) :- admin(U).
+user(alice).
+"#;
+        let rules = parse_rules(input).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].flag_key(), Some("admin_access"));
+        assert!(!rules[0].enabled_by_default());
+        assert!(rules[1].annotations.is_empty());
+        assert!(rules[1].enabled_by_default());
+
+        assert!(parse_rules("@bad name(x)\nuser(alice).").is_err());
+    }
+
+    #[test]
+    fn test_parse_disjunction() {
+        let input = r#"
+@id("write")
+can_write(U, R) :- role(U, Role), owner(R, U), (Role == "head" ; Role == "ceo").
+"#;
+        let rules = parse_rules(input).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            rules[0].to_string(),
+            r#"can_write(?U, ?R) :- role(?U, "head"), owner(?R, ?U)."#
+        );
+        assert_eq!(
+            rules[1].to_string(),
+            r#"can_write(?U, ?R) :- role(?U, "ceo"), owner(?R, ?U)."#
+        );
+        assert!(rules.iter().all(|r| r.flag_key() == Some("write")));
+
+        // `;` binds looser than `,`
+        let rules = parse_rules("p(X) :- a(X), b(X) ; c(X).").unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].body.len(), 2);
+        assert_eq!(rules[1].body.len(), 1);
+
+        // Groups multiply out
+        let rules = parse_rules("p(X) :- (a(X) ; b(X)), (c(X) ; d(X) ; e(X)).").unwrap();
+        assert_eq!(rules.len(), 6);
+
+        assert!(parse_rules("p(X) :- a(X), not (b(X) ; c(X)).").is_err());
+    }
+
+    #[test]
+    fn test_parse_comparisons() {
+        let rules = parse_rules("p(X, Y) :- edge(X, Y), X != Y.").unwrap();
+        assert_eq!(rules[0].body.len(), 1);
+        assert_eq!(
+            rules[0].guards,
+            vec![Guard::new(
+                DatalogTerm::var("X"),
+                CompareOp::Ne,
+                DatalogTerm::var("Y")
+            )]
+        );
+
+        // Equalities unify, so Y is bound through X
+        let rules = parse_rules("p(X, Y) :- a(X), Y == X.").unwrap();
+        assert_eq!(rules[0].to_string(), "p(?X, ?X) :- a(?X).");
+        assert!(rules[0].guards.is_empty());
+        assert!(rules[0].is_safe());
+
+        // Comparisons that become ground are decided statically
+        let rules = parse_rules(r#"p(X) :- a(X), X == 1, X != 2 ; b(X), 1 == 2."#).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].to_string(), "p(1) :- a(1).");
+
+        // Operators inside quoted strings are not comparisons
+        let rules = parse_rules(r#"p(X) :- label(X, "a==b")."#).unwrap();
+        assert!(rules[0].guards.is_empty());
+
+        assert!(parse_rules("p(X) :- a(X), X != .").is_err());
+    }
+
+    #[test]
+    fn test_parse_policy_annotations() {
+        let input = r#"
+@id("allow_read")
+@enabled("false")
+permit(principal, action == Action::"read", resource);
+forbid(principal, action == Action::"delete", resource);
+"#;
+        let policies = parse_policies(input).unwrap();
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies[0].id, "allow_read");
+        assert!(policies[0].content.starts_with("@id(\"allow_read\")"));
+        assert_eq!(policies[1].id, "policy_1");
+    }
+
+    #[test]
+    fn test_parse_canonicalize_section() {
+        let input = r#"version = "rune/1.0"
+
+[canonicalize]
+[[rules]]
+target = "principal"
+transforms = ["lowercase"]
+
+[rules]
+user(alice).
+"#;
+        let config = parse_rune_file(input).unwrap();
+        let canonicalize = config.canonicalize.unwrap();
+        assert_eq!(canonicalize.rules.len(), 1);
+        assert_eq!(config.rules.len(), 1);
+
+        let missing = parse_rune_file("version = \"rune/1.0\"\n").unwrap();
+        assert!(missing.canonicalize.is_none());
+    }
+
+    #[test]
+    fn test_format_versions() {
+        let v1 = parse_rune_file(
+            "version = \"rune/1.0\"\n\n[policies]\npermit(principal, action, resource);\n",
+        )
+        .unwrap();
+        assert_eq!(v1.format, FormatVersion::V1);
+        assert_eq!(v1.policies[0].id, "policy_0");
+
+        // 2.0 requires explicit policy IDs
+        let err = parse_rune_file(
+            "version = \"rune/2.0\"\n\n[policies]\npermit(principal, action, resource);\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("@id"));
+
+        let v2 = parse_rune_file(
+            "version = \"rune/2.0\"\n\n[policies]\n@id(\"allow\")\npermit(principal, action, resource);\n",
+        )
+        .unwrap();
+        assert_eq!(v2.format, FormatVersion::V2);
+        assert_eq!(v2.policies[0].id, "allow");
+
+        assert!(parse_rune_file(
+            "version = \"rune/2.0\"\n\n[cedar_policies]\npermit(principal, action, resource);\n"
+        )
+        .is_err());
+        assert!(parse_rune_file("version = \"rune/9.0\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_routes_section() {
+        let input = r#"version = "rune/1.0"
+
+[routes]
+GET /api/docs/{id} -> action read, resource doc:{id}
+
+[rules]
+user(alice).
+"#;
+        let config = parse_rune_file(input).unwrap();
+        let routes = config.routes.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(
+            routes.resolve("GET", "/api/docs/7").unwrap().resource,
+            "doc:7"
+        );
+        assert_eq!(config.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_scopes_section() {
+        let input = r#"version = "rune/2.0"
+
+[scopes]
+[tenant]
+column = 0
+
+[rules]
+@scope("tenant")
+can_read(T, U, D) :- member(T, U, G), grant(T, G, D).
+"#;
+        let config = parse_rune_file(input).unwrap();
+        let scopes = config.scopes.unwrap();
+        assert_eq!(scopes.get("tenant").unwrap().column, 0);
+        assert_eq!(config.rules[0].scope(), Some("tenant"));
+    }
+
+    #[test]
+    #[cfg(feature = "full")]
+    fn test_check_sources() {
+        let policies = "@id(\"readers\")\npermit(principal, action, resource);\n\n\
+                        @id(\"broken\")\nforbid(principal, action, resource) when { 1 + };\n";
+        let check = check_sources(
+            policies,
+            Some("can_read(U) :- member(U).\nbad(X :- a(X).\n"),
+        );
+        assert!(!check.is_valid());
+        assert_eq!(check.policies, 2);
+
+        // The Cedar error points into the second policy
+        let errors = check.policy_diagnostics.diagnostics();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("Failed to parse policy"));
+        let span = errors[0].span.as_ref().unwrap();
+        assert_eq!(span.line, 5);
+        assert_eq!((span.column, &policies[span.start..span.end]), (48, "}"));
+
+        let errors = check.rule_diagnostics.diagnostics();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span.as_ref().unwrap().line, 2);
+
+        let check = check_sources("permit(principal, action, resource);", None);
+        assert!(check.is_valid());
+        assert_eq!((check.policies, check.rules), (1, 0));
+    }
+
+    #[test]
+    fn test_parse_goal() {
+        let goal = parse_goal(r#"can_access("alice", R)?"#).unwrap();
+        assert_eq!(&*goal.predicate, "can_access");
+        assert_eq!(
+            goal.terms,
+            vec![
+                DatalogTerm::Constant(Value::string("alice")),
+                DatalogTerm::Variable("R".into()),
+            ]
+        );
+        assert_eq!(parse_goal("admin.").unwrap().terms.len(), 0);
+
+        assert!(parse_goal("").is_err());
+        assert!(parse_goal("can access(X)").is_err());
+        assert!(parse_goal("can_access(X").is_err());
+        assert!(parse_goal("a(X), b(X)").is_err());
+    }
+
+    #[test]
+    fn test_comments_in_rules_and_policies() {
+        let input = r#"version = "rune/2.0"
+
+[rules]
+// Admins see everything
+admin(U) :- role(U, "admin"). // not "guests"
+/* Retired:
+member(U) :- legacy(U).
+*/
+url("http://example.com/*"). // a URL, not a comment
+    # indented comment
+
+[policies]
+/* Reviewed
+permit(principal, action, resource);
+*/
+@id("read") // the only policy
+permit(principal, action == Action::"read", resource);
+"#;
+        let config = parse_rune_file(input).unwrap();
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(
+            config.rules[1].head.terms[0],
+            DatalogTerm::Constant(Value::string("http://example.com/*"))
+        );
+        assert_eq!(config.policies.len(), 1);
+        assert_eq!(config.policies[0].id, "read");
+        #[cfg(feature = "full")]
+        {
+            let mut set = PolicySet::new();
+            set.add_policy(&config.policies[0].id, &config.policies[0].content)
+                .unwrap();
+        }
+
+        // Offsets survive blanking, so errors still point at the right line
+        let bad = "/* one\ntwo */ ok(a).\nbad(X :- y(X).\n";
+        let error = first_diagnostic(parse_rules(bad).unwrap_err());
+        assert_eq!(error.span.unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let rules =
+            parse_rules("can(U, R,) :-\n    member(U, G,),\n    grant(G, R),\n.\nflag(a, b, ).")
+                .unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].head.terms.len(), 2);
+        assert_eq!(rules[0].body.len(), 2);
+        assert_eq!(rules[0].body[0].terms.len(), 2);
+        assert_eq!(rules[1].head.terms.len(), 2);
+
+        assert!(parse_rules("p(a, , b).").is_err());
+    }
+
+    #[test]
+    fn test_raw_strings_and_numeric_literals() {
+        let rules = parse_rules(
+            r##"path(r"C:\dir, (old)").
+quote(r#"say "hi""#).
+limit(1_000_000, -2_5).
+keep(r" padded ").
+check(X) :- note(X), X != r"a :- b", X == r#"x"#."##,
+        )
+        .unwrap();
+        let first = |i: usize| rules[i].head.terms[0].clone();
+        assert_eq!(
+            first(0),
+            DatalogTerm::Constant(Value::string(r"C:\dir, (old)"))
+        );
+        assert_eq!(first(1), DatalogTerm::Constant(Value::string("say \"hi\"")));
+        assert_eq!(
+            rules[2].head.terms,
+            vec![
+                DatalogTerm::Constant(Value::Integer(1_000_000)),
+                DatalogTerm::Constant(Value::Integer(-25)),
+            ]
+        );
+        assert_eq!(first(3), DatalogTerm::Constant(Value::string(" padded ")));
+        // The `:-` in the string does not split the rule, and with X bound
+        // the inequality is decided at parse time
+        assert_eq!(first(4), DatalogTerm::Constant(Value::string("x")));
+        assert_eq!(rules[4].body.len(), 1);
+        assert!(rules[4].guards.is_empty());
+
+        // Not raw strings
+        assert_eq!(
+            parse_rules("user(r).").unwrap()[0].head.terms[0],
+            DatalogTerm::Constant(Value::string("r"))
+        );
+    }
 }
@@ -0,0 +1,241 @@
+//! Principal permission summaries
+//!
+//! "View as user" screens need to show what a principal is, not just answer
+//! one request at a time. A summary is assembled from the fixpoint (base and
+//! derived facts together) by convention:
+//!
+//! ```text
+//! groups:  member("alice", "eng"). member_of("eng", "staff").   eng, staff
+//! roles:   has_role("alice", "reader"). role("eng", "deployer"). reader, deployer
+//! checks:  can_deploy("alice", "api") derived by a rule          listed as is
+//! ```
+//!
+//! Groups follow [`GROUP_PREDICATES`] transitively from the principal, and
+//! roles held by any of those groups are inherited. Checks are facts of
+//! rule-defined predicates with the principal as an argument. Policies are
+//! listed when their principal scope covers the principal or one of its
+//! groups; their `when` and `unless` conditions depend on the request and
+//! are not evaluated.
+//!
+//! Integrators that manage roles and groups through the engine rather than
+//! their own facts use [`crate::RUNEEngine::grant_role`],
+//! [`crate::RUNEEngine::add_to_group`] and their inverses, which record
+//! [`ROLE_PREDICATE`] and [`GROUP_PREDICATE`] facts between plain IDs
+//! (`alice`, not `User:alice`) and check the IDs first. Rules written
+//! against those two predicates then work whichever binding made the grant.
+
+use crate::datalog::Rule;
+use crate::error::{RUNEError, Result};
+use crate::facts::Fact;
+use crate::policy::PolicySet;
+use crate::types::{Principal, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+
+/// Predicates relating a member to a group it belongs to
+pub const GROUP_PREDICATES: &[&str] = &["member", "member_of", "in_group", "parent"];
+
+/// Predicates relating a principal or group to a role it holds
+pub const ROLE_PREDICATES: &[&str] = &["has_role", "role"];
+
+/// Predicate role grants are recorded with: `has_role(holder, role)`
+pub const ROLE_PREDICATE: &str = ROLE_PREDICATES[0];
+
+/// Predicate group memberships are recorded with: `member(member, group)`
+pub const GROUP_PREDICATE: &str = GROUP_PREDICATES[0];
+
+/// Fact granting `role` to `holder`, a principal or group ID
+pub fn role_fact(holder: &str, role: &str) -> Result<Fact> {
+    Ok(Fact::binary(
+        ROLE_PREDICATE,
+        Value::string(checked_id("holder", holder)?),
+        Value::string(checked_id("role", role)?),
+    ))
+}
+
+/// Fact making `member`, a principal or group ID, a member of `group`
+pub fn membership_fact(member: &str, group: &str) -> Result<Fact> {
+    let member = checked_id("member", member)?;
+    let group = checked_id("group", group)?;
+    if member == group {
+        return Err(RUNEError::InvalidRequest(format!(
+            "Group {} cannot be a member of itself",
+            group
+        )));
+    }
+    Ok(Fact::binary(
+        GROUP_PREDICATE,
+        Value::string(member),
+        Value::string(group),
+    ))
+}
+
+/// `id`, if it can name a `kind`
+fn checked_id<'a>(kind: &str, id: &'a str) -> Result<&'a str> {
+    if id.is_empty() {
+        return Err(RUNEError::InvalidRequest(format!("Empty {} ID", kind)));
+    }
+    if id.trim() != id || id.chars().any(char::is_control) {
+        return Err(RUNEError::InvalidRequest(format!(
+            "Invalid {} ID {:?}: surrounding whitespace or control characters",
+            kind, id
+        )));
+    }
+    Ok(id)
+}
+
+/// What a principal is and which checks and policies concern it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionSummary {
+    /// Principal ID the summary is for
+    pub principal: String,
+    /// Roles held directly or through a group, sorted
+    pub roles: Vec<String>,
+    /// Groups the principal belongs to, directly or transitively, sorted
+    pub groups: Vec<String>,
+    /// Rule-derived facts naming the principal, in argument order
+    pub checks: Vec<Fact>,
+    /// IDs of permit policies scoped to the principal or its groups
+    pub permits: Vec<String>,
+    /// IDs of forbid policies scoped to the principal or its groups
+    pub forbids: Vec<String>,
+}
+
+/// Summarize `principal` from the fixpoint `facts` of `rules`
+pub(crate) fn summarize(
+    principal: &Principal,
+    facts: &[Fact],
+    rules: &[Rule],
+    policies: &PolicySet,
+) -> PermissionSummary {
+    let id = principal.entity.id.as_ref();
+    let pairs = |predicates: &[&str]| -> Vec<(&str, &str)> {
+        facts
+            .iter()
+            .filter(|fact| predicates.contains(&fact.predicate.as_ref()))
+            .filter_map(|fact| match &*fact.args {
+                [Value::String(from), Value::String(to)] => Some((from.as_ref(), to.as_ref())),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Walk membership edges outwards from the principal
+    let memberships = pairs(GROUP_PREDICATES);
+    let mut groups: BTreeSet<String> = BTreeSet::new();
+    let mut frontier = vec![id];
+    while let Some(member) = frontier.pop() {
+        for &(from, group) in &memberships {
+            if from == member && group != id && groups.insert(group.to_string()) {
+                frontier.push(group);
+            }
+        }
+    }
+
+    let roles: BTreeSet<String> = pairs(ROLE_PREDICATES)
+        .into_iter()
+        .filter(|(holder, _)| *holder == id || groups.contains(*holder))
+        .map(|(_, role)| role.to_string())
+        .collect();
+
+    let derived: HashSet<&str> = rules
+        .iter()
+        .filter(|rule| !rule.is_fact())
+        .map(|rule| rule.head.predicate.as_ref())
+        .filter(|predicate| {
+            !GROUP_PREDICATES.contains(predicate) && !ROLE_PREDICATES.contains(predicate)
+        })
+        .collect();
+    let mut checks: Vec<Fact> = facts
+        .iter()
+        .filter(|fact| derived.contains(fact.predicate.as_ref()))
+        .filter(|fact| {
+            fact.args
+                .iter()
+                .any(|arg| matches!(arg, Value::String(s) if s.as_ref() == id))
+        })
+        .cloned()
+        .collect();
+    checks.sort_unstable_by(|a, b| (&a.predicate, &a.args).cmp(&(&b.predicate, &b.args)));
+
+    let (permits, forbids) = policies.scoped_to(principal, &groups);
+    PermissionSummary {
+        principal: id.to_string(),
+        roles: roles.into_iter().collect(),
+        groups: groups.into_iter().collect(),
+        checks,
+        permits,
+        forbids,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rules;
+
+    #[test]
+    fn test_summarize() {
+        let s = Value::string;
+        let facts = vec![
+            Fact::new("member", vec![s("alice"), s("eng")]),
+            Fact::new("member_of", vec![s("eng"), s("staff")]),
+            Fact::new("member", vec![s("bob"), s("sales")]),
+            Fact::new("has_role", vec![s("alice"), s("reader")]),
+            Fact::new("role", vec![s("eng"), s("deployer")]),
+            Fact::new("role", vec![s("sales"), s("seller")]),
+            Fact::new("can_deploy", vec![s("alice"), s("api")]),
+            Fact::new("can_deploy", vec![s("bob"), s("api")]),
+            Fact::new("owner", vec![s("alice"), s("api")]),
+        ];
+        let rules = parse_rules("can_deploy(U, S) :- owner(U, S).").unwrap();
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+@id("staff-read")
+permit(principal in Group::"staff", action == Action::"read", resource);
+@id("sales-write")
+permit(principal in Group::"sales", action == Action::"write", resource);
+@id("no-delete")
+forbid(principal, action == Action::"delete", resource);
+@id("alice-only")
+permit(principal == User::"alice", action, resource);
+"#,
+            )
+            .unwrap();
+
+        let summary = summarize(&Principal::user("alice"), &facts, &rules, &policies);
+        assert_eq!(summary.principal, "alice");
+        assert_eq!(summary.groups, ["eng", "staff"]);
+        assert_eq!(summary.roles, ["deployer", "reader"]);
+        assert_eq!(
+            summary.checks,
+            [Fact::new("can_deploy", vec![s("alice"), s("api")])]
+        );
+        assert_eq!(summary.permits, ["alice-only", "staff-read"]);
+        assert_eq!(summary.forbids, ["no-delete"]);
+
+        // A different principal of the same ID is not matched by `==`
+        let agent = summarize(&Principal::agent("alice"), &facts, &rules, &policies);
+        assert_eq!(agent.permits, ["staff-read"]);
+    }
+
+    #[test]
+    fn test_role_and_membership_facts() {
+        let s = Value::string;
+        assert_eq!(
+            role_fact("alice", "reader").unwrap(),
+            Fact::new("has_role", vec![s("alice"), s("reader")])
+        );
+        assert_eq!(
+            membership_fact("alice", "eng").unwrap(),
+            Fact::new("member", vec![s("alice"), s("eng")])
+        );
+
+        assert!(role_fact("", "reader").is_err());
+        assert!(role_fact("alice", " reader").is_err());
+        assert!(role_fact("alice\n", "reader").is_err());
+        assert!(membership_fact("eng", "eng").is_err());
+    }
+}
@@ -1,19 +1,88 @@
 //! Cedar policy integration
 
-use crate::engine::{AuthorizationResult, Decision};
+use crate::decision::{AuthorizationResult, Decision};
 use crate::error::{RUNEError, Result};
+use crate::flags::RuleFlags;
+use crate::labels::{LabelTarget, Labeled};
+use crate::obligations::{parse_obligations, Obligation, OBLIGATION_ANNOTATION};
+use crate::ownership::{OwnedItem, OWNER_ANNOTATION};
 use crate::request::Request;
+use crate::types::{Principal, Value};
+use crate::warnings::{parse_warnings, Warning, WARNING_ANNOTATION};
 use cedar_policy::{
-    Authorizer, Context, Entities, PolicySet as CedarPolicySet, Request as CedarRequest,
+    Authorizer, Context, Effect, Entities, Policy, PolicyId, PolicySet as CedarPolicySet,
+    PrincipalConstraint, Request as CedarRequest, RestrictedExpression,
 };
 use cedar_policy::{Entity as CedarEntity, EntityId, EntityTypeName, EntityUid};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
 use std::str::FromStr;
 use std::time::Instant;
 
+/// Annotation ranking a policy among those a request satisfies
+pub const PRIORITY_ANNOTATION: &str = "priority";
+
+/// Whether a policy grants or denies access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEffect {
+    /// `permit(...)`
+    Permit,
+    /// `forbid(...)`
+    Forbid,
+}
+
+impl From<Effect> for PolicyEffect {
+    fn from(effect: Effect) -> Self {
+        match effect {
+            Effect::Permit => PolicyEffect::Permit,
+            Effect::Forbid => PolicyEffect::Forbid,
+        }
+    }
+}
+
+/// A policy a request satisfies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyMatch {
+    /// `@id` annotation, or the Cedar policy ID
+    pub id: String,
+    /// Effect of the policy
+    pub effect: PolicyEffect,
+    /// `@priority` annotation, 0 when absent; higher ranks first
+    pub priority: i64,
+}
+
+/// Every policy a request satisfies, without stopping at the first forbid
+///
+/// A decision only needs one satisfied forbid, or one permit and no forbid,
+/// and Cedar reports just the policies behind it. Permission management
+/// screens want the whole picture: every grant that would let the request
+/// through and every forbid standing in its way. Both lists are ordered by
+/// priority, highest first, then by ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyMatches {
+    /// Satisfied permit policies
+    pub permits: Vec<PolicyMatch>,
+    /// Satisfied forbid policies
+    pub forbids: Vec<PolicyMatch>,
+    /// Policies whose conditions failed to evaluate, which match neither way
+    pub errors: Vec<String>,
+}
+
+impl PolicyMatches {
+    /// Whether the matches add up to a permit: some permit, no forbid
+    pub fn is_permitted(&self) -> bool {
+        !self.permits.is_empty() && self.forbids.is_empty()
+    }
+}
+
 /// Policy set wrapper for Cedar
 pub struct PolicySet {
+    /// Policies that take part in evaluation
     cedar_policies: CedarPolicySet,
+    /// Every loaded policy, including disabled ones
+    all_policies: CedarPolicySet,
     authorizer: Authorizer,
 }
 
@@ -22,6 +91,7 @@ impl PolicySet {
     pub fn new() -> Self {
         PolicySet {
             cedar_policies: CedarPolicySet::new(),
+            all_policies: CedarPolicySet::new(),
             authorizer: Authorizer::new(),
         }
     }
@@ -31,18 +101,23 @@ impl PolicySet {
         let policies = policy_str
             .parse::<CedarPolicySet>()
             .map_err(|e| RUNEError::ConfigError(format!("Failed to parse policies: {}", e)))?;
+        check_obligations(policies.policies())?;
+        check_warnings(policies.policies())?;
+        check_priorities(policies.policies())?;
 
-        self.cedar_policies = policies;
+        self.cedar_policies = active_policies(&policies, &RuleFlags::new())?;
+        self.all_policies = policies;
         Ok(())
     }
 
     /// Add a single policy
-    pub fn add_policy(&mut self, _id: &str, policy_str: &str) -> Result<()> {
-        use cedar_policy::Policy;
-
-        // Parse policy with a template-linked ID
-        let policy = Policy::parse(None, policy_str)
+    pub fn add_policy(&mut self, id: &str, policy_str: &str) -> Result<()> {
+        // Cedar would name every policy parsed on its own `policy0`
+        let policy = Policy::parse(Some(id.to_string()), policy_str)
             .map_err(|e| RUNEError::ConfigError(format!("Failed to parse policy: {}", e)))?;
+        check_obligations(std::iter::once(&policy))?;
+        check_warnings(std::iter::once(&policy))?;
+        check_priorities(std::iter::once(&policy))?;
 
         // For Cedar 3.x, we need to rebuild the policy set
         let mut new_set = CedarPolicySet::new();
@@ -51,16 +126,170 @@ impl PolicySet {
             .map_err(|e| RUNEError::ConfigError(format!("Failed to add policy: {}", e)))?;
 
         // Merge with existing policies
-        for p in self.cedar_policies.policies() {
+        for p in self.all_policies.policies() {
             new_set
                 .add(p.clone())
                 .map_err(|e| RUNEError::ConfigError(format!("Failed to merge policy: {}", e)))?;
         }
 
-        self.cedar_policies = new_set;
+        self.cedar_policies = active_policies(&new_set, &RuleFlags::new())?;
+        self.all_policies = new_set;
         Ok(())
     }
 
+    /// Create a copy of this set with runtime flags applied
+    pub fn with_flags(&self, flags: &RuleFlags) -> Result<Self> {
+        Ok(PolicySet {
+            cedar_policies: active_policies(&self.all_policies, flags)?,
+            all_policies: self.all_policies.clone(),
+            authorizer: Authorizer::new(),
+        })
+    }
+
+    /// Flag key and source default for every loaded policy
+    pub fn flag_keys(&self) -> impl Iterator<Item = (String, bool)> + '_ {
+        self.all_policies
+            .policies()
+            .map(|p| (policy_flag_key(p), policy_enabled_by_default(p)))
+    }
+
+    /// ID and Cedar source of every loaded policy, ordered by ID
+    ///
+    /// The ID is the `@id` annotation when present. Disabled policies are
+    /// included.
+    pub fn sources(&self) -> Vec<(String, String)> {
+        let mut sources: Vec<_> = self
+            .all_policies
+            .policies()
+            .map(|p| (policy_id(p), p.to_string().trim().to_string()))
+            .collect();
+        sources.sort();
+        sources
+    }
+
+    /// Labels (annotations) of every loaded policy that has any, by ID
+    pub fn labels(&self) -> Vec<Labeled> {
+        let mut labels: Vec<Labeled> = self
+            .all_policies
+            .policies()
+            .map(policy_labels)
+            .filter(|labeled| !labeled.labels.is_empty())
+            .collect();
+        labels.sort_by(|a, b| a.id.cmp(&b.id));
+        labels
+    }
+
+    /// Owner, flag key and source of every loaded policy with an owner
+    pub(crate) fn owned(&self) -> Vec<OwnedItem> {
+        self.all_policies
+            .policies()
+            .filter_map(|policy| {
+                Some(OwnedItem {
+                    target: LabelTarget::Policy,
+                    id: policy_id(policy),
+                    owner: policy.annotation(OWNER_ANNOTATION)?.to_string(),
+                    flag: Some(policy_flag_key(policy)),
+                    source: policy.to_string().trim().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Labels of the active policy Cedar knows as `policy_id`
+    pub fn labels_of(&self, policy_id: &str) -> Option<Labeled> {
+        self.cedar_policies
+            .policy(&PolicyId::from_str(policy_id).ok()?)
+            .map(policy_labels)
+    }
+
+    /// Policies that take part in evaluation
+    pub(crate) fn active_policies(&self) -> impl Iterator<Item = &Policy> {
+        self.cedar_policies.policies()
+    }
+
+    /// Every loaded policy, including disabled ones
+    pub(crate) fn loaded_policies(&self) -> impl Iterator<Item = &Policy> {
+        self.all_policies.policies()
+    }
+
+    /// Whether a loaded policy takes part in evaluation
+    pub(crate) fn is_active(&self, policy: &Policy) -> bool {
+        self.cedar_policies.policy(policy.id()).is_some()
+    }
+
+    /// Whether no policies are loaded, counting disabled ones
+    pub fn is_empty(&self) -> bool {
+        self.all_policies.policies().next().is_none()
+    }
+
+    /// Number of policies that take part in evaluation
+    pub fn active_count(&self) -> usize {
+        self.cedar_policies.policies().count()
+    }
+
+    /// Reduced set holding the given permit policies plus every forbid policy
+    ///
+    /// Because no forbid is left out, a request this set permits is permitted
+    /// by the full set too, and a forbid it matches is final as well. Only a
+    /// default deny (nothing matched) is inconclusive.
+    pub fn speculative_subset(&self, permit_ids: &[String]) -> Result<Self> {
+        let mut subset = CedarPolicySet::new();
+        for policy in self.cedar_policies.policies() {
+            let keep = policy.effect() == Effect::Forbid
+                || permit_ids.iter().any(|id| *id == policy.id().to_string());
+            if keep {
+                subset
+                    .add(policy.clone())
+                    .map_err(|e| RUNEError::ConfigError(format!("Failed to add policy: {}", e)))?;
+            }
+        }
+
+        Ok(PolicySet {
+            all_policies: subset.clone(),
+            cedar_policies: subset,
+            authorizer: Authorizer::new(),
+        })
+    }
+
+    /// IDs of the active permit and forbid policies whose principal scope
+    /// covers `principal`, as a member of `groups`, sorted
+    ///
+    /// Only the scope is checked, not the policies' conditions.
+    pub(crate) fn scoped_to(
+        &self,
+        principal: &Principal,
+        groups: &BTreeSet<String>,
+    ) -> (Vec<String>, Vec<String>) {
+        let entity = &principal.entity;
+        let is = |uid: &EntityUid| {
+            uid.type_name().to_string() == *entity.entity_type && uid.id().as_ref() == &*entity.id
+        };
+        let within = |uid: &EntityUid| is(uid) || groups.contains(uid.id().as_ref());
+        let of_type = |name: &EntityTypeName| name.to_string() == *entity.entity_type;
+
+        let mut permits = Vec::new();
+        let mut forbids = Vec::new();
+        for policy in self.cedar_policies.policies() {
+            let applies = match policy.principal_constraint() {
+                PrincipalConstraint::Any => true,
+                PrincipalConstraint::Eq(uid) => is(&uid),
+                PrincipalConstraint::In(uid) => within(&uid),
+                PrincipalConstraint::Is(name) => of_type(&name),
+                PrincipalConstraint::IsIn(name, uid) => of_type(&name) && within(&uid),
+            };
+            if applies {
+                let id = policy_id(policy);
+                match policy.effect() {
+                    Effect::Permit => permits.push(id),
+                    Effect::Forbid => forbids.push(id),
+                }
+            }
+        }
+        permits.sort();
+        forbids.sort();
+        (permits, forbids)
+    }
+
     /// Evaluate a request against the policies
     pub fn evaluate(&self, request: &Request) -> Result<AuthorizationResult> {
         let start = Instant::now();
@@ -91,10 +320,24 @@ impl PolicySet {
             explanation.push_str(&format!("Error: {}; ", error));
         }
 
-        // Collect the policy IDs that contributed to the decision
+        // Collect the policy IDs that contributed to the decision, and the
+        // obligations and warnings of those that permitted it
+        let mut obligations = Vec::new();
+        let mut warnings = Vec::new();
         for policy_id in response.diagnostics().reason() {
             evaluated_rules.push(policy_id.to_string());
+            if decision == Decision::Permit {
+                if let Some(policy) = self.cedar_policies.policy(policy_id) {
+                    obligations.extend(policy_obligations(policy));
+                    warnings.extend(policy_warnings(policy));
+                }
+            }
         }
+        // Reasons come back in no particular order
+        obligations.sort();
+        obligations.dedup();
+        warnings.sort();
+        warnings.dedup();
 
         if explanation.is_empty() {
             explanation = match decision {
@@ -109,11 +352,64 @@ impl PolicySet {
             explanation,
             evaluated_rules,
             facts_used: vec![], // Cedar doesn't expose this directly
+            policies_evaluated: self.cedar_policies.policies().count(),
             evaluation_time_ns: start.elapsed().as_nanos() as u64,
             cached: false,
+            coalesced: false,
+            failures: Vec::new(),
+            obligations,
+            warnings,
+            reason_codes: Vec::new(),
+            valid_for_ms: 0,
+            proofs: Vec::new(),
+            near_misses: Vec::new(),
         })
     }
 
+    /// Every active policy `request` satisfies (see [`PolicyMatches`])
+    ///
+    /// Permits and forbids are evaluated as separate sets, so neither side
+    /// hides the other and each reports all of its satisfied policies.
+    pub fn matching_policies(&self, request: &Request) -> Result<PolicyMatches> {
+        let cedar_request = self.convert_request(request)?;
+        let entities = self.create_entities(request)?;
+
+        let mut matches = PolicyMatches::default();
+        for effect in [Effect::Permit, Effect::Forbid] {
+            let mut side = CedarPolicySet::new();
+            for policy in self.cedar_policies.policies() {
+                if policy.effect() == effect {
+                    side.add(policy.clone()).map_err(|e| {
+                        RUNEError::ConfigError(format!("Failed to add policy: {}", e))
+                    })?;
+                }
+            }
+
+            let response = self
+                .authorizer
+                .is_authorized(&cedar_request, &side, &entities);
+            let mut found: Vec<PolicyMatch> = response
+                .diagnostics()
+                .reason()
+                .filter_map(|id| side.policy(id))
+                .map(|policy| PolicyMatch {
+                    id: policy_id(policy),
+                    effect: effect.into(),
+                    priority: policy_priority(policy),
+                })
+                .collect();
+            found.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.id.cmp(&b.id)));
+            matches
+                .errors
+                .extend(response.diagnostics().errors().map(|e| e.to_string()));
+            match effect {
+                Effect::Permit => matches.permits = found,
+                Effect::Forbid => matches.forbids = found,
+            }
+        }
+        Ok(matches)
+    }
+
     /// Convert RUNE request to Cedar request
     fn convert_request(&self, request: &Request) -> Result<CedarRequest> {
         // Convert principal
@@ -198,8 +494,12 @@ impl PolicySet {
 
         let uid = EntityUid::from_type_name_and_id(entity_type, entity_id);
 
-        // Convert attributes (simplified for now - Cedar has strict typing)
-        let attributes = HashMap::new();
+        // Nulls have no Cedar counterpart, so those attributes are left out
+        let attributes = entity
+            .attributes
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), restricted_expression(value)?)))
+            .collect::<HashMap<_, _>>();
 
         // Convert parent relationships
         let mut parents = std::collections::HashSet::new();
@@ -219,6 +519,159 @@ impl PolicySet {
     }
 }
 
+/// Cedar literal for an attribute value
+/// Bytes of `policy_str` that Cedar blames for its first parse error, if
+/// it fails to parse
+pub(crate) fn parse_error_range(policy_str: &str) -> Option<Range<usize>> {
+    let errors = Policy::parse(None, policy_str).err()?;
+    let label = miette::Diagnostic::labels(&errors)?.next()?;
+    Some(label.offset()..label.offset() + label.len())
+}
+
+fn restricted_expression(value: &Value) -> Option<RestrictedExpression> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some(RestrictedExpression::new_bool(*b)),
+        Value::Integer(i) => Some(RestrictedExpression::new_long(*i)),
+        Value::String(s) => Some(RestrictedExpression::new_string(s.to_string())),
+        Value::Array(values) => Some(RestrictedExpression::new_set(
+            values.iter().filter_map(restricted_expression),
+        )),
+        Value::Object(fields) => RestrictedExpression::new_record(
+            fields
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), restricted_expression(value)?))),
+        )
+        .ok(),
+    }
+}
+
+/// Name of a policy: its `@id` annotation, or the Cedar policy ID
+pub(crate) fn policy_id(policy: &Policy) -> String {
+    policy
+        .annotation("id")
+        .map(str::to_string)
+        .unwrap_or_else(|| policy.id().to_string())
+}
+
+/// Labels of a policy: its annotations
+fn policy_labels(policy: &Policy) -> Labeled {
+    Labeled {
+        target: LabelTarget::Policy,
+        id: policy_id(policy),
+        labels: policy
+            .annotations()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+/// Rank of a policy (`@priority`); checked when the policy was loaded
+fn policy_priority(policy: &Policy) -> i64 {
+    policy
+        .annotation(PRIORITY_ANNOTATION)
+        .and_then(|priority| priority.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Key used to toggle a policy: `@flag`, then `@id`, then the Cedar policy id
+fn policy_flag_key(policy: &Policy) -> String {
+    policy
+        .annotation("flag")
+        .or_else(|| policy.annotation("id"))
+        .map(str::to_string)
+        .unwrap_or_else(|| policy.id().to_string())
+}
+
+/// Whether a policy is enabled before any runtime override (`@enabled("false")`)
+fn policy_enabled_by_default(policy: &Policy) -> bool {
+    policy.annotation("enabled") != Some("false")
+}
+
+/// Obligations a policy declares; checked when the policy was loaded
+fn policy_obligations(policy: &Policy) -> Vec<Obligation> {
+    policy
+        .annotation(OBLIGATION_ANNOTATION)
+        .and_then(|annotation| parse_obligations(annotation).ok())
+        .unwrap_or_default()
+}
+
+/// Reject policies whose `@obligation` annotation does not parse, or that
+/// put obligations on a forbid
+fn check_obligations<'a>(policies: impl Iterator<Item = &'a Policy>) -> Result<()> {
+    for policy in policies {
+        let Some(annotation) = policy.annotation(OBLIGATION_ANNOTATION) else {
+            continue;
+        };
+        if policy.effect() == Effect::Forbid {
+            return Err(RUNEError::ConfigError(format!(
+                "Policy {} is a forbid; only permits carry obligations",
+                policy.id()
+            )));
+        }
+        parse_obligations(annotation)
+            .map_err(|e| RUNEError::ConfigError(format!("Policy {}: {}", policy.id(), e)))?;
+    }
+    Ok(())
+}
+
+/// Warnings a policy declares; checked when the policy was loaded
+fn policy_warnings(policy: &Policy) -> Vec<Warning> {
+    policy
+        .annotation(WARNING_ANNOTATION)
+        .and_then(|annotation| parse_warnings(annotation).ok())
+        .unwrap_or_default()
+}
+
+/// Reject policies whose `@warning` annotation does not parse, or that put
+/// warnings on a forbid
+fn check_warnings<'a>(policies: impl Iterator<Item = &'a Policy>) -> Result<()> {
+    for policy in policies {
+        let Some(annotation) = policy.annotation(WARNING_ANNOTATION) else {
+            continue;
+        };
+        if policy.effect() == Effect::Forbid {
+            return Err(RUNEError::ConfigError(format!(
+                "Policy {} is a forbid; only permits carry warnings",
+                policy.id()
+            )));
+        }
+        parse_warnings(annotation)
+            .map_err(|e| RUNEError::ConfigError(format!("Policy {}: {}", policy.id(), e)))?;
+    }
+    Ok(())
+}
+
+/// Reject policies whose `@priority` annotation is not an integer
+fn check_priorities<'a>(policies: impl Iterator<Item = &'a Policy>) -> Result<()> {
+    for policy in policies {
+        if let Some(priority) = policy.annotation(PRIORITY_ANNOTATION) {
+            priority.trim().parse::<i64>().map_err(|_| {
+                RUNEError::ConfigError(format!(
+                    "Policy {}: priority '{}' is not an integer",
+                    policy.id(),
+                    priority
+                ))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Build the set of policies that are switched on
+fn active_policies(all: &CedarPolicySet, flags: &RuleFlags) -> Result<CedarPolicySet> {
+    let mut active = CedarPolicySet::new();
+    for policy in all.policies() {
+        let key = policy_flag_key(policy);
+        if flags.is_enabled(Some(&key), policy_enabled_by_default(policy)) {
+            active
+                .add(policy.clone())
+                .map_err(|e| RUNEError::ConfigError(format!("Failed to add policy: {}", e)))?;
+        }
+    }
+    Ok(active)
+}
+
 impl Default for PolicySet {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,246 @@
+//! Reverse index from policies and rules to the traffic they can affect
+//!
+//! Before editing a policy, an operator wants to know which requests could
+//! be decided differently afterwards. [`PolicyIndex`] answers that without
+//! evaluating anything, mapping each policy to the (action, resource type,
+//! principal type) space its head reaches:
+//!
+//! - `action == Action::"read"` and `action in [...]` fix the actions;
+//!   a bare `action` reaches every action
+//! - `principal == User::"alice"`, `principal is User` and
+//!   `principal is User in Group::"staff"` fix the principal type, while
+//!   `principal in Group::"staff"` does not, since a group's members may be
+//!   of any type; resources likewise
+//!
+//! `when`/`unless` clauses only narrow a policy further, so the space is an
+//! upper bound. Datalog rules take part in every decision whatever the
+//! request, so each rule reaches the whole space; they are indexed anyway,
+//! by `@id` or rule text as labels are, so that asking about a rule gets an
+//! answer.
+//!
+//! The engine keeps the index of its current generation (see
+//! [`RUNEEngine::policy_index`](crate::RUNEEngine::policy_index)).
+
+use crate::conflicts::{actions, EntityScope};
+use crate::datalog::types::Rule;
+use crate::labels::LabelTarget;
+use crate::policy::{policy_id, PolicyEffect, PolicySet};
+use cedar_policy::Policy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The requests a policy or rule can affect
+///
+/// Each part is `None` when the scope leaves it open.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficScope {
+    /// Action names
+    pub actions: Option<Vec<String>>,
+    /// Resource entity type
+    pub resource_type: Option<String>,
+    /// Principal entity type
+    pub principal_type: Option<String>,
+}
+
+impl TrafficScope {
+    /// Scope reaching every request
+    pub fn any() -> Self {
+        TrafficScope::default()
+    }
+
+    fn of_policy(policy: &Policy) -> Self {
+        let entity_type = |scope: EntityScope| scope.entity_type().map(|name| name.to_string());
+        TrafficScope {
+            actions: actions(policy.action_constraint()).map(|uids| {
+                uids.iter()
+                    .map(|uid| uid.id().as_ref().to_string())
+                    .collect()
+            }),
+            resource_type: entity_type(EntityScope::resource(policy.resource_constraint())),
+            principal_type: entity_type(EntityScope::principal(policy.principal_constraint())),
+        }
+    }
+
+    /// Whether requests of this traffic class are in scope
+    pub fn contains(&self, action: &str, resource_type: &str, principal_type: &str) -> bool {
+        self.actions
+            .as_ref()
+            .is_none_or(|actions| actions.iter().any(|a| a == action))
+            && self
+                .resource_type
+                .as_ref()
+                .is_none_or(|name| name == resource_type)
+            && self
+                .principal_type
+                .as_ref()
+                .is_none_or(|name| name == principal_type)
+    }
+}
+
+/// The scope of one policy or rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedScope {
+    /// `@id` annotation, or the Cedar policy ID or rule text
+    pub id: String,
+    /// Whether this is a policy or a rule
+    pub target: LabelTarget,
+    /// Effect, for policies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<PolicyEffect>,
+    /// Whether it takes part in evaluation, rather than being switched off
+    /// by a flag
+    pub enabled: bool,
+    /// The requests it can affect
+    pub scope: TrafficScope,
+}
+
+/// Scopes of every loaded policy and rule, for one configuration generation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyIndex {
+    /// Generation the index was built for
+    pub generation: u64,
+    /// Policies by ID, including disabled ones
+    pub policies: BTreeMap<String, IndexedScope>,
+    /// Rules by ID, including disabled ones
+    pub rules: BTreeMap<String, IndexedScope>,
+}
+
+impl PolicyIndex {
+    /// Index the loaded `policies` and `rules`, of which `active` take part
+    /// in evaluation
+    pub fn build(generation: u64, policies: &PolicySet, rules: &[Rule], active: &[Rule]) -> Self {
+        let policies = policies
+            .loaded_policies()
+            .map(|policy| {
+                let id = policy_id(policy);
+                let indexed = IndexedScope {
+                    id: id.clone(),
+                    target: LabelTarget::Policy,
+                    effect: Some(policy.effect().into()),
+                    enabled: policies.is_active(policy),
+                    scope: TrafficScope::of_policy(policy),
+                };
+                (id, indexed)
+            })
+            .collect();
+
+        let mut indexed_rules: BTreeMap<String, IndexedScope> = BTreeMap::new();
+        for rule in rules.iter().filter(|rule| !rule.is_fact()) {
+            let id = rule
+                .annotations
+                .get("id")
+                .cloned()
+                .unwrap_or_else(|| rule.to_string());
+            let enabled = active.contains(rule);
+            // The same rule may be loaded twice; it is enabled if either is
+            indexed_rules
+                .entry(id.clone())
+                .and_modify(|indexed| indexed.enabled |= enabled)
+                .or_insert(IndexedScope {
+                    id,
+                    target: LabelTarget::Rule,
+                    effect: None,
+                    enabled,
+                    scope: TrafficScope::any(),
+                });
+        }
+
+        PolicyIndex {
+            generation,
+            policies,
+            rules: indexed_rules,
+        }
+    }
+
+    /// Scope of the policy, or failing that the rule, named `id`
+    pub fn get(&self, id: &str) -> Option<&IndexedScope> {
+        self.policies.get(id).or_else(|| self.rules.get(id))
+    }
+
+    /// Policies and rules that can affect requests of a traffic class,
+    /// policies first
+    pub fn affecting(
+        &self,
+        action: &str,
+        resource_type: &str,
+        principal_type: &str,
+    ) -> Vec<&IndexedScope> {
+        self.policies
+            .values()
+            .chain(self.rules.values())
+            .filter(|indexed| {
+                indexed
+                    .scope
+                    .contains(action, resource_type, principal_type)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::RuleFlags;
+    use crate::parser::parse_rules;
+
+    #[test]
+    fn test_policy_index() {
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+@id("readers")
+permit(principal is User in Group::"staff", action in [Action::"read", Action::"list"], resource in Folder::"q3");
+
+@id("no-secrets")
+@flag("secrets")
+forbid(principal, action, resource == Document::"secret");
+"#,
+            )
+            .unwrap();
+        let flags = RuleFlags::new();
+        flags.set("secrets", false);
+        let policies = policies.with_flags(&flags).unwrap();
+        let rules = parse_rules(
+            "@id(\"admins\")\nadmin(U) :- member(U, \"admins\").\nmember(\"alice\", \"admins\").",
+        )
+        .unwrap();
+
+        let index = PolicyIndex::build(7, &policies, &rules, &rules);
+        assert_eq!(index.generation, 7);
+
+        let readers = index.get("readers").unwrap();
+        assert_eq!(readers.effect, Some(PolicyEffect::Permit));
+        assert!(readers.enabled);
+        assert_eq!(
+            readers.scope,
+            TrafficScope {
+                actions: Some(vec!["read".to_string(), "list".to_string()]),
+                resource_type: None,
+                principal_type: Some("User".to_string()),
+            }
+        );
+
+        let secrets = index.get("no-secrets").unwrap();
+        assert!(!secrets.enabled);
+        assert_eq!(secrets.scope.resource_type.as_deref(), Some("Document"));
+        assert_eq!(secrets.scope.actions, None);
+
+        // Facts are not rules; rules reach everything
+        assert_eq!(index.rules.len(), 1);
+        assert_eq!(index.get("admins").unwrap().scope, TrafficScope::any());
+
+        let affecting: Vec<&str> = index
+            .affecting("read", "Document", "User")
+            .iter()
+            .map(|indexed| indexed.id.as_str())
+            .collect();
+        assert_eq!(affecting, ["no-secrets", "readers", "admins"]);
+        let affecting: Vec<&str> = index
+            .affecting("write", "Folder", "User")
+            .iter()
+            .map(|indexed| indexed.id.as_str())
+            .collect();
+        assert_eq!(affecting, ["admins"]);
+    }
+}
@@ -3,15 +3,24 @@
 //! This module orchestrates automatic reloading when .rune files change,
 //! using the file watcher to detect changes and the RUNEEngine's atomic swap
 //! capabilities to update rules and policies without downtime.
+//!
+//! Changes are applied as transactions. When several watched files change
+//! together (rules, policies and entities pushed by one deploy), the
+//! coordinator waits until none of them has changed for the debounce
+//! duration, then reads every watched file, validates them jointly as
+//! layers in the order they were watched (see [`crate::layers`]) and swaps
+//! the result in as one generation. A bad file, or an `@id` defined in two
+//! files, keeps the previous configuration for all of them.
 
 use crate::engine::RUNEEngine;
 use crate::error::{RUNEError, Result};
+use crate::layers::ConfigLayer;
 use crate::parser::parse_rune_file;
-use crate::policy::PolicySet;
-use crate::watcher::{EventDebouncer, RUNEWatcher};
+use crate::watcher::{EventDebouncer, RUNEWatcher, WatchMode, WatcherHealth};
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
@@ -48,6 +57,19 @@ pub struct ReloadConfig {
     pub retry_delay: Duration,
     /// Enable automatic reload on file changes
     pub auto_reload: bool,
+    /// Minimum time between engine swaps once the burst is spent
+    pub min_reload_interval: Duration,
+    /// Number of swaps allowed back-to-back before throttling kicks in
+    pub reload_burst: u32,
+    /// How the watcher detects changes; use polling on network
+    /// filesystems that drop notifications
+    pub watch_mode: WatchMode,
+    /// How often to compare watched files' modification times against the
+    /// events seen, catching changes the watcher missed (zero disables)
+    pub verify_interval: Duration,
+    /// Longest a change waits for the rest of its set to settle before the
+    /// set is reloaded anyway
+    pub max_settle_wait: Duration,
 }
 
 impl Default for ReloadConfig {
@@ -57,6 +79,70 @@ impl Default for ReloadConfig {
             max_retry_attempts: 3,
             retry_delay: Duration::from_secs(1),
             auto_reload: true,
+            min_reload_interval: Duration::from_secs(1),
+            reload_burst: 1,
+            watch_mode: WatchMode::Native,
+            verify_interval: Duration::from_secs(5),
+            max_settle_wait: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Token bucket limiting how often the engine is swapped
+///
+/// Each reload spends a token; tokens refill one per `interval` up to
+/// `burst`. Every swap clears the decision cache, so this bounds cache churn
+/// when many files change in quick succession.
+#[derive(Debug, Clone)]
+pub struct ReloadThrottle {
+    interval: Duration,
+    burst: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl ReloadThrottle {
+    /// Create a throttle with a full bucket
+    pub fn new(interval: Duration, burst: u32) -> Self {
+        let burst = burst.max(1);
+        ReloadThrottle {
+            interval,
+            burst,
+            tokens: f64::from(burst),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token if one is available
+    pub fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    /// Time until the next token becomes available
+    pub fn time_until_ready(&self) -> Duration {
+        if self.interval.is_zero() || self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+        let elapsed = self.last_refill.elapsed().as_secs_f64() / self.interval.as_secs_f64();
+        let missing = (1.0 - self.tokens - elapsed).max(0.0);
+        self.interval.mul_f64(missing)
+    }
+
+    fn try_acquire_at(&mut self, now: Instant) -> bool {
+        if self.interval.is_zero() {
+            return true;
+        }
+
+        let refilled =
+            now.duration_since(self.last_refill).as_secs_f64() / self.interval.as_secs_f64();
+        self.tokens = (self.tokens + refilled).min(f64::from(self.burst));
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
         }
     }
 }
@@ -78,6 +164,12 @@ pub struct ReloadCoordinator {
     event_tx: Option<mpsc::UnboundedSender<ReloadEvent>>,
     /// Watched files
     watched_files: Vec<PathBuf>,
+    /// Rate limiter for engine swaps
+    throttle: ReloadThrottle,
+    /// Settled changes waiting for the throttle, merged into one reload
+    pending: BTreeSet<PathBuf>,
+    /// When watched files were last checked for missed changes
+    last_verify: Instant,
 }
 
 impl ReloadCoordinator {
@@ -88,8 +180,9 @@ impl ReloadCoordinator {
 
     /// Create a reload coordinator with custom configuration
     pub fn with_config(engine: Arc<RUNEEngine>, config: ReloadConfig) -> Result<Self> {
-        let watcher = RUNEWatcher::new()?;
+        let watcher = RUNEWatcher::with_mode(config.watch_mode)?;
         let debouncer = EventDebouncer::new(config.debounce_duration);
+        let throttle = ReloadThrottle::new(config.min_reload_interval, config.reload_burst);
 
         Ok(ReloadCoordinator {
             engine,
@@ -98,6 +191,9 @@ impl ReloadCoordinator {
             config,
             event_tx: None,
             watched_files: Vec::new(),
+            throttle,
+            pending: BTreeSet::new(),
+            last_verify: Instant::now(),
         })
     }
 
@@ -142,29 +238,60 @@ impl ReloadCoordinator {
                 self.debouncer.add_event(event);
             }
 
-            // Check for settled events (debounced)
-            let settled_events = self.debouncer.get_settled_events();
+            // Catch changes the watcher never reported
+            if !self.config.verify_interval.is_zero()
+                && self.last_verify.elapsed() >= self.config.verify_interval
+            {
+                self.last_verify = Instant::now();
+                if self.watcher.check_for_missed_changes() > 0
+                    && self.config.watch_mode == WatchMode::Native
+                {
+                    warn!("File watcher is missing changes; consider polling (watch_mode = Poll)");
+                }
+            }
+
+            // Wait for the whole changed set to settle
+            let settled_events = self.debouncer.get_settled_set(self.config.max_settle_wait);
 
             for event in settled_events {
                 if !self.config.auto_reload {
                     debug!("Auto-reload disabled, skipping: {:?}", event.path);
                     continue;
                 }
+                self.pending.insert(event.path);
+            }
 
-                // Attempt reload
-                let reload_result = self.reload_file(&event.path).await;
-
-                // Send reload event
-                if let Some(tx) = &self.event_tx {
-                    let reload_event = ReloadEvent {
-                        path: event.path.clone(),
-                        result: reload_result,
-                        timestamp: std::time::Instant::now(),
-                    };
-
-                    if tx.send(reload_event).is_err() {
-                        warn!("Failed to send reload event (no subscribers)");
+            // Swap at most once per token; changes arriving meanwhile are
+            // merged into the next reload
+            if !self.pending.is_empty() {
+                if self.throttle.try_acquire() {
+                    let paths: Vec<PathBuf> =
+                        std::mem::take(&mut self.pending).into_iter().collect();
+                    // Every watched file, so the files that did not change
+                    // stay part of the new generation
+                    let reload_result = self.reload_files(&self.watched_files).await;
+
+                    // Send one reload event per changed file
+                    if let Some(tx) = &self.event_tx {
+                        let timestamp = std::time::Instant::now();
+                        for path in paths {
+                            let reload_event = ReloadEvent {
+                                path,
+                                result: reload_result.clone(),
+                                timestamp,
+                            };
+
+                            if tx.send(reload_event).is_err() {
+                                warn!("Failed to send reload event (no subscribers)");
+                            }
+                        }
                     }
+                } else {
+                    debug!(
+                        "Reload throttled, {} file(s) pending for {:?}",
+                        self.pending.len(),
+                        self.throttle.time_until_ready()
+                    );
                 }
             }
 
@@ -175,55 +302,53 @@ impl ReloadCoordinator {
 
     /// Reload configuration from a file
     async fn reload_file(&self, path: &Path) -> ReloadResult {
-        // Read file
-        let content = match tokio::fs::read_to_string(path).await {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to read {:?}: {}", path, e);
-                return ReloadResult::Failed(format!("Failed to read file: {}", e));
-            }
-        };
-
-        // Parse configuration
-        let config = match parse_rune_file(&content) {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to parse {:?}: {}", path, e);
-                return ReloadResult::Failed(format!("Parse error: {}", e));
-            }
-        };
-
-        // Reload Datalog rules
-        if !config.rules.is_empty() {
-            if let Err(e) = self.engine.reload_datalog_rules(config.rules) {
-                error!("Failed to reload Datalog rules: {}", e);
-                return ReloadResult::Failed(format!("Datalog reload error: {}", e));
-            }
-            info!("Reloaded Datalog rules from {:?}", path);
-        }
+        self.reload_files(&[path.to_path_buf()]).await
+    }
 
-        // Reload Cedar policies
-        if !config.policies.is_empty() {
-            // Create new policy set
-            let mut policy_set = PolicySet::new();
+    /// Reload several files as a single engine generation
+    ///
+    /// Every file is read and parsed, then the files are validated together
+    /// as layers in the order given, and only then swapped in. One bad file
+    /// keeps the previous configuration for the whole set.
+    async fn reload_files(&self, paths: &[PathBuf]) -> ReloadResult {
+        let mut layers = Vec::with_capacity(paths.len());
+        let mut seen = BTreeSet::new();
+        for path in paths.iter().filter(|path| seen.insert(*path)) {
+            // Read file
+            let content = match tokio::fs::read_to_string(path).await {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Failed to read {:?}: {}", path, e);
+                    return ReloadResult::Failed(format!("Failed to read file: {}", e));
+                }
+            };
 
-            // Add each policy
-            for policy in config.policies {
-                if let Err(e) = policy_set.add_policy(&policy.id, &policy.content) {
-                    error!("Failed to add policy {}: {}", policy.id, e);
-                    return ReloadResult::Failed(format!("Policy add error: {}", e));
+            // Parse configuration
+            let config = match parse_rune_file(&content) {
+                Ok(c) => c.with_path(path),
+                Err(e) => {
+                    error!("Failed to parse {:?}: {}", path, e);
+                    return ReloadResult::Failed(format!("Parse error: {}", e));
                 }
-            }
+            };
+
+            layers.push(ConfigLayer::new(path.display().to_string(), config));
+        }
 
-            // Reload the policy set
-            if let Err(e) = self.engine.reload_policies(policy_set) {
-                error!("Failed to reload policies: {}", e);
-                return ReloadResult::Failed(format!("Policy reload error: {}", e));
+        // Validate the files jointly, then swap them in as one generation
+        let layered = match crate::layers::compose(layers) {
+            Ok(layered) => layered,
+            Err(e) => {
+                error!("Files conflict in {:?}: {}", paths, e);
+                return ReloadResult::Failed(format!("Layer conflict: {}", e));
             }
-            info!("Reloaded Cedar policies from {:?}", path);
+        };
+        if let Err(e) = self.engine.apply_config(layered.config) {
+            error!("Failed to reload {:?}: {}", paths, e);
+            return ReloadResult::Failed(format!("Policy or rule error: {}", e));
         }
 
-        info!("Successfully reloaded configuration from {:?}", path);
+        info!("Successfully reloaded configuration from {:?}", paths);
         ReloadResult::Success
     }
 
@@ -241,6 +366,11 @@ impl ReloadCoordinator {
     pub fn watched_files(&self) -> &[PathBuf] {
         &self.watched_files
     }
+
+    /// Watch mode, last event time and missed changes of the file watcher
+    pub fn watcher_health(&self) -> WatcherHealth {
+        self.watcher.health()
+    }
 }
 
 #[cfg(test)]
@@ -272,6 +402,21 @@ mod tests {
         assert_eq!(coordinator.watched_files().len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_coordinator_watch_mode() {
+        let engine = Arc::new(RUNEEngine::new());
+        let config = ReloadConfig {
+            watch_mode: WatchMode::Poll(Duration::from_millis(200)),
+            ..ReloadConfig::default()
+        };
+        let coordinator = ReloadCoordinator::with_config(engine, config).unwrap();
+
+        let health = coordinator.watcher_health();
+        assert_eq!(health.mode, WatchMode::Poll(Duration::from_millis(200)));
+        assert_eq!(health.events, 0);
+        assert_eq!(health.missed_changes, 0);
+    }
+
     #[tokio::test]
     async fn test_manual_reload() {
         let engine = Arc::new(RUNEEngine::new());
@@ -320,6 +465,9 @@ mod tests {
             max_retry_attempts: 5,
             retry_delay: Duration::from_millis(500),
             auto_reload: false,
+            min_reload_interval: Duration::from_secs(1),
+            reload_burst: 1,
+            ..ReloadConfig::default()
         };
         assert_eq!(config.debounce_duration, Duration::from_secs(2));
         assert_eq!(config.max_retry_attempts, 5);
@@ -335,6 +483,9 @@ mod tests {
             max_retry_attempts: 10,
             retry_delay: Duration::from_millis(100),
             auto_reload: false,
+            min_reload_interval: Duration::from_secs(1),
+            reload_burst: 1,
+            ..ReloadConfig::default()
         };
         let coordinator = ReloadCoordinator::with_config(engine, config.clone());
         assert!(coordinator.is_ok());
@@ -738,6 +889,9 @@ fact(unclosed.
             max_retry_attempts: 3,
             retry_delay: Duration::from_secs(1),
             auto_reload: false, // Disabled
+            min_reload_interval: Duration::from_secs(1),
+            reload_burst: 1,
+            ..ReloadConfig::default()
         };
         let mut coordinator = ReloadCoordinator::with_config(engine, config).unwrap();
 
@@ -866,12 +1020,11 @@ permit (
     }
 
     #[tokio::test]
-    async fn test_reload_with_duplicate_policy_ids() {
+    async fn test_reload_with_multiple_policies() {
         let engine = Arc::new(RUNEEngine::new());
-        let coordinator = ReloadCoordinator::new(engine).unwrap();
+        let coordinator = ReloadCoordinator::new(engine.clone()).unwrap();
 
-        // Create temp file with multiple Cedar policies in the same section
-        // This causes duplicate IDs and should fail
+        // Policies in the same section get distinct positional IDs
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(
             temp_file,
@@ -894,7 +1047,32 @@ permit (
         .unwrap();
         temp_file.flush().unwrap();
 
-        // Reload should fail due to duplicate policy IDs
+        let result = coordinator.manual_reload(temp_file.path()).await;
+        assert_eq!(result, ReloadResult::Success);
+        assert_eq!(engine.policies_version().active_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_with_duplicate_policy_ids() {
+        let engine = Arc::new(RUNEEngine::new());
+        let coordinator = ReloadCoordinator::new(engine).unwrap();
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(
+            temp_file,
+            r#"version = "rune/2.0"
+
+[policies]
+@id("read")
+permit(principal, action == Action::"read", resource);
+
+@id("read")
+permit(principal, action == Action::"write", resource);
+"#
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
         let result = coordinator.manual_reload(temp_file.path()).await;
         assert!(
             matches!(result, ReloadResult::Failed(msg) if msg.contains("duplicate") || msg.contains("Policy add error"))
@@ -1044,6 +1222,9 @@ fact(value).
             max_retry_attempts: 7,
             retry_delay: Duration::from_millis(456),
             auto_reload: true,
+            min_reload_interval: Duration::from_secs(1),
+            reload_burst: 1,
+            ..ReloadConfig::default()
         };
 
         // Verify all fields are accessible
@@ -1052,4 +1233,93 @@ fact(value).
         assert_eq!(config.retry_delay, Duration::from_millis(456));
         assert!(config.auto_reload);
     }
+
+    #[test]
+    fn test_throttle_allows_burst_then_waits() {
+        let mut throttle = ReloadThrottle::new(Duration::from_secs(1), 2);
+        let start = throttle.last_refill;
+
+        assert!(throttle.try_acquire_at(start));
+        assert!(throttle.try_acquire_at(start));
+        assert!(!throttle.try_acquire_at(start + Duration::from_millis(500)));
+        assert!(throttle.try_acquire_at(start + Duration::from_millis(1000)));
+        assert!(!throttle.try_acquire_at(start + Duration::from_millis(1100)));
+    }
+
+    #[test]
+    fn test_throttle_zero_interval_never_blocks() {
+        let mut throttle = ReloadThrottle::new(Duration::ZERO, 1);
+        for _ in 0..10 {
+            assert!(throttle.try_acquire());
+        }
+        assert_eq!(throttle.time_until_ready(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_reload_files_merges_into_one_generation() {
+        let engine = Arc::new(RUNEEngine::new());
+        let coordinator = ReloadCoordinator::new(engine.clone()).unwrap();
+
+        let mut first = NamedTempFile::new().unwrap();
+        writeln!(first, "version = \"rune/1.0\"\n\n[rules]\nuser(alice).").unwrap();
+        first.flush().unwrap();
+        let mut second = NamedTempFile::new().unwrap();
+        writeln!(second, "version = \"rune/1.0\"\n\n[rules]\nuser(bob).").unwrap();
+        second.flush().unwrap();
+
+        let before = engine.datalog_version();
+        let paths = vec![first.path().to_path_buf(), second.path().to_path_buf()];
+        assert_eq!(
+            coordinator.reload_files(&paths).await,
+            ReloadResult::Success
+        );
+
+        // Both files land in a single swap
+        let after = engine.datalog_version();
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(after.rules().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_files_bad_file_keeps_previous() {
+        let engine = Arc::new(RUNEEngine::new());
+        let coordinator = ReloadCoordinator::new(engine.clone()).unwrap();
+
+        let mut good = NamedTempFile::new().unwrap();
+        writeln!(good, "version = \"rune/1.0\"\n\n[rules]\nuser(alice).").unwrap();
+        good.flush().unwrap();
+        let mut bad = NamedTempFile::new().unwrap();
+        writeln!(bad, "[rules]\nuser(bob).").unwrap();
+        bad.flush().unwrap();
+
+        let paths = vec![good.path().to_path_buf(), bad.path().to_path_buf()];
+        let result = coordinator.reload_files(&paths).await;
+        assert!(matches!(result, ReloadResult::Failed(_)));
+        assert_eq!(engine.datalog_version().rules().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reload_files_rejects_ids_defined_in_two_files() {
+        let engine = Arc::new(RUNEEngine::new());
+        let coordinator = ReloadCoordinator::new(engine.clone()).unwrap();
+
+        let file = |rule: &str| {
+            let mut file = NamedTempFile::new().unwrap();
+            writeln!(
+                file,
+                "version = \"rune/2.0\"\n\n[rules]\n@id(\"readers\")\n{}",
+                rule
+            )
+            .unwrap();
+            file.flush().unwrap();
+            file
+        };
+        let rules = file("reader(U) :- member(U, \"staff\").");
+        let entities = file("reader(U) :- member(U, \"guests\").");
+
+        let paths = vec![rules.path().to_path_buf(), entities.path().to_path_buf()];
+        let result = coordinator.reload_files(&paths).await;
+        assert!(matches!(result, ReloadResult::Failed(msg) if msg.contains("Layer conflict")));
+        assert_eq!(engine.generation(), 0);
+    }
 }
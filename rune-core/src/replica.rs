@@ -0,0 +1,396 @@
+//! Fact store replication
+//!
+//! A primary engine records every change to its fact store in a bounded
+//! change log (`EngineConfig::change_log`). Replicas pull it in batches with
+//! [`RUNEEngine::changes_since`] and apply them with
+//! [`RUNEEngine::apply_changes`], so read traffic can be spread over engines
+//! that only lag the primary by the polling interval.
+//!
+//! A replica tracks how long ago it last caught up with the primary's head
+//! and refuses to decide a request when that is longer than the request's
+//! `max_staleness` hint, or the configured default. It answers
+//! [`RUNEError::ReplicaStale`] instead, which callers that can reach the
+//! primary treat as a cue to ask it there.
+//!
+//! Positions in the log belong to one log instance. A replica that fell
+//! further behind than the log reaches back, or that follows a primary which
+//! restarted, receives a reset batch holding the primary's full fact set.
+//!
+//! Only facts are replicated. Rules, policies and the rest of the
+//! configuration are loaded on each replica as usual.
+//!
+//! [`RUNEEngine::changes_since`]: crate::RUNEEngine::changes_since
+//! [`RUNEEngine::apply_changes`]: crate::RUNEEngine::apply_changes
+
+use crate::error::{RUNEError, Result};
+use crate::facts::{Fact, FactStore};
+use crate::history::ChangeOrigin;
+use crate::seed;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// One change to a fact store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "op", content = "fact")]
+pub enum FactChange {
+    /// A fact was added
+    Add(Fact),
+    /// Every copy of a fact was removed
+    Retract(Fact),
+    /// The store was emptied
+    Clear,
+}
+
+/// A point in a primary's change log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangePosition {
+    /// Change log instance, new each time the primary starts
+    pub log: u64,
+    /// Sequence number of the last change applied
+    pub seq: u64,
+}
+
+/// Changes read from a primary's change log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeBatch {
+    /// Change log instance
+    pub log: u64,
+    /// Sequence number the batch follows
+    pub from: u64,
+    /// Sequence number of the batch's last change
+    pub to: u64,
+    /// Latest sequence number on the primary when the batch was read
+    pub head: u64,
+    /// The batch replaces the replica's facts rather than extending them
+    pub reset: bool,
+    /// Changes in order
+    pub changes: Vec<FactChange>,
+}
+
+impl ChangeBatch {
+    /// Position a replica is at after applying the batch
+    pub fn end(&self) -> ChangePosition {
+        ChangePosition {
+            log: self.log,
+            seq: self.to,
+        }
+    }
+
+    /// Whether the batch brings a replica up to the primary's head
+    pub fn is_current(&self) -> bool {
+        self.to == self.head
+    }
+}
+
+/// Bounded record of a store's recent changes
+pub(crate) struct ChangeLog {
+    log: u64,
+    state: Mutex<LogState>,
+}
+
+pub(crate) struct LogState {
+    /// Sequence number of the latest change
+    head: u64,
+    /// Retained changes with their sequence numbers, oldest first
+    entries: VecDeque<(u64, FactChange)>,
+    capacity: usize,
+}
+
+impl LogState {
+    pub(crate) fn push(&mut self, change: FactChange) {
+        self.head += 1;
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((self.head, change));
+    }
+}
+
+impl ChangeLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        ChangeLog {
+            log: seed::rng("replica-log").next_u64(),
+            state: Mutex::new(LogState {
+                head: 0,
+                entries: VecDeque::with_capacity(capacity.min(1024)),
+                capacity,
+            }),
+        }
+    }
+
+    /// Lock the log; stores hold it across a change and its record so the
+    /// log order matches the order changes were made in
+    pub(crate) fn lock(&self) -> MutexGuard<'_, LogState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Up to `limit` changes after `since`, or a reset batch built from
+    /// `facts` when `since` is not in the log
+    pub(crate) fn read(
+        &self,
+        state: &LogState,
+        since: Option<ChangePosition>,
+        limit: usize,
+        facts: impl FnOnce() -> Vec<Fact>,
+    ) -> ChangeBatch {
+        // The oldest position changes can still be replayed from
+        let oldest = state.entries.front().map_or(state.head, |(seq, _)| seq - 1);
+        match since {
+            Some(since) if since.log == self.log && (oldest..=state.head).contains(&since.seq) => {
+                let changes: Vec<FactChange> = state
+                    .entries
+                    .iter()
+                    .skip_while(|(seq, _)| *seq <= since.seq)
+                    .take(limit.max(1))
+                    .map(|(_, change)| change.clone())
+                    .collect();
+                ChangeBatch {
+                    log: self.log,
+                    from: since.seq,
+                    to: since.seq + changes.len() as u64,
+                    head: state.head,
+                    reset: false,
+                    changes,
+                }
+            }
+            _ => ChangeBatch {
+                log: self.log,
+                from: 0,
+                to: state.head,
+                head: state.head,
+                reset: true,
+                changes: facts().into_iter().map(FactChange::Add).collect(),
+            },
+        }
+    }
+}
+
+/// Settings for an engine following a primary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicaConfig {
+    /// Longest a request may wait on data since the last catch-up when it
+    /// gives no `max_staleness` of its own
+    pub max_staleness: Duration,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        ReplicaConfig {
+            max_staleness: Duration::from_secs(5),
+        }
+    }
+}
+
+/// How far a replica is behind its primary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicaStatus {
+    /// Last change applied; `None` before the first batch
+    pub position: Option<ChangePosition>,
+    /// Changes the primary had that the last batch did not include
+    pub pending: u64,
+    /// Time since the replica last caught up; `None` if it never has
+    pub staleness: Option<Duration>,
+}
+
+/// Replication progress of a replica engine
+pub(crate) struct Replica {
+    config: ReplicaConfig,
+    position: Mutex<Option<(ChangePosition, u64)>>,
+    epoch: Instant,
+    /// When the replica last caught up, in nanoseconds since `epoch`,
+    /// `u64::MAX` for never, so requests can check it without locking
+    caught_up: AtomicU64,
+}
+
+impl Replica {
+    pub(crate) fn new(config: ReplicaConfig) -> Self {
+        Replica {
+            config,
+            position: Mutex::new(None),
+            epoch: Instant::now(),
+            caught_up: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Apply a batch that must continue from the current position
+    pub(crate) fn apply(&self, store: &FactStore, batch: &ChangeBatch) -> Result<ReplicaStatus> {
+        let mut position = self.position.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = position.map(|(position, _)| position);
+        let expected = ChangePosition {
+            log: batch.log,
+            seq: batch.from,
+        };
+        if !batch.reset && current != Some(expected) {
+            return Err(RUNEError::Replication(format!(
+                "batch continues from {}:{} but the replica is at {}",
+                batch.log,
+                batch.from,
+                current.map_or("the start".to_string(), |p| format!("{}:{}", p.log, p.seq))
+            )));
+        }
+
+        let origin = ChangeOrigin::system("replication");
+        if batch.reset {
+            let facts = batch.changes.iter().filter_map(|change| match change {
+                FactChange::Add(fact) => Some(fact.clone()),
+                _ => None,
+            });
+            store.reset(facts.collect(), Some(&origin));
+        } else {
+            // Runs of additions are applied as one bulk insert
+            let writer = store.attributed(origin);
+            let mut added = Vec::new();
+            for change in &batch.changes {
+                match change {
+                    FactChange::Add(fact) => added.push(fact.clone()),
+                    FactChange::Retract(fact) => {
+                        writer.add_facts_bulk(std::mem::take(&mut added));
+                        writer.retract_fact(fact);
+                    }
+                    FactChange::Clear => {
+                        writer.add_facts_bulk(std::mem::take(&mut added));
+                        writer.clear();
+                    }
+                }
+            }
+            writer.add_facts_bulk(added);
+        }
+
+        let pending = batch.head.saturating_sub(batch.to);
+        *position = Some((batch.end(), pending));
+        if pending == 0 {
+            self.caught_up
+                .store(self.nanos(Instant::now()), Ordering::Release);
+        }
+        Ok(self.status_with(*position, Instant::now()))
+    }
+
+    /// Fail unless the replica caught up within the allowed staleness
+    pub(crate) fn check(&self, max_staleness: Option<Duration>, now: Instant) -> Result<()> {
+        let max = max_staleness.unwrap_or(self.config.max_staleness);
+        match self.staleness(now) {
+            Some(staleness) if staleness <= max => Ok(()),
+            staleness => Err(RUNEError::ReplicaStale {
+                lag_ms: staleness.map(|s| s.as_millis() as u64),
+                max_ms: max.as_millis() as u64,
+            }),
+        }
+    }
+
+    pub(crate) fn status(&self, now: Instant) -> ReplicaStatus {
+        let position = *self.position.lock().unwrap_or_else(PoisonError::into_inner);
+        self.status_with(position, now)
+    }
+
+    fn status_with(&self, position: Option<(ChangePosition, u64)>, now: Instant) -> ReplicaStatus {
+        ReplicaStatus {
+            position: position.map(|(position, _)| position),
+            pending: position.map_or(0, |(_, pending)| pending),
+            staleness: self.staleness(now),
+        }
+    }
+
+    fn staleness(&self, now: Instant) -> Option<Duration> {
+        let caught_up = self.caught_up.load(Ordering::Acquire);
+        (caught_up != u64::MAX).then(|| {
+            let elapsed = self.nanos(now).saturating_sub(caught_up);
+            Duration::from_nanos(elapsed)
+        })
+    }
+
+    fn nanos(&self, instant: Instant) -> u64 {
+        let nanos = instant.saturating_duration_since(self.epoch).as_nanos();
+        u64::try_from(nanos).unwrap_or(u64::MAX - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn fact(name: &str) -> Fact {
+        Fact::new("member", vec![Value::string(name)])
+    }
+
+    #[test]
+    fn test_replica_follows_primary() {
+        let primary = FactStore::new();
+        primary.enable_change_log(3);
+        primary.add_fact(fact("alice"));
+
+        let replica_store = FactStore::new();
+        let replica = Replica::new(ReplicaConfig::default());
+        let now = Instant::now();
+        assert!(matches!(
+            replica.check(None, now),
+            Err(RUNEError::ReplicaStale { lag_ms: None, .. })
+        ));
+
+        // First contact gets the full fact set
+        let batch = primary.changes_since(None, 100).unwrap();
+        assert!(batch.reset && batch.is_current());
+        let status = replica.apply(&replica_store, &batch).unwrap();
+        assert_eq!(status.position, Some(batch.end()));
+        assert_eq!(replica_store.len(), 1);
+        assert!(replica.check(None, Instant::now()).is_ok());
+
+        // Later batches carry only the changes, `limit` at a time
+        primary.add_fact(fact("bob"));
+        primary.retract_fact(&fact("alice"));
+        let batch = primary.changes_since(status.position, 1).unwrap();
+        assert!(!batch.reset && !batch.is_current());
+        assert_eq!(batch.changes, vec![FactChange::Add(fact("bob"))]);
+        let status = replica.apply(&replica_store, &batch).unwrap();
+        assert_eq!(status.pending, 1);
+        let batch = primary.changes_since(status.position, 10).unwrap();
+        replica.apply(&replica_store, &batch).unwrap();
+        assert_eq!(replica_store.all_facts().to_vec(), vec![fact("bob")]);
+
+        // A batch that skips changes is refused
+        assert!(matches!(
+            replica.apply(&replica_store, &batch),
+            Err(RUNEError::Replication(_))
+        ));
+
+        // Falling behind the log's reach means starting over
+        let behind = Some(batch.end());
+        for name in ["carol", "dave", "erin", "frank"] {
+            primary.add_fact(fact(name));
+        }
+        let batch = primary.changes_since(behind, 10).unwrap();
+        assert!(batch.reset);
+        replica.apply(&replica_store, &batch).unwrap();
+        assert_eq!(replica_store.len(), 5);
+        assert_eq!(
+            replica_store.get_by_predicate("member").len(),
+            primary.get_by_predicate("member").len()
+        );
+    }
+
+    #[test]
+    fn test_staleness_limits() {
+        let replica = Replica::new(ReplicaConfig {
+            max_staleness: Duration::from_secs(60),
+        });
+        let store = FactStore::new();
+        store.enable_change_log(8);
+        replica
+            .apply(&store, &store.changes_since(None, 8).unwrap())
+            .unwrap();
+
+        let later = Instant::now() + Duration::from_secs(2);
+        assert!(replica.check(None, later).is_ok());
+        // A request's own bound overrides the default
+        assert!(replica.check(Some(Duration::from_secs(1)), later).is_err());
+        assert!(replica
+            .check(
+                Some(Duration::from_secs(120)),
+                later + Duration::from_secs(90)
+            )
+            .is_ok());
+    }
+}
@@ -1,11 +1,13 @@
 //! Request types for authorization
 
+use crate::attributes::AttributeSource;
 use crate::types::{Action, Principal, Resource, Value};
 use ahash::AHasher;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Authorization request
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -20,6 +22,19 @@ pub struct Request {
     pub context: Arc<BTreeMap<String, Value>>,
     /// Request ID for tracing
     pub request_id: Arc<str>,
+    /// Oldest replica data the request accepts, overriding the replica's
+    /// default; ignored by engines that are not replicas
+    #[serde(default)]
+    pub max_staleness: Option<Duration>,
+    /// Principal attributes from other sources, merged into the principal
+    /// before evaluation (see [`crate::attributes`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attribute_sources: Vec<AttributeSource>,
+    /// Return the proof tree of every derived fact with the decision, or
+    /// for a denial the rules that almost matched; explained requests are
+    /// never answered from the decision cache
+    #[serde(default)]
+    pub explain: bool,
 }
 
 impl Request {
@@ -31,6 +46,9 @@ impl Request {
             resource,
             context: Arc::new(BTreeMap::new()),
             request_id: Arc::from(generate_request_id().into_boxed_str()),
+            max_staleness: None,
+            attribute_sources: Vec::new(),
+            explain: false,
         }
     }
 
@@ -42,6 +60,12 @@ impl Request {
         self
     }
 
+    /// Add a source of principal attributes
+    pub fn with_attribute_source(mut self, source: AttributeSource) -> Self {
+        self.attribute_sources.push(source);
+        self
+    }
+
     /// Calculate hash for caching
     pub fn cache_key(&self) -> u64 {
         let mut hasher = AHasher::default();
@@ -49,6 +73,7 @@ impl Request {
         // Hash principal
         self.principal.entity.entity_type.hash(&mut hasher);
         self.principal.entity.id.hash(&mut hasher);
+        self.principal.entity.attributes.hash(&mut hasher);
 
         // Hash action
         self.action.name.hash(&mut hasher);
@@ -60,6 +85,7 @@ impl Request {
         // Hash resource
         self.resource.entity.entity_type.hash(&mut hasher);
         self.resource.entity.id.hash(&mut hasher);
+        self.resource.entity.attributes.hash(&mut hasher);
 
         // Hash context
         for (k, v) in self.context.iter() {
@@ -77,6 +103,9 @@ pub struct RequestBuilder {
     action: Option<Action>,
     resource: Option<Resource>,
     context: BTreeMap<String, Value>,
+    max_staleness: Option<Duration>,
+    attribute_sources: Vec<AttributeSource>,
+    explain: bool,
 }
 
 impl RequestBuilder {
@@ -87,6 +116,9 @@ impl RequestBuilder {
             action: None,
             resource: None,
             context: BTreeMap::new(),
+            max_staleness: None,
+            attribute_sources: Vec::new(),
+            explain: false,
         }
     }
 
@@ -114,11 +146,33 @@ impl RequestBuilder {
         self
     }
 
+    /// Accept replica data up to `max_staleness` old
+    pub fn max_staleness(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = Some(max_staleness);
+        self
+    }
+
+    /// Add a source of principal attributes
+    pub fn attribute_source(mut self, source: AttributeSource) -> Self {
+        self.attribute_sources.push(source);
+        self
+    }
+
+    /// Ask for the derivation of the decision (see [`Request::explain`])
+    pub fn explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
     /// Build the request
+    ///
+    /// Fails if a part is missing or the principal lacks an attribute its
+    /// [`PrincipalKind`](crate::types::PrincipalKind) requires.
     pub fn build(self) -> crate::Result<Request> {
         let principal = self
             .principal
             .ok_or_else(|| crate::error::RUNEError::InvalidRequest("Missing principal".into()))?;
+        principal.validate()?;
         let action = self
             .action
             .ok_or_else(|| crate::error::RUNEError::InvalidRequest("Missing action".into()))?;
@@ -130,6 +184,9 @@ impl RequestBuilder {
         for (k, v) in self.context {
             request = request.with_context(k, v);
         }
+        request.max_staleness = self.max_staleness;
+        request.attribute_sources = self.attribute_sources;
+        request.explain = self.explain;
 
         Ok(request)
     }
@@ -146,10 +203,14 @@ fn generate_request_id() -> String {
     use std::sync::atomic::{AtomicU64, Ordering};
     static COUNTER: AtomicU64 = AtomicU64::new(0);
 
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
+    // Seeded runs replace the clock so IDs repeat from run to run
+    let timestamp = match crate::seed::seed() {
+        Some(seed) => seed as u128,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos(),
+    };
     let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
 
     format!("req_{:x}_{:x}", timestamp, counter)
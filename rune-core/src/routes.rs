@@ -0,0 +1,301 @@
+//! HTTP route mapping for forward-auth deployments
+//!
+//! When rune-server sits behind nginx (`auth_request`) or Traefik
+//! (`forwardAuth`), the proxy only knows the method and URI of the request it
+//! is guarding. A `[routes]` section maps those onto RUNE actions and
+//! resources, one mapping per line:
+//!
+//! ```text
+//! [routes]
+//! GET /api/docs/{id}        -> action read, resource doc:{id}
+//! PUT|PATCH /api/docs/{id}  -> action write, resource doc:{id}
+//! * /static/{path*}         -> action read, resource asset:{path}
+//! ```
+//!
+//! `{name}` captures one path segment and `{name*}` the rest of the path.
+//! Captures can be used in both the action and the resource. Mappings are
+//! tried in order and the first match wins; the query string and trailing
+//! slashes are ignored.
+
+use crate::error::{RUNEError, Result};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest(String),
+}
+
+/// A single `METHOD /path -> action ..., resource ...` mapping
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteMapping {
+    methods: Option<Vec<String>>,
+    segments: Vec<Segment>,
+    action: String,
+    resource: String,
+    pattern: String,
+}
+
+impl RouteMapping {
+    /// Parse one mapping line
+    pub fn parse(line: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            RUNEError::ParseError(format!("Invalid route mapping '{}': {}", line, reason))
+        };
+
+        let (route, target) = line
+            .split_once("->")
+            .ok_or_else(|| invalid("expected '->'"))?;
+        let (method, path) = route
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid("expected METHOD /path"))?;
+        let path = path.trim();
+        if !path.starts_with('/') {
+            return Err(invalid("path must start with '/'"));
+        }
+
+        let methods = match method {
+            "*" | "ANY" => None,
+            _ => Some(method.split('|').map(str::to_ascii_uppercase).collect()),
+        };
+
+        let parts: Vec<&str> = split_path(path).collect();
+        let mut segments = Vec::new();
+        for (i, segment) in parts.iter().enumerate() {
+            let segment = match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) => match name.strip_suffix('*') {
+                    Some(name) if i + 1 == parts.len() => Segment::Rest(name.to_string()),
+                    Some(_) => return Err(invalid("{name*} must be the last segment")),
+                    None => Segment::Param(name.to_string()),
+                },
+                None => Segment::Literal(segment.to_string()),
+            };
+            segments.push(segment);
+        }
+
+        let mut action = None;
+        let mut resource = None;
+        for part in target.split(',') {
+            match part.trim().split_once(char::is_whitespace) {
+                Some(("action", value)) => action = Some(value.trim().to_string()),
+                Some(("resource", value)) => resource = Some(value.trim().to_string()),
+                _ => return Err(invalid("expected 'action <name>, resource <template>'")),
+            }
+        }
+
+        let mapping = RouteMapping {
+            methods,
+            segments,
+            action: action.ok_or_else(|| invalid("missing action"))?,
+            resource: resource.ok_or_else(|| invalid("missing resource"))?,
+            pattern: route.trim().to_string(),
+        };
+
+        // Catch typos in placeholders when the file is loaded, not per request
+        for template in [&mapping.action, &mapping.resource] {
+            for name in placeholders(template) {
+                if !mapping.param_names().any(|p| p == name) {
+                    return Err(invalid(&format!("unknown placeholder {{{}}}", name)));
+                }
+            }
+        }
+
+        Ok(mapping)
+    }
+
+    /// Method and path template as written, e.g. `GET /api/docs/{id}`
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    fn param_names(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Param(name) | Segment::Rest(name) => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    fn matches(&self, method: &str, path: &[&str]) -> Option<BTreeMap<String, String>> {
+        if let Some(methods) = &self.methods {
+            if !methods.iter().any(|m| m.eq_ignore_ascii_case(method)) {
+                return None;
+            }
+        }
+
+        let mut params = BTreeMap::new();
+        let mut rest = path;
+        for segment in &self.segments {
+            match segment {
+                Segment::Rest(name) => {
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    params.insert(name.clone(), rest.join("/"));
+                    rest = &[];
+                }
+                Segment::Param(name) => {
+                    let (value, tail) = rest.split_first()?;
+                    params.insert(name.clone(), value.to_string());
+                    rest = tail;
+                }
+                Segment::Literal(literal) => {
+                    let (value, tail) = rest.split_first()?;
+                    if value != literal {
+                        return None;
+                    }
+                    rest = tail;
+                }
+            }
+        }
+
+        rest.is_empty().then_some(params)
+    }
+}
+
+/// Result of resolving a request against a [`RouteTable`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteMatch {
+    /// Action with placeholders filled in
+    pub action: String,
+    /// Resource with placeholders filled in (`type:id` form)
+    pub resource: String,
+    /// Pattern of the mapping that matched
+    pub pattern: String,
+}
+
+/// Ordered set of route mappings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteTable {
+    routes: Vec<RouteMapping>,
+}
+
+impl RouteTable {
+    /// Create a table from parsed mappings
+    pub fn new(routes: Vec<RouteMapping>) -> Self {
+        RouteTable { routes }
+    }
+
+    /// Parse the body of a `[routes]` section
+    pub fn parse(input: &str) -> Result<Self> {
+        let routes = input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(RouteMapping::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RouteTable { routes })
+    }
+
+    /// Mappings in match order
+    pub fn routes(&self) -> &[RouteMapping] {
+        &self.routes
+    }
+
+    /// Number of mappings
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// Check whether the table has no mappings
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Map an HTTP method and URI onto an action and resource
+    pub fn resolve(&self, method: &str, uri: &str) -> Option<RouteMatch> {
+        let path = uri.split(['?', '#']).next().unwrap_or(uri);
+        let segments: Vec<&str> = split_path(path).collect();
+
+        self.routes.iter().find_map(|route| {
+            let params = route.matches(method, &segments)?;
+            Some(RouteMatch {
+                action: fill(&route.action, &params),
+                resource: fill(&route.resource, &params),
+                pattern: route.pattern.clone(),
+            })
+        })
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+}
+
+fn fill(template: &str, params: &BTreeMap<String, String>) -> String {
+    params
+        .iter()
+        .fold(template.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> RouteTable {
+        RouteTable::parse(
+            r#"
+# Documents
+GET /api/docs/{id} -> action read, resource doc:{id}
+PUT|PATCH /api/docs/{id} -> action write, resource doc:{id}
+* /static/{path*} -> action read, resource asset:{path}
+POST /api/{kind}/{id}/approve -> action approve_{kind}, resource {kind}:{id}
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_params() {
+        let table = table();
+        assert_eq!(table.len(), 4);
+
+        let m = table.resolve("GET", "/api/docs/42?expand=true").unwrap();
+        assert_eq!(m.action, "read");
+        assert_eq!(m.resource, "doc:42");
+        assert_eq!(m.pattern, "GET /api/docs/{id}");
+
+        let m = table.resolve("patch", "/api/docs/42/").unwrap();
+        assert_eq!(m.action, "write");
+
+        let m = table.resolve("POST", "/api/invoice/7/approve").unwrap();
+        assert_eq!(m.action, "approve_invoice");
+        assert_eq!(m.resource, "invoice:7");
+    }
+
+    #[test]
+    fn test_rest_capture_and_misses() {
+        let table = table();
+        let m = table.resolve("HEAD", "/static/css/site.css").unwrap();
+        assert_eq!(m.resource, "asset:css/site.css");
+
+        assert!(table.resolve("DELETE", "/api/docs/42").is_none());
+        assert!(table.resolve("GET", "/api/docs").is_none());
+        assert!(table.resolve("GET", "/api/docs/42/history").is_none());
+        assert!(table.resolve("GET", "/static").is_none());
+    }
+
+    #[test]
+    fn test_invalid_mappings() {
+        for line in [
+            "GET /api/docs/{id} action read",
+            "GET api/docs -> action read, resource doc:x",
+            "GET /api/{rest*}/tail -> action read, resource doc:{rest}",
+            "GET /api/docs/{id} -> action read",
+            "GET /api/docs/{id} -> action read, resource doc:{docid}",
+            "GET /api/docs/{id} -> verb read, resource doc:{id}",
+        ] {
+            assert!(RouteMapping::parse(line).is_err(), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Scoped fact views for Datalog rules
+//!
+//! In a multi-tenant deployment every tenant's facts share one store, and a
+//! rule that forgets to constrain the tenant column happily joins one
+//! tenant's memberships with another's grants. A `[scopes]` section declares
+//! how facts are partitioned, and rules opt in with `@scope("...")`:
+//!
+//! ```toml
+//! [tenant]
+//! column = 0                      # argument holding the tenant ID
+//! shared = ["role_permission"]    # predicates every tenant may read
+//! ```
+//!
+//! ```text
+//! @scope("tenant")
+//! can_read(T, U, D) :- member(T, U, G), grant(T, G, D).
+//! ```
+//!
+//! Scope names must not clash with RUNE section names such as `rules`, since
+//! `[rules]` would end the section.
+//!
+//! Scoped rules are evaluated once per partition, against only that
+//! partition's facts plus the shared predicates, so no body match can mix
+//! facts from two tenants whatever the rule says. Facts too short to carry
+//! the column are invisible to scoped rules. Scoped rules read stored facts
+//! and each other's conclusions; unscoped rules then see everything,
+//! including what the scoped rules derived. A rule naming an undeclared
+//! scope derives nothing.
+
+use crate::datalog::types::Rule;
+use crate::error::{RUNEError, Result};
+use crate::facts::Fact;
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// How one scope partitions the fact store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FactScope {
+    /// Argument position holding the scope key
+    pub column: usize,
+    /// Predicates visible in every partition
+    #[serde(default)]
+    pub shared: BTreeSet<String>,
+}
+
+impl FactScope {
+    /// Split facts into one view per scope key
+    ///
+    /// Shared facts are added to every view. Without any partitioned facts
+    /// there are no views, so scoped rules derive nothing.
+    pub fn partition<'a>(
+        &self,
+        facts: impl IntoIterator<Item = &'a Fact>,
+    ) -> HashMap<Value, Vec<Fact>> {
+        let mut views: HashMap<Value, Vec<Fact>> = HashMap::new();
+        let mut shared = Vec::new();
+
+        for fact in facts {
+            if self.shared.contains(fact.predicate.as_ref()) {
+                shared.push(fact.clone());
+            } else if let Some(key) = fact.args.get(self.column) {
+                views.entry(key.clone()).or_default().push(fact.clone());
+            }
+        }
+
+        for view in views.values_mut() {
+            view.extend(shared.iter().cloned());
+        }
+        views
+    }
+}
+
+/// Scopes declared in a `[scopes]` section, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FactScopes {
+    scopes: BTreeMap<String, FactScope>,
+}
+
+impl FactScopes {
+    /// Create a scope set from named scopes
+    pub fn new(scopes: BTreeMap<String, FactScope>) -> Self {
+        FactScopes { scopes }
+    }
+
+    /// Parse the TOML body of a `[scopes]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        toml::from_str(input)
+            .map_err(|e| RUNEError::ParseError(format!("Failed to parse scopes section: {}", e)))
+    }
+
+    /// Look up a scope by name
+    pub fn get(&self, name: &str) -> Option<&FactScope> {
+        self.scopes.get(name)
+    }
+
+    /// Declared scope names
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.scopes.keys().map(String::as_str)
+    }
+
+    /// Number of declared scopes
+    pub fn len(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Check whether no scopes are declared
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// Scopes referenced by `@scope` annotations but not declared
+    pub fn undeclared<'a>(&self, rules: &'a [Rule]) -> BTreeSet<&'a str> {
+        rules
+            .iter()
+            .filter_map(Rule::scope)
+            .filter(|name| !self.scopes.contains_key(*name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact(predicate: &str, args: &[&str]) -> Fact {
+        Fact::new(
+            predicate,
+            args.iter().map(|a| Value::string(*a)).collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        let scopes = FactScopes::from_toml(
+            r#"
+[tenant]
+column = 0
+shared = ["role_permission"]
+
+[region]
+column = 2
+"#,
+        )
+        .unwrap();
+        assert_eq!(scopes.len(), 2);
+        assert_eq!(scopes.get("region").unwrap().column, 2);
+        assert!(scopes
+            .get("tenant")
+            .unwrap()
+            .shared
+            .contains("role_permission"));
+
+        assert!(FactScopes::from_toml("[tenant]\ncolumn = \"first\"\n").is_err());
+        assert!(FactScopes::from_toml("[tenant]\ncolumn = 0\nshard = []\n").is_err());
+    }
+
+    #[test]
+    fn test_partition_by_column() {
+        let scope = FactScope {
+            column: 0,
+            shared: ["role_permission".to_string()].into(),
+        };
+        let views = scope.partition(&[
+            fact("member", &["acme", "alice"]),
+            fact("member", &["globex", "bob"]),
+            fact("grant", &["acme", "docs"]),
+            fact("role_permission", &["admin", "write"]),
+            fact("flag", &[]),
+        ]);
+
+        assert_eq!(views.len(), 2);
+        let acme = &views[&Value::string("acme")];
+        assert_eq!(acme.len(), 3);
+        assert!(acme.iter().all(|f| f.args[0] != Value::string("globex")));
+        assert_eq!(views[&Value::string("globex")].len(), 2);
+    }
+}
@@ -0,0 +1,320 @@
+//! Principal sessions
+//!
+//! Per-login attributes such as the MFA level or whether the device is
+//! trusted belong to a principal only while it is logged in. Passing them as
+//! request context makes every caller repeat them and trusts each caller to
+//! get them right; storing them as plain facts leaves them behind after
+//! logout. A session installs them as facts for its lifetime instead:
+//!
+//! ```text
+//! open:   principal "alice", attributes mfa_level(2), device_trusted(true)
+//! facts:  mfa_level("alice", 2). device_trusted("alice", true).
+//! ```
+//!
+//! Each attribute becomes a fact whose first argument is the principal's ID,
+//! so rules join on it like any other fact. The facts are retracted when the
+//! session is closed or its TTL runs out. Sessions may share facts (two
+//! logins from trusted devices); a shared fact stays until the last session
+//! holding it ends. Use predicates reserved for session attributes: a fact
+//! that was also added directly is still retracted with its last session.
+
+use crate::facts::{Fact, FactStore};
+use crate::seed::{self, SeededRng};
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// One attribute installed by a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionAttribute {
+    /// Fact predicate
+    pub predicate: String,
+    /// Arguments following the principal ID
+    #[serde(default)]
+    pub args: Vec<Value>,
+}
+
+impl SessionAttribute {
+    /// Create an attribute
+    pub fn new(predicate: impl Into<String>, args: Vec<Value>) -> Self {
+        SessionAttribute {
+            predicate: predicate.into(),
+            args,
+        }
+    }
+
+    fn to_fact(&self, principal: &str) -> Fact {
+        let mut args = Vec::with_capacity(self.args.len() + 1);
+        args.push(Value::string(principal));
+        args.extend(self.args.iter().cloned());
+        Fact::new(self.predicate.as_str(), args)
+    }
+}
+
+/// An open session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// Session ID
+    pub id: String,
+    /// ID of the principal the session belongs to
+    pub principal: String,
+    /// Facts the session installed
+    pub facts: Vec<Fact>,
+    /// Time left before the session expires; `None` lasts until closed
+    pub expires_in: Option<Duration>,
+}
+
+struct Session {
+    principal: String,
+    facts: Vec<Fact>,
+    deadline: Option<Instant>,
+}
+
+#[derive(Default)]
+struct State {
+    sessions: HashMap<String, Session>,
+    /// Open sessions holding each installed fact
+    holders: HashMap<Fact, usize>,
+}
+
+/// Open sessions of one engine
+///
+/// Fact store changes happen under the table lock, so a fact shared by a
+/// session being opened and one being closed is never retracted from under
+/// the new session.
+pub(crate) struct SessionTable {
+    state: Mutex<State>,
+    epoch: Instant,
+    /// Earliest deadline in nanoseconds since `epoch`, `u64::MAX` for none,
+    /// so the authorization path can check for expiries without locking
+    next_deadline: AtomicU64,
+    counter: AtomicU64,
+    /// Key mixed into session IDs so they cannot be guessed from the counter
+    ids: u64,
+}
+
+impl SessionTable {
+    pub(crate) fn new() -> Self {
+        SessionTable {
+            state: Mutex::default(),
+            epoch: Instant::now(),
+            next_deadline: AtomicU64::new(u64::MAX),
+            counter: AtomicU64::new(0),
+            ids: seed::rng("session-ids").next_u64(),
+        }
+    }
+
+    /// Install `attributes` for `principal`
+    pub(crate) fn open(
+        &self,
+        store: &FactStore,
+        principal: &str,
+        attributes: &[SessionAttribute],
+        ttl: Option<Duration>,
+    ) -> SessionInfo {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        let id = format!(
+            "sess_{:016x}_{:x}",
+            SeededRng::new(self.ids.wrapping_add(counter)).next_u64(),
+            counter
+        );
+
+        let mut facts: Vec<Fact> = attributes.iter().map(|a| a.to_fact(principal)).collect();
+        facts.sort_by(|a, b| (&a.predicate, &a.args).cmp(&(&b.predicate, &b.args)));
+        facts.dedup();
+
+        let mut state = self.lock();
+        let new: Vec<Fact> = facts
+            .iter()
+            .filter(|fact| {
+                let holders = state.holders.entry((*fact).clone()).or_default();
+                *holders += 1;
+                *holders == 1
+            })
+            .cloned()
+            .collect();
+        store.add_facts(new);
+
+        let info = SessionInfo {
+            id: id.clone(),
+            principal: principal.to_string(),
+            facts: facts.clone(),
+            expires_in: ttl,
+        };
+        state.sessions.insert(
+            id,
+            Session {
+                principal: info.principal.clone(),
+                facts,
+                deadline: ttl.map(|ttl| Instant::now() + ttl),
+            },
+        );
+        self.update_deadline(&state);
+        info
+    }
+
+    /// End a session, returning whether it was open
+    pub(crate) fn close(&self, store: &FactStore, id: &str) -> bool {
+        let mut state = self.lock();
+        let Some(session) = state.sessions.remove(id) else {
+            return false;
+        };
+        release(&mut state, store, session);
+        self.update_deadline(&state);
+        true
+    }
+
+    /// Whether a session may have expired by `now`
+    pub(crate) fn due(&self, now: Instant) -> bool {
+        let deadline = self.next_deadline.load(Ordering::Acquire);
+        deadline != u64::MAX && self.nanos(now) >= deadline
+    }
+
+    /// Earliest deadline of an open session, if any
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        let deadline = self.next_deadline.load(Ordering::Acquire);
+        (deadline != u64::MAX).then(|| self.epoch + Duration::from_nanos(deadline))
+    }
+
+    /// End every session whose TTL ran out by `now`, returning how many
+    pub(crate) fn expire(&self, store: &FactStore, now: Instant) -> usize {
+        let mut state = self.lock();
+        let expired: Vec<String> = state
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            if let Some(session) = state.sessions.remove(id) {
+                release(&mut state, store, session);
+            }
+        }
+        self.update_deadline(&state);
+        expired.len()
+    }
+
+    /// Open sessions, ordered by ID
+    pub(crate) fn list(&self, now: Instant) -> Vec<SessionInfo> {
+        let state = self.lock();
+        let mut sessions: Vec<SessionInfo> = state
+            .sessions
+            .iter()
+            .map(|(id, session)| SessionInfo {
+                id: id.clone(),
+                principal: session.principal.clone(),
+                facts: session.facts.clone(),
+                expires_in: session
+                    .deadline
+                    .map(|deadline| deadline.saturating_duration_since(now)),
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        sessions
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn nanos(&self, instant: Instant) -> u64 {
+        let nanos = instant.saturating_duration_since(self.epoch).as_nanos();
+        u64::try_from(nanos).unwrap_or(u64::MAX - 1)
+    }
+
+    fn update_deadline(&self, state: &State) {
+        let next = state
+            .sessions
+            .values()
+            .filter_map(|session| session.deadline)
+            .min()
+            .map_or(u64::MAX, |deadline| self.nanos(deadline));
+        self.next_deadline.store(next, Ordering::Release);
+    }
+}
+
+/// Retract the facts no other open session holds
+fn release(state: &mut State, store: &FactStore, session: Session) {
+    for fact in session.facts {
+        let last = match state.holders.get_mut(&fact) {
+            Some(holders) if *holders > 1 => {
+                *holders -= 1;
+                false
+            }
+            _ => true,
+        };
+        if last {
+            state.holders.remove(&fact);
+            store.retract_fact(&fact);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes() -> Vec<SessionAttribute> {
+        vec![
+            SessionAttribute::new("mfa_level", vec![Value::Integer(2)]),
+            SessionAttribute::new("device_trusted", vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_shared_facts_outlive_first_session() {
+        let store = FactStore::new();
+        let table = SessionTable::new();
+
+        let first = table.open(&store, "alice", &attributes(), None).id;
+        let second = table.open(&store, "alice", &attributes()[1..], None).id;
+        assert_ne!(first, second);
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            store.get_by_predicate("mfa_level")[0].args[..],
+            [Value::string("alice"), Value::Integer(2)]
+        );
+
+        assert!(table.close(&store, &first));
+        assert!(!table.close(&store, &first));
+        assert!(store.get_by_predicate("mfa_level").is_empty());
+        assert_eq!(store.get_by_predicate("device_trusted").len(), 1);
+
+        assert!(table.close(&store, &second));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let store = FactStore::new();
+        let table = SessionTable::new();
+        let now = Instant::now();
+        assert!(!table.due(now));
+
+        table.open(&store, "alice", &attributes(), Some(Duration::ZERO));
+        let bob = table
+            .open(
+                &store,
+                "bob",
+                &attributes(),
+                Some(Duration::from_secs(3600)),
+            )
+            .id;
+        let later = Instant::now();
+        assert!(table.due(later));
+
+        assert_eq!(table.expire(&store, later), 1);
+        assert!(!table.due(later));
+        let open = table.list(later);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].id, bob);
+        assert!(open[0].expires_in.unwrap() > Duration::from_secs(3500));
+        assert!(store
+            .all_facts()
+            .iter()
+            .all(|fact| fact.args[0] == Value::string("bob")));
+    }
+}
@@ -0,0 +1,242 @@
+//! Profile-guided speculative policy evaluation
+//!
+//! Most traffic for a given `(action, resource type)` pair is decided by the
+//! same handful of Cedar policies. [`DecisionProfile`] counts which policies
+//! decide each pair and, once a pair has enough samples, hands the engine a
+//! reduced policy set holding the top-k permits plus every forbid (see
+//! [`PolicySet::speculative_subset`]). Under forbid-overrides that subset is
+//! conclusive whenever it permits or a forbid matches; only a bare default
+//! deny falls back to the full set.
+//!
+//! Because the Datalog and Cedar decisions are combined with forbid > deny >
+//! permit, a non-permit Cedar result also settles the request on its own, so
+//! sequential evaluation skips the Datalog fixpoint in that case.
+
+use crate::error::Result;
+use crate::policy::PolicySet;
+use crate::request::Request;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Speculative evaluation settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeculationConfig {
+    /// Evaluate likely policies first and return early when conclusive
+    pub enabled: bool,
+    /// Number of permit policies to try before the full set
+    pub top_k: usize,
+    /// Decisions to observe for a pair before speculating on it
+    pub min_samples: u64,
+}
+
+impl Default for SpeculationConfig {
+    fn default() -> Self {
+        SpeculationConfig {
+            enabled: false,
+            top_k: 8,
+            min_samples: 100,
+        }
+    }
+}
+
+/// Counters describing how often speculation paid off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpeculationStats {
+    /// Requests settled by the reduced policy set
+    pub hits: u64,
+    /// Requests that needed the full policy set after speculating
+    pub fallbacks: u64,
+    /// Datalog fixpoints skipped because Cedar already settled the request
+    pub skipped_fixpoints: u64,
+}
+
+type ProfileKey = (Arc<str>, Arc<str>);
+
+#[derive(Default)]
+struct ProfileEntry {
+    samples: u64,
+    deciders: HashMap<String, u64>,
+    /// Subset built for (generation, top-k IDs), reused until either changes
+    subset: Option<(u64, Vec<String>, Arc<PolicySet>)>,
+}
+
+/// Per-(action, resource type) record of which policies decide requests
+#[derive(Default)]
+pub struct DecisionProfile {
+    entries: DashMap<ProfileKey, ProfileEntry>,
+    hits: AtomicU64,
+    fallbacks: AtomicU64,
+    skipped_fixpoints: AtomicU64,
+}
+
+impl DecisionProfile {
+    /// Create an empty profile
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the policies that decided a request
+    pub fn record(&self, request: &Request, deciding: &[String]) {
+        let mut entry = self.entries.entry(key(request)).or_default();
+        entry.samples += 1;
+        for id in deciding {
+            *entry.deciders.entry(id.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// Number of decisions recorded for the request's pair
+    pub fn samples(&self, request: &Request) -> u64 {
+        self.entries
+            .get(&key(request))
+            .map_or(0, |entry| entry.samples)
+    }
+
+    /// Most frequent deciding policies for the request's pair
+    pub fn top_k(&self, request: &Request, k: usize) -> Vec<String> {
+        self.entries
+            .get(&key(request))
+            .map(|entry| top_k(&entry.deciders, k))
+            .unwrap_or_default()
+    }
+
+    /// Reduced policy set for the request, once its pair has enough samples
+    pub fn speculative_set(
+        &self,
+        request: &Request,
+        policies: &PolicySet,
+        generation: u64,
+        config: &SpeculationConfig,
+    ) -> Result<Option<Arc<PolicySet>>> {
+        let Some(mut entry) = self.entries.get_mut(&key(request)) else {
+            return Ok(None);
+        };
+        if entry.samples < config.min_samples {
+            return Ok(None);
+        }
+
+        let ids = top_k(&entry.deciders, config.top_k);
+        if let Some((cached_generation, cached_ids, subset)) = &entry.subset {
+            if *cached_generation == generation && *cached_ids == ids {
+                return Ok(Some(subset.clone()));
+            }
+        }
+
+        let subset = Arc::new(policies.speculative_subset(&ids)?);
+        entry.subset = Some((generation, ids, subset.clone()));
+        Ok(Some(subset))
+    }
+
+    /// Forget all samples, e.g. after a large policy change
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    /// Hit/fallback counters
+    pub fn stats(&self) -> SpeculationStats {
+        SpeculationStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            fallbacks: self.fallbacks.load(Ordering::Relaxed),
+            skipped_fixpoints: self.skipped_fixpoints.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_fallback(&self) {
+        self.fallbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_skipped_fixpoint(&self) {
+        self.skipped_fixpoints.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn key(request: &Request) -> ProfileKey {
+    (
+        request.action.name.clone(),
+        request.resource.entity.entity_type.clone(),
+    )
+}
+
+fn top_k(deciders: &HashMap<String, u64>, k: usize) -> Vec<String> {
+    let mut ranked: Vec<_> = deciders.iter().collect();
+    // Ties broken by ID so the subset (and its cache entry) stays stable
+    ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .take(k)
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, Principal, Resource};
+
+    fn request(action: &str, resource_type: &str) -> Request {
+        Request::new(
+            Principal::user("alice"),
+            Action::new(action),
+            Resource::new(resource_type, "1"),
+        )
+    }
+
+    #[test]
+    fn test_profile_ranks_per_pair() {
+        let profile = DecisionProfile::new();
+        let read_doc = request("read", "Document");
+        for _ in 0..3 {
+            profile.record(&read_doc, &["p1".to_string()]);
+        }
+        profile.record(&read_doc, &["p2".to_string()]);
+        profile.record(&request("write", "Document"), &["p9".to_string()]);
+
+        assert_eq!(profile.samples(&read_doc), 4);
+        assert_eq!(profile.top_k(&read_doc, 1), vec!["p1"]);
+        assert_eq!(profile.top_k(&read_doc, 5), vec!["p1", "p2"]);
+        assert!(profile.top_k(&request("read", "File"), 5).is_empty());
+    }
+
+    #[test]
+    fn test_speculative_set_waits_for_samples() {
+        let profile = DecisionProfile::new();
+        let policies = PolicySet::new();
+        let config = SpeculationConfig {
+            enabled: true,
+            top_k: 2,
+            min_samples: 2,
+        };
+        let req = request("read", "Document");
+
+        profile.record(&req, &[]);
+        assert!(profile
+            .speculative_set(&req, &policies, 0, &config)
+            .unwrap()
+            .is_none());
+
+        profile.record(&req, &[]);
+        let first = profile
+            .speculative_set(&req, &policies, 0, &config)
+            .unwrap()
+            .unwrap();
+        let again = profile
+            .speculative_set(&req, &policies, 0, &config)
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        // A new configuration generation rebuilds the subset
+        let rebuilt = profile
+            .speculative_set(&req, &policies, 1, &config)
+            .unwrap()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&first, &rebuilt));
+    }
+}
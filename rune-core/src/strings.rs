@@ -0,0 +1,387 @@
+//! String and regex builtins
+//!
+//! A `[strings]` section registers native string functions as builtins (see
+//! [`crate::builtins`]) and sets the limits every call runs under. An empty
+//! section takes the defaults shown:
+//!
+//! ```text
+//! [strings]
+//! max_input_bytes = 4096
+//! max_pattern_bytes = 256
+//! max_regex_size = 262_144
+//! max_regex_nesting = 16
+//! regex_cache = 256
+//!
+//! [rules]
+//! staff(U) :- email(U, E), regex_match(E, "@example[.]com$", true).
+//! ```
+//!
+//! | Builtin                     | Output                                    |
+//! |-----------------------------|-------------------------------------------|
+//! | `regex_match(S, P, B)`      | whether `P` matches somewhere in `S`      |
+//! | `regex_find(S, P, M)`       | the first match, none if there is none    |
+//! | `regex_capture(S, P, C)`    | its first capture group, none if unset    |
+//! | `str_lower(S, L)`           | `S` in lowercase                          |
+//! | `str_upper(S, U)`           | `S` in uppercase                          |
+//! | `str_len(S, N)`             | number of characters in `S`               |
+//! | `str_concat(A, B, C)`       | `A` followed by `B`                       |
+//! | `str_starts_with(S, P, B)`  | whether `S` starts with `P`               |
+//! | `str_ends_with(S, P, B)`    | whether `S` ends with `P`                 |
+//! | `str_contains(S, P, B)`     | whether `S` contains `P`                  |
+//!
+//! # Limits
+//!
+//! Rules run on the authorization hot path, often on request data, so a
+//! careless pattern or a hostile input must not stall evaluation. Patterns
+//! are compiled by the `regex` crate, which matches in time linear in the
+//! input and has no backreferences or lookaround, so there is no
+//! catastrophic backtracking to begin with. On top of that:
+//!
+//! - string inputs (patterns aside) over `max_input_bytes` in total are
+//!   refused
+//! - patterns over `max_pattern_bytes`, nested deeper than
+//!   `max_regex_nesting`, or compiling to more than `max_regex_size` bytes
+//!   (`a{1000}{1000}`, say) are refused
+//! - compiled patterns, refusals included, are cached up to `regex_cache`
+//!   entries, so a pattern is compiled once per configuration rather than
+//!   once per match; a full cache is emptied, which bounds the memory a
+//!   stream of distinct patterns built from facts can take
+//!
+//! A refused call fails like any builtin call: the match is dropped and a
+//! warning logged.
+
+use crate::builtins::BuiltinRegistry;
+use crate::datalog::types::BuiltinFunction;
+use crate::error::{RUNEError, Result};
+use crate::types::Value;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// `[strings]` section: limits for the string and regex builtins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StringLimits {
+    /// Total size of a call's string inputs, patterns aside
+    pub max_input_bytes: usize,
+    /// Size of a regex pattern
+    pub max_pattern_bytes: usize,
+    /// Size of a compiled regex, and of its lazy DFA's cache
+    pub max_regex_size: usize,
+    /// Nesting depth of groups and repetitions in a pattern
+    pub max_regex_nesting: u32,
+    /// Compiled patterns kept; 0 compiles on every call
+    pub regex_cache: usize,
+}
+
+impl Default for StringLimits {
+    fn default() -> Self {
+        StringLimits {
+            max_input_bytes: 4096,
+            max_pattern_bytes: 256,
+            max_regex_size: 256 * 1024,
+            max_regex_nesting: 16,
+            regex_cache: 256,
+        }
+    }
+}
+
+impl StringLimits {
+    /// Parse the TOML body of a `[strings]` section
+    pub fn from_toml(input: &str) -> Result<Self> {
+        let limits: Self = toml::from_str(input).map_err(|e| {
+            RUNEError::ParseError(format!("Failed to parse strings section: {}", e))
+        })?;
+        for (name, value) in [
+            ("max_input_bytes", limits.max_input_bytes),
+            ("max_pattern_bytes", limits.max_pattern_bytes),
+            ("max_regex_size", limits.max_regex_size),
+            ("max_regex_nesting", limits.max_regex_nesting as usize),
+        ] {
+            if value == 0 {
+                return Err(RUNEError::ConfigError(format!(
+                    "{} in the strings section must be at least 1",
+                    name
+                )));
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Register the string and regex builtins in `registry`, replacing any
+    /// registered under the same names
+    ///
+    /// The regex builtins share one cache of compiled patterns.
+    pub fn register(&self, registry: &mut BuiltinRegistry) -> Result<()> {
+        let sandbox = Arc::new(Sandbox {
+            limits: *self,
+            cache: Mutex::new(HashMap::new()),
+        });
+        for (name, arity, op) in BUILTINS {
+            let builtin = StringBuiltin {
+                op,
+                sandbox: sandbox.clone(),
+            };
+            registry.register(name, arity, Arc::new(builtin))?;
+        }
+        Ok(())
+    }
+}
+
+/// What a string builtin computes
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    RegexMatch,
+    RegexFind,
+    RegexCapture,
+    Lower,
+    Upper,
+    Len,
+    Concat,
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+impl Op {
+    /// Whether the second input is a regex pattern
+    fn takes_pattern(self) -> bool {
+        matches!(self, Op::RegexMatch | Op::RegexFind | Op::RegexCapture)
+    }
+}
+
+/// Builtin names, arities (including the output) and operations
+const BUILTINS: [(&str, usize, Op); 10] = [
+    ("regex_match", 3, Op::RegexMatch),
+    ("regex_find", 3, Op::RegexFind),
+    ("regex_capture", 3, Op::RegexCapture),
+    ("str_lower", 2, Op::Lower),
+    ("str_upper", 2, Op::Upper),
+    ("str_len", 2, Op::Len),
+    ("str_concat", 3, Op::Concat),
+    ("str_starts_with", 3, Op::StartsWith),
+    ("str_ends_with", 3, Op::EndsWith),
+    ("str_contains", 3, Op::Contains),
+];
+
+/// Limits and compiled patterns shared by one registration's builtins
+struct Sandbox {
+    limits: StringLimits,
+    cache: Mutex<HashMap<String, std::result::Result<Arc<Regex>, String>>>,
+}
+
+impl Sandbox {
+    /// Compile `pattern` within the limits, or take it from the cache
+    fn regex(&self, pattern: &str) -> std::result::Result<Arc<Regex>, String> {
+        let limits = &self.limits;
+        if pattern.len() > limits.max_pattern_bytes {
+            return Err(format!(
+                "pattern of {} bytes exceeds the {} byte limit",
+                pattern.len(),
+                limits.max_pattern_bytes
+            ));
+        }
+        if limits.regex_cache == 0 {
+            return self.compile(pattern);
+        }
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(compiled) = cache.get(pattern) {
+            return compiled.clone();
+        }
+        let compiled = self.compile(pattern);
+        if cache.len() >= limits.regex_cache {
+            cache.clear();
+        }
+        cache.insert(pattern.to_string(), compiled.clone());
+        compiled
+    }
+
+    fn compile(&self, pattern: &str) -> std::result::Result<Arc<Regex>, String> {
+        RegexBuilder::new(pattern)
+            .size_limit(self.limits.max_regex_size)
+            .dfa_size_limit(self.limits.max_regex_size)
+            .nest_limit(self.limits.max_regex_nesting)
+            .build()
+            .map(Arc::new)
+            .map_err(|e| format!("pattern {:?} refused: {}", pattern, e))
+    }
+}
+
+/// One string or regex builtin
+struct StringBuiltin {
+    op: Op,
+    sandbox: Arc<Sandbox>,
+}
+
+impl BuiltinFunction for StringBuiltin {
+    fn call(&self, inputs: &[Value]) -> std::result::Result<Option<Value>, String> {
+        let strings = inputs
+            .iter()
+            .map(|input| match input {
+                Value::String(s) => Ok(s.as_ref()),
+                _ => Err("expected string inputs".to_string()),
+            })
+            .collect::<std::result::Result<Vec<&str>, String>>()?;
+
+        let limit = self.sandbox.limits.max_input_bytes;
+        let size: usize = strings
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !(self.op.takes_pattern() && *i == 1))
+            .map(|(_, s)| s.len())
+            .sum();
+        if size > limit {
+            return Err(format!(
+                "inputs of {} bytes exceed the {} byte limit",
+                size, limit
+            ));
+        }
+
+        let output = match (self.op, strings.as_slice()) {
+            (Op::RegexMatch, [s, pattern]) => Value::Bool(self.sandbox.regex(pattern)?.is_match(s)),
+            (Op::RegexFind, [s, pattern]) => match self.sandbox.regex(pattern)?.find(s) {
+                Some(found) => Value::string(found.as_str()),
+                None => return Ok(None),
+            },
+            (Op::RegexCapture, [s, pattern]) => {
+                let regex = self.sandbox.regex(pattern)?;
+                match regex.captures(s).and_then(|captures| captures.get(1)) {
+                    Some(group) => Value::string(group.as_str()),
+                    None => return Ok(None),
+                }
+            }
+            (Op::Lower, [s]) => Value::string(s.to_lowercase()),
+            (Op::Upper, [s]) => Value::string(s.to_uppercase()),
+            (Op::Len, [s]) => Value::Integer(s.chars().count() as i64),
+            (Op::Concat, [a, b]) => Value::string(format!("{}{}", a, b)),
+            (Op::StartsWith, [s, prefix]) => Value::Bool(s.starts_with(prefix)),
+            (Op::EndsWith, [s, suffix]) => Value::Bool(s.ends_with(suffix)),
+            (Op::Contains, [s, part]) => Value::Bool(s.contains(part)),
+            _ => return Err(format!("unexpected number of inputs: {}", inputs.len())),
+        };
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin(name: &str, limits: StringLimits) -> Arc<dyn BuiltinFunction> {
+        let (_, _, op) = BUILTINS.into_iter().find(|(n, _, _)| *n == name).unwrap();
+        Arc::new(StringBuiltin {
+            op,
+            sandbox: Arc::new(Sandbox {
+                limits,
+                cache: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    fn call(name: &str, inputs: &[&str]) -> std::result::Result<Option<Value>, String> {
+        let inputs: Vec<Value> = inputs.iter().map(|s| Value::string(*s)).collect();
+        builtin(name, StringLimits::default()).call(&inputs)
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let email = "alice@example.com";
+        assert_eq!(
+            call("regex_match", &[email, "@example[.]com$"]).unwrap(),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            call("regex_match", &[email, "^bob"]).unwrap(),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(
+            call("regex_find", &[email, "[a-z]+[.]com"]).unwrap(),
+            Some(Value::string("example.com"))
+        );
+        assert_eq!(call("regex_find", &[email, "^bob"]).unwrap(), None);
+        assert_eq!(
+            call("regex_capture", &[email, "^([^@]+)@"]).unwrap(),
+            Some(Value::string("alice"))
+        );
+        assert_eq!(call("regex_capture", &[email, "@"]).unwrap(), None);
+        assert_eq!(
+            call("str_upper", &["Alice"]).unwrap(),
+            Some(Value::string("ALICE"))
+        );
+        assert_eq!(
+            call("str_len", &["héllo"]).unwrap(),
+            Some(Value::Integer(5))
+        );
+        assert_eq!(
+            call("str_concat", &["team-", "ops"]).unwrap(),
+            Some(Value::string("team-ops"))
+        );
+        assert_eq!(
+            call("str_ends_with", &[email, ".com"]).unwrap(),
+            Some(Value::Bool(true))
+        );
+
+        assert!(builtin("str_len", StringLimits::default())
+            .call(&[Value::Integer(3)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = StringLimits {
+            max_input_bytes: 16,
+            max_pattern_bytes: 32,
+            ..StringLimits::default()
+        };
+        let regex_match = builtin("regex_match", limits);
+        let check =
+            |s: &str, pattern: &str| regex_match.call(&[Value::string(s), Value::string(pattern)]);
+
+        // The pattern does not count towards the input limit
+        assert!(check("short", "^(short|long)$").is_ok());
+        let error = check(&"a".repeat(17), "a").unwrap_err();
+        assert!(
+            error.contains("17 bytes exceed the 16 byte limit"),
+            "{}",
+            error
+        );
+        assert!(check("a", &"a".repeat(33)).is_err());
+
+        // Patterns that compile to huge programs or nest deeply are refused
+        let error = check("a", "a{1000}{1000}").unwrap_err();
+        assert!(error.contains("refused"), "{}", error);
+        assert!(check("a", &format!("{}a{}", "(".repeat(17), ")".repeat(17))).is_err());
+
+        // Refusals are cached along with compiled patterns, up to the limit
+        let sandbox = Sandbox {
+            limits: StringLimits {
+                regex_cache: 2,
+                ..StringLimits::default()
+            },
+            cache: Mutex::new(HashMap::new()),
+        };
+        for pattern in ["a", "b", "a{1000}{1000}"] {
+            let _ = sandbox.regex(pattern);
+        }
+        let cache = sandbox.cache.lock().unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(cache["a{1000}{1000}"].is_err());
+    }
+
+    #[test]
+    fn test_register_from_toml() {
+        let limits = StringLimits::from_toml("max_input_bytes = 64").unwrap();
+        assert_eq!(limits.max_input_bytes, 64);
+        assert_eq!(limits.regex_cache, 256);
+        assert!(StringLimits::from_toml("max_pattern_bytes = 0").is_err());
+        assert!(StringLimits::from_toml("max_bytes = 10").is_err());
+
+        let mut registry = BuiltinRegistry::new();
+        limits.register(&mut registry).unwrap();
+        for (name, _, _) in BUILTINS {
+            assert!(registry.contains(name), "{}", name);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 //! Type system for RUNE
 
+use crate::error::{RUNEError, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 
 /// Core value type in RUNE
@@ -107,12 +110,145 @@ impl Principal {
 
     /// Create an agent principal
     pub fn agent(id: impl Into<String>) -> Self {
-        Self::new("Agent", id)
+        Self::new(PrincipalKind::Agent.type_name(), id)
     }
 
     /// Create a user principal
     pub fn user(id: impl Into<String>) -> Self {
-        Self::new("User", id)
+        Self::new(PrincipalKind::User.type_name(), id)
+    }
+
+    /// Create a service account principal, owned by the team or person
+    /// answerable for it
+    pub fn service_account(id: impl Into<String>, owner: impl Into<String>) -> Self {
+        Self::new(PrincipalKind::ServiceAccount.type_name(), id)
+            .with_attribute("owner", Value::string(owner))
+    }
+
+    /// Create a device principal running on `platform`, e.g. `ios`
+    pub fn device(id: impl Into<String>, platform: impl Into<String>) -> Self {
+        Self::new(PrincipalKind::Device.type_name(), id)
+            .with_attribute("platform", Value::string(platform))
+    }
+
+    /// Create an API client principal issued to `organization`
+    pub fn api_client(id: impl Into<String>, organization: impl Into<String>) -> Self {
+        Self::new(PrincipalKind::ApiClient.type_name(), id)
+            .with_attribute("organization", Value::string(organization))
+    }
+
+    /// Add an attribute to the principal
+    pub fn with_attribute(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.entity = self.entity.with_attribute(key, value);
+        self
+    }
+
+    /// Built-in kind of the principal; None for other entity types
+    pub fn kind(&self) -> Option<PrincipalKind> {
+        self.entity.entity_type.parse().ok()
+    }
+
+    /// Check that the principal has the attributes its kind requires
+    pub fn validate(&self) -> Result<()> {
+        let Some(kind) = self.kind() else {
+            return Ok(());
+        };
+        let missing = kind
+            .required_attributes()
+            .iter()
+            .find(|name| matches!(self.entity.attributes.get(**name), None | Some(Value::Null)));
+        match missing {
+            Some(name) => Err(RUNEError::InvalidRequest(format!(
+                "{} principal {} is missing required attribute `{}`",
+                kind, self.entity.id, name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Parse the `type:id` form used by the HTTP API and bindings; a bare
+    /// ID is a `User`
+    pub fn parse(s: &str) -> Self {
+        match s.split_once(':') {
+            Some((entity_type, id)) => Self::new(entity_type, id),
+            None => Self::user(s),
+        }
+    }
+}
+
+/// Kinds of principal with their own constructors
+///
+/// Each kind is a separate Cedar entity type, so policies can tell them
+/// apart with `principal is Device`. Kinds other than users and agents
+/// must carry the attributes in [`PrincipalKind::required_attributes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrincipalKind {
+    /// A person
+    User,
+    /// An autonomous agent acting on someone's behalf
+    Agent,
+    /// A non-human account used by a workload
+    ServiceAccount,
+    /// A physical or virtual device
+    Device,
+    /// A third-party application calling the API with its own credentials
+    ApiClient,
+}
+
+impl PrincipalKind {
+    /// Every kind, in declaration order
+    pub const ALL: [PrincipalKind; 5] = [
+        PrincipalKind::User,
+        PrincipalKind::Agent,
+        PrincipalKind::ServiceAccount,
+        PrincipalKind::Device,
+        PrincipalKind::ApiClient,
+    ];
+
+    /// Entity type name, as Cedar policies see it
+    pub fn type_name(self) -> &'static str {
+        match self {
+            PrincipalKind::User => "User",
+            PrincipalKind::Agent => "Agent",
+            PrincipalKind::ServiceAccount => "ServiceAccount",
+            PrincipalKind::Device => "Device",
+            PrincipalKind::ApiClient => "ApiClient",
+        }
+    }
+
+    /// Attributes every principal of this kind must have
+    pub fn required_attributes(self) -> &'static [&'static str] {
+        match self {
+            PrincipalKind::User | PrincipalKind::Agent => &[],
+            PrincipalKind::ServiceAccount => &["owner"],
+            PrincipalKind::Device => &["platform"],
+            PrincipalKind::ApiClient => &["organization"],
+        }
+    }
+}
+
+impl fmt::Display for PrincipalKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.type_name())
+    }
+}
+
+impl FromStr for PrincipalKind {
+    type Err = RUNEError;
+
+    /// Accepts the entity type name (`ServiceAccount`) or its snake or
+    /// kebab case form (`service_account`, `service-account`)
+    fn from_str(s: &str) -> Result<Self> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect();
+        PrincipalKind::ALL
+            .into_iter()
+            .find(|kind| kind.type_name().to_lowercase() == normalized)
+            .ok_or_else(|| RUNEError::InvalidRequest(format!("Unknown principal kind: {}", s)))
     }
 }
 
@@ -172,4 +308,13 @@ impl Resource {
     pub fn api(endpoint: impl Into<String>) -> Self {
         Self::new("API", endpoint)
     }
+
+    /// Parse the `type:id` form used by the HTTP API and bindings; a bare
+    /// ID is a `Resource`
+    pub fn parse(s: &str) -> Self {
+        match s.split_once(':') {
+            Some((entity_type, id)) => Self::new(entity_type, id),
+            None => Self::new("Resource", s),
+        }
+    }
 }
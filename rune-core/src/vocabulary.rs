@@ -0,0 +1,375 @@
+//! Names policy and rule authors can refer to
+//!
+//! An editor offering completions, or flagging `memebr(U, G)` as a typo,
+//! needs the names the running engine knows about. [`Vocabulary`] lists
+//! them, each marked as declared by the configuration, observed in data,
+//! or both:
+//!
+//! - predicates with their arities: declared by the heads and bodies of
+//!   loaded rules, observed in the base facts of the store
+//! - entity types: declared by policy scopes and the entity literals in
+//!   policy conditions, observed as the principals and resources of
+//!   authorized requests
+//! - actions: declared by policy action constraints, observed in requests
+//! - context keys: declared by `context.key` and `context has key` in
+//!   policy conditions, observed in request context
+//!
+//! The engine records what it observes in requests as it authorizes them
+//! ([`ObservedNames`]), keeping at most [`MAX_OBSERVED`] names of each
+//! kind so that unbounded identifiers cannot grow it without limit.
+
+use crate::datalog::types::Rule;
+use crate::fact_vec::FactVec;
+use crate::policy::PolicySet;
+use crate::request::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{PoisonError, RwLock};
+
+/// Most names of each kind recorded from requests
+pub const MAX_OBSERVED: usize = 1024;
+
+/// An entity type, action or context key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownName {
+    /// The name
+    pub name: String,
+    /// Whether the configuration refers to it
+    pub declared: bool,
+    /// Whether requests carried it
+    pub observed: bool,
+}
+
+/// A predicate at one arity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownPredicate {
+    /// Predicate name
+    pub name: String,
+    /// Number of arguments
+    pub arity: usize,
+    /// Whether a loaded rule uses it
+    pub declared: bool,
+    /// Whether the fact store holds facts of it
+    pub observed: bool,
+}
+
+/// Every name the engine knows about, sorted by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vocabulary {
+    /// Configuration generation the declared names come from
+    pub generation: u64,
+    /// Predicates with their arities; a predicate used at two arities is
+    /// listed twice
+    pub predicates: Vec<KnownPredicate>,
+    /// Entity types
+    pub entity_types: Vec<KnownName>,
+    /// Action names
+    pub actions: Vec<KnownName>,
+    /// Request context keys
+    pub context_keys: Vec<KnownName>,
+}
+
+/// Declared and observed flags of each name, while collecting
+type Names = BTreeMap<String, (bool, bool)>;
+
+impl Vocabulary {
+    /// Collect the names in `policies`, `rules` and `facts`, and those
+    /// `observed` in requests
+    pub fn build(
+        generation: u64,
+        policies: &PolicySet,
+        rules: &[Rule],
+        facts: &FactVec,
+        observed: &ObservedNames,
+    ) -> Self {
+        let mut predicates: BTreeMap<(String, usize), (bool, bool)> = BTreeMap::new();
+        for rule in rules.iter().filter(|rule| !rule.is_fact()) {
+            for atom in std::iter::once(&rule.head).chain(&rule.body) {
+                predicates
+                    .entry((atom.predicate.to_string(), atom.arity()))
+                    .or_default()
+                    .0 = true;
+            }
+        }
+        // Facts written in the rules section are loaded into the store too
+        for fact in facts.iter() {
+            predicates
+                .entry((fact.predicate.to_string(), fact.args.len()))
+                .or_default()
+                .1 = true;
+        }
+
+        let mut declared = DeclaredNames::default();
+        for policy in policies.loaded_policies() {
+            if let Ok(json) = policy.to_json() {
+                declared.collect(&json);
+            }
+        }
+        let mut entity_types = mark(Names::new(), declared.entity_types, true);
+        let mut actions = mark(Names::new(), declared.actions, true);
+        let mut context_keys = mark(Names::new(), declared.context_keys, true);
+        {
+            let seen = observed.read();
+            entity_types = mark(entity_types, seen.entity_types.iter().cloned(), false);
+            actions = mark(actions, seen.actions.iter().cloned(), false);
+            context_keys = mark(context_keys, seen.context_keys.iter().cloned(), false);
+        }
+
+        Vocabulary {
+            generation,
+            predicates: predicates
+                .into_iter()
+                .map(|((name, arity), (declared, observed))| KnownPredicate {
+                    name,
+                    arity,
+                    declared,
+                    observed,
+                })
+                .collect(),
+            entity_types: known(entity_types),
+            actions: known(actions),
+            context_keys: known(context_keys),
+        }
+    }
+}
+
+fn mark(mut names: Names, found: impl IntoIterator<Item = String>, declared: bool) -> Names {
+    for name in found {
+        let flags = names.entry(name).or_default();
+        if declared {
+            flags.0 = true;
+        } else {
+            flags.1 = true;
+        }
+    }
+    names
+}
+
+fn known(names: Names) -> Vec<KnownName> {
+    names
+        .into_iter()
+        .map(|(name, (declared, observed))| KnownName {
+            name,
+            declared,
+            observed,
+        })
+        .collect()
+}
+
+/// Names found in the JSON form of Cedar policies
+#[derive(Default)]
+struct DeclaredNames {
+    entity_types: BTreeSet<String>,
+    actions: BTreeSet<String>,
+    context_keys: BTreeSet<String>,
+}
+
+impl DeclaredNames {
+    /// Walk a policy's JSON form: entity references carry `type` and `id`,
+    /// `is` constraints an `entity_type`, and attribute accesses and `has`
+    /// tests on `context` an `attr`
+    fn collect(&mut self, json: &serde_json::Value) {
+        match json {
+            serde_json::Value::Object(object) => {
+                if let (Some(entity_type), Some(id)) = (
+                    object.get("type").and_then(|v| v.as_str()),
+                    object.get("id").and_then(|v| v.as_str()),
+                ) {
+                    if entity_type == "Action" || entity_type.ends_with("::Action") {
+                        self.actions.insert(id.to_string());
+                    } else {
+                        self.entity_types.insert(entity_type.to_string());
+                    }
+                }
+                if let Some(entity_type) = object.get("entity_type").and_then(|v| v.as_str()) {
+                    self.entity_types.insert(entity_type.to_string());
+                }
+                for op in [".", "has"] {
+                    let Some(access) = object.get(op) else {
+                        continue;
+                    };
+                    let on_context = access
+                        .get("left")
+                        .and_then(|left| left.get("Var"))
+                        .and_then(|var| var.as_str())
+                        == Some("context");
+                    match access.get("attr").and_then(|v| v.as_str()) {
+                        Some(attr) if on_context => {
+                            self.context_keys.insert(attr.to_string());
+                        }
+                        _ => {}
+                    }
+                }
+                object.values().for_each(|value| self.collect(value));
+            }
+            serde_json::Value::Array(values) => values.iter().for_each(|value| self.collect(value)),
+            _ => {}
+        }
+    }
+}
+
+/// Names recorded from authorized requests
+#[derive(Debug, Default)]
+pub struct ObservedNames {
+    seen: RwLock<Seen>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    entity_types: BTreeSet<String>,
+    actions: BTreeSet<String>,
+    context_keys: BTreeSet<String>,
+}
+
+impl ObservedNames {
+    /// Create an empty record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the names `request` carries
+    ///
+    /// Names already known only take a read lock, so once traffic settles
+    /// this costs a few set lookups per request.
+    pub fn observe(&self, request: &Request) {
+        let principal_type = &*request.principal.entity.entity_type;
+        let resource_type = &*request.resource.entity.entity_type;
+        let action = &*request.action.name;
+        {
+            let seen = self.read();
+            let known = seen.entity_types.contains(principal_type)
+                && seen.entity_types.contains(resource_type)
+                && seen.actions.contains(action)
+                && request
+                    .context
+                    .keys()
+                    .all(|key| seen.context_keys.contains(key));
+            if known {
+                return;
+            }
+        }
+        let mut seen = self.seen.write().unwrap_or_else(PoisonError::into_inner);
+        let seen = &mut *seen;
+        insert_bounded(&mut seen.entity_types, principal_type);
+        insert_bounded(&mut seen.entity_types, resource_type);
+        insert_bounded(&mut seen.actions, action);
+        for key in request.context.keys() {
+            insert_bounded(&mut seen.context_keys, key);
+        }
+    }
+
+    /// Forget everything recorded
+    pub fn clear(&self) {
+        *self.seen.write().unwrap_or_else(PoisonError::into_inner) = Seen::default();
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Seen> {
+        self.seen.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn insert_bounded(names: &mut BTreeSet<String>, name: &str) {
+    if names.len() < MAX_OBSERVED && !names.contains(name) {
+        names.insert(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_rules;
+    use crate::types::{Action, Principal, Resource, Value};
+
+    #[test]
+    fn test_vocabulary() {
+        let mut policies = PolicySet::new();
+        policies
+            .load_policies(
+                r#"
+permit(principal is User in Group::"staff", action in [Action::"read", Action::"list"], resource)
+when { context.ip == "10.0.0.1" && resource in Folder::"q3" }
+unless { context has mfa };
+"#,
+            )
+            .unwrap();
+        let rules = parse_rules(
+            "can_read(U, D) :- member(U, G), owns(G, D).\nmember(\"alice\", \"staff\").",
+        )
+        .unwrap();
+        let facts = FactVec::from(vec![
+            crate::facts::Fact::binary("member", Value::string("alice"), Value::string("staff")),
+            crate::facts::Fact::unary("member", Value::string("bob")),
+        ]);
+
+        let observed = ObservedNames::new();
+        let request = Request::new(
+            Principal::parse("Device:phone-3"),
+            Action::new("read"),
+            Resource::parse("Document:q3"),
+        )
+        .with_context("ip", Value::string("10.0.0.2"));
+        observed.observe(&request);
+        observed.observe(&request);
+
+        let vocabulary = Vocabulary::build(3, &policies, &rules, &facts, &observed);
+        assert_eq!(vocabulary.generation, 3);
+
+        let predicates: Vec<(&str, usize, bool, bool)> = vocabulary
+            .predicates
+            .iter()
+            .map(|p| (p.name.as_str(), p.arity, p.declared, p.observed))
+            .collect();
+        assert_eq!(
+            predicates,
+            [
+                ("can_read", 2, true, false),
+                ("member", 1, false, true),
+                ("member", 2, true, true),
+                ("owns", 2, true, false),
+            ]
+        );
+
+        let names = |names: &[KnownName]| -> Vec<(String, bool, bool)> {
+            names
+                .iter()
+                .map(|n| (n.name.clone(), n.declared, n.observed))
+                .collect()
+        };
+        let entry = |name: &str, declared, observed| (name.to_string(), declared, observed);
+        assert_eq!(
+            names(&vocabulary.entity_types),
+            [
+                entry("Device", false, true),
+                entry("Document", false, true),
+                entry("Folder", true, false),
+                entry("Group", true, false),
+                entry("User", true, false),
+            ]
+        );
+        assert_eq!(
+            names(&vocabulary.actions),
+            [entry("list", true, false), entry("read", true, true)]
+        );
+        assert_eq!(
+            names(&vocabulary.context_keys),
+            [entry("ip", true, true), entry("mfa", true, false)]
+        );
+
+        observed.clear();
+        let vocabulary = Vocabulary::build(3, &policies, &rules, &facts, &observed);
+        assert!(vocabulary.actions.iter().all(|a| !a.observed));
+    }
+
+    #[test]
+    fn test_observed_names_are_bounded() {
+        let observed = ObservedNames::new();
+        for i in 0..MAX_OBSERVED + 10 {
+            observed.observe(&Request::new(
+                Principal::user("alice"),
+                Action::new(format!("action-{}", i)),
+                Resource::file("/tmp/report"),
+            ));
+        }
+        assert_eq!(observed.read().actions.len(), MAX_OBSERVED);
+    }
+}
@@ -0,0 +1,113 @@
+//! Warnings attached to permit policies
+//!
+//! A permit can carry a nudge for the caller without blocking it: a soft
+//! limit being approached, a credential about to expire. Warnings are
+//! declared with a `@warning` annotation listing `code: message` pairs
+//! separated by `;`:
+//!
+//! ```text
+//! @id("quota-soft-limit")
+//! @warning("quota_near: Storage is over 90% of your quota")
+//! permit(principal, action == Action::"upload", resource)
+//!     when { principal.storage_used >= 90 };
+//! ```
+//!
+//! A permitted request carries the warnings of every policy that permitted
+//! it, so a soft-limit policy sits next to the grant and only adds its
+//! warning while its condition holds. Unlike [obligations](crate::obligations)
+//! warnings are for the caller to show, not for the enforcer to apply.
+//! Denials carry none. The code is short and stable, for metrics and for
+//! clients to branch on; the message is for people.
+
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Policy annotation declaring warnings
+pub const WARNING_ANNOTATION: &str = "warning";
+
+/// A warning to surface alongside a permit
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Warning {
+    /// Stable identifier, lowercase letters, digits, `_` and `-`
+    pub code: String,
+    /// Human-readable text
+    pub message: String,
+}
+
+impl Warning {
+    /// Parse one `code: message` pair
+    pub fn parse(input: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            RUNEError::ConfigError(format!("Invalid warning '{}': {}", input, reason))
+        };
+
+        let (code, message) = input
+            .split_once(':')
+            .ok_or_else(|| invalid("expected 'code: message'"))?;
+        let code = code.trim();
+        let message = message.trim();
+
+        if code.is_empty()
+            || !code
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-')
+        {
+            return Err(invalid(
+                "code must be non-empty lowercase letters, digits, '_' or '-'",
+            ));
+        }
+        if message.is_empty() {
+            return Err(invalid("message must not be empty"));
+        }
+
+        Ok(Warning {
+            code: code.to_string(),
+            message: message.to_string(),
+        })
+    }
+}
+
+/// Parse the `;`-separated pairs of a `@warning` annotation
+pub fn parse_warnings(annotation: &str) -> Result<Vec<Warning>> {
+    annotation
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(Warning::parse)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_warnings() {
+        let warnings = parse_warnings(
+            "quota_near: Storage is over 90% of your quota; cert-expiry: Renew by Friday: soon",
+        )
+        .unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                Warning {
+                    code: "quota_near".into(),
+                    message: "Storage is over 90% of your quota".into(),
+                },
+                Warning {
+                    code: "cert-expiry".into(),
+                    message: "Renew by Friday: soon".into(),
+                },
+            ]
+        );
+        assert!(parse_warnings("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_warnings() {
+        assert!(parse_warnings("quota_near").is_err());
+        assert!(parse_warnings("Quota: near").is_err());
+        assert!(parse_warnings("quota near: x").is_err());
+        assert!(parse_warnings("quota:").is_err());
+    }
+}
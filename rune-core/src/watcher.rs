@@ -2,14 +2,28 @@
 //!
 //! This module provides automatic detection of .rune file changes
 //! and triggers configuration reloads without downtime.
+//!
+//! Native notifications miss changes on some network filesystems, so the
+//! watcher can poll instead ([`WatchMode::Poll`]); it also falls back to
+//! polling when the platform watcher cannot be created. Watched files are
+//! followed through their parent directory, so editors that save by writing
+//! a temporary file and renaming it over the original keep being seen.
+//!
+//! As a safety net, [`RUNEWatcher::check_for_missed_changes`] compares each
+//! watched file's modification time and size with what the last event saw
+//! and reports any difference as a change. [`RUNEWatcher::health`] and the
+//! `rune_watcher_*` metrics show when events last arrived and how many
+//! changes had to be caught this way.
 
 use crate::error::{RUNEError, Result};
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashSet;
+use notify::event::{MetadataKind, ModifyKind, RenameMode};
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::time::Duration;
-use tracing::{debug, error, info, trace};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, info, trace, warn};
 
 /// File change event
 #[derive(Debug, Clone)]
@@ -33,94 +47,260 @@ pub enum ChangeKind {
     Removed,
 }
 
+/// How the watcher learns about changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatchMode {
+    /// Platform notifications (inotify, FSEvents, ...)
+    #[default]
+    Native,
+    /// Scan watched paths for changed modification times at this interval
+    Poll(Duration),
+}
+
+/// How well the watcher is keeping up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatcherHealth {
+    /// How changes are being detected
+    pub mode: WatchMode,
+    /// When the last change event arrived, if any has
+    pub last_event: Option<Instant>,
+    /// Change events received
+    pub events: u64,
+    /// Changes only found by comparing modification times
+    pub missed_changes: u64,
+    /// Errors reported by the underlying watcher
+    pub errors: u64,
+}
+
+/// Modification time and size of a file as last seen
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// What a watched path resolved to
+#[derive(Debug, Clone)]
+enum Target {
+    /// A file, followed through its parent directory
+    File { file: PathBuf, parent: PathBuf },
+    /// A directory, watched recursively
+    Directory(PathBuf),
+}
+
+/// State shared with the notification callback
+#[derive(Debug)]
+struct Shared {
+    /// Watched files, with their last seen stamps
+    files: HashMap<PathBuf, Stamp>,
+    /// Watched directories
+    directories: HashSet<PathBuf>,
+    health: WatcherHealth,
+}
+
+impl Shared {
+    fn is_relevant(&self, path: &Path) -> bool {
+        self.files.contains_key(path) || self.directories.iter().any(|dir| path.starts_with(dir))
+    }
+
+    fn record(&mut self, event: &FileChangeEvent) {
+        self.health.last_event = Some(event.timestamp);
+        self.health.events += 1;
+        if let Some(seen) = self.files.get_mut(&event.path) {
+            *seen = stamp(&event.path);
+        }
+        metrics::counter!("rune_watcher_events_total").increment(1);
+    }
+}
+
 /// File watcher for .rune configuration files
 pub struct RUNEWatcher {
     /// The underlying notify watcher
-    watcher: RecommendedWatcher,
+    watcher: Box<dyn Watcher + Send>,
     /// Channel receiver for events
     event_rx: Receiver<FileChangeEvent>,
     /// Channel sender (kept for cloning)
     event_tx: Sender<FileChangeEvent>,
-    /// Paths being watched
-    watched_paths: HashSet<PathBuf>,
+    /// Paths being watched, as given, and what they resolved to
+    targets: HashMap<PathBuf, Target>,
+    /// Directories registered with the underlying watcher, with how many
+    /// targets need each
+    registered: HashMap<PathBuf, (RecursiveMode, usize)>,
     /// File extensions to watch
     extensions: Vec<String>,
+    /// Watched paths, stamps and health, shared with the callback
+    shared: Arc<Mutex<Shared>>,
 }
 
 impl RUNEWatcher {
-    /// Create a new file watcher
+    /// Create a new file watcher using platform notifications
     pub fn new() -> Result<Self> {
+        Self::with_mode(WatchMode::Native)
+    }
+
+    /// Create a file watcher detecting changes the given way
+    ///
+    /// If platform notifications are unavailable, the watcher polls every
+    /// second instead.
+    pub fn with_mode(mode: WatchMode) -> Result<Self> {
         let (tx, rx) = channel();
-        let tx_clone = tx.clone();
-
-        // Create notify watcher with custom event handler
-        let watcher = RecommendedWatcher::new(
-            move |result: notify::Result<Event>| match result {
-                Ok(event) => {
-                    if let Some(change_event) = process_notify_event(event) {
-                        if let Err(e) = tx.send(change_event) {
-                            error!("Failed to send file change event: {}", e);
-                        }
-                    }
+        let mut mode = mode;
+        let shared = Arc::new(Mutex::new(Shared {
+            files: HashMap::new(),
+            directories: HashSet::new(),
+            health: WatcherHealth {
+                mode,
+                last_event: None,
+                events: 0,
+                missed_changes: 0,
+                errors: 0,
+            },
+        }));
+
+        let watcher = match mode {
+            WatchMode::Native => match create_watcher::<RecommendedWatcher>(
+                tx.clone(),
+                shared.clone(),
+                Config::default(),
+            ) {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    warn!("Native file watching unavailable ({}), polling instead", e);
+                    mode = WatchMode::Poll(Duration::from_secs(1));
+                    create_watcher::<PollWatcher>(tx.clone(), shared.clone(), poll_config(mode))?
                 }
-                Err(e) => error!("File watch error: {}", e),
             },
-            Config::default()
-                .with_poll_interval(Duration::from_secs(1))
-                .with_compare_contents(false),
-        )
-        .map_err(|e| RUNEError::ConfigError(format!("Failed to create watcher: {}", e)))?;
+            WatchMode::Poll(_) => {
+                create_watcher::<PollWatcher>(tx.clone(), shared.clone(), poll_config(mode))?
+            }
+        };
+        lock(&shared).health.mode = mode;
+        metrics::gauge!("rune_watcher_polling").set(if mode == WatchMode::Native {
+            0.0
+        } else {
+            1.0
+        });
 
         Ok(RUNEWatcher {
             watcher,
             event_rx: rx,
-            event_tx: tx_clone,
-            watched_paths: HashSet::new(),
+            event_tx: tx,
+            targets: HashMap::new(),
+            registered: HashMap::new(),
             extensions: vec!["rune".to_string(), "toml".to_string()],
+            shared,
         })
     }
 
     /// Watch a file or directory
+    ///
+    /// Files are followed by name, so replacing one (as editors do on save)
+    /// does not end the watch.
     pub fn watch(&mut self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
 
         // Check if already watching
-        if self.watched_paths.contains(path) {
+        if self.targets.contains_key(path) {
             debug!("Already watching path: {:?}", path);
             return Ok(());
         }
 
-        // Determine recursive mode based on path type
-        let mode = if path.is_dir() {
-            RecursiveMode::Recursive
+        let canonical = std::fs::canonicalize(path)
+            .map_err(|e| RUNEError::ConfigError(format!("Failed to watch {:?}: {}", path, e)))?;
+        let (target, directory, mode) = if canonical.is_dir() {
+            (
+                Target::Directory(canonical.clone()),
+                canonical,
+                RecursiveMode::Recursive,
+            )
         } else {
-            RecursiveMode::NonRecursive
+            let parent = canonical
+                .parent()
+                .map(Path::to_path_buf)
+                .ok_or_else(|| RUNEError::ConfigError(format!("Cannot watch {:?}", path)))?;
+            (
+                Target::File {
+                    file: canonical,
+                    parent: parent.clone(),
+                },
+                parent,
+                RecursiveMode::NonRecursive,
+            )
         };
 
-        // Start watching
-        self.watcher
-            .watch(path, mode)
-            .map_err(|e| RUNEError::ConfigError(format!("Failed to watch {:?}: {}", path, e)))?;
-
-        self.watched_paths.insert(path.to_path_buf());
+        self.register(&directory, mode)?;
+        {
+            let mut shared = lock(&self.shared);
+            match &target {
+                Target::File { file, .. } => {
+                    shared.files.insert(file.clone(), stamp(file));
+                }
+                Target::Directory(dir) => {
+                    shared.directories.insert(dir.clone());
+                }
+            }
+        }
+        self.targets.insert(path.to_path_buf(), target);
         info!("Now watching: {:?} (mode: {:?})", path, mode);
 
         Ok(())
     }
 
+    /// Register a directory with the underlying watcher, once per mode
+    fn register(&mut self, directory: &Path, mode: RecursiveMode) -> Result<()> {
+        if let Some((registered, count)) = self.registered.get_mut(directory) {
+            if *registered == RecursiveMode::Recursive || mode == RecursiveMode::NonRecursive {
+                *count += 1;
+                return Ok(());
+            }
+            // Widen a parent watched for its files to the whole tree
+            self.watcher.unwatch(directory).map_err(|e| {
+                RUNEError::ConfigError(format!("Failed to unwatch {:?}: {}", directory, e))
+            })?;
+        }
+        self.watcher.watch(directory, mode).map_err(|e| {
+            RUNEError::ConfigError(format!("Failed to watch {:?}: {}", directory, e))
+        })?;
+        let entry = self
+            .registered
+            .entry(directory.to_path_buf())
+            .or_insert((mode, 0));
+        entry.0 = mode;
+        entry.1 += 1;
+        Ok(())
+    }
+
     /// Stop watching a path
     pub fn unwatch(&mut self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
 
-        if !self.watched_paths.contains(path) {
+        let Some(target) = self.targets.remove(path) else {
             return Ok(());
-        }
-
-        self.watcher
-            .unwatch(path)
-            .map_err(|e| RUNEError::ConfigError(format!("Failed to unwatch {:?}: {}", path, e)))?;
+        };
+        let directory = {
+            let mut shared = lock(&self.shared);
+            match target {
+                Target::File { file, parent } => {
+                    shared.files.remove(&file);
+                    parent
+                }
+                Target::Directory(dir) => {
+                    shared.directories.remove(&dir);
+                    dir
+                }
+            }
+        };
 
-        self.watched_paths.remove(path);
+        if let Some((_, count)) = self.registered.get_mut(&directory) {
+            *count -= 1;
+            if *count == 0 {
+                self.registered.remove(&directory);
+                self.watcher.unwatch(&directory).map_err(|e| {
+                    RUNEError::ConfigError(format!("Failed to unwatch {:?}: {}", path, e))
+                })?;
+            }
+        }
         info!("Stopped watching: {:?}", path);
 
         Ok(())
@@ -160,38 +340,141 @@ impl RUNEWatcher {
 
     /// Get watched paths
     pub fn watched_paths(&self) -> Vec<PathBuf> {
-        self.watched_paths.iter().cloned().collect()
+        self.targets.keys().cloned().collect()
     }
 
     /// Clear all watches
     pub fn clear(&mut self) -> Result<()> {
-        let paths: Vec<PathBuf> = self.watched_paths.iter().cloned().collect();
+        let paths: Vec<PathBuf> = self.targets.keys().cloned().collect();
         for path in paths {
             self.unwatch(&path)?;
         }
         Ok(())
     }
+
+    /// Report watched files whose modification time or size changed
+    /// without an event
+    ///
+    /// Each such file is sent as a `Modified` (or `Removed`) event, as if
+    /// the watcher had seen it, and counted as a missed change.
+    pub fn check_for_missed_changes(&self) -> usize {
+        let now = Instant::now();
+        let mut missed = Vec::new();
+        {
+            let mut shared = lock(&self.shared);
+            for (file, seen) in shared.files.iter_mut() {
+                let current = stamp(file);
+                if current != *seen {
+                    let kind = match current {
+                        Some(_) => ChangeKind::Modified,
+                        None => ChangeKind::Removed,
+                    };
+                    *seen = current;
+                    missed.push(FileChangeEvent {
+                        path: file.clone(),
+                        kind,
+                        timestamp: now,
+                    });
+                }
+            }
+            shared.health.missed_changes += missed.len() as u64;
+            if let Some(last) = shared.health.last_event {
+                metrics::gauge!("rune_watcher_last_event_age_seconds")
+                    .set(now.duration_since(last).as_secs_f64());
+            }
+        }
+
+        for event in &missed {
+            warn!("Watcher missed a change to {:?}", event.path);
+            metrics::counter!("rune_watcher_missed_changes_total").increment(1);
+            let _ = self.event_tx.send(event.clone());
+        }
+        missed.len()
+    }
+
+    /// Detection mode, event counts and missed changes so far
+    pub fn health(&self) -> WatcherHealth {
+        lock(&self.shared).health.clone()
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> std::sync::MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn poll_config(mode: WatchMode) -> Config {
+    let interval = match mode {
+        WatchMode::Poll(interval) => interval,
+        WatchMode::Native => Duration::from_secs(1),
+    };
+    // Hash contents too: coarse modification times on network filesystems
+    // would hide quick successive saves
+    Config::default()
+        .with_poll_interval(interval)
+        .with_compare_contents(true)
+}
+
+/// Create a notify watcher forwarding relevant changes to `tx`
+fn create_watcher<W: Watcher + Send + 'static>(
+    tx: Sender<FileChangeEvent>,
+    shared: Arc<Mutex<Shared>>,
+    config: Config,
+) -> Result<Box<dyn Watcher + Send>> {
+    let watcher = W::new(
+        move |result: notify::Result<Event>| match result {
+            Ok(event) => {
+                let Some(change_event) = process_notify_event(event) else {
+                    return;
+                };
+                {
+                    let mut shared = lock(&shared);
+                    if !shared.is_relevant(&change_event.path) {
+                        trace!("Ignoring unwatched file: {:?}", change_event.path);
+                        return;
+                    }
+                    shared.record(&change_event);
+                }
+                if let Err(e) = tx.send(change_event) {
+                    error!("Failed to send file change event: {}", e);
+                }
+            }
+            Err(e) => {
+                lock(&shared).health.errors += 1;
+                metrics::counter!("rune_watcher_errors_total").increment(1);
+                error!("File watch error: {}", e);
+            }
+        },
+        config,
+    )
+    .map_err(|e| RUNEError::ConfigError(format!("Failed to create watcher: {}", e)))?;
+    Ok(Box::new(watcher))
 }
 
 /// Process notify event into our event type
+///
+/// A rename counts as a change to the name it ends up with, so a file
+/// saved by renaming a temporary copy over it is reported as modified.
 fn process_notify_event(event: Event) -> Option<FileChangeEvent> {
     // Filter for relevant event kinds
-    let kind = match event.kind {
-        EventKind::Create(_) => ChangeKind::Created,
-        EventKind::Modify(modify_kind) => {
-            use notify::event::ModifyKind;
-            match modify_kind {
-                ModifyKind::Data(_) | ModifyKind::Any => ChangeKind::Modified,
-                _ => return None, // Ignore metadata changes
-            }
+    let (kind, path) = match event.kind {
+        EventKind::Create(_) => (ChangeKind::Created, event.paths.into_iter().next()?),
+        EventKind::Modify(
+            ModifyKind::Data(_) | ModifyKind::Any | ModifyKind::Metadata(MetadataKind::WriteTime),
+        ) => (ChangeKind::Modified, event.paths.into_iter().next()?),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            (ChangeKind::Modified, event.paths.into_iter().nth(1)?)
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            (ChangeKind::Modified, event.paths.into_iter().next()?)
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            (ChangeKind::Removed, event.paths.into_iter().next()?)
         }
-        EventKind::Remove(_) => ChangeKind::Removed,
+        EventKind::Modify(_) => return None, // Ignore metadata changes
+        EventKind::Remove(_) => (ChangeKind::Removed, event.paths.into_iter().next()?),
         _ => return None, // Ignore access and other events
     };
 
-    // Get the first path (usually there's only one)
-    let path = event.paths.into_iter().next()?;
-
     // Filter for .rune and .toml files
     if let Some(ext) = path.extension() {
         let ext_str = ext.to_str()?;
@@ -221,10 +504,10 @@ pub struct EventDebouncer {
     pending: HashMap<PathBuf, FileChangeEvent>,
     /// Last event time for each path
     last_event_time: HashMap<PathBuf, std::time::Instant>,
+    /// When the oldest pending change arrived
+    first_pending: Option<std::time::Instant>,
 }
 
-use std::collections::HashMap;
-
 impl EventDebouncer {
     /// Create a new debouncer with specified duration
     pub fn new(duration: Duration) -> Self {
@@ -232,6 +515,7 @@ impl EventDebouncer {
             duration,
             pending: HashMap::new(),
             last_event_time: HashMap::new(),
+            first_pending: None,
         }
     }
 
@@ -240,6 +524,7 @@ impl EventDebouncer {
         let now = std::time::Instant::now();
         self.pending.insert(event.path.clone(), event.clone());
         self.last_event_time.insert(event.path, now);
+        self.first_pending.get_or_insert(now);
     }
 
     /// Get events that have settled (no new events for duration)
@@ -267,10 +552,41 @@ impl EventDebouncer {
                 settled.push(event);
             }
         }
+        if self.pending.is_empty() {
+            self.first_pending = None;
+        }
 
         settled
     }
 
+    /// Take every pending event once the whole changed set has settled
+    ///
+    /// Files changed together, such as rules and policies pushed by one
+    /// deploy, come back together: only when none of them has changed for
+    /// the debounce duration, or when the first of them has waited
+    /// `max_wait`, so a file rewritten continuously cannot hold back the
+    /// rest forever. Events are ordered by path.
+    pub fn get_settled_set(&mut self, max_wait: Duration) -> Vec<FileChangeEvent> {
+        let now = std::time::Instant::now();
+        let quiet = self
+            .last_event_time
+            .values()
+            .all(|time| now.duration_since(*time) >= self.duration);
+        let overdue = self
+            .first_pending
+            .is_some_and(|first| now.duration_since(first) >= max_wait);
+        if !quiet && !overdue {
+            return Vec::new();
+        }
+
+        self.last_event_time.clear();
+        self.first_pending = None;
+        let mut settled: Vec<FileChangeEvent> =
+            self.pending.drain().map(|(_, event)| event).collect();
+        settled.sort_by(|a, b| a.path.cmp(&b.path));
+        settled
+    }
+
     /// Check if any events are pending
     pub fn has_pending(&self) -> bool {
         !self.pending.is_empty()
@@ -280,6 +596,7 @@ impl EventDebouncer {
     pub fn clear(&mut self) {
         self.pending.clear();
         self.last_event_time.clear();
+        self.first_pending = None;
     }
 }
 
@@ -666,6 +983,47 @@ mod tests {
         assert_eq!(settled[0].kind, ChangeKind::Modified); // Should have the latest event
     }
 
+    #[test]
+    fn test_debouncer_settles_changed_set_together() {
+        let mut debouncer = EventDebouncer::new(Duration::from_millis(100));
+        let event = |path: &str| FileChangeEvent {
+            path: PathBuf::from(path),
+            kind: ChangeKind::Modified,
+            timestamp: std::time::Instant::now(),
+        };
+
+        debouncer.add_event(event("/tmp/rules.rune"));
+        std::thread::sleep(Duration::from_millis(70));
+        debouncer.add_event(event("/tmp/policies.rune"));
+        std::thread::sleep(Duration::from_millis(70));
+
+        // The first file has settled, but the set has not
+        assert!(debouncer
+            .get_settled_set(Duration::from_secs(10))
+            .is_empty());
+
+        std::thread::sleep(Duration::from_millis(50));
+        let settled = debouncer.get_settled_set(Duration::from_secs(10));
+        let paths: Vec<_> = settled.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/tmp/policies.rune"),
+                PathBuf::from("/tmp/rules.rune")
+            ]
+        );
+        assert!(!debouncer.has_pending());
+
+        // A file that keeps changing is flushed after the longest wait
+        debouncer.add_event(event("/tmp/rules.rune"));
+        std::thread::sleep(Duration::from_millis(30));
+        debouncer.add_event(event("/tmp/rules.rune"));
+        assert_eq!(
+            debouncer.get_settled_set(Duration::from_millis(20)).len(),
+            1
+        );
+    }
+
     #[test]
     fn test_debouncer_clear() {
         let mut debouncer = EventDebouncer::new(Duration::from_millis(100));
@@ -791,4 +1149,123 @@ mod tests {
         let result = watcher.watch(&invalid_path);
         assert!(result.is_err());
     }
+
+    /// Wait for an event about `path`, skipping others
+    fn wait_for(watcher: &RUNEWatcher, path: &Path) -> Option<FileChangeEvent> {
+        let path = fs::canonicalize(path).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(event) = watcher.recv_timeout(Duration::from_millis(50)) {
+                if event.path == path {
+                    return Some(event);
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_process_notify_event_rename() {
+        let event = Event {
+            kind: EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            paths: vec![PathBuf::from(".test.rune.swp"), PathBuf::from("test.rune")],
+            attrs: Default::default(),
+        };
+        let result = process_notify_event(event).unwrap();
+        assert_eq!(result.path, PathBuf::from("test.rune"));
+        assert_eq!(result.kind, ChangeKind::Modified);
+
+        let event = Event {
+            kind: EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+            paths: vec![PathBuf::from("test.rune")],
+            attrs: Default::default(),
+        };
+        assert_eq!(
+            process_notify_event(event).unwrap().kind,
+            ChangeKind::Removed
+        );
+    }
+
+    #[test]
+    fn test_atomic_save_is_followed() {
+        let mut watcher = RUNEWatcher::new().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.rune");
+        fs::write(&file_path, "version = \"1.0\"").unwrap();
+        watcher.watch(&file_path).unwrap();
+
+        // Save the way editors do: write a temporary file, rename it over
+        for version in ["2.0", "3.0"] {
+            let temp_path = temp_dir.path().join("test.rune.tmp");
+            fs::write(&temp_path, format!("version = \"{}\"", version)).unwrap();
+            fs::rename(&temp_path, &file_path).unwrap();
+            assert!(
+                wait_for(&watcher, &file_path).is_some(),
+                "no event for save of {}",
+                version
+            );
+        }
+
+        // Files next to the watched one are not reported
+        fs::write(temp_dir.path().join("other.rune"), "").unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        while let Some(event) = watcher.try_recv() {
+            assert_eq!(event.path, fs::canonicalize(&file_path).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_poll_mode() {
+        let mut watcher =
+            RUNEWatcher::with_mode(WatchMode::Poll(Duration::from_millis(50))).unwrap();
+        assert_eq!(
+            watcher.health().mode,
+            WatchMode::Poll(Duration::from_millis(50))
+        );
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.rune");
+        fs::write(&file_path, "version = \"1.0\"").unwrap();
+        watcher.watch(&file_path).unwrap();
+
+        fs::write(&file_path, "version = \"2.0\" # changed").unwrap();
+        assert!(wait_for(&watcher, &file_path).is_some());
+
+        let health = watcher.health();
+        assert!(health.events >= 1);
+        assert!(health.last_event.is_some());
+    }
+
+    #[test]
+    fn test_check_for_missed_changes() {
+        let mut watcher = RUNEWatcher::new().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.rune");
+        fs::write(&file_path, "version = \"1.0\"").unwrap();
+        watcher.watch(&file_path).unwrap();
+        assert_eq!(watcher.check_for_missed_changes(), 0);
+
+        // Forget what the file looked like, as if its last event was lost
+        let forget = |watcher: &RUNEWatcher| {
+            std::thread::sleep(Duration::from_millis(200));
+            while watcher.try_recv().is_some() {}
+            for seen in lock(&watcher.shared).files.values_mut() {
+                *seen = Some((SystemTime::UNIX_EPOCH, 0));
+            }
+        };
+
+        forget(&watcher);
+        assert_eq!(watcher.check_for_missed_changes(), 1);
+        let event = watcher.try_recv().unwrap();
+        assert_eq!(event.path, fs::canonicalize(&file_path).unwrap());
+        assert_eq!(event.kind, ChangeKind::Modified);
+
+        // Reported once, not on every check
+        assert_eq!(watcher.check_for_missed_changes(), 0);
+        assert_eq!(watcher.health().missed_changes, 1);
+
+        fs::remove_file(&file_path).unwrap();
+        forget(&watcher);
+        assert_eq!(watcher.check_for_missed_changes(), 1);
+        assert_eq!(watcher.try_recv().unwrap().kind, ChangeKind::Removed);
+    }
 }
@@ -0,0 +1,186 @@
+//! Worker pool for asynchronous authorization
+//!
+//! A Datalog fixpoint can keep a thread busy for the whole evaluation
+//! budget, which on an async runtime stalls every other task scheduled on
+//! that thread. [`crate::RUNEEngine::authorize_async`] hands evaluations to
+//! a pool of dedicated threads instead and awaits the answer.
+//!
+//! The pool is bounded twice: `threads` evaluations run at once, and at most
+//! `queue` more wait for a thread. Callers beyond that wait for room before
+//! their request is queued, so a burst slows callers down rather than
+//! growing an unbounded backlog. A deadline covers all of it: the future
+//! resolves with [`RUNEError::Timeout`] once the deadline passes, and a
+//! request still waiting in the queue by then is dropped without being
+//! evaluated. An evaluation already running completes on its worker (its
+//! decision is still cached) within the engine's own Datalog budget.
+//!
+//! The pool is started by the first asynchronous request. Awaiting requests
+//! with a deadline needs a Tokio runtime for its timer.
+
+use crate::error::{RUNEError, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tokio::sync::{oneshot, Semaphore};
+
+/// Worker pool settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerConfig {
+    /// Evaluations run at once; 0 means one per CPU
+    pub threads: usize,
+    /// Evaluations waiting for a thread before callers have to wait too
+    pub queue: usize,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            threads: 0,
+            queue: 1024,
+        }
+    }
+}
+
+/// Threads evaluating requests on behalf of async callers
+pub(crate) struct WorkerPool {
+    config: WorkerConfig,
+    pool: OnceLock<rayon::ThreadPool>,
+    /// One permit per running or queued evaluation
+    slots: Arc<Semaphore>,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(config: WorkerConfig) -> Self {
+        let threads = match config.threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        };
+        WorkerPool {
+            slots: Arc::new(Semaphore::new(threads + config.queue)),
+            config: WorkerConfig { threads, ..config },
+            pool: OnceLock::new(),
+        }
+    }
+
+    fn pool(&self) -> &rayon::ThreadPool {
+        self.pool.get_or_init(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(self.config.threads)
+                .thread_name(|i| format!("rune-worker-{}", i))
+                // A panicking job only loses its own answer
+                .panic_handler(|_| {})
+                .build()
+                .expect("Failed to start the worker pool")
+        })
+    }
+
+    /// Evaluations running or queued right now
+    pub(crate) fn pending(&self) -> usize {
+        (self.config.threads + self.config.queue).saturating_sub(self.slots.available_permits())
+    }
+
+    /// Run `job` on a worker without waiting for it, if a slot is free
+    ///
+    /// Returns whether the job was queued; background work never makes
+    /// callers wait for room.
+    pub(crate) fn spawn(&self, job: impl FnOnce() + Send + 'static) -> bool {
+        let Ok(slot) = self.slots.clone().try_acquire_owned() else {
+            return false;
+        };
+        self.pool().spawn(move || {
+            job();
+            drop(slot);
+        });
+        true
+    }
+
+    /// Run `job` on a worker and await its result, giving up at `deadline`
+    pub(crate) async fn run<T: Send + 'static>(
+        &self,
+        deadline: Option<Instant>,
+        job: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let queued = async {
+            let slot = self
+                .slots
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|_| RUNEError::Cancelled)?;
+            let (answer, answered) = oneshot::channel();
+            self.pool().spawn(move || {
+                // Nobody is waiting for a request that outlived its deadline
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return;
+                }
+                let result = job();
+                // Free the slot before answering, so a caller that got its
+                // answer finds the slot free again
+                drop(slot);
+                let _ = answer.send(result);
+            });
+            answered.await.map_err(|_| RUNEError::Cancelled)?
+        };
+
+        let Some(deadline) = deadline else {
+            return queued.await;
+        };
+        let budget_ms = deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis() as u64;
+        match tokio::time::timeout_at(deadline.into(), queued).await {
+            Ok(Err(RUNEError::Cancelled)) if Instant::now() >= deadline => {
+                Err(RUNEError::Timeout(budget_ms))
+            }
+            Ok(result) => result,
+            Err(_) => Err(RUNEError::Timeout(budget_ms)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_deadline_and_queue() {
+        let pool = Arc::new(WorkerPool::new(WorkerConfig {
+            threads: 1,
+            queue: 0,
+        }));
+        assert_eq!(pool.run(None, || Ok(7)).await.unwrap(), 7);
+
+        // Occupy the only slot, so the next request cannot be queued
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let busy = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run(None, move || {
+                    let _ = released.recv();
+                    Ok(())
+                })
+                .await
+            }
+        });
+        while pool.pending() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.pending(), 1);
+        let late = pool
+            .run(Some(Instant::now() + Duration::from_millis(20)), || Ok(1))
+            .await;
+        assert!(matches!(late, Err(RUNEError::Timeout(_))));
+
+        release.send(()).unwrap();
+        busy.await.unwrap().unwrap();
+        assert_eq!(pool.pending(), 0);
+        assert_eq!(
+            pool.run(Some(Instant::now() + Duration::from_secs(5)), || Ok(2))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! Runs the shared conformance scenarios against rune-core
+//!
+//! The HTTP server and the Python binding run the same files; see
+//! `conformance/README.md`.
+
+use rune_core::conformance::Scenario;
+use std::path::{Path, PathBuf};
+
+fn scenarios() -> Vec<(PathBuf, Scenario)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../conformance/scenarios");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .expect("Failed to read scenario directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let contents = std::fs::read_to_string(&path).expect("Failed to read scenario");
+            let scenario = serde_yaml::from_str(&contents)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            (path, scenario)
+        })
+        .collect()
+}
+
+#[test]
+fn test_conformance_scenarios() {
+    let scenarios = scenarios();
+    assert!(!scenarios.is_empty(), "No conformance scenarios found");
+
+    let failures: Vec<String> = scenarios
+        .iter()
+        .filter_map(|(path, scenario)| {
+            let checked = scenario
+                .run()
+                .map_err(|e| format!("{}: {}", scenario.name, e))
+                .and_then(|outcome| scenario.check(&outcome));
+            checked.err().map(|e| format!("{}: {}", path.display(), e))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -2,7 +2,7 @@
 //!
 //! Tests the full pipeline: parser → evaluation → Cedar bridge → authorization
 
-use rune_core::datalog::{CedarDatalogBridge, DatalogEngine};
+use rune_core::datalog::{CedarDatalogBridge, DatalogEngine, EvaluationBackend};
 use rune_core::facts::{Fact, FactStore};
 use rune_core::parser::parse_rules;
 use rune_core::request::Request;
@@ -182,6 +182,76 @@ fn test_end_to_end_negation() {
         .any(|f| matches!(&f.args[..], [Value::String(u)] if u.as_ref() == "charlie")));
 }
 
+#[test]
+fn test_end_to_end_disjunction_and_comparisons() {
+    let rules_source = r#"
+        can_write(User, Doc) :-
+            user_role(User, Unit, Role),
+            owner(Doc, Unit),
+            (Role == "head" ; Role == "ceo").
+        peer(A, B) :- user_role(A, Unit, RoleA), user_role(B, Unit, RoleB), A != B.
+    "#;
+    let rules = parse_rules(rules_source).expect("Failed to parse rules");
+    assert_eq!(rules.len(), 3);
+
+    for backend in [EvaluationBackend::Interpreter, EvaluationBackend::Dataflow] {
+        let fact_store = Arc::new(FactStore::new());
+        for (user, unit, role) in [
+            ("ann", "eng", "head"),
+            ("bob", "eng", "member"),
+            ("cid", "hr", "ceo"),
+        ] {
+            fact_store.add_fact(Fact::new(
+                "user_role",
+                vec![
+                    Value::string(user),
+                    Value::string(unit),
+                    Value::string(role),
+                ],
+            ));
+        }
+        fact_store.add_fact(Fact::new(
+            "owner",
+            vec![Value::string("design"), Value::string("eng")],
+        ));
+        fact_store.add_fact(Fact::new(
+            "owner",
+            vec![Value::string("payroll"), Value::string("hr")],
+        ));
+
+        let engine = DatalogEngine::new(rules.clone(), fact_store).with_backend(backend);
+        let derived = engine.derive_facts().expect("Failed to derive facts");
+        let pairs = |predicate: &str| {
+            let mut pairs: Vec<_> = derived
+                .iter()
+                .filter(|f| f.predicate.as_ref() == predicate)
+                .map(|f| (f.args[0].clone(), f.args[1].clone()))
+                .collect();
+            pairs.sort();
+            pairs
+        };
+
+        assert_eq!(
+            pairs("can_write"),
+            vec![
+                (Value::string("ann"), Value::string("design")),
+                (Value::string("cid"), Value::string("payroll"))
+            ],
+            "{:?}",
+            backend
+        );
+        assert_eq!(
+            pairs("peer"),
+            vec![
+                (Value::string("ann"), Value::string("bob")),
+                (Value::string("bob"), Value::string("ann"))
+            ],
+            "{:?}",
+            backend
+        );
+    }
+}
+
 #[test]
 fn test_cedar_bridge_request_conversion() {
     // Create a request with hierarchical entities
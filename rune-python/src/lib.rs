@@ -7,6 +7,7 @@ use rune_core::{
     RUNEEngine as CoreEngine,
     RequestBuilder,
     Principal, Action, Resource,
+    SessionAttribute,
     Value, Decision,
 };
 use std::sync::Arc;
@@ -27,9 +28,8 @@ impl PythonRUNE {
         let engine = CoreEngine::new();
 
         if let Some(path) = config_path {
-            // TODO: Load configuration
-            // engine.load_configuration(&path)
-            //     .map_err(|e| PyValueError::new_err(format!("Failed to load config: {}", e)))?;
+            engine.load_configuration(&path)
+                .map_err(|e| PyValueError::new_err(format!("Failed to load config: {}", e)))?;
         }
 
         Ok(PythonRUNE {
@@ -38,19 +38,22 @@ impl PythonRUNE {
     }
 
     /// Authorize a request
-    #[pyo3(signature = (action, principal=None, resource=None, **kwargs))]
+    #[pyo3(signature = (action, principal=None, resource=None, principal_attributes=None, **kwargs))]
     fn authorize(
         &self,
         action: String,
         principal: Option<String>,
         resource: Option<String>,
+        principal_attributes: Option<&PyDict>,
         kwargs: Option<&PyDict>,
     ) -> PyResult<bool> {
         // Build request using RequestBuilder
-        let mut builder = RequestBuilder::new()
-            .principal(Principal::agent(principal.unwrap_or_else(|| "default".to_string())))
-            .action(Action::new(action))
-            .resource(Resource::file(resource.unwrap_or_else(|| "/".to_string())));
+        let mut builder = request_builder(
+            action,
+            &principal.unwrap_or_else(|| "default".to_string()),
+            &resource.unwrap_or_else(|| "/".to_string()),
+            principal_attributes,
+        )?;
 
         // Add context from kwargs
         if let Some(dict) = kwargs {
@@ -74,9 +77,9 @@ impl PythonRUNE {
         Ok(result.decision.is_permitted())
     }
 
-    /// Batch authorize multiple requests
+    /// Batch authorize multiple requests, sharing one Datalog evaluation
     fn authorize_batch(&self, requests: &PyList) -> PyResult<Vec<bool>> {
-        let mut results = Vec::new();
+        let mut batch = Vec::with_capacity(requests.len());
 
         for item in requests.iter() {
             let dict = item.downcast::<PyDict>()?;
@@ -98,11 +101,13 @@ impl PythonRUNE {
                 .transpose()?
                 .unwrap_or_else(|| "/".to_string());
 
+            let principal_attributes = dict
+                .get_item("principal_attributes")?
+                .map(|attributes| attributes.downcast::<PyDict>())
+                .transpose()?;
+
             // Build request using RequestBuilder
-            let mut builder = RequestBuilder::new()
-                .principal(Principal::agent(principal))
-                .action(Action::new(action))
-                .resource(Resource::file(resource));
+            let mut builder = request_builder(action, &principal, &resource, principal_attributes)?;
 
             // Add context if present
             if let Some(context) = dict.get_item("context")? {
@@ -118,15 +123,86 @@ impl PythonRUNE {
             let request = builder
                 .build()
                 .map_err(|e| PyValueError::new_err(format!("Invalid request: {}", e)))?;
+            batch.push(request);
+        }
 
-            let result = self.engine
-                .authorize(&request)
-                .map_err(|e| PyValueError::new_err(format!("Authorization failed: {}", e)))?;
+        self.engine
+            .authorize_batch(&batch)
+            .into_iter()
+            .map(|result| {
+                result
+                    .map(|result| result.decision.is_permitted())
+                    .map_err(|e| PyValueError::new_err(format!("Authorization failed: {}", e)))
+            })
+            .collect()
+    }
 
-            results.push(result.decision.is_permitted());
-        }
+    /// Authorize a request, returning `{"decision": ..., "reasons": [...]}`
+    ///
+    /// The decision is `"permit"`, `"deny"` or `"forbid"`; the reasons are
+    /// those the HTTP API reports.
+    #[pyo3(signature = (action, principal, resource, principal_attributes=None))]
+    fn decide(
+        &self,
+        py: Python,
+        action: String,
+        principal: String,
+        resource: String,
+        principal_attributes: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        let request = request_builder(action, &principal, &resource, principal_attributes)?
+            .build()
+            .map_err(|e| PyValueError::new_err(format!("Invalid request: {}", e)))?;
+        let result = self.engine
+            .authorize(&request)
+            .map_err(|e| PyValueError::new_err(format!("Authorization failed: {}", e)))?;
+
+        let decision = match result.decision {
+            Decision::Permit => "permit",
+            Decision::Deny => "deny",
+            Decision::Forbid => "forbid",
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("decision", decision)?;
+        dict.set_item("reasons", vec![result.explanation])?;
+        Ok(dict.into())
+    }
+
+    /// Open a session installing `(predicate, args)` attributes for a
+    /// principal, returning the session ID
+    #[pyo3(signature = (principal, attributes, ttl_secs=None))]
+    fn open_session(
+        &self,
+        principal: String,
+        attributes: Vec<(String, Vec<PyObject>)>,
+        ttl_secs: Option<u64>,
+    ) -> PyResult<String> {
+        let attributes = Python::with_gil(|py| {
+            attributes
+                .into_iter()
+                .map(|(predicate, args)| {
+                    let args = args
+                        .iter()
+                        .map(|obj| python_to_value(obj.as_ref(py)))
+                        .collect::<PyResult<Vec<Value>>>()?;
+                    Ok(SessionAttribute::new(predicate, args))
+                })
+                .collect::<PyResult<Vec<_>>>()
+        })?;
+
+        let session = self.engine
+            .open_session(
+                &Principal::parse(&principal),
+                &attributes,
+                ttl_secs.map(std::time::Duration::from_secs),
+            )
+            .map_err(|e| PyValueError::new_err(format!("Failed to open session: {}", e)))?;
+        Ok(session.id)
+    }
 
-        Ok(results)
+    /// Close a session, returning whether it was open
+    fn close_session(&self, id: String) -> bool {
+        self.engine.close_session(&id)
     }
 
     /// Add a fact to the engine
@@ -143,6 +219,37 @@ impl PythonRUNE {
         Ok(())
     }
 
+    /// Grant `role` to a principal (`User:alice`) or group (`eng`),
+    /// returning whether it is new to them
+    fn grant_role(&self, principal: String, role: String) -> PyResult<bool> {
+        self.engine
+            .grant_role(&Principal::parse(&principal).entity.id, &role)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Revoke `role` from a principal or group, returning whether it was held
+    fn revoke_role(&self, principal: String, role: String) -> PyResult<bool> {
+        self.engine
+            .revoke_role(&Principal::parse(&principal).entity.id, &role)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Add a principal or group to `group`, returning whether the
+    /// membership is new
+    fn add_to_group(&self, principal: String, group: String) -> PyResult<bool> {
+        self.engine
+            .add_to_group(&Principal::parse(&principal).entity.id, &group)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Remove a principal or group from `group`, returning whether it was a
+    /// member
+    fn remove_from_group(&self, principal: String, group: String) -> PyResult<bool> {
+        self.engine
+            .remove_from_group(&Principal::parse(&principal).entity.id, &group)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
     /// Clear the cache
     fn clear_cache(&self) -> PyResult<()> {
         self.engine.clear_cache();
@@ -159,6 +266,24 @@ impl PythonRUNE {
     }
 }
 
+/// Request builder for `type:id` principal and resource strings, parsed as
+/// the HTTP API parses them
+fn request_builder(
+    action: String,
+    principal: &str,
+    resource: &str,
+    principal_attributes: Option<&PyDict>,
+) -> PyResult<RequestBuilder> {
+    let mut principal = Principal::parse(principal);
+    for (key, value) in principal_attributes.into_iter().flat_map(|dict| dict.iter()) {
+        principal = principal.with_attribute(key.extract::<String>()?, python_to_value(value)?);
+    }
+    Ok(RequestBuilder::new()
+        .principal(principal)
+        .action(Action::new(action))
+        .resource(Resource::parse(resource)))
+}
+
 /// Convert Python value to RUNE Value
 fn python_to_value(obj: &PyAny) -> PyResult<Value> {
     if obj.is_none() {
@@ -241,6 +366,7 @@ mod tests {
                 Some("agent-1".to_string()),
                 Some("/tmp/test.txt".to_string()),
                 None,
+                None,
             ).unwrap();
 
             // Default implementation permits everything for now
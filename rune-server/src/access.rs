@@ -0,0 +1,87 @@
+//! Access request notifications
+//!
+//! Access requests (see [`rune_core::access_requests`]) wait on a reviewer,
+//! so reviewers have to hear about them. Every time a request is opened,
+//! approved or denied it is counted in `rune_access_requests_total` by its
+//! new status and, with `RUNE_ACCESS_WEBHOOK_URL` set, POSTed there as JSON,
+//! e.g. to open a ticket or page whoever approves. The webhook is called off
+//! the request path and notifications are dropped, with a warning, when it
+//! falls behind.
+
+use crate::api::AccessRequestResponse;
+use crate::metrics;
+use rune_core::AccessRequest;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Notifications waiting for the webhook before new ones are dropped
+const WEBHOOK_QUEUE: usize = 1024;
+
+/// Time allowed for a webhook call
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Read `RUNE_ACCESS_WEBHOOK_URL`
+pub fn webhook_from_env() -> Option<String> {
+    std::env::var("RUNE_ACCESS_WEBHOOK_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+/// Tells reviewers about access request changes
+#[derive(Debug)]
+pub struct AccessNotifier {
+    webhook: mpsc::Sender<AccessRequestResponse>,
+}
+
+impl AccessNotifier {
+    /// Start the task that POSTs changes to `url`
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(url: String) -> Self {
+        let (sender, receiver) = mpsc::channel(WEBHOOK_QUEUE);
+        tokio::spawn(deliver(url, receiver));
+        AccessNotifier { webhook: sender }
+    }
+
+    /// Forward a request that was just opened or reviewed
+    pub fn notify(&self, access: &AccessRequest) {
+        if self.webhook.try_send(access.clone().into()).is_err() {
+            warn!(
+                "Access request webhook is falling behind; dropping request {} ({})",
+                access.id, access.status
+            );
+        }
+    }
+}
+
+/// Count a request that was just opened or reviewed and forward it to the
+/// webhook, if there is one
+pub fn record(notifier: Option<&AccessNotifier>, access: &AccessRequest) {
+    metrics::record_access_request(&access.status.to_string());
+    if let Some(notifier) = notifier {
+        notifier.notify(access);
+    }
+}
+
+/// POST changes to the webhook until the notifier is dropped
+async fn deliver(url: String, mut changes: mpsc::Receiver<AccessRequestResponse>) {
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    while let Some(change) = changes.recv().await {
+        let sent = client
+            .post(&url)
+            .json(&change)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = sent {
+            warn!(
+                "Failed to deliver access request {} to webhook: {}",
+                change.id, e
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Who is making a management change
+//!
+//! Management changes are made on behalf of an actor: role and group
+//! changes and access request reviews are attributed to it in the fact
+//! history, and configuration reloads, rollbacks and flag switches that
+//! touch `@owner` rules or policies need it to be a member of the owning
+//! team (see [`rune_core::ownership`]).
+//!
+//! The actor is established by a credential, never by what a caller says
+//! about itself: `RUNE_ACTOR_TOKENS` lists `actor=token` pairs, separated
+//! by commas, and a request presenting one of the tokens as
+//! `Authorization: Bearer <token>` acts as its actor. A request presenting
+//! a token that is not listed is refused with 401. One presenting none is
+//! anonymous ([`rune_core::ANONYMOUS_ACTOR`]): its changes are attributed to
+//! `unknown`, and it cannot change owned rules or policies. Only digests of
+//! the tokens are kept. Tokens travel in the clear unless the management
+//! listener serves TLS.
+
+use crate::error::ApiError;
+use axum::http::{header, HeaderMap};
+use rune_core::{ChangeOrigin, ANONYMOUS_ACTOR};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Actors known by the digests of their tokens
+#[derive(Debug, Clone, Default)]
+pub struct ActorTokens {
+    actors: HashMap<[u8; 32], String>,
+}
+
+impl ActorTokens {
+    /// Read `RUNE_ACTOR_TOKENS`; every change is anonymous without it
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(value) = std::env::var("RUNE_ACTOR_TOKENS") else {
+            return Ok(None);
+        };
+        Self::parse(&value)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_ACTOR_TOKENS: {}", e))
+    }
+
+    /// Parse comma-separated `actor=token` pairs
+    pub fn parse(pairs: &str) -> anyhow::Result<Self> {
+        let mut actors = HashMap::new();
+        for pair in pairs.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((actor, token)) = pair.split_once('=') else {
+                anyhow::bail!("expected actor=token, found {:?}", pair);
+            };
+            let (actor, token) = (actor.trim(), token.trim());
+            if actor.is_empty() || token.is_empty() {
+                anyhow::bail!("actor and token must not be empty in {:?}", pair);
+            }
+            if actor == ANONYMOUS_ACTOR || actor == "system" {
+                anyhow::bail!("{:?} is reserved and cannot be given a token", actor);
+            }
+            if actors.insert(digest(token), actor.to_string()).is_some() {
+                anyhow::bail!("a token is listed twice");
+            }
+        }
+        if actors.is_empty() {
+            anyhow::bail!("no tokens listed");
+        }
+        Ok(ActorTokens { actors })
+    }
+
+    /// Number of tokens
+    pub fn len(&self) -> usize {
+        self.actors.len()
+    }
+
+    /// Whether no tokens are known
+    pub fn is_empty(&self) -> bool {
+        self.actors.is_empty()
+    }
+
+    /// Actor whose token `headers` present; `None` if they present none
+    ///
+    /// Fails with 401 when they present a token that is not known.
+    pub fn actor(&self, headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+        let Some(value) = headers.get(header::AUTHORIZATION) else {
+            return Ok(None);
+        };
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| self.actors.get(&digest(token.trim())))
+            .map(|actor| Some(actor.as_str()))
+            .ok_or_else(|| ApiError::Unauthorized("Unknown actor token".to_string()))
+    }
+}
+
+/// Origin of a change requested with `headers`, made through the API
+///
+/// Anonymous unless `tokens` are configured and the headers present one.
+pub fn change_origin(
+    tokens: Option<&ActorTokens>,
+    headers: &HeaderMap,
+) -> Result<ChangeOrigin, ApiError> {
+    let actor = match tokens {
+        Some(tokens) => tokens.actor(headers)?,
+        None => None,
+    };
+    Ok(ChangeOrigin::new(actor.unwrap_or(ANONYMOUS_ACTOR), "api"))
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_actor_from_token() {
+        let tokens = ActorTokens::parse("carol=c-secret, dave=d-secret").unwrap();
+        assert_eq!(tokens.len(), 2);
+        let origin = change_origin(Some(&tokens), &bearer("d-secret")).unwrap();
+        assert_eq!(origin, ChangeOrigin::new("dave", "api"));
+
+        // Without a token the caller is anonymous, whatever it claims
+        let mut claimed = HeaderMap::new();
+        claimed.insert("x-rune-actor", "carol".parse().unwrap());
+        let origin = change_origin(Some(&tokens), &claimed).unwrap();
+        assert_eq!(origin.actor, ANONYMOUS_ACTOR);
+        let origin = change_origin(None, &bearer("c-secret")).unwrap();
+        assert_eq!(origin.actor, ANONYMOUS_ACTOR);
+
+        assert!(matches!(
+            change_origin(Some(&tokens), &bearer("guess")),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_tokens() {
+        for pairs in ["", "carol", "carol=", "unknown=x", "a=x,b=x"] {
+            assert!(ActorTokens::parse(pairs).is_err(), "{:?}", pairs);
+        }
+    }
+}
@@ -0,0 +1,549 @@
+//! Decision anomaly detection
+//!
+//! SOC teams want early warning from the PDP itself rather than from a log
+//! pipeline downstream. An [`AnomalyDetector`] watches every decision the
+//! data plane makes and raises an event for:
+//!
+//! - **Forbid spikes**: a tenant's forbids in the current window exceed
+//!   `RUNE_ANOMALY_SPIKE_FACTOR` times its moving average of previous
+//!   windows, and at least `RUNE_ANOMALY_MIN_FORBIDS`
+//! - **Novel pairs**: a principal asks about a resource it has never been
+//!   seen with before
+//!
+//! Detection starts with `RUNE_ANOMALY_WINDOW_SECS`. The first window only
+//! learns, so a restart does not report every pair as new. Seen pairs are
+//! capped at `RUNE_ANOMALY_MAX_PAIRS`; reaching the cap forgets them and
+//! learns for another window. The tenant is the principal attribute (or,
+//! failing that, context value) named by `RUNE_ANOMALY_TENANT_ATTRIBUTE`.
+//!
+//! Every anomaly is logged and counted in `rune_decision_anomalies_total`.
+//! With `RUNE_ANOMALY_WEBHOOK_URL` set it is also POSTed there as JSON; the
+//! webhook is called off the request path and events are dropped, with a
+//! warning, when it falls behind.
+
+use crate::metrics;
+use rune_core::{Decision, Entity, Request, Value};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Weight of the latest window in a tenant's forbid average
+const BASELINE_WEIGHT: f64 = 0.3;
+
+/// Events waiting for the webhook before new ones are dropped
+const WEBHOOK_QUEUE: usize = 1024;
+
+/// Time allowed for a webhook call
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tenant for requests without the tenant attribute
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Settings for anomaly detection
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnomalyConfig {
+    /// Length of a counting window, and of the initial learning period
+    pub window: Duration,
+    /// How many times its average a tenant's forbids must reach
+    pub spike_factor: f64,
+    /// Fewest forbids in a window that can count as a spike
+    pub min_forbids: u64,
+    /// Report principal-resource pairs not seen before
+    pub novel_pairs: bool,
+    /// Most pairs remembered before starting over
+    pub max_pairs: usize,
+    /// Principal attribute or context key holding the tenant
+    pub tenant_attribute: String,
+    /// URL anomalies are POSTed to
+    pub webhook: Option<String>,
+}
+
+impl AnomalyConfig {
+    /// Detect with `window` and the default settings
+    pub fn new(window: Duration) -> Self {
+        AnomalyConfig {
+            window,
+            spike_factor: 3.0,
+            min_forbids: 20,
+            novel_pairs: true,
+            max_pairs: 100_000,
+            tenant_attribute: "tenant".to_string(),
+            webhook: None,
+        }
+    }
+
+    /// Read the `RUNE_ANOMALY_*` variables; `None` when
+    /// `RUNE_ANOMALY_WINDOW_SECS` is unset
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        fn var<T>(name: &str) -> anyhow::Result<Option<T>>
+        where
+            T: std::str::FromStr,
+            T::Err: std::fmt::Display,
+        {
+            std::env::var(name)
+                .ok()
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        }
+
+        let Some(secs) = var::<u64>("RUNE_ANOMALY_WINDOW_SECS")? else {
+            return Ok(None);
+        };
+        if secs == 0 {
+            anyhow::bail!("Invalid RUNE_ANOMALY_WINDOW_SECS: must be at least 1");
+        }
+        let mut config = Self::new(Duration::from_secs(secs));
+        if let Some(factor) = var::<f64>("RUNE_ANOMALY_SPIKE_FACTOR")? {
+            if factor.is_nan() || factor <= 1.0 {
+                anyhow::bail!("Invalid RUNE_ANOMALY_SPIKE_FACTOR: must be greater than 1");
+            }
+            config.spike_factor = factor;
+        }
+        if let Some(min) = var("RUNE_ANOMALY_MIN_FORBIDS")? {
+            config.min_forbids = min;
+        }
+        if let Some(novel_pairs) = var("RUNE_ANOMALY_NOVEL_PAIRS")? {
+            config.novel_pairs = novel_pairs;
+        }
+        if let Some(max) = var("RUNE_ANOMALY_MAX_PAIRS")? {
+            config.max_pairs = max;
+        }
+        if let Ok(attribute) = std::env::var("RUNE_ANOMALY_TENANT_ATTRIBUTE") {
+            config.tenant_attribute = attribute;
+        }
+        config.webhook = std::env::var("RUNE_ANOMALY_WEBHOOK_URL").ok();
+        Ok(Some(config))
+    }
+}
+
+/// Something unusual in the decision stream
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Anomaly {
+    /// A tenant is being forbidden far more often than usual
+    #[serde(rename_all = "camelCase")]
+    ForbidSpike {
+        /// Tenant the forbids were for
+        tenant: String,
+        /// Forbids so far in the current window
+        forbids: u64,
+        /// Average forbids per window before this one
+        baseline: f64,
+        /// Window length in seconds
+        window_secs: f64,
+    },
+    /// A principal asked about a resource for the first time
+    #[serde(rename_all = "camelCase")]
+    NovelPair {
+        /// Tenant of the request
+        tenant: String,
+        /// Principal as `type:id`
+        principal: String,
+        /// Resource as `type:id`
+        resource: String,
+        /// Decision the request got
+        decision: String,
+    },
+}
+
+impl Anomaly {
+    /// Metric label for the kind of anomaly
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Anomaly::ForbidSpike { .. } => "forbid_spike",
+            Anomaly::NovelPair { .. } => "novel_pair",
+        }
+    }
+}
+
+/// Body POSTed to the webhook
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnomalyEvent {
+    /// When the anomaly was detected, in milliseconds since the Unix epoch
+    pub detected_at_ms: u64,
+    /// What was detected
+    #[serde(flatten)]
+    pub anomaly: Anomaly,
+}
+
+/// Forbid counts for one tenant
+#[derive(Debug, Default)]
+struct TenantWindow {
+    /// Forbids in the current window
+    forbids: u64,
+    /// Moving average of forbids per window; `None` before the first
+    /// window closes
+    baseline: Option<f64>,
+    /// Whether the current window already raised a spike
+    alerted: bool,
+}
+
+#[derive(Debug)]
+struct DetectorState {
+    window_start: Instant,
+    learning_until: Instant,
+    tenants: HashMap<String, TenantWindow>,
+    pairs: HashSet<(String, String)>,
+}
+
+/// Watches decisions for anomalies
+#[derive(Debug)]
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    state: Mutex<DetectorState>,
+    webhook: Option<mpsc::Sender<AnomalyEvent>>,
+    detected: AtomicU64,
+}
+
+impl AnomalyDetector {
+    /// Create a detector that logs and counts anomalies
+    pub fn new(config: AnomalyConfig) -> Self {
+        let now = Instant::now();
+        AnomalyDetector {
+            state: Mutex::new(DetectorState {
+                window_start: now,
+                learning_until: now + config.window,
+                tenants: HashMap::new(),
+                pairs: HashSet::new(),
+            }),
+            config,
+            webhook: None,
+            detected: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a detector and, if a webhook is configured, the task that
+    /// calls it
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(config: AnomalyConfig) -> Self {
+        let webhook = config.webhook.clone();
+        let mut detector = Self::new(config);
+        if let Some(url) = webhook {
+            let (sender, receiver) = mpsc::channel(WEBHOOK_QUEUE);
+            tokio::spawn(deliver(url, receiver));
+            detector.webhook = Some(sender);
+        }
+        detector
+    }
+
+    /// Detection settings
+    pub fn config(&self) -> &AnomalyConfig {
+        &self.config
+    }
+
+    /// Anomalies raised so far
+    pub fn detected(&self) -> u64 {
+        self.detected.load(Ordering::Relaxed)
+    }
+
+    /// Check a decision and report any anomalies it reveals
+    pub fn observe(&self, request: &Request, decision: Decision) -> Vec<Anomaly> {
+        let tenant = tenant(request, &self.config.tenant_attribute);
+        let principal = entity_key(&request.principal.entity);
+        let resource = entity_key(&request.resource.entity);
+        let anomalies = self.observe_at(Instant::now(), tenant, principal, resource, decision);
+        for anomaly in &anomalies {
+            self.report(anomaly);
+        }
+        anomalies
+    }
+
+    /// Update the counts with one decision made at `now`
+    fn observe_at(
+        &self,
+        now: Instant,
+        tenant: String,
+        principal: String,
+        resource: String,
+        decision: Decision,
+    ) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.roll_windows(&mut state, now);
+        let learning = now < state.learning_until;
+
+        if decision == Decision::Forbid {
+            let window = state.tenants.entry(tenant.clone()).or_default();
+            window.forbids += 1;
+            if let Some(baseline) = window.baseline {
+                let spiking = window.forbids >= self.config.min_forbids
+                    && window.forbids as f64 > self.config.spike_factor * baseline.max(1.0);
+                if spiking && !window.alerted && !learning {
+                    window.alerted = true;
+                    anomalies.push(Anomaly::ForbidSpike {
+                        tenant: tenant.clone(),
+                        forbids: window.forbids,
+                        baseline,
+                        window_secs: self.config.window.as_secs_f64(),
+                    });
+                }
+            }
+        }
+
+        if self.config.novel_pairs {
+            if state.pairs.len() >= self.config.max_pairs {
+                debug!(
+                    "Forgetting {} principal-resource pairs and learning again",
+                    state.pairs.len()
+                );
+                state.pairs.clear();
+                state.learning_until = now + self.config.window;
+            }
+            let learning = now < state.learning_until;
+            let pair = (principal, resource);
+            if !state.pairs.contains(&pair) {
+                if !learning {
+                    anomalies.push(Anomaly::NovelPair {
+                        tenant,
+                        principal: pair.0.clone(),
+                        resource: pair.1.clone(),
+                        decision: decision_name(decision).to_string(),
+                    });
+                }
+                state.pairs.insert(pair);
+            }
+        }
+        anomalies
+    }
+
+    /// Close every window that ended before `now`
+    fn roll_windows(&self, state: &mut DetectorState, now: Instant) {
+        while now.duration_since(state.window_start) >= self.config.window {
+            state.window_start += self.config.window;
+            state.tenants.retain(|_, window| {
+                let baseline = match window.baseline {
+                    Some(baseline) => {
+                        baseline + BASELINE_WEIGHT * (window.forbids as f64 - baseline)
+                    }
+                    None => window.forbids as f64,
+                };
+                window.baseline = Some(baseline);
+                window.forbids = 0;
+                window.alerted = false;
+                // Tenants that have gone quiet are forgotten
+                baseline >= 0.01
+            });
+            if state.tenants.is_empty()
+                && now.duration_since(state.window_start) >= self.config.window
+            {
+                // Nothing left to decay; skip the idle windows
+                let windows = now.duration_since(state.window_start).as_nanos()
+                    / self.config.window.as_nanos();
+                state.window_start += self.config.window * windows as u32;
+            }
+        }
+    }
+
+    /// Log, count and forward an anomaly
+    fn report(&self, anomaly: &Anomaly) {
+        self.detected.fetch_add(1, Ordering::Relaxed);
+        metrics::record_anomaly(anomaly.kind());
+        match anomaly {
+            Anomaly::ForbidSpike {
+                tenant,
+                forbids,
+                baseline,
+                ..
+            } => warn!(
+                "Anomaly: {} forbids for tenant '{}' this window (average {:.1})",
+                forbids, tenant, baseline
+            ),
+            Anomaly::NovelPair {
+                tenant,
+                principal,
+                resource,
+                decision,
+            } => warn!(
+                "Anomaly: {} asked about {} for the first time (tenant '{}', {})",
+                principal, resource, tenant, decision
+            ),
+        }
+
+        if let Some(webhook) = &self.webhook {
+            let event = AnomalyEvent {
+                detected_at_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+                anomaly: anomaly.clone(),
+            };
+            if webhook.try_send(event).is_err() {
+                warn!(
+                    "Anomaly webhook is falling behind; dropping {}",
+                    anomaly.kind()
+                );
+            }
+        }
+    }
+}
+
+/// POST events to the webhook until the detector is dropped
+async fn deliver(url: String, mut events: mpsc::Receiver<AnomalyEvent>) {
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    while let Some(event) = events.recv().await {
+        let sent = client
+            .post(&url)
+            .json(&event)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = sent {
+            warn!(
+                "Failed to deliver {} to anomaly webhook: {}",
+                event.anomaly.kind(),
+                e
+            );
+        }
+    }
+}
+
+/// Tenant a request belongs to: the principal attribute or, failing that,
+/// context value named `attribute`, or [`DEFAULT_TENANT`]
+pub fn tenant(request: &Request, attribute: &str) -> String {
+    let value = request
+        .principal
+        .entity
+        .attributes
+        .get(attribute)
+        .or_else(|| request.context.get(attribute));
+    match value {
+        Some(Value::String(tenant)) => tenant.to_string(),
+        Some(Value::Integer(tenant)) => tenant.to_string(),
+        _ => DEFAULT_TENANT.to_string(),
+    }
+}
+
+/// Entity as `type:id`
+fn entity_key(entity: &Entity) -> String {
+    format!("{}:{}", entity.entity_type, entity.id)
+}
+
+fn decision_name(decision: Decision) -> &'static str {
+    match decision {
+        Decision::Permit => "permit",
+        Decision::Deny => "deny",
+        Decision::Forbid => "forbid",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn detector(novel_pairs: bool) -> AnomalyDetector {
+        let mut config = AnomalyConfig::new(WINDOW);
+        config.min_forbids = 5;
+        config.novel_pairs = novel_pairs;
+        AnomalyDetector::new(config)
+    }
+
+    fn forbid(detector: &AnomalyDetector, now: Instant, tenant: &str) -> Vec<Anomaly> {
+        detector.observe_at(
+            now,
+            tenant.to_string(),
+            "user:mallory".to_string(),
+            "file:/etc/passwd".to_string(),
+            Decision::Forbid,
+        )
+    }
+
+    #[test]
+    fn test_forbid_spike_against_baseline() {
+        let detector = detector(false);
+        let start = detector.state.lock().unwrap().window_start;
+
+        // Learning window: two forbids per window is normal for acme
+        for _ in 0..2 {
+            assert!(forbid(&detector, start, "acme").is_empty());
+        }
+        let next = start + WINDOW;
+        for _ in 0..2 {
+            assert!(forbid(&detector, next, "acme").is_empty());
+        }
+
+        // Seven forbids is over three times the average and the minimum
+        let spike = start + WINDOW * 2;
+        let anomalies: Vec<_> = (0..8)
+            .flat_map(|_| forbid(&detector, spike, "acme"))
+            .collect();
+        assert_eq!(
+            anomalies,
+            [Anomaly::ForbidSpike {
+                tenant: "acme".to_string(),
+                forbids: 7,
+                baseline: 2.0,
+                window_secs: 60.0,
+            }]
+        );
+
+        // Other tenants keep their own baselines
+        assert!(forbid(&detector, spike, "globex").is_empty());
+    }
+
+    #[test]
+    fn test_novel_pairs_after_learning() {
+        let detector = detector(true);
+        let start = detector.state.lock().unwrap().window_start;
+        let observe = |now, principal: &str, resource: &str| {
+            detector.observe_at(
+                now,
+                DEFAULT_TENANT.to_string(),
+                principal.to_string(),
+                resource.to_string(),
+                Decision::Permit,
+            )
+        };
+
+        assert!(observe(start, "user:alice", "doc:1").is_empty());
+        let later = start + WINDOW;
+        assert!(observe(later, "user:alice", "doc:1").is_empty());
+        assert_eq!(
+            observe(later, "user:alice", "doc:2"),
+            [Anomaly::NovelPair {
+                tenant: DEFAULT_TENANT.to_string(),
+                principal: "user:alice".to_string(),
+                resource: "doc:2".to_string(),
+                decision: "permit".to_string(),
+            }]
+        );
+        assert!(observe(later, "user:alice", "doc:2").is_empty());
+    }
+
+    #[test]
+    fn test_pair_limit_starts_learning_again() {
+        let mut config = AnomalyConfig::new(WINDOW);
+        config.max_pairs = 2;
+        let detector = AnomalyDetector::new(config);
+        let later = detector.state.lock().unwrap().window_start + WINDOW;
+        let observe = |resource: &str| {
+            detector.observe_at(
+                later,
+                DEFAULT_TENANT.to_string(),
+                "user:alice".to_string(),
+                resource.to_string(),
+                Decision::Deny,
+            )
+        };
+
+        assert_eq!(observe("doc:1").len(), 1);
+        assert_eq!(observe("doc:2").len(), 1);
+        // The third pair hits the limit: forget and learn for a window
+        assert!(observe("doc:3").is_empty());
+        assert!(observe("doc:4").is_empty());
+    }
+}
@@ -1,7 +1,7 @@
 //! API request and response types
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Authorization request
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -10,6 +10,16 @@ pub struct AuthorizeRequest {
     /// Principal making the request (e.g., "user:alice", "role:admin")
     pub principal: String,
 
+    /// Attributes of the principal, including those its kind requires
+    /// (e.g., `platform` for a "Device:..." principal)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub principal_attributes: BTreeMap<String, rune_core::Value>,
+
+    /// The same attributes as supplied by other sources (e.g., JWT claims,
+    /// a SCIM sync), merged per the configuration's `[attributes]` section
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attribute_sources: Vec<rune_core::AttributeSource>,
+
     /// Action being performed (e.g., "read", "write", "delete")
     pub action: String,
 
@@ -19,6 +29,16 @@ pub struct AuthorizeRequest {
     /// Additional context for the request
     #[serde(default)]
     pub context: HashMap<String, serde_json::Value>,
+
+    /// Oldest replica data, in milliseconds, this request accepts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_staleness_ms: Option<u64>,
+
+    /// Return how the decision was derived, in `proofs`, or for a denial
+    /// which rules almost matched, in `near_misses`; explained requests
+    /// bypass the decision cache
+    #[serde(default)]
+    pub explain: bool,
 }
 
 /// Authorization response
@@ -28,13 +48,98 @@ pub struct AuthorizeResponse {
     /// Authorization decision
     pub decision: Decision,
 
-    /// Reasons for the decision
+    /// Reasons for the decision, in the language given by `locale`
     #[serde(default)]
     pub reasons: Vec<String>,
 
+    /// Stable codes behind the reasons, each described in `locale`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reason_codes: Vec<ReasonDescription>,
+
+    /// Language the reasons are written in, negotiated from the request's
+    /// `Accept-Language` header (e.g., "fr")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
+    /// Warnings of the permitting policies, e.g. a soft limit being
+    /// approached; never present on a denial
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<rune_core::Warning>,
+
+    /// How long, in milliseconds, the caller may reuse this decision without
+    /// asking again; 0 means ask every time
+    #[serde(default)]
+    pub valid_for_ms: u64,
+
     /// Diagnostic information (only in debug mode)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub diagnostics: Option<Diagnostics>,
+
+    /// Proof tree of every fact the Datalog rules derived (only when the
+    /// request set `explain`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proofs: Vec<rune_core::ProofNode>,
+
+    /// Rules that almost matched and the condition each failed on, closest
+    /// first (only on a denial, when the request set `explain`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub near_misses: Vec<rune_core::NearMiss>,
+
+    /// Set when the server left out detail it would normally give, e.g.
+    /// proofs while under load (see [`crate::detail`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Detail>,
+
+    /// Decision signature (only when the server has a signing key)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ResponseSignature>,
+}
+
+/// How much of a decision's explanation a response carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    /// Proofs, near misses and diagnostics' rule lists were left out
+    Reduced,
+}
+
+/// A reason code with its localized description
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReasonDescription {
+    /// Stable code (e.g., "no_matching_permit")
+    pub code: rune_core::ReasonCode,
+
+    /// Values the description was filled in with
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+
+    /// Human-readable description
+    pub description: String,
+}
+
+/// HMAC signature binding a decision to its request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseSignature {
+    /// MAC algorithm (`HMAC-SHA256`)
+    pub algorithm: String,
+
+    /// Identifier of the signing key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+
+    /// Engine configuration generation that produced the decision
+    pub generation: u64,
+
+    /// Signing time in milliseconds since the Unix epoch
+    pub timestamp: u64,
+
+    /// Hex SHA-256 of the canonical request
+    pub request_hash: String,
+
+    /// Hex-encoded MAC
+    pub signature: String,
 }
 
 /// Authorization decision
@@ -90,6 +195,210 @@ pub struct BatchAuthorizeResponse {
     pub results: Vec<AuthorizeResponse>,
 }
 
+/// Request to warm the decision cache for a principal about to act
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchRequest {
+    /// Principal about to act (e.g., "user:alice")
+    pub principal: String,
+
+    /// Attributes of the principal, as for [`AuthorizeRequest`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub principal_attributes: BTreeMap<String, rune_core::Value>,
+
+    /// Actions the principal is likely to take
+    pub actions: Vec<String>,
+
+    /// Resources the principal is likely to act on; each action is
+    /// prefetched for each resource
+    pub resources: Vec<String>,
+}
+
+/// Accepted prefetch request
+///
+/// Decisions are evaluated in the background and never returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchResponse {
+    /// Action and resource pairs queued for evaluation
+    pub queued: usize,
+}
+
+/// Request for the decisions of several actions on one resource, e.g. to
+/// enable the buttons of a UI
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionsRequest {
+    /// Principal acting (e.g., "user:alice")
+    pub principal: String,
+
+    /// Attributes of the principal, as for [`AuthorizeRequest`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub principal_attributes: BTreeMap<String, rune_core::Value>,
+
+    /// Candidate actions (e.g., ["read", "write", "delete"])
+    pub actions: Vec<String>,
+
+    /// Resource acted on (e.g., "file:/tmp/data.txt")
+    pub resource: String,
+
+    /// Additional context, as for [`AuthorizeRequest`]
+    #[serde(default)]
+    pub context: HashMap<String, serde_json::Value>,
+}
+
+/// Decision of each action in an [`ActionsRequest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionsResponse {
+    /// Decision by action
+    pub decisions: BTreeMap<String, Decision>,
+
+    /// Actions answered from the decision cache
+    pub cached: usize,
+}
+
+/// Request for the resources a principal may act on
+///
+/// Candidates come from `resources`, from the facts matching `query`, or
+/// from both.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermittedResourcesRequest {
+    /// Principal acting (e.g., "user:alice")
+    pub principal: String,
+
+    /// Attributes of the principal, as for [`AuthorizeRequest`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub principal_attributes: BTreeMap<String, rune_core::Value>,
+
+    /// Action to check (e.g., "read")
+    pub action: String,
+
+    /// Candidate resources (e.g., "file:/tmp/data.txt")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<String>,
+
+    /// Goal with one variable, e.g. `shared_with("alice", R)`; every
+    /// string bound to the variable is a candidate resource
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+
+    /// Additional context, as for [`AuthorizeRequest`]
+    #[serde(default)]
+    pub context: HashMap<String, serde_json::Value>,
+}
+
+/// Candidates the principal may act on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermittedResourcesResponse {
+    /// Permitted resources, in candidate order
+    pub resources: Vec<String>,
+    /// Candidates checked
+    pub candidates: usize,
+    /// Candidates answered from the decision cache
+    pub cached: usize,
+}
+
+/// Request for the principals that may perform an action on a resource
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermittedPrincipalsRequest {
+    /// Action to check (e.g., "delete")
+    pub action: String,
+
+    /// Resource acted on (e.g., "Bucket:logs")
+    pub resource: String,
+
+    /// Candidate principals (e.g., "User:alice")
+    pub principals: Vec<String>,
+}
+
+/// Candidates that may act on the resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermittedPrincipalsResponse {
+    /// Permitted principals, in candidate order
+    pub principals: Vec<String>,
+    /// Candidates checked
+    pub candidates: usize,
+}
+
+/// Request for the decisions of every principal, action and resource
+/// combination, e.g. to render a permission grid
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatrixRequest {
+    /// Principals, one per matrix row (e.g., "user:alice")
+    pub principals: Vec<String>,
+
+    /// Actions checked for each principal
+    pub actions: Vec<String>,
+
+    /// Resources checked for each principal and action
+    pub resources: Vec<String>,
+
+    /// Context shared by every combination, as for [`AuthorizeRequest`]
+    #[serde(default)]
+    pub context: HashMap<String, serde_json::Value>,
+}
+
+/// Decisions of every combination in a [`MatrixRequest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatrixResponse {
+    /// `decisions[p][a][r]` decides principal `p` taking action `a` on
+    /// resource `r`, indexed as in the request; combinations that could not
+    /// be evaluated are `FORBID`
+    pub decisions: Vec<Vec<Vec<Decision>>>,
+
+    /// Combinations answered from the decision cache
+    pub cached: usize,
+}
+
+/// Server version and the provenance of the active configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionResponse {
+    /// Server version
+    pub version: String,
+
+    /// Configuration generation, as in signed responses
+    pub generation: u64,
+
+    /// Artifact the active rules and policies were loaded from; absent when
+    /// nothing was loaded from a RUNE file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<ArtifactInfo>,
+}
+
+/// Where a policy artifact came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactInfo {
+    /// `sha256:` digest of the artifact
+    pub digest: String,
+
+    /// Commit the artifact was built from, if stamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_sha: Option<String>,
+
+    /// Who signed the artifact, if stamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+}
+
+impl From<&rune_core::PolicyArtifact> for ArtifactInfo {
+    fn from(artifact: &rune_core::PolicyArtifact) -> Self {
+        ArtifactInfo {
+            digest: artifact.digest.clone(),
+            git_sha: artifact.git_sha.clone(),
+            signer: artifact.signer.clone(),
+        }
+    }
+}
+
 /// Health check response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -122,6 +431,527 @@ pub enum HealthStatus {
     Unhealthy,
 }
 
+/// Rule flag (kill switch) state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleFlag {
+    /// Flag key (`@flag` or `@id` annotation)
+    pub key: String,
+
+    /// Whether the flagged rules and policies are evaluated
+    pub enabled: bool,
+
+    /// Whether the state was set at runtime rather than in the config
+    pub overridden: bool,
+
+    /// Number of Datalog rules controlled by the flag
+    pub rules: usize,
+
+    /// Number of Cedar policies controlled by the flag
+    pub policies: usize,
+}
+
+/// Rule flag listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleFlagsResponse {
+    /// All known flags
+    pub flags: Vec<RuleFlag>,
+}
+
+/// Request to switch a rule flag on or off
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRuleFlagRequest {
+    /// New state for the flag
+    pub enabled: bool,
+}
+
+/// Request to open a principal session
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenSessionRequest {
+    /// Principal logging in (e.g., "user:alice")
+    pub principal: String,
+
+    /// Facts to install; each gets the principal ID as its first argument
+    #[serde(default)]
+    pub attributes: Vec<rune_core::SessionAttribute>,
+
+    /// Seconds until the session expires; omitted, it lasts until closed
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+}
+
+/// An open principal session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    /// Session ID, used to close it
+    pub id: String,
+
+    /// ID of the principal the session belongs to
+    pub principal: String,
+
+    /// Facts the session installed
+    pub facts: Vec<rune_core::Fact>,
+
+    /// Seconds until the session expires
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in_secs: Option<f64>,
+}
+
+/// Open session listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionsResponse {
+    /// All open sessions
+    pub sessions: Vec<SessionResponse>,
+}
+
+/// Request for access a principal was refused
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenAccessRequest {
+    /// Principal asking (e.g., "user:alice")
+    pub principal: String,
+
+    /// Action it needs (e.g., "read")
+    pub action: String,
+
+    /// Resource it needs (e.g., "file:/data/report.txt")
+    pub resource: String,
+
+    /// Why it needs the access
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+}
+
+/// Approval or denial of an access request
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewAccessRequest {
+    /// The reviewer's note
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Query parameters for access request listings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessRequestParams {
+    /// Only requests with this status: pending, approved or denied
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+/// An access request and where it stands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessRequestResponse {
+    /// Request ID
+    pub id: u64,
+
+    /// Principal asking, as `type:id`
+    pub principal: String,
+
+    /// Action asked for
+    pub action: String,
+
+    /// Resource asked for, as `type:id`
+    pub resource: String,
+
+    /// Why the principal needs the access
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+
+    /// pending, approved or denied
+    pub status: rune_core::AccessRequestStatus,
+
+    /// When the request was made, in milliseconds since the Unix epoch
+    pub requested_at_ms: u64,
+
+    /// When it was reviewed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reviewed_at_ms: Option<u64>,
+
+    /// Who reviewed it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<String>,
+
+    /// The reviewer's note
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Access request listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessRequestsResponse {
+    /// Matching requests, oldest first
+    pub requests: Vec<AccessRequestResponse>,
+}
+
+/// Query parameters for derived fact listings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FactQueryParams {
+    /// Only return facts with this predicate
+    #[serde(default)]
+    pub predicate: Option<String>,
+    /// Number of matching facts to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// Maximum number of facts to return
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Return only the number of matching facts
+    #[serde(default)]
+    pub count: bool,
+}
+
+/// Query parameters for configuration exports
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ExportParams {
+    /// Output format: `json` (default), `csv` or `rune`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// Query parameters for fact snapshots
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SnapshotParams {
+    /// Snapshot format: `binary` (default) or `json`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// Result of loading a fact snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotImportResponse {
+    /// Facts loaded, replacing every fact held before
+    pub facts_loaded: usize,
+}
+
+/// Query parameters for label listings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LabelsParams {
+    /// Label selector such as `owner=payments,ticket`; everything labelled
+    /// when absent
+    #[serde(default)]
+    pub selector: Option<String>,
+}
+
+/// Labelled facts, rules and policies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelsResponse {
+    /// Matching items, ordered by kind and then ID
+    pub items: Vec<rune_core::Labeled>,
+}
+
+/// Query parameters for the replication change feed
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChangesParams {
+    /// Change log the replica is following
+    #[serde(default)]
+    pub log: Option<u64>,
+    /// Last change the replica applied; with `log` unset the full fact set is sent
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Most changes to return (default 1000, at most 10000)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Query parameters for fact history listings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FactHistoryParams {
+    /// Only changes to facts with this predicate
+    #[serde(default)]
+    pub predicate: Option<String>,
+    /// Only changes made at or after this time, in milliseconds since the
+    /// Unix epoch
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Only changes made at or before this time, in milliseconds since the
+    /// Unix epoch
+    #[serde(default)]
+    pub until: Option<u64>,
+    /// Most changes to return, the latest ones (default 1000, at most 10000)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Recorded fact changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FactHistoryResponse {
+    /// Matching changes, oldest first
+    pub events: Vec<rune_core::FactEvent>,
+}
+
+/// Query parameters for fact store reconstructions
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FactsAsOfParams {
+    /// Time to reconstruct the store at, in milliseconds since the Unix
+    /// epoch
+    pub at: u64,
+    /// Only return facts with this predicate
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+/// Base facts as they were at a past time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FactsAsOfResponse {
+    /// Time the store was reconstructed at
+    pub at_ms: u64,
+    /// Facts added by then and still valid, ordered by predicate and
+    /// arguments
+    pub facts: Vec<rune_core::Fact>,
+}
+
+/// Query parameters for decision watches
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchParams {
+    /// Principal, as `Type:id`; a bare id is a user
+    pub principal: String,
+    /// Action
+    pub action: String,
+    /// Resource, as `Type:id`
+    pub resource: String,
+    /// Decision the caller holds; the watch returns once the decision
+    /// differs from it, and at once when unset
+    #[serde(default)]
+    pub decision: Option<Decision>,
+    /// Longest wait for a change, in milliseconds (default 30000, at most
+    /// 300000)
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// Current decision of a watched request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchResponse {
+    /// The decision
+    pub decision: Decision,
+    /// Whether it differs from the decision the caller held
+    pub changed: bool,
+    /// Configuration generation it was made with
+    pub generation: u64,
+    /// Fact store version it was made with
+    pub fact_version: u64,
+}
+
+/// Count-only response for derived fact listings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FactCountResponse {
+    /// Number of facts the query would return
+    pub count: usize,
+}
+
+/// Goal to answer from base and derived facts
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    /// One atom, e.g. `can_access("alice", R)`; variables match anything
+    pub goal: String,
+}
+
+/// Facts answering a goal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResponse {
+    /// Matching facts, in argument order
+    pub facts: Vec<rune_core::Fact>,
+    /// Number of matching facts
+    pub count: usize,
+    /// Whether the answer came from the query cache
+    pub cached: bool,
+}
+
+/// Result of loading a new RUNE file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadResponse {
+    /// Version the file declares
+    pub version: String,
+    /// Rules loaded
+    pub rules: usize,
+    /// Policies loaded
+    pub policies: usize,
+    /// Likely mistakes that did not stop the file from loading
+    pub warnings: rune_core::DiagnosticBag,
+}
+
+/// Result of staging a RUNE file for shadow evaluation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageResponse {
+    /// The staged configuration; its id goes in `X-Rune-Shadow-Policy`
+    pub staged: rune_core::StagedConfig,
+    /// Likely mistakes that did not stop the file from staging
+    pub warnings: rune_core::DiagnosticBag,
+}
+
+/// Configurations staged for shadow evaluation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StagedConfigsResponse {
+    /// Staged configurations, oldest first
+    pub staged: Vec<rune_core::StagedConfig>,
+}
+
+/// Recently installed configurations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionsResponse {
+    /// Version the active configuration was installed as, if it was
+    pub current: Option<u64>,
+    /// Installed configurations, oldest first
+    pub versions: Vec<rune_core::ConfigVersion>,
+}
+
+/// Staged configuration to shadow all traffic with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowModeRequest {
+    /// Id of the staged configuration
+    pub staged: u64,
+}
+
+/// Policy text, and optionally rules, to check without loading
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatePoliciesRequest {
+    /// Cedar policies, as they would appear in a `[policies]` section
+    pub policies: String,
+
+    /// Datalog rules, as they would appear in a `[rules]` section
+    #[serde(default)]
+    pub rules: Option<String>,
+}
+
+/// Problems found in posted policies and rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatePoliciesResponse {
+    /// Whether a reload with these policies and rules would succeed
+    pub valid: bool,
+    /// Policies found
+    pub policies: usize,
+    /// Rules found
+    pub rules: usize,
+    /// Problems with the policies, with spans into `policies`
+    pub policy_diagnostics: rune_core::DiagnosticBag,
+    /// Problems with the rules, with spans into `rules`
+    pub rule_diagnostics: rune_core::DiagnosticBag,
+}
+
+impl From<rune_core::SourceCheck> for ValidatePoliciesResponse {
+    fn from(check: rune_core::SourceCheck) -> Self {
+        ValidatePoliciesResponse {
+            valid: check.is_valid(),
+            policies: check.policies,
+            rules: check.rules,
+            policy_diagnostics: check.policy_diagnostics,
+            rule_diagnostics: check.rule_diagnostics,
+        }
+    }
+}
+
+/// What a principal is and which checks and policies concern it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionSummaryResponse {
+    /// Principal as requested (e.g., "user:alice")
+    pub principal: String,
+    /// Roles held directly or through a group
+    pub roles: Vec<String>,
+    /// Groups the principal belongs to, directly or transitively
+    pub groups: Vec<String>,
+    /// Rule-derived facts naming the principal
+    pub checks: Vec<rune_core::Fact>,
+    /// Permit policies scoped to the principal or its groups
+    pub permits: Vec<String>,
+    /// Forbid policies scoped to the principal or its groups
+    pub forbids: Vec<String>,
+}
+
+/// Result of a fact store compaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionResponse {
+    /// Stored entries before compaction
+    pub facts_before: usize,
+    /// Stored entries after compaction
+    pub facts_after: usize,
+    /// Duplicate entries merged
+    pub duplicates_removed: usize,
+    /// Expired facts dropped
+    pub expired_removed: usize,
+    /// Empty predicate index entries dropped
+    pub predicates_removed: usize,
+    /// Time spent compacting
+    pub duration_ms: f64,
+}
+
+impl From<FactQueryParams> for rune_core::FactQuery {
+    fn from(params: FactQueryParams) -> Self {
+        rune_core::FactQuery {
+            predicate: params.predicate,
+            offset: params.offset,
+            limit: params.limit,
+        }
+    }
+}
+
+impl From<rune_core::FlagStatus> for RuleFlag {
+    fn from(status: rune_core::FlagStatus) -> Self {
+        RuleFlag {
+            key: status.key,
+            enabled: status.enabled,
+            overridden: status.overridden,
+            rules: status.rules,
+            policies: status.policies,
+        }
+    }
+}
+
+impl From<rune_core::SessionInfo> for SessionResponse {
+    fn from(session: rune_core::SessionInfo) -> Self {
+        SessionResponse {
+            id: session.id,
+            principal: session.principal,
+            facts: session.facts,
+            expires_in_secs: session.expires_in.map(|left| left.as_secs_f64()),
+        }
+    }
+}
+
+impl From<rune_core::AccessRequest> for AccessRequestResponse {
+    fn from(access: rune_core::AccessRequest) -> Self {
+        AccessRequestResponse {
+            id: access.id,
+            principal: format!("{}:{}", access.principal_type, access.principal),
+            action: access.action,
+            resource: format!("{}:{}", access.resource_type, access.resource),
+            justification: access.justification,
+            status: access.status,
+            requested_at_ms: access.requested_at_ms,
+            reviewed_at_ms: access.reviewed_at_ms,
+            reviewer: access.reviewer,
+            note: access.note,
+        }
+    }
+}
+
 impl From<rune_core::Decision> for Decision {
     fn from(decision: rune_core::Decision) -> Self {
         match decision {
@@ -0,0 +1,236 @@
+//! Request and response body encodings
+//!
+//! The authorize endpoints accept CBOR and MessagePack as well as JSON.
+//! High-volume internal clients send `Content-Type: application/cbor` or
+//! `application/msgpack` (or the older `application/x-msgpack`) and skip the
+//! cost of printing and parsing JSON text on both sides; the response comes
+//! back in the encoding the request used. Anything else is handled exactly
+//! as the plain [`Json`] extractor would, rejections included.
+
+use crate::error::ApiError;
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Media type of CBOR bodies
+pub const CBOR: &str = "application/cbor";
+
+/// Media type of MessagePack bodies
+pub const MSGPACK: &str = "application/msgpack";
+
+/// Older media type of MessagePack bodies, still sent by many clients
+pub const X_MSGPACK: &str = "application/x-msgpack";
+
+/// Encoding of a request or response body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// `application/json`
+    #[default]
+    Json,
+    /// `application/cbor` (RFC 8949)
+    Cbor,
+    /// `application/msgpack`, answered as such whichever of its media types
+    /// the request used
+    MsgPack,
+}
+
+impl Format {
+    /// Encoding named by the request's `Content-Type`, JSON unless it is CBOR
+    /// or MessagePack
+    pub fn of(headers: &HeaderMap) -> Self {
+        let essence = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim);
+        match essence {
+            Some(media) if media.eq_ignore_ascii_case(CBOR) => Format::Cbor,
+            Some(media) if media.eq_ignore_ascii_case(MSGPACK) => Format::MsgPack,
+            Some(media) if media.eq_ignore_ascii_case(X_MSGPACK) => Format::MsgPack,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// Body in either encoding, remembering which one it came in
+///
+/// As an extractor it decodes the request body; as a response it encodes
+/// the value in the given format, so handlers answer in kind:
+///
+/// ```ignore
+/// async fn handler(Encoded(format, req): Encoded<Request>) -> Encoded<Response> {
+///     Encoded(format, respond(req))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Encoded<T>(pub Format, pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Encoded<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Format::of(req.headers()) {
+            Format::Json => Json::<T>::from_request(req, state)
+                .await
+                .map(|Json(value)| Encoded(Format::Json, value))
+                .map_err(IntoResponse::into_response),
+            Format::Cbor => {
+                let bytes = Bytes::from_request(req, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                ciborium::from_reader(bytes.as_ref())
+                    .map(|value| Encoded(Format::Cbor, value))
+                    .map_err(|e| {
+                        ApiError::BadRequest(format!("Invalid CBOR: {}", e)).into_response()
+                    })
+            }
+            Format::MsgPack => {
+                let bytes = Bytes::from_request(req, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                rmp_serde::from_slice(bytes.as_ref())
+                    .map(|value| Encoded(Format::MsgPack, value))
+                    .map_err(|e| {
+                        ApiError::BadRequest(format!("Invalid MessagePack: {}", e)).into_response()
+                    })
+            }
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Encoded<T> {
+    fn into_response(self) -> Response {
+        match self.0 {
+            Format::Json => Json(self.1).into_response(),
+            Format::Cbor => {
+                let mut body = Vec::new();
+                match ciborium::into_writer(&self.1, &mut body) {
+                    Ok(()) => (
+                        [(header::CONTENT_TYPE, HeaderValue::from_static(CBOR))],
+                        body,
+                    )
+                        .into_response(),
+                    Err(e) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Failed to encode CBOR: {}", e),
+                    )
+                        .into_response(),
+                }
+            }
+            // Maps keep their field names, as in JSON and CBOR
+            Format::MsgPack => match rmp_serde::to_vec_named(&self.1) {
+                Ok(body) => (
+                    [(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK))],
+                    body,
+                )
+                    .into_response(),
+                Err(e) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to encode MessagePack: {}", e),
+                )
+                    .into_response(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        principal: String,
+        attributes: serde_json::Value,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            principal: "alice".to_string(),
+            attributes: serde_json::json!({"level": 3, "teams": ["eng"]}),
+        }
+    }
+
+    #[test]
+    fn test_format_of_content_type() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Format::of(&headers), Format::Json);
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("Application/CBOR; charset=binary"),
+        );
+        assert_eq!(Format::of(&headers), Format::Cbor);
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(X_MSGPACK));
+        assert_eq!(Format::of(&headers), Format::MsgPack);
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        assert_eq!(Format::of(&headers), Format::Json);
+    }
+
+    #[tokio::test]
+    async fn test_cbor_round_trip() {
+        let mut body = Vec::new();
+        ciborium::into_writer(&sample(), &mut body).unwrap();
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, CBOR)
+            .body(Body::from(body))
+            .unwrap();
+        let Encoded(format, decoded) = Encoded::<Sample>::from_request(request, &()).await.unwrap();
+        assert_eq!(format, Format::Cbor);
+        assert_eq!(decoded, sample());
+
+        let response = Encoded(format, decoded).into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], CBOR);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let echoed: Sample = ciborium::from_reader(bytes.as_ref()).unwrap();
+        assert_eq!(echoed, sample());
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_round_trip() {
+        for media in [MSGPACK, X_MSGPACK] {
+            let body = rmp_serde::to_vec_named(&sample()).unwrap();
+            let request = Request::builder()
+                .header(header::CONTENT_TYPE, media)
+                .body(Body::from(body))
+                .unwrap();
+            let Encoded(format, decoded) =
+                Encoded::<Sample>::from_request(request, &()).await.unwrap();
+            assert_eq!(format, Format::MsgPack);
+            assert_eq!(decoded, sample());
+
+            let response = Encoded(format, decoded).into_response();
+            assert_eq!(response.headers()[header::CONTENT_TYPE], MSGPACK);
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let echoed: Sample = rmp_serde::from_slice(&bytes).unwrap();
+            assert_eq!(echoed, sample());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_cbor_is_rejected() {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, CBOR)
+            .body(Body::from(vec![0xff, 0x00]))
+            .unwrap();
+        let response = Encoded::<Sample>::from_request(request, &())
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
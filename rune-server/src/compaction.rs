@@ -0,0 +1,182 @@
+//! Background fact store compaction
+//!
+//! Facts that are re-asserted pile up as duplicate entries, facts added with
+//! a time-to-live linger until something removes them, and every append
+//! leaves spare capacity behind. [`spawn`] runs [`RUNEEngine::compact_facts`]
+//! periodically, but only when the previous interval was quiet: compaction
+//! rewrites the fact vectors, which competes with authorization requests for
+//! CPU and memory bandwidth. A busy server still compacts after
+//! `max_deferrals` postponed runs so the store cannot grow without bound.
+//!
+//! Operators can also trigger a pass through `POST /v1/admin/compact`.
+
+use crate::metrics;
+use rune_core::{CompactionStats, RUNEEngine};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Scheduling settings for background compaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionConfig {
+    /// Time between compaction checks; zero disables the task
+    pub interval: Duration,
+    /// Most authorization requests per interval that still count as idle
+    pub idle_requests: u64,
+    /// Consecutive postponed runs after which compaction runs regardless
+    pub max_deferrals: u32,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        CompactionConfig {
+            interval: Duration::from_secs(300),
+            idle_requests: 1_000,
+            max_deferrals: 12,
+        }
+    }
+}
+
+impl CompactionConfig {
+    /// Read settings from `RUNE_COMPACTION_INTERVAL_SECS`,
+    /// `RUNE_COMPACTION_IDLE_REQUESTS` and `RUNE_COMPACTION_MAX_DEFERRALS`,
+    /// falling back to the defaults
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.parse().ok()
+        }
+
+        let defaults = Self::default();
+        CompactionConfig {
+            interval: var("RUNE_COMPACTION_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.interval),
+            idle_requests: var("RUNE_COMPACTION_IDLE_REQUESTS").unwrap_or(defaults.idle_requests),
+            max_deferrals: var("RUNE_COMPACTION_MAX_DEFERRALS").unwrap_or(defaults.max_deferrals),
+        }
+    }
+
+    /// Check whether the background task should run at all
+    pub fn is_enabled(&self) -> bool {
+        !self.interval.is_zero()
+    }
+}
+
+/// Decides, once per interval, whether traffic is low enough to compact
+#[derive(Debug)]
+pub struct CompactionScheduler {
+    config: CompactionConfig,
+    last_total: u64,
+    deferrals: u32,
+}
+
+impl CompactionScheduler {
+    /// Create a scheduler starting from the current request total
+    pub fn new(config: CompactionConfig, total_requests: u64) -> Self {
+        CompactionScheduler {
+            config,
+            last_total: total_requests,
+            deferrals: 0,
+        }
+    }
+
+    /// Advance one interval given the engine's running request total
+    ///
+    /// Returns true when compaction should run now.
+    pub fn tick(&mut self, total_requests: u64) -> bool {
+        let requests = total_requests.saturating_sub(self.last_total);
+        self.last_total = total_requests;
+
+        if requests <= self.config.idle_requests || self.deferrals >= self.config.max_deferrals {
+            self.deferrals = 0;
+            true
+        } else {
+            self.deferrals += 1;
+            false
+        }
+    }
+}
+
+/// Compact the engine's fact store and record the outcome
+///
+/// `trigger` labels the metrics (`scheduled` or `manual`). Runs on the
+/// calling thread; async callers should use `spawn_blocking`.
+pub fn compact(engine: &RUNEEngine, trigger: &str) -> (CompactionStats, Duration) {
+    let start = Instant::now();
+    let stats = engine.compact_facts();
+    let elapsed = start.elapsed();
+
+    metrics::record_compaction(trigger, &stats, elapsed.as_secs_f64());
+    info!(
+        "Fact store compacted ({}): {} -> {} entries, {} duplicates, {} expired in {:?}",
+        trigger,
+        stats.facts_before,
+        stats.facts_after,
+        stats.duplicates_removed,
+        stats.expired_removed,
+        elapsed
+    );
+    (stats, elapsed)
+}
+
+/// Start the periodic compaction task
+pub fn spawn(engine: Arc<RUNEEngine>, config: CompactionConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let metrics = engine.metrics();
+        let mut scheduler =
+            CompactionScheduler::new(config.clone(), metrics.total_authorizations());
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick completes immediately; start measuring from there
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            if !scheduler.tick(metrics.total_authorizations()) {
+                debug!("Fact store compaction deferred: traffic above idle threshold");
+                metrics::record_compaction_deferred();
+                continue;
+            }
+
+            let engine = engine.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || compact(&engine, "scheduled")).await
+            {
+                warn!("Fact store compaction failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduler_waits_for_quiet_interval() {
+        let config = CompactionConfig {
+            interval: Duration::from_secs(60),
+            idle_requests: 10,
+            max_deferrals: 2,
+        };
+        let mut scheduler = CompactionScheduler::new(config, 100);
+
+        assert!(scheduler.tick(105));
+        assert!(!scheduler.tick(200));
+        assert!(!scheduler.tick(300));
+        // Forced after two deferrals, then the count starts over
+        assert!(scheduler.tick(400));
+        assert!(!scheduler.tick(500));
+        assert!(scheduler.tick(501));
+    }
+
+    #[test]
+    fn test_zero_interval_disables_task() {
+        let config = CompactionConfig {
+            interval: Duration::ZERO,
+            ..CompactionConfig::default()
+        };
+        assert!(!config.is_enabled());
+        assert!(CompactionConfig::default().is_enabled());
+    }
+}
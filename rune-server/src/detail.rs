@@ -0,0 +1,64 @@
+//! Explanation detail under load
+//!
+//! Proof trees, near misses and the rule lists of debug diagnostics cost
+//! more to capture and encode than the decision they explain. With
+//! `RUNE_DETAIL_SHED_PENDING` set, a decision requested while at least that
+//! many evaluations are running on or queued for the engine's workers is
+//! answered without them: proofs are not captured, which also lets an
+//! explained request be answered from the decision cache, and the response
+//! carries `"detail": "reduced"` in place of its proofs, near misses and
+//! diagnostics' rule list. The decision is made exactly as it would be
+//! otherwise.
+//!
+//! Applies to `/v1/authorize` and `/v1/authorize/batch`. Reduced responses
+//! are counted in `rune_reduced_detail_responses_total`.
+
+use crate::api::{AuthorizeResponse, Detail};
+use crate::metrics;
+use rune_core::{RUNEEngine, Request};
+
+/// When to reduce the detail of responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailShedding {
+    /// Pending evaluations at which detail is reduced
+    pub pending: usize,
+}
+
+impl DetailShedding {
+    /// Reduce detail once `pending` evaluations are running or queued
+    pub fn new(pending: usize) -> Self {
+        DetailShedding { pending }
+    }
+
+    /// Read `RUNE_DETAIL_SHED_PENDING`; unset means detail is never reduced
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(value) = std::env::var("RUNE_DETAIL_SHED_PENDING") else {
+            return Ok(None);
+        };
+        let pending = value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_DETAIL_SHED_PENDING: {}", e))?;
+        Ok(Some(DetailShedding::new(pending)))
+    }
+
+    /// Whether `engine` is loaded enough for detail to be reduced now
+    pub fn applies(&self, engine: &RUNEEngine) -> bool {
+        engine.pending_evaluations() >= self.pending
+    }
+}
+
+/// Stop `request` from capturing proofs
+pub fn reduce_request(request: &mut Request) {
+    request.explain = false;
+}
+
+/// Drop the expensive detail from `response` and mark it reduced
+pub fn reduce_response(response: &mut AuthorizeResponse) {
+    response.proofs.clear();
+    response.near_misses.clear();
+    if let Some(diagnostics) = &mut response.diagnostics {
+        diagnostics.matched_rules.clear();
+    }
+    response.detail = Some(Detail::Reduced);
+    metrics::record_reduced_detail();
+}
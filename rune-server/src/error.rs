@@ -23,12 +23,21 @@ pub enum ApiError {
     /// Not found (404)
     NotFound(String),
 
+    /// Conflict with a request still in progress (409)
+    Conflict(String),
+
+    /// Well-formed but not acceptable in the current state (422)
+    Unprocessable(String),
+
     /// Internal server error (500)
     Internal(String),
 
     /// Service unavailable (503)
     ServiceUnavailable(String),
 
+    /// Route budget exceeded (504)
+    Timeout(crate::timeouts::TimingBreakdown),
+
     /// RUNE core error
     RuneError(rune_core::RUNEError),
 
@@ -46,6 +55,10 @@ struct ErrorResponse {
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     details: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<rune_core::DiagnosticBag>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<crate::timeouts::TimingBreakdown>,
 }
 
 impl fmt::Display for ApiError {
@@ -55,8 +68,15 @@ impl fmt::Display for ApiError {
             ApiError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             ApiError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             ApiError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            ApiError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            ApiError::Unprocessable(msg) => write!(f, "Unprocessable: {}", msg),
             ApiError::Internal(msg) => write!(f, "Internal error: {}", msg),
             ApiError::ServiceUnavailable(msg) => write!(f, "Service unavailable: {}", msg),
+            ApiError::Timeout(timing) => write!(
+                f,
+                "Timeout: {} request exceeded its {}ms budget",
+                timing.route, timing.budget_ms
+            ),
             ApiError::RuneError(e) => write!(f, "RUNE error: {}", e),
             ApiError::SerializationError(e) => write!(f, "Serialization error: {}", e),
         }
@@ -79,11 +99,17 @@ impl From<serde_json::Error> for ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let mut diagnostics = None;
+        let mut timing = None;
         let (status, error_type, message, details) = match self {
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "bad_request", msg, None),
             ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "unauthorized", msg, None),
             ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, "forbidden", msg, None),
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg, None),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg, None),
+            ApiError::Unprocessable(msg) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "unprocessable", msg, None)
+            }
             ApiError::Internal(msg) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "internal_error",
@@ -96,6 +122,39 @@ impl IntoResponse for ApiError {
                 msg,
                 None,
             ),
+            ApiError::Timeout(breakdown) => {
+                let msg = format!(
+                    "{} request exceeded its {}ms budget",
+                    breakdown.route, breakdown.budget_ms
+                );
+                timing = Some(breakdown);
+                (StatusCode::GATEWAY_TIMEOUT, "timeout", msg, None)
+            }
+            ApiError::RuneError(e @ rune_core::RUNEError::ReplicaStale { .. }) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "replica_stale",
+                e.to_string(),
+                None,
+            ),
+            ApiError::RuneError(e @ rune_core::RUNEError::Cancelled) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "cancelled",
+                e.to_string(),
+                None,
+            ),
+            ApiError::RuneError(e @ rune_core::RUNEError::AuthorizationDenied { .. }) => {
+                (StatusCode::FORBIDDEN, "forbidden", e.to_string(), None)
+            }
+            ApiError::RuneError(rune_core::RUNEError::DiagnosticError(bag)) => {
+                let msg = format!("Invalid configuration: {} error(s)", bag.error_count());
+                diagnostics = Some(bag);
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "invalid_config",
+                    msg,
+                    None,
+                )
+            }
             ApiError::RuneError(e) => {
                 let msg = format!("Authorization engine error: {}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "engine_error", msg, None)
@@ -110,6 +169,8 @@ impl IntoResponse for ApiError {
             error: error_type.to_string(),
             message,
             details,
+            diagnostics,
+            timing,
         });
 
         (status, body).into_response()
@@ -314,6 +375,8 @@ mod tests {
             error: "test_error".to_string(),
             message: "Test message".to_string(),
             details: None,
+            diagnostics: None,
+            timing: None,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -325,6 +388,8 @@ mod tests {
             error: "test_error".to_string(),
             message: "Test message".to_string(),
             details: Some("Additional details".to_string()),
+            diagnostics: None,
+            timing: None,
         };
 
         let json = serde_json::to_string(&response_with_details).unwrap();
@@ -332,6 +397,24 @@ mod tests {
         assert!(json.contains("Additional details"));
     }
 
+    #[tokio::test]
+    async fn test_api_error_into_response_diagnostics() {
+        let err = ApiError::RuneError(rune_core::RUNEError::from_diagnostic(
+            rune_core::Diagnostic::error("Unclosed `(`").with_code("E0101"),
+        ));
+        let response = err.into_response();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = response.into_body();
+        let bytes = to_bytes(body, usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(json["error"], "invalid_config");
+        assert_eq!(json["diagnostics"][0]["code"], "E0101");
+        assert_eq!(json["diagnostics"][0]["severity"], "error");
+    }
+
     #[test]
     fn test_api_result_type() {
         // Test that ApiResult type alias works correctly
@@ -0,0 +1,45 @@
+//! Background expiry of facts with a time-to-live
+//!
+//! The engine removes expired facts before any decision or query that finds
+//! one due, so they are never evaluated. Between decisions they would
+//! linger, still counted, replicated and exported, until the next
+//! compaction, which a busy server postpones. [`spawn`] sweeps them out
+//! every `RUNE_EXPIRY_SWEEP_MS` milliseconds (default 1000; 0 disables the
+//! task); a sweep with nothing due costs one atomic load.
+
+use crate::metrics;
+use rune_core::RUNEEngine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Time between sweeps unless `RUNE_EXPIRY_SWEEP_MS` says otherwise
+pub const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Read `RUNE_EXPIRY_SWEEP_MS`; zero disables the sweeper
+pub fn sweep_interval_from_env() -> anyhow::Result<Duration> {
+    match std::env::var("RUNE_EXPIRY_SWEEP_MS") {
+        Ok(ms) => ms
+            .parse()
+            .map(Duration::from_millis)
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_EXPIRY_SWEEP_MS: {}", e)),
+        Err(_) => Ok(DEFAULT_SWEEP_INTERVAL),
+    }
+}
+
+/// Start the periodic sweep
+pub fn spawn(engine: Arc<RUNEEngine>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let expired = engine.expire_facts();
+            if expired > 0 {
+                debug!("Removed {} expired facts", expired);
+                metrics::record_facts_expired(expired);
+            }
+        }
+    })
+}
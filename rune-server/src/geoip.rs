@@ -0,0 +1,533 @@
+//! GeoIP enrichment of authorization requests
+//!
+//! With `RUNE_GEOIP_DB` pointing at a MaxMind database (GeoIP2/GeoLite2
+//! City or Country), [`enrich`] looks up each data plane request's client
+//! address and the authorization handlers add what it found to the request
+//! context:
+//!
+//! - `geo_country`: ISO 3166-1 country code, e.g. `"DE"`
+//! - `geo_region`: ISO 3166-2 code of the largest subdivision, e.g. `"BY"`
+//!
+//! Attributes the database has no answer for are left out. The client
+//! address is the connection's peer address; behind a proxy, set
+//! `RUNE_GEOIP_TRUST_FORWARDED=true` to use the first `X-Forwarded-For` entry
+//! instead. Only enable that when every request passes through a proxy that
+//! overwrites the header, or clients can pick their own country.
+//!
+//! Lookups are cached per address. The database file is checked for changes
+//! every `RUNE_GEOIP_RELOAD_SECS` (default 300) and swapped in without a
+//! restart, so the usual weekly database updates need no redeploy.
+
+use anyhow::Context;
+use arc_swap::ArcSwap;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use dashmap::DashMap;
+use maxminddb::{geoip2, Reader};
+use rune_core::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::state::AppState;
+
+/// Header carrying the original client address behind a proxy
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// GeoIP settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeoIpConfig {
+    /// MaxMind database file
+    pub database: PathBuf,
+    /// Time between checks for a new database file; zero disables reloading
+    pub reload_interval: Duration,
+    /// Addresses whose lookups are cached
+    pub cache_size: usize,
+    /// Take the client address from `X-Forwarded-For`
+    pub trust_forwarded_for: bool,
+}
+
+impl GeoIpConfig {
+    /// Settings for `database` with the defaults
+    pub fn new(database: impl Into<PathBuf>) -> Self {
+        GeoIpConfig {
+            database: database.into(),
+            reload_interval: Duration::from_secs(300),
+            cache_size: 10_000,
+            trust_forwarded_for: false,
+        }
+    }
+
+    /// Read `RUNE_GEOIP_DB`, `RUNE_GEOIP_RELOAD_SECS`,
+    /// `RUNE_GEOIP_CACHE_SIZE` and `RUNE_GEOIP_TRUST_FORWARDED`; `None` when
+    /// no database is configured
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        fn var<T: std::str::FromStr>(name: &str) -> anyhow::Result<Option<T>>
+        where
+            T::Err: std::fmt::Display,
+        {
+            std::env::var(name)
+                .ok()
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        }
+
+        let Some(database) = std::env::var_os("RUNE_GEOIP_DB") else {
+            return Ok(None);
+        };
+        let mut config = Self::new(database);
+        if let Some(secs) = var("RUNE_GEOIP_RELOAD_SECS")? {
+            config.reload_interval = Duration::from_secs(secs);
+        }
+        if let Some(size) = var("RUNE_GEOIP_CACHE_SIZE")? {
+            config.cache_size = size;
+        }
+        if let Some(trust) = var("RUNE_GEOIP_TRUST_FORWARDED")? {
+            config.trust_forwarded_for = trust;
+        }
+        Ok(Some(config))
+    }
+}
+
+/// Where a client address is, as far as the database knows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoLocation {
+    /// ISO 3166-1 country code
+    pub country: Option<String>,
+    /// ISO 3166-2 subdivision code, without the country prefix
+    pub region: Option<String>,
+}
+
+impl GeoLocation {
+    /// Request context attributes for this location
+    pub fn context(&self) -> Vec<(&'static str, Value)> {
+        [("geo_country", &self.country), ("geo_region", &self.region)]
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, Value::string(value.as_deref()?))))
+            .collect()
+    }
+}
+
+/// A MaxMind database with a lookup cache
+pub struct GeoIp {
+    config: GeoIpConfig,
+    reader: ArcSwap<Reader<Vec<u8>>>,
+    /// Modification time of the loaded file
+    modified: Mutex<Option<SystemTime>>,
+    cache: DashMap<IpAddr, Option<GeoLocation>>,
+}
+
+impl GeoIp {
+    /// Load the configured database
+    pub fn open(config: GeoIpConfig) -> anyhow::Result<Self> {
+        let (reader, modified) = load(&config)?;
+        Ok(GeoIp {
+            config,
+            reader: ArcSwap::from_pointee(reader),
+            modified: Mutex::new(modified),
+            cache: DashMap::new(),
+        })
+    }
+
+    /// Settings the database was opened with
+    pub fn config(&self) -> &GeoIpConfig {
+        &self.config
+    }
+
+    /// Look up an address; `None` when the database has no record for it
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoLocation> {
+        if let Some(cached) = self.cache.get(&ip) {
+            return cached.clone();
+        }
+
+        let location = locate(&self.reader.load(), ip);
+        // A full cache starts over rather than tracking recency
+        if self.cache.len() >= self.config.cache_size {
+            self.cache.clear();
+        }
+        if self.config.cache_size > 0 {
+            self.cache.insert(ip, location.clone());
+        }
+        location
+    }
+
+    /// Load the database file again if it changed, returning whether it did
+    ///
+    /// A file that fails to load leaves the current database in place.
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let current = modified(&self.config)?;
+        let mut loaded = self.modified.lock().unwrap_or_else(PoisonError::into_inner);
+        if current == *loaded {
+            return Ok(false);
+        }
+
+        let (reader, modified) = load(&self.config)?;
+        self.reader.store(Arc::new(reader));
+        *loaded = modified;
+        self.cache.clear();
+        Ok(true)
+    }
+
+    /// Client address of a request
+    fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+        if self.config.trust_forwarded_for {
+            let forwarded = headers
+                .get(FORWARDED_FOR)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .and_then(|first| first.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+        peer.map(|addr| addr.ip())
+    }
+}
+
+fn modified(config: &GeoIpConfig) -> anyhow::Result<Option<SystemTime>> {
+    let metadata = std::fs::metadata(&config.database)
+        .with_context(|| format!("Failed to read {}", config.database.display()))?;
+    Ok(metadata.modified().ok())
+}
+
+fn load(config: &GeoIpConfig) -> anyhow::Result<(Reader<Vec<u8>>, Option<SystemTime>)> {
+    let modified = modified(config)?;
+    let reader = Reader::open_readfile(&config.database).with_context(|| {
+        format!(
+            "Failed to load GeoIP database {}",
+            config.database.display()
+        )
+    })?;
+    Ok((reader, modified))
+}
+
+fn locate(reader: &Reader<Vec<u8>>, ip: IpAddr) -> Option<GeoLocation> {
+    let record: geoip2::City = match reader.lookup(ip) {
+        Ok(record) => record,
+        Err(maxminddb::MaxMindDBError::AddressNotFoundError(_)) => return None,
+        Err(e) => {
+            debug!("GeoIP lookup for {} failed: {}", ip, e);
+            return None;
+        }
+    };
+
+    let location = GeoLocation {
+        country: record
+            .country
+            .and_then(|country| country.iso_code)
+            .map(str::to_string),
+        region: record
+            .subdivisions
+            .and_then(|subdivisions| subdivisions.into_iter().next())
+            .and_then(|subdivision| subdivision.iso_code)
+            .map(str::to_string),
+    };
+    (location != GeoLocation::default()).then_some(location)
+}
+
+/// Middleware attaching the client's [`GeoLocation`] to the request
+///
+/// Requests pass through unchanged when GeoIP is off or the address is
+/// unknown.
+pub async fn enrich(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    if let Some(geoip) = &state.geoip {
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        if let Some(location) = geoip
+            .client_ip(request.headers(), peer)
+            .and_then(|ip| geoip.lookup(ip))
+        {
+            request.extensions_mut().insert(location);
+        }
+    }
+    next.run(request).await
+}
+
+/// Start the task that picks up database updates
+pub fn spawn_reloader(geoip: Arc<GeoIp>) -> JoinHandle<()> {
+    let interval = geoip.config.reload_interval;
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick fires immediately
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            let geoip = geoip.clone();
+            match tokio::task::spawn_blocking(move || geoip.reload_if_changed()).await {
+                Ok(Ok(true)) => info!("GeoIP database reloaded"),
+                Ok(Ok(false)) => {}
+                Ok(Err(e)) => warn!("GeoIP database reload failed: {:#}", e),
+                Err(e) => warn!("GeoIP database reload failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test_db {
+    //! Minimal MaxMind DB writer for tests
+
+    /// Encode a map of string keys
+    pub fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![(7 << 5) | entries.len() as u8];
+        for (key, value) in entries {
+            out.extend(string(key));
+            out.extend(value);
+        }
+        out
+    }
+
+    pub fn string(s: &str) -> Vec<u8> {
+        let mut out = vec![(2 << 5) | s.len() as u8];
+        out.extend(s.as_bytes());
+        out
+    }
+
+    pub fn array(items: &[Vec<u8>]) -> Vec<u8> {
+        // Extended type 11
+        let mut out = vec![items.len() as u8, 11 - 7];
+        for item in items {
+            out.extend(item);
+        }
+        out
+    }
+
+    fn uint16(n: u16) -> Vec<u8> {
+        let mut out = vec![(5 << 5) | 2];
+        out.extend(n.to_be_bytes());
+        out
+    }
+
+    fn uint32(n: u32) -> Vec<u8> {
+        let mut out = vec![(6 << 5) | 4];
+        out.extend(n.to_be_bytes());
+        out
+    }
+
+    fn uint64(n: u64) -> Vec<u8> {
+        // Extended type 9
+        let mut out = vec![8, 9 - 7];
+        out.extend(n.to_be_bytes());
+        out
+    }
+
+    /// City record with a country and optional subdivision
+    pub fn city(country: &str, region: Option<&str>) -> Vec<u8> {
+        let mut entries = vec![("country", map(&[("iso_code", string(country))]))];
+        if let Some(region) = region {
+            entries.push((
+                "subdivisions",
+                array(&[map(&[("iso_code", string(region))])]),
+            ));
+        }
+        map(&entries)
+    }
+
+    #[derive(Clone, Copy)]
+    enum Record {
+        Empty,
+        Node(usize),
+        Data(usize),
+    }
+
+    /// IPv4 database mapping `(network, prefix length)` to encoded records
+    pub fn ipv4(networks: &[([u8; 4], u8, Vec<u8>)]) -> Vec<u8> {
+        let mut nodes = vec![[Record::Empty; 2]];
+        let mut data: Vec<u8> = Vec::new();
+
+        for (network, prefix, record) in networks {
+            let bits = u32::from_be_bytes(*network);
+            let mut node = 0;
+            for depth in 0..*prefix {
+                let bit = ((bits >> (31 - depth)) & 1) as usize;
+                if depth + 1 == *prefix {
+                    nodes[node][bit] = Record::Data(data.len());
+                    data.extend(record);
+                } else {
+                    node = match nodes[node][bit] {
+                        Record::Node(next) => next,
+                        _ => {
+                            nodes.push([Record::Empty; 2]);
+                            nodes[node][bit] = Record::Node(nodes.len() - 1);
+                            nodes.len() - 1
+                        }
+                    };
+                }
+            }
+        }
+
+        let node_count = nodes.len();
+        let mut out = Vec::new();
+        for node in &nodes {
+            for record in node {
+                let value = match *record {
+                    Record::Empty => node_count,
+                    Record::Node(next) => next,
+                    Record::Data(offset) => node_count + 16 + offset,
+                };
+                out.extend(&(value as u32).to_be_bytes()[1..]);
+            }
+        }
+        out.extend([0; 16]);
+        out.extend(data);
+
+        out.extend(b"\xAB\xCD\xEFMaxMind.com");
+        out.extend(map(&[
+            ("node_count", uint32(node_count as u32)),
+            ("record_size", uint16(24)),
+            ("ip_version", uint16(4)),
+            ("database_type", string("GeoIP2-City")),
+            ("languages", array(&[string("en")])),
+            ("binary_format_major_version", uint16(2)),
+            ("binary_format_minor_version", uint16(0)),
+            ("build_epoch", uint64(0)),
+            ("description", map(&[("en", string("test"))])),
+        ]));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn write_db(path: &std::path::Path, country: &str) {
+        let db = test_db::ipv4(&[
+            ([81, 2, 69, 0], 24, test_db::city(country, Some("ENG"))),
+            ([89, 160, 20, 0], 24, test_db::city("SE", None)),
+        ]);
+        std::fs::write(path, db).unwrap();
+    }
+
+    #[test]
+    fn test_lookup_and_reload() {
+        let dir = std::env::temp_dir().join(format!("rune-geoip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("city.mmdb");
+        write_db(&path, "GB");
+
+        let geoip = GeoIp::open(GeoIpConfig::new(&path)).unwrap();
+        let london: IpAddr = "81.2.69.160".parse().unwrap();
+        let location = geoip.lookup(london).unwrap();
+        assert_eq!(location.country.as_deref(), Some("GB"));
+        assert_eq!(
+            location.context(),
+            vec![
+                ("geo_country", Value::string("GB")),
+                ("geo_region", Value::string("ENG")),
+            ]
+        );
+        let sweden = geoip.lookup("89.160.20.1".parse().unwrap()).unwrap();
+        assert_eq!(sweden.context().len(), 1);
+        assert_eq!(geoip.lookup("10.0.0.1".parse().unwrap()), None);
+        assert_eq!(geoip.lookup("::1".parse().unwrap()), None);
+
+        assert!(!geoip.reload_if_changed().unwrap());
+
+        // A replaced file is picked up and the cache dropped
+        write_db(&path, "IE");
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert!(geoip.reload_if_changed().unwrap());
+        assert_eq!(geoip.lookup(london).unwrap().country.as_deref(), Some("IE"));
+
+        // A broken file keeps the loaded database
+        std::fs::write(&path, b"not a database").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(120))
+            .unwrap();
+        assert!(geoip.reload_if_changed().is_err());
+        assert_eq!(geoip.lookup(london).unwrap().country.as_deref(), Some("IE"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let path = std::env::temp_dir().join(format!("rune-geoip-ip-{}.mmdb", std::process::id()));
+        write_db(&path, "GB");
+        let mut config = GeoIpConfig::new(&path);
+        let peer: SocketAddr = "192.0.2.7:4000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            FORWARDED_FOR,
+            HeaderValue::from_static("81.2.69.160, 10.0.0.1"),
+        );
+
+        let geoip = GeoIp::open(config.clone()).unwrap();
+        assert_eq!(geoip.client_ip(&headers, Some(peer)), Some(peer.ip()));
+
+        config.trust_forwarded_for = true;
+        let geoip = GeoIp::open(config).unwrap();
+        assert_eq!(
+            geoip.client_ip(&headers, Some(peer)),
+            Some("81.2.69.160".parse().unwrap())
+        );
+        assert_eq!(
+            geoip.client_ip(&HeaderMap::new(), Some(peer)),
+            Some(peer.ip())
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(GeoIp::open(GeoIpConfig::new(&path)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enrich_attaches_location() {
+        use axum::{body::Body, routing::get, Extension, Router};
+        use tower::ServiceExt;
+
+        let path =
+            std::env::temp_dir().join(format!("rune-geoip-enrich-{}.mmdb", std::process::id()));
+        write_db(&path, "GB");
+        let geoip = Arc::new(GeoIp::open(GeoIpConfig::new(&path)).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let state = AppState::new(Arc::new(rune_core::RUNEEngine::new())).with_geoip(geoip);
+        let app = Router::new()
+            .route(
+                "/",
+                get(|location: Option<Extension<GeoLocation>>| async move {
+                    location.map_or("none".to_string(), |Extension(location)| {
+                        format!("{:?}", location.context())
+                    })
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(state, enrich));
+
+        let call = |peer: &str| {
+            let mut request = Request::new(Body::empty());
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+            app.clone().oneshot(request)
+        };
+        let body = |response: Response| async move {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let located = body(call("81.2.69.160:5000").await.unwrap()).await;
+        assert!(located.contains("geo_country") && located.contains("ENG"));
+        assert_eq!(body(call("10.1.2.3:5000").await.unwrap()).await, "none");
+    }
+}
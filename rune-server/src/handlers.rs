@@ -1,50 +1,179 @@
 //! HTTP request handlers
 
+use crate::access;
+use crate::actors;
 use crate::api::{
-    AuthorizeRequest, AuthorizeResponse, BatchAuthorizeRequest, BatchAuthorizeResponse, Decision,
-    Diagnostics, HealthResponse, HealthStatus,
+    AccessRequestParams, AccessRequestResponse, AccessRequestsResponse, ActionsRequest,
+    ActionsResponse, ArtifactInfo, AuthorizeRequest, AuthorizeResponse, BatchAuthorizeRequest,
+    BatchAuthorizeResponse, ChangesParams, CompactionResponse, Decision, Diagnostics, ExportParams,
+    FactCountResponse, FactHistoryParams, FactHistoryResponse, FactQueryParams, FactsAsOfParams,
+    FactsAsOfResponse, HealthResponse, HealthStatus, LabelsParams, LabelsResponse, MatrixRequest,
+    MatrixResponse, OpenAccessRequest, OpenSessionRequest, PermissionSummaryResponse,
+    PermittedPrincipalsRequest, PermittedPrincipalsResponse, PermittedResourcesRequest,
+    PermittedResourcesResponse, PrefetchRequest, PrefetchResponse, QueryRequest, QueryResponse,
+    ReasonDescription, ReloadResponse, ReviewAccessRequest, RuleFlag, RuleFlagsResponse,
+    SessionResponse, SessionsResponse, ShadowModeRequest, SnapshotImportResponse, SnapshotParams,
+    StageResponse, StagedConfigsResponse, UpdateRuleFlagRequest, ValidatePoliciesRequest,
+    ValidatePoliciesResponse, VersionResponse, VersionsResponse, WatchParams,
 };
+use crate::codec::Encoded;
+use crate::compaction;
+use crate::detail;
 use crate::error::{ApiError, ApiResult};
-use crate::metrics;
+use crate::geoip::GeoLocation;
+use crate::metrics::{self, SloReport};
+use crate::mirror::MirrorReport;
+use crate::replication;
+use crate::shadow::{self, ShadowReport};
 use crate::state::AppState;
+use crate::timeouts::RequestTiming;
+use crate::usage::UsageReport;
+use crate::watch::{self, DecisionWatch};
 use axum::{
-    extract::{Query, State},
-    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use rune_core::datalog::Term;
+use rune_core::{
+    Action, AuthorizationResult, ChangeBatch, ChangeOrigin, ChangePosition, Diagnostic,
+    ExportFormat, FactQuery, HistoryQuery, LabelSelector, Principal, RUNEError, ReplicaStatus,
+    RequestBuilder, Resource, SnapshotFormat,
 };
-use rune_core::{Action, Principal, RequestBuilder, Resource};
 use serde::Deserialize;
-use std::time::Instant;
+use std::collections::{BTreeSet, HashSet};
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::{debug, error, info, warn};
 
-/// Parse a principal string (format: "type:id" or just "id")
-fn parse_principal(s: &str) -> Principal {
-    if let Some((typ, id)) = s.split_once(':') {
-        Principal::new(typ, id)
-    } else {
-        Principal::new("User", s)
+/// Query parameters for debug mode
+#[derive(Debug, Deserialize)]
+pub struct DebugParams {
+    #[serde(default)]
+    debug: bool,
+}
+
+/// Request builder carrying the client's location, if GeoIP found one
+fn request_builder(location: &Option<Extension<GeoLocation>>) -> RequestBuilder {
+    let context = location
+        .iter()
+        .flat_map(|Extension(location)| location.context());
+    context.fold(RequestBuilder::new(), |builder, (key, value)| {
+        builder.context(key, value)
+    })
+}
+
+/// Core request for an API authorization request
+fn core_request(
+    req: &AuthorizeRequest,
+    location: &Option<Extension<GeoLocation>>,
+) -> rune_core::Result<rune_core::Request> {
+    let principal = req.principal_attributes.iter().fold(
+        Principal::parse(&req.principal),
+        |principal, (key, value)| principal.with_attribute(key, value.clone()),
+    );
+    let mut builder = request_builder(location)
+        .principal(principal)
+        .action(Action::new(&req.action))
+        .resource(Resource::parse(&req.resource));
+    for source in &req.attribute_sources {
+        builder = builder.attribute_source(source.clone());
+    }
+    if let Some(ms) = req.max_staleness_ms {
+        builder = builder.max_staleness(Duration::from_millis(ms));
     }
+    builder.explain(req.explain).build()
 }
 
-/// Parse a resource string (format: "type:id" or "type:path/to/resource")
-fn parse_resource(s: &str) -> Resource {
-    if let Some((typ, id)) = s.split_once(':') {
-        Resource::new(typ, id)
-    } else {
-        Resource::new("Resource", s)
+/// Charge a decision to the caller's account (see [`crate::usage`]) and
+/// show it to the anomaly detector, if there is one
+fn watch_decision(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: &rune_core::Request,
+    result: &AuthorizationResult,
+) {
+    state.usage.record(headers, request, result);
+    if let Some(detector) = &state.anomalies {
+        detector.observe(request, result.decision);
     }
 }
 
-/// Query parameters for debug mode
-#[derive(Debug, Deserialize)]
-pub struct DebugParams {
-    #[serde(default)]
-    debug: bool,
+/// The active artifact as audit records name it: digest, then the commit
+/// and signer when stamped, or `none`
+fn audit_artifact(state: &AppState) -> String {
+    let Some(artifact) = state.engine.artifact() else {
+        return "none".to_string();
+    };
+    let mut audit = artifact.digest.clone();
+    if let Some(sha) = &artifact.git_sha {
+        audit.push_str(&format!(" git_sha={}", sha));
+    }
+    if let Some(signer) = &artifact.signer {
+        audit.push_str(&format!(" signer={}", signer));
+    }
+    audit
+}
+
+/// Whether decisions made now should leave out their detail (see
+/// [`crate::detail`])
+fn reduces_detail(state: &AppState) -> bool {
+    state
+        .detail_shedding
+        .is_some_and(|shedding| shedding.applies(&state.engine))
+}
+
+/// Value of the request's `Accept-Language` header
+fn accept_language(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Response for a decision, its reasons rendered in the caller's language
+fn decision_response(
+    state: &AppState,
+    result: &AuthorizationResult,
+    accept_language: Option<&str>,
+) -> AuthorizeResponse {
+    let rendered = state
+        .engine
+        .explanation_renderer()
+        .render(result, accept_language);
+    let reason_codes = result
+        .reason_codes
+        .iter()
+        .zip(rendered.descriptions)
+        .map(|(reason, description)| ReasonDescription {
+            code: reason.code,
+            params: reason.params.clone(),
+            description,
+        })
+        .collect();
+
+    AuthorizeResponse {
+        decision: result.decision.into(),
+        reasons: vec![rendered.explanation],
+        reason_codes,
+        locale: Some(rendered.locale),
+        warnings: result.warnings.clone(),
+        valid_for_ms: result.valid_for_ms,
+        diagnostics: None,
+        proofs: result.proofs.clone(),
+        near_misses: result.near_misses.clone(),
+        detail: None,
+        signature: None,
+    }
 }
 
 /// Handle authorization request
 #[tracing::instrument(
     name = "authorize",
-    skip(state, params),
+    skip(state, params, location, headers),
     fields(
         principal = %req.principal,
         action = %req.action,
@@ -56,31 +185,64 @@ pub struct DebugParams {
 pub async fn authorize(
     State(state): State<AppState>,
     Query(params): Query<DebugParams>,
-    Json(req): Json<AuthorizeRequest>,
-) -> ApiResult<Json<AuthorizeResponse>> {
+    location: Option<Extension<GeoLocation>>,
+    timing: Option<Extension<RequestTiming>>,
+    headers: HeaderMap,
+    Encoded(format, req): Encoded<AuthorizeRequest>,
+) -> ApiResult<Encoded<AuthorizeResponse>> {
     let start = Instant::now();
+    let timing = timing.map(|Extension(timing)| timing).unwrap_or_default();
 
     debug!("Authorization request: {:?}", req);
 
     // Build the request with tracing
-    let request = crate::tracing::trace_parse_request(|| {
-        RequestBuilder::new()
-            .principal(parse_principal(&req.principal))
-            .action(Action::new(&req.action))
-            .resource(parse_resource(&req.resource))
-            .build()
+    let mut request = crate::tracing::trace_parse_request(|| {
+        core_request(&req, &location)
             .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))
     })?;
+    timing.mark("parse");
 
-    // Evaluate authorization with tracing
-    let result = crate::tracing::trace_datalog_evaluation(0, || {
-        state
-            .engine
-            .authorize(&request)
-            .map_err(|e| ApiError::Internal(format!("Authorization failed: {}", e)))
-    })?;
+    // Under load, decide without capturing detail nobody is waiting on
+    let reduced = reduces_detail(&state);
+    if reduced {
+        detail::reduce_request(&mut request);
+    }
+
+    // Read before evaluating so a signature or audit record never claims a
+    // newer config
+    let generation = state.engine.generation();
+    let artifact = audit_artifact(&state);
+
+    // Evaluate authorization with tracing, off the runtime's threads
+    timing.begin("evaluate");
+    let evaluation = crate::tracing::trace_datalog_evaluation_async(
+        0,
+        state.engine.authorize_async(&request, timing.deadline()),
+    );
+    let result = match evaluation.await {
+        Ok(result) => result,
+        Err(e @ RUNEError::ReplicaStale { .. }) => {
+            return replication::escalate(&state, &req, accept_language(&headers), e)
+                .await
+                .map(|response| Encoded(format, response));
+        }
+        Err(e) => {
+            metrics::record_operation("authorize", start.elapsed().as_secs_f64(), false);
+            return Err(ApiError::Internal(format!("Authorization failed: {}", e)));
+        }
+    };
+    timing.mark("evaluate");
 
     let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+    watch_decision(&state, &headers, &request, &result);
+
+    // Labels of the deciding policies say where the grant came from
+    let sources: Vec<String> = state
+        .engine
+        .decision_labels(&result)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
 
     // Convert decision
     let decision = result.decision.into();
@@ -92,16 +254,29 @@ pub async fn authorize(
         Decision::Forbid => "forbid",
     };
     metrics::record_authorization(decision_str, elapsed_ms / 1000.0, result.cached);
+    if result.cached {
+        metrics::record_cache_saving(result.evaluation_time_ns);
+    }
     metrics::record_rule_evaluations(result.evaluated_rules.len());
+    metrics::record_evaluation_failures(&result.failures);
+    metrics::record_warnings(&result.warnings);
+    if result.coalesced {
+        metrics::record_coalesced_request();
+    }
 
     // Record decision in trace
     crate::tracing::record_decision(decision_str, elapsed_ms);
 
+    // Compare a sample with the secondary, off the request path
+    if let Some(mirror) = &state.mirror {
+        mirror.offer(&req, decision, elapsed_ms);
+    }
+    // Try a staged configuration if the caller asked for it, likewise
+    shadow::shadow(&state, &headers, &req, &request, result.decision);
+
     // Build response with tracing
-    let mut response = crate::tracing::trace_format_response(|| AuthorizeResponse {
-        decision,
-        reasons: vec![result.explanation],
-        diagnostics: None,
+    let mut response = crate::tracing::trace_format_response(|| {
+        decision_response(&state, &result, accept_language(&headers))
     });
 
     // Add diagnostics if in debug mode
@@ -110,24 +285,39 @@ pub async fn authorize(
             evaluation_time_ms: elapsed_ms,
             cache_hit: result.cached,
             rules_evaluated: result.evaluated_rules.len(),
-            policies_evaluated: 0, // TODO: Track Cedar policies
+            policies_evaluated: result.policies_evaluated,
             matched_rules: result.evaluated_rules,
             matched_policies: Vec::new(), // TODO: Track matched policies
         });
     }
 
+    if reduced {
+        detail::reduce_response(&mut response);
+    }
+
+    if let Some(signer) = &state.signer {
+        response.signature = Some(signer.sign(&req, decision, generation));
+    }
+
     info!(
-        "Authorization: {} {} {} -> {:?} ({:.2}ms)",
-        req.principal, req.action, req.resource, decision, elapsed_ms
+        request_id = %crate::logging::request_id(&headers),
+        principal_hash = %crate::logging::principal_hash(&req.principal),
+        decision = decision_str,
+        latency_ms = elapsed_ms,
+        generation,
+        sources = %sources.join(" "),
+        artifact = %artifact,
+        "Authorization: {} {} -> {:?} ({:.2}ms)",
+        req.action, req.resource, decision, elapsed_ms
     );
 
-    Ok(Json(response))
+    Ok(Encoded(format, response))
 }
 
 /// Handle batch authorization request
 #[tracing::instrument(
     name = "batch_authorize",
-    skip(state, params),
+    skip(state, params, location, headers),
     fields(
         batch_size = req.requests.len(),
         latency_ms = tracing::field::Empty,
@@ -136,9 +326,13 @@ pub async fn authorize(
 pub async fn batch_authorize(
     State(state): State<AppState>,
     Query(params): Query<DebugParams>,
-    Json(req): Json<BatchAuthorizeRequest>,
-) -> ApiResult<Json<BatchAuthorizeResponse>> {
+    location: Option<Extension<GeoLocation>>,
+    timing: Option<Extension<RequestTiming>>,
+    headers: HeaderMap,
+    Encoded(format, req): Encoded<BatchAuthorizeRequest>,
+) -> ApiResult<Encoded<BatchAuthorizeResponse>> {
     let start = Instant::now();
+    let timing = timing.map(|Extension(timing)| timing).unwrap_or_default();
 
     debug!(
         "Batch authorization request: {} requests",
@@ -156,35 +350,70 @@ pub async fn batch_authorize(
     }
 
     let mut results = Vec::with_capacity(req.requests.len());
+    let generation = state.engine.generation();
+    let artifact = audit_artifact(&state);
+
+    // Decide the valid requests together, sharing one Datalog evaluation
+    let reduced = reduces_detail(&state);
+    let parsed: Vec<rune_core::Result<_>> = req
+        .requests
+        .iter()
+        .map(|auth_req| {
+            let mut request = core_request(auth_req, &location)?;
+            if reduced {
+                detail::reduce_request(&mut request);
+            }
+            Ok(request)
+        })
+        .collect();
+    let valid: Vec<_> = parsed
+        .iter()
+        .filter_map(|request| request.as_ref().ok().cloned())
+        .collect();
+    // Off the runtime's threads, like single decisions, and given up on
+    // at the route's deadline
+    timing.begin("evaluate");
+    let mut decided = state
+        .engine
+        .authorize_batch_async(&valid, timing.deadline())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Authorization failed: {}", e)))?
+        .into_iter();
 
-    // Process each request
-    for auth_req in req.requests {
-        let request = match RequestBuilder::new()
-            .principal(parse_principal(&auth_req.principal))
-            .action(Action::new(&auth_req.action))
-            .resource(parse_resource(&auth_req.resource))
-            .build()
-        {
+    for (auth_req, request) in req.requests.iter().zip(&parsed) {
+        let request = match request {
             Ok(r) => r,
             Err(e) => {
                 // Add error response for this request
                 results.push(AuthorizeResponse {
                     decision: Decision::Forbid,
                     reasons: vec![format!("Invalid request: {}", e)],
+                    reason_codes: Vec::new(),
+                    locale: None,
+                    warnings: Vec::new(),
+                    valid_for_ms: 0,
                     diagnostics: None,
+                    proofs: Vec::new(),
+                    near_misses: Vec::new(),
+                    detail: None,
+                    signature: None,
                 });
                 continue;
             }
         };
 
-        // Evaluate authorization
-        match state.engine.authorize(&request) {
+        match decided.next().expect("one decision per valid request") {
             Ok(result) => {
-                let mut response = AuthorizeResponse {
-                    decision: result.decision.into(),
-                    reasons: vec![result.explanation],
-                    diagnostics: None,
-                };
+                watch_decision(&state, &headers, request, &result);
+                metrics::record_evaluation_failures(&result.failures);
+                if result.cached {
+                    metrics::record_cache_saving(result.evaluation_time_ns);
+                }
+                metrics::record_warnings(&result.warnings);
+                if result.coalesced {
+                    metrics::record_coalesced_request();
+                }
+                let mut response = decision_response(&state, &result, accept_language(&headers));
 
                 // Add diagnostics if in debug mode
                 if state.debug || params.debug {
@@ -192,24 +421,61 @@ pub async fn batch_authorize(
                         evaluation_time_ms: 0.0, // Not tracked per-request in batch
                         cache_hit: result.cached,
                         rules_evaluated: result.evaluated_rules.len(),
-                        policies_evaluated: 0, // TODO: Track Cedar policies
+                        policies_evaluated: result.policies_evaluated,
                         matched_rules: result.evaluated_rules,
                         matched_policies: Vec::new(),
                     });
                 }
+                if reduced {
+                    detail::reduce_response(&mut response);
+                }
 
                 results.push(response);
             }
+            Err(e @ RUNEError::ReplicaStale { .. }) => {
+                results.push(
+                    replication::escalate(&state, auth_req, accept_language(&headers), e)
+                        .await
+                        .unwrap_or_else(|e| AuthorizeResponse {
+                            decision: Decision::Forbid,
+                            reasons: vec![e.to_string()],
+                            reason_codes: Vec::new(),
+                            locale: None,
+                            warnings: Vec::new(),
+                            valid_for_ms: 0,
+                            diagnostics: None,
+                            proofs: Vec::new(),
+                            near_misses: Vec::new(),
+                            detail: None,
+                            signature: None,
+                        }),
+                );
+            }
             Err(e) => {
                 error!("Batch authorization error: {}", e);
                 results.push(AuthorizeResponse {
                     decision: Decision::Forbid,
                     reasons: vec![format!("Authorization error: {}", e)],
+                    reason_codes: Vec::new(),
+                    locale: None,
+                    warnings: Vec::new(),
+                    valid_for_ms: 0,
                     diagnostics: None,
+                    proofs: Vec::new(),
+                    near_misses: Vec::new(),
+                    detail: None,
+                    signature: None,
                 });
             }
         }
     }
+    timing.mark("evaluate");
+
+    if let Some(signer) = &state.signer {
+        for (response, auth_req) in results.iter_mut().zip(&req.requests) {
+            response.signature = Some(signer.sign(auth_req, response.decision, generation));
+        }
+    }
 
     let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
 
@@ -218,12 +484,385 @@ pub async fn batch_authorize(
     tracing::Span::current().record("latency_ms", elapsed_ms);
 
     info!(
+        request_id = %crate::logging::request_id(&headers),
+        latency_ms = elapsed_ms,
+        generation,
+        artifact = %artifact,
         "Batch authorization: {} requests processed in {:.2}ms",
         results.len(),
         elapsed_ms
     );
 
-    Ok(Json(BatchAuthorizeResponse { results }))
+    Ok(Encoded(format, BatchAuthorizeResponse { results }))
+}
+
+/// Most action and resource pairs one prefetch request may queue
+const MAX_PREFETCH_PAIRS: usize = 100;
+
+/// Warm the decision cache for a principal about to act
+///
+/// Every action is paired with every resource. The pairs are checked up
+/// front and evaluated in the background; the response only says how many
+/// were queued, never what was decided.
+pub async fn prefetch(
+    State(state): State<AppState>,
+    location: Option<Extension<GeoLocation>>,
+    Json(req): Json<PrefetchRequest>,
+) -> ApiResult<(StatusCode, Json<PrefetchResponse>)> {
+    let pairs = req.actions.len() * req.resources.len();
+    if pairs == 0 {
+        return Err(ApiError::BadRequest(
+            "No actions or resources provided".to_string(),
+        ));
+    }
+    if pairs > MAX_PREFETCH_PAIRS {
+        return Err(ApiError::BadRequest(format!(
+            "Too many action and resource pairs ({}, max {})",
+            pairs, MAX_PREFETCH_PAIRS
+        )));
+    }
+
+    let mut requests = Vec::with_capacity(pairs);
+    for action in &req.actions {
+        for resource in &req.resources {
+            let auth_req = AuthorizeRequest {
+                principal: req.principal.clone(),
+                principal_attributes: req.principal_attributes.clone(),
+                attribute_sources: Vec::new(),
+                action: action.clone(),
+                resource: resource.clone(),
+                context: Default::default(),
+                max_staleness_ms: None,
+                explain: false,
+            };
+            let request = core_request(&auth_req, &location)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))?;
+            requests.push(request);
+        }
+    }
+
+    let engine = state.engine.clone();
+    tokio::spawn(async move {
+        match engine.prefetch_async(requests).await {
+            Ok(cached) => {
+                metrics::record_prefetch(cached);
+                debug!("Prefetch cached {} of {} decisions", cached, pairs);
+            }
+            Err(e) => debug!("Prefetch skipped: {}", e),
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(PrefetchResponse { queued: pairs }),
+    ))
+}
+
+/// Most candidate actions one request may decide
+const MAX_CANDIDATE_ACTIONS: usize = 100;
+
+/// Decide several actions of one principal on one resource
+///
+/// The actions are decided together (see
+/// [`rune_core::RUNEEngine::authorize_actions`]), sharing one Datalog
+/// evaluation, so a UI can enable or disable each of its buttons with one
+/// call.
+pub async fn authorize_actions(
+    State(state): State<AppState>,
+    location: Option<Extension<GeoLocation>>,
+    Json(req): Json<ActionsRequest>,
+) -> ApiResult<Json<ActionsResponse>> {
+    let mut actions = req.actions;
+    let mut seen = HashSet::new();
+    actions.retain(|action| seen.insert(action.clone()));
+    if actions.is_empty() {
+        return Err(ApiError::BadRequest("No actions provided".to_string()));
+    }
+    if actions.len() > MAX_CANDIDATE_ACTIONS {
+        return Err(ApiError::BadRequest(format!(
+            "Too many actions ({}, max {})",
+            actions.len(),
+            MAX_CANDIDATE_ACTIONS
+        )));
+    }
+
+    let auth_req = AuthorizeRequest {
+        principal: req.principal,
+        principal_attributes: req.principal_attributes,
+        attribute_sources: Vec::new(),
+        action: actions[0].clone(),
+        resource: req.resource,
+        context: req.context,
+        max_staleness_ms: None,
+        explain: false,
+    };
+    let request = core_request(&auth_req, &location)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))?;
+    let candidates: Vec<Action> = actions.iter().map(Action::new).collect();
+
+    let start = Instant::now();
+    let results = state
+        .engine
+        .authorize_actions_async(&request, &candidates, None)
+        .await?;
+    metrics::record_action_checks(actions.len(), start.elapsed().as_secs_f64());
+
+    let cached = results.iter().filter(|result| result.cached).count();
+    let decisions = actions
+        .into_iter()
+        .zip(results)
+        .map(|(action, result)| (action, Decision::from(result.decision)))
+        .collect();
+    Ok(Json(ActionsResponse { decisions, cached }))
+}
+
+/// Most candidate resources one permitted resource request may check
+const MAX_CANDIDATE_RESOURCES: usize = 1000;
+
+/// List which candidate resources a principal may act on
+///
+/// Candidates are the listed resources plus, with `query`, every string the
+/// goal's variable binds to. They are checked together (see
+/// [`rune_core::RUNEEngine::authorize_resources`]), so a UI can filter a
+/// listing with one call instead of one authorization per item.
+pub async fn permitted_resources(
+    State(state): State<AppState>,
+    location: Option<Extension<GeoLocation>>,
+    timing: Option<Extension<RequestTiming>>,
+    Json(req): Json<PermittedResourcesRequest>,
+) -> ApiResult<Json<PermittedResourcesResponse>> {
+    let timing = timing.map(|Extension(timing)| timing).unwrap_or_default();
+    let mut candidates = req.resources.clone();
+    if let Some(query) = &req.query {
+        let goal = rune_core::parse_goal(query).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        let variables: BTreeSet<&str> = goal
+            .terms
+            .iter()
+            .filter_map(|term| match term {
+                Term::Variable(name) => Some(name.as_str()),
+                Term::Constant(_) => None,
+            })
+            .collect();
+        if variables.len() != 1 {
+            return Err(ApiError::BadRequest(format!(
+                "Query must have exactly one variable, found {}",
+                variables.len()
+            )));
+        }
+
+        let engine = state.engine.clone();
+        let query_goal = goal.clone();
+        let guard = state.shutdown.child().drop_guard();
+        let cancel = guard.token().clone();
+        let answer =
+            tokio::task::spawn_blocking(move || engine.query_cancellable(&query_goal, &cancel))
+                .await
+                .map_err(|e| ApiError::Internal(format!("Query failed: {}", e)))??;
+        metrics::record_query(answer.cached);
+        for fact in answer.facts.iter() {
+            for (term, value) in goal.terms.iter().zip(fact.args.iter()) {
+                if let (Term::Variable(_), rune_core::Value::String(resource)) = (term, value) {
+                    candidates.push(resource.to_string());
+                }
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    candidates.retain(|resource| seen.insert(resource.clone()));
+    if candidates.is_empty() {
+        return Err(ApiError::BadRequest(
+            "No candidate resources provided".to_string(),
+        ));
+    }
+    if candidates.len() > MAX_CANDIDATE_RESOURCES {
+        return Err(ApiError::BadRequest(format!(
+            "Too many candidate resources ({}, max {})",
+            candidates.len(),
+            MAX_CANDIDATE_RESOURCES
+        )));
+    }
+
+    let auth_req = AuthorizeRequest {
+        principal: req.principal,
+        principal_attributes: req.principal_attributes,
+        attribute_sources: Vec::new(),
+        action: req.action,
+        resource: candidates[0].clone(),
+        context: req.context,
+        max_staleness_ms: None,
+        explain: false,
+    };
+    let request = core_request(&auth_req, &location)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))?;
+    let resources: Vec<Resource> = candidates.iter().map(|r| Resource::parse(r)).collect();
+
+    let results = state
+        .engine
+        .authorize_resources_async(&request, &resources, timing.deadline())
+        .await?;
+    metrics::record_permitted_resources(candidates.len());
+
+    let cached = results.iter().filter(|result| result.cached).count();
+    let count = candidates.len();
+    let permitted = candidates
+        .into_iter()
+        .zip(&results)
+        .filter(|(_, result)| result.decision.is_permitted())
+        .map(|(resource, _)| resource)
+        .collect();
+    Ok(Json(PermittedResourcesResponse {
+        resources: permitted,
+        candidates: count,
+        cached,
+    }))
+}
+
+/// Most candidate principals one permitted principal request may check
+const MAX_CANDIDATE_PRINCIPALS: usize = 1000;
+
+/// List which candidate principals may perform an action on a resource
+///
+/// Answers "who can delete this bucket?" for audit and administration (see
+/// [`rune_core::RUNEEngine::list_permitted_principals`]): candidates no
+/// permit policy could apply to are skipped, and the rest are checked
+/// together.
+pub async fn permitted_principals(
+    State(state): State<AppState>,
+    timing: Option<Extension<RequestTiming>>,
+    Json(req): Json<PermittedPrincipalsRequest>,
+) -> ApiResult<Json<PermittedPrincipalsResponse>> {
+    let timing = timing.map(|Extension(timing)| timing).unwrap_or_default();
+    let mut candidates = req.principals;
+    let mut seen = HashSet::new();
+    candidates.retain(|principal| seen.insert(principal.clone()));
+    if candidates.is_empty() {
+        return Err(ApiError::BadRequest(
+            "No candidate principals provided".to_string(),
+        ));
+    }
+    if candidates.len() > MAX_CANDIDATE_PRINCIPALS {
+        return Err(ApiError::BadRequest(format!(
+            "Too many candidate principals ({}, max {})",
+            candidates.len(),
+            MAX_CANDIDATE_PRINCIPALS
+        )));
+    }
+
+    let action = Action::new(&req.action);
+    let resource = Resource::parse(&req.resource);
+    let principals: Vec<Principal> = candidates.iter().map(|p| Principal::parse(p)).collect();
+    let permitted = state
+        .engine
+        .list_permitted_principals_async(&action, &resource, &principals, timing.deadline())
+        .await?;
+    metrics::record_permitted_principals(candidates.len());
+
+    let count = candidates.len();
+    let mut permitted = permitted.into_iter().peekable();
+    let principals = candidates
+        .into_iter()
+        .filter(|candidate| {
+            permitted
+                .next_if(|principal| *principal == Principal::parse(candidate))
+                .is_some()
+        })
+        .collect();
+    Ok(Json(PermittedPrincipalsResponse {
+        principals,
+        candidates: count,
+    }))
+}
+
+/// Most principal, action and resource combinations one matrix may hold
+const MAX_MATRIX_CELLS: usize = 10_000;
+
+/// Decide every combination of the listed principals, actions and resources
+///
+/// All combinations are decided together (see
+/// [`rune_core::RUNEEngine::authorize_batch`]), sharing one Datalog
+/// evaluation, so an admin UI can fill a permission grid with one call.
+pub async fn authorize_matrix(
+    State(state): State<AppState>,
+    location: Option<Extension<GeoLocation>>,
+    timing: Option<Extension<RequestTiming>>,
+    Json(req): Json<MatrixRequest>,
+) -> ApiResult<Json<MatrixResponse>> {
+    let timing = timing.map(|Extension(timing)| timing).unwrap_or_default();
+    let cells = req.principals.len() * req.actions.len() * req.resources.len();
+    if cells == 0 {
+        return Err(ApiError::BadRequest(
+            "No principals, actions or resources provided".to_string(),
+        ));
+    }
+    if cells > MAX_MATRIX_CELLS {
+        return Err(ApiError::BadRequest(format!(
+            "Too many combinations ({}, max {})",
+            cells, MAX_MATRIX_CELLS
+        )));
+    }
+
+    let mut requests = Vec::with_capacity(cells);
+    for principal in &req.principals {
+        for action in &req.actions {
+            for resource in &req.resources {
+                let auth_req = AuthorizeRequest {
+                    principal: principal.clone(),
+                    principal_attributes: Default::default(),
+                    attribute_sources: Vec::new(),
+                    action: action.clone(),
+                    resource: resource.clone(),
+                    context: req.context.clone(),
+                    max_staleness_ms: None,
+                    explain: false,
+                };
+                let request = core_request(&auth_req, &location)
+                    .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))?;
+                requests.push(request);
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let results = state
+        .engine
+        .authorize_batch_async(&requests, timing.deadline())
+        .await?;
+    metrics::record_matrix(cells, start.elapsed().as_secs_f64());
+
+    let mut cached = 0;
+    let mut decisions = results.into_iter().map(|result| match result {
+        Ok(result) => {
+            cached += usize::from(result.cached);
+            Decision::from(result.decision)
+        }
+        Err(e) => {
+            debug!("Matrix cell failed: {}", e);
+            Decision::Forbid
+        }
+    });
+    let decisions = req
+        .principals
+        .iter()
+        .map(|_| {
+            req.actions
+                .iter()
+                .map(|_| decisions.by_ref().take(req.resources.len()).collect())
+                .collect()
+        })
+        .collect();
+
+    Ok(Json(MatrixResponse { decisions, cached }))
+}
+
+/// Server version, configuration generation and the artifact the active
+/// rules and policies were loaded from
+pub async fn version(State(state): State<AppState>) -> Json<VersionResponse> {
+    Json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        generation: state.engine.generation(),
+        artifact: state.engine.artifact().as_deref().map(ArtifactInfo::from),
+    })
 }
 
 /// Health check - liveness probe
@@ -265,39 +904,1102 @@ pub async fn health_ready(State(state): State<AppState>) -> ApiResult<Json<Healt
     }
 }
 
+/// List rule and policy kill switches
+pub async fn list_rule_flags(State(state): State<AppState>) -> Json<RuleFlagsResponse> {
+    let flags = state
+        .engine
+        .rule_flags()
+        .into_iter()
+        .map(RuleFlag::from)
+        .collect();
+    Json(RuleFlagsResponse { flags })
+}
+
+/// Switch the rules and policies behind a flag on or off
+///
+/// A flag switching rules or policies with an `@owner` can only be changed
+/// by members of the owning teams, identified by their actor tokens.
+pub async fn update_rule_flag(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateRuleFlagRequest>,
+) -> ApiResult<Json<RuleFlag>> {
+    let origin = change_origin(&state, &headers)?;
+    let matched = state
+        .engine
+        .set_rule_enabled_as(&key, req.enabled, &origin.actor)?;
+    if matched == 0 {
+        return Err(ApiError::NotFound(format!("Unknown rule flag: {}", key)));
+    }
+
+    warn!(
+        "Rule flag '{}' set to {} by {} ({} rules/policies affected)",
+        key,
+        if req.enabled { "enabled" } else { "disabled" },
+        origin.actor,
+        matched
+    );
+
+    find_rule_flag(&state, &key)
+}
+
+/// Remove a runtime override, restoring the configured state
+///
+/// Subject to the same ownership check as [`update_rule_flag`].
+pub async fn reset_rule_flag(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<Json<RuleFlag>> {
+    let origin = change_origin(&state, &headers)?;
+    if !state.engine.reset_rule_flag_as(&key, &origin.actor)? {
+        return Err(ApiError::NotFound(format!(
+            "No override for rule flag: {}",
+            key
+        )));
+    }
+
+    info!(
+        "Rule flag '{}' reset to configured state by {}",
+        key, origin.actor
+    );
+    find_rule_flag(&state, &key)
+}
+
+/// Replace the running configuration with the RUNE file in the body
+///
+/// A file that does not parse or load leaves the engine as it was and is
+/// answered with 422 and its diagnostics, with spans into the posted text.
+/// One that adds, removes or rewrites a rule or policy with an `@owner` is
+/// refused with 403 unless the caller's actor token identifies a member of
+/// the owning team (see [`rune_core::ownership`] and [`crate::actors`]).
+pub async fn reload_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> ApiResult<Json<ReloadResponse>> {
+    let engine = state.engine.clone();
+    let origin = change_origin(&state, &headers)?;
+    let result = tokio::task::spawn_blocking(move || {
+        let config = rune_core::parse_rune_file(&body)?;
+        let response = ReloadResponse {
+            version: config.version.clone(),
+            rules: config.rules.len(),
+            policies: config.policies.len(),
+            warnings: config.warnings.clone(),
+        };
+        engine.apply_config_as(config, &origin.actor)?;
+        Ok::<_, RUNEError>(response)
+    })
+    .await
+    .map_err(|e| ApiError::Internal(format!("Reload failed: {}", e)))?;
+
+    match result {
+        Ok(response) => {
+            metrics::record_reload("applied");
+            info!(
+                "Configuration reloaded: {} rules, {} policies",
+                response.rules, response.policies
+            );
+            Ok(Json(response))
+        }
+        Err(error @ RUNEError::AuthorizationDenied { .. }) => {
+            metrics::record_reload("forbidden");
+            warn!("Configuration reload refused: {}", error);
+            Err(ApiError::RuneError(error))
+        }
+        Err(error) => {
+            metrics::record_reload("rejected");
+            warn!("Configuration reload rejected: {}", error);
+            Err(rejected_config(error))
+        }
+    }
+}
+
+/// A RUNE file that failed to parse or build, as a 422 with diagnostics
+///
+/// Every rejection carries diagnostics, even ones the parser reports as
+/// plain errors.
+fn rejected_config(error: RUNEError) -> ApiError {
+    ApiError::RuneError(match error {
+        RUNEError::DiagnosticError(_) => error,
+        other => RUNEError::from_diagnostic(Diagnostic::error(other.to_string())),
+    })
+}
+
+/// Stage the RUNE file in the body for shadow evaluation (see
+/// [`crate::shadow`])
+///
+/// The file is checked as a reload would check it and rejected the same
+/// way; the active configuration is left alone either way.
+pub async fn stage_config(
+    State(state): State<AppState>,
+    body: String,
+) -> ApiResult<(StatusCode, Json<StageResponse>)> {
+    let engine = state.engine.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let config = rune_core::parse_rune_file(&body)?;
+        let warnings = config.warnings.clone();
+        let staged = engine.stage_config(config)?;
+        Ok::<_, RUNEError>(StageResponse { staged, warnings })
+    })
+    .await
+    .map_err(|e| ApiError::Internal(format!("Staging failed: {}", e)))?;
+
+    let response = result.map_err(|error| {
+        warn!("Staged configuration rejected: {}", error);
+        rejected_config(error)
+    })?;
+    info!(
+        "Configuration staged as {}: {} rules, {} policies",
+        response.staged.id, response.staged.rules, response.staged.policies
+    );
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// List the configurations staged for shadow evaluation
+pub async fn list_staged(State(state): State<AppState>) -> Json<StagedConfigsResponse> {
+    Json(StagedConfigsResponse {
+        staged: state.engine.staged_configs(),
+    })
+}
+
+/// Drop a staged configuration
+pub async fn discard_staged(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> ApiResult<StatusCode> {
+    if !state.engine.discard_staged(id) {
+        return Err(ApiError::NotFound(format!(
+            "Unknown staged configuration: {}",
+            id
+        )));
+    }
+    state.shadow_mode.stop_staged(id);
+    info!("Staged configuration {} discarded", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Shadow all traffic with a staged configuration (see [`crate::shadow`])
+pub async fn start_shadow(
+    State(state): State<AppState>,
+    Json(req): Json<ShadowModeRequest>,
+) -> ApiResult<Json<ShadowReport>> {
+    let report = state
+        .shadow_mode
+        .start(&state.engine, req.staged)
+        .ok_or_else(|| {
+            ApiError::NotFound(format!("Unknown staged configuration: {}", req.staged))
+        })?;
+    info!(
+        "Shadowing all traffic with staged configuration {}",
+        req.staged
+    );
+    Ok(Json(report))
+}
+
+/// Divergences between the active configuration and the one all traffic
+/// is shadowed with
+pub async fn shadow_report(State(state): State<AppState>) -> ApiResult<Json<ShadowReport>> {
+    state
+        .shadow_mode
+        .current()
+        .map(|run| Json(run.report()))
+        .ok_or_else(|| ApiError::NotFound("Shadowing is off".to_string()))
+}
+
+/// Stop shadowing all traffic, answering with the final report
+pub async fn stop_shadow(State(state): State<AppState>) -> ApiResult<Json<ShadowReport>> {
+    let report = state
+        .shadow_mode
+        .stop()
+        .ok_or_else(|| ApiError::NotFound("Shadowing is off".to_string()))?;
+    info!(
+        "Stopped shadowing with staged configuration {}: {} of {} decisions diverged",
+        report.staged, report.mismatched, report.evaluated
+    );
+    Ok(Json(report))
+}
+
+/// List the recently installed configurations and the active one
+pub async fn list_versions(State(state): State<AppState>) -> Json<VersionsResponse> {
+    Json(VersionsResponse {
+        current: state.engine.current_version(),
+        versions: state.engine.config_versions(),
+    })
+}
+
+/// Reinstall a recently installed configuration
+///
+/// The rollback is installed as a new version, so it can itself be rolled
+/// back. Like a reload, one that adds, removes or rewrites a rule or policy
+/// with an `@owner` is refused with 403 unless the caller is a member of
+/// the owning team.
+pub async fn rollback_version(
+    State(state): State<AppState>,
+    Path(version): Path<u64>,
+    headers: HeaderMap,
+) -> ApiResult<Json<rune_core::ConfigVersion>> {
+    if !state
+        .engine
+        .config_versions()
+        .iter()
+        .any(|installed| installed.version == version)
+    {
+        return Err(ApiError::NotFound(format!(
+            "Unknown configuration version: {}",
+            version
+        )));
+    }
+    let engine = state.engine.clone();
+    let origin = change_origin(&state, &headers)?;
+    let actor = origin.actor.clone();
+    let restored = tokio::task::spawn_blocking(move || engine.rollback_to_as(version, &actor))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Rollback failed: {}", e)))?;
+    let restored = match restored {
+        Ok(restored) => restored,
+        Err(error @ RUNEError::AuthorizationDenied { .. }) => {
+            metrics::record_reload("forbidden");
+            warn!("Configuration rollback refused: {}", error);
+            return Err(ApiError::RuneError(error));
+        }
+        Err(error) => return Err(error.into()),
+    };
+    metrics::record_reload("rolled_back");
+    info!(
+        "Configuration rolled back to version {} as version {} by {}",
+        version, restored.version, origin.actor
+    );
+    Ok(Json(restored))
+}
+
+/// Evaluation time charged to each account this period (see
+/// [`crate::usage`])
+pub async fn usage(State(state): State<AppState>) -> Json<UsageReport> {
+    Json(state.usage.report())
+}
+
+/// End the usage period, answering with its totals
+pub async fn reset_usage(State(state): State<AppState>) -> Json<UsageReport> {
+    let report = state.usage.reset();
+    info!(
+        "Usage period ended: {} accounts since {}",
+        report.accounts.len(),
+        report.since_ms
+    );
+    Json(report)
+}
+
+/// Check Cedar policies, and optionally Datalog rules, without loading them
+///
+/// Runs the checks a reload would and answers 200 either way, with
+/// `valid` and diagnostics spanning the posted texts.
+pub async fn validate_policies(
+    Json(req): Json<ValidatePoliciesRequest>,
+) -> ApiResult<Json<ValidatePoliciesResponse>> {
+    let check = tokio::task::spawn_blocking(move || {
+        rune_core::check_sources(&req.policies, req.rules.as_deref())
+    })
+    .await
+    .map_err(|e| ApiError::Internal(format!("Validation failed: {}", e)))?;
+    Ok(Json(check.into()))
+}
+
+/// Compact the fact store now, regardless of traffic
+pub async fn compact_facts(State(state): State<AppState>) -> ApiResult<Json<CompactionResponse>> {
+    let engine = state.engine.clone();
+    let (stats, elapsed) =
+        tokio::task::spawn_blocking(move || compaction::compact(&engine, "manual"))
+            .await
+            .map_err(|e| ApiError::Internal(format!("Compaction failed: {}", e)))?;
+
+    Ok(Json(CompactionResponse {
+        facts_before: stats.facts_before,
+        facts_after: stats.facts_after,
+        duplicates_removed: stats.duplicates_removed,
+        expired_removed: stats.expired_removed,
+        predicates_removed: stats.predicates_removed,
+        duration_ms: elapsed.as_secs_f64() * 1000.0,
+    }))
+}
+
+/// Download every fact, with its labels and time-to-live, as a snapshot
+pub async fn export_fact_snapshot(
+    State(state): State<AppState>,
+    Query(params): Query<SnapshotParams>,
+) -> ApiResult<Response> {
+    let format: SnapshotFormat = params
+        .format
+        .as_deref()
+        .unwrap_or("binary")
+        .parse()
+        .map_err(|e: RUNEError| ApiError::BadRequest(e.to_string()))?;
+
+    let engine = state.engine.clone();
+    let body = tokio::task::spawn_blocking(move || engine.export_facts(format))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Snapshot failed: {}", e)))??;
+    info!(
+        "Exported a {} fact snapshot ({} bytes)",
+        format.as_str(),
+        body.len()
+    );
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body).into_response())
+}
+
+/// Replace every fact with those of an uploaded snapshot in either format
+pub async fn import_fact_snapshot(
+    State(state): State<AppState>,
+    body: axum::body::Bytes,
+) -> ApiResult<Json<SnapshotImportResponse>> {
+    let engine = state.engine.clone();
+    let loaded = tokio::task::spawn_blocking(move || engine.import_facts(&body))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Snapshot import failed: {}", e)))?
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    info!("Imported a fact snapshot ({} facts)", loaded);
+
+    Ok(Json(SnapshotImportResponse {
+        facts_loaded: loaded,
+    }))
+}
+
+/// Look up the current state of a single flag
+fn find_rule_flag(state: &AppState, key: &str) -> ApiResult<Json<RuleFlag>> {
+    state
+        .engine
+        .rule_flags()
+        .into_iter()
+        .find(|flag| flag.key == key)
+        .map(|flag| Json(flag.into()))
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown rule flag: {}", key)))
+}
+
+/// Headers carrying the original request method (Traefik, then nginx)
+const METHOD_HEADERS: [&str; 2] = ["x-forwarded-method", "x-original-method"];
+
+/// Headers carrying the original request URI (Traefik, then nginx)
+const URI_HEADERS: [&str; 2] = ["x-forwarded-uri", "x-original-uri"];
+
+/// Header carrying the authenticated user set by the proxy
+const USER_HEADER: &str = "x-forwarded-user";
+
+/// Header carrying the code of each warning on a forward-auth permit
+const WARNING_HEADER: &str = "x-rune-warning";
+
+/// First non-empty header value among `names`
+fn first_header<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .filter_map(|name| headers.get(*name)?.to_str().ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Forward-auth endpoint for nginx `auth_request` and Traefik `forwardAuth`
+///
+/// The original method and URI are mapped onto an action and resource via
+/// the `[routes]` section. Answers 200 to let the request through and 403 to
+/// block it, including when no route matches. A 200 also carries the
+/// obligations of the permitting policies as headers, for the proxy to copy
+/// onto the upstream request (e.g. `authResponseHeaders` in Traefik), and
+/// the codes of their warnings in `x-rune-warning`. Every answer says in
+/// `Cache-Control` how long a proxy caching decisions may reuse it.
+pub async fn forward_auth(
+    State(state): State<AppState>,
+    location: Option<Extension<GeoLocation>>,
+    timing: Option<Extension<RequestTiming>>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let start = Instant::now();
+
+    let method = first_header(&headers, &METHOD_HEADERS)
+        .ok_or_else(|| ApiError::BadRequest("Missing original request method".to_string()))?;
+    let uri = first_header(&headers, &URI_HEADERS)
+        .ok_or_else(|| ApiError::BadRequest("Missing original request URI".to_string()))?;
+    let user = first_header(&headers, &[USER_HEADER])
+        .ok_or_else(|| ApiError::Unauthorized("Missing authenticated user".to_string()))?;
+
+    let Some(route) = state.engine.routes().resolve(method, uri) else {
+        debug!("No route mapping for {} {}", method, uri);
+        metrics::record_authorization("deny", start.elapsed().as_secs_f64(), false);
+        return Ok((StatusCode::FORBIDDEN, [("x-rune-decision", "DENY")]).into_response());
+    };
+
+    let request = request_builder(&location)
+        .principal(Principal::parse(user))
+        .action(Action::new(&route.action))
+        .resource(Resource::parse(&route.resource))
+        .build()
+        .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))?;
+    let generation = state.engine.generation();
+    let artifact = audit_artifact(&state);
+    let deadline = timing.and_then(|Extension(timing)| timing.deadline());
+    let result = state.engine.authorize_async(&request, deadline).await?;
+    watch_decision(&state, &headers, &request, &result);
+
+    let decision = Decision::from(result.decision);
+    let (status, decision_str) = match decision {
+        Decision::Permit => (StatusCode::OK, "permit"),
+        Decision::Deny => (StatusCode::FORBIDDEN, "deny"),
+        Decision::Forbid => (StatusCode::FORBIDDEN, "forbid"),
+    };
+    metrics::record_authorization(decision_str, start.elapsed().as_secs_f64(), result.cached);
+    if result.cached {
+        metrics::record_cache_saving(result.evaluation_time_ns);
+    }
+    metrics::record_evaluation_failures(&result.failures);
+    metrics::record_warnings(&result.warnings);
+    if result.coalesced {
+        metrics::record_coalesced_request();
+    }
+
+    info!(
+        request_id = %crate::logging::request_id(&headers),
+        principal_hash = %crate::logging::principal_hash(user),
+        decision = decision_str,
+        latency_ms = start.elapsed().as_secs_f64() * 1000.0,
+        generation,
+        artifact = %artifact,
+        "Forward auth: {} {} via '{}' -> {:?}",
+        method, uri, route.pattern, decision
+    );
+
+    let mut response = (
+        status,
+        [
+            ("x-rune-decision", decision_str.to_ascii_uppercase()),
+            ("x-rune-action", route.action),
+            ("x-rune-resource", route.resource),
+            (header::CACHE_CONTROL.as_str(), cache_control(&result)),
+        ],
+    )
+        .into_response();
+
+    // Obligations become headers the proxy copies onto the upstream request;
+    // they were validated when their policies were loaded
+    for obligation in &result.obligations {
+        match (
+            HeaderName::from_bytes(obligation.header.as_bytes()),
+            HeaderValue::from_str(&obligation.value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                response.headers_mut().append(name, value);
+            }
+            _ => warn!("Skipping unusable obligation header {}", obligation.header),
+        }
+    }
+    // Warning codes are restricted to header-safe characters
+    for warning in &result.warnings {
+        if let Ok(value) = HeaderValue::from_str(&warning.code) {
+            response.headers_mut().append(WARNING_HEADER, value);
+        }
+    }
+    Ok(response)
+}
+
+/// `Cache-Control` value telling a proxy how long it may reuse `result`
+fn cache_control(result: &AuthorizationResult) -> String {
+    match result.valid_for_ms / 1000 {
+        0 => "no-store".to_string(),
+        secs => format!("private, max-age={}", secs),
+    }
+}
+
+/// Fact changes for replicas following this server
+pub async fn replication_changes(
+    State(state): State<AppState>,
+    Query(params): Query<ChangesParams>,
+) -> ApiResult<Json<ChangeBatch>> {
+    let since = params
+        .log
+        .zip(params.since)
+        .map(|(log, seq)| ChangePosition { log, seq });
+    let limit = params.limit.unwrap_or(1000).min(10_000);
+    state
+        .engine
+        .changes_since(since, limit)
+        .map(Json)
+        .map_err(|_| ApiError::NotFound("Change log is disabled".to_string()))
+}
+
+/// How far this replica is behind its primary
+pub async fn replication_status(State(state): State<AppState>) -> ApiResult<Json<ReplicaStatus>> {
+    state
+        .engine
+        .replica_status()
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound("Not a replica".to_string()))
+}
+
+/// Recorded changes to base facts: what changed, when, and by whom
+pub async fn fact_history(
+    State(state): State<AppState>,
+    Query(params): Query<FactHistoryParams>,
+) -> ApiResult<Json<FactHistoryResponse>> {
+    let query = HistoryQuery {
+        predicate: params.predicate,
+        since_ms: params.since,
+        until_ms: params.until,
+        limit: Some(params.limit.unwrap_or(1000).min(10_000)),
+    };
+    state
+        .engine
+        .fact_history(&query)
+        .map(|events| Json(FactHistoryResponse { events }))
+        .map_err(|_| ApiError::NotFound("Fact history is disabled".to_string()))
+}
+
+/// Base facts as they were at a past time, rebuilt from the fact history
+pub async fn facts_as_of(
+    State(state): State<AppState>,
+    Query(params): Query<FactsAsOfParams>,
+) -> ApiResult<Json<FactsAsOfResponse>> {
+    if state.engine.fact_history(&HistoryQuery::default()).is_err() {
+        return Err(ApiError::NotFound("Fact history is disabled".to_string()));
+    }
+    let mut facts = state
+        .engine
+        .facts_as_of(params.at)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if let Some(predicate) = &params.predicate {
+        facts.retain(|fact| fact.predicate.as_ref() == predicate);
+    }
+    Ok(Json(FactsAsOfResponse {
+        at_ms: params.at,
+        facts,
+    }))
+}
+
+/// Decisions compared with the mirror's secondary so far
+pub async fn mirror_report(State(state): State<AppState>) -> ApiResult<Json<MirrorReport>> {
+    state
+        .mirror
+        .as_ref()
+        .map(|mirror| Json(mirror.report()))
+        .ok_or_else(|| ApiError::NotFound("Mirroring is not configured".to_string()))
+}
+
+/// Latency objectives and the error budget left over the current window
+pub async fn slo_report() -> Json<SloReport> {
+    Json(metrics::slo_report())
+}
+
+/// Open a session installing per-login facts for a principal
+pub async fn open_session(
+    State(state): State<AppState>,
+    Json(req): Json<OpenSessionRequest>,
+) -> ApiResult<(StatusCode, Json<SessionResponse>)> {
+    let principal = Principal::parse(&req.principal);
+    let ttl = req.ttl_secs.map(Duration::from_secs);
+    let session = state
+        .engine
+        .open_session(&principal, &req.attributes, ttl)?;
+    info!(
+        "Session {} opened for '{}' with {} facts",
+        session.id,
+        session.principal,
+        session.facts.len()
+    );
+    Ok((StatusCode::CREATED, Json(session.into())))
+}
+
+/// List open sessions
+pub async fn list_sessions(State(state): State<AppState>) -> Json<SessionsResponse> {
+    let sessions = state
+        .engine
+        .sessions()
+        .into_iter()
+        .map(SessionResponse::from)
+        .collect();
+    Json(SessionsResponse { sessions })
+}
+
+/// Close a session, retracting its facts
+pub async fn close_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !state.engine.close_session(&id) {
+        return Err(ApiError::NotFound(format!("Unknown session: {}", id)));
+    }
+    info!("Session {} closed", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Ask for access a principal was refused (see
+/// [`rune_core::access_requests`])
+///
+/// 201 with the new request, or 200 with the one already pending.
+pub async fn open_access_request(
+    State(state): State<AppState>,
+    Json(req): Json<OpenAccessRequest>,
+) -> ApiResult<(StatusCode, Json<AccessRequestResponse>)> {
+    let request = RequestBuilder::new()
+        .principal(Principal::parse(&req.principal))
+        .action(Action::new(&req.action))
+        .resource(Resource::parse(&req.resource))
+        .build()?;
+    let (access, new) = state.engine.request_access(&request, req.justification)?;
+    if !new {
+        return Ok((StatusCode::OK, Json(access.into())));
+    }
+    info!(
+        "Access request {}: {} asks to {} {}",
+        access.id, req.principal, access.action, req.resource
+    );
+    access::record(state.access_notifier.as_deref(), &access);
+    Ok((StatusCode::CREATED, Json(access.into())))
+}
+
+/// List access requests, optionally only those with `?status=`
+pub async fn list_access_requests(
+    State(state): State<AppState>,
+    Query(params): Query<AccessRequestParams>,
+) -> ApiResult<Json<AccessRequestsResponse>> {
+    let status = params
+        .status
+        .map(|status| status.parse())
+        .transpose()
+        .map_err(|e: RUNEError| ApiError::BadRequest(e.to_string()))?;
+    let requests = state
+        .engine
+        .access_requests(status)
+        .into_iter()
+        .map(AccessRequestResponse::from)
+        .collect();
+    Ok(Json(AccessRequestsResponse { requests }))
+}
+
+/// Show one access request
+pub async fn get_access_request(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<AccessRequestResponse>> {
+    state
+        .engine
+        .access_request(id)
+        .map(|access| Json(access.into()))
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown access request: {}", id)))
+}
+
+/// Approve a pending access request, recording its approval fact
+pub async fn approve_access_request(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+    review: Option<Json<ReviewAccessRequest>>,
+) -> ApiResult<Json<AccessRequestResponse>> {
+    let origin = change_origin(&state, &headers)?;
+    let note = review.and_then(|Json(review)| review.note);
+    let reviewed = state.engine.approve_access(id, origin, note);
+    reviewed_access(&state, id, reviewed)
+}
+
+/// Deny a pending access request
+pub async fn deny_access_request(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+    review: Option<Json<ReviewAccessRequest>>,
+) -> ApiResult<Json<AccessRequestResponse>> {
+    let origin = change_origin(&state, &headers)?;
+    let note = review.and_then(|Json(review)| review.note);
+    let reviewed = state.engine.deny_access(id, origin, note);
+    reviewed_access(&state, id, reviewed)
+}
+
+/// 404 for an unknown request, 409 for one already reviewed
+fn reviewed_access(
+    state: &AppState,
+    id: u64,
+    reviewed: rune_core::Result<Option<rune_core::AccessRequest>>,
+) -> ApiResult<Json<AccessRequestResponse>> {
+    let access = reviewed
+        .map_err(|e| ApiError::Conflict(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown access request: {}", id)))?;
+    info!(
+        "Access request {} {} by {}",
+        access.id,
+        access.status,
+        access.reviewer.as_deref().unwrap_or("unknown")
+    );
+    access::record(state.access_notifier.as_deref(), &access);
+    Ok(Json(access.into()))
+}
+
+/// Summarize a principal's roles, groups, checks and policies
+///
+/// Policies are listed by scope; conditions that depend on the request are
+/// not evaluated (see [`rune_core::permissions`]).
+pub async fn permission_summary(
+    State(state): State<AppState>,
+    Path(principal): Path<String>,
+) -> ApiResult<Json<PermissionSummaryResponse>> {
+    let engine = state.engine.clone();
+    let parsed = Principal::parse(&principal);
+    let summary = tokio::task::spawn_blocking(move || engine.permission_summary(&parsed))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Permission summary failed: {}", e)))??;
+
+    Ok(Json(PermissionSummaryResponse {
+        principal,
+        roles: summary.roles,
+        groups: summary.groups,
+        checks: summary.checks,
+        permits: summary.permits,
+        forbids: summary.forbids,
+    }))
+}
+
+/// Origin of a change requested with `headers`: the actor whose token they
+/// present, or the anonymous actor (see [`crate::actors`])
+fn change_origin(state: &AppState, headers: &HeaderMap) -> ApiResult<ChangeOrigin> {
+    actors::change_origin(state.actor_tokens.as_deref(), headers)
+}
+
+/// 201 for a new grant or membership, 200 for one already in place
+fn granted(changed: rune_core::Result<bool>) -> ApiResult<StatusCode> {
+    match changed {
+        Ok(true) => Ok(StatusCode::CREATED),
+        Ok(false) => Ok(StatusCode::OK),
+        Err(e) => Err(ApiError::BadRequest(e.to_string())),
+    }
+}
+
+/// Grant a role to a principal or group (see [`rune_core::permissions`])
+pub async fn grant_role(
+    State(state): State<AppState>,
+    Path((principal, role)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<StatusCode> {
+    let origin = change_origin(&state, &headers)?;
+    let holder = Principal::parse(&principal).entity.id;
+    let status = granted(
+        state
+            .engine
+            .attributed(origin.clone())
+            .grant_role(&holder, &role),
+    )?;
+    info!("Role {} granted to {} by {}", role, holder, origin.actor);
+    Ok(status)
+}
+
+/// Revoke a role from a principal or group
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    Path((principal, role)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<StatusCode> {
+    let origin = change_origin(&state, &headers)?;
+    let holder = Principal::parse(&principal).entity.id;
+    let revoked = state
+        .engine
+        .attributed(origin.clone())
+        .revoke_role(&holder, &role)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if !revoked {
+        return Err(ApiError::NotFound(format!(
+            "{} does not hold role {}",
+            holder, role
+        )));
+    }
+    info!("Role {} revoked from {} by {}", role, holder, origin.actor);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Add a principal or group to a group
+pub async fn add_to_group(
+    State(state): State<AppState>,
+    Path((principal, group)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<StatusCode> {
+    let origin = change_origin(&state, &headers)?;
+    let member = Principal::parse(&principal).entity.id;
+    let status = granted(
+        state
+            .engine
+            .attributed(origin.clone())
+            .add_to_group(&member, &group),
+    )?;
+    info!("{} added to group {} by {}", member, group, origin.actor);
+    Ok(status)
+}
+
+/// Remove a principal or group from a group
+pub async fn remove_from_group(
+    State(state): State<AppState>,
+    Path((principal, group)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<StatusCode> {
+    let origin = change_origin(&state, &headers)?;
+    let member = Principal::parse(&principal).entity.id;
+    let removed = state
+        .engine
+        .attributed(origin.clone())
+        .remove_from_group(&member, &group)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if !removed {
+        return Err(ApiError::NotFound(format!(
+            "{} is not a member of {}",
+            member, group
+        )));
+    }
+    info!(
+        "{} removed from group {} by {}",
+        member, group, origin.actor
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List labelled facts, rules and policies matching `?selector=`
+pub async fn labels(
+    State(state): State<AppState>,
+    Query(params): Query<LabelsParams>,
+) -> ApiResult<Json<LabelsResponse>> {
+    let selector: LabelSelector = params
+        .selector
+        .as_deref()
+        .unwrap_or_default()
+        .parse()
+        .map_err(|e: RUNEError| ApiError::BadRequest(e.to_string()))?;
+    Ok(Json(LabelsResponse {
+        items: state.engine.labeled(&selector),
+    }))
+}
+
+/// Serialized facts buffered ahead of a slow client
+const FACT_STREAM_BUFFER: usize = 1024;
+
+/// Stream derived facts as NDJSON, or just their count with `?count=true`
+pub async fn derived_facts(
+    State(state): State<AppState>,
+    Query(params): Query<FactQueryParams>,
+) -> ApiResult<Response> {
+    let count_only = params.count;
+    let query = FactQuery::from(params);
+    let engine = state.engine.clone();
+    // Dropped with this future when the client disconnects, stopping the
+    // evaluation started on its behalf
+    let guard = state.shutdown.child().drop_guard();
+    let cancel = guard.token().clone();
+
+    // Fixpoint evaluation is CPU-bound; keep it off the async workers
+    if count_only {
+        let count =
+            tokio::task::spawn_blocking(move || engine.count_facts_cancellable(&query, &cancel))
+                .await
+                .map_err(|e| ApiError::Internal(format!("Fact query failed: {}", e)))??;
+        return Ok(Json(FactCountResponse { count }).into_response());
+    }
+
+    let facts =
+        tokio::task::spawn_blocking(move || engine.query_facts_cancellable(&query, &cancel))
+            .await
+            .map_err(|e| ApiError::Internal(format!("Fact query failed: {}", e)))??;
+    debug!("Streaming {} derived facts", facts.len());
+
+    // Serialize on a blocking thread and hand lines over a bounded channel so
+    // memory stays flat however many facts the query yields
+    let (tx, rx) = mpsc::channel::<Result<String, Infallible>>(FACT_STREAM_BUFFER);
+    tokio::task::spawn_blocking(move || {
+        for fact in facts {
+            let line = match serde_json::to_string(&fact) {
+                Ok(json) => json + "\n",
+                Err(e) => {
+                    error!("Failed to serialize fact: {}", e);
+                    break;
+                }
+            };
+            // Client went away
+            if tx.blocking_send(Ok(line)).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+/// Answer a goal from base and derived facts
+///
+/// Answers are cached until facts, rules or policies change, so dashboards
+/// polling the same goal do not re-evaluate the rules each time.
+pub async fn query(
+    State(state): State<AppState>,
+    timing: Option<Extension<RequestTiming>>,
+    Json(req): Json<QueryRequest>,
+) -> ApiResult<Json<QueryResponse>> {
+    let timing = timing.map(|Extension(timing)| timing).unwrap_or_default();
+    let goal = rune_core::parse_goal(&req.goal).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    timing.mark("parse");
+    timing.begin("evaluate");
+    let engine = state.engine.clone();
+    let guard = state.shutdown.child().drop_guard();
+    let cancel = guard.token().clone();
+    let answer = tokio::task::spawn_blocking(move || engine.query_cancellable(&goal, &cancel))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Query failed: {}", e)))??;
+    timing.mark("evaluate");
+    metrics::record_query(answer.cached);
+
+    Ok(Json(QueryResponse {
+        count: answer.facts.len(),
+        facts: answer.facts.to_vec(),
+        cached: answer.cached,
+    }))
+}
+
 /// Prometheus metrics endpoint
 pub async fn metrics() -> String {
     metrics::get_prometheus_metrics()
 }
 
+/// Response header carrying the generation an export was taken from
+const GENERATION_HEADER: HeaderName = HeaderName::from_static("x-rune-generation");
+
+/// Dump the active rules, policies and base facts
+pub async fn export(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+) -> ApiResult<Response> {
+    let format: ExportFormat = params
+        .format
+        .as_deref()
+        .unwrap_or("json")
+        .parse()
+        .map_err(|e: rune_core::RUNEError| ApiError::BadRequest(e.to_string()))?;
+
+    let engine = state.engine.clone();
+    let export = tokio::task::spawn_blocking(move || engine.export())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Export failed: {}", e)))??;
+    // Only the rune format can fail, on values RUNE syntax cannot express
+    let body = export
+        .render(format)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    info!(
+        "Exported generation {} as {} ({} rules, {} policies, {} facts)",
+        export.generation,
+        format,
+        export.rules.len(),
+        export.policies.len(),
+        export.facts.len()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (GENERATION_HEADER, export.generation.to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// The traffic a policy or rule can affect, as (action, resource type,
+/// principal type) constraints read from its scope
+pub async fn policy_scope(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Response> {
+    let index = state.engine.policy_index();
+    let scope = index
+        .get(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown policy or rule: {}", id)))?;
+    Ok((
+        [(GENERATION_HEADER, index.generation.to_string())],
+        Json(scope),
+    )
+        .into_response())
+}
+
+/// Predicates, entity types, actions and context keys the configuration
+/// declares or the engine has seen, for policy editors to complete and
+/// check names against (see [`rune_core::vocabulary`])
+pub async fn vocabulary(State(state): State<AppState>) -> ApiResult<Json<rune_core::Vocabulary>> {
+    let engine = state.engine.clone();
+    let vocabulary = tokio::task::spawn_blocking(move || engine.vocabulary())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Vocabulary failed: {}", e)))?;
+    Ok(Json(vocabulary))
+}
+
+/// Wait for the decision of a request to change, as a long poll or a
+/// server-sent event stream (see [`crate::watch`])
+pub async fn watch(
+    State(state): State<AppState>,
+    Query(params): Query<WatchParams>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let request = RequestBuilder::new()
+        .principal(Principal::parse(&params.principal))
+        .action(Action::new(&params.action))
+        .resource(Resource::parse(&params.resource))
+        // Explained requests skip the decision cache, which would hold a
+        // denial past a grant until its TTL ran out
+        .explain(true)
+        .build()
+        .map_err(|e| ApiError::BadRequest(format!("Invalid request: {}", e)))?;
+    let watch = DecisionWatch::new(&state, request);
+    let mut current = watch.evaluate().await?;
+    current.changed = params.decision.is_some_and(|seen| seen != current.decision);
+
+    let streaming = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    if streaming {
+        return Ok(watch::events(watch, current).into_response());
+    }
+
+    if params.decision.is_none() || current.changed {
+        return Ok(Json(current).into_response());
+    }
+    let timeout = params
+        .timeout_ms
+        .map_or(watch::DEFAULT_TIMEOUT, Duration::from_millis)
+        .min(watch::MAX_TIMEOUT);
+    let changed = watch
+        .changed_from(current.clone(), Some(Instant::now() + timeout))
+        .await?;
+    Ok(Json(changed.unwrap_or(current)).into_response())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_parse_principal_with_type() {
-        let principal = parse_principal("User:alice");
+        let principal = Principal::parse("User:alice");
         assert_eq!(&*principal.entity.entity_type, "User");
         assert_eq!(&*principal.entity.id, "alice");
     }
 
     #[test]
     fn test_parse_principal_without_type() {
-        let principal = parse_principal("bob");
+        let principal = Principal::parse("bob");
         assert_eq!(&*principal.entity.entity_type, "User");
         assert_eq!(&*principal.entity.id, "bob");
     }
 
     #[test]
     fn test_parse_principal_with_colon_in_id() {
-        let principal = parse_principal("Service:api:v2:production");
+        let principal = Principal::parse("Service:api:v2:production");
         assert_eq!(&*principal.entity.entity_type, "Service");
         assert_eq!(&*principal.entity.id, "api:v2:production");
     }
 
     #[test]
     fn test_parse_principal_empty() {
-        let principal = parse_principal("");
+        let principal = Principal::parse("");
         assert_eq!(&*principal.entity.entity_type, "User");
         assert_eq!(&*principal.entity.id, "");
     }
@@ -313,7 +2015,7 @@ mod tests {
         ];
 
         for (input, expected_type, expected_id) in test_cases {
-            let principal = parse_principal(input);
+            let principal = Principal::parse(input);
             assert_eq!(&*principal.entity.entity_type, expected_type);
             assert_eq!(&*principal.entity.id, expected_id);
         }
@@ -321,28 +2023,28 @@ mod tests {
 
     #[test]
     fn test_parse_resource_with_type() {
-        let resource = parse_resource("File:/tmp/data.txt");
+        let resource = Resource::parse("File:/tmp/data.txt");
         assert_eq!(&*resource.entity.entity_type, "File");
         assert_eq!(&*resource.entity.id, "/tmp/data.txt");
     }
 
     #[test]
     fn test_parse_resource_without_type() {
-        let resource = parse_resource("/var/log/app.log");
+        let resource = Resource::parse("/var/log/app.log");
         assert_eq!(&*resource.entity.entity_type, "Resource");
         assert_eq!(&*resource.entity.id, "/var/log/app.log");
     }
 
     #[test]
     fn test_parse_resource_with_colon_in_path() {
-        let resource = parse_resource("Database:postgres://localhost:5432/mydb");
+        let resource = Resource::parse("Database:postgres://localhost:5432/mydb");
         assert_eq!(&*resource.entity.entity_type, "Database");
         assert_eq!(&*resource.entity.id, "postgres://localhost:5432/mydb");
     }
 
     #[test]
     fn test_parse_resource_empty() {
-        let resource = parse_resource("");
+        let resource = Resource::parse("");
         assert_eq!(&*resource.entity.entity_type, "Resource");
         assert_eq!(&*resource.entity.id, "");
     }
@@ -358,7 +2060,7 @@ mod tests {
         ];
 
         for (input, expected_type, expected_id) in test_cases {
-            let resource = parse_resource(input);
+            let resource = Resource::parse(input);
             assert_eq!(&*resource.entity.entity_type, expected_type);
             assert_eq!(&*resource.entity.id, expected_id);
         }
@@ -403,7 +2105,7 @@ mod tests {
         ];
 
         for (input, expected_type, expected_id) in test_cases {
-            let principal = parse_principal(input);
+            let principal = Principal::parse(input);
             assert_eq!(&*principal.entity.entity_type, expected_type);
             assert_eq!(&*principal.entity.id, expected_id);
         }
@@ -430,7 +2132,7 @@ mod tests {
         ];
 
         for (input, expected_type, expected_id) in test_cases {
-            let resource = parse_resource(input);
+            let resource = Resource::parse(input);
             assert_eq!(&*resource.entity.entity_type, expected_type);
             assert_eq!(&*resource.entity.id, expected_id);
         }
@@ -438,28 +2140,28 @@ mod tests {
 
     #[test]
     fn test_parse_principal_with_only_colon() {
-        let principal = parse_principal(":");
+        let principal = Principal::parse(":");
         assert_eq!(&*principal.entity.entity_type, "");
         assert_eq!(&*principal.entity.id, "");
     }
 
     #[test]
     fn test_parse_resource_with_only_colon() {
-        let resource = parse_resource(":");
+        let resource = Resource::parse(":");
         assert_eq!(&*resource.entity.entity_type, "");
         assert_eq!(&*resource.entity.id, "");
     }
 
     #[test]
     fn test_parse_principal_with_multiple_colons() {
-        let principal = parse_principal("Type:part1:part2:part3");
+        let principal = Principal::parse("Type:part1:part2:part3");
         assert_eq!(&*principal.entity.entity_type, "Type");
         assert_eq!(&*principal.entity.id, "part1:part2:part3");
     }
 
     #[test]
     fn test_parse_resource_with_windows_path() {
-        let resource = parse_resource("File:C:\\Users\\Documents\\file.txt");
+        let resource = Resource::parse("File:C:\\Users\\Documents\\file.txt");
         assert_eq!(&*resource.entity.entity_type, "File");
         assert_eq!(&*resource.entity.id, "C:\\Users\\Documents\\file.txt");
     }
@@ -0,0 +1,555 @@
+//! Idempotency keys for management plane mutations
+//!
+//! Automation that retries a reload, a flag change or a session login
+//! cannot tell whether a timed-out attempt was applied. With an
+//! `Idempotency-Key` header on a `POST`, `PUT`, `PATCH` or `DELETE`, the
+//! first response for that key is kept for `RUNE_IDEMPOTENCY_RETENTION_SECS`
+//! (default one day) and retries are answered with it, marked
+//! `Idempotent-Replayed: true`, without running the handler again.
+//!
+//! - Reusing a key for a different method, path or body, or with different
+//!   credentials (`Authorization` or `X-Api-Key`), is answered with 422,
+//!   since the retry is not the request the key was first used for. One
+//!   caller can never be replayed another's response.
+//! - A retry that arrives while the first attempt is still running is
+//!   answered with 409. An attempt still running after
+//!   `RUNE_IDEMPOTENCY_PENDING_SECS` (default 300) no longer holds its key.
+//! - Server errors (5xx) are not kept, so a retry gets another attempt.
+//!
+//! At most `RUNE_IDEMPOTENCY_MAX_KEYS` (default 10,000) keys are held,
+//! running attempts included; past that the oldest response is forgotten
+//! first, and a new key is refused with 503 while every key held belongs
+//! to a running attempt. A retention of zero disables deduplication. Keys are held in memory, so they do not survive a restart
+//! and are not shared between servers.
+
+use crate::error::ApiError;
+use crate::metrics;
+use crate::state::AppState;
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Header a client names a mutation with
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Header marking a response as a replay of an earlier one
+pub const REPLAYED: &str = "idempotent-replayed";
+
+/// Longest accepted key
+const MAX_KEY_LEN: usize = 255;
+
+/// Largest request body that is fingerprinted; bigger requests are refused
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// Deduplication settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyConfig {
+    /// How long a response is replayed for; zero disables deduplication
+    pub retention: Duration,
+    /// How long a running first attempt holds its key
+    pub pending_timeout: Duration,
+    /// Most keys held at once, running attempts included
+    pub max_keys: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig {
+            retention: Duration::from_secs(24 * 60 * 60),
+            pending_timeout: Duration::from_secs(5 * 60),
+            max_keys: 10_000,
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    /// Read settings from `RUNE_IDEMPOTENCY_RETENTION_SECS`,
+    /// `RUNE_IDEMPOTENCY_PENDING_SECS` and `RUNE_IDEMPOTENCY_MAX_KEYS`,
+    /// falling back to the defaults
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.parse().ok()
+        }
+
+        let defaults = Self::default();
+        IdempotencyConfig {
+            retention: var("RUNE_IDEMPOTENCY_RETENTION_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.retention),
+            pending_timeout: var("RUNE_IDEMPOTENCY_PENDING_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.pending_timeout),
+            max_keys: var("RUNE_IDEMPOTENCY_MAX_KEYS").unwrap_or(defaults.max_keys),
+        }
+    }
+
+    /// Check whether requests are deduplicated at all
+    pub fn is_enabled(&self) -> bool {
+        !self.retention.is_zero() && self.max_keys > 0
+    }
+}
+
+/// A response kept for replay
+#[derive(Debug, Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl StoredResponse {
+    fn replay(&self) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+            .headers_mut()
+            .insert(REPLAYED, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+#[derive(Debug)]
+enum Slot {
+    /// The first attempt is still running, since `at`
+    Pending { fingerprint: [u8; 32], at: Instant },
+    /// The first attempt finished with `response`
+    Done {
+        fingerprint: [u8; 32],
+        response: StoredResponse,
+        at: Instant,
+    },
+}
+
+impl Slot {
+    fn fingerprint(&self) -> &[u8; 32] {
+        match self {
+            Slot::Pending { fingerprint, .. } | Slot::Done { fingerprint, .. } => fingerprint,
+        }
+    }
+
+    /// Whether the slot no longer holds its key at `now`
+    fn expired(&self, config: &IdempotencyConfig, now: Instant) -> bool {
+        match self {
+            Slot::Pending { at, .. } => now.duration_since(*at) >= config.pending_timeout,
+            Slot::Done { at, .. } => now.duration_since(*at) >= config.retention,
+        }
+    }
+}
+
+/// What to do with a request carrying a key
+#[derive(Debug)]
+enum Lookup {
+    /// First use of the key: run the handler
+    Claimed,
+    /// Answer with the earlier response
+    Replay(Response),
+    /// The first attempt has not finished
+    InProgress,
+    /// The key was used for a different request
+    Mismatch,
+    /// Every key held belongs to a running attempt
+    Full,
+}
+
+/// Responses by idempotency key
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    config: IdempotencyConfig,
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+impl IdempotencyStore {
+    /// Empty store
+    pub fn new(config: IdempotencyConfig) -> Self {
+        IdempotencyStore {
+            config,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Settings the store was created with
+    pub fn config(&self) -> &IdempotencyConfig {
+        &self.config
+    }
+
+    /// Number of keys with a kept or pending response
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no keys are held
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Slot>> {
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lookup(&self, key: &str, fingerprint: [u8; 32], now: Instant) -> Lookup {
+        let mut slots = self.lock();
+        if let Some(slot) = slots.get(key) {
+            if !slot.expired(&self.config, now) {
+                return if *slot.fingerprint() != fingerprint {
+                    Lookup::Mismatch
+                } else {
+                    match slot {
+                        Slot::Pending { .. } => Lookup::InProgress,
+                        Slot::Done { response, .. } => Lookup::Replay(response.replay()),
+                    }
+                };
+            }
+        }
+
+        // An expired slot for this key is replaced rather than evicted
+        let replacing = slots.contains_key(key);
+        if !replacing && slots.len() >= self.config.max_keys && !self.evict(&mut slots, now) {
+            return Lookup::Full;
+        }
+        slots.insert(
+            key.to_string(),
+            Slot::Pending {
+                fingerprint,
+                at: now,
+            },
+        );
+        Lookup::Claimed
+    }
+
+    /// Drop expired slots, then the oldest response if still full;
+    /// false if every slot left belongs to a running attempt
+    fn evict(&self, slots: &mut HashMap<String, Slot>, now: Instant) -> bool {
+        slots.retain(|_, slot| !slot.expired(&self.config, now));
+        if slots.len() < self.config.max_keys {
+            return true;
+        }
+        let oldest = slots
+            .iter()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Done { at, .. } => Some((*at, key)),
+                Slot::Pending { .. } => None,
+            })
+            .min()
+            .map(|(_, key)| key.clone());
+        match oldest {
+            Some(key) => slots.remove(&key).is_some(),
+            None => false,
+        }
+    }
+
+    fn complete(&self, key: &str, fingerprint: [u8; 32], response: Option<StoredResponse>) {
+        let mut slots = self.lock();
+        // A claim that outlived its timeout may have been taken over
+        if !matches!(slots.get(key), Some(Slot::Pending { fingerprint: held, .. }) if *held == fingerprint)
+        {
+            return;
+        }
+        match response {
+            Some(response) => {
+                slots.insert(
+                    key.to_string(),
+                    Slot::Done {
+                        fingerprint,
+                        response,
+                        at: Instant::now(),
+                    },
+                );
+            }
+            None => {
+                slots.remove(key);
+            }
+        }
+    }
+}
+
+/// Releases a claimed key if the handler never finishes, e.g. because the
+/// client disconnected, so a retry can run it
+struct Claim<'a> {
+    store: &'a IdempotencyStore,
+    key: &'a str,
+    fingerprint: [u8; 32],
+    done: bool,
+}
+
+impl Claim<'_> {
+    fn finish(mut self, response: Option<StoredResponse>) {
+        self.store.complete(self.key, self.fingerprint, response);
+        self.done = true;
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.store.complete(self.key, self.fingerprint, None);
+        }
+    }
+}
+
+/// Headers identifying the caller, whose values are part of a fingerprint
+const CREDENTIALS: [&str; 2] = ["authorization", crate::usage::API_KEY_HEADER];
+
+/// Hash of everything that makes two requests the same request, the
+/// caller's credentials included
+///
+/// Only the hash is kept, so credentials are never held in the store.
+fn fingerprint(method: &Method, target: &str, headers: &HeaderMap, body: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update([0]);
+    hasher.update(target);
+    hasher.update([0]);
+    for name in CREDENTIALS {
+        for value in headers.get_all(name) {
+            hasher.update(Sha256::digest(value.as_bytes()));
+        }
+        hasher.update([0]);
+    }
+    hasher.update(body);
+    hasher.finalize().into()
+}
+
+/// Middleware deduplicating mutations that carry an `Idempotency-Key`
+pub async fn dedupe(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(store) = state.idempotency.as_deref() else {
+        return next.run(request).await;
+    };
+    let mutating = matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY).filter(|_| mutating) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return ApiError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+            .into_response()
+        }
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                .into_response()
+        }
+    };
+    let target = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), |target| target.as_str());
+    let fingerprint = fingerprint(&parts.method, target, &parts.headers, &body);
+
+    match store.lookup(&key, fingerprint, Instant::now()) {
+        Lookup::Claimed => {}
+        Lookup::Replay(response) => {
+            debug!(key = %key, "Replaying response for idempotency key");
+            metrics::record_idempotent_replay();
+            return response;
+        }
+        Lookup::InProgress => {
+            return ApiError::Conflict(format!(
+                "A request with Idempotency-Key '{}' is still in progress",
+                key
+            ))
+            .into_response()
+        }
+        Lookup::Mismatch => {
+            return ApiError::Unprocessable(format!(
+                "Idempotency-Key '{}' was already used for a different request",
+                key
+            ))
+            .into_response()
+        }
+        Lookup::Full => {
+            return ApiError::ServiceUnavailable(
+                "Too many requests with an Idempotency-Key are in progress".into(),
+            )
+            .into_response()
+        }
+    }
+
+    let claim = Claim {
+        store,
+        key: &key,
+        fingerprint,
+        done: false,
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        claim.finish(None);
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, usize::MAX).await {
+        Ok(body) => {
+            claim.finish(Some(StoredResponse {
+                status: parts.status,
+                headers: parts.headers.clone(),
+                body: body.clone(),
+            }));
+            Response::from_parts(parts, Body::from(body))
+        }
+        Err(e) => {
+            claim.finish(None);
+            ApiError::Internal(format!("Failed to read response body: {}", e)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(status: StatusCode, body: &'static str) -> StoredResponse {
+        StoredResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_lookup_replays_and_rejects_reuse() {
+        let store = IdempotencyStore::new(IdempotencyConfig::default());
+        let now = Instant::now();
+        let first = fingerprint(&Method::POST, "/v1/sessions", &HeaderMap::new(), b"{}");
+        let other = fingerprint(
+            &Method::POST,
+            "/v1/sessions",
+            &HeaderMap::new(),
+            b"{\"ttlSecs\":5}",
+        );
+
+        assert!(matches!(store.lookup("k", first, now), Lookup::Claimed));
+        assert!(matches!(store.lookup("k", first, now), Lookup::InProgress));
+        store.complete("k", first, Some(stored(StatusCode::CREATED, "done")));
+
+        match store.lookup("k", first, now) {
+            Lookup::Replay(response) => {
+                assert_eq!(response.status(), StatusCode::CREATED);
+                assert_eq!(response.headers()[REPLAYED], "true");
+            }
+            other => panic!("expected a replay, got {:?}", other),
+        }
+        assert!(matches!(store.lookup("k", other, now), Lookup::Mismatch));
+    }
+
+    #[test]
+    fn test_expiry_and_eviction() {
+        let store = IdempotencyStore::new(IdempotencyConfig {
+            retention: Duration::from_secs(60),
+            pending_timeout: Duration::from_secs(10),
+            max_keys: 2,
+        });
+        let now = Instant::now();
+        let print = fingerprint(&Method::DELETE, "/v1/sessions/1", &HeaderMap::new(), b"");
+        for key in ["a", "b"] {
+            assert!(matches!(store.lookup(key, print, now), Lookup::Claimed));
+            store.complete(key, print, Some(stored(StatusCode::NO_CONTENT, "")));
+        }
+
+        // A third key pushes out the oldest
+        assert!(matches!(store.lookup("c", print, now), Lookup::Claimed));
+        assert_eq!(store.len(), 2);
+        assert!(matches!(store.lookup("a", print, now), Lookup::Claimed));
+
+        // Past the retention window the key starts over
+        let later = Instant::now() + Duration::from_secs(61);
+        store.complete("a", print, Some(stored(StatusCode::NO_CONTENT, "")));
+        assert!(matches!(store.lookup("a", print, later), Lookup::Claimed));
+    }
+
+    #[test]
+    fn test_running_attempts_count_and_expire() {
+        let store = IdempotencyStore::new(IdempotencyConfig {
+            retention: Duration::from_secs(60),
+            pending_timeout: Duration::from_secs(10),
+            max_keys: 2,
+        });
+        let now = Instant::now();
+        let print = fingerprint(&Method::POST, "/v1/admin/reload", &HeaderMap::new(), b"");
+        for key in ["a", "b"] {
+            assert!(matches!(store.lookup(key, print, now), Lookup::Claimed));
+        }
+
+        // Running attempts are not evicted, so no key is free
+        assert!(matches!(store.lookup("c", print, now), Lookup::Full));
+        assert_eq!(store.len(), 2);
+
+        // Past the timeout their keys are free again
+        let later = now + Duration::from_secs(11);
+        assert!(matches!(store.lookup("a", print, later), Lookup::Claimed));
+        assert!(matches!(store.lookup("c", print, later), Lookup::Claimed));
+        assert_eq!(store.len(), 2);
+
+        // The attempt that held `b` finishing late stores nothing
+        store.complete("b", print, Some(stored(StatusCode::OK, "late")));
+        assert!(matches!(store.lookup("b", print, later), Lookup::Full));
+    }
+
+    #[test]
+    fn test_credentials_are_part_of_the_fingerprint() {
+        let store = IdempotencyStore::new(IdempotencyConfig::default());
+        let now = Instant::now();
+        let caller = |token: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("authorization", HeaderValue::from_static(token));
+            fingerprint(&Method::PUT, "/v1/flags/beta", &headers, b"{}")
+        };
+        let alice = caller("Bearer alice");
+        assert!(matches!(store.lookup("k", alice, now), Lookup::Claimed));
+        store.complete("k", alice, Some(stored(StatusCode::OK, "alice's")));
+
+        // Another caller reusing the key is not replayed alice's response
+        assert!(matches!(
+            store.lookup("k", caller("Bearer mallory"), now),
+            Lookup::Mismatch
+        ));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            crate::usage::API_KEY_HEADER,
+            HeaderValue::from_static("key"),
+        );
+        let keyed = fingerprint(&Method::PUT, "/v1/flags/beta", &headers, b"{}");
+        assert_ne!(
+            keyed,
+            fingerprint(&Method::PUT, "/v1/flags/beta", &HeaderMap::new(), b"{}")
+        );
+        assert!(matches!(store.lookup("k", alice, now), Lookup::Replay(_)));
+    }
+
+    #[test]
+    fn test_abandoned_claim_is_released() {
+        let store = IdempotencyStore::new(IdempotencyConfig::default());
+        let print = fingerprint(&Method::POST, "/v1/admin/compact", &HeaderMap::new(), b"");
+        assert!(matches!(
+            store.lookup("k", print, Instant::now()),
+            Lookup::Claimed
+        ));
+        drop(Claim {
+            store: &store,
+            key: "k",
+            fingerprint: print,
+            done: false,
+        });
+        assert!(store.is_empty());
+    }
+}
@@ -3,13 +3,51 @@
 //! This crate provides an HTTP API for RUNE authorization engine,
 //! enabling remote authorization queries with sub-10ms latency.
 
+pub mod access;
+pub mod actors;
+pub mod anomaly;
 pub mod api;
+pub mod codec;
+pub mod compaction;
+pub mod detail;
 pub mod error;
+pub mod expiry;
+pub mod geoip;
 pub mod handlers;
+pub mod idempotency;
+pub mod listener;
+pub mod logging;
 pub mod metrics;
+pub mod mirror;
+pub mod otel_metrics;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod replication;
+pub mod router;
+pub mod shadow;
+pub mod signing;
+pub mod snapshot;
+pub mod startup;
 pub mod state;
+pub mod timeouts;
 pub mod tracing;
+pub mod usage;
+pub mod watch;
 
+pub use access::AccessNotifier;
+pub use actors::ActorTokens;
+pub use anomaly::{AnomalyConfig, AnomalyDetector};
 pub use api::{AuthorizeRequest, AuthorizeResponse, HealthResponse};
+pub use compaction::CompactionConfig;
+pub use detail::DetailShedding;
 pub use error::{ApiError, ApiResult};
+pub use geoip::{GeoIp, GeoIpConfig, GeoLocation};
+pub use idempotency::IdempotencyConfig;
+pub use listener::{ListenerConfig, ListenersConfig};
+pub use mirror::{Mirror, MirrorConfig};
+pub use replication::{Escalation, ReplicationConfig};
+pub use signing::ResponseSigner;
+pub use startup::{ConfigErrors, Environment, ServerConfig};
 pub use state::AppState;
+pub use timeouts::RouteTimeouts;
+pub use usage::UsageMeter;
@@ -0,0 +1,679 @@
+//! Listener configuration for the data and management planes
+//!
+//! By default one listener serves every endpoint. Setting
+//! `RUNE_MANAGEMENT_BIND_ADDRESS` moves the management endpoints (see
+//! [`crate::router`]) onto their own listener, so they can be bound to an
+//! internal interface or firewalled separately from authorization traffic.
+//! Each listener can terminate TLS with its own certificate.
+//!
+//! | Variable                        | Plane      | Default        |
+//! |---------------------------------|------------|----------------|
+//! | `BIND_ADDRESS`                  | data       | `0.0.0.0:8080` |
+//! | `RUNE_TLS_CERT`, `RUNE_TLS_KEY` | data       | plain HTTP     |
+//! | `RUNE_MANAGEMENT_BIND_ADDRESS`  | management | shared         |
+//! | `RUNE_MANAGEMENT_TLS_CERT`, `RUNE_MANAGEMENT_TLS_KEY` | management | plain HTTP |
+//! | `RUNE_REUSE_PORT`               | both       | `false`        |
+//!
+//! # Binary upgrades
+//!
+//! On Unix, `SIGUSR2` hands the listening sockets to a new `rune-server`:
+//!
+//! 1. Replace the binary on disk, then send the running process `SIGUSR2`.
+//! 2. It starts the binary at its own path with the same arguments and
+//!    environment, passing its listening sockets as inherited descriptors
+//!    (`RUNE_LISTEN_FDS`, from descriptor 3) and the write end of a
+//!    readiness pipe (`RUNE_UPGRADE_READY_FD`).
+//! 3. The new process loads its configuration, adopts the sockets instead
+//!    of binding, and reports on the pipe once it is serving.
+//! 4. The old process then shuts down as on `SIGTERM` (see [`Handle`]) and
+//!    exits. If the new process exits or does not report within
+//!    [`UPGRADE_TIMEOUT`], it is killed and the old one keeps serving.
+//!
+//! Both processes accept from the same socket, so connections queued on it
+//! when the old process stops accepting are accepted by the new one; none
+//! are reset.
+//! A service manager tracking the main PID must be told about the new
+//! process (systemd: `Type=notify` with `NotifyAccess=all`, or a
+//! `PIDFile`).
+//!
+//! `RUNE_REUSE_PORT=true` (Unix only) binds listeners with `SO_REUSEPORT`
+//! instead, so separately started processes can serve the same addresses.
+//! Each then has its own socket, and stopping one resets the connections
+//! the kernel had queued on its socket but it had not accepted yet; upgrade
+//! with `SIGUSR2` to avoid that.
+
+use anyhow::{bail, Context};
+use axum::extract::ConnectInfo;
+use axum::{Extension, Router};
+use axum_server::accept::{Accept, DefaultAcceptor};
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Default data plane address
+pub const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0:8080";
+
+/// PEM certificate chain and private key for a listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Certificate chain file
+    pub cert_path: PathBuf,
+    /// Private key file
+    pub key_path: PathBuf,
+}
+
+impl TlsConfig {
+    /// Read the certificate and key into a rustls configuration
+    pub async fn load(&self) -> anyhow::Result<RustlsConfig> {
+        // Only the ring provider is compiled in; installing fails harmlessly
+        // when another listener got there first
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        RustlsConfig::from_pem_file(&self.cert_path, &self.key_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to load TLS certificate {} and key {}",
+                    self.cert_path.display(),
+                    self.key_path.display()
+                )
+            })
+    }
+}
+
+/// Address and optional TLS settings of one listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerConfig {
+    /// Socket address to bind
+    pub addr: SocketAddr,
+    /// Terminate TLS with these credentials
+    pub tls: Option<TlsConfig>,
+}
+
+impl fmt::Display for ListenerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        write!(f, "{}://{}", scheme, self.addr)
+    }
+}
+
+/// Listeners for the data plane and, optionally, a separate management plane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenersConfig {
+    /// Authorization endpoints, plus management ones when not split out
+    pub data: ListenerConfig,
+    /// Dedicated management listener
+    pub management: Option<ListenerConfig>,
+    /// Bind with `SO_REUSEPORT` so other processes can bind the addresses
+    pub reuse_port: bool,
+}
+
+impl ListenersConfig {
+    /// Read the listener layout from the environment
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Read the listener layout through `lookup`, which maps variable names
+    /// to values
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let data_addr = lookup("BIND_ADDRESS").unwrap_or_else(|| DEFAULT_BIND_ADDRESS.to_string());
+        let data = ListenerConfig {
+            addr: parse_addr("BIND_ADDRESS", &data_addr)?,
+            tls: tls_config(&lookup, "RUNE_TLS_CERT", "RUNE_TLS_KEY")?,
+        };
+
+        let management_tls = tls_config(
+            &lookup,
+            "RUNE_MANAGEMENT_TLS_CERT",
+            "RUNE_MANAGEMENT_TLS_KEY",
+        )?;
+        let management = match lookup("RUNE_MANAGEMENT_BIND_ADDRESS") {
+            Some(addr) => Some(ListenerConfig {
+                addr: parse_addr("RUNE_MANAGEMENT_BIND_ADDRESS", &addr)?,
+                tls: management_tls,
+            }),
+            None if management_tls.is_some() => {
+                bail!("RUNE_MANAGEMENT_TLS_* requires RUNE_MANAGEMENT_BIND_ADDRESS")
+            }
+            None => None,
+        };
+
+        if let Some(management) = &management {
+            if management.addr == data.addr {
+                bail!(
+                    "Management and data planes cannot share address {}",
+                    data.addr
+                );
+            }
+        }
+
+        let reuse_port = match lookup("RUNE_REUSE_PORT") {
+            Some(value) => value
+                .parse()
+                .with_context(|| format!("Invalid RUNE_REUSE_PORT: {}", value))?,
+            None => false,
+        };
+        if reuse_port && !cfg!(unix) {
+            bail!("RUNE_REUSE_PORT is only supported on Unix");
+        }
+
+        Ok(ListenersConfig {
+            data,
+            management,
+            reuse_port,
+        })
+    }
+}
+
+fn parse_addr(name: &str, value: &str) -> anyhow::Result<SocketAddr> {
+    value
+        .parse()
+        .with_context(|| format!("Invalid {}: {}", name, value))
+}
+
+fn tls_config(
+    lookup: &impl Fn(&str) -> Option<String>,
+    cert_var: &str,
+    key_var: &str,
+) -> anyhow::Result<Option<TlsConfig>> {
+    match (lookup(cert_var), lookup(key_var)) {
+        (Some(cert), Some(key)) => Ok(Some(TlsConfig {
+            cert_path: cert.into(),
+            key_path: key.into(),
+        })),
+        (None, None) => Ok(None),
+        _ => bail!("{} and {} must be set together", cert_var, key_var),
+    }
+}
+
+/// Listening sockets of the data and management planes
+#[derive(Debug)]
+pub struct Sockets {
+    /// Data plane socket
+    pub data: TcpListener,
+    /// Management plane socket, when that plane is split out
+    pub management: Option<TcpListener>,
+}
+
+impl Sockets {
+    /// Adopt the sockets a previous process handed over (see the module
+    /// documentation), or bind new ones
+    pub fn open(config: &ListenersConfig) -> anyhow::Result<Self> {
+        #[cfg(unix)]
+        if let Ok(count) = std::env::var(LISTEN_FDS_VAR) {
+            return Self::inherit(config, &count);
+        }
+        Ok(Sockets {
+            data: bind(config.data.addr, config.reuse_port)?,
+            management: config
+                .management
+                .as_ref()
+                .map(|management| bind(management.addr, config.reuse_port))
+                .transpose()?,
+        })
+    }
+
+    /// Duplicate the descriptors, so they can be handed over while the
+    /// originals are served
+    pub fn try_clone(&self) -> anyhow::Result<Self> {
+        Ok(Sockets {
+            data: self.data.try_clone()?,
+            management: self
+                .management
+                .as_ref()
+                .map(TcpListener::try_clone)
+                .transpose()?,
+        })
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &TcpListener> {
+        std::iter::once(&self.data).chain(&self.management)
+    }
+
+    /// Take the `count` sockets inherited from descriptor 3 on
+    #[cfg(unix)]
+    fn inherit(config: &ListenersConfig, count: &str) -> anyhow::Result<Self> {
+        use std::os::fd::FromRawFd;
+
+        let count: usize = count
+            .parse()
+            .with_context(|| format!("Invalid {}: {}", LISTEN_FDS_VAR, count))?;
+        let configured = 1 + usize::from(config.management.is_some());
+        if count != configured {
+            bail!(
+                "{} listening sockets were handed over but {} are configured",
+                count,
+                configured
+            );
+        }
+        let take = |index: i32, config: &ListenerConfig| {
+            // SAFETY: the process that started this one put a listening
+            // socket at this descriptor, and nothing else here owns it
+            let listener = unsafe { TcpListener::from_raw_fd(FIRST_INHERITED_FD + index) };
+            adopt(listener, config.addr)
+        };
+        Ok(Sockets {
+            data: take(0, &config.data)?,
+            management: config
+                .management
+                .as_ref()
+                .map(|management| take(1, management))
+                .transpose()?,
+        })
+    }
+}
+
+/// Check that a handed-over socket listens on `addr` and prepare it for
+/// serving
+#[cfg(unix)]
+fn adopt(listener: TcpListener, addr: SocketAddr) -> anyhow::Result<TcpListener> {
+    let bound = listener
+        .local_addr()
+        .context("Handed-over descriptor is not a listening socket")?;
+    if bound != addr {
+        bail!(
+            "Handed-over socket listens on {} but {} is configured",
+            bound,
+            addr
+        );
+    }
+    listener.set_nonblocking(true)?;
+    // Inherited descriptors stay open across exec until marked otherwise
+    socket2::SockRef::from(&listener).set_cloexec(true)?;
+    Ok(listener)
+}
+
+/// Time connections accepted just before a shutdown get to send their
+/// request before they are asked to finish
+///
+/// A connection is told to finish only once it is idle, and one whose
+/// request has not been read yet looks idle; closing it with the request
+/// unread would reset it.
+pub const SHUTDOWN_SETTLE: Duration = Duration::from_secs(1);
+
+/// Starts the shutdown of the listeners serving with it
+///
+/// A graceful shutdown stops accepting at once, waits [`SHUTDOWN_SETTLE`],
+/// then lets connections finish the requests they are serving, up to the
+/// grace period, and closes them once idle.
+#[derive(Debug, Clone)]
+pub struct Handle {
+    /// Grace period, once a shutdown has started
+    shutdown: Arc<watch::Sender<Option<Option<Duration>>>>,
+    /// Listeners accepting connections
+    listening: Arc<watch::Sender<usize>>,
+}
+
+impl Default for Handle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handle {
+    /// Create a handle for listeners to serve with
+    pub fn new() -> Self {
+        Handle {
+            shutdown: Arc::new(watch::Sender::new(None)),
+            listening: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    /// Stop accepting and finish in-flight requests, giving them `grace`
+    /// if set
+    pub fn graceful_shutdown(&self, grace: Option<Duration>) {
+        self.shutdown.send_replace(Some(grace));
+    }
+
+    /// Wait until `listeners` listeners accept connections
+    pub async fn listening(&self, listeners: usize) {
+        let mut listening = self.listening.subscribe();
+        let _ = listening.wait_for(|count| *count >= listeners).await;
+    }
+
+    /// Wait for a graceful shutdown, returning its grace period
+    async fn stopping(&self) -> Option<Duration> {
+        let mut shutdown = self.shutdown.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail
+        let grace = shutdown.wait_for(Option::is_some).await.ok()?.flatten();
+        grace
+    }
+}
+
+/// Serve `app` on `listener` until `handle` shuts it down
+pub async fn serve(
+    config: &ListenerConfig,
+    listener: TcpListener,
+    app: Router,
+    handle: Handle,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::from_std(listener)
+        .with_context(|| format!("Listener {} failed", config))?;
+    match &config.tls {
+        Some(tls) => {
+            let acceptor = RustlsAcceptor::new(tls.load().await?);
+            accept_loop(listener, acceptor, app, handle).await
+        }
+        None => accept_loop(listener, DefaultAcceptor::new(), app, handle).await,
+    }
+    .with_context(|| format!("Listener {} failed", config))
+}
+
+/// Accept connections until a shutdown starts, then wait for the accepted
+/// ones to finish
+async fn accept_loop<A>(
+    listener: tokio::net::TcpListener,
+    acceptor: A,
+    app: Router,
+    handle: Handle,
+) -> std::io::Result<()>
+where
+    A: Accept<tokio::net::TcpStream, (), Service = ()> + Clone + Send + 'static,
+    A::Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    A::Future: Send,
+{
+    let mut connections = JoinSet::new();
+    handle.listening.send_modify(|count| *count += 1);
+    loop {
+        let (stream, peer) = tokio::select! {
+            biased;
+            _ = handle.stopping() => break,
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                // Out of descriptors or a connection aborted in the queue
+                Err(_) => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    continue;
+                }
+            },
+        };
+        let (acceptor, handle) = (acceptor.clone(), handle.clone());
+        // Peer addresses feed GeoIP enrichment
+        let app = app.clone().layer(Extension(ConnectInfo(peer)));
+        connections.spawn(async move {
+            if let Ok((stream, ())) = acceptor.accept(stream, ()).await {
+                serve_connection(stream, app, handle).await;
+            }
+        });
+    }
+    // Queued connections stay on the socket for whoever still accepts on it
+    drop(listener);
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// Serve one connection until it closes or a shutdown finishes it
+async fn serve_connection<S>(stream: S, app: Router, handle: Handle)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let builder = Builder::new(TokioExecutor::new());
+    let connection =
+        builder.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(app));
+    tokio::pin!(connection);
+
+    let grace = tokio::select! {
+        _ = connection.as_mut() => return,
+        grace = handle.stopping() => grace,
+    };
+    tokio::select! {
+        _ = connection.as_mut() => return,
+        _ = tokio::time::sleep(SHUTDOWN_SETTLE) => {}
+    }
+    connection.as_mut().graceful_shutdown();
+    match grace {
+        Some(grace) => {
+            let _ = tokio::time::timeout(grace, connection).await;
+        }
+        None => {
+            let _ = connection.await;
+        }
+    }
+}
+
+/// Pending connections a listener queues before refusing more
+const BACKLOG: i32 = 1024;
+
+/// Bind `addr`, with `SO_REUSEPORT` if `reuse_port` is set so other
+/// processes of the same user can bind it too
+fn bind(addr: SocketAddr, reuse_port: bool) -> anyhow::Result<TcpListener> {
+    let bind = || -> std::io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(reuse_port)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(BACKLOG)?;
+        Ok(socket.into())
+    };
+    if reuse_port {
+        bind().with_context(|| format!("Failed to bind {} with SO_REUSEPORT", addr))
+    } else {
+        bind().with_context(|| format!("Failed to bind {}", addr))
+    }
+}
+
+/// Number of listening sockets a new process is handed
+pub const LISTEN_FDS_VAR: &str = "RUNE_LISTEN_FDS";
+
+/// Descriptor a new process reports readiness on
+pub const READY_FD_VAR: &str = "RUNE_UPGRADE_READY_FD";
+
+/// First inherited descriptor, after standard input, output and error
+#[cfg(unix)]
+const FIRST_INHERITED_FD: i32 = 3;
+
+/// Time a new process gets to start serving before the upgrade is given up
+pub const UPGRADE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Start the binary at this process's path with the same arguments and
+/// environment, hand it `sockets`, and wait for it to start serving
+///
+/// Returns the new process's ID. On failure the new process is killed and
+/// this one should keep serving.
+#[cfg(unix)]
+pub async fn hand_over(sockets: &Sockets) -> anyhow::Result<u32> {
+    let binary = std::env::current_exe().context("Failed to locate the server binary")?;
+    let mut command = std::process::Command::new(binary);
+    command.args(std::env::args_os().skip(1));
+    hand_over_to(command, sockets).await
+}
+
+/// Run `command` with `sockets` and a readiness pipe handed over, and wait
+/// for it to report on the pipe
+#[cfg(unix)]
+async fn hand_over_to(
+    mut command: std::process::Command,
+    sockets: &Sockets,
+) -> anyhow::Result<u32> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+    use tokio::io::AsyncReadExt;
+
+    let (ready, ready_writer) =
+        std::os::unix::net::UnixStream::pair().context("Failed to create a readiness pipe")?;
+    let mut fds = [0; 3];
+    let mut count = 0;
+    for fd in sockets
+        .iter()
+        .map(AsRawFd::as_raw_fd)
+        .chain([ready_writer.as_raw_fd()])
+    {
+        fds[count] = fd;
+        count += 1;
+    }
+    command.env(LISTEN_FDS_VAR, (count - 1).to_string()).env(
+        READY_FD_VAR,
+        (FIRST_INHERITED_FD + count as i32 - 1).to_string(),
+    );
+    // SAFETY: `place_fds` makes only async-signal-safe calls and does not
+    // allocate, so it may run between fork and exec
+    unsafe {
+        command.pre_exec(move || place_fds(&fds[..count]));
+    }
+    let mut child = command
+        .spawn()
+        .context("Failed to start the new server binary")?;
+    // Only the child may hold the write end, or a crash would go unnoticed
+    drop(ready_writer);
+
+    ready.set_nonblocking(true)?;
+    let mut ready = tokio::net::UnixStream::from_std(ready)?;
+    let mut byte = [0; 1];
+    let outcome = tokio::time::timeout(UPGRADE_TIMEOUT, ready.read(&mut byte)).await;
+    if let Ok(Ok(1)) = outcome {
+        return Ok(child.id());
+    }
+    let _ = child.kill();
+    let _ = tokio::task::spawn_blocking(move || child.wait()).await;
+    match outcome {
+        Err(_) => bail!(
+            "New process did not start serving within {:?}",
+            UPGRADE_TIMEOUT
+        ),
+        Ok(Err(e)) => Err(e).context("Failed to read the new process's readiness"),
+        Ok(Ok(_)) => bail!("New process exited before it started serving"),
+    }
+}
+
+/// Move `fds` to consecutive descriptors from [`FIRST_INHERITED_FD`], left
+/// open across exec
+#[cfg(unix)]
+fn place_fds(fds: &[i32]) -> std::io::Result<()> {
+    let check = |result: i32| match result {
+        -1 => Err(std::io::Error::last_os_error()),
+        fd => Ok(fd),
+    };
+    // Copy every descriptor above the target range first, so none is
+    // overwritten before it has been moved
+    let above = FIRST_INHERITED_FD + fds.len() as i32;
+    let mut copies = [0; 3];
+    for (copy, fd) in copies.iter_mut().zip(fds) {
+        // SAFETY: duplicating a descriptor this process owns
+        *copy = check(unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, above) })?;
+    }
+    for (target, copy) in (FIRST_INHERITED_FD..).zip(&copies[..fds.len()]) {
+        // SAFETY: the target is free or about to be replaced; `dup2` leaves
+        // the new descriptor open across exec
+        check(unsafe { libc::dup2(*copy, target) })?;
+    }
+    Ok(())
+}
+
+/// Tell the process that handed its sockets over that this one is serving
+///
+/// Does nothing unless this process was started by [`hand_over`]; call it
+/// once.
+#[cfg(unix)]
+pub fn signal_ready() -> anyhow::Result<()> {
+    use std::io::Write;
+    use std::os::fd::FromRawFd;
+
+    let Ok(fd) = std::env::var(READY_FD_VAR) else {
+        return Ok(());
+    };
+    let fd: i32 = fd
+        .parse()
+        .with_context(|| format!("Invalid {}: {}", READY_FD_VAR, fd))?;
+    // SAFETY: the handing-over process put the write end of its readiness
+    // pipe at this descriptor, and nothing else here owns it
+    let mut ready = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+    ready
+        .write_all(b"1")
+        .context("Failed to report readiness to the previous process")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn from_vars(vars: &[(&str, &str)]) -> anyhow::Result<ListenersConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ListenersConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_single_listener_by_default() {
+        let config = from_vars(&[]).unwrap();
+        assert_eq!(config.data.to_string(), "http://0.0.0.0:8080");
+        assert!(config.management.is_none());
+        assert!(!config.reuse_port);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reuse_port_lets_a_second_process_bind() {
+        let config = from_vars(&[("RUNE_REUSE_PORT", "true")]).unwrap();
+        assert!(config.reuse_port);
+        assert!(from_vars(&[("RUNE_REUSE_PORT", "yes")]).is_err());
+
+        let old = bind("127.0.0.1:0".parse().unwrap(), true).unwrap();
+        let addr = old.local_addr().unwrap();
+        let new = bind(addr, true).unwrap();
+        assert_eq!(new.local_addr().unwrap(), addr);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_adopt_checks_the_handed_over_address() {
+        let listener = bind("127.0.0.1:0".parse().unwrap(), false).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let other: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let err = adopt(listener.try_clone().unwrap(), other).unwrap_err();
+        assert!(err.to_string().contains("configured"), "{}", err);
+
+        let adopted = adopt(listener, addr).unwrap();
+        assert_eq!(adopted.local_addr().unwrap(), addr);
+    }
+
+    #[test]
+    fn test_split_planes_with_tls() {
+        let config = from_vars(&[
+            ("BIND_ADDRESS", "0.0.0.0:8443"),
+            ("RUNE_TLS_CERT", "/etc/rune/data.pem"),
+            ("RUNE_TLS_KEY", "/etc/rune/data.key"),
+            ("RUNE_MANAGEMENT_BIND_ADDRESS", "10.0.0.5:9090"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.data.to_string(), "https://0.0.0.0:8443");
+        let management = config.management.unwrap();
+        assert_eq!(management.to_string(), "http://10.0.0.5:9090");
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        for vars in [
+            &[("BIND_ADDRESS", "localhost")][..],
+            &[("RUNE_TLS_CERT", "/etc/rune/data.pem")],
+            &[
+                ("RUNE_MANAGEMENT_TLS_CERT", "a"),
+                ("RUNE_MANAGEMENT_TLS_KEY", "b"),
+            ],
+            &[
+                ("BIND_ADDRESS", "127.0.0.1:8080"),
+                ("RUNE_MANAGEMENT_BIND_ADDRESS", "127.0.0.1:8080"),
+            ],
+        ] {
+            assert!(from_vars(vars).is_err(), "{:?}", vars);
+        }
+    }
+}
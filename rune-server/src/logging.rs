@@ -0,0 +1,360 @@
+//! Log output formats
+//!
+//! `RUNE_LOG_FORMAT` picks how the server writes its logs:
+//!
+//! - `console` (default): readable lines for a terminal
+//! - `json`: one JSON object per line on stdout, with `timestamp`, `level`,
+//!   `target`, `message` and every event field at the top level, for log
+//!   shippers
+//! - `journald`: native entries sent to systemd-journald's socket, with
+//!   `MESSAGE`, `PRIORITY`, `SYSLOG_IDENTIFIER` and `TARGET` plus every event
+//!   field upper-cased, so `journalctl DECISION=deny` filters on them
+//!
+//! Decision events carry `request_id` (the caller's `X-Request-Id`, or one
+//! generated for the request), `principal_hash`, `decision`, `latency_ms`
+//! and `generation` as fields rather than prose. The principal is logged
+//! only as a hash: a principal's decisions can be correlated without the
+//! log naming who they are.
+
+use sha2::{Digest, Sha256};
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
+
+/// Socket systemd-journald accepts native entries on
+pub const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Longest `X-Request-Id` taken from a caller; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// How the server writes its logs, from `RUNE_LOG_FORMAT`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Readable lines for a terminal
+    #[default]
+    Console,
+    /// JSON lines on stdout
+    Json,
+    /// Native systemd-journald entries
+    Journald,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "console" => Ok(LogFormat::Console),
+            "json" => Ok(LogFormat::Json),
+            "journald" => Ok(LogFormat::Journald),
+            other => anyhow::bail!(
+                "Invalid RUNE_LOG_FORMAT: {} (expected console, json or journald)",
+                other
+            ),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Read `RUNE_LOG_FORMAT`, console when unset
+    ///
+    /// `journald` is refused when the journal's socket is missing, so a
+    /// server does not start logging into the void.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let format = match std::env::var("RUNE_LOG_FORMAT") {
+            Ok(format) => format.parse()?,
+            Err(_) => LogFormat::Console,
+        };
+        if format == LogFormat::Journald && !std::path::Path::new(JOURNALD_SOCKET).exists() {
+            anyhow::bail!(
+                "RUNE_LOG_FORMAT=journald, but there is no journal socket at {}",
+                JOURNALD_SOCKET
+            );
+        }
+        Ok(format)
+    }
+}
+
+/// Install the global subscriber writing logs in `format`, exporting spans
+/// to OpenTelemetry as well when `otel_service` names the service
+pub fn init(format: LogFormat, otel_service: Option<&str>) -> anyhow::Result<()> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,rune=debug"));
+    let otel = otel_service
+        .map(crate::tracing::init_telemetry)
+        .transpose()?
+        .map(tracing_opentelemetry::OpenTelemetryLayer::new);
+
+    let console = (format == LogFormat::Console).then(tracing_subscriber::fmt::layer);
+    let json = (format == LogFormat::Json).then(|| {
+        // Span fields would repeat the principal the events only hash
+        tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(false)
+    });
+    let journald = match format {
+        LogFormat::Journald => Some(journald::JournaldLayer::connect(
+            std::path::Path::new(JOURNALD_SOCKET),
+            "rune-server",
+        )?),
+        _ => None,
+    };
+
+    Registry::default()
+        .with(filter)
+        .with(console)
+        .with(json)
+        .with(journald)
+        .with(otel)
+        .try_init()?;
+    Ok(())
+}
+
+/// The caller's `X-Request-Id`, or a new ID when it sent none or an
+/// unusable one
+pub fn request_id(headers: &axum::http::HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id)
+}
+
+/// A new request ID: 16 hex digits, unique within the process
+pub fn new_request_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(NEXT.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
+}
+
+/// First 16 hex digits of the SHA-256 of `principal`
+pub fn principal_hash(principal: &str) -> String {
+    Sha256::digest(principal.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(unix)]
+mod journald {
+    use std::fmt;
+    use std::io;
+    use std::os::unix::net::UnixDatagram;
+    use std::path::Path;
+    use tracing::field::{Field, Visit};
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+
+    /// Layer sending each event to the journal as one datagram
+    ///
+    /// Entries too large for a datagram are dropped rather than blocking
+    /// the request that logged them.
+    pub struct JournaldLayer {
+        socket: UnixDatagram,
+        identifier: String,
+    }
+
+    impl JournaldLayer {
+        /// Send entries to the journal socket at `path`, tagged with
+        /// `identifier`
+        pub fn connect(path: &Path, identifier: &str) -> io::Result<Self> {
+            let socket = UnixDatagram::unbound()?;
+            socket.connect(path)?;
+            Ok(JournaldLayer {
+                socket,
+                identifier: identifier.to_string(),
+            })
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for JournaldLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let metadata = event.metadata();
+            let mut entry = Vec::with_capacity(256);
+            put_field(
+                &mut entry,
+                "PRIORITY",
+                priority(metadata.level()).as_bytes(),
+            );
+            put_field(&mut entry, "SYSLOG_IDENTIFIER", self.identifier.as_bytes());
+            put_field(&mut entry, "TARGET", metadata.target().as_bytes());
+            if let Some(file) = metadata.file() {
+                put_field(&mut entry, "CODE_FILE", file.as_bytes());
+            }
+            if let Some(line) = metadata.line() {
+                put_field(&mut entry, "CODE_LINE", line.to_string().as_bytes());
+            }
+            event.record(&mut EntryVisitor(&mut entry));
+            let _ = self.socket.send(&entry);
+        }
+    }
+
+    /// Syslog priority of a level, as journald expects it
+    fn priority(level: &Level) -> &'static str {
+        match *level {
+            Level::ERROR => "3",
+            Level::WARN => "4",
+            Level::INFO => "6",
+            Level::DEBUG | Level::TRACE => "7",
+        }
+    }
+
+    /// Writes event fields as journal fields
+    struct EntryVisitor<'a>(&'a mut Vec<u8>);
+
+    impl Visit for EntryVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            put_field(self.0, &field_name(field.name()), value.as_bytes());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            put_field(
+                self.0,
+                &field_name(field.name()),
+                format!("{:?}", value).as_bytes(),
+            );
+        }
+    }
+
+    /// Journal field name for a tracing field: upper case, with anything
+    /// other than letters, digits and underscores replaced
+    ///
+    /// Leading underscores are dropped, since journald reserves those
+    /// names for fields it adds itself.
+    pub(super) fn field_name(name: &str) -> String {
+        if name == "message" {
+            return "MESSAGE".to_string();
+        }
+        let name: String = name
+            .trim_start_matches('_')
+            .chars()
+            .map(|c| match c {
+                'a'..='z' => c.to_ascii_uppercase(),
+                'A'..='Z' | '0'..='9' => c,
+                _ => '_',
+            })
+            .collect();
+        match name.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() => name,
+            _ => format!("F_{}", name),
+        }
+    }
+
+    /// Append one field in the journal's native format
+    ///
+    /// Values with a newline use the length-prefixed binary form.
+    pub(super) fn put_field(entry: &mut Vec<u8>, name: &str, value: &[u8]) {
+        entry.extend_from_slice(name.as_bytes());
+        if value.contains(&b'\n') {
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value);
+        entry.push(b'\n');
+    }
+}
+
+#[cfg(not(unix))]
+mod journald {
+    use std::io;
+    use std::path::Path;
+
+    /// Stand-in for platforms without a journal
+    pub struct JournaldLayer;
+
+    impl JournaldLayer {
+        pub fn connect(_path: &Path, _identifier: &str) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "journald logging is only available on Unix",
+            ))
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for JournaldLayer {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!(
+            "journald".parse::<LogFormat>().unwrap(),
+            LogFormat::Journald
+        );
+        assert!("syslog".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_request_ids_and_principal_hashes() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert_ne!(request_id(&headers), request_id(&headers));
+        headers.insert("x-request-id", "req-42".parse().unwrap());
+        assert_eq!(request_id(&headers), "req-42");
+        headers.insert("x-request-id", "x".repeat(200).parse().unwrap());
+        assert_eq!(request_id(&headers).len(), 16);
+
+        let hash = principal_hash("user:alice");
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, principal_hash("user:alice"));
+        assert_ne!(hash, principal_hash("user:bob"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_journald_entries() {
+        use std::os::unix::net::UnixDatagram;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let path = std::env::temp_dir().join(format!("rune-journal-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let journal = UnixDatagram::bind(&path).unwrap();
+        let layer = journald::JournaldLayer::connect(&path, "rune-test").unwrap();
+
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(
+                request_id = "req-1",
+                decision = "deny",
+                latency_ms = 1.5,
+                "Authorization\nfailed"
+            );
+        });
+
+        let mut buffer = vec![0; 4096];
+        let size = journal.recv(&mut buffer).unwrap();
+        let entry = &buffer[..size];
+        let text = String::from_utf8_lossy(entry);
+        assert!(text.contains("PRIORITY=4\n"));
+        assert!(text.contains("SYSLOG_IDENTIFIER=rune-test\n"));
+        assert!(text.contains("REQUEST_ID=req-1\n"));
+        assert!(text.contains("DECISION=deny\n"));
+        assert!(text.contains("LATENCY_MS=1.5\n"));
+        // The multi-line message is length-prefixed
+        let mut message = b"MESSAGE\n".to_vec();
+        message.extend_from_slice(&20u64.to_le_bytes());
+        message.extend_from_slice(b"Authorization\nfailed\n");
+        assert!(entry.windows(message.len()).any(|w| w == message));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_journald_field_names() {
+        assert_eq!(journald::field_name("message"), "MESSAGE");
+        assert_eq!(journald::field_name("principal_hash"), "PRINCIPAL_HASH");
+        assert_eq!(journald::field_name("otel.kind"), "OTEL_KIND");
+        assert_eq!(journald::field_name("_private"), "PRIVATE");
+        assert_eq!(journald::field_name("2fa"), "F_2FA");
+    }
+}
@@ -1,117 +1,489 @@
 //! RUNE HTTP Server binary
 
-use axum::{
-    routing::{get, post},
-    Router,
+use axum::Router;
+use rune_server::timeouts::RouteClass;
+use rune_server::{
+    compaction, expiry, geoip, listener, replication, router, snapshot, AccessNotifier,
+    AnomalyDetector, AppState, Escalation, Mirror, ServerConfig, UsageMeter,
 };
-use rune_core::RUNEEngine;
-use rune_server::{handlers, AppState};
-use std::net::SocketAddr;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::{
     compression::CompressionLayer,
     cors::{Any, CorsLayer},
     trace::TraceLayer,
 };
-use tracing::info;
+use tracing::{info, warn};
+
+/// jemalloc, for heap profiles
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Command-line usage
+const USAGE: &str = "Usage: rune-server [--check] [--config <file>]
+
+Options:
+  --check          Load and validate the configuration, then exit
+  --config <file>  RUNE file to serve (default: $RUNE_CONFIG)
+  -h, --help       Print this help";
+
+/// Command-line options
+#[derive(Debug, Default)]
+struct Options {
+    /// Validate the configuration and exit without binding a port
+    check: bool,
+    /// RUNE file to load, overriding `RUNE_CONFIG`
+    config: Option<PathBuf>,
+    /// Print usage and exit
+    help: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--config needs a file"))?;
+                options.config = Some(path.into());
+            }
+            "-h" | "--help" => options.help = true,
+            other => match other.strip_prefix("--config=") {
+                Some(path) => options.config = Some(path.into()),
+                None => anyhow::bail!("Unknown argument: {}", other),
+            },
+        }
+    }
+    Ok(options)
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize OpenTelemetry tracing
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    // Read and validate the whole configuration before anything starts, so
+    // a bad setting stops the server with every problem listed
+    let color = std::io::stderr().is_terminal();
+    let config = match ServerConfig::load(options.config.as_deref()) {
+        Ok(config) => config,
+        Err(errors) => {
+            eprint!("{}", errors.render(color));
+            std::process::exit(1);
+        }
+    };
+    if let Err(errors) = config.verify().await {
+        eprint!("{}", errors.render(color));
+        std::process::exit(1);
+    }
+    if options.check {
+        report_check(&config, color);
+        return Ok(());
+    }
+
+    // Initialize logging, with OpenTelemetry tracing when enabled
     let enable_otel = std::env::var("OTEL_ENABLED")
         .unwrap_or_else(|_| "false".to_string())
         .parse::<bool>()
         .unwrap_or(false);
 
+    rune_server::logging::init(config.log_format, enable_otel.then_some("rune-server"))?;
     if enable_otel {
-        rune_server::tracing::init_tracing_stack("rune-server")?;
         info!("OpenTelemetry tracing enabled");
-    } else {
-        // Fallback to simple console logging
-        use tracing_subscriber::{EnvFilter, FmtSubscriber};
-        let subscriber = FmtSubscriber::builder()
-            .with_env_filter(
-                EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| EnvFilter::new("info,rune=debug")),
-            )
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)?;
-        info!("Console logging enabled (set OTEL_ENABLED=true for OpenTelemetry)");
     }
 
     info!("Starting RUNE HTTP Server v{}", env!("CARGO_PKG_VERSION"));
 
-    // Initialize Prometheus metrics
-    rune_server::metrics::init_prometheus()?;
+    // Install the Prometheus and/or OTLP metrics exporters
+    let exporters = config.exporters;
+    let meter_provider = rune_server::metrics::init_exporters(exporters, "rune-server")?;
+    info!(
+        "Metrics exporters: prometheus={}, otlp={}",
+        exporters.prometheus, exporters.otlp
+    );
 
     // Initialize metric descriptions
     rune_server::metrics::init_metrics();
+    info!(
+        "Latency objectives over {:?}: {}",
+        config.slo.window,
+        config
+            .slo
+            .objectives
+            .iter()
+            .map(|o| format!("{}={}% within {:?}", o.operation, o.target, o.latency))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    rune_server::metrics::init_slo(config.slo.clone())?;
 
     // Create RUNE engine
-    let engine = Arc::new(RUNEEngine::new());
+    info!(
+        "Failure policy: datalog_error={}, datalog_timeout={} ({}ms), cedar_error={}",
+        config.engine.failure_policy.datalog_error,
+        config.engine.failure_policy.datalog_timeout,
+        config.engine.timeout_ms,
+        config.engine.failure_policy.cedar_error
+    );
+    let engine = Arc::new(config.build_engine()?);
+    if config.rune_file.is_none() {
+        if config.engine.allow_all_bootstrap {
+            warn!("No RUNE file loaded; permitting every request (RUNE_ALLOW_ALL_BOOTSTRAP)");
+        } else {
+            warn!("No RUNE file loaded; denying every request");
+        }
+    }
+    if let Some(file) = &config.rune_file {
+        info!(
+            "Loaded {}: {} rules, {} policies",
+            file.path.display(),
+            file.config.rules.len(),
+            file.config.policies.len()
+        );
+        for warning in file.config.warnings.diagnostics() {
+            warn!("{}: {}", file.path.display(), warning.message);
+        }
+    }
 
-    // TODO: Load configuration from file or environment
-    // engine.load_config("config.rune")?;
+    // Pick up the facts the previous run saved
+    if let Some(path) = &config.fact_snapshot {
+        match snapshot::restore(&engine, path)? {
+            Some(loaded) => info!("Loaded {} facts from {}", loaded, path.display()),
+            None => info!("No fact snapshot at {} yet", path.display()),
+        }
+    }
 
     // Create application state
     let debug = std::env::var("DEBUG").is_ok();
-    let state = AppState::with_debug(engine, debug);
-
-    // Build the application
-    let app = Router::new()
-        // Authorization endpoints
-        .route("/v1/authorize", post(handlers::authorize))
-        .route("/v1/authorize/batch", post(handlers::batch_authorize))
-        // Health checks
-        .route("/health/live", get(handlers::health_live))
-        .route("/health/ready", get(handlers::health_ready))
-        // Metrics
-        .route("/metrics", get(handlers::metrics))
-        // Add state
-        .with_state(state)
-        // Add middleware
-        .layer(CompressionLayer::new())
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        )
-        .layer(TraceLayer::new_for_http());
+    let mut state = AppState::with_debug(engine, debug);
+
+    // Sign decisions for callers that verify them downstream
+    if let Some(signer) = config.signer {
+        info!(
+            "Response signing enabled ({})",
+            rune_server::signing::ALGORITHM
+        );
+        state = state.with_signer(signer);
+    }
+
+    // Follow the primary's fact store as a read replica
+    if let Some(replication) = config.replication {
+        info!(
+            "Replica of {} (max staleness {:?}, escalating to {})",
+            replication.primary,
+            replication.max_staleness,
+            replication.escalate_to.as_deref().unwrap_or("nobody")
+        );
+        if let Some(url) = &replication.escalate_to {
+            state = state.with_escalation(Escalation::new(url));
+        }
+        replication::spawn_follower(state.engine.clone(), replication);
+    } else if config.engine.change_log > 0 {
+        info!(
+            "Keeping the last {} fact changes for replicas",
+            config.engine.change_log
+        );
+    }
+
+    // Compact the fact store in the background during quiet periods
+    if config.compaction.is_enabled() {
+        info!(
+            "Fact store compaction every {:?} when idle",
+            config.compaction.interval
+        );
+        compaction::spawn(state.engine.clone(), config.compaction);
+    }
+
+    // Remove facts whose time-to-live ran out between decisions
+    if !config.expiry_sweep.is_zero() {
+        expiry::spawn(state.engine.clone(), config.expiry_sweep);
+    }
+
+    // Answer retried management mutations instead of applying them twice
+    if config.idempotency.is_enabled() {
+        info!(
+            "Idempotency keys kept for {:?} (at most {})",
+            config.idempotency.retention, config.idempotency.max_keys
+        );
+        state = state.with_idempotency(config.idempotency);
+    }
+
+    // Add the client's country and region to authorization requests
+    if let Some(geoip) = config.geoip {
+        let geoip = Arc::new(geoip);
+        info!(
+            "GeoIP enrichment from {} (trust X-Forwarded-For: {})",
+            geoip.config().database.display(),
+            geoip.config().trust_forwarded_for
+        );
+        if !geoip.config().reload_interval.is_zero() {
+            geoip::spawn_reloader(geoip.clone());
+        }
+        state = state.with_geoip(geoip);
+    }
+
+    // Raise early warnings about unusual decisions
+    if let Some(anomaly) = config.anomaly {
+        info!(
+            "Anomaly detection over {:?} windows (webhook: {})",
+            anomaly.window,
+            anomaly.webhook.as_deref().unwrap_or("none")
+        );
+        state = state.with_anomaly_detector(AnomalyDetector::spawn(anomaly));
+    }
+
+    // Charge evaluation time to the tenants and API keys asking for it
+    state = state.with_usage_meter(UsageMeter::new(config.usage_tenant_attribute));
+
+    // Attribute management changes to the actors whose tokens they carry
+    match config.actor_tokens {
+        Some(tokens) => {
+            info!("Actor tokens: {}", tokens.len());
+            state = state.with_actor_tokens(tokens);
+        }
+        None => info!("No actor tokens; management changes are anonymous"),
+    }
+
+    // Tell reviewers about access requests
+    if let Some(url) = config.access_webhook {
+        info!("Access request webhook: {}", url);
+        state = state.with_access_notifier(AccessNotifier::spawn(url));
+    }
+
+    // Compare a sample of decisions with a secondary, e.g. an upgrade
+    if let Some(mirror) = config.mirror {
+        info!(
+            "Mirroring {:.2}% of decisions to {}",
+            mirror.sample_rate * 100.0,
+            mirror.target
+        );
+        state = state.with_mirror(Mirror::spawn(mirror));
+    }
 
-    // Get bind address from environment or use default
-    let addr: SocketAddr = std::env::var("BIND_ADDRESS")
-        .unwrap_or_else(|_| "0.0.0.0:8080".to_string())
-        .parse()?;
+    // Answer requests that run past their route's budget with 504
+    if config.timeouts.is_enabled() {
+        let budget = |class| match config.timeouts.budget(class) {
+            Some(limit) => format!("{:?}", limit),
+            None => "none".to_string(),
+        };
+        info!(
+            "Route timeouts: authorize={}, batch={}, query={}, management={}",
+            budget(RouteClass::Authorize),
+            budget(RouteClass::Batch),
+            budget(RouteClass::Query),
+            budget(RouteClass::Management)
+        );
+        state = state.with_timeouts(config.timeouts);
+    }
 
-    info!("Listening on {}", addr);
+    // Keep explanations from adding to the load when the engine is busy
+    if let Some(shedding) = config.detail_shedding {
+        info!(
+            "Reducing decision detail at {} pending evaluations",
+            shedding.pending
+        );
+        state = state.with_detail_shedding(shedding);
+    }
 
-    // Create the server
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    // Serve CPU and heap profiles to holders of the profiling token
+    #[cfg(feature = "profiling")]
+    if let Some(profiling) = config.profiling {
+        info!("Profiling endpoints enabled at /debug/pprof");
+        state = state.with_profiling(profiling);
+    }
 
-    // Run the server with graceful shutdown
-    let server = axum::serve(listener, app);
+    let listeners = config.listeners;
+    let engine = state.engine.clone();
+
+    // Adopt the sockets of the process this one replaces, or bind
+    let sockets = listener::Sockets::open(&listeners)?;
 
     // Set up shutdown signal handler
-    let shutdown_signal = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to install CTRL+C signal handler");
+    let handle = listener::Handle::new();
+    let shutdown = handle.clone();
+    let evaluations = state.shutdown.clone();
+    let handover = sockets.try_clone()?;
+    tokio::spawn(async move {
+        shutdown_signal(&handover).await;
         info!("Received shutdown signal, shutting down gracefully...");
+        // In-flight queries would outlast the grace period for nobody
+        evaluations.cancel();
+        shutdown.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+    });
+
+    // Run the listeners until shutdown; a failing listener stops the server
+    if listeners.reuse_port {
+        info!("Listeners bound with SO_REUSEPORT; other processes can bind their addresses");
+    }
+    let listener::Sockets { data, management } = sockets;
+    let listening = handle.clone();
+    let serving = async {
+        match (&listeners.management, management) {
+            (Some(config), Some(management)) => {
+                info!("Data plane listening on {}", listeners.data);
+                info!("Management plane listening on {}", config);
+                tokio::try_join!(
+                    listener::serve(
+                        &listeners.data,
+                        data,
+                        with_middleware(router::data_plane(state.clone())),
+                        handle.clone(),
+                    ),
+                    listener::serve(
+                        config,
+                        management,
+                        with_middleware(router::management_plane(state)),
+                        handle.clone(),
+                    ),
+                )
+                .map(|_| ())
+            }
+            _ => {
+                info!("Listening on {}", listeners.data);
+                listener::serve(
+                    &listeners.data,
+                    data,
+                    with_middleware(router::combined(state)),
+                    handle,
+                )
+                .await
+            }
+        }
+    };
+    // The previous process stops once this one serves its sockets
+    let planes = 1 + usize::from(listeners.management.is_some());
+    let ready = async move {
+        listening.listening(planes).await;
+        #[cfg(unix)]
+        listener::signal_ready()?;
+        Ok(())
     };
+    tokio::try_join!(serving, ready)?;
 
-    // Run server with graceful shutdown
-    server
-        .with_graceful_shutdown(shutdown_signal)
-        .await
-        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    // Save the facts for the next run, now that no request can change them
+    if let Some(path) = &config.fact_snapshot {
+        match snapshot::save(&engine, path) {
+            Ok(bytes) => info!("Saved facts to {} ({} bytes)", path.display(), bytes),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
 
     // Cleanup OpenTelemetry on shutdown
     if enable_otel {
         info!("Flushing OpenTelemetry traces...");
         rune_server::tracing::shutdown_telemetry();
     }
+    if let Some(provider) = meter_provider {
+        info!("Flushing OpenTelemetry metrics...");
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("Failed to flush OpenTelemetry metrics: {}", e);
+        }
+    }
 
     info!("Server shutdown complete");
     Ok(())
 }
+
+/// Time in-flight requests get to finish after a shutdown signal
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Wait for CTRL+C or, on Unix, `SIGTERM`, which service managers send, or
+/// a `SIGUSR2` upgrade that handed `sockets` to a new process (see
+/// [`listener`])
+async fn shutdown_signal(sockets: &listener::Sockets) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install CTRL+C signal handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(unix)]
+    let upgrade = async {
+        let mut signal =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined2())
+                .expect("Failed to install SIGUSR2 handler");
+        loop {
+            signal.recv().await;
+            info!("Received SIGUSR2, handing listeners to a new process...");
+            match listener::hand_over(sockets).await {
+                Ok(pid) => {
+                    info!(pid, "Handed listeners over to the new process");
+                    return;
+                }
+                Err(e) => warn!("Upgrade failed, still serving: {:#}", e),
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    #[cfg(not(unix))]
+    let upgrade = {
+        let _ = sockets;
+        std::future::pending::<()>()
+    };
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+        _ = upgrade => {}
+    }
+}
+
+/// Summarize a configuration that passed `--check`
+fn report_check(config: &ServerConfig, color: bool) {
+    match &config.rune_file {
+        Some(file) => {
+            println!(
+                "{}: {} rules, {} policies",
+                file.path.display(),
+                file.config.rules.len(),
+                file.config.policies.len()
+            );
+            if !file.config.warnings.is_empty() {
+                eprint!("{}", file.config.warnings.render(Some(&file.source), color));
+            }
+        }
+        None if config.engine.allow_all_bootstrap => println!(
+            "No RUNE file configured; the server would permit every request (bootstrap override)"
+        ),
+        None => println!("No RUNE file configured; the server would deny every request"),
+    }
+    match &config.listeners.management {
+        Some(management) => println!(
+            "Data plane: {}\nManagement plane: {}",
+            config.listeners.data, management
+        ),
+        None => println!("Listening: {}", config.listeners.data),
+    }
+    println!("Configuration OK");
+}
+
+fn with_middleware(app: Router) -> Router {
+    app.layer(CompressionLayer::new())
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any),
+        )
+        .layer(TraceLayer::new_for_http())
+}
@@ -1,7 +1,28 @@
 //! Prometheus metrics collection for RUNE server
+//!
+//! Alongside the raw counters, the module tracks latency objectives for the
+//! decision endpoints, such as 99.9% of `/v1/authorize` requests answered
+//! within 10ms. Compliance and the share of the error budget left over a
+//! rolling window are exported as `rune_slo_compliance` and
+//! `rune_slo_error_budget_remaining` and reported by `GET /v1/slo`, so
+//! alerts can fire on how fast the budget burns rather than on single slow
+//! requests:
+//!
+//! - `RUNE_SLO_WINDOW_SECS`: length of the rolling window (default 3600)
+//! - `RUNE_SLO_AUTHORIZE_TARGET`, `RUNE_SLO_AUTHORIZE_MS`: percentage of
+//!   single decisions that must succeed within the latency (default 99.9
+//!   within 10ms)
+//! - `RUNE_SLO_BATCH_TARGET`, `RUNE_SLO_BATCH_MS`: the same for batch
+//!   decisions (default 99 within 100ms)
+//!
+//! A request counts against its objective when it is slower than the
+//! latency or fails with a server error.
 
 use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
 
 /// Initialize all metric descriptions
 pub fn init_metrics() {
@@ -12,6 +33,10 @@ pub fn init_metrics() {
     );
     describe_counter!("rune_cache_hits_total", "Total number of cache hits");
     describe_counter!("rune_cache_misses_total", "Total number of cache misses");
+    describe_histogram!(
+        "rune_cache_saved_evaluation_seconds",
+        "Evaluation time each cache hit saved, as the time the cached decision originally took"
+    );
     describe_counter!(
         "rune_rule_evaluations_total",
         "Total number of rule evaluations"
@@ -25,6 +50,98 @@ pub fn init_metrics() {
         "Total number of configuration reload events"
     );
     describe_counter!("rune_errors_total", "Total number of errors");
+    describe_counter!(
+        "rune_evaluation_failures_total",
+        "Datalog and Cedar failures answered by the failure policy"
+    );
+    describe_counter!(
+        "rune_coalesced_requests_total",
+        "Requests answered by an identical in-flight evaluation"
+    );
+    describe_counter!(
+        "rune_prefetched_decisions_total",
+        "Decisions cached ahead of time by prefetch requests"
+    );
+    describe_counter!(
+        "rune_permitted_resource_checks_total",
+        "Candidate resources checked by permitted resource requests"
+    );
+    describe_counter!(
+        "rune_permitted_principal_checks_total",
+        "Candidate principals checked by permitted principal requests"
+    );
+    describe_counter!(
+        "rune_matrix_cells_total",
+        "Principal, action and resource combinations decided by matrix requests"
+    );
+    describe_counter!(
+        "rune_query_cache_hits_total",
+        "Goal queries answered from the query cache"
+    );
+    describe_counter!(
+        "rune_query_cache_misses_total",
+        "Goal queries that evaluated the rules"
+    );
+    describe_counter!(
+        "rune_replica_escalations_total",
+        "Requests a stale replica forwarded to its primary"
+    );
+    describe_counter!(
+        "rune_decision_anomalies_total",
+        "Forbid spikes and novel principal-resource pairs detected"
+    );
+    describe_counter!(
+        "rune_access_requests_total",
+        "Access requests opened, approved and denied, by new status"
+    );
+    describe_counter!(
+        "rune_mirror_comparisons_total",
+        "Mirrored decisions by outcome: match, mismatch, error or dropped"
+    );
+    describe_counter!(
+        "rune_shadow_decisions_total",
+        "Shadow evaluations against staged configurations by generation and outcome: match, mismatch, error or unknown"
+    );
+    describe_counter!(
+        "rune_usage_decisions_total",
+        "Data plane decisions by the tenant they are charged to"
+    );
+    describe_counter!(
+        "rune_usage_evaluation_microseconds_total",
+        "Evaluation time charged to each tenant; cached decisions cost nothing"
+    );
+    describe_counter!(
+        "rune_reduced_detail_responses_total",
+        "Decisions answered without proofs or rule lists because the engine was loaded"
+    );
+    describe_counter!(
+        "rune_idempotent_replays_total",
+        "Management mutations answered with the response to an earlier attempt"
+    );
+    describe_counter!(
+        "rune_route_timeouts_total",
+        "Requests answered with 504 for exceeding their route's budget"
+    );
+    describe_counter!(
+        "rune_decision_warnings_total",
+        "Warnings attached to permit decisions, by code"
+    );
+    describe_counter!(
+        "rune_fact_compactions_total",
+        "Total number of fact store compactions"
+    );
+    describe_counter!(
+        "rune_facts_expired_total",
+        "Facts removed by the background sweep after their time-to-live"
+    );
+    describe_counter!(
+        "rune_fact_compactions_deferred_total",
+        "Scheduled compactions postponed because of traffic"
+    );
+    describe_counter!(
+        "rune_fact_compaction_removed_total",
+        "Fact store entries removed by compaction"
+    );
 
     // Histograms
     describe_histogram!(
@@ -44,6 +161,14 @@ pub fn init_metrics() {
         "Cache lookup latency in seconds"
     );
     describe_histogram!("rune_batch_size", "Batch authorization request size");
+    describe_histogram!(
+        "rune_mirror_latency_seconds",
+        "Latency of mirrored decisions on this server and the secondary"
+    );
+    describe_histogram!(
+        "rune_fact_compaction_duration_seconds",
+        "Fact store compaction duration in seconds"
+    );
 
     // Gauges
     describe_gauge!("rune_loaded_rules_count", "Number of loaded Datalog rules");
@@ -60,52 +185,505 @@ pub fn init_metrics() {
         "rune_active_connections",
         "Number of active HTTP connections"
     );
+    describe_gauge!(
+        "rune_replica_pending_changes",
+        "Primary fact changes a replica has yet to apply"
+    );
+    describe_gauge!(
+        "rune_slo_compliance",
+        "Fraction of requests meeting their latency objective over the SLO window, by operation"
+    );
+    describe_gauge!(
+        "rune_slo_error_budget_remaining",
+        "Fraction of the error budget left over the SLO window, by operation; negative once overspent"
+    );
 }
 
 /// Record an authorization request
 pub fn record_authorization(decision: &str, latency_seconds: f64, cached: bool) {
-    counter!("rune_authorization_requests_total", 1, "decision" => decision.to_string());
-    histogram!("rune_authorization_latency_seconds", latency_seconds);
+    counter!("rune_authorization_requests_total", "decision" => decision.to_string()).increment(1);
+    histogram!("rune_authorization_latency_seconds").record(latency_seconds);
+    record_operation("authorize", latency_seconds, true);
 
     if cached {
-        counter!("rune_cache_hits_total", 1);
+        counter!("rune_cache_hits_total").increment(1);
     } else {
-        counter!("rune_cache_misses_total", 1);
+        counter!("rune_cache_misses_total").increment(1);
     }
 }
 
+/// Record the evaluation time a cache hit saved: `nanos`, what the cached
+/// decision originally took
+pub fn record_cache_saving(nanos: u64) {
+    histogram!("rune_cache_saved_evaluation_seconds").record(nanos as f64 / 1e9);
+}
+
 /// Record a batch authorization request
 pub fn record_batch_authorization(count: usize, latency_seconds: f64) {
-    histogram!("rune_batch_size", count as f64);
-    histogram!("rune_authorization_latency_seconds", latency_seconds, "type" => "batch");
+    histogram!("rune_batch_size").record(count as f64);
+    histogram!("rune_authorization_latency_seconds", "type" => "batch").record(latency_seconds);
+    record_operation("batch", latency_seconds, true);
+}
+
+/// Record a request to `operation` against its latency objective, if it
+/// has one; `ok` is false when the request failed
+pub fn record_operation(operation: &str, latency_seconds: f64, ok: bool) {
+    if let Some(status) = slo_tracker().record(operation, latency_seconds * 1000.0, ok) {
+        gauge!("rune_slo_compliance", "operation" => status.operation.clone())
+            .set(status.compliance);
+        gauge!("rune_slo_error_budget_remaining", "operation" => status.operation)
+            .set(status.error_budget_remaining);
+    }
 }
 
 /// Record rule evaluations
 pub fn record_rule_evaluations(count: usize) {
-    counter!("rune_rule_evaluations_total", count as u64);
+    counter!("rune_rule_evaluations_total").increment(count as u64);
 }
 
 /// Record policy evaluations
 pub fn record_policy_evaluations(count: usize) {
-    counter!("rune_policy_evaluations_total", count as u64);
+    counter!("rune_policy_evaluations_total").increment(count as u64);
 }
 
 /// Record an error
 pub fn record_error(error_type: &str) {
-    counter!("rune_errors_total", 1, "type" => error_type.to_string());
+    counter!("rune_errors_total", "type" => error_type.to_string()).increment(1);
+}
+
+/// Record dependency failures handled by the engine's failure policy
+pub fn record_evaluation_failures(failures: &[rune_core::FailureOutcome]) {
+    for failure in failures {
+        counter!(
+            "rune_evaluation_failures_total",
+            "class" => failure.class.as_str(),
+            "mode" => failure.mode.as_str()
+        )
+        .increment(1);
+    }
+}
+
+/// Record a request that exceeded its route's budget
+pub fn record_route_timeout(route: &'static str) {
+    counter!("rune_route_timeouts_total", "route" => route).increment(1);
+}
+
+/// Record the warnings attached to a permit
+pub fn record_warnings(warnings: &[rune_core::Warning]) {
+    for warning in warnings {
+        counter!("rune_decision_warnings_total", "code" => warning.code.clone()).increment(1);
+    }
+}
+
+/// Record a request that shared a concurrent evaluation's result
+pub fn record_coalesced_request() {
+    counter!("rune_coalesced_requests_total").increment(1);
+}
+
+/// Record decisions a prefetch request added to the cache
+pub fn record_prefetch(cached: usize) {
+    counter!("rune_prefetched_decisions_total").increment(cached as u64);
+}
+
+/// Record the candidates a permitted resource request checked
+pub fn record_permitted_resources(candidates: usize) {
+    counter!("rune_permitted_resource_checks_total").increment(candidates as u64);
+}
+
+/// Record the candidates a permitted principal request checked
+pub fn record_permitted_principals(candidates: usize) {
+    counter!("rune_permitted_principal_checks_total").increment(candidates as u64);
+}
+
+/// Record the actions a multi-action request decided
+pub fn record_action_checks(actions: usize, latency_seconds: f64) {
+    counter!("rune_action_checks_total").increment(actions as u64);
+    histogram!("rune_authorization_latency_seconds", "type" => "actions").record(latency_seconds);
+}
+
+/// Record the combinations a decision matrix decided
+pub fn record_matrix(cells: usize, latency_seconds: f64) {
+    counter!("rune_matrix_cells_total").increment(cells as u64);
+    histogram!("rune_authorization_latency_seconds", "type" => "matrix").record(latency_seconds);
+}
+
+/// Record a goal query, answered from the cache or not
+pub fn record_query(cached: bool) {
+    if cached {
+        counter!("rune_query_cache_hits_total").increment(1);
+    } else {
+        counter!("rune_query_cache_misses_total").increment(1);
+    }
+}
+
+/// Record a request a stale replica forwarded to its primary
+pub fn record_replica_escalation() {
+    counter!("rune_replica_escalations_total").increment(1);
+}
+
+/// Record a configuration reload, `applied`, `rejected` or `forbidden`
+pub fn record_reload(outcome: &'static str) {
+    counter!("rune_reload_events_total", "outcome" => outcome).increment(1);
+}
+
+/// Record an anomaly in the decision stream
+pub fn record_anomaly(kind: &'static str) {
+    counter!("rune_decision_anomalies_total", "kind" => kind).increment(1);
+}
+
+/// Record an access request reaching `status`
+pub fn record_access_request(status: &str) {
+    counter!("rune_access_requests_total", "status" => status.to_string()).increment(1);
+}
+
+/// Record the outcome of a decision mirrored to the secondary
+pub fn record_mirror_comparison(outcome: &'static str) {
+    counter!("rune_mirror_comparisons_total", "outcome" => outcome).increment(1);
+}
+
+/// Record how long `engine` (`primary` or `secondary`) took on a mirrored
+/// decision
+pub fn record_mirror_latency(engine: &'static str, seconds: f64) {
+    histogram!("rune_mirror_latency_seconds", "engine" => engine).record(seconds);
+}
+
+/// Record the outcome of a shadow evaluation against staged configuration
+/// `generation`
+pub fn record_shadow_decision(generation: String, outcome: &'static str) {
+    counter!("rune_shadow_decisions_total", "generation" => generation, "outcome" => outcome)
+        .increment(1);
+}
+
+/// Charge a decision costing `evaluation_ns` to `tenant`
+pub fn record_usage(tenant: String, evaluation_ns: u64) {
+    counter!("rune_usage_decisions_total", "tenant" => tenant.clone()).increment(1);
+    counter!("rune_usage_evaluation_microseconds_total", "tenant" => tenant)
+        .increment(evaluation_ns / 1000);
+}
+
+/// Record a response whose detail was reduced under load
+pub fn record_reduced_detail() {
+    counter!("rune_reduced_detail_responses_total").increment(1);
+}
+
+/// Record a retried mutation answered from the idempotency store
+pub fn record_idempotent_replay() {
+    counter!("rune_idempotent_replays_total").increment(1);
+}
+
+/// Record how many primary changes a replica has yet to apply
+pub fn record_replica_pending(pending: u64) {
+    gauge!("rune_replica_pending_changes").set(pending as f64);
+}
+
+/// Record a completed fact store compaction
+pub fn record_compaction(trigger: &str, stats: &rune_core::CompactionStats, seconds: f64) {
+    counter!("rune_fact_compactions_total", "trigger" => trigger.to_string()).increment(1);
+    counter!("rune_fact_compaction_removed_total", "reason" => "duplicate")
+        .increment(stats.duplicates_removed as u64);
+    counter!("rune_fact_compaction_removed_total", "reason" => "expired")
+        .increment(stats.expired_removed as u64);
+    histogram!("rune_fact_compaction_duration_seconds").record(seconds);
+    gauge!("rune_fact_store_entries").set(stats.facts_after as f64);
+}
+
+/// Record facts the background sweep removed after their time-to-live
+pub fn record_facts_expired(count: usize) {
+    counter!("rune_facts_expired_total").increment(count as u64);
+}
+
+/// Record a scheduled compaction postponed because of traffic
+pub fn record_compaction_deferred() {
+    counter!("rune_fact_compactions_deferred_total").increment(1);
 }
 
 /// Update gauge metrics
 pub fn update_engine_metrics(rules: usize, policies: usize, facts: usize, cache_size: usize) {
-    gauge!("rune_loaded_rules_count", rules as f64);
-    gauge!("rune_loaded_policies_count", policies as f64);
-    gauge!("rune_fact_store_entries", facts as f64);
-    gauge!("rune_cache_size_bytes", cache_size as f64);
+    gauge!("rune_loaded_rules_count").set(rules as f64);
+    gauge!("rune_loaded_policies_count").set(policies as f64);
+    gauge!("rune_fact_store_entries").set(facts as f64);
+    gauge!("rune_cache_size_bytes").set(cache_size as f64);
 }
 
 /// Update connection count
 pub fn update_connections(count: usize) {
-    gauge!("rune_active_connections", count as f64);
+    gauge!("rune_active_connections").set(count as f64);
+}
+
+/// Buckets each SLO window is divided into; requests leave the window a
+/// bucket at a time
+const SLO_BUCKETS: u64 = 60;
+
+/// Latency objective for one operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloObjective {
+    /// Operation the objective covers, `authorize` or `batch`
+    pub operation: String,
+    /// Percentage of requests that must succeed within `latency`
+    pub target: f64,
+    /// Latency within which a request meets the objective
+    pub latency: Duration,
+}
+
+impl SloObjective {
+    fn new(operation: &str, target: f64, latency_ms: u64) -> Self {
+        SloObjective {
+            operation: operation.to_string(),
+            target,
+            latency: Duration::from_millis(latency_ms),
+        }
+    }
+}
+
+/// Latency objectives and the window they are measured over
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloConfig {
+    /// Length of the rolling window
+    pub window: Duration,
+    /// One objective per operation
+    pub objectives: Vec<SloObjective>,
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        SloConfig {
+            window: Duration::from_secs(3600),
+            objectives: vec![
+                SloObjective::new("authorize", 99.9, 10),
+                SloObjective::new("batch", 99.0, 100),
+            ],
+        }
+    }
+}
+
+impl SloConfig {
+    /// Read the `RUNE_SLO_*` variables over the defaults
+    pub fn from_env() -> anyhow::Result<Self> {
+        fn var<T: std::str::FromStr>(name: &str) -> anyhow::Result<Option<T>>
+        where
+            T::Err: std::fmt::Display,
+        {
+            std::env::var(name)
+                .ok()
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        }
+
+        let mut config = Self::default();
+        if let Some(secs) = var::<u64>("RUNE_SLO_WINDOW_SECS")? {
+            if secs < SLO_BUCKETS {
+                anyhow::bail!(
+                    "Invalid RUNE_SLO_WINDOW_SECS: must be at least {}",
+                    SLO_BUCKETS
+                );
+            }
+            config.window = Duration::from_secs(secs);
+        }
+        for objective in &mut config.objectives {
+            let prefix = format!("RUNE_SLO_{}", objective.operation.to_ascii_uppercase());
+            if let Some(target) = var::<f64>(&format!("{}_TARGET", prefix))? {
+                if !(target > 0.0 && target < 100.0) {
+                    anyhow::bail!("Invalid {}_TARGET: must be above 0 and below 100", prefix);
+                }
+                objective.target = target;
+            }
+            if let Some(ms) = var::<u64>(&format!("{}_MS", prefix))? {
+                if ms == 0 {
+                    anyhow::bail!("Invalid {}_MS: must be at least 1", prefix);
+                }
+                objective.latency = Duration::from_millis(ms);
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Where one operation stands against its objective
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SloStatus {
+    /// Operation the objective covers
+    pub operation: String,
+    /// Percentage of requests that must meet the objective
+    pub target: f64,
+    /// Latency within which a request meets it, in milliseconds
+    pub latency_ms: f64,
+    /// Requests in the window
+    pub total: u64,
+    /// Requests in the window that met it
+    pub good: u64,
+    /// Fraction of requests that met it; 1 with no requests
+    pub compliance: f64,
+    /// Fraction of the error budget left; negative once overspent
+    pub error_budget_remaining: f64,
+}
+
+/// Every objective over the current window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SloReport {
+    /// Length of the rolling window, in seconds
+    pub window_secs: u64,
+    /// One status per objective
+    pub objectives: Vec<SloStatus>,
+}
+
+/// Rolling count of requests meeting one objective
+#[derive(Debug, Default)]
+struct SloWindow {
+    /// `(bucket, good, total)`, oldest first
+    buckets: VecDeque<(u64, u64, u64)>,
+    good: u64,
+    total: u64,
+}
+
+impl SloWindow {
+    /// Drop buckets that have left the window ending in `bucket`
+    fn advance(&mut self, bucket: u64) {
+        while let Some(&(oldest, good, total)) = self.buckets.front() {
+            if oldest + SLO_BUCKETS > bucket {
+                break;
+            }
+            self.buckets.pop_front();
+            self.good -= good;
+            self.total -= total;
+        }
+    }
+
+    fn record(&mut self, bucket: u64, good: bool) {
+        self.advance(bucket);
+        match self.buckets.back_mut() {
+            Some((latest, good_count, total)) if *latest == bucket => {
+                *good_count += u64::from(good);
+                *total += 1;
+            }
+            _ => self.buckets.push_back((bucket, u64::from(good), 1)),
+        }
+        self.good += u64::from(good);
+        self.total += 1;
+    }
+
+    fn status(&self, objective: &SloObjective) -> SloStatus {
+        let (compliance, error_budget_remaining) = if self.total == 0 {
+            (1.0, 1.0)
+        } else {
+            let total = self.total as f64;
+            let bad = (self.total - self.good) as f64;
+            let allowed = total * (1.0 - objective.target / 100.0);
+            (self.good as f64 / total, 1.0 - bad / allowed)
+        };
+        SloStatus {
+            operation: objective.operation.clone(),
+            target: objective.target,
+            latency_ms: objective.latency.as_secs_f64() * 1000.0,
+            total: self.total,
+            good: self.good,
+            compliance,
+            error_budget_remaining,
+        }
+    }
+}
+
+/// Tracks each objective over a rolling window
+#[derive(Debug)]
+pub struct SloTracker {
+    config: SloConfig,
+    start: Instant,
+    bucket_len: Duration,
+    windows: Vec<Mutex<SloWindow>>,
+}
+
+impl SloTracker {
+    /// Track `config`'s objectives from now
+    pub fn new(config: SloConfig) -> Self {
+        let bucket_len = (config.window / SLO_BUCKETS as u32).max(Duration::from_millis(1));
+        SloTracker {
+            windows: config.objectives.iter().map(|_| Mutex::default()).collect(),
+            config,
+            start: Instant::now(),
+            bucket_len,
+        }
+    }
+
+    fn bucket(&self, at: Instant) -> u64 {
+        (at.duration_since(self.start).as_nanos() / self.bucket_len.as_nanos()) as u64
+    }
+
+    fn record_at(
+        &self,
+        at: Instant,
+        operation: &str,
+        latency_ms: f64,
+        ok: bool,
+    ) -> Option<SloStatus> {
+        let index = self
+            .config
+            .objectives
+            .iter()
+            .position(|objective| objective.operation == operation)?;
+        let objective = &self.config.objectives[index];
+        let good = ok && latency_ms <= objective.latency.as_secs_f64() * 1000.0;
+        let mut window = self.windows[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        window.record(self.bucket(at), good);
+        Some(window.status(objective))
+    }
+
+    /// Count a request to `operation` that took `latency_ms`; returns the
+    /// operation's updated status, or `None` when it has no objective
+    pub fn record(&self, operation: &str, latency_ms: f64, ok: bool) -> Option<SloStatus> {
+        self.record_at(Instant::now(), operation, latency_ms, ok)
+    }
+
+    fn report_at(&self, at: Instant) -> SloReport {
+        let bucket = self.bucket(at);
+        let objectives = self
+            .config
+            .objectives
+            .iter()
+            .zip(&self.windows)
+            .map(|(objective, window)| {
+                let mut window = window.lock().unwrap_or_else(PoisonError::into_inner);
+                window.advance(bucket);
+                window.status(objective)
+            })
+            .collect();
+        SloReport {
+            window_secs: self.config.window.as_secs(),
+            objectives,
+        }
+    }
+
+    /// Every objective over the window ending now
+    pub fn report(&self) -> SloReport {
+        self.report_at(Instant::now())
+    }
+}
+
+/// Objectives the server tracks
+static SLO_TRACKER: OnceLock<SloTracker> = OnceLock::new();
+
+/// Track `config`'s objectives instead of the defaults
+///
+/// Fails once any request has been recorded, or when called twice.
+pub fn init_slo(config: SloConfig) -> anyhow::Result<()> {
+    SLO_TRACKER
+        .set(SloTracker::new(config))
+        .map_err(|_| anyhow::anyhow!("SLO tracking has already started"))
+}
+
+fn slo_tracker() -> &'static SloTracker {
+    SLO_TRACKER.get_or_init(|| SloTracker::new(SloConfig::default()))
+}
+
+/// Where each operation stands against its objective
+pub fn slo_report() -> SloReport {
+    slo_tracker().report()
 }
 
 /// Timer for measuring operation latency
@@ -124,7 +702,7 @@ impl LatencyTimer {
 
     pub fn record(self) {
         let elapsed = self.start.elapsed().as_secs_f64();
-        histogram!(self.metric_name, elapsed);
+        histogram!(self.metric_name).record(elapsed);
     }
 }
 
@@ -142,6 +720,88 @@ pub fn init_prometheus() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Metric exporters to install at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsExporters {
+    /// Serve the Prometheus text format on `/metrics`
+    pub prometheus: bool,
+    /// Push to an OpenTelemetry collector over OTLP
+    pub otlp: bool,
+}
+
+impl Default for MetricsExporters {
+    fn default() -> Self {
+        MetricsExporters {
+            prometheus: true,
+            otlp: false,
+        }
+    }
+}
+
+impl MetricsExporters {
+    /// Parse a comma-separated list such as `prometheus,otlp` (or `none`)
+    pub fn parse(list: &str) -> anyhow::Result<Self> {
+        let mut exporters = MetricsExporters {
+            prometheus: false,
+            otlp: false,
+        };
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match name.to_ascii_lowercase().as_str() {
+                "prometheus" => exporters.prometheus = true,
+                "otlp" => exporters.otlp = true,
+                "none" => {}
+                _ => anyhow::bail!(
+                    "Unknown metrics exporter '{}' (expected prometheus, otlp or none)",
+                    name
+                ),
+            }
+        }
+        Ok(exporters)
+    }
+
+    /// Read `RUNE_METRICS_EXPORTERS`, defaulting to Prometheus only
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("RUNE_METRICS_EXPORTERS") {
+            Ok(list) => Self::parse(&list),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+/// Install the recorders for the selected exporters
+///
+/// Returns the OTLP meter provider when OTLP export is enabled; shut it down
+/// on exit so the last interval is flushed. OTLP export needs a Tokio runtime.
+pub fn init_exporters(
+    exporters: MetricsExporters,
+    service_name: &str,
+) -> anyhow::Result<Option<opentelemetry_sdk::metrics::MeterProvider>> {
+    let mut fanout = metrics_util::layers::FanoutBuilder::default();
+
+    if exporters.prometheus {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        PROMETHEUS_HANDLE
+            .set(recorder.handle())
+            .map_err(|_| anyhow::anyhow!("Failed to set Prometheus handle"))?;
+        fanout = fanout.add_recorder(recorder);
+    }
+
+    let provider = if exporters.otlp {
+        let provider = crate::otel_metrics::init_meter_provider(service_name)?;
+        fanout = fanout.add_recorder(crate::otel_metrics::OtelRecorder::from_provider(
+            &provider,
+            service_name,
+        ));
+        Some(provider)
+    } else {
+        None
+    };
+
+    metrics::set_global_recorder(fanout.build())
+        .map_err(|_| anyhow::anyhow!("A metrics recorder is already installed"))?;
+    Ok(provider)
+}
+
 /// Get Prometheus metrics string
 pub fn get_prometheus_metrics() -> String {
     PROMETHEUS_HANDLE
@@ -205,6 +865,41 @@ mod tests {
         record_batch_authorization(100, 0.025);
     }
 
+    #[test]
+    fn test_slo_window_and_error_budget() {
+        let tracker = SloTracker::new(SloConfig {
+            window: Duration::from_secs(60),
+            objectives: vec![SloObjective::new("authorize", 99.0, 10)],
+        });
+        let start = tracker.start;
+        for _ in 0..198 {
+            tracker.record_at(start, "authorize", 2.0, true);
+        }
+        // One too slow, one failed
+        tracker.record_at(start, "authorize", 25.0, true);
+        let status = tracker.record_at(start, "authorize", 2.0, false).unwrap();
+        assert_eq!((status.total, status.good), (200, 198));
+        assert_eq!(status.compliance, 0.99);
+        assert!(status.error_budget_remaining.abs() < 1e-9);
+        assert!(tracker.record_at(start, "query", 1.0, true).is_none());
+
+        // Half a window later the early requests still count
+        let later = start + Duration::from_secs(30);
+        let status = tracker.record_at(later, "authorize", 50.0, true).unwrap();
+        assert_eq!(status.total, 201);
+        assert!(status.error_budget_remaining < 0.0);
+
+        // A window after them, only the late one is left
+        let report = tracker.report_at(start + Duration::from_secs(60));
+        assert_eq!(report.window_secs, 60);
+        assert_eq!(report.objectives[0].total, 1);
+        assert_eq!(report.objectives[0].compliance, 0.0);
+
+        let empty = tracker.report_at(start + Duration::from_secs(120));
+        assert_eq!(empty.objectives[0].compliance, 1.0);
+        assert_eq!(empty.objectives[0].error_budget_remaining, 1.0);
+    }
+
     #[test]
     fn test_record_rule_evaluations() {
         setup();
@@ -231,6 +926,72 @@ mod tests {
         record_error("unauthorized");
     }
 
+    #[test]
+    fn test_record_compaction() {
+        setup();
+        let stats = rune_core::CompactionStats {
+            facts_before: 10,
+            facts_after: 7,
+            duplicates_removed: 2,
+            expired_removed: 1,
+            ..Default::default()
+        };
+        record_compaction("manual", &stats, 0.004);
+        record_compaction_deferred();
+    }
+
+    #[test]
+    fn test_parse_metrics_exporters() {
+        let both = MetricsExporters::parse("prometheus, OTLP").unwrap();
+        assert!(both.prometheus && both.otlp);
+
+        let otlp = MetricsExporters::parse("otlp").unwrap();
+        assert!(!otlp.prometheus && otlp.otlp);
+
+        let none = MetricsExporters::parse("none").unwrap();
+        assert!(!none.prometheus && !none.otlp);
+
+        assert!(MetricsExporters::parse("statsd").is_err());
+    }
+
+    #[test]
+    fn test_record_evaluation_failures() {
+        setup();
+        record_evaluation_failures(&[]);
+        record_evaluation_failures(&[rune_core::FailureOutcome {
+            class: rune_core::FailureClass::DatalogTimeout,
+            mode: rune_core::FailureMode::FailClosed,
+            error: "Operation timed out after 100ms".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_record_coalesced_request() {
+        setup();
+        record_coalesced_request();
+    }
+
+    #[test]
+    fn test_record_replica_metrics() {
+        setup();
+        record_replica_escalation();
+        record_replica_pending(3);
+        record_reload("applied");
+    }
+
+    #[test]
+    fn test_record_mirror_metrics() {
+        setup();
+        record_mirror_comparison("mismatch");
+        record_mirror_latency("secondary", 0.002);
+    }
+
+    #[test]
+    fn test_record_shadow_decision() {
+        setup();
+        record_shadow_decision("1".to_string(), "mismatch");
+    }
+
     #[test]
     fn test_update_engine_metrics() {
         setup();
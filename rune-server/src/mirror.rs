@@ -0,0 +1,356 @@
+//! Decision mirroring for upgrade validation
+//!
+//! Before an upgrade takes traffic, it helps to see it decide real
+//! requests. With `RUNE_MIRROR_URL` pointing at the data plane of a
+//! secondary server (typically the new version, following this one with
+//! `RUNE_REPLICA_OF` so it has the same facts), a sample of `/v1/authorize`
+//! requests is replayed against it and the answers are compared:
+//!
+//! - `RUNE_MIRROR_URL`: base URL of the secondary's data plane
+//! - `RUNE_MIRROR_SAMPLE_RATE`: fraction of requests mirrored, above 0 and
+//!   at most 1 (default 0.01)
+//! - `RUNE_MIRROR_QUEUE`: mirrored requests waiting for the secondary
+//!   before new ones are dropped (default 1024)
+//!
+//! Mirroring happens off the request path: the caller always gets this
+//! server's answer, and a slow or failing secondary only costs samples.
+//! Sampling is deterministic, every `1 / rate`-th request, so a low rate
+//! still sees steady traffic. The secondary is asked for diagnostics and
+//! its latency is the evaluation time it reports, the same measure as this
+//! server's, falling back to the round trip when it reports none.
+//!
+//! Comparisons are counted in `rune_mirror_comparisons_total` by outcome,
+//! latencies of both servers go to `rune_mirror_latency_seconds`, and
+//! decision mismatches are logged. `GET /v1/mirror` on the management plane
+//! summarizes them, with the most recent mismatches.
+
+use crate::api::{AuthorizeRequest, AuthorizeResponse, Decision};
+use crate::metrics;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Time allowed for a call to the secondary
+const SECONDARY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Mismatches kept for the report
+const RECENT_MISMATCHES: usize = 20;
+
+/// Settings for mirroring decisions to a secondary
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorConfig {
+    /// Base URL of the secondary's data plane
+    pub target: String,
+    /// Fraction of requests mirrored
+    pub sample_rate: f64,
+    /// Mirrored requests waiting for the secondary before new ones are
+    /// dropped
+    pub queue: usize,
+}
+
+impl MirrorConfig {
+    /// Mirror to `target` with the default settings
+    pub fn new(target: impl Into<String>) -> Self {
+        MirrorConfig {
+            target: target.into(),
+            sample_rate: 0.01,
+            queue: 1024,
+        }
+    }
+
+    /// Read the `RUNE_MIRROR_*` variables; `None` when `RUNE_MIRROR_URL` is
+    /// unset
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(target) = std::env::var("RUNE_MIRROR_URL") else {
+            return Ok(None);
+        };
+        let mut config = Self::new(target);
+        if let Ok(rate) = std::env::var("RUNE_MIRROR_SAMPLE_RATE") {
+            let rate: f64 = rate
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid RUNE_MIRROR_SAMPLE_RATE: {}", e))?;
+            if !(rate > 0.0 && rate <= 1.0) {
+                anyhow::bail!("Invalid RUNE_MIRROR_SAMPLE_RATE: must be above 0 and at most 1");
+            }
+            config.sample_rate = rate;
+        }
+        if let Ok(queue) = std::env::var("RUNE_MIRROR_QUEUE") {
+            config.queue = queue
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid RUNE_MIRROR_QUEUE: {}", e))?;
+            if config.queue == 0 {
+                anyhow::bail!("Invalid RUNE_MIRROR_QUEUE: must be at least 1");
+            }
+        }
+        Ok(Some(config))
+    }
+}
+
+/// A decision this server made, waiting to be compared
+#[derive(Debug)]
+struct Sample {
+    request: AuthorizeRequest,
+    decision: Decision,
+    latency_ms: f64,
+}
+
+/// A request the two servers decided differently
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorMismatch {
+    /// Principal of the request
+    pub principal: String,
+    /// Action of the request
+    pub action: String,
+    /// Resource of the request
+    pub resource: String,
+    /// This server's decision
+    pub primary: Decision,
+    /// The secondary's decision
+    pub secondary: Decision,
+}
+
+/// What mirroring has found so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorReport {
+    /// Base URL of the secondary's data plane
+    pub target: String,
+    /// Fraction of requests mirrored
+    pub sample_rate: f64,
+    /// Requests selected for mirroring
+    pub sampled: u64,
+    /// Requests the secondary answered
+    pub compared: u64,
+    /// Answered requests it decided differently
+    pub mismatched: u64,
+    /// Requests the secondary failed to answer
+    pub failed: u64,
+    /// Requests dropped because the secondary fell behind
+    pub dropped: u64,
+    /// Mean latency of this server over the answered requests
+    pub primary_latency_ms: f64,
+    /// Mean latency of the secondary over the answered requests
+    pub secondary_latency_ms: f64,
+    /// Secondary minus primary mean latency
+    pub latency_delta_ms: f64,
+    /// Most recent mismatches, oldest first
+    pub recent_mismatches: VecDeque<MirrorMismatch>,
+}
+
+impl MirrorReport {
+    /// Count one answered request
+    fn record(&mut self, sample: &Sample, secondary: Decision, secondary_ms: f64) {
+        self.compared += 1;
+        let n = self.compared as f64;
+        self.primary_latency_ms += (sample.latency_ms - self.primary_latency_ms) / n;
+        self.secondary_latency_ms += (secondary_ms - self.secondary_latency_ms) / n;
+        self.latency_delta_ms = self.secondary_latency_ms - self.primary_latency_ms;
+
+        if secondary != sample.decision {
+            self.mismatched += 1;
+            if self.recent_mismatches.len() == RECENT_MISMATCHES {
+                self.recent_mismatches.pop_front();
+            }
+            self.recent_mismatches.push_back(MirrorMismatch {
+                principal: sample.request.principal.clone(),
+                action: sample.request.action.clone(),
+                resource: sample.request.resource.clone(),
+                primary: sample.decision,
+                secondary,
+            });
+        }
+    }
+}
+
+/// Replays a sample of decisions against a secondary server
+#[derive(Debug)]
+pub struct Mirror {
+    config: MirrorConfig,
+    seen: AtomicU64,
+    samples: mpsc::Sender<Sample>,
+    report: Arc<Mutex<MirrorReport>>,
+}
+
+impl Mirror {
+    /// Create a mirror and the task that calls the secondary
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(config: MirrorConfig) -> Self {
+        let (samples, receiver) = mpsc::channel(config.queue);
+        let report = Arc::new(Mutex::new(MirrorReport {
+            target: config.target.clone(),
+            sample_rate: config.sample_rate,
+            ..MirrorReport::default()
+        }));
+        tokio::spawn(compare(
+            format!("{}/v1/authorize", config.target.trim_end_matches('/')),
+            receiver,
+            report.clone(),
+        ));
+        Mirror {
+            config,
+            seen: AtomicU64::new(0),
+            samples,
+            report,
+        }
+    }
+
+    /// Mirroring settings
+    pub fn config(&self) -> &MirrorConfig {
+        &self.config
+    }
+
+    /// Offer a decision this server made in `latency_ms`; mirrored if
+    /// sampled
+    pub fn offer(&self, request: &AuthorizeRequest, decision: Decision, latency_ms: f64) {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        if !sampled(n, self.config.sample_rate) {
+            return;
+        }
+        let sample = Sample {
+            request: request.clone(),
+            decision,
+            latency_ms,
+        };
+        let dropped = self.samples.try_send(sample).is_err();
+        let mut report = self.report.lock().unwrap_or_else(|e| e.into_inner());
+        report.sampled += 1;
+        if dropped {
+            report.dropped += 1;
+            metrics::record_mirror_comparison("dropped");
+        }
+    }
+
+    /// Summary of the comparisons so far
+    pub fn report(&self) -> MirrorReport {
+        self.report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Whether the `n`th request (from 0) is mirrored at `rate`
+fn sampled(n: u64, rate: f64) -> bool {
+    ((n + 1) as f64 * rate).floor() > (n as f64 * rate).floor()
+}
+
+/// Ask the secondary about each sample until the mirror is dropped
+async fn compare(
+    url: String,
+    mut samples: mpsc::Receiver<Sample>,
+    report: Arc<Mutex<MirrorReport>>,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(SECONDARY_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    while let Some(sample) = samples.recv().await {
+        let start = Instant::now();
+        let answer = async {
+            client
+                .post(&url)
+                .query(&[("debug", "true")])
+                .json(&sample.request)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<AuthorizeResponse>()
+                .await
+        }
+        .await;
+        let round_trip_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        let mut report = report.lock().unwrap_or_else(|e| e.into_inner());
+        match answer {
+            Ok(response) => {
+                let secondary_ms = response
+                    .diagnostics
+                    .as_ref()
+                    .map_or(round_trip_ms, |d| d.evaluation_time_ms);
+                report.record(&sample, response.decision, secondary_ms);
+                metrics::record_mirror_latency("primary", sample.latency_ms / 1000.0);
+                metrics::record_mirror_latency("secondary", secondary_ms / 1000.0);
+                if response.decision == sample.decision {
+                    metrics::record_mirror_comparison("match");
+                } else {
+                    metrics::record_mirror_comparison("mismatch");
+                    warn!(
+                        "Mirror mismatch: {} {} {} -> {:?} here, {:?} on {}",
+                        sample.request.principal,
+                        sample.request.action,
+                        sample.request.resource,
+                        sample.decision,
+                        response.decision,
+                        url
+                    );
+                }
+            }
+            Err(e) => {
+                report.failed += 1;
+                metrics::record_mirror_comparison("error");
+                debug!("Mirroring to {} failed: {}", url, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_is_evenly_spaced() {
+        let picked: Vec<u64> = (0..20).filter(|n| sampled(*n, 0.25)).collect();
+        assert_eq!(picked, [3, 7, 11, 15, 19]);
+        assert!((0..10).all(|n| sampled(n, 1.0)));
+        assert_eq!((0..10_000).filter(|n| sampled(*n, 0.01)).count(), 100);
+    }
+
+    #[test]
+    fn test_report_tracks_mismatches_and_latency() {
+        let sample = |resource: &str, decision, latency_ms| Sample {
+            request: serde_json::from_value(serde_json::json!({
+                "principal": "user:alice",
+                "action": "read",
+                "resource": resource,
+            }))
+            .unwrap(),
+            decision,
+            latency_ms,
+        };
+
+        let mut report = MirrorReport::default();
+        report.record(
+            &sample("file:/a", Decision::Permit, 1.0),
+            Decision::Permit,
+            2.0,
+        );
+        report.record(
+            &sample("file:/b", Decision::Permit, 3.0),
+            Decision::Deny,
+            6.0,
+        );
+        assert_eq!((report.compared, report.mismatched), (2, 1));
+        assert_eq!(report.primary_latency_ms, 2.0);
+        assert_eq!(report.secondary_latency_ms, 4.0);
+        assert_eq!(report.latency_delta_ms, 2.0);
+        assert_eq!(report.recent_mismatches[0].resource, "file:/b");
+        assert_eq!(report.recent_mismatches[0].secondary, Decision::Deny);
+
+        for _ in 0..RECENT_MISMATCHES {
+            report.record(
+                &sample("file:/c", Decision::Forbid, 1.0),
+                Decision::Permit,
+                1.0,
+            );
+        }
+        assert_eq!(report.recent_mismatches.len(), RECENT_MISMATCHES);
+        assert_eq!(report.recent_mismatches[0].resource, "file:/c");
+    }
+}
@@ -0,0 +1,426 @@
+//! OpenTelemetry metrics export
+//!
+//! Handlers record through the `metrics` facade. [`OtelRecorder`] forwards
+//! those measurements to an OpenTelemetry meter, so deployments that collect
+//! OTLP rather than scraping `/metrics` (Grafana Cloud, Datadog) receive the
+//! same series. Counters and histograms map onto their OpenTelemetry
+//! counterparts; gauges become observable gauges reporting the last value
+//! set for each label set.
+//!
+//! The exporter follows the standard OpenTelemetry environment variables:
+//! `OTEL_EXPORTER_OTLP_METRICS_ENDPOINT` (falling back to
+//! `OTEL_EXPORTER_OTLP_ENDPOINT`), `OTEL_METRIC_EXPORT_INTERVAL` in
+//! milliseconds, and `OTEL_EXPORTER_OTLP_METRICS_TEMPORALITY_PREFERENCE`
+//! (`cumulative` or `delta`).
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use opentelemetry::metrics::{Meter, MeterProvider as _, ObservableGauge};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::data::Temporality;
+use opentelemetry_sdk::metrics::reader::TemporalitySelector;
+use opentelemetry_sdk::metrics::{InstrumentKind, MeterProvider};
+use opentelemetry_sdk::runtime;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Default time between exports, per the OpenTelemetry specification
+const DEFAULT_EXPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Build an OTLP metrics pipeline exporting over gRPC
+///
+/// The returned provider must be shut down on exit to flush the last
+/// interval.
+pub fn init_meter_provider(service_name: &str) -> anyhow::Result<MeterProvider> {
+    let endpoint = std::env::var("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT")
+        .or_else(|_| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT"))
+        .unwrap_or_else(|_| "http://localhost:4317".to_string());
+    let interval = std::env::var("OTEL_METRIC_EXPORT_INTERVAL")
+        .ok()
+        .and_then(|ms| ms.parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_EXPORT_INTERVAL);
+
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(endpoint)
+        .with_timeout(Duration::from_secs(3));
+
+    let mut pipeline = opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::Tokio)
+        .with_exporter(exporter)
+        .with_resource(crate::tracing::service_resource(service_name))
+        .with_period(interval);
+
+    let temporality = std::env::var("OTEL_EXPORTER_OTLP_METRICS_TEMPORALITY_PREFERENCE");
+    if temporality.is_ok_and(|t| t.eq_ignore_ascii_case("delta")) {
+        pipeline = pipeline.with_temporality_selector(DeltaPreferred);
+    }
+
+    Ok(pipeline.build()?)
+}
+
+/// Delta temporality for monotonic instruments, as Datadog expects
+#[derive(Debug, Clone, Copy)]
+struct DeltaPreferred;
+
+impl TemporalitySelector for DeltaPreferred {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        match kind {
+            InstrumentKind::Counter
+            | InstrumentKind::Histogram
+            | InstrumentKind::ObservableCounter => Temporality::Delta,
+            _ => Temporality::Cumulative,
+        }
+    }
+}
+
+/// `metrics` recorder that forwards to an OpenTelemetry meter
+pub struct OtelRecorder {
+    meter: Meter,
+    state: Mutex<Instruments>,
+}
+
+#[derive(Default)]
+struct Instruments {
+    descriptions: HashMap<String, SharedString>,
+    counters: HashMap<String, opentelemetry::metrics::Counter<u64>>,
+    histograms: HashMap<String, opentelemetry::metrics::Histogram<f64>>,
+    gauges: HashMap<String, (ObservableGauge<f64>, Arc<GaugeSeries>)>,
+    counter_handles: HashMap<Key, Arc<OtelCounter>>,
+    gauge_handles: HashMap<Key, Arc<OtelGauge>>,
+}
+
+type GaugeSeries = Mutex<Vec<Arc<OtelGauge>>>;
+
+impl OtelRecorder {
+    /// Create a recorder for the given meter
+    pub fn new(meter: Meter) -> Self {
+        OtelRecorder {
+            meter,
+            state: Mutex::new(Instruments::default()),
+        }
+    }
+
+    /// Create a recorder for a meter of the given provider
+    pub fn from_provider(provider: &MeterProvider, service_name: &str) -> Self {
+        Self::new(provider.versioned_meter(
+            service_name.to_string(),
+            Some(env!("CARGO_PKG_VERSION")),
+            None::<&'static str>,
+            None,
+        ))
+    }
+
+    fn describe(&self, key: KeyName, description: SharedString) {
+        self.lock()
+            .descriptions
+            .insert(key.as_str().to_string(), description);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Instruments> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Recorder for OtelRecorder {
+    fn describe_counter(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        let mut state = self.lock();
+        if let Some(handle) = state.counter_handles.get(key) {
+            return Counter::from_arc(handle.clone());
+        }
+
+        let name = key.name().to_string();
+        let description = state.descriptions.get(&name).cloned();
+        let counter = state
+            .counters
+            .entry(name.clone())
+            .or_insert_with(|| {
+                let builder = self.meter.u64_counter(name);
+                match description {
+                    Some(description) => builder.with_description(description.into_owned()),
+                    None => builder,
+                }
+                .init()
+            })
+            .clone();
+
+        let handle = Arc::new(OtelCounter {
+            counter,
+            attributes: attributes(key),
+            total: AtomicU64::new(0),
+        });
+        state.counter_handles.insert(key.clone(), handle.clone());
+        Counter::from_arc(handle)
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        let mut state = self.lock();
+        if let Some(handle) = state.gauge_handles.get(key) {
+            return Gauge::from_arc(handle.clone());
+        }
+
+        let name = key.name().to_string();
+        let description = state.descriptions.get(&name).cloned();
+        let series = state
+            .gauges
+            .entry(name.clone())
+            .or_insert_with(|| {
+                let series = Arc::new(GaugeSeries::default());
+                let observed = series.clone();
+                let builder =
+                    self.meter
+                        .f64_observable_gauge(name)
+                        .with_callback(move |observer| {
+                            let gauges = observed.lock().unwrap_or_else(PoisonError::into_inner);
+                            for gauge in gauges.iter() {
+                                observer.observe(gauge.get(), &gauge.attributes);
+                            }
+                        });
+                let gauge = match description {
+                    Some(description) => builder.with_description(description.into_owned()),
+                    None => builder,
+                }
+                .init();
+                (gauge, series)
+            })
+            .1
+            .clone();
+
+        let handle = Arc::new(OtelGauge {
+            attributes: attributes(key),
+            value: AtomicU64::new(0f64.to_bits()),
+        });
+        series
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(handle.clone());
+        state.gauge_handles.insert(key.clone(), handle.clone());
+        Gauge::from_arc(handle)
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let mut state = self.lock();
+        let name = key.name().to_string();
+        let description = state.descriptions.get(&name).cloned();
+        let histogram = state
+            .histograms
+            .entry(name.clone())
+            .or_insert_with(|| {
+                let builder = self.meter.f64_histogram(name);
+                match description {
+                    Some(description) => builder.with_description(description.into_owned()),
+                    None => builder,
+                }
+                .init()
+            })
+            .clone();
+
+        Histogram::from_arc(Arc::new(OtelHistogram {
+            histogram,
+            attributes: attributes(key),
+        }))
+    }
+}
+
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|label| KeyValue::new(label.key().to_string(), label.value().to_string()))
+        .collect()
+}
+
+struct OtelCounter {
+    counter: opentelemetry::metrics::Counter<u64>,
+    attributes: Vec<KeyValue>,
+    /// Running total, needed to turn `absolute` into an increment
+    total: AtomicU64,
+}
+
+impl CounterFn for OtelCounter {
+    fn increment(&self, value: u64) {
+        self.total.fetch_add(value, Ordering::Relaxed);
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn absolute(&self, value: u64) {
+        let previous = self.total.fetch_max(value, Ordering::Relaxed);
+        if value > previous {
+            self.counter.add(value - previous, &self.attributes);
+        }
+    }
+}
+
+struct OtelGauge {
+    attributes: Vec<KeyValue>,
+    /// `f64` bits of the last value
+    value: AtomicU64,
+}
+
+impl OtelGauge {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.value.load(Ordering::Relaxed))
+    }
+
+    fn update(&self, f: impl Fn(f64) -> f64) {
+        // The closure always returns Some, so this cannot fail
+        let _ = self
+            .value
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some(f(f64::from_bits(bits)).to_bits())
+            });
+    }
+}
+
+impl GaugeFn for OtelGauge {
+    fn increment(&self, value: f64) {
+        self.update(|current| current + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|current| current - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.value.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+struct OtelHistogram {
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl HistogramFn for OtelHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::metrics::data::{Gauge as GaugeData, ResourceMetrics, Sum};
+    use opentelemetry_sdk::metrics::reader::{AggregationSelector, MetricReader};
+    use opentelemetry_sdk::metrics::{Aggregation, ManualReader, Pipeline};
+    use opentelemetry_sdk::Resource;
+    use std::sync::Weak;
+
+    /// Lets the test keep a handle on the reader it gives the provider
+    #[derive(Debug, Clone)]
+    struct SharedReader(Arc<ManualReader>);
+
+    impl TemporalitySelector for SharedReader {
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    impl AggregationSelector for SharedReader {
+        fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+            self.0.aggregation(kind)
+        }
+    }
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline)
+        }
+
+        fn collect(&self, rm: &mut ResourceMetrics) -> opentelemetry::metrics::Result<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self) -> opentelemetry::metrics::Result<()> {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> opentelemetry::metrics::Result<()> {
+            self.0.shutdown()
+        }
+    }
+
+    fn collect(reader: &SharedReader) -> ResourceMetrics {
+        let mut rm = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut rm).unwrap();
+        rm
+    }
+
+    #[test]
+    fn test_measurements_reach_the_meter() {
+        let reader = SharedReader(Arc::new(ManualReader::builder().build()));
+        let provider = MeterProvider::builder().with_reader(reader.clone()).build();
+        let recorder = OtelRecorder::from_provider(&provider, "rune-test");
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::describe_counter!("requests", "Requests served");
+            metrics::counter!("requests", "decision" => "permit").increment(2);
+            metrics::counter!("requests", "decision" => "permit").increment(1);
+            metrics::counter!("requests", "decision" => "deny").absolute(5);
+            metrics::gauge!("entries").set(4.0);
+            metrics::gauge!("entries").increment(1.5);
+            metrics::histogram!("latency").record(0.25);
+        });
+
+        let rm = collect(&reader);
+        let metrics = &rm.scope_metrics[0].metrics;
+        let metric = |name: &str| metrics.iter().find(|m| m.name == name).unwrap();
+
+        let requests = metric("requests");
+        assert_eq!(requests.description, "Requests served");
+        let sum = requests.data.as_any().downcast_ref::<Sum<u64>>().unwrap();
+        let mut totals: Vec<_> = sum
+            .data_points
+            .iter()
+            .map(|p| {
+                let (_, decision) = p.attributes.iter().next().unwrap();
+                (decision.as_str().into_owned(), p.value)
+            })
+            .collect();
+        totals.sort();
+        assert_eq!(
+            totals,
+            vec![("deny".to_string(), 5), ("permit".to_string(), 3)]
+        );
+
+        let entries = metric("entries")
+            .data
+            .as_any()
+            .downcast_ref::<GaugeData<f64>>()
+            .unwrap();
+        assert_eq!(entries.data_points[0].value, 5.5);
+
+        assert!(metrics.iter().any(|m| m.name == "latency"));
+    }
+
+    #[test]
+    fn test_delta_preference() {
+        assert_eq!(
+            DeltaPreferred.temporality(InstrumentKind::Counter),
+            Temporality::Delta
+        );
+        assert_eq!(
+            DeltaPreferred.temporality(InstrumentKind::UpDownCounter),
+            Temporality::Cumulative
+        );
+    }
+}
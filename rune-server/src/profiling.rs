@@ -0,0 +1,296 @@
+//! CPU and heap profiles of a running server
+//!
+//! Built only with the `profiling` feature, and served only when
+//! `RUNE_PROFILING_TOKEN` is set: each request must carry it as
+//! `Authorization: Bearer <token>`. Both endpoints are on the management
+//! plane.
+//!
+//! - `GET /debug/pprof/profile?seconds=30` samples every thread's stack 99
+//!   times a second for the given time (at most five minutes) and answers
+//!   with a pprof protobuf profile, for `go tool pprof` or any other pprof
+//!   viewer. `format=flamegraph` answers with an SVG flame graph instead.
+//!   One CPU profile is taken at a time.
+//! - `GET /debug/pprof/heap` dumps jemalloc's sampled heap profile, for
+//!   `jeprof`. The feature makes jemalloc the server's allocator, but
+//!   sampling has to be switched on when the process starts:
+//!   `_RJEM_MALLOC_CONF=prof:true,lg_prof_sample:19` samples an allocation
+//!   every 512 KiB on average.
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, HeaderMap},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Longest CPU profile one request may ask for
+const MAX_SECONDS: u64 = 300;
+
+/// CPU profile length when the request names none
+const DEFAULT_SECONDS: u64 = 30;
+
+/// Stack samples taken per second
+const FREQUENCY: i32 = 99;
+
+/// Profiling settings
+#[derive(Debug, Clone)]
+pub struct ProfilingConfig {
+    /// Bearer token every profiling request must present
+    pub token: String,
+}
+
+impl ProfilingConfig {
+    /// Read `RUNE_PROFILING_TOKEN`; profiling stays off without it
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(token) = std::env::var("RUNE_PROFILING_TOKEN") else {
+            return Ok(None);
+        };
+        if token.trim().is_empty() {
+            anyhow::bail!("Invalid RUNE_PROFILING_TOKEN: must not be empty");
+        }
+        Ok(Some(ProfilingConfig { token }))
+    }
+}
+
+/// Token check and the one-at-a-time CPU profiler
+pub struct Profiler {
+    /// Digest of the token; comparing digests keeps the comparison from
+    /// leaking how much of a guess was right
+    token: [u8; 32],
+    /// Whether a CPU profile is being taken
+    busy: AtomicBool,
+}
+
+impl Profiler {
+    /// Create a profiler accepting `config`'s token
+    pub fn new(config: ProfilingConfig) -> Self {
+        Profiler {
+            token: Sha256::digest(config.token.as_bytes()).into(),
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether `headers` carry the token
+    fn accepts(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| <[u8; 32]>::from(Sha256::digest(token.as_bytes())) == self.token)
+    }
+}
+
+/// Profiling routes, when the state has a profiler
+pub fn routes(state: &AppState) -> Option<Router<AppState>> {
+    state.profiler.as_ref()?;
+    Some(
+        Router::new()
+            .route("/debug/pprof/profile", get(cpu_profile))
+            .route("/debug/pprof/heap", get(heap_profile))
+            .route_layer(middleware::from_fn_with_state(state.clone(), require_token)),
+    )
+}
+
+/// Refuse requests without the profiling token
+async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> ApiResult<Response> {
+    match &state.profiler {
+        Some(profiler) if profiler.accepts(request.headers()) => Ok(next.run(request).await),
+        _ => Err(ApiError::Unauthorized(
+            "Profiling needs the profiling token".to_string(),
+        )),
+    }
+}
+
+/// Query parameters for CPU profiles
+#[derive(Debug, Deserialize)]
+pub struct CpuProfileParams {
+    /// How long to sample for
+    pub seconds: Option<u64>,
+    /// `pprof` (default) or `flamegraph`
+    pub format: Option<String>,
+}
+
+/// Sample the server's stacks and answer with the profile
+pub async fn cpu_profile(
+    State(state): State<AppState>,
+    Query(params): Query<CpuProfileParams>,
+) -> ApiResult<Response> {
+    let seconds = params.seconds.unwrap_or(DEFAULT_SECONDS);
+    if seconds == 0 || seconds > MAX_SECONDS {
+        return Err(ApiError::BadRequest(format!(
+            "seconds must be between 1 and {}",
+            MAX_SECONDS
+        )));
+    }
+    let flamegraph = match params.format.as_deref() {
+        None | Some("pprof") => false,
+        Some("flamegraph") => true,
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "Unknown profile format '{}' (expected pprof or flamegraph)",
+                other
+            )))
+        }
+    };
+
+    let profiler = state
+        .profiler
+        .clone()
+        .ok_or_else(|| ApiError::NotFound("Profiling is not enabled".to_string()))?;
+    if profiler.busy.swap(true, Ordering::AcqRel) {
+        return Err(ApiError::Conflict(
+            "A CPU profile is already being taken".to_string(),
+        ));
+    }
+    let profile = tokio::task::spawn_blocking(move || {
+        let profile = sample(Duration::from_secs(seconds), flamegraph);
+        profiler.busy.store(false, Ordering::Release);
+        profile
+    })
+    .await
+    .map_err(|e| ApiError::Internal(format!("Profiling failed: {}", e)))??;
+
+    let content_type = if flamegraph {
+        "image/svg+xml"
+    } else {
+        "application/octet-stream"
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], profile).into_response())
+}
+
+/// Take a CPU profile on this thread
+fn sample(duration: Duration, flamegraph: bool) -> ApiResult<Vec<u8>> {
+    use pprof::protos::Message;
+
+    let failed = |e: &dyn std::fmt::Display| ApiError::Internal(format!("Profiling failed: {}", e));
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|e| failed(&e))?;
+    std::thread::sleep(duration);
+    let report = guard.report().build().map_err(|e| failed(&e))?;
+
+    let mut body = Vec::new();
+    if flamegraph {
+        report.flamegraph(&mut body).map_err(|e| failed(&e))?;
+    } else {
+        let profile = report.pprof().map_err(|e| failed(&e))?;
+        profile.encode(&mut body).map_err(|e| failed(&e))?;
+    }
+    Ok(body)
+}
+
+/// Dump jemalloc's heap profile
+pub async fn heap_profile() -> ApiResult<Response> {
+    let profile = tokio::task::spawn_blocking(dump_heap)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Heap profiling failed: {}", e)))??;
+    Ok((
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        profile,
+    )
+        .into_response())
+}
+
+fn dump_heap() -> ApiResult<Vec<u8>> {
+    use tikv_jemalloc_ctl::raw;
+    static DUMPS: AtomicU64 = AtomicU64::new(0);
+
+    // SAFETY: `opt.prof` is a read-only boolean
+    let enabled: bool = unsafe { raw::read(b"opt.prof\0") }.unwrap_or(false);
+    if !enabled {
+        return Err(ApiError::ServiceUnavailable(
+            "Heap profiling is off; start the server with _RJEM_MALLOC_CONF=prof:true".to_string(),
+        ));
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "rune-heap-{}-{}.prof",
+        std::process::id(),
+        DUMPS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let c_path = std::ffi::CString::new(path.to_string_lossy().into_owned())
+        .map_err(|e| ApiError::Internal(format!("Heap profiling failed: {}", e)))?;
+    // SAFETY: `prof.dump` takes a NUL-terminated path, which `c_path` keeps
+    // alive until the call returns
+    unsafe { raw::write(b"prof.dump\0", c_path.as_ptr()) }
+        .map_err(|e| ApiError::Internal(format!("Heap profiling failed: {}", e)))?;
+
+    let profile = std::fs::read(&path)
+        .map_err(|e| ApiError::Internal(format!("Heap profiling failed: {}", e)));
+    let _ = std::fs::remove_file(&path);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode};
+    use pprof::protos::Message;
+    use rune_core::RUNEEngine;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let state = AppState::new(Arc::new(RUNEEngine::new())).with_profiling(ProfilingConfig {
+            token: "s3cret".to_string(),
+        });
+        routes(&state).unwrap().with_state(state)
+    }
+
+    fn get(uri: &str, token: Option<&str>) -> Request {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_no_routes_without_a_profiler() {
+        assert!(routes(&AppState::new(Arc::new(RUNEEngine::new()))).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_token_is_required() {
+        for token in [None, Some("guess")] {
+            let response = app()
+                .oneshot(get("/debug/pprof/profile?seconds=1", token))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = app()
+            .oneshot(get("/debug/pprof/profile?seconds=0", Some("s3cret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_profile() {
+        let response = app()
+            .oneshot(get("/debug/pprof/profile?seconds=1", Some("s3cret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let profile = pprof::protos::Profile::decode(body.as_ref()).unwrap();
+        assert!(!profile.string_table.is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+//! Read replicas
+//!
+//! A primary keeps a change log of its fact store when
+//! `RUNE_CHANGE_LOG_SIZE` is set and serves it at
+//! `GET /v1/replication/changes` on the management plane. A server started
+//! with `RUNE_REPLICA_OF` pointing at that plane follows it:
+//!
+//! - `RUNE_REPLICA_OF`: base URL of the primary's management plane
+//! - `RUNE_REPLICA_POLL_MS`: pause between polls once caught up (default 500)
+//! - `RUNE_REPLICA_BATCH_SIZE`: changes fetched per poll (default 1000)
+//! - `RUNE_REPLICA_MAX_STALENESS_MS`: default staleness bound (default 5000)
+//! - `RUNE_REPLICA_ESCALATE_URL`: base URL of the primary's data plane
+//!
+//! Requests may tighten or relax the bound with `maxStalenessMs`. When the
+//! replica is further behind than a request allows, it forwards the request
+//! to the escalation URL and relays the primary's answer, or answers 503
+//! when no escalation URL is set. Forward-auth requests always use the
+//! default bound and never escalate.
+
+use crate::api::{AuthorizeRequest, AuthorizeResponse};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+use anyhow::Context;
+use rune_core::{ChangeBatch, ChangePosition, RUNEEngine, RUNEError, ReplicaConfig};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Time allowed for a call to the primary
+const PRIMARY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Settings for following a primary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationConfig {
+    /// Base URL of the primary's management plane
+    pub primary: String,
+    /// Pause between polls once caught up
+    pub poll_interval: Duration,
+    /// Changes fetched per poll
+    pub batch_size: usize,
+    /// Staleness allowed for requests without their own bound
+    pub max_staleness: Duration,
+    /// Base URL of the primary's data plane for requests the replica is
+    /// too stale to answer
+    pub escalate_to: Option<String>,
+}
+
+impl ReplicationConfig {
+    /// Follow `primary` with the default settings
+    pub fn new(primary: impl Into<String>) -> Self {
+        ReplicationConfig {
+            primary: primary.into(),
+            poll_interval: Duration::from_millis(500),
+            batch_size: 1000,
+            max_staleness: ReplicaConfig::default().max_staleness,
+            escalate_to: None,
+        }
+    }
+
+    /// Read the `RUNE_REPLICA_*` variables; `None` when `RUNE_REPLICA_OF`
+    /// is unset
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        fn millis(name: &str) -> anyhow::Result<Option<Duration>> {
+            std::env::var(name)
+                .ok()
+                .map(|value| {
+                    value
+                        .parse()
+                        .map(Duration::from_millis)
+                        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        }
+
+        let Ok(primary) = std::env::var("RUNE_REPLICA_OF") else {
+            return Ok(None);
+        };
+        let mut config = Self::new(primary);
+        if let Some(interval) = millis("RUNE_REPLICA_POLL_MS")? {
+            config.poll_interval = interval;
+        }
+        if let Some(max) = millis("RUNE_REPLICA_MAX_STALENESS_MS")? {
+            config.max_staleness = max;
+        }
+        if let Ok(size) = std::env::var("RUNE_REPLICA_BATCH_SIZE") {
+            config.batch_size = size
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid RUNE_REPLICA_BATCH_SIZE: {}", e))?;
+        }
+        config.escalate_to = std::env::var("RUNE_REPLICA_ESCALATE_URL").ok();
+        Ok(Some(config))
+    }
+
+    /// Engine settings for the replica
+    pub fn replica_config(&self) -> ReplicaConfig {
+        ReplicaConfig {
+            max_staleness: self.max_staleness,
+        }
+    }
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(PRIMARY_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Read changes after `since` from a primary's management plane
+pub async fn fetch_changes(
+    client: &reqwest::Client,
+    primary: &str,
+    since: Option<ChangePosition>,
+    limit: usize,
+) -> anyhow::Result<ChangeBatch> {
+    let mut query = vec![("limit", limit.to_string())];
+    if let Some(since) = since {
+        query.push(("log", since.log.to_string()));
+        query.push(("since", since.seq.to_string()));
+    }
+    let url = format!("{}/v1/replication/changes", primary.trim_end_matches('/'));
+    client
+        .get(&url)
+        .query(&query)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("Failed to fetch changes from {}", url))?
+        .json()
+        .await
+        .context("Invalid change batch")
+}
+
+/// Start the task that keeps `engine` in step with the primary
+pub fn spawn_follower(engine: Arc<RUNEEngine>, config: ReplicationConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = client();
+        loop {
+            let since = engine.replica_status().and_then(|status| status.position);
+            let caught_up = match fetch_changes(&client, &config.primary, since, config.batch_size)
+                .await
+            {
+                Ok(batch) => {
+                    let current = batch.is_current();
+                    let engine = engine.clone();
+                    match tokio::task::spawn_blocking(move || engine.apply_changes(&batch)).await {
+                        Ok(Ok(status)) => {
+                            crate::metrics::record_replica_pending(status.pending);
+                            current
+                        }
+                        Ok(Err(e)) => {
+                            warn!("Applying replicated changes failed: {}", e);
+                            true
+                        }
+                        Err(e) => {
+                            warn!("Applying replicated changes failed: {}", e);
+                            true
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("{:#}", e);
+                    true
+                }
+            };
+            // Fetch the next batch right away while catching up
+            if caught_up {
+                tokio::time::sleep(config.poll_interval).await;
+            }
+        }
+    })
+}
+
+/// Forwards requests a stale replica cannot answer to the primary
+pub struct Escalation {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Escalation {
+    /// Escalate to the primary data plane at `base_url`
+    pub fn new(base_url: &str) -> Self {
+        Escalation {
+            client: client(),
+            url: format!("{}/v1/authorize", base_url.trim_end_matches('/')),
+        }
+    }
+
+    /// Ask the primary for a decision, explained in one of the
+    /// `accept_language` languages
+    pub async fn authorize(
+        &self,
+        request: &AuthorizeRequest,
+        accept_language: Option<&str>,
+    ) -> ApiResult<AuthorizeResponse> {
+        let unavailable = |e: reqwest::Error| {
+            ApiError::ServiceUnavailable(format!("Replica is stale and the primary failed: {}", e))
+        };
+        let mut post = self.client.post(&self.url).json(request);
+        if let Some(accept_language) = accept_language {
+            post = post.header(reqwest::header::ACCEPT_LANGUAGE, accept_language);
+        }
+        post.send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(unavailable)?
+            .json()
+            .await
+            .map_err(unavailable)
+    }
+}
+
+/// Answer a request the replica was too stale for
+pub(crate) async fn escalate(
+    state: &AppState,
+    request: &AuthorizeRequest,
+    accept_language: Option<&str>,
+    error: RUNEError,
+) -> ApiResult<AuthorizeResponse> {
+    match &state.escalation {
+        Some(escalation) => {
+            debug!("Escalating to the primary: {}", error);
+            crate::metrics::record_replica_escalation();
+            escalation.authorize(request, accept_language).await
+        }
+        None => Err(ApiError::RuneError(error)),
+    }
+}
@@ -0,0 +1,221 @@
+//! Route tables for the data and management planes
+//!
+//! The data plane carries authorization traffic from services and proxies.
+//! The management plane carries everything operators use to inspect and
+//! change a running server. Health checks are served on both, so each
+//! listener can be probed on its own.
+//!
+//! Data plane, where decisions get GeoIP enrichment when configured
+//! ([`crate::geoip`]):
+//!
+//! - `/v1/authorize`, `/v1/authorize/actions`, `/v1/forward-auth`:
+//!   single decisions; the authorize endpoints also take CBOR
+//!   ([`crate::codec`])
+//! - `/v1/authorize/batch`, `/v1/authorize/matrix`, `/v1/prefetch`,
+//!   `/v1/permissions/resources`, `/v1/permissions/principals`: many
+//!   decisions at once
+//! - `/v1/watch`: changes to a decision ([`crate::watch`])
+//!
+//! Management plane:
+//!
+//! - `/v1/admin/reload`, `/v1/admin/versions`: reloads and rollbacks
+//! - `/v1/admin/staged`, `/v1/admin/shadow`: staging and shadowing
+//!   ([`crate::shadow`])
+//! - `/v1/policies/validate`, `/v1/policies/:id/scope`,
+//!   `/v1/meta/vocabulary`: policy validation and vocabulary
+//! - `/v1/admin/flags`: rule flags
+//! - `/v1/admin/compact`, `/v1/facts/snapshot`, `/v1/facts/history`,
+//!   `/v1/facts/as-of`: fact maintenance and history
+//! - `/v1/facts/derived`, `/v1/query`: derived facts and goal queries
+//! - `/v1/sessions`: principal sessions
+//! - `/v1/access-requests`: access requests and their approval
+//! - `/v1/principals/:principal/roles`, `/v1/principals/:principal/groups`,
+//!   `/v1/permissions/:principal`: roles, groups and permission summaries
+//! - `/v1/labels`: governance labels
+//! - `/v1/export`, `/v1/version`: configuration exports, and the version
+//!   and provenance of the active configuration
+//! - `/v1/replication/changes`, `/v1/replication/status`: replication
+//! - `/v1/mirror`: decision mirroring reports ([`crate::mirror`])
+//! - `/v1/usage`: usage by tenant and API key ([`crate::usage`])
+//! - `/metrics`, `/v1/slo`: metrics and latency objectives
+//!   ([`crate::metrics`])
+//! - `/debug/pprof/profile`, `/debug/pprof/heap`: CPU and heap profiles,
+//!   with the `profiling` feature
+//!
+//! Management mutations can carry an `Idempotency-Key` so retries are not
+//! applied twice ([`crate::idempotency`]). Every route but `/v1/watch` and
+//! the health checks runs under its class's response time budget
+//! ([`crate::timeouts`]).
+
+use crate::geoip;
+use crate::handlers;
+use crate::idempotency;
+use crate::state::AppState;
+use crate::timeouts::{self, RouteClass};
+use axum::{
+    middleware,
+    routing::{any, delete, get, post, put},
+    Router,
+};
+
+/// Authorization endpoints
+pub fn data_plane(state: AppState) -> Router {
+    data_routes(&state).merge(health_routes()).with_state(state)
+}
+
+/// Administration, fact inspection and metrics endpoints
+pub fn management_plane(state: AppState) -> Router {
+    management_routes(&state)
+        .merge(health_routes())
+        .with_state(state)
+}
+
+/// Both planes on a single listener
+pub fn combined(state: AppState) -> Router {
+    data_routes(&state)
+        .merge(management_routes(&state))
+        .merge(health_routes())
+        .with_state(state)
+}
+
+fn data_routes(state: &AppState) -> Router<AppState> {
+    let batches = Router::new()
+        .route("/v1/authorize/batch", post(handlers::batch_authorize))
+        .route("/v1/authorize/matrix", post(handlers::authorize_matrix))
+        .route("/v1/prefetch", post(handlers::prefetch))
+        .route(
+            "/v1/permissions/resources",
+            post(handlers::permitted_resources),
+        )
+        .route(
+            "/v1/permissions/principals",
+            post(handlers::permitted_principals),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.timeouts.route(RouteClass::Batch),
+            timeouts::enforce,
+        ));
+    Router::new()
+        .route("/v1/authorize", post(handlers::authorize))
+        .route("/v1/authorize/actions", post(handlers::authorize_actions))
+        .route("/v1/forward-auth", any(handlers::forward_auth))
+        .route_layer(middleware::from_fn_with_state(
+            state.timeouts.route(RouteClass::Authorize),
+            timeouts::enforce,
+        ))
+        .merge(batches)
+        .route_layer(middleware::from_fn_with_state(state.clone(), geoip::enrich))
+        // Watches wait for changes on purpose, so no budget applies
+        .route("/v1/watch", get(handlers::watch))
+}
+
+fn management_routes(state: &AppState) -> Router<AppState> {
+    #[cfg(feature = "profiling")]
+    if let Some(profiling) = crate::profiling::routes(state) {
+        return admin_routes(state).merge(profiling);
+    }
+    admin_routes(state)
+}
+
+fn admin_routes(state: &AppState) -> Router<AppState> {
+    let queries = Router::new()
+        .route("/v1/facts/derived", get(handlers::derived_facts))
+        .route("/v1/query", post(handlers::query))
+        .route_layer(middleware::from_fn_with_state(
+            state.timeouts.route(RouteClass::Query),
+            timeouts::enforce,
+        ));
+    Router::new()
+        .route("/v1/admin/reload", post(handlers::reload_config))
+        .route(
+            "/v1/admin/staged",
+            post(handlers::stage_config).get(handlers::list_staged),
+        )
+        .route("/v1/admin/staged/:id", delete(handlers::discard_staged))
+        .route(
+            "/v1/admin/shadow",
+            put(handlers::start_shadow)
+                .get(handlers::shadow_report)
+                .delete(handlers::stop_shadow),
+        )
+        .route("/v1/admin/versions", get(handlers::list_versions))
+        .route(
+            "/v1/admin/versions/:version/rollback",
+            post(handlers::rollback_version),
+        )
+        .route("/v1/policies/validate", post(handlers::validate_policies))
+        .route("/v1/policies/:id/scope", get(handlers::policy_scope))
+        .route("/v1/meta/vocabulary", get(handlers::vocabulary))
+        .route("/v1/admin/flags", get(handlers::list_rule_flags))
+        .route(
+            "/v1/admin/flags/:key",
+            put(handlers::update_rule_flag).delete(handlers::reset_rule_flag),
+        )
+        .route("/v1/admin/compact", post(handlers::compact_facts))
+        .route(
+            "/v1/sessions",
+            get(handlers::list_sessions).post(handlers::open_session),
+        )
+        .route("/v1/sessions/:id", delete(handlers::close_session))
+        .route(
+            "/v1/access-requests",
+            get(handlers::list_access_requests).post(handlers::open_access_request),
+        )
+        .route("/v1/access-requests/:id", get(handlers::get_access_request))
+        .route(
+            "/v1/access-requests/:id/approve",
+            post(handlers::approve_access_request),
+        )
+        .route(
+            "/v1/access-requests/:id/deny",
+            post(handlers::deny_access_request),
+        )
+        .route(
+            "/v1/replication/changes",
+            get(handlers::replication_changes),
+        )
+        .route("/v1/replication/status", get(handlers::replication_status))
+        .route(
+            "/v1/facts/snapshot",
+            get(handlers::export_fact_snapshot).put(handlers::import_fact_snapshot),
+        )
+        .route("/v1/facts/history", get(handlers::fact_history))
+        .route("/v1/facts/as-of", get(handlers::facts_as_of))
+        .route("/v1/mirror", get(handlers::mirror_report))
+        .route("/v1/slo", get(handlers::slo_report))
+        .route(
+            "/v1/usage",
+            get(handlers::usage).delete(handlers::reset_usage),
+        )
+        .route(
+            "/v1/permissions/:principal",
+            get(handlers::permission_summary),
+        )
+        .route(
+            "/v1/principals/:principal/roles/:role",
+            put(handlers::grant_role).delete(handlers::revoke_role),
+        )
+        .route(
+            "/v1/principals/:principal/groups/:group",
+            put(handlers::add_to_group).delete(handlers::remove_from_group),
+        )
+        .route("/v1/labels", get(handlers::labels))
+        .route("/v1/export", get(handlers::export))
+        .route("/v1/version", get(handlers::version))
+        .route("/metrics", get(handlers::metrics))
+        .route_layer(middleware::from_fn_with_state(
+            state.timeouts.route(RouteClass::Management),
+            timeouts::enforce,
+        ))
+        .merge(queries)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            idempotency::dedupe,
+        ))
+}
+
+fn health_routes() -> Router<AppState> {
+    Router::new()
+        .route("/health/live", get(handlers::health_live))
+        .route("/health/ready", get(handlers::health_ready))
+}
@@ -0,0 +1,257 @@
+//! Caller-driven shadow evaluation of staged configurations
+//!
+//! A configuration posted to `POST /v1/admin/staged` is built and checked
+//! as a reload would be, but decides nothing. To see how it would treat real
+//! traffic, a caller adds `X-Rune-Shadow-Policy: <id>` to `/v1/authorize`:
+//! the active configuration answers as usual, and staged configuration `id`
+//! decides the same request against the same live facts, off the request
+//! path. Its decision is never returned. It is counted in
+//! `rune_shadow_decisions_total` by staged id and outcome (`match`,
+//! `mismatch` or `error`, with `unknown` for ids that are not staged), and
+//! mismatches are logged.
+//!
+//! Before a rollout, an operator can shadow all traffic instead:
+//! `PUT /v1/admin/shadow` with `{"staged": id}` has every `/v1/authorize`
+//! request decided by staged configuration `id` as well, the same way, until
+//! `DELETE /v1/admin/shadow` or the staged configuration is dropped.
+//! `GET /v1/admin/shadow` reports how many decisions diverged, with the most
+//! recent divergences. At most [`MAX_PENDING`] shadow evaluations wait at
+//! once; requests beyond that are not shadowed and are counted as dropped.
+//!
+//! Unlike the mirror ([`crate::mirror`]), which samples traffic against a
+//! second server, shadowing stays in this process. Staged configurations
+//! are listed at `GET /v1/admin/staged` and dropped with
+//! `DELETE /v1/admin/staged/:id`.
+
+use crate::api::{self, AuthorizeRequest};
+use crate::metrics;
+use crate::state::AppState;
+use arc_swap::ArcSwapOption;
+use axum::http::HeaderMap;
+use rune_core::{Decision, EngineSnapshot, RUNEEngine, Request};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Header naming the staged configuration to shadow a request with
+pub const SHADOW_HEADER: &str = "x-rune-shadow-policy";
+
+/// Shadow evaluations of all traffic allowed to wait at once
+pub const MAX_PENDING: usize = 256;
+
+/// Divergences kept for the report
+const RECENT_MISMATCHES: usize = 20;
+
+/// Decide `request`, parsed from `req`, with the staged configuration
+/// `headers` name, if any, and record how that compares with the active
+/// `decision`
+pub fn shadow(
+    state: &AppState,
+    headers: &HeaderMap,
+    req: &AuthorizeRequest,
+    request: &Request,
+    decision: Decision,
+) {
+    if let Some(run) = state.shadow_mode.current() {
+        run.offer(req, request, decision);
+    }
+    let Some(value) = headers.get(SHADOW_HEADER) else {
+        return;
+    };
+    let handle = value
+        .to_str()
+        .ok()
+        .and_then(|id| id.trim().parse::<u64>().ok())
+        .and_then(|id| Some((id, state.engine.staged_handle(id)?)));
+    let Some((id, handle)) = handle else {
+        // Caller-supplied values would make unbounded labels
+        metrics::record_shadow_decision("unknown".to_string(), "unknown");
+        debug!("No staged configuration for shadow header {:?}", value);
+        return;
+    };
+
+    let request = request.clone();
+    let summary = format!("{} {} {}", req.principal, req.action, req.resource);
+    tokio::task::spawn_blocking(move || {
+        let outcome = match handle.authorize(&request) {
+            Ok(result) if result.decision == decision => "match",
+            Ok(result) => {
+                warn!(
+                    "Shadow mismatch: {} -> {:?} active, {:?} staged {}",
+                    summary, decision, result.decision, id
+                );
+                "mismatch"
+            }
+            Err(e) => {
+                debug!("Shadow evaluation with staged {} failed: {}", id, e);
+                "error"
+            }
+        };
+        metrics::record_shadow_decision(id.to_string(), outcome);
+    });
+}
+
+/// A request the staged configuration decided differently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowMismatch {
+    /// Principal of the request
+    pub principal: String,
+    /// Action of the request
+    pub action: String,
+    /// Resource of the request
+    pub resource: String,
+    /// The active configuration's decision
+    pub active: api::Decision,
+    /// The staged configuration's decision
+    pub staged: api::Decision,
+}
+
+/// What shadowing all traffic has found so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowReport {
+    /// Staged configuration traffic is shadowed with
+    pub staged: u64,
+    /// When shadowing started, in milliseconds since the Unix epoch
+    pub since_ms: u64,
+    /// Requests the staged configuration decided
+    pub evaluated: u64,
+    /// Decided requests it decided differently
+    pub mismatched: u64,
+    /// Requests it failed to decide
+    pub failed: u64,
+    /// Requests not shadowed because too many evaluations were waiting
+    pub dropped: u64,
+    /// Most recent divergences, oldest first
+    pub recent_mismatches: VecDeque<ShadowMismatch>,
+}
+
+/// Shadowing of all traffic with one staged configuration
+pub struct ShadowRun {
+    staged: u64,
+    handle: EngineSnapshot,
+    pending: AtomicUsize,
+    report: Mutex<ShadowReport>,
+}
+
+impl ShadowRun {
+    /// Decide `request`, parsed from `req`, with the staged configuration
+    /// off the request path, and record how that compares with the active
+    /// `decision`
+    fn offer(self: &Arc<Self>, req: &AuthorizeRequest, request: &Request, decision: Decision) {
+        if self.pending.fetch_add(1, Ordering::AcqRel) >= MAX_PENDING {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            self.update(|report| report.dropped += 1);
+            return;
+        }
+
+        let run = self.clone();
+        let request = request.clone();
+        let (principal, action, resource) = (
+            req.principal.clone(),
+            req.action.clone(),
+            req.resource.clone(),
+        );
+        tokio::task::spawn_blocking(move || {
+            let outcome = run.handle.authorize(&request);
+            run.pending.fetch_sub(1, Ordering::AcqRel);
+            let id = run.staged;
+            let outcome = match outcome {
+                Ok(result) if result.decision == decision => {
+                    run.update(|report| report.evaluated += 1);
+                    "match"
+                }
+                Ok(result) => {
+                    warn!(
+                        "Shadow mismatch: {} {} {} -> {:?} active, {:?} staged {}",
+                        principal, action, resource, decision, result.decision, id
+                    );
+                    run.update(|report| {
+                        report.evaluated += 1;
+                        report.mismatched += 1;
+                        if report.recent_mismatches.len() == RECENT_MISMATCHES {
+                            report.recent_mismatches.pop_front();
+                        }
+                        report.recent_mismatches.push_back(ShadowMismatch {
+                            principal,
+                            action,
+                            resource,
+                            active: decision.into(),
+                            staged: result.decision.into(),
+                        });
+                    });
+                    "mismatch"
+                }
+                Err(e) => {
+                    debug!("Shadow evaluation with staged {} failed: {}", id, e);
+                    run.update(|report| report.failed += 1);
+                    "error"
+                }
+            };
+            metrics::record_shadow_decision(id.to_string(), outcome);
+        });
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut ShadowReport) -> T) -> T {
+        change(&mut self.report.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// What the run has found so far
+    pub fn report(&self) -> ShadowReport {
+        self.update(|report| report.clone())
+    }
+}
+
+/// The staged configuration all traffic is shadowed with, if any
+#[derive(Default)]
+pub struct ShadowMode {
+    run: ArcSwapOption<ShadowRun>,
+}
+
+impl ShadowMode {
+    /// Shadowing off
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shadow all traffic with staged configuration `id`, replacing any
+    /// earlier run; `None` if `id` is not staged
+    pub fn start(&self, engine: &RUNEEngine, id: u64) -> Option<ShadowReport> {
+        let since_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let run = Arc::new(ShadowRun {
+            staged: id,
+            handle: engine.staged_handle(id)?,
+            pending: AtomicUsize::new(0),
+            report: Mutex::new(ShadowReport {
+                staged: id,
+                since_ms,
+                ..ShadowReport::default()
+            }),
+        });
+        let report = run.report();
+        self.run.store(Some(run));
+        Some(report)
+    }
+
+    /// Stop shadowing; the final report, or `None` if shadowing was off
+    pub fn stop(&self) -> Option<ShadowReport> {
+        self.run.swap(None).map(|run| run.report())
+    }
+
+    /// Stop shadowing if it uses staged configuration `id`
+    pub fn stop_staged(&self, id: u64) {
+        self.run
+            .rcu(|run| run.clone().filter(|run| run.staged != id));
+    }
+
+    /// The current run, if shadowing is on
+    pub fn current(&self) -> Option<Arc<ShadowRun>> {
+        self.run.load_full()
+    }
+}
@@ -0,0 +1,279 @@
+//! HMAC signatures over authorization decisions
+//!
+//! Services that enforce RUNE decisions often sit behind proxies, sidecars or
+//! message queues. When a [`ResponseSigner`] is configured, every
+//! [`AuthorizeResponse`] carries a [`ResponseSignature`] binding the decision
+//! to the request it answers, the configuration generation that produced it
+//! and the time it was issued. Holders of the shared key can check the
+//! signature with [`ResponseSigner::verify`] and reject decisions that were
+//! altered or replayed for a different request.
+//!
+//! The MAC covers these newline-separated fields:
+//!
+//! ```text
+//! rune-sig-v1
+//! <decision>        PERMIT | DENY | FORBID
+//! <generation>      engine configuration generation
+//! <timestamp>       milliseconds since the Unix epoch
+//! <request hash>    hex SHA-256 of the canonical request
+//! ```
+
+use crate::api::{AuthorizeRequest, AuthorizeResponse, Decision, ResponseSignature};
+use anyhow::{bail, Context};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Algorithm name reported in signatures
+pub const ALGORITHM: &str = "HMAC-SHA256";
+
+/// Shortest key accepted, matching the HMAC-SHA256 output size
+pub const MIN_KEY_LEN: usize = 32;
+
+const VERSION_TAG: &str = "rune-sig-v1";
+
+/// Signs authorization responses with a shared secret
+#[derive(Clone)]
+pub struct ResponseSigner {
+    key: Vec<u8>,
+    key_id: Option<String>,
+}
+
+impl std::fmt::Debug for ResponseSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseSigner")
+            .field("key", &"<redacted>")
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl ResponseSigner {
+    /// Create a signer from raw key bytes
+    pub fn new(key: impl Into<Vec<u8>>) -> anyhow::Result<Self> {
+        let key = key.into();
+        if key.len() < MIN_KEY_LEN {
+            bail!(
+                "Signing key must be at least {} bytes, got {}",
+                MIN_KEY_LEN,
+                key.len()
+            );
+        }
+        Ok(ResponseSigner { key, key_id: None })
+    }
+
+    /// Create a signer from a hex-encoded key
+    pub fn from_hex(key: &str) -> anyhow::Result<Self> {
+        let key = hex::decode(key.trim()).context("Signing key is not valid hex")?;
+        Self::new(key)
+    }
+
+    /// Label signatures with a key identifier, so verifiers can pick the
+    /// right key during rotation
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Sign a decision for the given request
+    pub fn sign(
+        &self,
+        request: &AuthorizeRequest,
+        decision: Decision,
+        generation: u64,
+    ) -> ResponseSignature {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let request_hash = request_hash(request);
+        let mac = self.mac(decision, generation, timestamp, &request_hash);
+
+        ResponseSignature {
+            algorithm: ALGORITHM.to_string(),
+            key_id: self.key_id.clone(),
+            generation,
+            timestamp,
+            request_hash,
+            signature: hex::encode(mac.finalize().into_bytes()),
+        }
+    }
+
+    /// Check that a response was signed with this key for this request
+    ///
+    /// Freshness is left to the caller: compare `signature.timestamp` against
+    /// the clock with whatever skew the deployment tolerates.
+    pub fn verify(&self, request: &AuthorizeRequest, response: &AuthorizeResponse) -> bool {
+        let Some(sig) = &response.signature else {
+            return false;
+        };
+        if sig.algorithm != ALGORITHM || sig.request_hash != request_hash(request) {
+            return false;
+        }
+        let Ok(expected) = hex::decode(&sig.signature) else {
+            return false;
+        };
+
+        self.mac(
+            response.decision,
+            sig.generation,
+            sig.timestamp,
+            &sig.request_hash,
+        )
+        .verify_slice(&expected)
+        .is_ok()
+    }
+
+    fn mac(
+        &self,
+        decision: Decision,
+        generation: u64,
+        timestamp: u64,
+        request_hash: &str,
+    ) -> HmacSha256 {
+        let decision = match decision {
+            Decision::Permit => "PERMIT",
+            Decision::Deny => "DENY",
+            Decision::Forbid => "FORBID",
+        };
+        let payload = format!(
+            "{}\n{}\n{}\n{}\n{}",
+            VERSION_TAG, decision, generation, timestamp, request_hash
+        );
+
+        let mut mac =
+            HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// Hex SHA-256 of a request in canonical form
+///
+/// Context objects are hashed with their keys sorted, so semantically equal
+/// requests hash the same regardless of how the client ordered its JSON.
+pub fn request_hash(request: &AuthorizeRequest) -> String {
+    let mut context: Vec<_> = request.context.iter().collect();
+    context.sort_by(|a, b| a.0.cmp(b.0));
+    let context: serde_json::Map<_, _> = context
+        .into_iter()
+        .map(|(k, v)| (k.clone(), canonical_json(v)))
+        .collect();
+
+    let mut hasher = Sha256::new();
+    for field in [&request.principal, &request.action, &request.resource] {
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(serde_json::Value::Object(context).to_string().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn canonical_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.clone(), canonical_json(v)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(canonical_json).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn signer() -> ResponseSigner {
+        ResponseSigner::new(vec![7u8; 32])
+            .unwrap()
+            .with_key_id("k1")
+    }
+
+    fn request(context: serde_json::Value) -> AuthorizeRequest {
+        serde_json::from_value(json!({
+            "principal": "user:alice",
+            "action": "read",
+            "resource": "file:/docs/1",
+            "context": context,
+        }))
+        .unwrap()
+    }
+
+    fn signed(signer: &ResponseSigner, req: &AuthorizeRequest) -> AuthorizeResponse {
+        AuthorizeResponse {
+            decision: Decision::Permit,
+            reasons: Vec::new(),
+            reason_codes: Vec::new(),
+            locale: None,
+            warnings: Vec::new(),
+            valid_for_ms: 0,
+            diagnostics: None,
+            proofs: Vec::new(),
+            near_misses: Vec::new(),
+            detail: None,
+            signature: Some(signer.sign(req, Decision::Permit, 3)),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = signer();
+        let req = request(json!({}));
+        let response = signed(&signer, &req);
+
+        let sig = response.signature.as_ref().unwrap();
+        assert_eq!(sig.key_id.as_deref(), Some("k1"));
+        assert_eq!(sig.generation, 3);
+        assert!(signer.verify(&req, &response));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let signer = signer();
+        let req = request(json!({}));
+
+        let mut flipped = signed(&signer, &req);
+        flipped.decision = Decision::Deny;
+        assert!(!signer.verify(&req, &flipped));
+
+        let mut stale = signed(&signer, &req);
+        stale.signature.as_mut().unwrap().generation = 2;
+        assert!(!signer.verify(&req, &stale));
+
+        // A decision for alice cannot be replayed for bob
+        let response = signed(&signer, &req);
+        let mut other = req.clone();
+        other.principal = "user:bob".to_string();
+        assert!(!signer.verify(&other, &response));
+
+        let foreign = ResponseSigner::new(vec![8u8; 32]).unwrap();
+        assert!(!foreign.verify(&req, &response));
+    }
+
+    #[test]
+    fn test_request_hash_ignores_context_key_order() {
+        let a = request(json!({"ip": "10.0.0.1", "meta": {"x": 1, "y": 2}}));
+        let b = request(json!({"meta": {"y": 2, "x": 1}, "ip": "10.0.0.1"}));
+        assert_eq!(request_hash(&a), request_hash(&b));
+        assert_ne!(request_hash(&a), request_hash(&request(json!({}))));
+    }
+
+    #[test]
+    fn test_short_keys_are_rejected() {
+        assert!(ResponseSigner::new(vec![1u8; 16]).is_err());
+        assert!(ResponseSigner::from_hex("not hex").is_err());
+        assert!(ResponseSigner::from_hex(&"ab".repeat(32)).is_ok());
+    }
+}
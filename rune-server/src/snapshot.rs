@@ -0,0 +1,102 @@
+//! Warm restarts from a fact snapshot
+//!
+//! Facts added through the API live in memory, so a restart used to start
+//! from the RUNE file alone. With `RUNE_FACT_SNAPSHOT` set to a path, the
+//! server loads the facts saved there, if the file exists, before it starts
+//! listening, and saves every fact back to it once a graceful shutdown has
+//! drained in-flight requests. The file is a binary snapshot (see
+//! [`rune_core::SnapshotFormat`]), written next to its destination and then
+//! renamed over it, so a crash mid-write leaves the previous snapshot.
+//!
+//! The snapshot is not encrypted: every fact's arguments, labels and
+//! time-to-live are stored as plain CBOR. On Unix the file is created
+//! readable and writable by its owner only; keep it on an encrypted volume
+//! if the facts are sensitive.
+//!
+//! Facts whose time-to-live ran out while the server was down are not
+//! loaded. A snapshot that cannot be read stops the server rather than
+//! letting it start without the facts it was meant to have.
+
+use anyhow::Context;
+use rune_core::{RUNEEngine, SnapshotFormat};
+use std::path::{Path, PathBuf};
+
+/// Read `RUNE_FACT_SNAPSHOT`
+pub fn path_from_env() -> Option<PathBuf> {
+    std::env::var_os("RUNE_FACT_SNAPSHOT")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Load the facts saved at `path`, returning how many; `None` when there
+/// is no snapshot yet
+pub fn restore(engine: &RUNEEngine, path: &Path) -> anyhow::Result<Option<usize>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    let loaded = engine
+        .import_facts(&bytes)
+        .with_context(|| format!("Failed to load fact snapshot {}", path.display()))?;
+    Ok(Some(loaded))
+}
+
+/// Save every fact to `path`, returning the snapshot's size in bytes
+pub fn save(engine: &RUNEEngine, path: &Path) -> anyhow::Result<usize> {
+    let bytes = engine.export_facts(SnapshotFormat::Binary)?;
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    write_private(&partial, &bytes)
+        .and_then(|()| std::fs::rename(&partial, path))
+        .with_context(|| format!("Failed to write fact snapshot {}", path.display()))?;
+    Ok(bytes.len())
+}
+
+/// Write `bytes` to a new file at `path` that, on Unix, only its owner
+/// can read
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rune_core::Value;
+
+    #[test]
+    fn test_save_and_restore() {
+        let path = std::env::temp_dir().join(format!("rune-facts-{}.snap", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let engine = RUNEEngine::new();
+        assert_eq!(restore(&engine, &path).unwrap(), None);
+
+        engine.add_fact("member", vec![Value::string("alice")]);
+        assert!(save(&engine, &path).unwrap() > 0);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let restarted = RUNEEngine::new();
+        assert_eq!(restore(&restarted, &path).unwrap(), Some(1));
+        assert_eq!(restarted.fact_store_len(), 1);
+
+        std::fs::write(&path, b"not a snapshot").unwrap();
+        assert!(restore(&restarted, &path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
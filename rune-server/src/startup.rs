@@ -0,0 +1,536 @@
+//! Startup configuration
+//!
+//! [`ServerConfig::load`] reads everything the server is configured with
+//! before any port is bound: engine settings, the RUNE file, listeners,
+//! response signing, replication, compaction, expiry sweeps, idempotency
+//! keys, GeoIP, anomaly detection, actor tokens, access request webhooks,
+//! decision mirroring, route timeouts, detail shedding, metrics exporters,
+//! latency objectives and the log format.
+//! Problems are collected rather than reported one at a time, so a single
+//! run shows all of them. `rune-server --check` stops after loading and
+//! exits non-zero when anything is wrong.
+//!
+//! The RUNE file comes from `--config` or, failing that, `RUNE_CONFIG`.
+//! With `RUNE_COMPILE_CACHE_DIR` set, its parsed rules are kept there and
+//! read back when the server starts again with the same file (see
+//! [`rune_core::compile_cache`]).
+//! Without one the server starts with no rules or policies and denies every
+//! request, unless `RUNE_ALLOW_ALL_BOOTSTRAP=true` makes it permit them for
+//! development. With `RUNE_ENV=production` both are configuration problems:
+//! a production server does not start without a RUNE file, nor with the
+//! bootstrap override.
+
+use crate::logging::LogFormat;
+use crate::metrics::{MetricsExporters, SloConfig};
+use crate::{
+    ActorTokens, AnomalyConfig, CompactionConfig, DetailShedding, GeoIp, GeoIpConfig,
+    IdempotencyConfig, ListenersConfig, MirrorConfig, ReplicationConfig, ResponseSigner,
+    RouteTimeouts,
+};
+use rune_core::engine::EngineConfig;
+use rune_core::parser::RUNEConfig;
+use rune_core::{parse_rune_file, CompileCache, FailureMode, FailurePolicy, RUNEEngine, RUNEError};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Deployment environment the server runs in, from `RUNE_ENV`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Environment {
+    /// Starts without a configuration if asked to
+    #[default]
+    Development,
+    /// Refuses to start without a RUNE file or with the bootstrap override
+    Production,
+}
+
+impl FromStr for Environment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(Environment::Development),
+            "production" | "prod" => Ok(Environment::Production),
+            other => anyhow::bail!(
+                "Invalid RUNE_ENV: {} (expected development or production)",
+                other
+            ),
+        }
+    }
+}
+
+impl Environment {
+    /// Read `RUNE_ENV`, development when unset
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("RUNE_ENV") {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Environment::default()),
+        }
+    }
+}
+
+/// A RUNE file read at startup
+pub struct RuneFile {
+    /// Where the file was read from
+    pub path: PathBuf,
+    /// File contents, for rendering diagnostics
+    pub source: String,
+    /// The parsed configuration
+    pub config: RUNEConfig,
+}
+
+/// Everything the server needs before it starts listening
+pub struct ServerConfig {
+    /// Deployment environment
+    pub environment: Environment,
+    /// Engine settings, including replica settings when following a primary
+    pub engine: EngineConfig,
+    /// Rules and policies to start with
+    pub rune_file: Option<RuneFile>,
+    /// Data and management plane listeners
+    pub listeners: ListenersConfig,
+    /// Metrics exporters to install
+    pub exporters: MetricsExporters,
+    /// Signer for authorization responses
+    pub signer: Option<ResponseSigner>,
+    /// Primary to follow as a read replica
+    pub replication: Option<ReplicationConfig>,
+    /// Background fact store compaction
+    pub compaction: CompactionConfig,
+    /// Time between sweeps for expired facts; zero disables them
+    pub expiry_sweep: Duration,
+    /// File facts are loaded from at startup and saved to at shutdown
+    pub fact_snapshot: Option<PathBuf>,
+    /// Deduplication of retried management mutations
+    pub idempotency: IdempotencyConfig,
+    /// Opened GeoIP database
+    pub geoip: Option<GeoIp>,
+    /// Decision anomaly detection
+    pub anomaly: Option<AnomalyConfig>,
+    /// Tokens identifying the actors making management changes
+    pub actor_tokens: Option<ActorTokens>,
+    /// URL access request changes are POSTed to
+    pub access_webhook: Option<String>,
+    /// Principal attribute or context key usage is charged by
+    pub usage_tenant_attribute: String,
+    /// Secondary server to compare a sample of decisions with
+    pub mirror: Option<MirrorConfig>,
+    /// Response time budget of each route class
+    pub timeouts: RouteTimeouts,
+    /// Pending evaluations at which decisions lose their detail
+    pub detail_shedding: Option<DetailShedding>,
+    /// Latency objectives of the decision endpoints
+    pub slo: SloConfig,
+    /// How logs are written
+    pub log_format: LogFormat,
+    /// Token guarding the profiling endpoints
+    #[cfg(feature = "profiling")]
+    pub profiling: Option<crate::profiling::ProfilingConfig>,
+}
+
+/// One thing wrong with the server's configuration
+#[derive(Debug)]
+pub enum ConfigProblem {
+    /// An environment setting is missing, malformed or unusable
+    Setting(anyhow::Error),
+    /// The RUNE file could not be read, parsed or applied
+    RuneFile {
+        /// Where the file was read from
+        path: PathBuf,
+        /// File contents, when the file could be read
+        source: Option<String>,
+        /// What went wrong
+        error: RUNEError,
+    },
+}
+
+impl ConfigProblem {
+    /// Format the problem, with source excerpts for RUNE file diagnostics
+    pub fn render(&self, color: bool) -> String {
+        match self {
+            ConfigProblem::Setting(error) => format!("error: {:#}\n", error),
+            ConfigProblem::RuneFile {
+                path,
+                source,
+                error,
+            } => format!(
+                "error: invalid configuration file {}\n{}\n",
+                path.display(),
+                error
+                    .render_with_source(source.as_deref(), color)
+                    .trim_end()
+            ),
+        }
+    }
+}
+
+/// Every problem found while loading the configuration
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<ConfigProblem>);
+
+impl ConfigErrors {
+    /// Format every problem, followed by a count
+    pub fn render(&self, color: bool) -> String {
+        let mut output: String = self.0.iter().map(|p| p.render(color)).collect();
+        output.push_str(&format!(
+            "{} configuration problem{} found\n",
+            self.0.len(),
+            if self.0.len() == 1 { "" } else { "s" }
+        ));
+        output
+    }
+}
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(false))
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+impl ServerConfig {
+    /// Read the whole configuration from the environment and `config_path`
+    /// (or `RUNE_CONFIG`)
+    pub fn load(config_path: Option<&Path>) -> Result<Self, ConfigErrors> {
+        let mut problems = Vec::new();
+        let environment = setting(&mut problems, Environment::from_env()).unwrap_or_default();
+        let engine = setting(&mut problems, engine_config_from_env());
+        let replication = setting(&mut problems, ReplicationConfig::from_env()).flatten();
+        let listeners = setting(&mut problems, ListenersConfig::from_env());
+        let exporters = setting(&mut problems, MetricsExporters::from_env());
+        let signer = setting(&mut problems, signer_from_env()).flatten();
+        let geoip = setting(
+            &mut problems,
+            GeoIpConfig::from_env().and_then(|config| config.map(GeoIp::open).transpose()),
+        )
+        .flatten();
+        let anomaly = setting(&mut problems, AnomalyConfig::from_env()).flatten();
+        let actor_tokens = setting(&mut problems, ActorTokens::from_env()).flatten();
+        let mirror = setting(&mut problems, MirrorConfig::from_env()).flatten();
+        let timeouts = setting(&mut problems, RouteTimeouts::from_env()).unwrap_or_default();
+        let detail_shedding = setting(&mut problems, DetailShedding::from_env()).flatten();
+        let slo = setting(&mut problems, SloConfig::from_env()).unwrap_or_default();
+        let log_format = setting(&mut problems, LogFormat::from_env()).unwrap_or_default();
+        let expiry_sweep = setting(&mut problems, crate::expiry::sweep_interval_from_env())
+            .unwrap_or(crate::expiry::DEFAULT_SWEEP_INTERVAL);
+        #[cfg(feature = "profiling")]
+        let profiling =
+            setting(&mut problems, crate::profiling::ProfilingConfig::from_env()).flatten();
+
+        let mut engine = engine.unwrap_or_default();
+        if let Some(replication) = &replication {
+            engine.replica = Some(replication.replica_config());
+        }
+
+        let config_path = config_path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os("RUNE_CONFIG").map(PathBuf::from));
+        if environment == Environment::Production {
+            problems.extend(production_problems(config_path.is_some(), &engine));
+        }
+        // Posted configurations find their modules where the file's are
+        if engine.builtin_module_dir.is_none() {
+            engine.builtin_module_dir = config_path.as_deref().and_then(Path::parent).map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    dir.to_path_buf()
+                }
+            });
+        }
+        let compile_cache = std::env::var_os("RUNE_COMPILE_CACHE_DIR").map(CompileCache::new);
+        let rune_file = config_path.and_then(|path| {
+            match load_rune_file(&path, &engine, compile_cache.as_ref()) {
+                Ok(file) => Some(file),
+                Err(problem) => {
+                    problems.push(problem);
+                    None
+                }
+            }
+        });
+
+        match (listeners, exporters) {
+            (Some(listeners), Some(exporters)) if problems.is_empty() => Ok(ServerConfig {
+                environment,
+                engine,
+                rune_file,
+                listeners,
+                exporters,
+                signer,
+                replication,
+                compaction: CompactionConfig::from_env(),
+                expiry_sweep,
+                fact_snapshot: crate::snapshot::path_from_env(),
+                idempotency: IdempotencyConfig::from_env(),
+                geoip,
+                anomaly,
+                actor_tokens,
+                access_webhook: crate::access::webhook_from_env(),
+                usage_tenant_attribute: crate::usage::tenant_attribute_from_env(),
+                mirror,
+                timeouts,
+                detail_shedding,
+                slo,
+                log_format,
+                #[cfg(feature = "profiling")]
+                profiling,
+            }),
+            _ => Err(ConfigErrors(problems)),
+        }
+    }
+
+    /// Check what can only be checked asynchronously: that each listener's
+    /// TLS certificate and key load
+    pub async fn verify(&self) -> Result<(), ConfigErrors> {
+        let mut problems = Vec::new();
+        let listeners = std::iter::once(&self.listeners.data).chain(&self.listeners.management);
+        for tls in listeners.filter_map(|listener| listener.tls.as_ref()) {
+            if let Err(e) = tls.load().await {
+                problems.push(ConfigProblem::Setting(e));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(problems))
+        }
+    }
+
+    /// Create the engine with the RUNE file's rules and policies installed
+    pub fn build_engine(&self) -> rune_core::Result<RUNEEngine> {
+        let engine = RUNEEngine::with_config(self.engine.clone());
+        if let Some(file) = &self.rune_file {
+            engine.apply_config(file.config.clone())?;
+        }
+        Ok(engine)
+    }
+}
+
+/// Reasons a production server must not start: no RUNE file to load, or
+/// the allow-all bootstrap override
+fn production_problems(has_rune_file: bool, engine: &EngineConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    if !has_rune_file {
+        problems.push(ConfigProblem::Setting(anyhow::anyhow!(
+            "RUNE_ENV=production requires a RUNE file (--config or RUNE_CONFIG)"
+        )));
+    }
+    if engine.allow_all_bootstrap {
+        problems.push(ConfigProblem::Setting(anyhow::anyhow!(
+            "RUNE_ALLOW_ALL_BOOTSTRAP cannot be enabled with RUNE_ENV=production"
+        )));
+    }
+    problems
+}
+
+/// Keep a setting's value, or record why it is unusable
+fn setting<T>(problems: &mut Vec<ConfigProblem>, result: anyhow::Result<T>) -> Option<T> {
+    result
+        .map_err(|e| problems.push(ConfigProblem::Setting(e)))
+        .ok()
+}
+
+/// Read, parse (through `cache`, if given) and trial-apply a RUNE file
+fn load_rune_file(
+    path: &Path,
+    engine: &EngineConfig,
+    cache: Option<&CompileCache>,
+) -> Result<RuneFile, ConfigProblem> {
+    let problem = |source: Option<&str>, error| ConfigProblem::RuneFile {
+        path: path.to_path_buf(),
+        source: source.map(str::to_string),
+        error,
+    };
+
+    let source = std::fs::read_to_string(path).map_err(|e| problem(None, e.into()))?;
+    let parsed = match cache {
+        Some(cache) => cache.parse(&source),
+        None => parse_rune_file(&source),
+    };
+    let config = parsed
+        .map_err(|e| problem(Some(&source), e))?
+        .with_path(path);
+    // Policies are only compiled when applied
+    RUNEEngine::with_config(engine.clone())
+        .apply_config(config.clone())
+        .map_err(|e| problem(Some(&source), e))?;
+
+    Ok(RuneFile {
+        path: path.to_path_buf(),
+        source,
+        config,
+    })
+}
+
+/// Read `RUNE_SIGNING_KEY` and `RUNE_SIGNING_KEY_ID`
+fn signer_from_env() -> anyhow::Result<Option<ResponseSigner>> {
+    let Ok(key) = std::env::var("RUNE_SIGNING_KEY") else {
+        return Ok(None);
+    };
+    let mut signer = ResponseSigner::from_hex(&key)
+        .map_err(|e| anyhow::anyhow!("Invalid RUNE_SIGNING_KEY: {}", e))?;
+    if let Ok(key_id) = std::env::var("RUNE_SIGNING_KEY_ID") {
+        signer = signer.with_key_id(key_id);
+    }
+    Ok(Some(signer))
+}
+
+/// Engine settings from the environment
+///
+/// `RUNE_EVALUATION_TIMEOUT_MS` sets the Datalog budget. `RUNE_FAILURE_MODE`
+/// sets the failure mode for every dependency, and `RUNE_FAILURE_MODE_DATALOG_ERROR`,
+/// `RUNE_FAILURE_MODE_DATALOG_TIMEOUT` and `RUNE_FAILURE_MODE_CEDAR_ERROR`
+/// override it per dependency. `RUNE_ADAPTIVE_CACHE_TTL=true` derives each
+/// cached decision's TTL from how often its entities' facts change, between
+/// `RUNE_CACHE_TTL_MIN_SECS` and `RUNE_CACHE_TTL_MAX_SECS`.
+/// `RUNE_ALLOW_ALL_BOOTSTRAP=true` permits every request while no rules or
+/// policies are loaded. `RUNE_BUILTIN_MODULE_DIR` is the only directory WASM
+/// builtin modules load from (by default the RUNE file's).
+pub fn engine_config_from_env() -> anyhow::Result<EngineConfig> {
+    fn mode(name: &str) -> anyhow::Result<Option<FailureMode>> {
+        match std::env::var(name) {
+            Ok(value) => Ok(Some(
+                value
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?,
+            )),
+            Err(_) => Ok(None),
+        }
+    }
+
+    let mut config = EngineConfig::default();
+    if let Ok(timeout) = std::env::var("RUNE_EVALUATION_TIMEOUT_MS") {
+        config.timeout_ms = timeout
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_EVALUATION_TIMEOUT_MS: {}", e))?;
+    }
+
+    // Evaluations for async handlers run on a bounded pool of threads
+    if let Ok(threads) = std::env::var("RUNE_EVALUATION_WORKERS") {
+        config.workers.threads = threads
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_EVALUATION_WORKERS: {}", e))?;
+    }
+    if let Ok(queue) = std::env::var("RUNE_EVALUATION_QUEUE") {
+        config.workers.queue = queue
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_EVALUATION_QUEUE: {}", e))?;
+    }
+
+    let mut policy = FailurePolicy::uniform(mode("RUNE_FAILURE_MODE")?.unwrap_or_default());
+    if let Some(mode) = mode("RUNE_FAILURE_MODE_DATALOG_ERROR")? {
+        policy.datalog_error = mode;
+    }
+    if let Some(mode) = mode("RUNE_FAILURE_MODE_DATALOG_TIMEOUT")? {
+        policy.datalog_timeout = mode;
+    }
+    if let Some(mode) = mode("RUNE_FAILURE_MODE_CEDAR_ERROR")? {
+        policy.cedar_error = mode;
+    }
+    config.failure_policy = policy;
+
+    // Keep recent fact changes for replicas to follow
+    if let Ok(size) = std::env::var("RUNE_CHANGE_LOG_SIZE") {
+        config.change_log = size
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_CHANGE_LOG_SIZE: {}", e))?;
+    }
+
+    // Keep attributed fact changes for audits
+    if let Ok(size) = std::env::var("RUNE_FACT_HISTORY_SIZE") {
+        config.fact_history = size
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_FACT_HISTORY_SIZE: {}", e))?;
+    }
+
+    if let Ok(enabled) = std::env::var("RUNE_ADAPTIVE_CACHE_TTL") {
+        config.adaptive_ttl.enabled = enabled
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_ADAPTIVE_CACHE_TTL: {}", e))?;
+    }
+    if let Ok(secs) = std::env::var("RUNE_CACHE_TTL_MIN_SECS") {
+        config.adaptive_ttl.min_ttl_secs = secs
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_CACHE_TTL_MIN_SECS: {}", e))?;
+    }
+    if let Ok(secs) = std::env::var("RUNE_CACHE_TTL_MAX_SECS") {
+        config.adaptive_ttl.max_ttl_secs = secs
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_CACHE_TTL_MAX_SECS: {}", e))?;
+    }
+    // Answer expired decisions while they are re-evaluated in the background
+    if let Ok(ms) = std::env::var("RUNE_STALE_WHILE_REVALIDATE_MS") {
+        config.stale_while_revalidate_ms = ms
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_STALE_WHILE_REVALIDATE_MS: {}", e))?;
+    }
+    if let Ok(enabled) = std::env::var("RUNE_ALLOW_ALL_BOOTSTRAP") {
+        config.allow_all_bootstrap = enabled
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid RUNE_ALLOW_ALL_BOOTSTRAP: {}", e))?;
+    }
+    config.builtin_module_dir = std::env::var_os("RUNE_BUILTIN_MODULE_DIR").map(PathBuf::from);
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_rune_file() {
+        let problem = load_rune_file(
+            Path::new("/nonexistent/config.rune"),
+            &EngineConfig::default(),
+            None,
+        )
+        .err()
+        .unwrap();
+        let rendered = ConfigErrors(vec![problem]).render(false);
+        assert!(rendered.starts_with("error: invalid configuration file /nonexistent/config.rune"));
+        assert!(rendered.ends_with("1 configuration problem found\n"));
+    }
+
+    #[test]
+    fn test_problems_render_with_excerpts() {
+        let source =
+            "version = \"rune/2.0\"\n\n[rules]\nallowed(U) :-\n    member(U, \"admins\".\n";
+        let problem = ConfigProblem::RuneFile {
+            path: PathBuf::from("config.rune"),
+            source: Some(source.to_string()),
+            error: parse_rune_file(source).err().unwrap(),
+        };
+        let errors = ConfigErrors(vec![
+            ConfigProblem::Setting(anyhow::anyhow!("Invalid BIND_ADDRESS: nowhere")),
+            problem,
+        ]);
+
+        let rendered = errors.render(false);
+        assert!(rendered.contains("error: Invalid BIND_ADDRESS: nowhere"));
+        assert!(rendered.contains("invalid configuration file config.rune"));
+        assert!(rendered.contains("member(U, \"admins\"."));
+        assert!(rendered.contains("2 configuration problems found"));
+    }
+
+    #[test]
+    fn test_production_requires_configuration() {
+        assert_eq!(
+            "PRODUCTION".parse::<Environment>().unwrap(),
+            Environment::Production
+        );
+        assert!("staging".parse::<Environment>().is_err());
+
+        let bootstrap = EngineConfig {
+            allow_all_bootstrap: true,
+            ..EngineConfig::default()
+        };
+        assert!(production_problems(true, &EngineConfig::default()).is_empty());
+        let problems = production_problems(false, &bootstrap);
+        let rendered = ConfigErrors(problems).render(false);
+        assert!(rendered.contains("RUNE_ENV=production requires a RUNE file"));
+        assert!(rendered.contains("RUNE_ALLOW_ALL_BOOTSTRAP cannot be enabled"));
+        assert!(rendered.ends_with("2 configuration problems found\n"));
+    }
+}
@@ -1,6 +1,18 @@
 //! Application state
 
-use rune_core::RUNEEngine;
+use crate::access::AccessNotifier;
+use crate::actors::ActorTokens;
+use crate::anomaly::AnomalyDetector;
+use crate::detail::DetailShedding;
+use crate::geoip::GeoIp;
+use crate::idempotency::{IdempotencyConfig, IdempotencyStore};
+use crate::mirror::Mirror;
+use crate::replication::Escalation;
+use crate::shadow::ShadowMode;
+use crate::signing::ResponseSigner;
+use crate::timeouts::RouteTimeouts;
+use crate::usage::UsageMeter;
+use rune_core::{CancellationToken, RUNEEngine};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -15,6 +27,49 @@ pub struct AppState {
 
     /// Debug mode flag
     pub debug: bool,
+
+    /// Signs authorization responses when configured
+    pub signer: Option<Arc<ResponseSigner>>,
+
+    /// Enriches data plane requests with the client's location when configured
+    pub geoip: Option<Arc<GeoIp>>,
+
+    /// Where a stale replica sends requests it cannot answer
+    pub escalation: Option<Arc<Escalation>>,
+
+    /// Watches decisions for anomalies when configured
+    pub anomalies: Option<Arc<AnomalyDetector>>,
+
+    /// Identifies the actors making management changes when configured
+    pub actor_tokens: Option<Arc<ActorTokens>>,
+
+    /// Tells reviewers about access requests when configured
+    pub access_notifier: Option<Arc<AccessNotifier>>,
+
+    /// Replays a sample of decisions against a secondary when configured
+    pub mirror: Option<Arc<Mirror>>,
+
+    /// Staged configuration all traffic is shadowed with, when switched on
+    pub shadow_mode: Arc<ShadowMode>,
+
+    /// Evaluation time charged to the accounts that asked for it
+    pub usage: Arc<UsageMeter>,
+
+    /// Reduces the detail of decisions made under load when configured
+    pub detail_shedding: Option<DetailShedding>,
+
+    /// Responses to management mutations, by idempotency key
+    pub idempotency: Option<Arc<IdempotencyStore>>,
+
+    /// Cancelled on shutdown; long evaluations run under a child of it
+    pub shutdown: CancellationToken,
+
+    /// Response time budget of each route class
+    pub timeouts: RouteTimeouts,
+
+    /// Serves CPU and heap profiles when configured
+    #[cfg(feature = "profiling")]
+    pub profiler: Option<Arc<crate::profiling::Profiler>>,
 }
 
 impl AppState {
@@ -24,6 +79,21 @@ impl AppState {
             engine,
             start_time: Instant::now(),
             debug: false,
+            signer: None,
+            geoip: None,
+            escalation: None,
+            anomalies: None,
+            actor_tokens: None,
+            access_notifier: None,
+            mirror: None,
+            shadow_mode: Arc::new(ShadowMode::new()),
+            usage: Arc::new(UsageMeter::default()),
+            detail_shedding: None,
+            idempotency: None,
+            shutdown: CancellationToken::new(),
+            timeouts: RouteTimeouts::default(),
+            #[cfg(feature = "profiling")]
+            profiler: None,
         }
     }
 
@@ -33,9 +103,97 @@ impl AppState {
             engine,
             start_time: Instant::now(),
             debug,
+            signer: None,
+            geoip: None,
+            escalation: None,
+            anomalies: None,
+            actor_tokens: None,
+            access_notifier: None,
+            mirror: None,
+            shadow_mode: Arc::new(ShadowMode::new()),
+            usage: Arc::new(UsageMeter::default()),
+            detail_shedding: None,
+            idempotency: None,
+            shutdown: CancellationToken::new(),
+            timeouts: RouteTimeouts::default(),
+            #[cfg(feature = "profiling")]
+            profiler: None,
         }
     }
 
+    /// Sign authorization responses with the given signer
+    pub fn with_signer(mut self, signer: ResponseSigner) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Add the client's location to authorization requests
+    pub fn with_geoip(mut self, geoip: Arc<GeoIp>) -> Self {
+        self.geoip = Some(geoip);
+        self
+    }
+
+    /// Forward requests to the primary when this replica is too stale
+    pub fn with_escalation(mut self, escalation: Escalation) -> Self {
+        self.escalation = Some(Arc::new(escalation));
+        self
+    }
+
+    /// Report anomalies in the decisions this server makes
+    pub fn with_anomaly_detector(mut self, detector: AnomalyDetector) -> Self {
+        self.anomalies = Some(Arc::new(detector));
+        self
+    }
+
+    /// Identify the actors making management changes by their tokens
+    pub fn with_actor_tokens(mut self, tokens: ActorTokens) -> Self {
+        self.actor_tokens = Some(Arc::new(tokens));
+        self
+    }
+
+    /// POST access request changes to a webhook
+    pub fn with_access_notifier(mut self, notifier: AccessNotifier) -> Self {
+        self.access_notifier = Some(Arc::new(notifier));
+        self
+    }
+
+    /// Compare a sample of decisions with a secondary server's
+    pub fn with_mirror(mut self, mirror: Mirror) -> Self {
+        self.mirror = Some(Arc::new(mirror));
+        self
+    }
+
+    /// Charge usage with `meter`
+    pub fn with_usage_meter(mut self, meter: UsageMeter) -> Self {
+        self.usage = Arc::new(meter);
+        self
+    }
+
+    /// Leave proofs and rule lists out of decisions made under load
+    pub fn with_detail_shedding(mut self, shedding: DetailShedding) -> Self {
+        self.detail_shedding = Some(shedding);
+        self
+    }
+
+    /// Replay management mutations retried with the same idempotency key
+    pub fn with_idempotency(mut self, config: IdempotencyConfig) -> Self {
+        self.idempotency = Some(Arc::new(IdempotencyStore::new(config)));
+        self
+    }
+
+    /// Answer requests that exceed their route's budget with 504
+    pub fn with_timeouts(mut self, timeouts: RouteTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Serve CPU and heap profiles to holders of the profiling token
+    #[cfg(feature = "profiling")]
+    pub fn with_profiling(mut self, config: crate::profiling::ProfilingConfig) -> Self {
+        self.profiler = Some(Arc::new(crate::profiling::Profiler::new(config)));
+        self
+    }
+
     /// Get uptime in seconds
     pub fn uptime_seconds(&self) -> u64 {
         self.start_time.elapsed().as_secs()
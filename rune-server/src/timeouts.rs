@@ -0,0 +1,377 @@
+//! Response time budgets per route class
+//!
+//! A service checking permissions on its hot path would rather get an
+//! answer it can act on, a 504, than wait past its own deadline. Each class
+//! of route gets its own budget:
+//!
+//! - `authorize` (`RUNE_TIMEOUT_AUTHORIZE_MS`): `/v1/authorize` and
+//!   `/v1/forward-auth`
+//! - `batch` (`RUNE_TIMEOUT_BATCH_MS`): `/v1/authorize/batch`,
+//!   `/v1/permissions/resources`, `/v1/permissions/principals` and
+//!   `/v1/prefetch`
+//! - `query` (`RUNE_TIMEOUT_QUERY_MS`): `/v1/query` and `/v1/facts/derived`
+//! - `management` (`RUNE_TIMEOUT_MANAGEMENT_MS`): the rest of the
+//!   management plane
+//!
+//! Budgets are in milliseconds; unset or zero means no limit. A request
+//! over budget is answered with 504 and a `timing` breakdown of the phases
+//! its handler got through, as soon as the budget runs out: evaluations
+//! run on the engine's worker pool, which drops those still queued at the
+//! deadline, and one still running finishes there without holding up the
+//! answer. That includes a handler that finished, but
+//! late: the caller has stopped waiting either way. Whether a mutation that
+//! timed out was applied is unknown; retry it with its `Idempotency-Key`.
+
+use crate::error::ApiError;
+use crate::metrics;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Class of routes sharing a budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    /// Single authorization decisions
+    Authorize,
+    /// Decisions about many requests or resources at once
+    Batch,
+    /// Goal queries and derived fact listings
+    Query,
+    /// Everything else on the management plane
+    Management,
+}
+
+impl RouteClass {
+    /// Name used in settings, metrics and timeout responses
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RouteClass::Authorize => "authorize",
+            RouteClass::Batch => "batch",
+            RouteClass::Query => "query",
+            RouteClass::Management => "management",
+        }
+    }
+}
+
+impl fmt::Display for RouteClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Budget of each route class; `None` is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteTimeouts {
+    /// Budget of single authorization decisions
+    pub authorize: Option<Duration>,
+    /// Budget of batch decisions
+    pub batch: Option<Duration>,
+    /// Budget of queries
+    pub query: Option<Duration>,
+    /// Budget of other management requests
+    pub management: Option<Duration>,
+}
+
+impl RouteTimeouts {
+    /// Read `RUNE_TIMEOUT_AUTHORIZE_MS`, `RUNE_TIMEOUT_BATCH_MS`,
+    /// `RUNE_TIMEOUT_QUERY_MS` and `RUNE_TIMEOUT_MANAGEMENT_MS`
+    pub fn from_env() -> anyhow::Result<Self> {
+        fn budget(name: &str) -> anyhow::Result<Option<Duration>> {
+            let Ok(value) = std::env::var(name) else {
+                return Ok(None);
+            };
+            let millis: u64 = value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?;
+            Ok((millis > 0).then(|| Duration::from_millis(millis)))
+        }
+
+        Ok(RouteTimeouts {
+            authorize: budget("RUNE_TIMEOUT_AUTHORIZE_MS")?,
+            batch: budget("RUNE_TIMEOUT_BATCH_MS")?,
+            query: budget("RUNE_TIMEOUT_QUERY_MS")?,
+            management: budget("RUNE_TIMEOUT_MANAGEMENT_MS")?,
+        })
+    }
+
+    /// Budget of `class`
+    pub fn budget(&self, class: RouteClass) -> Option<Duration> {
+        match class {
+            RouteClass::Authorize => self.authorize,
+            RouteClass::Batch => self.batch,
+            RouteClass::Query => self.query,
+            RouteClass::Management => self.management,
+        }
+    }
+
+    /// Whether any class has a budget
+    pub fn is_enabled(&self) -> bool {
+        [
+            RouteClass::Authorize,
+            RouteClass::Batch,
+            RouteClass::Query,
+            RouteClass::Management,
+        ]
+        .into_iter()
+        .any(|class| self.budget(class).is_some())
+    }
+
+    /// State for an [`enforce`] layer over the routes of `class`
+    pub fn route(&self, class: RouteClass) -> RouteBudget {
+        RouteBudget {
+            class,
+            limit: self.budget(class),
+        }
+    }
+}
+
+/// Budget applied by one layer
+#[derive(Debug, Clone, Copy)]
+pub struct RouteBudget {
+    class: RouteClass,
+    limit: Option<Duration>,
+}
+
+/// Phases a request's handler has completed, for timeout reports
+///
+/// Handlers find it in the request extensions and [`mark`](Self::mark) the
+/// end of each phase. Without a budget on the route there is nothing to
+/// report, and handlers mark a detached default.
+#[derive(Debug, Clone)]
+pub struct RequestTiming {
+    inner: Arc<Mutex<Marks>>,
+    /// The route's budget, if any
+    limit: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Marks {
+    start: Instant,
+    last: Instant,
+    phases: Vec<PhaseTiming>,
+    /// Phase begun but not yet marked
+    current: Option<&'static str>,
+}
+
+impl Default for RequestTiming {
+    fn default() -> Self {
+        let now = Instant::now();
+        RequestTiming {
+            inner: Arc::new(Mutex::new(Marks {
+                start: now,
+                last: now,
+                phases: Vec::new(),
+                current: None,
+            })),
+            limit: None,
+        }
+    }
+}
+
+impl RequestTiming {
+    fn with_limit(limit: Duration) -> Self {
+        RequestTiming {
+            limit: Some(limit),
+            ..RequestTiming::default()
+        }
+    }
+
+    /// When the route's budget runs out, for handlers to pass on to work
+    /// they wait for
+    pub fn deadline(&self) -> Option<Instant> {
+        let start = self
+            .inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .start;
+        self.limit.map(|limit| start + limit)
+    }
+
+    /// Record that `phase` is under way, so a timeout before it is marked
+    /// reports it as unfinished
+    pub fn begin(&self, phase: &'static str) {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .current = Some(phase);
+    }
+
+    /// Record that `phase` ended now, having started when the previous one
+    /// ended
+    pub fn mark(&self, phase: &'static str) {
+        let mut marks = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let took = now.duration_since(marks.last);
+        marks.last = now;
+        marks.current = None;
+        marks.phases.push(PhaseTiming {
+            phase: phase.to_string(),
+            elapsed_ms: millis(took),
+            unfinished: false,
+        });
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .start
+            .elapsed()
+    }
+
+    fn report(&self, class: RouteClass, budget: Duration) -> TimingBreakdown {
+        let marks = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let unfinished_ms = millis(marks.last.elapsed());
+        let mut phases = marks.phases.clone();
+        if let Some(phase) = marks.current {
+            phases.push(PhaseTiming {
+                phase: phase.to_string(),
+                elapsed_ms: unfinished_ms,
+                unfinished: true,
+            });
+        }
+        TimingBreakdown {
+            route: class.to_string(),
+            budget_ms: millis(budget),
+            elapsed_ms: millis(marks.start.elapsed()),
+            phases,
+            unfinished_ms,
+        }
+    }
+}
+
+/// Where the time went in a request that ran over budget
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimingBreakdown {
+    /// Route class whose budget was exceeded
+    pub route: String,
+    /// The budget
+    pub budget_ms: f64,
+    /// Time from the request reaching the route to the 504
+    pub elapsed_ms: f64,
+    /// Phases the handler completed, in order, then the one it was in when
+    /// the budget ran out, if it had begun one
+    pub phases: Vec<PhaseTiming>,
+    /// Time since the last completed phase, spent in the unfinished one
+    pub unfinished_ms: f64,
+}
+
+/// One phase of a handler
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTiming {
+    /// Phase name, e.g. `parse` or `evaluate`
+    pub phase: String,
+    /// Time the phase took, or had taken when the budget ran out
+    pub elapsed_ms: f64,
+    /// Whether the phase was still running when the budget ran out
+    #[serde(default)]
+    pub unfinished: bool,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Answer with 504 once the route's budget is spent
+///
+/// Handlers hand their evaluations to the engine's worker pool with the
+/// [`RequestTiming::deadline`], so the runtime stays free to answer on time
+/// while an evaluation is still running. A handler that does not yield is
+/// only caught when it returns, so its late response is replaced too.
+pub async fn enforce(
+    State(budget): State<RouteBudget>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(limit) = budget.limit else {
+        return next.run(request).await;
+    };
+    let timing = RequestTiming::with_limit(limit);
+    request.extensions_mut().insert(timing.clone());
+
+    match tokio::time::timeout(limit, next.run(request)).await {
+        Ok(response) if timing.elapsed() <= limit => response,
+        _ => {
+            metrics::record_route_timeout(budget.class.as_str());
+            ApiError::Timeout(timing.report(budget.class, limit)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::to_bytes, http::StatusCode, middleware, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    async fn slow(timing: Option<Extension<RequestTiming>>) -> &'static str {
+        if let Some(Extension(timing)) = timing {
+            timing.mark("parse");
+            timing.begin("evaluate");
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        "done"
+    }
+
+    fn app(timeouts: RouteTimeouts) -> Router {
+        Router::new()
+            .route("/slow", get(slow))
+            .route_layer(middleware::from_fn_with_state(
+                timeouts.route(RouteClass::Query),
+                enforce,
+            ))
+    }
+
+    fn request() -> Request {
+        Request::builder()
+            .uri("/slow")
+            .body(axum::body::Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_over_budget_is_504_with_timing() {
+        let timeouts = RouteTimeouts {
+            query: Some(Duration::from_millis(20)),
+            ..RouteTimeouts::default()
+        };
+        let response = app(timeouts).oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["error"], "timeout");
+        assert_eq!(json["timing"]["route"], "query");
+        assert_eq!(json["timing"]["budgetMs"], 20.0);
+        assert_eq!(json["timing"]["phases"][0]["phase"], "parse");
+        assert_eq!(json["timing"]["phases"][0]["unfinished"], false);
+        assert_eq!(json["timing"]["phases"][1]["phase"], "evaluate");
+        assert_eq!(json["timing"]["phases"][1]["unfinished"], true);
+        assert!(json["timing"]["elapsedMs"].as_f64().unwrap() >= 20.0);
+    }
+
+    #[tokio::test]
+    async fn test_within_budget_or_unlimited() {
+        let unlimited = app(RouteTimeouts {
+            authorize: Some(Duration::from_millis(1)),
+            ..RouteTimeouts::default()
+        });
+        let response = unlimited.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let generous = app(RouteTimeouts {
+            query: Some(Duration::from_secs(10)),
+            ..RouteTimeouts::default()
+        });
+        let response = generous.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
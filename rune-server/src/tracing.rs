@@ -7,9 +7,9 @@ use opentelemetry_sdk::{
     trace::{self, RandomIdGenerator, Sampler},
     Resource,
 };
+use std::future::Future;
 use std::time::Duration;
-use tracing_opentelemetry::OpenTelemetryLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
+use tracing::instrument::{Instrument, Instrumented};
 
 /// Initialize OpenTelemetry with OTLP exporter
 pub fn init_telemetry(service_name: &str) -> anyhow::Result<opentelemetry_sdk::trace::Tracer> {
@@ -18,10 +18,7 @@ pub fn init_telemetry(service_name: &str) -> anyhow::Result<opentelemetry_sdk::t
         .unwrap_or_else(|_| "http://localhost:4317".to_string());
 
     // Configure resource attributes
-    let resource = Resource::new(vec![
-        KeyValue::new("service.name", service_name.to_string()),
-        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
-    ]);
+    let resource = service_resource(service_name);
 
     // Configure OTLP exporter
     let exporter = opentelemetry_otlp::new_exporter()
@@ -44,6 +41,14 @@ pub fn init_telemetry(service_name: &str) -> anyhow::Result<opentelemetry_sdk::t
     Ok(tracer)
 }
 
+/// Resource attributes identifying this server in exported telemetry
+pub(crate) fn service_resource(service_name: &str) -> Resource {
+    Resource::new(vec![
+        KeyValue::new("service.name", service_name.to_string()),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ])
+}
+
 /// Get sampler configuration from environment
 fn get_sampler() -> Sampler {
     let sample_rate = std::env::var("OTEL_TRACES_SAMPLER_ARG")
@@ -62,29 +67,7 @@ fn get_sampler() -> Sampler {
 
 /// Initialize the complete tracing stack (console + OpenTelemetry)
 pub fn init_tracing_stack(service_name: &str) -> anyhow::Result<()> {
-    // Initialize OpenTelemetry
-    let tracer = init_telemetry(service_name)?;
-
-    // Create OpenTelemetry layer
-    let otel_layer = OpenTelemetryLayer::new(tracer);
-
-    // Create console layer for local logging
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_thread_ids(true)
-        .with_thread_names(true);
-
-    // Create env filter
-    let filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,rune=debug"));
-
-    // Combine all layers
-    Registry::default()
-        .with(filter)
-        .with(fmt_layer)
-        .with(otel_layer)
-        .init();
-
-    Ok(())
+    crate::logging::init(crate::logging::LogFormat::Console, Some(service_name))
 }
 
 /// Shutdown OpenTelemetry provider
@@ -150,6 +133,12 @@ where
     f()
 }
 
+/// Run `future` in a child span for Datalog evaluation, covering every
+/// poll until it completes
+pub fn trace_datalog_evaluation_async<F: Future>(rules_count: usize, future: F) -> Instrumented<F> {
+    future.instrument(tracing::info_span!("datalog_evaluation", rules_count))
+}
+
 /// Create a child span for Cedar evaluation
 #[tracing::instrument(
     name = "cedar_evaluation",
@@ -306,6 +295,18 @@ mod tests {
         });
     }
 
+    #[tokio::test]
+    async fn test_trace_datalog_evaluation_async() {
+        let _default = tracing::subscriber::set_default(Registry::default());
+        let span = trace_datalog_evaluation_async(3, async {
+            // Still inside the span after yielding
+            tokio::task::yield_now().await;
+            tracing::Span::current().metadata().map(|meta| meta.name())
+        })
+        .await;
+        assert_eq!(span, Some("datalog_evaluation"));
+    }
+
     #[test]
     fn test_trace_cedar_evaluation() {
         let subscriber = Registry::default();
@@ -0,0 +1,294 @@
+//! Evaluation cost attribution
+//!
+//! Platform teams charge authorization compute back to the teams that
+//! generate the load. A [`UsageMeter`] charges the evaluation time of every
+//! data plane decision to an account: the request's tenant, read from the
+//! principal attribute or context value named by
+//! `RUNE_USAGE_TENANT_ATTRIBUTE` (`tenant` unless set), and the API key the
+//! caller sent in `X-Api-Key`. Keys are only ever recorded as the first 16
+//! hex digits of their SHA-256. Decisions answered from the decision cache
+//! are counted but cost nothing.
+//!
+//! `GET /v1/usage` reports each account's totals since the server started,
+//! costliest first; `DELETE /v1/usage` reports them and starts a new
+//! period, for billing by period. Per tenant, the totals are also exported
+//! as `rune_usage_decisions_total` and
+//! `rune_usage_evaluation_microseconds_total`. At most [`MAX_ACCOUNTS`]
+//! accounts are kept per period; decisions for further accounts are charged
+//! to the [`OVERFLOW_TENANT`].
+
+use crate::anomaly;
+use crate::metrics;
+use arc_swap::ArcSwap;
+use axum::http::HeaderMap;
+use dashmap::DashMap;
+use rune_core::{AuthorizationResult, Request};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Header carrying the caller's API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Most accounts kept in one period
+pub const MAX_ACCOUNTS: usize = 1000;
+
+/// Tenant charged for accounts beyond [`MAX_ACCOUNTS`]
+pub const OVERFLOW_TENANT: &str = "other";
+
+/// Read `RUNE_USAGE_TENANT_ATTRIBUTE`
+pub fn tenant_attribute_from_env() -> String {
+    std::env::var("RUNE_USAGE_TENANT_ATTRIBUTE")
+        .ok()
+        .filter(|attribute| !attribute.is_empty())
+        .unwrap_or_else(|| "tenant".to_string())
+}
+
+/// What one account used
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountUsage {
+    /// Tenant of the requests
+    pub tenant: String,
+    /// Fingerprint of the API key they came with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Decisions made
+    pub decisions: u64,
+    /// Of those, decisions answered from the cache
+    pub cached_decisions: u64,
+    /// Time spent evaluating the rest
+    pub evaluation_ms: f64,
+}
+
+/// Usage of every account over one period
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReport {
+    /// When the period started, in milliseconds since the Unix epoch
+    pub since_ms: u64,
+    /// Accounts, costliest first
+    pub accounts: Vec<AccountUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Account {
+    tenant: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    decisions: AtomicU64,
+    cached: AtomicU64,
+    evaluation_ns: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Period {
+    since_ms: u64,
+    accounts: DashMap<Account, Usage>,
+}
+
+impl Period {
+    fn start() -> Self {
+        Period {
+            since_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+            accounts: DashMap::new(),
+        }
+    }
+
+    fn report(&self) -> UsageReport {
+        let mut accounts: Vec<AccountUsage> = self
+            .accounts
+            .iter()
+            .map(|entry| {
+                let usage = entry.value();
+                AccountUsage {
+                    tenant: entry.key().tenant.clone(),
+                    api_key: entry.key().api_key.clone(),
+                    decisions: usage.decisions.load(Ordering::Relaxed),
+                    cached_decisions: usage.cached.load(Ordering::Relaxed),
+                    evaluation_ms: usage.evaluation_ns.load(Ordering::Relaxed) as f64 / 1e6,
+                }
+            })
+            .collect();
+        accounts.sort_by(|a, b| {
+            b.evaluation_ms
+                .total_cmp(&a.evaluation_ms)
+                .then(b.decisions.cmp(&a.decisions))
+                .then_with(|| (&a.tenant, &a.api_key).cmp(&(&b.tenant, &b.api_key)))
+        });
+        UsageReport {
+            since_ms: self.since_ms,
+            accounts,
+        }
+    }
+}
+
+/// Charges decisions' evaluation time to the accounts that asked for them
+#[derive(Debug)]
+pub struct UsageMeter {
+    tenant_attribute: String,
+    period: ArcSwap<Period>,
+}
+
+impl Default for UsageMeter {
+    fn default() -> Self {
+        Self::new("tenant")
+    }
+}
+
+impl UsageMeter {
+    /// Meter usage by the tenant in principal attribute or context value
+    /// `tenant_attribute`
+    pub fn new(tenant_attribute: impl Into<String>) -> Self {
+        UsageMeter {
+            tenant_attribute: tenant_attribute.into(),
+            period: ArcSwap::from_pointee(Period::start()),
+        }
+    }
+
+    /// Charge the decision of `request`, made for a caller that sent
+    /// `headers`
+    pub fn record(&self, headers: &HeaderMap, request: &Request, result: &AuthorizationResult) {
+        let mut account = Account {
+            tenant: anomaly::tenant(request, &self.tenant_attribute),
+            api_key: headers
+                .get(API_KEY_HEADER)
+                .map(|key| key_fingerprint(key.as_bytes())),
+        };
+        let period = self.period.load();
+        if period.accounts.len() >= MAX_ACCOUNTS && !period.accounts.contains_key(&account) {
+            account = Account {
+                tenant: OVERFLOW_TENANT.to_string(),
+                api_key: None,
+            };
+        }
+        let cost_ns = if result.cached {
+            0
+        } else {
+            result.evaluation_time_ns
+        };
+        metrics::record_usage(account.tenant.clone(), cost_ns);
+
+        let usage = period.accounts.entry(account).or_default();
+        usage.decisions.fetch_add(1, Ordering::Relaxed);
+        if result.cached {
+            usage.cached.fetch_add(1, Ordering::Relaxed);
+        }
+        usage.evaluation_ns.fetch_add(cost_ns, Ordering::Relaxed);
+    }
+
+    /// Usage in the current period
+    pub fn report(&self) -> UsageReport {
+        self.period.load().report()
+    }
+
+    /// Usage in the current period, which ends; the next starts now
+    ///
+    /// Decisions being charged while the period ends may land in either.
+    pub fn reset(&self) -> UsageReport {
+        self.period.swap(Arc::new(Period::start())).report()
+    }
+}
+
+/// First 16 hex digits of the SHA-256 of an API key
+fn key_fingerprint(key: &[u8]) -> String {
+    Sha256::digest(key)[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rune_core::{Action, Principal, Resource, Value};
+
+    fn request(tenant: Option<&str>) -> Request {
+        let principal = match tenant {
+            Some(tenant) => {
+                Principal::user("alice").with_attribute("tenant", Value::string(tenant))
+            }
+            None => Principal::user("alice"),
+        };
+        Request::new(
+            principal,
+            Action::new("read"),
+            Resource::file("/tmp/report"),
+        )
+    }
+
+    fn result(evaluation_time_ns: u64, cached: bool) -> AuthorizationResult {
+        AuthorizationResult {
+            decision: rune_core::Decision::Permit,
+            explanation: String::new(),
+            evaluated_rules: Vec::new(),
+            facts_used: Vec::new(),
+            policies_evaluated: 0,
+            evaluation_time_ns,
+            cached,
+            coalesced: false,
+            failures: Vec::new(),
+            obligations: Vec::new(),
+            warnings: Vec::new(),
+            reason_codes: Vec::new(),
+            valid_for_ms: 0,
+            proofs: Vec::new(),
+            near_misses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_usage_by_account() {
+        let meter = UsageMeter::default();
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, "secret".parse().unwrap());
+
+        meter.record(&headers, &request(Some("acme")), &result(3_000_000, false));
+        meter.record(&headers, &request(Some("acme")), &result(3_000_000, true));
+        meter.record(&HeaderMap::new(), &request(None), &result(1_000_000, false));
+
+        let report = meter.report();
+        assert_eq!(report.accounts.len(), 2);
+        let acme = &report.accounts[0];
+        assert_eq!(acme.tenant, "acme");
+        assert_eq!(acme.api_key.as_deref(), Some("2bb80d537b1da3e3"));
+        assert_eq!((acme.decisions, acme.cached_decisions), (2, 1));
+        assert_eq!(acme.evaluation_ms, 3.0);
+        let default = &report.accounts[1];
+        assert_eq!(default.tenant, anomaly::DEFAULT_TENANT);
+        assert_eq!(default.api_key, None);
+
+        let ended = meter.reset();
+        assert_eq!(ended, report);
+        assert!(meter.report().accounts.is_empty());
+    }
+
+    #[test]
+    fn test_accounts_are_bounded() {
+        let meter = UsageMeter::default();
+        for i in 0..MAX_ACCOUNTS + 5 {
+            let tenant = format!("tenant-{}", i);
+            meter.record(
+                &HeaderMap::new(),
+                &request(Some(&tenant)),
+                &result(1, false),
+            );
+        }
+        let report = meter.report();
+        assert_eq!(report.accounts.len(), MAX_ACCOUNTS + 1);
+        let other = report
+            .accounts
+            .iter()
+            .find(|account| account.tenant == OVERFLOW_TENANT)
+            .unwrap();
+        assert_eq!(other.decisions, 5);
+    }
+}
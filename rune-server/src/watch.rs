@@ -0,0 +1,152 @@
+//! Decision watches
+//!
+//! A collaborative app showing a document wants to drop a user's access the
+//! moment it is revoked, without calling `/v1/authorize` in a loop.
+//! `GET /v1/watch?principal=alice&action=read&resource=Document:1` is a long
+//! poll: called with `decision` set to the decision the app holds, it
+//! returns as soon as the decision differs, or unchanged after `timeoutMs`,
+//! and the app watches again with what it got. Without `decision` it
+//! answers at once, which is how a watch starts.
+//!
+//! With `Accept: text/event-stream` the watch is a stream of server-sent
+//! `decision` events instead: the current decision, then one event per
+//! change until the client goes away.
+//!
+//! A decision only changes when facts or configuration do, so a watch
+//! re-evaluates its request when the fact store version or configuration
+//! generation moves, checked every [`POLL_INTERVAL`], and otherwise every
+//! [`RECHECK_INTERVAL`] for changes time alone brings, such as expiring
+//! sessions and facts. Watches are exempt from the route time budgets
+//! ([`crate::timeouts`]) and end on shutdown.
+
+use crate::api::WatchResponse;
+use crate::error::ApiResult;
+use crate::state::AppState;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use rune_core::{CancellationToken, RUNEEngine, Request};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tracing::debug;
+
+/// How often a watch looks for fact and configuration changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often a watch re-evaluates when nothing changed
+pub const RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Long poll wait when the caller sets none
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest long poll wait a caller may ask for
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The decision of one request, followed over time
+pub struct DecisionWatch {
+    engine: Arc<RUNEEngine>,
+    request: Request,
+    shutdown: CancellationToken,
+}
+
+impl DecisionWatch {
+    /// Watch `request`'s decision on the server in `state`
+    pub fn new(state: &AppState, request: Request) -> Self {
+        DecisionWatch {
+            engine: state.engine.clone(),
+            request,
+            shutdown: state.shutdown.clone(),
+        }
+    }
+
+    /// The decision now
+    pub async fn evaluate(&self) -> ApiResult<WatchResponse> {
+        // Read before evaluating so a change made meanwhile is seen by the
+        // next check
+        let generation = self.engine.generation();
+        let fact_version = self.engine.fact_version();
+        let result = self.engine.authorize_async(&self.request, None).await?;
+        Ok(WatchResponse {
+            decision: result.decision.into(),
+            changed: false,
+            generation,
+            fact_version,
+        })
+    }
+
+    /// Wait for the decision to differ from `current`; `None` if `deadline`
+    /// or shutdown comes first
+    pub async fn changed_from(
+        &self,
+        mut current: WatchResponse,
+        deadline: Option<Instant>,
+    ) -> ApiResult<Option<WatchResponse>> {
+        let mut checked = Instant::now();
+        loop {
+            let wait = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => left.min(POLL_INTERVAL),
+                    _ => return Ok(None),
+                },
+                None => POLL_INTERVAL,
+            };
+            if self.shutdown.is_cancelled() {
+                return Ok(None);
+            }
+            tokio::time::sleep(wait).await;
+
+            let moved = self.engine.generation() != current.generation
+                || self.engine.fact_version() != current.fact_version;
+            if !moved && checked.elapsed() < RECHECK_INTERVAL {
+                continue;
+            }
+            let now = self.evaluate().await?;
+            checked = Instant::now();
+            if now.decision != current.decision {
+                return Ok(Some(WatchResponse {
+                    changed: true,
+                    ..now
+                }));
+            }
+            current = now;
+        }
+    }
+}
+
+/// Stream `first`, then each change `watch` sees, as server-sent events
+pub fn events(
+    watch: DecisionWatch,
+    first: WatchResponse,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut current = first;
+        loop {
+            let event = match Event::default().event("decision").json_data(&current) {
+                Ok(event) => event,
+                Err(e) => {
+                    debug!("Failed to encode a watch event: {}", e);
+                    return;
+                }
+            };
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
+            let next = tokio::select! {
+                _ = tx.closed() => return,
+                next = watch.changed_from(current.clone(), None) => next,
+            };
+            match next {
+                Ok(Some(next)) => current = next,
+                Ok(None) => return,
+                Err(e) => {
+                    debug!("Watch ended: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
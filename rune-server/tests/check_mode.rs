@@ -0,0 +1,102 @@
+//! Runs `rune-server --check` against good and bad configurations
+//!
+//! The binary must report every problem and exit without serving, so each
+//! test waits for it to finish on its own.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Write `contents` to a file named for the test
+fn rune_file(name: &str, contents: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("rune-check-{}-{}.rune", name, std::process::id()));
+    std::fs::write(&path, contents).expect("Failed to write config");
+    path
+}
+
+/// Run `rune-server --check` with only the given `RUNE_*` settings
+fn check(args: &[&str], vars: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rune-server"));
+    command.arg("--check").args(args);
+    for (name, _) in std::env::vars() {
+        if name.starts_with("RUNE_") || name == "BIND_ADDRESS" {
+            command.env_remove(name);
+        }
+    }
+    command
+        .envs(vars.iter().copied())
+        .output()
+        .expect("Failed to run rune-server")
+}
+
+#[test]
+fn test_check_accepts_valid_config() {
+    let path = rune_file(
+        "valid",
+        r#"version = "rune/2.0"
+
+[rules]
+allowed(U) :- member(U, "admins").
+"#,
+    );
+    let output = check(
+        &["--config", path.to_str().unwrap()],
+        &[("BIND_ADDRESS", "127.0.0.1:1")],
+    );
+    std::fs::remove_file(&path).ok();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout.contains("1 rules, 0 policies"));
+    assert!(stdout.contains("Listening: http://127.0.0.1:1"));
+    assert!(stdout.contains("Configuration OK"));
+}
+
+#[test]
+fn test_check_reports_every_problem() {
+    let path = rune_file(
+        "invalid",
+        "version = \"rune/2.0\"\n\n[rules]\nallowed(U) :-\n    member(U, \"admins\".\n",
+    );
+    let output = check(
+        &[],
+        &[
+            ("RUNE_CONFIG", path.to_str().unwrap()),
+            ("BIND_ADDRESS", "nowhere"),
+            ("RUNE_SIGNING_KEY", "not hex"),
+        ],
+    );
+    std::fs::remove_file(&path).ok();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("Invalid BIND_ADDRESS: nowhere"));
+    assert!(stderr.contains("Invalid RUNE_SIGNING_KEY"));
+    assert!(stderr.contains("error[E0101]"));
+    assert!(stderr.contains("member(U, \"admins\"."));
+    assert!(stderr.contains("3 configuration problems found"));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_check_reports_missing_tls_files() {
+    let output = check(
+        &[],
+        &[
+            ("BIND_ADDRESS", "127.0.0.1:1"),
+            ("RUNE_TLS_CERT", "/nonexistent/cert.pem"),
+            ("RUNE_TLS_KEY", "/nonexistent/key.pem"),
+        ],
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("Failed to load TLS certificate /nonexistent/cert.pem"));
+}
+
+#[test]
+fn test_unknown_argument() {
+    let output = check(&["--chek"], &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown argument: --chek"));
+}
@@ -0,0 +1,100 @@
+//! Runs the shared conformance scenarios through the HTTP API
+//!
+//! Each scenario's configuration and facts are loaded into an engine
+//! directly; its session and request go over HTTP, so principal and resource
+//! parsing, session handling and response mapping are what is under test.
+
+use rune_core::conformance::{Outcome, Scenario};
+use rune_server::{router, AppState};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn scenarios() -> Vec<(PathBuf, Scenario)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../conformance/scenarios");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .expect("Failed to read scenario directory")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let contents = std::fs::read_to_string(&path).expect("Failed to read scenario");
+            let scenario = serde_yaml::from_str(&contents)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            (path, scenario)
+        })
+        .collect()
+}
+
+/// Serve the scenario's engine and put its session and request to it
+async fn run_over_http(scenario: &Scenario) -> Result<Outcome, String> {
+    let engine = Arc::new(scenario.engine().map_err(|e| e.to_string())?);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Failed to bind to port");
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let app = router::combined(AppState::new(engine));
+    let server = tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+
+    if !scenario.session.is_empty() {
+        let response = client
+            .post(format!("{}/v1/sessions", base_url))
+            .json(&json!({
+                "principal": scenario.request.principal,
+                "attributes": scenario.session,
+            }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        if !response.status().is_success() {
+            server.abort();
+            return Err(format!("Opening the session failed: {}", response.status()));
+        }
+    }
+
+    let response = client
+        .post(format!("{}/v1/authorize", base_url))
+        .json(&scenario.request)
+        .send()
+        .await
+        .expect("Failed to send request");
+    server.abort();
+    if !response.status().is_success() {
+        return Err(format!("Authorization failed: {}", response.status()));
+    }
+
+    let body: serde_json::Value = response.json().await.expect("Failed to parse response");
+    Ok(Outcome {
+        decision: body["decision"].as_str().unwrap_or_default().to_string(),
+        reasons: body["reasons"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|reason| reason.as_str().map(String::from))
+            .collect(),
+    })
+}
+
+#[tokio::test]
+async fn test_conformance_scenarios() {
+    let scenarios = scenarios();
+    assert!(!scenarios.is_empty(), "No conformance scenarios found");
+
+    let mut failures = Vec::new();
+    for (path, scenario) in &scenarios {
+        let checked = run_over_http(scenario)
+            .await
+            .and_then(|outcome| scenario.check(&outcome));
+        if let Err(e) = checked {
+            failures.push(format!("{}: {}", path.display(), e));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,143 @@
+//! Upgrades a running `rune-server` with `SIGUSR2` under load
+//!
+//! The old process hands its listening sockets to the new one, so every
+//! request sent while the upgrade runs must be answered: a connection the
+//! kernel queued on the old process and reset when it exited would show up
+//! here as a failed request.
+
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// Start `rune-server` on `addr` with JSON logs on a pipe
+fn start(addr: SocketAddr) -> Child {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rune-server"));
+    for (name, _) in std::env::vars() {
+        if name.starts_with("RUNE_") || name == "BIND_ADDRESS" {
+            command.env_remove(name);
+        }
+    }
+    command
+        .env("BIND_ADDRESS", addr.to_string())
+        .env("RUNE_LOG_FORMAT", "json")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to start rune-server")
+}
+
+/// Wait until `check` holds, failing the test after `timeout`
+async fn wait_for(what: &str, timeout: Duration, mut check: impl FnMut() -> bool) {
+    let deadline = Instant::now() + timeout;
+    while !check() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+fn signal(pid: u32, signal: i32) {
+    // SAFETY: sending a signal has no memory safety requirements
+    assert_eq!(unsafe { libc::kill(pid as i32, signal) }, 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_sigusr2_upgrade_resets_no_connection() {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap();
+    let mut old = start(addr);
+
+    // Log lines of both processes, which share the pipe
+    let (lines, logged) = mpsc::channel();
+    let stdout = old.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // A fresh connection per request, so every request goes through accept
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(0)
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+    let url = format!("http://{}/health/live", addr);
+    wait_for("the old process to listen", Duration::from_secs(30), || {
+        TcpStream::connect(addr).is_ok()
+    })
+    .await;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let answered = Arc::new(AtomicUsize::new(0));
+    let sent = Arc::new(AtomicUsize::new(0));
+    let mut load = Vec::new();
+    for _ in 0..8 {
+        let (client, url) = (client.clone(), url.clone());
+        let (stop, answered, sent) = (stop.clone(), answered.clone(), sent.clone());
+        load.push(tokio::spawn(async move {
+            let mut failures = Vec::new();
+            while !stop.load(Ordering::Relaxed) {
+                let response = client.get(&url).send().await;
+                sent.fetch_add(1, Ordering::Relaxed);
+                match response {
+                    Ok(response) if response.status().is_success() => {
+                        answered.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(response) => failures.push(response.status().to_string()),
+                    Err(e) => failures.push(format!("{:?}", e)),
+                }
+            }
+            failures
+        }));
+    }
+    wait_for("load before the upgrade", Duration::from_secs(10), || {
+        answered.load(Ordering::Relaxed) >= 200
+    })
+    .await;
+
+    signal(old.id(), libc::SIGUSR2);
+    wait_for("the old process to exit", Duration::from_secs(60), || {
+        old.try_wait().unwrap().is_some()
+    })
+    .await;
+    let new_pid = logged
+        .try_iter()
+        .filter(|line| line.contains("Handed listeners over"))
+        .find_map(|line| {
+            let event: serde_json::Value = serde_json::from_str(&line).ok()?;
+            event["pid"].as_u64()
+        })
+        .expect("old process did not log the new process's ID") as u32;
+
+    // The new process alone serves the address now
+    let before = sent.load(Ordering::Relaxed);
+    wait_for("load after the upgrade", Duration::from_secs(30), || {
+        sent.load(Ordering::Relaxed) >= before + 200
+    })
+    .await;
+    stop.store(true, Ordering::Relaxed);
+    let mut failures = Vec::new();
+    for task in load {
+        failures.extend(task.await.unwrap());
+    }
+
+    signal(new_pid, libc::SIGTERM);
+    wait_for("the new process to exit", Duration::from_secs(30), || {
+        TcpStream::connect(addr).is_err()
+    })
+    .await;
+    assert!(
+        failures.is_empty(),
+        "{} failed: {:?}",
+        failures.len(),
+        failures
+    );
+}